@@ -0,0 +1,557 @@
+//! The Chip8 processor, and the `Bus` it reaches the rest of the machine through.
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    decoder::{self, Instruction},
+    memory::{font_address, PROGRAM_START},
+    quirks::Quirks,
+};
+
+pub const REGISTER_COUNT: usize = 16;
+const STACK_SIZE: u8 = 16;
+
+/// A stack component built on top of a fixed-size array with Result<> types to prevent overflows and underflows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stack {
+    memory: [u16; STACK_SIZE as usize],
+    p: u8,
+}
+
+impl Stack {
+    pub fn new() -> Stack {
+        Stack {
+            memory: [0; STACK_SIZE as usize],
+            p: 0,
+        }
+    }
+    /// Pushes a value onto the stack. On error, it will give you the stack pointer.
+    pub fn push(&mut self, value: u16) -> Result<(), u8> {
+        if self.p >= STACK_SIZE {
+            Err(self.p)
+        } else {
+            self.memory[self.p as usize] = value;
+            self.p += 1;
+            Ok(())
+        }
+    }
+    /// Pops a value from the stack, giving you the value if successful, or the stack pointer if unsuccessful.
+    pub fn pop(&mut self) -> Result<u16, u8> {
+        if self.p == 0 {
+            Err(0)
+        } else {
+            self.p -= 1;
+            Ok(self.memory[self.p as usize])
+        }
+    }
+    /// The return addresses on the stack, from the bottom up.
+    pub fn entries(&self) -> &[u16] {
+        &self.memory[..self.p as usize]
+    }
+    /// How many return addresses are on the stack.
+    pub fn depth(&self) -> usize {
+        self.p as usize
+    }
+    /// How many return addresses the stack can hold.
+    pub fn capacity(&self) -> usize {
+        STACK_SIZE as usize
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why the CPU couldn't execute an instruction. Each error carries the address of the offending instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    MemoryOutOfBounds { pc: u16, address: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {opcode:04X} at {pc:03X}")
+            }
+            CpuError::StackOverflow { pc } => write!(f, "stack overflow at {pc:03X}"),
+            CpuError::StackUnderflow { pc } => write!(f, "stack underflow at {pc:03X}"),
+            CpuError::MemoryOutOfBounds { pc, address } => {
+                write!(
+                    f,
+                    "memory access out of bounds at {address:04X} from {pc:03X}"
+                )
+            }
+        }
+    }
+}
+
+impl core::error::Error for CpuError {}
+
+/// A register an instruction can write: one of V0 through VF, the index register, or a timer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Register {
+    V(u8),
+    Index,
+    Delay,
+    Sound,
+}
+
+/// Writes the register's usual name: `V0` through `VF`, `I`, `DT`, or `ST`.
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{x:X}"),
+            Register::Index => write!(f, "I"),
+            Register::Delay => write!(f, "DT"),
+            Register::Sound => write!(f, "ST"),
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = &'static str;
+
+    /// Parses a register's usual name, in either case.
+    fn from_str(name: &str) -> Result<Register, &'static str> {
+        let is = |known: &str| known.eq_ignore_ascii_case(name);
+        if is("I") {
+            Ok(Register::Index)
+        } else if is("DT") {
+            Ok(Register::Delay)
+        } else if is("ST") {
+            Ok(Register::Sound)
+        } else {
+            name.strip_prefix(['V', 'v'])
+                .filter(|digit| digit.len() == 1)
+                .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+                .map(Register::V)
+                .ok_or("expected a register, V0 through VF, I, DT, or ST")
+        }
+    }
+}
+
+/// Everything an instruction can touch besides the CPU itself: memory, the display, the keypad, the timers, and a
+/// source of random numbers.
+///
+/// `Machine` implements this over fixed-size parts for hosts without `std`; `chip8-rust` implements it over its own
+/// growable memory, shared display, and threaded timers. Memory accesses out of bounds give back the offending
+/// address.
+pub trait Bus {
+    fn read(&self, address: u16) -> Result<u8, u16>;
+    fn write(&mut self, address: u16, value: u8) -> Result<(), u16>;
+    /// Reads the big-endian opcode at an address.
+    fn read_opcode(&self, address: u16) -> Result<u16, u16> {
+        let high = self.read(address)?;
+        let low = self.read(address.wrapping_add(1))?;
+        Ok(u16::from_be_bytes([high, low]))
+    }
+    fn clear_display(&mut self);
+    /// XORs a sprite onto the display at a position that wraps around the screen, returning whether any pixel was
+    /// turned off. The sprite is clipped at the edges, or wraps around them with `wrap`.
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool;
+    fn is_key_pressed(&self, key: u8) -> bool;
+    /// Takes the last key to be released, if any has been since this was last called.
+    fn take_released_key(&mut self) -> Option<u8>;
+    fn delay_timer(&self) -> u8;
+    fn set_delay_timer(&mut self, value: u8);
+    fn set_sound_timer(&mut self, value: u8);
+    fn random_byte(&mut self) -> u8;
+    /// Hears that an instruction wrote a register, with what the register holds now. A register is reported even
+    /// when it was written with the value it already held. Timers counting down aren't writes.
+    fn register_written(&mut self, _register: Register, _value: u16) {}
+    /// Hears that an instruction read a byte of memory as data, as a sprite or into the registers. Fetching
+    /// instructions isn't a read, and writes are seen through `write()`.
+    fn memory_read(&mut self, _address: u16, _value: u8) {}
+}
+
+/// The Chip8 processor: registers, the index register, the call stack, and the program counter.
+///
+/// Instructions follow the original COSMAC VIP behaviour unless other quirks are chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cpu {
+    quirks: Quirks,
+    registers: [u8; REGISTER_COUNT],
+    stack: Stack,
+    pc: u16,
+    index: u16,
+    /// Set while FX0A is waiting on a key, so the CPU stalls without fetching.
+    waiting_for_key: bool,
+}
+
+impl Cpu {
+    pub fn new() -> Cpu {
+        Cpu::with_quirks(Quirks::default())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Cpu {
+        Cpu {
+            quirks,
+            registers: [0; REGISTER_COUNT],
+            stack: Stack::new(),
+            pc: PROGRAM_START as u16,
+            index: 0,
+            waiting_for_key: false,
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn registers(&self) -> &[u8; REGISTER_COUNT] {
+        &self.registers
+    }
+
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// Sets VX from outside the program, as a debugger does.
+    ///
+    /// # Panics
+    ///
+    /// If `x` isn't a register, 0 through F.
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.registers[x] = value;
+    }
+
+    /// Sets the index register from outside the program.
+    pub fn set_index(&mut self, value: u16) {
+        self.index = value;
+    }
+
+    /// Moves the program counter from outside the program, as a jump would.
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    /// Whether the CPU is stalled on FX0A until a key is released.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    /// Fetches, decodes, and executes one instruction.
+    ///
+    /// On error the program counter is left on the offending instruction.
+    pub fn step(&mut self, bus: &mut impl Bus) -> Result<Instruction, CpuError> {
+        let pc = self.pc;
+        let opcode = bus
+            .read_opcode(pc)
+            .map_err(|address| CpuError::MemoryOutOfBounds { pc, address })?;
+        let instruction = decoder::decode(opcode);
+        self.pc = pc.wrapping_add(2);
+        if let Err(error) = self.execute(instruction, opcode, bus) {
+            self.pc = pc;
+            return Err(error);
+        }
+        self.report_writes(instruction, bus);
+        Ok(instruction)
+    }
+
+    /// Tells the bus which registers an instruction just wrote.
+    fn report_writes(&self, instruction: Instruction, bus: &mut impl Bus) {
+        let v = |x: u8| (Register::V(x), self.registers[x as usize] as u16);
+        let flag = v(0xF);
+        let index = (Register::Index, self.index);
+        let mut report = |(register, value)| bus.register_written(register, value);
+        match instruction {
+            Instruction::SetValue { x, .. }
+            | Instruction::AddValue { x, .. }
+            | Instruction::Set { x, .. }
+            | Instruction::Random { x, .. }
+            | Instruction::GetDelay { x } => report(v(x)),
+            Instruction::Or { x, .. } | Instruction::And { x, .. } | Instruction::Xor { x, .. } => {
+                report(v(x));
+                if self.quirks.vf_reset {
+                    report(flag);
+                }
+            }
+            Instruction::Add { x, .. }
+            | Instruction::Sub { x, .. }
+            | Instruction::SubReverse { x, .. }
+            | Instruction::ShiftRight { x, .. }
+            | Instruction::ShiftLeft { x, .. } => {
+                report(v(x));
+                report(flag);
+            }
+            Instruction::Draw { .. } => report(flag),
+            Instruction::WaitKey { x } if !self.waiting_for_key => report(v(x)),
+            Instruction::SetIndex { .. }
+            | Instruction::AddIndex { .. }
+            | Instruction::FontCharacter { .. } => report(index),
+            Instruction::SetDelay { x } => report((Register::Delay, v(x).1)),
+            Instruction::SetSound { x } => report((Register::Sound, v(x).1)),
+            Instruction::StoreRegisters { .. } if self.quirks.load_store_increments_index => {
+                report(index)
+            }
+            Instruction::LoadRegisters { x } => {
+                for register in 0..=x {
+                    report(v(register));
+                }
+                if self.quirks.load_store_increments_index {
+                    report(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn execute(
+        &mut self,
+        instruction: Instruction,
+        opcode: u16,
+        bus: &mut impl Bus,
+    ) -> Result<(), CpuError> {
+        let pc = self.pc.wrapping_sub(2);
+        let out_of_bounds = |address| CpuError::MemoryOutOfBounds { pc, address };
+        let v = &mut self.registers;
+        let mut skip = false;
+        match instruction {
+            Instruction::ClearScreen => bus.clear_display(),
+            Instruction::Return => {
+                self.pc = self
+                    .stack
+                    .pop()
+                    .map_err(|_| CpuError::StackUnderflow { pc })?;
+            }
+            Instruction::Jump { address } => self.pc = address,
+            Instruction::Call { address } => {
+                self.stack
+                    .push(self.pc)
+                    .map_err(|_| CpuError::StackOverflow { pc })?;
+                self.pc = address;
+            }
+            Instruction::SkipEqualValue { x, value } => skip = v[x as usize] == value,
+            Instruction::SkipNotEqualValue { x, value } => skip = v[x as usize] != value,
+            Instruction::SkipEqual { x, y } => skip = v[x as usize] == v[y as usize],
+            Instruction::SkipNotEqual { x, y } => skip = v[x as usize] != v[y as usize],
+            Instruction::SetValue { x, value } => v[x as usize] = value,
+            Instruction::AddValue { x, value } => v[x as usize] = v[x as usize].wrapping_add(value),
+            Instruction::Set { x, y } => v[x as usize] = v[y as usize],
+            Instruction::Or { x, y } => {
+                v[x as usize] |= v[y as usize];
+                if self.quirks.vf_reset {
+                    v[0xF] = 0;
+                }
+            }
+            Instruction::And { x, y } => {
+                v[x as usize] &= v[y as usize];
+                if self.quirks.vf_reset {
+                    v[0xF] = 0;
+                }
+            }
+            Instruction::Xor { x, y } => {
+                v[x as usize] ^= v[y as usize];
+                if self.quirks.vf_reset {
+                    v[0xF] = 0;
+                }
+            }
+            Instruction::Add { x, y } => {
+                let (sum, carry) = v[x as usize].overflowing_add(v[y as usize]);
+                v[x as usize] = sum;
+                v[0xF] = carry as u8;
+            }
+            Instruction::Sub { x, y } => {
+                let (difference, borrow) = v[x as usize].overflowing_sub(v[y as usize]);
+                v[x as usize] = difference;
+                v[0xF] = !borrow as u8;
+            }
+            Instruction::SubReverse { x, y } => {
+                let (difference, borrow) = v[y as usize].overflowing_sub(v[x as usize]);
+                v[x as usize] = difference;
+                v[0xF] = !borrow as u8;
+            }
+            Instruction::ShiftRight { x, y } => {
+                let value = v[if self.quirks.shift_uses_vy { y } else { x } as usize];
+                v[x as usize] = value >> 1;
+                v[0xF] = value & 1;
+            }
+            Instruction::ShiftLeft { x, y } => {
+                let value = v[if self.quirks.shift_uses_vy { y } else { x } as usize];
+                v[x as usize] = value << 1;
+                v[0xF] = value >> 7;
+            }
+            Instruction::SetIndex { address } => self.index = address,
+            Instruction::JumpOffset { address } => {
+                let offset = if self.quirks.jump_uses_vx {
+                    v[(address >> 8) as usize]
+                } else {
+                    v[0]
+                };
+                self.pc = address.wrapping_add(offset as u16);
+            }
+            Instruction::Random { x, mask } => v[x as usize] = bus.random_byte() & mask,
+            Instruction::Draw { x, y, height } => {
+                // sprites are at most 15 rows, so they're read onto the stack rather than borrowed from memory
+                let mut sprite = [0; 15];
+                let sprite = &mut sprite[..height as usize];
+                for (offset, row) in sprite.iter_mut().enumerate() {
+                    let address = self.index.wrapping_add(offset as u16);
+                    *row = bus.read(address).map_err(out_of_bounds)?;
+                    bus.memory_read(address, *row);
+                }
+                let (x, y) = (v[x as usize] as usize, v[y as usize] as usize);
+                v[0xF] = bus.draw(x, y, sprite, self.quirks.wrap_sprites) as u8;
+            }
+            Instruction::SkipKeyPressed { x } => skip = bus.is_key_pressed(v[x as usize]),
+            Instruction::SkipKeyNotPressed { x } => skip = !bus.is_key_pressed(v[x as usize]),
+            Instruction::GetDelay { x } => v[x as usize] = bus.delay_timer(),
+            Instruction::WaitKey { x } => {
+                if !self.waiting_for_key {
+                    // only count keys released after the instruction started waiting
+                    bus.take_released_key();
+                }
+                match bus.take_released_key() {
+                    Some(key) => {
+                        v[x as usize] = key;
+                        self.waiting_for_key = false;
+                    }
+                    None => {
+                        self.waiting_for_key = true;
+                        self.pc = pc;
+                    }
+                }
+            }
+            Instruction::SetDelay { x } => bus.set_delay_timer(v[x as usize]),
+            Instruction::SetSound { x } => bus.set_sound_timer(v[x as usize]),
+            Instruction::AddIndex { x } => {
+                self.index = self.index.wrapping_add(v[x as usize] as u16)
+            }
+            Instruction::FontCharacter { x } => self.index = font_address(v[x as usize]),
+            Instruction::StoreBcd { x } => {
+                let value = v[x as usize];
+                for (offset, digit) in [value / 100, value / 10 % 10, value % 10]
+                    .into_iter()
+                    .enumerate()
+                {
+                    bus.write(self.index.wrapping_add(offset as u16), digit)
+                        .map_err(out_of_bounds)?;
+                }
+            }
+            Instruction::StoreRegisters { x } => {
+                let mut address = self.index;
+                for &register in &v[..=x as usize] {
+                    bus.write(address, register).map_err(out_of_bounds)?;
+                    address = address.wrapping_add(1);
+                }
+                if self.quirks.load_store_increments_index {
+                    self.index = address;
+                }
+            }
+            Instruction::LoadRegisters { x } => {
+                let mut address = self.index;
+                for register in &mut v[..=x as usize] {
+                    *register = bus.read(address).map_err(out_of_bounds)?;
+                    bus.memory_read(address, *register);
+                    address = address.wrapping_add(1);
+                }
+                if self.quirks.load_store_increments_index {
+                    self.index = address;
+                }
+            }
+            // the extensions are never decoded for running, only for disassembly
+            Instruction::MachineCall { .. }
+            | Instruction::Unknown { .. }
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowResolution
+            | Instruction::HighResolution
+            | Instruction::LargeFontCharacter { .. }
+            | Instruction::StoreFlags { .. }
+            | Instruction::LoadFlags { .. }
+            | Instruction::ScrollUp { .. }
+            | Instruction::StoreRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::SetLongIndex { .. }
+            | Instruction::SelectPlanes { .. }
+            | Instruction::LoadAudio
+            | Instruction::SetPitch { .. } => {
+                return Err(CpuError::UnknownOpcode { pc, opcode });
+            }
+        }
+        if skip {
+            self.pc = self.pc.wrapping_add(2);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers_parse_from_their_names() {
+        for register in [
+            Register::V(0xA),
+            Register::Index,
+            Register::Delay,
+            Register::Sound,
+        ] {
+            let name = register.to_string();
+            assert_eq!(name.parse(), Ok(register));
+            assert_eq!(name.to_lowercase().parse(), Ok(register));
+        }
+        assert!("VG".parse::<Register>().is_err());
+        assert!("V10".parse::<Register>().is_err());
+    }
+
+    #[test]
+    fn stack_push_pop() {
+        let mut stack = Stack::new();
+        assert!(stack.push(0xEF).is_ok(), "push failed on 0xEF");
+        assert!(stack.push(0xAB).is_ok(), "push failed on 0xAB");
+        assert_eq!(stack.pop(), Ok(0xAB), "stack is not last-in first-out");
+        assert_eq!(stack.pop(), Ok(0xEF), "stack is not last-in first-out");
+    }
+    #[test]
+    fn pop_on_empty() {
+        let mut stack = Stack::new();
+        assert!(stack.pop().is_err());
+    }
+    #[test]
+    fn stack_overflow() {
+        let mut stack = Stack::new();
+        for i in 1..17 {
+            assert!(stack.push(i as u16).is_ok());
+        }
+        assert!(stack.push(0xEF).is_err());
+    }
+    #[test]
+    fn pop_when_full() {
+        let mut stack = Stack::new();
+        for i in 0..STACK_SIZE {
+            assert!(stack.push(i as u16).is_ok());
+        }
+        assert_eq!(
+            stack.pop(),
+            Ok(STACK_SIZE as u16 - 1),
+            "full stack cannot be popped"
+        );
+    }
+}
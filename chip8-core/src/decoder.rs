@@ -0,0 +1,520 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::quirks::Variant;
+
+/// A decoded Chip8 instruction.
+///
+/// Register operands are register numbers, 0 through F. The SUPER-CHIP and XO-CHIP instructions are only decoded by
+/// `decode_for()`, for disassembling programs written for those machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0: clears the display.
+    ClearScreen,
+    /// 00EE: returns from a subroutine.
+    Return,
+    /// 0NNN: calls a machine language routine, which isn't supported.
+    MachineCall { address: u16 },
+    /// 1NNN: jumps to an address.
+    Jump { address: u16 },
+    /// 2NNN: calls a subroutine.
+    Call { address: u16 },
+    /// 3XNN: skips the next instruction if VX equals a value.
+    SkipEqualValue { x: u8, value: u8 },
+    /// 4XNN: skips the next instruction if VX doesn't equal a value.
+    SkipNotEqualValue { x: u8, value: u8 },
+    /// 5XY0: skips the next instruction if VX equals VY.
+    SkipEqual { x: u8, y: u8 },
+    /// 6XNN: sets VX to a value.
+    SetValue { x: u8, value: u8 },
+    /// 7XNN: adds a value to VX, without setting the carry flag.
+    AddValue { x: u8, value: u8 },
+    /// 8XY0: sets VX to VY.
+    Set { x: u8, y: u8 },
+    /// 8XY1: sets VX to VX | VY.
+    Or { x: u8, y: u8 },
+    /// 8XY2: sets VX to VX & VY.
+    And { x: u8, y: u8 },
+    /// 8XY3: sets VX to VX ^ VY.
+    Xor { x: u8, y: u8 },
+    /// 8XY4: adds VY to VX, setting VF on carry.
+    Add { x: u8, y: u8 },
+    /// 8XY5: subtracts VY from VX, setting VF when there's no borrow.
+    Sub { x: u8, y: u8 },
+    /// 8XY6: shifts VY right into VX, setting VF to the bit shifted out.
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7: sets VX to VY - VX, setting VF when there's no borrow.
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE: shifts VY left into VX, setting VF to the bit shifted out.
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0: skips the next instruction if VX doesn't equal VY.
+    SkipNotEqual { x: u8, y: u8 },
+    /// ANNN: sets I to an address.
+    SetIndex { address: u16 },
+    /// BNNN: jumps to an address plus V0.
+    JumpOffset { address: u16 },
+    /// CXNN: sets VX to a random byte masked with a value.
+    Random { x: u8, mask: u8 },
+    /// DXYN: draws an N-byte sprite from I at (VX, VY), setting VF on collision.
+    Draw { x: u8, y: u8, height: u8 },
+    /// EX9E: skips the next instruction if the key in VX is pressed.
+    SkipKeyPressed { x: u8 },
+    /// EXA1: skips the next instruction if the key in VX isn't pressed.
+    SkipKeyNotPressed { x: u8 },
+    /// FX07: sets VX to the delay timer.
+    GetDelay { x: u8 },
+    /// FX0A: waits for a key to be pressed and released, and stores it in VX.
+    WaitKey { x: u8 },
+    /// FX15: sets the delay timer to VX.
+    SetDelay { x: u8 },
+    /// FX18: sets the sound timer to VX.
+    SetSound { x: u8 },
+    /// FX1E: adds VX to I.
+    AddIndex { x: u8 },
+    /// FX29: sets I to the font sprite for the digit in VX.
+    FontCharacter { x: u8 },
+    /// FX33: stores the binary-coded decimal of VX at I, I+1, and I+2.
+    StoreBcd { x: u8 },
+    /// FX55: stores V0 through VX in memory starting at I.
+    StoreRegisters { x: u8 },
+    /// FX65: loads V0 through VX from memory starting at I.
+    LoadRegisters { x: u8 },
+    /// 00CN: scrolls the display down N pixels. SUPER-CHIP.
+    ScrollDown { rows: u8 },
+    /// 00FB: scrolls the display right 4 pixels. SUPER-CHIP.
+    ScrollRight,
+    /// 00FC: scrolls the display left 4 pixels. SUPER-CHIP.
+    ScrollLeft,
+    /// 00FD: exits the interpreter. SUPER-CHIP.
+    Exit,
+    /// 00FE: switches to the 64x32 display. SUPER-CHIP.
+    LowResolution,
+    /// 00FF: switches to the 128x64 display. SUPER-CHIP.
+    HighResolution,
+    /// FX30: sets I to the large font sprite for the digit in VX. SUPER-CHIP.
+    LargeFontCharacter { x: u8 },
+    /// FX75: stores V0 through VX in the persistent flag registers. SUPER-CHIP.
+    StoreFlags { x: u8 },
+    /// FX85: loads V0 through VX from the persistent flag registers. SUPER-CHIP.
+    LoadFlags { x: u8 },
+    /// 00DN: scrolls the display up N pixels. XO-CHIP.
+    ScrollUp { rows: u8 },
+    /// 5XY2: stores VX through VY in memory starting at I. XO-CHIP.
+    StoreRange { x: u8, y: u8 },
+    /// 5XY3: loads VX through VY from memory starting at I. XO-CHIP.
+    LoadRange { x: u8, y: u8 },
+    /// F000 NNNN: sets I to the 16-bit address in the next two bytes. XO-CHIP.
+    SetLongIndex { address: u16 },
+    /// FN01: selects the bitplanes in the mask N for drawing. XO-CHIP.
+    SelectPlanes { mask: u8 },
+    /// F002: loads the 16-byte audio pattern at I. XO-CHIP.
+    LoadAudio,
+    /// FX3A: sets the audio pitch to VX. XO-CHIP.
+    SetPitch { x: u8 },
+    /// Any opcode that isn't a known instruction.
+    Unknown { opcode: u16 },
+}
+
+impl Instruction {
+    /// How many bytes the instruction takes up, which is 2 for all but XO-CHIP's `F000 NNNN`.
+    pub fn size(self) -> u16 {
+        match self {
+            Instruction::SetLongIndex { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    /// The instruction's mnemonic, the first word of how it's written, such as `LD` or `DRW`.
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Instruction::ClearScreen => "CLS",
+            Instruction::Return => "RET",
+            Instruction::MachineCall { .. } => "SYS",
+            Instruction::Jump { .. } | Instruction::JumpOffset { .. } => "JP",
+            Instruction::Call { .. } => "CALL",
+            Instruction::SkipEqualValue { .. } | Instruction::SkipEqual { .. } => "SE",
+            Instruction::SkipNotEqualValue { .. } | Instruction::SkipNotEqual { .. } => "SNE",
+            Instruction::SetValue { .. }
+            | Instruction::Set { .. }
+            | Instruction::SetIndex { .. }
+            | Instruction::GetDelay { .. }
+            | Instruction::WaitKey { .. }
+            | Instruction::SetDelay { .. }
+            | Instruction::SetSound { .. }
+            | Instruction::FontCharacter { .. }
+            | Instruction::StoreBcd { .. }
+            | Instruction::StoreRegisters { .. }
+            | Instruction::LoadRegisters { .. }
+            | Instruction::LargeFontCharacter { .. }
+            | Instruction::StoreFlags { .. }
+            | Instruction::LoadFlags { .. }
+            | Instruction::SetLongIndex { .. } => "LD",
+            Instruction::AddValue { .. }
+            | Instruction::Add { .. }
+            | Instruction::AddIndex { .. } => "ADD",
+            Instruction::Or { .. } => "OR",
+            Instruction::And { .. } => "AND",
+            Instruction::Xor { .. } => "XOR",
+            Instruction::Sub { .. } => "SUB",
+            Instruction::ShiftRight { .. } => "SHR",
+            Instruction::SubReverse { .. } => "SUBN",
+            Instruction::ShiftLeft { .. } => "SHL",
+            Instruction::Random { .. } => "RND",
+            Instruction::Draw { .. } => "DRW",
+            Instruction::SkipKeyPressed { .. } => "SKP",
+            Instruction::SkipKeyNotPressed { .. } => "SKNP",
+            Instruction::ScrollDown { .. } => "SCD",
+            Instruction::ScrollRight => "SCR",
+            Instruction::ScrollLeft => "SCL",
+            Instruction::Exit => "EXIT",
+            Instruction::LowResolution => "LOW",
+            Instruction::HighResolution => "HIGH",
+            Instruction::ScrollUp { .. } => "SCU",
+            Instruction::StoreRange { .. } => "SAVE",
+            Instruction::LoadRange { .. } => "LOAD",
+            Instruction::SelectPlanes { .. } => "PLANE",
+            Instruction::LoadAudio => "AUDIO",
+            Instruction::SetPitch { .. } => "PITCH",
+            Instruction::Unknown { .. } => "DW",
+        }
+    }
+
+    /// The address written into the instruction, for those that jump or call to one or point I at one. `JP V0` jumps
+    /// to V0 past it.
+    pub fn target(self) -> Option<u16> {
+        match self {
+            Instruction::Jump { address }
+            | Instruction::Call { address }
+            | Instruction::SetIndex { address }
+            | Instruction::JumpOffset { address }
+            | Instruction::SetLongIndex { address } => Some(address),
+            _ => None,
+        }
+    }
+
+    /// Encodes the instruction back into the bytes it was decoded from, the inverse of `decode_for()`. Needs the
+    /// `alloc` feature.
+    #[cfg(feature = "alloc")]
+    pub fn to_bytes(self) -> Vec<u8> {
+        let xy = |prefix: u16, x: u8, y: u8, suffix: u16| {
+            prefix << 12 | (x as u16) << 8 | (y as u16) << 4 | suffix
+        };
+        let xnn = |prefix: u16, x: u8, value: u8| prefix << 12 | (x as u16) << 8 | value as u16;
+        let opcode = match self {
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::MachineCall { address } => address & 0xFFF,
+            Instruction::Jump { address } => 0x1000 | address & 0xFFF,
+            Instruction::Call { address } => 0x2000 | address & 0xFFF,
+            Instruction::SkipEqualValue { x, value } => xnn(0x3, x, value),
+            Instruction::SkipNotEqualValue { x, value } => xnn(0x4, x, value),
+            Instruction::SkipEqual { x, y } => xy(0x5, x, y, 0x0),
+            Instruction::SetValue { x, value } => xnn(0x6, x, value),
+            Instruction::AddValue { x, value } => xnn(0x7, x, value),
+            Instruction::Set { x, y } => xy(0x8, x, y, 0x0),
+            Instruction::Or { x, y } => xy(0x8, x, y, 0x1),
+            Instruction::And { x, y } => xy(0x8, x, y, 0x2),
+            Instruction::Xor { x, y } => xy(0x8, x, y, 0x3),
+            Instruction::Add { x, y } => xy(0x8, x, y, 0x4),
+            Instruction::Sub { x, y } => xy(0x8, x, y, 0x5),
+            Instruction::ShiftRight { x, y } => xy(0x8, x, y, 0x6),
+            Instruction::SubReverse { x, y } => xy(0x8, x, y, 0x7),
+            Instruction::ShiftLeft { x, y } => xy(0x8, x, y, 0xE),
+            Instruction::SkipNotEqual { x, y } => xy(0x9, x, y, 0x0),
+            Instruction::SetIndex { address } => 0xA000 | address & 0xFFF,
+            Instruction::JumpOffset { address } => 0xB000 | address & 0xFFF,
+            Instruction::Random { x, mask } => xnn(0xC, x, mask),
+            Instruction::Draw { x, y, height } => xy(0xD, x, y, height as u16),
+            Instruction::SkipKeyPressed { x } => xnn(0xE, x, 0x9E),
+            Instruction::SkipKeyNotPressed { x } => xnn(0xE, x, 0xA1),
+            Instruction::GetDelay { x } => xnn(0xF, x, 0x07),
+            Instruction::WaitKey { x } => xnn(0xF, x, 0x0A),
+            Instruction::SetDelay { x } => xnn(0xF, x, 0x15),
+            Instruction::SetSound { x } => xnn(0xF, x, 0x18),
+            Instruction::AddIndex { x } => xnn(0xF, x, 0x1E),
+            Instruction::FontCharacter { x } => xnn(0xF, x, 0x29),
+            Instruction::StoreBcd { x } => xnn(0xF, x, 0x33),
+            Instruction::StoreRegisters { x } => xnn(0xF, x, 0x55),
+            Instruction::LoadRegisters { x } => xnn(0xF, x, 0x65),
+            Instruction::ScrollDown { rows } => 0x00C0 | rows as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowResolution => 0x00FE,
+            Instruction::HighResolution => 0x00FF,
+            Instruction::LargeFontCharacter { x } => xnn(0xF, x, 0x30),
+            Instruction::StoreFlags { x } => xnn(0xF, x, 0x75),
+            Instruction::LoadFlags { x } => xnn(0xF, x, 0x85),
+            Instruction::ScrollUp { rows } => 0x00D0 | rows as u16,
+            Instruction::StoreRange { x, y } => xy(0x5, x, y, 0x2),
+            Instruction::LoadRange { x, y } => xy(0x5, x, y, 0x3),
+            Instruction::SetLongIndex { address } => {
+                return [0xF0, 0x00, (address >> 8) as u8, address as u8].to_vec();
+            }
+            Instruction::SelectPlanes { mask } => xnn(0xF, mask, 0x01),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::SetPitch { x } => xnn(0xF, x, 0x3A),
+            Instruction::Unknown { opcode } => opcode,
+        };
+        opcode.to_be_bytes().to_vec()
+    }
+}
+
+/// Writes the instruction in the usual assembly syntax, such as `LD V0, 0x05` or `DRW V0, V1, 5`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::MachineCall { address } => write!(f, "SYS 0x{address:03X}"),
+            Instruction::Jump { address } => write!(f, "JP 0x{address:03X}"),
+            Instruction::Call { address } => write!(f, "CALL 0x{address:03X}"),
+            Instruction::SkipEqualValue { x, value } => write!(f, "SE V{x:X}, 0x{value:02X}"),
+            Instruction::SkipNotEqualValue { x, value } => write!(f, "SNE V{x:X}, 0x{value:02X}"),
+            Instruction::SkipEqual { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            Instruction::SetValue { x, value } => write!(f, "LD V{x:X}, 0x{value:02X}"),
+            Instruction::AddValue { x, value } => write!(f, "ADD V{x:X}, 0x{value:02X}"),
+            Instruction::Set { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Instruction::Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            Instruction::And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Instruction::Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            Instruction::Add { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Instruction::Sub { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            Instruction::SkipNotEqual { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            Instruction::SetIndex { address } => write!(f, "LD I, 0x{address:03X}"),
+            Instruction::JumpOffset { address } => write!(f, "JP V0, 0x{address:03X}"),
+            Instruction::Random { x, mask } => write!(f, "RND V{x:X}, 0x{mask:02X}"),
+            Instruction::Draw { x, y, height } => write!(f, "DRW V{x:X}, V{y:X}, {height}"),
+            Instruction::SkipKeyPressed { x } => write!(f, "SKP V{x:X}"),
+            Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{x:X}"),
+            Instruction::GetDelay { x } => write!(f, "LD V{x:X}, DT"),
+            Instruction::WaitKey { x } => write!(f, "LD V{x:X}, K"),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{x:X}"),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{x:X}"),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{x:X}"),
+            Instruction::FontCharacter { x } => write!(f, "LD F, V{x:X}"),
+            Instruction::StoreBcd { x } => write!(f, "LD B, V{x:X}"),
+            Instruction::StoreRegisters { x } => write!(f, "LD [I], V{x:X}"),
+            Instruction::LoadRegisters { x } => write!(f, "LD V{x:X}, [I]"),
+            Instruction::ScrollDown { rows } => write!(f, "SCD {rows}"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowResolution => write!(f, "LOW"),
+            Instruction::HighResolution => write!(f, "HIGH"),
+            Instruction::LargeFontCharacter { x } => write!(f, "LD HF, V{x:X}"),
+            Instruction::StoreFlags { x } => write!(f, "LD R, V{x:X}"),
+            Instruction::LoadFlags { x } => write!(f, "LD V{x:X}, R"),
+            Instruction::ScrollUp { rows } => write!(f, "SCU {rows}"),
+            Instruction::StoreRange { x, y } => write!(f, "SAVE V{x:X}, V{y:X}"),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{x:X}, V{y:X}"),
+            Instruction::SetLongIndex { address } => write!(f, "LD I, LONG 0x{address:04X}"),
+            Instruction::SelectPlanes { mask } => write!(f, "PLANE {mask}"),
+            Instruction::LoadAudio => write!(f, "AUDIO"),
+            Instruction::SetPitch { x } => write!(f, "PITCH V{x:X}"),
+            Instruction::Unknown { opcode } => write!(f, "DW 0x{opcode:04X}"),
+        }
+    }
+}
+
+/// Decodes an opcode as one of the instructions of a variant.
+///
+/// `next` is the opcode after, which XO-CHIP's `F000 NNNN` takes its address from; check `Instruction::size()` to
+/// tell whether it was used.
+pub fn decode_for(opcode: u16, next: u16, variant: Variant) -> Instruction {
+    let base = decode(opcode);
+    if variant == Variant::Chip8 {
+        return base;
+    }
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let xo_chip = variant == Variant::XoChip;
+    match opcode {
+        0x00C0..=0x00CF => Instruction::ScrollDown { rows: n },
+        0x00D0..=0x00DF if xo_chip => Instruction::ScrollUp { rows: n },
+        0x00FB => Instruction::ScrollRight,
+        0x00FC => Instruction::ScrollLeft,
+        0x00FD => Instruction::Exit,
+        0x00FE => Instruction::LowResolution,
+        0x00FF => Instruction::HighResolution,
+        0xF000 if xo_chip => Instruction::SetLongIndex { address: next },
+        0xF002 if xo_chip => Instruction::LoadAudio,
+        _ => match (opcode >> 12, opcode & 0xFF) {
+            (0x5, _) if xo_chip && n == 2 => Instruction::StoreRange { x, y },
+            (0x5, _) if xo_chip && n == 3 => Instruction::LoadRange { x, y },
+            (0xF, 0x01) if xo_chip => Instruction::SelectPlanes { mask: x },
+            (0xF, 0x30) => Instruction::LargeFontCharacter { x },
+            (0xF, 0x3A) if xo_chip => Instruction::SetPitch { x },
+            (0xF, 0x75) => Instruction::StoreFlags { x },
+            (0xF, 0x85) => Instruction::LoadFlags { x },
+            _ => base,
+        },
+    }
+}
+
+/// Decodes an opcode into one of the original CHIP-8 instructions.
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode >> 8) & 0xF) as u8;
+    let y = ((opcode >> 4) & 0xF) as u8;
+    let n = (opcode & 0xF) as u8;
+    let value = (opcode & 0xFF) as u8;
+    let address = opcode & 0xFFF;
+    match opcode >> 12 {
+        0x0 => match opcode {
+            0x00E0 => Instruction::ClearScreen,
+            0x00EE => Instruction::Return,
+            _ => Instruction::MachineCall { address },
+        },
+        0x1 => Instruction::Jump { address },
+        0x2 => Instruction::Call { address },
+        0x3 => Instruction::SkipEqualValue { x, value },
+        0x4 => Instruction::SkipNotEqualValue { x, value },
+        0x5 if n == 0 => Instruction::SkipEqual { x, y },
+        0x6 => Instruction::SetValue { x, value },
+        0x7 => Instruction::AddValue { x, value },
+        0x8 => match n {
+            0x0 => Instruction::Set { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::Add { x, y },
+            0x5 => Instruction::Sub { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubReverse { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => Instruction::Unknown { opcode },
+        },
+        0x9 if n == 0 => Instruction::SkipNotEqual { x, y },
+        0xA => Instruction::SetIndex { address },
+        0xB => Instruction::JumpOffset { address },
+        0xC => Instruction::Random { x, mask: value },
+        0xD => Instruction::Draw { x, y, height: n },
+        0xE => match value {
+            0x9E => Instruction::SkipKeyPressed { x },
+            0xA1 => Instruction::SkipKeyNotPressed { x },
+            _ => Instruction::Unknown { opcode },
+        },
+        0xF => match value {
+            0x07 => Instruction::GetDelay { x },
+            0x0A => Instruction::WaitKey { x },
+            0x15 => Instruction::SetDelay { x },
+            0x18 => Instruction::SetSound { x },
+            0x1E => Instruction::AddIndex { x },
+            0x29 => Instruction::FontCharacter { x },
+            0x33 => Instruction::StoreBcd { x },
+            0x55 => Instruction::StoreRegisters { x },
+            0x65 => Instruction::LoadRegisters { x },
+            _ => Instruction::Unknown { opcode },
+        },
+        _ => Instruction::Unknown { opcode },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_operands() {
+        assert_eq!(decode(0x00E0), Instruction::ClearScreen);
+        assert_eq!(decode(0x1ABC), Instruction::Jump { address: 0xABC });
+        assert_eq!(
+            decode(0x3A42),
+            Instruction::SkipEqualValue {
+                x: 0xA,
+                value: 0x42
+            }
+        );
+        assert_eq!(decode(0x8AB4), Instruction::Add { x: 0xA, y: 0xB });
+        assert_eq!(
+            decode(0xD125),
+            Instruction::Draw {
+                x: 1,
+                y: 2,
+                height: 5
+            }
+        );
+        assert_eq!(decode(0xF365), Instruction::LoadRegisters { x: 3 });
+    }
+
+    #[test]
+    fn unknown_opcodes() {
+        for opcode in [0x5121, 0x8128, 0x9121, 0xE1FF, 0xF1FF] {
+            assert_eq!(decode(opcode), Instruction::Unknown { opcode });
+        }
+    }
+
+    #[test]
+    fn formats_mnemonics() {
+        assert_eq!(decode(0x00E0).to_string(), "CLS");
+        assert_eq!(decode(0x6A05).to_string(), "LD VA, 0x05");
+        assert_eq!(decode(0xD125).to_string(), "DRW V1, V2, 5");
+        assert_eq!(decode(0xF355).to_string(), "LD [I], V3");
+        assert_eq!(decode(0xB300).to_string(), "JP V0, 0x300");
+        assert_eq!(decode(0xFFFF).to_string(), "DW 0xFFFF");
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn encodes_what_it_decodes() {
+        for variant in [Variant::Chip8, Variant::SuperChip, Variant::XoChip] {
+            for opcode in 0..=u16::MAX {
+                let instruction = decode_for(opcode, 0x1234, variant);
+                let mut bytes = opcode.to_be_bytes().to_vec();
+                if instruction.size() == 4 {
+                    bytes.extend([0x12, 0x34]);
+                }
+                assert_eq!(
+                    instruction.to_bytes(),
+                    bytes,
+                    "{instruction:?} on {variant:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn mnemonics_start_what_is_written() {
+        for opcode in 0..=u16::MAX {
+            let instruction = decode_for(opcode, 0x1234, Variant::XoChip);
+            let written = instruction.to_string();
+            assert_eq!(
+                written.split(' ').next(),
+                Some(instruction.mnemonic()),
+                "{instruction:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_extensions_for_their_variants() {
+        assert_eq!(
+            decode_for(0x00FF, 0, Variant::Chip8),
+            Instruction::MachineCall { address: 0xFF }
+        );
+        assert_eq!(
+            decode_for(0x00FF, 0, Variant::SuperChip),
+            Instruction::HighResolution
+        );
+        assert_eq!(
+            decode_for(0xF000, 0x1234, Variant::SuperChip),
+            Instruction::Unknown { opcode: 0xF000 }
+        );
+        let long = decode_for(0xF000, 0x1234, Variant::XoChip);
+        assert_eq!(long.to_string(), "LD I, LONG 0x1234");
+        assert_eq!(long.size(), 4);
+        assert_eq!(
+            decode_for(0x5123, 0, Variant::XoChip),
+            Instruction::LoadRange { x: 1, y: 2 }
+        );
+        assert_eq!(
+            decode_for(0xF275, 0, Variant::XoChip).to_string(),
+            "LD R, V2"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+/// The width of the display, in pixels.
+pub const WIDTH: usize = 64;
+/// The height of the display, in pixels.
+pub const HEIGHT: usize = 32;
+
+/// The Chip8's monochrome screen, a bit to a pixel: 256 bytes in all, held inline.
+///
+/// Each row is a `u64`, with the leftmost pixel in the highest bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    rows: [u64; HEIGHT],
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer { rows: [0; HEIGHT] }
+    }
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << (WIDTH - 1 - x)) != 0
+    }
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        let bit = 1 << (WIDTH - 1 - x);
+        if on {
+            self.rows[y] |= bit;
+        } else {
+            self.rows[y] &= !bit;
+        }
+    }
+    /// The rows from the top, the leftmost pixel of each in its highest bit.
+    pub fn rows(&self) -> &[u64; HEIGHT] {
+        &self.rows
+    }
+    pub fn clear(&mut self) {
+        self.rows = [0; HEIGHT];
+    }
+    /// XORs a sprite onto the screen, returning whether any pixel was turned off.
+    ///
+    /// The starting position wraps around the screen. The sprite itself is clipped at the edges, or wraps around
+    /// too with `wrap`.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
+        let (x, y) = (x % WIDTH, y % HEIGHT);
+        let mut collision = false;
+        for (row, &byte) in sprite.iter().enumerate() {
+            let mut py = y + row;
+            if py >= HEIGHT {
+                if !wrap {
+                    break;
+                }
+                py %= HEIGHT;
+            }
+            // the sprite's byte at the left edge of the row, then rotated or shifted into place
+            let placed = (byte as u64) << (WIDTH - 8);
+            let bits = if wrap {
+                placed.rotate_right(x as u32)
+            } else {
+                placed >> x
+            };
+            collision |= self.rows[py] & bits != 0;
+            self.rows[py] ^= bits;
+        }
+        collision
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_and_collides() {
+        let mut framebuffer = Framebuffer::new();
+        assert!(!framebuffer.draw(0, 0, &[0xF0], false));
+        assert!(framebuffer.get_pixel(3, 0) && !framebuffer.get_pixel(4, 0));
+        assert!(framebuffer.draw(2, 0, &[0x80], false));
+        assert!(!framebuffer.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn clips_or_wraps_at_the_edges() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.draw(60, 31, &[0xFF, 0xFF], false);
+        assert!(framebuffer.get_pixel(63, 31));
+        assert!(!framebuffer.get_pixel(0, 31) && !framebuffer.get_pixel(60, 0));
+
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.draw(60, 31, &[0xFF, 0xFF], true);
+        assert!(framebuffer.get_pixel(3, 31) && framebuffer.get_pixel(60, 0));
+    }
+}
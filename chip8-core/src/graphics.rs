@@ -0,0 +1,102 @@
+//! Drawing the framebuffer with embedded-graphics, onto a display driver or anything else that's a `DrawTarget`.
+//!
+//! Only available with the `embedded-graphics` feature.
+
+use embedded_graphics_core::{
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Point, Size},
+    pixelcolor::PixelColor,
+    primitives::Rectangle,
+    Drawable,
+};
+
+use crate::framebuffer::{Framebuffer, HEIGHT, WIDTH};
+
+/// The framebuffer as something to draw, in two colours and blown up by a whole number.
+///
+/// It's drawn at the target's origin with a single `fill_contiguous()`, which most drivers send in one transfer. To
+/// draw it somewhere else, translate the target.
+#[derive(Debug, Clone, Copy)]
+pub struct Screen<'a, C> {
+    framebuffer: &'a Framebuffer,
+    on: C,
+    off: C,
+    scale: u32,
+}
+
+impl<'a, C: PixelColor> Screen<'a, C> {
+    /// Draws lit pixels in `on` and unlit pixels in `off`, one to one.
+    pub fn new(framebuffer: &'a Framebuffer, on: C, off: C) -> Screen<'a, C> {
+        Screen {
+            framebuffer,
+            on,
+            off,
+            scale: 1,
+        }
+    }
+
+    /// Draws each pixel as a square `scale` pixels across. A scale of zero is taken as one.
+    pub fn scale(mut self, scale: u32) -> Screen<'a, C> {
+        self.scale = scale.max(1);
+        self
+    }
+}
+
+impl<C: PixelColor> OriginDimensions for Screen<'_, C> {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32 * self.scale, HEIGHT as u32 * self.scale)
+    }
+}
+
+impl<C: PixelColor> Drawable for Screen<'_, C> {
+    type Color = C;
+    type Output = ();
+
+    fn draw<D: DrawTarget<Color = C>>(&self, target: &mut D) -> Result<(), D::Error> {
+        let scale = self.scale as usize;
+        let colors = (0..HEIGHT * scale).flat_map(move |y| {
+            (0..WIDTH * scale).map(move |x| {
+                if self.framebuffer.get_pixel(x / scale, y / scale) {
+                    self.on
+                } else {
+                    self.off
+                }
+            })
+        });
+        target.fill_contiguous(&Rectangle::new(Point::zero(), self.size()), colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{mock_display::MockDisplay, pixelcolor::BinaryColor};
+
+    use super::*;
+
+    #[test]
+    fn draws_lit_and_unlit_pixels() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.draw(62, 0, &[0xC0], false);
+        let mut display = MockDisplay::new();
+        Screen::new(&framebuffer, BinaryColor::On, BinaryColor::Off)
+            .draw(&mut display)
+            .expect("failed to draw");
+        assert_eq!(display.get_pixel(Point::new(63, 0)), Some(BinaryColor::On));
+        assert_eq!(display.get_pixel(Point::new(61, 0)), Some(BinaryColor::Off));
+        assert_eq!(display.get_pixel(Point::new(0, 31)), Some(BinaryColor::Off));
+        assert_eq!(display.get_pixel(Point::new(0, 32)), None);
+    }
+
+    #[test]
+    fn scales_pixels_up() {
+        let mut framebuffer = Framebuffer::new();
+        framebuffer.set_pixel(0, 0, true);
+        let screen = Screen::new(&framebuffer, BinaryColor::On, BinaryColor::Off).scale(2);
+        assert_eq!(screen.size(), Size::new(128, 64));
+        let mut display = MockDisplay::new();
+        display.set_allow_out_of_bounds_drawing(true);
+        screen.draw(&mut display).expect("failed to draw");
+        assert_eq!(display.get_pixel(Point::new(1, 1)), Some(BinaryColor::On));
+        assert_eq!(display.get_pixel(Point::new(2, 1)), Some(BinaryColor::Off));
+    }
+}
@@ -0,0 +1,102 @@
+use core::mem;
+
+/// The number of keys on the hex keypad, 0 through F.
+pub const KEY_COUNT: usize = 16;
+
+/// Held by `Keypad::released` while no key has been released. A plain byte rather than an `Option` keeps every
+/// keypad the same size when serialized.
+const NO_KEY: u8 = 0xFF;
+
+/// The Chip8's 16-key hex keypad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keypad {
+    keys: [bool; KEY_COUNT],
+    /// The last key released since it was taken, for instructions that wait on a keypress, or `NO_KEY`.
+    released: u8,
+}
+
+impl Keypad {
+    pub fn new() -> Keypad {
+        Keypad {
+            keys: [false; KEY_COUNT],
+            released: NO_KEY,
+        }
+    }
+    pub fn press(&mut self, key: u8) {
+        self.keys[(key & 0xF) as usize] = true;
+    }
+    pub fn release(&mut self, key: u8) {
+        let key = key & 0xF;
+        if self.keys[key as usize] {
+            self.released = key;
+        }
+        self.keys[key as usize] = false;
+    }
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+    /// Takes the last key to be released, if any has been since this was last called.
+    pub fn take_released(&mut self) -> Option<u8> {
+        match mem::replace(&mut self.released, NO_KEY) {
+            NO_KEY => None,
+            key => Some(key),
+        }
+    }
+    /// The pressed keys as a bitmask, with key N in bit N.
+    pub fn mask(&self) -> u16 {
+        self.keys
+            .iter()
+            .enumerate()
+            .fold(0, |mask, (key, &pressed)| mask | (pressed as u16) << key)
+    }
+    /// Presses and releases keys to match a bitmask from `mask()`.
+    pub fn set_mask(&mut self, mask: u16) {
+        for key in 0..KEY_COUNT as u8 {
+            let pressed = mask & (1 << key) != 0;
+            if pressed && !self.is_pressed(key) {
+                self.press(key);
+            } else if !pressed && self.is_pressed(key) {
+                self.release(key);
+            }
+        }
+    }
+    /// Releases every key and forgets any pending release.
+    pub fn reset(&mut self) {
+        *self = Keypad::new();
+    }
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_is_taken_once() {
+        let mut keypad = Keypad::new();
+        keypad.release(0x3);
+        assert_eq!(keypad.take_released(), None, "release without press");
+        keypad.press(0x3);
+        assert!(keypad.is_pressed(0x3));
+        keypad.release(0x3);
+        assert!(!keypad.is_pressed(0x3));
+        assert_eq!(keypad.take_released(), Some(0x3));
+        assert_eq!(keypad.take_released(), None);
+    }
+
+    #[test]
+    fn masks_press_and_release() {
+        let mut keypad = Keypad::new();
+        keypad.set_mask(0b1010);
+        assert!(keypad.is_pressed(0x1) && keypad.is_pressed(0x3));
+        assert_eq!(keypad.mask(), 0b1010);
+        keypad.set_mask(0b0010);
+        assert_eq!(keypad.take_released(), Some(0x3));
+    }
+}
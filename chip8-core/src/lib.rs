@@ -0,0 +1,29 @@
+//! The parts of a Chip8 that need nothing from the host: the instruction set, memory, the framebuffer, and the
+//! keypad, for running on microcontrollers as well as under `chip8-rust`.
+//!
+//! Nothing here needs `std` or an allocator, and nothing keeps time by itself. `Machine` runs a program on fixed-size
+//! memory and counts its timers down as the host ticks it, 60 times a second; `chip8-rust` runs the same `Cpu` with
+//! its own memory, display, and threaded clock behind the `Bus` trait.
+//!
+//! With the `alloc` feature, instructions can be encoded back into bytes; with `serde`, the state can be saved; and
+//! with `embedded-graphics`, the framebuffer can be drawn on any `DrawTarget`, such as an SPI LCD.
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod cpu;
+pub mod decoder;
+pub mod framebuffer;
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
+pub mod keypad;
+pub mod machine;
+pub mod memory;
+pub mod quirks;
+pub mod rng;
+
+pub use cpu::{Bus, Cpu, CpuError, Register};
+pub use framebuffer::Framebuffer;
+pub use machine::Machine;
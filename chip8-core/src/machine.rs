@@ -0,0 +1,202 @@
+//! A whole Chip8 in a few kilobytes, for hosts without threads or an allocator.
+
+use crate::{
+    cpu::{Bus, Cpu, CpuError},
+    decoder::Instruction,
+    framebuffer::Framebuffer,
+    keypad::Keypad,
+    memory::Ram,
+    quirks::Quirks,
+    rng::Rng,
+};
+
+/// A Chip8 that runs only when told to.
+///
+/// Nothing here keeps time: the host calls `run_frame()` 60 times a second, from a timer interrupt or its main loop,
+/// and draws the framebuffer and switches its buzzer on and off after each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Machine {
+    cpu: Cpu,
+    hardware: Hardware,
+}
+
+/// Everything but the CPU, which the CPU borrows as its `Bus`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hardware {
+    ram: Ram,
+    framebuffer: Framebuffer,
+    keypad: Keypad,
+    delay: u8,
+    sound: u8,
+    rng: Rng,
+}
+
+impl Machine {
+    /// Creates a machine with empty memory but for the font. `seed` seeds the CXNN instruction's random numbers.
+    pub fn new(quirks: Quirks, seed: u64) -> Machine {
+        Machine {
+            cpu: Cpu::with_quirks(quirks),
+            hardware: Hardware {
+                ram: Ram::new(),
+                framebuffer: Framebuffer::new(),
+                keypad: Keypad::new(),
+                delay: 0,
+                sound: 0,
+                rng: Rng::new(seed),
+            },
+        }
+    }
+
+    /// Starts a program over from a blank screen, with the CPU and timers reset and the keys left as they are.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), &'static str> {
+        let mut ram = Ram::new();
+        ram.load_rom(rom)?;
+        self.hardware.ram = ram;
+        self.hardware.framebuffer.clear();
+        self.hardware.delay = 0;
+        self.hardware.sound = 0;
+        self.cpu = Cpu::with_quirks(self.cpu.quirks());
+        Ok(())
+    }
+
+    /// Executes one instruction.
+    pub fn step(&mut self) -> Result<Instruction, CpuError> {
+        self.cpu.step(&mut self.hardware)
+    }
+
+    /// Counts the delay and sound timers down by one, as happens 60 times a second.
+    pub fn tick(&mut self) {
+        self.hardware.delay = self.hardware.delay.saturating_sub(1);
+        self.hardware.sound = self.hardware.sound.saturating_sub(1);
+    }
+
+    /// Executes a frame's worth of instructions and then ticks the timers, for calling 60 times a second.
+    ///
+    /// The original ran around 11 instructions a frame; most programs are happy with somewhere between 10 and 20.
+    pub fn run_frame(&mut self, instructions: usize) -> Result<(), CpuError> {
+        for _ in 0..instructions {
+            self.step()?;
+        }
+        self.tick();
+        Ok(())
+    }
+
+    /// Presses one of the 16 keys, 0 through F.
+    pub fn press_key(&mut self, key: u8) {
+        self.hardware.keypad.press(key);
+    }
+
+    pub fn release_key(&mut self, key: u8) {
+        self.hardware.keypad.release(key);
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.hardware.framebuffer
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn ram(&self) -> &Ram {
+        &self.hardware.ram
+    }
+
+    /// Whether the buzzer should be sounding, which it does while the sound timer is nonzero.
+    pub fn is_tone_on(&self) -> bool {
+        self.hardware.sound > 0
+    }
+}
+
+impl Bus for Hardware {
+    fn read(&self, address: u16) -> Result<u8, u16> {
+        self.ram.read(address)
+    }
+
+    fn write(&mut self, address: u16, value: u8) -> Result<(), u16> {
+        self.ram.write(address, value)
+    }
+
+    fn clear_display(&mut self) {
+        self.framebuffer.clear();
+    }
+
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
+        self.framebuffer.draw(x, y, sprite, wrap)
+    }
+
+    fn is_key_pressed(&self, key: u8) -> bool {
+        self.keypad.is_pressed(key)
+    }
+
+    fn take_released_key(&mut self) -> Option<u8> {
+        self.keypad.take_released()
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.delay
+    }
+
+    fn set_delay_timer(&mut self, value: u8) {
+        self.delay = value;
+    }
+
+    fn set_sound_timer(&mut self, value: u8) {
+        self.sound = value;
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng.next_u8()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::memory::{font_address, PROGRAM_START};
+
+    use super::*;
+
+    #[test]
+    fn draws_a_font_digit() {
+        let mut machine = Machine::new(Quirks::default(), 1);
+        // V0 = 7, I = font 7, draw it at V1,V1 (0,0), jump to self
+        machine
+            .load_rom(&[0x60, 0x07, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06])
+            .expect("failed to load rom");
+        machine.run_frame(4).expect("failed to run frame");
+        assert_eq!(machine.cpu().index(), font_address(7));
+        // 7 is F0 10 20 40 40
+        assert!((0..4).all(|x| machine.framebuffer().get_pixel(x, 0)));
+        assert!(machine.framebuffer().get_pixel(3, 1));
+        assert!(!machine.framebuffer().get_pixel(0, 1));
+        assert_eq!(machine.cpu().pc(), PROGRAM_START as u16 + 6);
+    }
+
+    #[test]
+    fn timers_count_down_a_frame_at_a_time() {
+        let mut machine = Machine::new(Quirks::default(), 1);
+        // V0 = 2, sound = V0, jump to self
+        machine
+            .load_rom(&[0x60, 0x02, 0xF0, 0x18, 0x12, 0x04])
+            .expect("failed to load rom");
+        machine.run_frame(2).expect("failed to run frame");
+        assert!(machine.is_tone_on());
+        machine.run_frame(1).expect("failed to run frame");
+        assert!(!machine.is_tone_on());
+    }
+
+    #[test]
+    fn waits_for_a_key() {
+        let mut machine = Machine::new(Quirks::default(), 1);
+        // V3 = key, jump to self
+        machine
+            .load_rom(&[0xF3, 0x0A, 0x12, 0x02])
+            .expect("failed to load rom");
+        machine.run_frame(3).expect("failed to run frame");
+        assert!(machine.cpu().is_waiting_for_key());
+        machine.press_key(0xB);
+        machine.release_key(0xB);
+        machine.run_frame(1).expect("failed to run frame");
+        assert_eq!(machine.cpu().registers()[3], 0xB);
+    }
+}
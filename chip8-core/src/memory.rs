@@ -0,0 +1,118 @@
+/// The RAM of the original machine, and of `Ram`.
+pub const RAM_SIZE: usize = 4096;
+/// Where programs are loaded, and where execution starts.
+pub const PROGRAM_START: usize = 0x200;
+/// Where the built-in font is stored, below the program area.
+pub const FONT_START: usize = 0x050;
+
+/// The hex digits 0 through F, as 4x5 sprites stored at `FONT_START`.
+pub static FONT: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
+    [0x20, 0x60, 0x20, 0x20, 0x70], // 1
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
+    [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
+    [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
+    [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
+    [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
+    [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
+    [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
+    [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
+    [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
+    [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
+    [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
+    [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
+];
+
+/// Gets the address of the font sprite for a hex digit.
+pub fn font_address(digit: u8) -> u16 {
+    (FONT_START + (digit & 0xF) as usize * FONT[0].len()) as u16
+}
+
+/// Copies the font into memory at `FONT_START`.
+pub fn load_font(ram: &mut [u8]) {
+    for (i, glyph) in FONT.iter().enumerate() {
+        let start = FONT_START + i * glyph.len();
+        ram[start..start + glyph.len()].copy_from_slice(glyph);
+    }
+}
+
+/// The original machine's 4K of RAM, held inline with the font loaded, for hosts without an allocator.
+///
+/// Out-of-bounds accesses give you the offending address as an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ram {
+    bytes: [u8; RAM_SIZE],
+}
+
+impl Ram {
+    pub fn new() -> Ram {
+        let mut ram = Ram {
+            bytes: [0; RAM_SIZE],
+        };
+        load_font(&mut ram.bytes);
+        ram
+    }
+    /// Zeroes memory, leaving only the font loaded.
+    pub fn clear(&mut self) {
+        *self = Ram::new();
+    }
+    /// Copies a program into memory at `PROGRAM_START`.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), &'static str> {
+        if rom.len() > RAM_SIZE - PROGRAM_START {
+            return Err("rom is too large to fit in memory");
+        }
+        self.bytes[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+    pub fn read(&self, address: u16) -> Result<u8, u16> {
+        self.bytes.get(address as usize).copied().ok_or(address)
+    }
+    pub fn write(&mut self, address: u16, value: u8) -> Result<(), u16> {
+        match self.bytes.get_mut(address as usize) {
+            Some(byte) => {
+                *byte = value;
+                Ok(())
+            }
+            None => Err(address),
+        }
+    }
+    /// Reads `len` bytes starting at an address.
+    pub fn slice(&self, address: u16, len: usize) -> Result<&[u8], u16> {
+        let start = address as usize;
+        self.bytes
+            .get(start..start + len)
+            .ok_or((start + len).min(u16::MAX as usize) as u16)
+    }
+    pub fn bytes(&self) -> &[u8; RAM_SIZE] {
+        &self.bytes
+    }
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_is_loaded() {
+        let ram = Ram::new();
+        assert_eq!(ram.slice(font_address(0xA), 5), Ok(&FONT[0xA][..]));
+    }
+
+    #[test]
+    fn rom_loading_and_bounds() {
+        let mut ram = Ram::new();
+        assert!(ram.load_rom(&[0x12, 0x00]).is_ok());
+        assert_eq!(ram.read(PROGRAM_START as u16), Ok(0x12));
+        assert!(ram.load_rom(&[0; RAM_SIZE - PROGRAM_START + 1]).is_err());
+        assert_eq!(ram.read(0x1000), Err(0x1000));
+        assert_eq!(ram.write(0xFFFF, 1), Err(0xFFFF));
+    }
+}
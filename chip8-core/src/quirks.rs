@@ -0,0 +1,95 @@
+use core::str::FromStr;
+
+/// The family of machine being emulated, which decides the default quirks and memory size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Variant {
+    /// The original COSMAC VIP interpreter.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(alias = "chip-8", alias = "vip"))]
+    Chip8,
+    /// SUPER-CHIP 1.1 on the HP48.
+    #[cfg_attr(feature = "serde", serde(alias = "schip", alias = "super-chip"))]
+    SuperChip,
+    /// Octo's XO-CHIP extension.
+    #[cfg_attr(feature = "serde", serde(alias = "xo-chip", alias = "octo"))]
+    XoChip,
+}
+
+impl Variant {
+    /// The default quirks of this variant.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Variant::Chip8 => Quirks {
+                vf_reset: true,
+                shift_uses_vy: true,
+                load_store_increments_index: true,
+                jump_uses_vx: false,
+                wrap_sprites: false,
+            },
+            Variant::SuperChip => Quirks {
+                vf_reset: false,
+                shift_uses_vy: false,
+                load_store_increments_index: false,
+                jump_uses_vx: true,
+                wrap_sprites: false,
+            },
+            Variant::XoChip => Quirks {
+                vf_reset: false,
+                shift_uses_vy: true,
+                load_store_increments_index: true,
+                jump_uses_vx: false,
+                wrap_sprites: true,
+            },
+        }
+    }
+
+    /// The default amount of RAM of this variant, in bytes.
+    pub fn ram_size(self) -> usize {
+        match self {
+            Variant::Chip8 | Variant::SuperChip => 0x1000,
+            Variant::XoChip => 0x10000,
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = &'static str;
+
+    /// Parses the usual names of a variant, such as `chip8`, `schip`, or `xo-chip`. `vip` also names CHIP-8.
+    fn from_str(name: &str) -> Result<Variant, &'static str> {
+        let is = |names: &[&str]| names.iter().any(|known| known.eq_ignore_ascii_case(name));
+        if is(&["chip8", "chip-8", "vip"]) {
+            Ok(Variant::Chip8)
+        } else if is(&["schip", "superchip", "super-chip"]) {
+            Ok(Variant::SuperChip)
+        } else if is(&["xochip", "xo-chip", "octo"]) {
+            Ok(Variant::XoChip)
+        } else {
+            Err("unknown variant, expected chip8, schip, or xochip")
+        }
+    }
+}
+
+/// Behaviours that differ between interpreters, which programs may depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// 8XY1, 8XY2, and 8XY3 reset VF to zero.
+    pub vf_reset: bool,
+    /// 8XY6 and 8XYE shift VY into VX, rather than shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// FX55 and FX65 leave I pointing past the last register.
+    pub load_store_increments_index: bool,
+    /// BNNN jumps to NNN plus VX, where X is the high nibble of NNN, rather than plus V0.
+    pub jump_uses_vx: bool,
+    /// Sprites wrap around the edges of the screen rather than being clipped.
+    pub wrap_sprites: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Variant::default().quirks()
+    }
+}
@@ -0,0 +1,207 @@
+//! Plays a program in a macroquad window: `cargo run --example macroquad --features macroquad -- rom.ch8`.
+//!
+//! macroquad runs the game loop itself and has no audio callback, so this drives the machine the way the browser
+//! frontend does, running the frames owed each time round with `run_frame()`, and plays the tone as a looped sound
+//! pulled from `SquareWave` up front. The config file is read as `chip8 run` reads it.
+
+use std::{env, error::Error, fs, path::Path, time::Duration};
+
+use macroquad::{
+    audio::{self, PlaySoundParams, Sound},
+    prelude::*,
+};
+
+use chip8_rust::{
+    audio::AudioSink,
+    clock::{ManualClock, VsyncClock},
+    config::{Color as ConfigColor, Config},
+    display::{HEIGHT, WIDTH},
+    frontend::{frame_to_rgba, KeyInput, Outputs, SquareWave, ToneSwitch, RGBA_FRAME_LEN},
+    machine::MachineState,
+};
+
+/// The sample rate the tone is made at. macroquad resamples whatever it's given to this.
+const SAMPLE_RATE: u32 = 44_100;
+
+fn window_conf() -> Conf {
+    let scale = Config::load().map_or(10, |config| config.display.scale.max(1));
+    Conf {
+        window_title: "chip8".to_string(),
+        window_width: (WIDTH as u32 * scale) as i32,
+        window_height: (HEIGHT as u32 * scale) as i32,
+        window_resizable: true,
+        ..Conf::default()
+    }
+}
+
+#[macroquad::main(window_conf)]
+async fn main() {
+    if let Err(error) = run().await {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("usage: macroquad <rom>")?;
+    let path = Path::new(&path);
+    let rom = fs::read(path)?;
+    let config = Config::load()?.for_rom(&rom, Some(path))?;
+
+    let outputs = Outputs::new();
+    let builder = config
+        .builder()
+        .clock(Box::new(ManualClock::new()), config.tick_rate());
+    let mut machine = outputs.attach(builder).build()?;
+    machine.load_rom(&rom)?;
+    let mut input = KeyInput::new(&config, machine.handle());
+    let mut clock = VsyncClock::with_rate(config.tick_rate());
+
+    let mut rgba = vec![0; RGBA_FRAME_LEN];
+    let texture = Texture2D::from_rgba8(WIDTH as u16, HEIGHT as u16, &rgba);
+    texture.set_filter(FilterMode::Nearest);
+    let tone = if config.audio.enabled {
+        Some(tone_sound(&config).await?)
+    } else {
+        None
+    };
+    let mut tone_on = false;
+
+    // let the machine shut down and autosave when the window's closed
+    prevent_quit();
+    while !is_quit_requested() {
+        for key in get_keys_pressed() {
+            input.key_down(&key_name(key))?;
+        }
+        for key in get_keys_released() {
+            input.key_up(&key_name(key))?;
+        }
+        machine.process_commands();
+        clock.set_speed(machine.speed());
+        let due = clock.present_after(Duration::from_secs_f32(get_frame_time()))?;
+        match machine.state() {
+            MachineState::Stopped => break,
+            MachineState::Paused => {}
+            _ => {
+                for _ in 0..due {
+                    machine.run_frame()?;
+                }
+            }
+        }
+
+        if let Some(sound) = &tone {
+            if outputs.tone.is_on() != tone_on {
+                tone_on = !tone_on;
+                if tone_on {
+                    audio::play_sound(
+                        sound,
+                        PlaySoundParams {
+                            looped: true,
+                            volume: 1.0,
+                        },
+                    );
+                } else {
+                    audio::stop_sound(sound);
+                }
+            }
+        }
+
+        frame_to_rgba(
+            &outputs.display.frame(),
+            config.display.foreground,
+            config.display.background,
+            &mut rgba,
+        );
+        texture.update_from_bytes(WIDTH as u32, HEIGHT as u32, &rgba);
+        draw(&texture, color(config.display.background));
+        next_frame().await;
+    }
+    Ok(machine.stop()?)
+}
+
+/// Draws the display as large as the window allows, keeping its shape.
+fn draw(texture: &Texture2D, background: Color) {
+    clear_background(background);
+    let scale = (screen_width() / WIDTH as f32).min(screen_height() / HEIGHT as f32);
+    let size = vec2(WIDTH as f32 * scale, HEIGHT as f32 * scale);
+    draw_texture_ex(
+        texture,
+        (screen_width() - size.x) / 2.0,
+        (screen_height() - size.y) / 2.0,
+        WHITE,
+        DrawTextureParams {
+            dest_size: Some(size),
+            ..DrawTextureParams::default()
+        },
+    );
+}
+
+/// The tone as a sound to loop, a tenth of a second or so of whole cycles so it loops without a click.
+async fn tone_sound(config: &Config) -> Result<Sound, Box<dyn Error>> {
+    let mut switch = ToneSwitch::new();
+    switch.set_tone(true);
+    let mut wave = SquareWave::new(switch, &config.audio, SAMPLE_RATE);
+    let frequency = config.audio.frequency;
+    let cycles = (frequency / 10.0).round().max(1.0);
+    let mut samples = vec![0.0; (SAMPLE_RATE as f32 / frequency * cycles).round() as usize];
+    wave.fill(&mut samples);
+    Ok(audio::load_sound_from_bytes(&wav(&samples)).await?)
+}
+
+/// Wraps mono samples up as a 16-bit WAV file, which is what macroquad loads sounds from.
+fn wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for &sample in samples {
+        wav.extend_from_slice(&((sample * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    wav
+}
+
+fn color(color: ConfigColor) -> Color {
+    Color::from_rgba(color.r, color.g, color.b, 0xFF)
+}
+
+/// The name SDL gives a key, which is what the config file uses.
+fn key_name(key: KeyCode) -> String {
+    let name = match key {
+        KeyCode::Key0 => "0",
+        KeyCode::Key1 => "1",
+        KeyCode::Key2 => "2",
+        KeyCode::Key3 => "3",
+        KeyCode::Key4 => "4",
+        KeyCode::Key5 => "5",
+        KeyCode::Key6 => "6",
+        KeyCode::Key7 => "7",
+        KeyCode::Key8 => "8",
+        KeyCode::Key9 => "9",
+        KeyCode::Equal => "=",
+        KeyCode::Minus => "-",
+        KeyCode::Comma => ",",
+        KeyCode::Period => ".",
+        KeyCode::Slash => "/",
+        KeyCode::Semicolon => ";",
+        KeyCode::Apostrophe => "'",
+        KeyCode::LeftBracket => "[",
+        KeyCode::RightBracket => "]",
+        KeyCode::Backslash => "\\",
+        KeyCode::GraveAccent => "`",
+        KeyCode::Enter => "Return",
+        // letters, function keys, arrows, and the rest are named the same in both
+        _ => return format!("{key:?}"),
+    };
+    name.to_string()
+}
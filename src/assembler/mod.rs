@@ -0,0 +1,404 @@
+//! An assembler for the mnemonics the decoder writes, so a disassembly can be edited and assembled back into the
+//! same program.
+//!
+//! Each line holds an optional `label:`, then an instruction such as `LD V0, 0x05` or a directive, then an optional
+//! `; comment`. Mnemonics and registers are case-insensitive, and numbers can be decimal, `0x` hex, or `0b` binary.
+//! Anywhere an address is expected, a label can be given instead. The directives are:
+//!
+//! - `DB 1, 0x02, 0b11`: bytes of data.
+//! - `DW 0x1234`: big-endian words of data.
+//! - `ALIAS name operand`: lets `name` stand for an operand, such as a register, from then on.
+//!
+//! Programs written in Octo's syntax are assembled by `octo::assemble()` instead.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    debugger::Symbols,
+    decoder::{decode_for, Instruction},
+    memory::PROGRAM_START,
+    quirks::Variant,
+};
+
+pub mod octo;
+
+/// Why a program couldn't be assembled, and the line it was on, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+/// A line's contents, waiting on the addresses of labels to be encoded.
+enum Item {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<String>,
+    },
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+}
+
+impl Item {
+    fn size(&self) -> usize {
+        match self {
+            Item::Instruction { operands, .. } => {
+                let long = operands
+                    .iter()
+                    .any(|operand| long_operand(operand).is_some());
+                if long {
+                    4
+                } else {
+                    2
+                }
+            }
+            Item::Bytes(values) => values.len(),
+            Item::Words(values) => values.len() * 2,
+        }
+    }
+}
+
+/// Assembles a program for a variant, refusing instructions the variant doesn't have.
+pub fn assemble(source: &str, variant: Variant) -> Result<Vec<u8>, AssembleError> {
+    assemble_with_symbols(source, variant).map(|(program, _)| program)
+}
+
+/// Assembles a program as `assemble()` does, along with the addresses of its labels.
+pub fn assemble_with_symbols(
+    source: &str,
+    variant: Variant,
+) -> Result<(Vec<u8>, Symbols), AssembleError> {
+    let mut labels = HashMap::new();
+    let mut symbols = Symbols::new();
+    let mut aliases: HashMap<String, String> = HashMap::new();
+    let mut items = Vec::new();
+    let mut address = PROGRAM_START;
+    for (number, line) in source.lines().enumerate() {
+        let line_number = number + 1;
+        let error = |message: String| AssembleError {
+            line: line_number,
+            message,
+        };
+        let mut line = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_name(label) {
+                return Err(error(format!("`{label}` is not a valid label")));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("label `{label}` is defined twice")));
+            }
+            symbols.alias(address as u16, label);
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        if mnemonic == "ALIAS" {
+            let (name, operand) = rest
+                .trim()
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| error("expected `ALIAS name operand`".to_string()))?;
+            if !is_name(name) {
+                return Err(error(format!("`{name}` is not a valid alias")));
+            }
+            aliases.insert(name.to_string(), operand.trim().to_string());
+            continue;
+        }
+        let operands: Vec<String> = if rest.trim().is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',')
+                .map(|operand| {
+                    let operand = operand.trim();
+                    aliases
+                        .get(operand)
+                        .map_or(operand, String::as_str)
+                        .to_string()
+                })
+                .collect()
+        };
+        let item = match mnemonic.as_str() {
+            "DB" => Item::Bytes(operands),
+            "DW" => Item::Words(operands),
+            _ => Item::Instruction { mnemonic, operands },
+        };
+        address += item.size();
+        items.push((line_number, item));
+    }
+
+    let mut program = Vec::new();
+    for (line, item) in items {
+        let error = |message: String| AssembleError { line, message };
+        match item {
+            Item::Bytes(values) => {
+                for value in values {
+                    program.push(value_of(&value, &labels, 0xFF).map_err(error)? as u8);
+                }
+            }
+            Item::Words(values) => {
+                for value in values {
+                    let word = value_of(&value, &labels, 0xFFFF).map_err(error)?;
+                    program.extend(word.to_be_bytes());
+                }
+            }
+            Item::Instruction { mnemonic, operands } => {
+                let instruction = parse(&mnemonic, &operands, &labels).map_err(error)?;
+                let bytes = encode(instruction, variant).ok_or_else(|| {
+                    error(format!("`{mnemonic}` is not a {variant:?} instruction"))
+                })?;
+                program.extend(bytes);
+            }
+        }
+    }
+    if program.len() > u16::MAX as usize + 1 - PROGRAM_START {
+        return Err(AssembleError {
+            line: source.lines().count(),
+            message: "program is too large to fit in memory".to_string(),
+        });
+    }
+    Ok((program, symbols))
+}
+
+/// Encodes an instruction, if the variant has it.
+fn encode(instruction: Instruction, variant: Variant) -> Option<Vec<u8>> {
+    let bytes = instruction.to_bytes();
+    let next = bytes
+        .get(2..4)
+        .map_or(0, |next| u16::from_be_bytes([next[0], next[1]]));
+    let decoded = decode_for(u16::from_be_bytes([bytes[0], bytes[1]]), next, variant);
+    (decoded == instruction).then_some(bytes)
+}
+
+/// Whether a word can name a label or alias.
+fn is_name(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The address of a `LONG` operand, as in `LD I, LONG label`.
+fn long_operand(operand: &str) -> Option<&str> {
+    let (keyword, address) = operand.split_once(char::is_whitespace)?;
+    keyword.eq_ignore_ascii_case("LONG").then(|| address.trim())
+}
+
+/// Evaluates a number or label, checking it's no more than `max`.
+fn value_of(operand: &str, labels: &HashMap<String, usize>, max: u32) -> Result<u16, String> {
+    let lower = operand.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2).ok()
+    } else if operand.starts_with(|c: char| c.is_ascii_digit()) {
+        operand.parse().ok()
+    } else if is_name(operand) {
+        let address = labels
+            .get(operand)
+            .ok_or_else(|| format!("unknown label `{operand}`"))?;
+        Some(*address as u32)
+    } else {
+        None
+    };
+    let value = parsed.ok_or_else(|| format!("`{operand}` is not a number or label"))?;
+    if value > max {
+        return Err(format!("`{operand}` is more than 0x{max:X}"));
+    }
+    Ok(value as u16)
+}
+
+/// An operand, classified for matching against the forms of each mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand<'a> {
+    Register(u8),
+    /// A keyword operand such as `I`, `DT`, or `[I]`, in upper case.
+    Keyword(&'static str),
+    Long(&'a str),
+    Value(&'a str),
+}
+
+fn classify(operand: &str) -> Operand<'_> {
+    const KEYWORDS: [&str; 9] = ["I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R"];
+    let upper = operand.to_ascii_uppercase();
+    if let Some(keyword) = KEYWORDS.iter().find(|&&keyword| keyword == upper) {
+        return Operand::Keyword(keyword);
+    }
+    if let Some(register) = upper.strip_prefix('V') {
+        if register.len() == 1 {
+            if let Ok(register) = u8::from_str_radix(register, 16) {
+                return Operand::Register(register);
+            }
+        }
+    }
+    match long_operand(operand) {
+        Some(address) => Operand::Long(address),
+        None => Operand::Value(operand),
+    }
+}
+
+/// Parses an instruction in the syntax `Instruction`'s `Display` writes.
+fn parse(
+    mnemonic: &str,
+    operands: &[String],
+    labels: &HashMap<String, usize>,
+) -> Result<Instruction, String> {
+    use Operand::{Keyword, Long, Register, Value};
+
+    let operands: Vec<Operand> = operands.iter().map(|operand| classify(operand)).collect();
+    let address = |value: &str| value_of(value, labels, 0xFFF);
+    let byte = |value: &str| value_of(value, labels, 0xFF).map(|value| value as u8);
+    let nibble = |value: &str| value_of(value, labels, 0xF).map(|value| value as u8);
+    let instruction = match (mnemonic, operands.as_slice()) {
+        ("CLS", []) => Instruction::ClearScreen,
+        ("RET", []) => Instruction::Return,
+        ("SYS", [Value(a)]) => Instruction::MachineCall {
+            address: address(a)?,
+        },
+        ("JP", [Value(a)]) => Instruction::Jump {
+            address: address(a)?,
+        },
+        ("JP", [Register(0), Value(a)]) => Instruction::JumpOffset {
+            address: address(a)?,
+        },
+        ("CALL", [Value(a)]) => Instruction::Call {
+            address: address(a)?,
+        },
+        ("SE", [Register(x), Value(v)]) => Instruction::SkipEqualValue {
+            x: *x,
+            value: byte(v)?,
+        },
+        ("SE", [Register(x), Register(y)]) => Instruction::SkipEqual { x: *x, y: *y },
+        ("SNE", [Register(x), Value(v)]) => Instruction::SkipNotEqualValue {
+            x: *x,
+            value: byte(v)?,
+        },
+        ("SNE", [Register(x), Register(y)]) => Instruction::SkipNotEqual { x: *x, y: *y },
+        ("LD", [Register(x), Value(v)]) => Instruction::SetValue {
+            x: *x,
+            value: byte(v)?,
+        },
+        ("LD", [Register(x), Register(y)]) => Instruction::Set { x: *x, y: *y },
+        ("LD", [Keyword("I"), Value(a)]) => Instruction::SetIndex {
+            address: address(a)?,
+        },
+        ("LD", [Keyword("I"), Long(a)]) => Instruction::SetLongIndex {
+            address: value_of(a, labels, 0xFFFF)?,
+        },
+        ("LD", [Register(x), Keyword("DT")]) => Instruction::GetDelay { x: *x },
+        ("LD", [Register(x), Keyword("K")]) => Instruction::WaitKey { x: *x },
+        ("LD", [Keyword("DT"), Register(x)]) => Instruction::SetDelay { x: *x },
+        ("LD", [Keyword("ST"), Register(x)]) => Instruction::SetSound { x: *x },
+        ("LD", [Keyword("F"), Register(x)]) => Instruction::FontCharacter { x: *x },
+        ("LD", [Keyword("HF"), Register(x)]) => Instruction::LargeFontCharacter { x: *x },
+        ("LD", [Keyword("B"), Register(x)]) => Instruction::StoreBcd { x: *x },
+        ("LD", [Keyword("[I]"), Register(x)]) => Instruction::StoreRegisters { x: *x },
+        ("LD", [Register(x), Keyword("[I]")]) => Instruction::LoadRegisters { x: *x },
+        ("LD", [Keyword("R"), Register(x)]) => Instruction::StoreFlags { x: *x },
+        ("LD", [Register(x), Keyword("R")]) => Instruction::LoadFlags { x: *x },
+        ("ADD", [Register(x), Value(v)]) => Instruction::AddValue {
+            x: *x,
+            value: byte(v)?,
+        },
+        ("ADD", [Register(x), Register(y)]) => Instruction::Add { x: *x, y: *y },
+        ("ADD", [Keyword("I"), Register(x)]) => Instruction::AddIndex { x: *x },
+        ("OR", [Register(x), Register(y)]) => Instruction::Or { x: *x, y: *y },
+        ("AND", [Register(x), Register(y)]) => Instruction::And { x: *x, y: *y },
+        ("XOR", [Register(x), Register(y)]) => Instruction::Xor { x: *x, y: *y },
+        ("SUB", [Register(x), Register(y)]) => Instruction::Sub { x: *x, y: *y },
+        ("SHR", [Register(x), Register(y)]) => Instruction::ShiftRight { x: *x, y: *y },
+        ("SUBN", [Register(x), Register(y)]) => Instruction::SubReverse { x: *x, y: *y },
+        ("SHL", [Register(x), Register(y)]) => Instruction::ShiftLeft { x: *x, y: *y },
+        ("RND", [Register(x), Value(v)]) => Instruction::Random {
+            x: *x,
+            mask: byte(v)?,
+        },
+        ("DRW", [Register(x), Register(y), Value(n)]) => Instruction::Draw {
+            x: *x,
+            y: *y,
+            height: nibble(n)?,
+        },
+        ("SKP", [Register(x)]) => Instruction::SkipKeyPressed { x: *x },
+        ("SKNP", [Register(x)]) => Instruction::SkipKeyNotPressed { x: *x },
+        ("SCD", [Value(n)]) => Instruction::ScrollDown { rows: nibble(n)? },
+        ("SCR", []) => Instruction::ScrollRight,
+        ("SCL", []) => Instruction::ScrollLeft,
+        ("EXIT", []) => Instruction::Exit,
+        ("LOW", []) => Instruction::LowResolution,
+        ("HIGH", []) => Instruction::HighResolution,
+        ("SCU", [Value(n)]) => Instruction::ScrollUp { rows: nibble(n)? },
+        ("SAVE", [Register(x), Register(y)]) => Instruction::StoreRange { x: *x, y: *y },
+        ("LOAD", [Register(x), Register(y)]) => Instruction::LoadRange { x: *x, y: *y },
+        ("PLANE", [Value(n)]) => Instruction::SelectPlanes { mask: nibble(n)? },
+        ("AUDIO", []) => Instruction::LoadAudio,
+        ("PITCH", [Register(x)]) => Instruction::SetPitch { x: *x },
+        _ => return Err(format!("can't assemble `{mnemonic}` with these operands")),
+    };
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_labels_data_and_aliases() {
+        let source = "
+            alias score V3
+            start:  LD score, 0x05   ; the starting score
+                    LD I, digits
+                    JP start
+            digits: DB 0xF0, 0b1001, 144
+                    DW 0xBEEF
+        ";
+        assert_eq!(
+            assemble(source, Variant::Chip8),
+            Ok(vec![
+                0x63, 0x05, 0xA2, 0x06, 0x12, 0x00, 0xF0, 0x09, 0x90, 0xBE, 0xEF
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        assert_eq!(
+            assemble("CLS\nJP nowhere", Variant::Chip8),
+            Err(AssembleError {
+                line: 2,
+                message: "unknown label `nowhere`".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("LD V0, 0x100", Variant::Chip8)
+                .unwrap_err()
+                .message,
+            "`0x100` is more than 0xFF"
+        );
+        assert_eq!(
+            assemble("HIGH", Variant::Chip8).unwrap_err().message,
+            "`HIGH` is not a Chip8 instruction"
+        );
+    }
+
+    #[test]
+    fn assembles_every_disassembly_back() {
+        for variant in [Variant::Chip8, Variant::SuperChip, Variant::XoChip] {
+            for opcode in 0..=u16::MAX {
+                let instruction = decode_for(opcode, 0x1234, variant);
+                assert_eq!(
+                    assemble(&instruction.to_string(), variant),
+                    Ok(instruction.to_bytes()),
+                    "{instruction} on {variant:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,865 @@
+//! An assembler for Octo's syntax, the one most CHIP-8 programs are written in today.
+//!
+//! Words are separated by whitespace, and `#` starts a comment. The parts of Octo understood here are:
+//!
+//! - `: name` to label the next address, `:alias name vX` to name a register, `:const name value` to name a
+//!   number, and `:byte value` or a bare number for a byte of data, such as a sprite's rows.
+//! - `:breakpoint name` to have the debugger stop at the next address, when given the program's symbols.
+//! - `name` or `:call name` to call a subroutine, `return` or `;` to return, `jump name`, and `jump0 name`.
+//! - Assignments such as `v0 := 5`, `v1 += v0`, `v2 := random 0xFF`, `v3 := key`, `i := name`, `i := hex v0`,
+//!   `delay := v0`, and `buzzer := v0`.
+//! - `clear`, `sprite vX vY n`, `bcd vX`, `save vX`, `load vX`, and the SUPER-CHIP and XO-CHIP statements.
+//! - `if vX == value then statement`, with `!=`, `key`, and `-key` too, and the blocks `if ... begin ... else ...
+//!   end` and `loop ... while condition ... again`.
+//!
+//! Labels can be used before they're defined. If a `main` label isn't the start of the program, the program starts
+//! with a jump to it, as in Octo. Macros, `:calc`, `:org`, and the comparisons needing a scratch register aren't
+//! supported.
+
+use std::collections::HashMap;
+
+use crate::{debugger::Symbols, decoder::Instruction, memory::PROGRAM_START, quirks::Variant};
+
+use super::{encode, AssembleError};
+
+/// A word of source, and the line it's on, counting from 1.
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+/// An address to fill in once every label is known.
+struct Fixup {
+    offset: usize,
+    label: String,
+    line: usize,
+    /// Whether the address is XO-CHIP's 16-bit one after `F000`, rather than the low 12 bits of the opcode.
+    long: bool,
+}
+
+/// A block waiting on its end.
+enum Block {
+    /// An `if ... begin`, with the jump past it to fill in at its `else` or `end`.
+    If {
+        jump: usize,
+        has_else: bool,
+        line: usize,
+    },
+    /// A `loop`, with where it starts and the jumps out of it for each `while`.
+    Loop {
+        start: u16,
+        breaks: Vec<usize>,
+        line: usize,
+    },
+}
+
+/// When a condition holds: a register compared with a value or another register, or a key held or not.
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    Equal(u8, Operand),
+    NotEqual(u8, Operand),
+    Key(u8),
+    NotKey(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Value(u8),
+}
+
+impl Condition {
+    /// The instruction skipping the next when the condition holds, or when it doesn't.
+    fn skip(self, holds: bool) -> Instruction {
+        let (x, operand, equal) = match self {
+            Condition::Equal(x, operand) => (x, operand, holds),
+            Condition::NotEqual(x, operand) => (x, operand, !holds),
+            Condition::Key(x) if holds => return Instruction::SkipKeyPressed { x },
+            Condition::Key(x) => return Instruction::SkipKeyNotPressed { x },
+            Condition::NotKey(x) if holds => return Instruction::SkipKeyNotPressed { x },
+            Condition::NotKey(x) => return Instruction::SkipKeyPressed { x },
+        };
+        match (operand, equal) {
+            (Operand::Value(value), true) => Instruction::SkipEqualValue { x, value },
+            (Operand::Value(value), false) => Instruction::SkipNotEqualValue { x, value },
+            (Operand::Register(y), true) => Instruction::SkipEqual { x, y },
+            (Operand::Register(y), false) => Instruction::SkipNotEqual { x, y },
+        }
+    }
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    variant: Variant,
+    program: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    aliases: HashMap<&'a str, u8>,
+    constants: HashMap<&'a str, u16>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    /// The labels and breakpoints, in the order they were defined.
+    symbols: Symbols,
+}
+
+/// Assembles a program written in Octo's syntax for a variant, refusing instructions the variant doesn't have.
+pub fn assemble(source: &str, variant: Variant) -> Result<Vec<u8>, AssembleError> {
+    assemble_with_symbols(source, variant).map(|(program, _)| program)
+}
+
+/// Assembles a program as `assemble()` does, along with its symbols: the addresses of its labels, and its
+/// breakpoints.
+pub fn assemble_with_symbols(
+    source: &str,
+    variant: Variant,
+) -> Result<(Vec<u8>, Symbols), AssembleError> {
+    let tokens: Vec<Token> = source
+        .lines()
+        .enumerate()
+        .flat_map(|(number, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |text| Token {
+                text,
+                line: number + 1,
+            })
+        })
+        .collect();
+    let last_line = source.lines().count();
+    let mut assembler = Assembler {
+        tokens,
+        next: 0,
+        variant,
+        program: Vec::new(),
+        labels: HashMap::new(),
+        aliases: HashMap::new(),
+        constants: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+        symbols: Symbols::new(),
+    };
+    assembler.jump_to_main()?;
+    while let Some(token) = assembler.take() {
+        assembler.statement(token)?;
+    }
+    assembler.finish(last_line)
+}
+
+impl<'a> Assembler<'a> {
+    fn take(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.next).copied();
+        self.next += 1;
+        token
+    }
+
+    /// Takes the next word, which the statement begun on `after` needs.
+    fn expect(&mut self, after: Token<'a>) -> Result<Token<'a>, AssembleError> {
+        self.take().ok_or_else(|| AssembleError {
+            line: after.line,
+            message: format!("`{}` is missing what comes after it", after.text),
+        })
+    }
+
+    fn address(&self) -> u16 {
+        (PROGRAM_START + self.program.len()) as u16
+    }
+
+    /// Starts the program with a jump to `main`, if there's a `main` that isn't at the start already.
+    fn jump_to_main(&mut self) -> Result<(), AssembleError> {
+        let Some(main) = self
+            .tokens
+            .windows(2)
+            .position(|pair| pair[0].text == ":" && pair[1].text == "main")
+        else {
+            return Ok(());
+        };
+        // aliases and constants take up no room, so `main` can come after them and still be at the start
+        let mut start = 0;
+        while matches!(self.tokens.get(start), Some(token) if [":alias", ":const"].contains(&token.text))
+        {
+            start += 3;
+        }
+        if start == main {
+            return Ok(());
+        }
+        let line = self.tokens[main].line;
+        self.emit_to(Instruction::Jump { address: 0 }, "main", line)
+    }
+
+    fn statement(&mut self, token: Token<'a>) -> Result<(), AssembleError> {
+        let error = |message: String| AssembleError {
+            line: token.line,
+            message,
+        };
+        match token.text {
+            ":" => {
+                let name = self.expect(token)?;
+                self.define(name, |assembler| {
+                    let address = assembler.address();
+                    assembler.labels.insert(name.text, address);
+                    assembler.symbols.alias(address, name.text);
+                })
+            }
+            ":breakpoint" => {
+                let name = self.expect(token)?;
+                self.symbols.add_breakpoint(self.address(), name.text);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.expect(token)?;
+                let register = self.expect(token)?;
+                let register = self.register(register)?;
+                self.define(name, |assembler| {
+                    assembler.aliases.insert(name.text, register);
+                })
+            }
+            ":const" => {
+                let name = self.expect(token)?;
+                let value = self.expect(token)?;
+                let value = self.number(value, 0xFFFF)?;
+                self.define(name, |assembler| {
+                    assembler.constants.insert(name.text, value);
+                })
+            }
+            ":byte" => {
+                let value = self.expect(token)?;
+                let byte = self.byte(value)?;
+                self.program.push(byte);
+                Ok(())
+            }
+            ":call" => {
+                let target = self.expect(token)?;
+                self.emit_to(Instruction::Call { address: 0 }, target.text, target.line)
+            }
+            "return" | ";" => self.emit(Instruction::Return, token),
+            "clear" => self.emit(Instruction::ClearScreen, token),
+            "exit" => self.emit(Instruction::Exit, token),
+            "hires" => self.emit(Instruction::HighResolution, token),
+            "lores" => self.emit(Instruction::LowResolution, token),
+            "scroll-left" => self.emit(Instruction::ScrollLeft, token),
+            "scroll-right" => self.emit(Instruction::ScrollRight, token),
+            "audio" => self.emit(Instruction::LoadAudio, token),
+            "scroll-down" | "scroll-up" | "plane" => {
+                let value = self.expect(token)?;
+                let n = self.number(value, 0xF)? as u8;
+                let instruction = match token.text {
+                    "scroll-down" => Instruction::ScrollDown { rows: n },
+                    "scroll-up" => Instruction::ScrollUp { rows: n },
+                    _ => Instruction::SelectPlanes { mask: n },
+                };
+                self.emit(instruction, token)
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let register = self.expect(token)?;
+                let x = self.register(register)?;
+                let instruction = match token.text {
+                    "bcd" => Instruction::StoreBcd { x },
+                    "saveflags" => Instruction::StoreFlags { x },
+                    _ => Instruction::LoadFlags { x },
+                };
+                self.emit(instruction, token)
+            }
+            "save" | "load" => {
+                let register = self.expect(token)?;
+                let x = self.register(register)?;
+                let save = token.text == "save";
+                let instruction = if self.peek_is("-") {
+                    self.next += 1;
+                    let last = self.expect(token)?;
+                    let y = self.register(last)?;
+                    if save {
+                        Instruction::StoreRange { x, y }
+                    } else {
+                        Instruction::LoadRange { x, y }
+                    }
+                } else if save {
+                    Instruction::StoreRegisters { x }
+                } else {
+                    Instruction::LoadRegisters { x }
+                };
+                self.emit(instruction, token)
+            }
+            "sprite" => {
+                let (x, y, height) = (
+                    self.expect(token)?,
+                    self.expect(token)?,
+                    self.expect(token)?,
+                );
+                let instruction = Instruction::Draw {
+                    x: self.register(x)?,
+                    y: self.register(y)?,
+                    height: self.number(height, 0xF)? as u8,
+                };
+                self.emit(instruction, token)
+            }
+            "native" => {
+                let target = self.expect(token)?;
+                self.emit_to(
+                    Instruction::MachineCall { address: 0 },
+                    target.text,
+                    target.line,
+                )
+            }
+            "jump" => {
+                let target = self.expect(token)?;
+                self.emit_to(Instruction::Jump { address: 0 }, target.text, target.line)
+            }
+            "jump0" => {
+                let target = self.expect(token)?;
+                self.emit_to(
+                    Instruction::JumpOffset { address: 0 },
+                    target.text,
+                    target.line,
+                )
+            }
+            "if" => {
+                let condition = self.condition(token)?;
+                let then = self.expect(token)?;
+                match then.text {
+                    "then" => self.emit(condition.skip(false), token),
+                    "begin" => {
+                        self.emit(condition.skip(true), token)?;
+                        let jump = self.program.len();
+                        self.emit(Instruction::Jump { address: 0 }, token)?;
+                        self.blocks.push(Block::If {
+                            jump,
+                            has_else: false,
+                            line: token.line,
+                        });
+                        Ok(())
+                    }
+                    other => Err(error(format!("expected `then` or `begin`, not `{other}`"))),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If {
+                    jump,
+                    has_else: false,
+                    line,
+                }) => {
+                    let end = self.program.len();
+                    self.emit(Instruction::Jump { address: 0 }, token)?;
+                    self.patch(jump, self.address());
+                    self.blocks.push(Block::If {
+                        jump: end,
+                        has_else: true,
+                        line,
+                    });
+                    Ok(())
+                }
+                _ => Err(error("`else` without `if ... begin`".to_string())),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => {
+                    self.patch(jump, self.address());
+                    Ok(())
+                }
+                _ => Err(error("`end` without `if ... begin`".to_string())),
+            },
+            "loop" => {
+                self.blocks.push(Block::Loop {
+                    start: self.address(),
+                    breaks: Vec::new(),
+                    line: token.line,
+                });
+                Ok(())
+            }
+            "while" => {
+                let condition = self.condition(token)?;
+                self.emit(condition.skip(true), token)?;
+                let jump = self.program.len();
+                self.emit(Instruction::Jump { address: 0 }, token)?;
+                let innermost = self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { breaks, .. } => Some(breaks),
+                    Block::If { .. } => None,
+                });
+                match innermost {
+                    Some(breaks) => {
+                        breaks.push(jump);
+                        Ok(())
+                    }
+                    None => Err(error("`while` outside a `loop`".to_string())),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks, .. }) => {
+                    self.emit(Instruction::Jump { address: start }, token)?;
+                    for jump in breaks {
+                        self.patch(jump, self.address());
+                    }
+                    Ok(())
+                }
+                _ => Err(error("`again` without `loop`".to_string())),
+            },
+            "i" => self.index_statement(token),
+            "delay" | "buzzer" | "pitch" => {
+                let assign = self.expect(token)?;
+                if assign.text != ":=" {
+                    return Err(error(format!("expected `:=` after `{}`", token.text)));
+                }
+                let register = self.expect(token)?;
+                let x = self.register(register)?;
+                let instruction = match token.text {
+                    "delay" => Instruction::SetDelay { x },
+                    "buzzer" => Instruction::SetSound { x },
+                    _ => Instruction::SetPitch { x },
+                };
+                self.emit(instruction, token)
+            }
+            _ if self.is_register(token.text) => self.register_statement(token),
+            _ if is_number(token.text) || self.constants.contains_key(token.text) => {
+                let byte = self.byte(token)?;
+                self.program.push(byte);
+                Ok(())
+            }
+            _ if is_name(token.text) => {
+                self.emit_to(Instruction::Call { address: 0 }, token.text, token.line)
+            }
+            other => Err(error(format!("`{other}` isn't a statement"))),
+        }
+    }
+
+    /// Assignments to a register: `vX := ...`, `vX += ...`, and the rest.
+    fn register_statement(&mut self, token: Token<'a>) -> Result<(), AssembleError> {
+        let x = self.register(token)?;
+        let operator = self.expect(token)?;
+        let source = self.expect(operator)?;
+        let error = |message: String| AssembleError {
+            line: operator.line,
+            message,
+        };
+        let y = || self.register(source);
+        let instruction = match (operator.text, source.text) {
+            (":=", "random") => {
+                let mask = self.expect(source)?;
+                Instruction::Random {
+                    x,
+                    mask: self.byte(mask)?,
+                }
+            }
+            (":=", "delay") => Instruction::GetDelay { x },
+            (":=", "key") => Instruction::WaitKey { x },
+            (":=", _) if self.is_register(source.text) => Instruction::Set { x, y: y()? },
+            (":=", _) => Instruction::SetValue {
+                x,
+                value: self.byte(source)?,
+            },
+            ("+=", _) if self.is_register(source.text) => Instruction::Add { x, y: y()? },
+            ("+=", _) => Instruction::AddValue {
+                x,
+                value: self.byte(source)?,
+            },
+            ("-=", _) if self.is_register(source.text) => Instruction::Sub { x, y: y()? },
+            // there's no instruction subtracting a value, so add its negation
+            ("-=", _) => Instruction::AddValue {
+                x,
+                value: self.byte(source)?.wrapping_neg(),
+            },
+            ("=-", _) => Instruction::SubReverse { x, y: y()? },
+            ("|=", _) => Instruction::Or { x, y: y()? },
+            ("&=", _) => Instruction::And { x, y: y()? },
+            ("^=", _) => Instruction::Xor { x, y: y()? },
+            (">>=", _) => Instruction::ShiftRight { x, y: y()? },
+            ("<<=", _) => Instruction::ShiftLeft { x, y: y()? },
+            (other, _) => return Err(error(format!("`{other}` isn't an assignment"))),
+        };
+        self.emit(instruction, token)
+    }
+
+    /// Assignments to I: `i := name`, `i := long name`, `i := hex vX`, `i := bighex vX`, and `i += vX`.
+    fn index_statement(&mut self, token: Token<'a>) -> Result<(), AssembleError> {
+        let operator = self.expect(token)?;
+        let source = self.expect(operator)?;
+        match (operator.text, source.text) {
+            (":=", "hex") => {
+                let register = self.expect(source)?;
+                let x = self.register(register)?;
+                self.emit(Instruction::FontCharacter { x }, token)
+            }
+            (":=", "bighex") => {
+                let register = self.expect(source)?;
+                let x = self.register(register)?;
+                self.emit(Instruction::LargeFontCharacter { x }, token)
+            }
+            (":=", "long") => {
+                let target = self.expect(source)?;
+                self.emit_to(
+                    Instruction::SetLongIndex { address: 0 },
+                    target.text,
+                    target.line,
+                )
+            }
+            (":=", _) => self.emit_to(
+                Instruction::SetIndex { address: 0 },
+                source.text,
+                source.line,
+            ),
+            ("+=", _) => {
+                let x = self.register(source)?;
+                self.emit(Instruction::AddIndex { x }, token)
+            }
+            (other, _) => Err(AssembleError {
+                line: operator.line,
+                message: format!("`{other}` isn't an assignment to `i`"),
+            }),
+        }
+    }
+
+    /// Parses `vX == value`, `vX != vY`, `vX key`, or `vX -key`.
+    fn condition(&mut self, token: Token<'a>) -> Result<Condition, AssembleError> {
+        let register = self.expect(token)?;
+        let x = self.register(register)?;
+        let operator = self.expect(register)?;
+        let operand = |assembler: &mut Assembler<'a>| {
+            let operand = assembler.expect(operator)?;
+            if assembler.is_register(operand.text) {
+                Ok(Operand::Register(assembler.register(operand)?))
+            } else {
+                Ok(Operand::Value(assembler.byte(operand)?))
+            }
+        };
+        match operator.text {
+            "==" => Ok(Condition::Equal(x, operand(self)?)),
+            "!=" => Ok(Condition::NotEqual(x, operand(self)?)),
+            "key" => Ok(Condition::Key(x)),
+            "-key" => Ok(Condition::NotKey(x)),
+            "<" | ">" | "<=" | ">=" => Err(AssembleError {
+                line: operator.line,
+                message: format!(
+                    "`{}` needs a scratch register, so isn't supported",
+                    operator.text
+                ),
+            }),
+            other => Err(AssembleError {
+                line: operator.line,
+                message: format!("`{other}` isn't a comparison"),
+            }),
+        }
+    }
+
+    /// Runs `define` for a new name, refusing names already taken.
+    fn define(
+        &mut self,
+        name: Token<'a>,
+        define: impl FnOnce(&mut Assembler<'a>),
+    ) -> Result<(), AssembleError> {
+        let taken = self.labels.contains_key(name.text)
+            || self.aliases.contains_key(name.text)
+            || self.constants.contains_key(name.text);
+        if !is_name(name.text) || is_register_name(name.text) {
+            return Err(AssembleError {
+                line: name.line,
+                message: format!("`{}` is not a valid name", name.text),
+            });
+        }
+        if taken {
+            return Err(AssembleError {
+                line: name.line,
+                message: format!("`{}` is defined twice", name.text),
+            });
+        }
+        define(self);
+        Ok(())
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens
+            .get(self.next)
+            .is_some_and(|token| token.text == text)
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        is_register_name(text) || self.aliases.contains_key(text)
+    }
+
+    fn register(&self, token: Token) -> Result<u8, AssembleError> {
+        if let Some(&register) = self.aliases.get(token.text) {
+            return Ok(register);
+        }
+        token
+            .text
+            .strip_prefix(['v', 'V'])
+            .filter(|_| is_register_name(token.text))
+            .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+            .ok_or_else(|| AssembleError {
+                line: token.line,
+                message: format!("`{}` is not a register", token.text),
+            })
+    }
+
+    /// A number or constant no more than `max`.
+    fn number(&self, token: Token, max: u16) -> Result<u16, AssembleError> {
+        let error = |message: String| AssembleError {
+            line: token.line,
+            message,
+        };
+        let value = match self.constants.get(token.text) {
+            Some(&value) => value as i32,
+            None => parse_number(token.text)
+                .ok_or_else(|| error(format!("`{}` is not a number", token.text)))?,
+        };
+        if value < 0 || value > max as i32 {
+            return Err(error(format!("`{}` is more than 0x{max:X}", token.text)));
+        }
+        Ok(value as u16)
+    }
+
+    /// A byte, which may be written as a negative number, as in `v0 += -1`.
+    fn byte(&self, token: Token) -> Result<u8, AssembleError> {
+        match parse_number(token.text) {
+            Some(value @ -128..=-1) => Ok(value as u8),
+            _ => self.number(token, 0xFF).map(|value| value as u8),
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction, token: Token) -> Result<(), AssembleError> {
+        let bytes = encode(instruction, self.variant).ok_or_else(|| AssembleError {
+            line: token.line,
+            message: format!("`{}` is not a {:?} instruction", token.text, self.variant),
+        })?;
+        self.program.extend(bytes);
+        Ok(())
+    }
+
+    /// Emits an instruction whose address is the value of `target`, filled in later if it's a label.
+    fn emit_to(
+        &mut self,
+        instruction: Instruction,
+        target: &str,
+        line: usize,
+    ) -> Result<(), AssembleError> {
+        let offset = self.program.len();
+        let long = matches!(instruction, Instruction::SetLongIndex { .. });
+        self.emit(instruction, Token { text: target, line })?;
+        if is_number(target) || self.constants.contains_key(target) {
+            let max = if long { 0xFFFF } else { 0xFFF };
+            let address = self.number(Token { text: target, line }, max)?;
+            self.patch_address(offset, address, long);
+        } else if is_name(target) {
+            self.fixups.push(Fixup {
+                offset,
+                label: target.to_string(),
+                line,
+                long,
+            });
+        } else {
+            return Err(AssembleError {
+                line,
+                message: format!("`{target}` is not an address or label"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Points the jump at `offset` to `address`.
+    fn patch(&mut self, offset: usize, address: u16) {
+        self.patch_address(offset, address, false);
+    }
+
+    fn patch_address(&mut self, offset: usize, address: u16, long: bool) {
+        if long {
+            self.program[offset + 2..offset + 4].copy_from_slice(&address.to_be_bytes());
+        } else {
+            self.program[offset] = self.program[offset] & 0xF0 | (address >> 8) as u8 & 0x0F;
+            self.program[offset + 1] = address as u8;
+        }
+    }
+
+    /// Fills in every label's address, and checks every block was closed.
+    fn finish(mut self, last_line: usize) -> Result<(Vec<u8>, Symbols), AssembleError> {
+        if let Some(block) = self.blocks.last() {
+            let (line, message) = match block {
+                Block::If { line, .. } => (*line, "`if ... begin` without `end`"),
+                Block::Loop { line, .. } => (*line, "`loop` without `again`"),
+            };
+            return Err(AssembleError {
+                line,
+                message: message.to_string(),
+            });
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let address = *self
+                .labels
+                .get(fixup.label.as_str())
+                .ok_or_else(|| AssembleError {
+                    line: fixup.line,
+                    message: format!("unknown label `{}`", fixup.label),
+                })?;
+            if !fixup.long && address > 0xFFF {
+                return Err(AssembleError {
+                    line: fixup.line,
+                    message: format!("`{}` is past 0xFFF, so needs `long`", fixup.label),
+                });
+            }
+            self.patch_address(fixup.offset, address, fixup.long);
+        }
+        if self.program.len() > u16::MAX as usize + 1 - PROGRAM_START {
+            return Err(AssembleError {
+                line: last_line,
+                message: "program is too large to fit in memory".to_string(),
+            });
+        }
+        Ok((self.program, self.symbols))
+    }
+}
+
+/// Whether a word names a register, `v0` through `vF`.
+fn is_register_name(word: &str) -> bool {
+    word.len() == 2
+        && word.starts_with(['v', 'V'])
+        && word[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether a word can name a label, alias, or constant. Octo allows dashes too.
+fn is_name(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_number(word: &str) -> bool {
+    parse_number(word).is_some()
+}
+
+/// Parses a decimal, `0x` hex, or `0b` binary number, which may be negative.
+fn parse_number(word: &str) -> Option<i32> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    let lower = digits.to_ascii_lowercase();
+    let value = if let Some(hex) = lower.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_statements_and_sprite_data() {
+        let source = "
+            :alias x v1
+            :const SPEED 2
+            : main
+                clear
+                i := ball         # the ball's sprite
+                loop
+                    sprite x v2 2
+                    x += SPEED
+                    if x == 60 then x := 0
+                again
+            : ball
+                0b11000000 0xC0
+        ";
+        assert_eq!(
+            assemble(source, Variant::Chip8),
+            Ok(vec![
+                0x00, 0xE0, // clear
+                0xA2, 0x0E, // i := ball
+                0xD1, 0x22, // sprite
+                0x71, 0x02, // x += SPEED
+                0x41, 0x3C, // if x == 60 then
+                0x61, 0x00, // x := 0
+                0x12, 0x04, // again
+                0xC0, 0xC0, // ball
+            ])
+        );
+    }
+
+    #[test]
+    fn jumps_to_main_and_calls_subroutines() {
+        let source = "
+            : draw
+                sprite v0 v0 5
+                ;
+            : main
+                draw
+                v3 -= 1
+                jump main
+        ";
+        assert_eq!(
+            assemble(source, Variant::Chip8),
+            Ok(vec![
+                0x12, 0x06, // jump main
+                0xD0, 0x05, // draw:
+                0x00, 0xEE, //
+                0x22, 0x02, // main: draw
+                0x73, 0xFF, // v3 -= 1
+                0x12, 0x06, // jump main
+            ])
+        );
+    }
+
+    #[test]
+    fn lists_labels_and_breakpoints() {
+        let source = "
+            : main
+            : start
+                v0 := 1
+                :breakpoint set
+                v1 := 2
+                jump start
+        ";
+        let (_, symbols) =
+            assemble_with_symbols(source, Variant::Chip8).expect("failed to assemble");
+        assert_eq!(symbols.name(0x200), Some("main"));
+        assert_eq!(symbols.address("start"), Some(0x200));
+        assert_eq!(symbols.breakpoints().collect::<Vec<_>>(), [(0x202, "set")]);
+    }
+
+    #[test]
+    fn assembles_blocks() {
+        let source = "
+            loop
+                v0 := key
+                while v0 != 5
+                if v0 == v1 begin
+                    v2 := 1
+                else
+                    v2 := 2
+                end
+            again
+        ";
+        assert_eq!(
+            assemble(source, Variant::Chip8),
+            Ok(vec![
+                0xF0, 0x0A, // 200: v0 := key
+                0x40, 0x05, // 202: while v0 != 5: skip if it holds
+                0x12, 0x12, // 204: out of the loop
+                0x50, 0x10, // 206: if v0 == v1 begin: skip if it holds
+                0x12, 0x0E, // 208: to the else
+                0x62, 0x01, // 20A
+                0x12, 0x10, // 20C: past the else
+                0x62, 0x02, // 20E
+                0x12, 0x00, // 210: again
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        let error = |source| assemble(source, Variant::Chip8).unwrap_err();
+        assert_eq!(
+            error("clear\njump nowhere"),
+            AssembleError {
+                line: 2,
+                message: "unknown label `nowhere`".to_string()
+            }
+        );
+        assert_eq!(error("v0 := 256").message, "`256` is more than 0xFF");
+        assert_eq!(error("\n\nloop\nclear").line, 3);
+        assert_eq!(error("hires").message, "`hires` is not a Chip8 instruction");
+        assert_eq!(error(": a\n: a").message, "`a` is defined twice");
+        assert_eq!(error("v0 *= v1").message, "`*=` isn't an assignment");
+    }
+}
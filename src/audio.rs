@@ -0,0 +1,7 @@
+/// Somewhere to play the Chip8's tone, which sounds whenever the sound timer is nonzero.
+///
+/// The sink is driven from whichever thread counts the timers down, usually the clock's.
+pub trait AudioSink: Send {
+    /// Starts or stops the tone.
+    fn set_tone(&mut self, on: bool);
+}
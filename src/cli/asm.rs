@@ -0,0 +1,62 @@
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+
+use chip8_rust::{
+    assembler::{assemble_with_symbols, octo},
+    quirks::Variant,
+};
+
+use super::CliResult;
+
+#[derive(Debug, Args)]
+pub struct AsmArgs {
+    /// The source to assemble, in the syntax `chip8 disasm --source` writes, or Octo's for a .8o file.
+    source: PathBuf,
+    /// Where to write the program. Defaults to the source with a .ch8 extension.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Allows the instructions of this machine: chip8, schip, or xochip.
+    #[arg(long, default_value = "chip8")]
+    variant: Variant,
+    /// Reads the source as Octo's syntax, whatever its extension.
+    #[arg(long)]
+    octo: bool,
+    /// Writes the addresses of the program's labels, and any breakpoints it marks, to this symbol file, for the
+    /// debugger and disassembler.
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+}
+
+/// Assembles a source file into a program.
+pub fn execute(args: AsmArgs) -> CliResult {
+    let source = fs::read_to_string(&args.source)
+        .map_err(|error| format!("could not read {}: {error}", args.source.display()))?;
+    let is_octo = args.octo
+        || args
+            .source
+            .extension()
+            .is_some_and(|extension| extension == "8o");
+    let (program, symbols) = if is_octo {
+        octo::assemble_with_symbols(&source, args.variant)
+    } else {
+        assemble_with_symbols(&source, args.variant)
+    }
+    .map_err(|error| format!("{}: {error}", args.source.display()))?;
+    let output = args
+        .output
+        .unwrap_or_else(|| args.source.with_extension("ch8"));
+    fs::write(&output, &program)
+        .map_err(|error| format!("could not write {}: {error}", output.display()))?;
+    println!("wrote {} bytes to {}", program.len(), output.display());
+    if let Some(path) = &args.symbols {
+        fs::write(path, symbols.to_string())
+            .map_err(|error| format!("could not write {}: {error}", path.display()))?;
+        println!(
+            "wrote {} symbols to {}",
+            symbols.names().count(),
+            path.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use chip8_rust::{
+    debugger::Debugger,
+    reference::{compare, Comparison, TraceFormat, DEFAULT_TRACE_FORMAT},
+};
+
+use super::{headless_builder, read_rom, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct CompareArgs {
+    /// The program the trace log is of.
+    rom: PathBuf,
+    /// The trace log another emulator wrote, with a line for each instruction it ran.
+    trace: PathBuf,
+    /// Which columns of the log's lines hold what, such as `pc opcode v0-vf i`. `_` is a column to skip, and `*`
+    /// however many columns leave the rest at the end of the line.
+    #[arg(long, value_name = "COLUMNS", default_value = DEFAULT_TRACE_FORMAT)]
+    format: TraceFormat,
+    #[command(flatten)]
+    machine: MachineArgs,
+}
+
+/// Runs the program alongside the trace log, failing at the first instruction the two disagree on.
+pub fn execute(args: CompareArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let reference = read_text(&args.trace)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    // comparing a run shouldn't touch its saves
+    let mut machine = headless_builder(&config).autosave(false).build()?;
+    machine.load_rom(&rom)?;
+    let mut debugger = Debugger::new(machine);
+    let comparison = compare(&mut debugger, &reference, &args.format)
+        .map_err(|message| format!("{}: {message}", args.trace.display()))?;
+    debugger.into_machine().stop()?;
+    match comparison {
+        Comparison::Matched { steps } => {
+            println!("ok: {steps} instructions matched the reference");
+            Ok(())
+        }
+        Comparison::Diverged(divergence) => {
+            eprint!("{divergence}");
+            Err(format!("diverged from the reference at line {}", divergence.line).into())
+        }
+        Comparison::Halted { line, step, error } => Err(format!(
+            "stopped after {step} instructions, before line {line} of the reference: {error}"
+        )
+        .into()),
+        Comparison::WaitingForKey { line, step } => Err(format!(
+            "waiting for a key after {step} instructions, before line {line} of the reference"
+        )
+        .into()),
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    io,
+    net::{Ipv4Addr, TcpListener},
+    path::Path,
+};
+
+use clap::Args;
+
+use chip8_rust::{debugger::dap, machine::Chip8};
+
+use super::{headless_builder, read_rom, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct DapArgs {
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Listens for one editor on this TCP port of localhost, rather than talking over stdin and stdout.
+    #[arg(long)]
+    port: Option<u16>,
+}
+
+/// Serves the Debug Adapter Protocol to an editor, which launches programs on machines set up from the config file
+/// and these options.
+pub fn execute(args: DapArgs) -> CliResult {
+    let machine = args.machine;
+    let launch = move |path: &Path| -> Result<Chip8, String> {
+        let launched = || -> Result<Chip8, Box<dyn std::error::Error>> {
+            let rom = read_rom(path)?;
+            let config = machine.config(&rom, Some(path))?;
+            let mut machine = headless_builder(&config).build()?;
+            machine.load_rom(&rom)?;
+            Ok(machine)
+        };
+        launched().map_err(|error| error.to_string())
+    };
+    match args.port {
+        Some(port) => {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                .map_err(|error| format!("could not listen on port {port}: {error}"))?;
+            eprintln!("waiting for an editor on port {port}");
+            let (stream, _) = listener.accept()?;
+            dap::serve(stream.try_clone()?, stream, launch)?;
+        }
+        None => dap::serve(io::stdin(), io::stdout(), launch)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use chip8_rust::{deadcode::DeadCodeReport, machine::Coverage};
+
+use super::{headless_builder, parse_inputs, read_rom, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct DeadCodeArgs {
+    /// The program to look through.
+    rom: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Runs the program for this many frames, counting what it runs and reads as used too, and reporting code it
+    /// could reach but never ran.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    frames: u64,
+    /// Holds keys down while running, as an input script in the format `test` reads says.
+    #[arg(long, value_name = "FILE")]
+    inputs: Option<PathBuf>,
+}
+
+/// Prints the parts of the program that look unused, from following its code and, if asked, from running it.
+pub fn execute(args: DeadCodeArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let coverage = if args.frames > 0 {
+        let inputs = match &args.inputs {
+            Some(path) => parse_inputs(&read_text(path)?)?,
+            None => Vec::new(),
+        };
+        // looking at a program shouldn't touch its saves
+        let mut machine = headless_builder(&config).autosave(false).build()?;
+        machine.load_rom(&rom)?;
+        machine.start_coverage(Coverage::new());
+        let mut inputs = inputs.into_iter().peekable();
+        for frame in 0..args.frames {
+            while let Some((_, keys)) = inputs.next_if(|&(at, _)| at <= frame) {
+                machine.keypad_mut().set_mask(keys);
+            }
+            // what ran before a crash is still worth counting
+            if let Err(error) = machine.run_frame() {
+                eprintln!("stopped on frame {frame}: {error}");
+                break;
+            }
+        }
+        let coverage = machine.stop_coverage();
+        machine.stop()?;
+        coverage
+    } else {
+        None
+    };
+    let report = DeadCodeReport::new(&rom, config.machine.variant, coverage.as_ref());
+    print!("{report}");
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+#[cfg(feature = "egui")]
+use chip8_rust::frontend::egui;
+#[cfg(feature = "terminal")]
+use chip8_rust::frontend::terminal;
+use chip8_rust::frontend::Outputs;
+
+use super::{headless_builder, read_rom, read_symbols, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct DebugArgs {
+    /// The program to debug.
+    rom: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Starts paused on the first instruction, rather than running.
+    #[arg(long)]
+    paused: bool,
+    /// Names addresses from this symbol file, such as one Octo writes. The terminal debugger also sets the
+    /// breakpoints it marks.
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+    /// Debugs in the terminal instead of a window, which works over SSH.
+    #[cfg(feature = "terminal")]
+    #[arg(long)]
+    terminal: bool,
+}
+
+/// Opens the program in the debugger window, or in the terminal, which drives the machine itself a frame at a time.
+pub fn execute(args: DebugArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let symbols = read_symbols(args.symbols.as_deref())?;
+    let outputs = Outputs::new();
+    let mut machine = outputs.attach(headless_builder(&config)).build()?;
+    machine.load_rom(&rom)?;
+    if args.paused {
+        machine.pause();
+    }
+    let name = args.rom.file_name().unwrap_or(args.rom.as_os_str());
+    let title = format!("chip8 debugger - {}", name.to_string_lossy());
+    #[cfg(feature = "terminal")]
+    if args.terminal {
+        return Ok(terminal::debugger::run(machine, &symbols, &config, &title)?);
+    }
+    #[cfg(feature = "egui")]
+    let shown = egui::run(machine, &outputs, &symbols, &config, &title);
+    // without a window to open, the terminal is all there is
+    #[cfg(not(feature = "egui"))]
+    let shown = terminal::debugger::run(machine, &symbols, &config, &title);
+    Ok(shown?)
+}
@@ -0,0 +1,31 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+
+use chip8_rust::machine::{LogDivergence, StateLogEntry};
+
+use super::{read_text, CliResult};
+
+#[derive(Debug, Args)]
+pub struct DiffStatesArgs {
+    /// A state log written by `run --state-log`.
+    first: PathBuf,
+    /// Another, of a run to compare with the first.
+    second: PathBuf,
+}
+
+/// Compares two state logs, failing at the first frame they differ on.
+pub fn execute(args: DiffStatesArgs) -> CliResult {
+    let read = |path: &Path| {
+        StateLogEntry::parse_log(&read_text(path)?)
+            .map_err(|error| format!("{}: {error}", path.display()))
+    };
+    let (first, second) = (read(&args.first)?, read(&args.second)?);
+    match LogDivergence::find(&first, &second) {
+        Some(divergence) => Err(format!("the runs diverge at {divergence}").into()),
+        None => {
+            println!("ok: the runs agree on all {} frames", first.len());
+            Ok(())
+        }
+    }
+}
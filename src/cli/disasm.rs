@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use chip8_rust::{
+    disassembler::{follow, sweep},
+    memory::PROGRAM_START,
+    quirks::Variant,
+};
+
+use super::{read_rom, read_symbols, CliResult};
+
+#[derive(Debug, Args)]
+pub struct DisasmArgs {
+    /// The program to disassemble.
+    rom: PathBuf,
+    /// Decodes the instructions of this machine: chip8, schip, or xochip.
+    #[arg(long, default_value = "chip8")]
+    variant: Variant,
+    /// Follows jumps, calls, and skips from the start of the program, showing bytes never reached as data, rather
+    /// than decoding every two bytes in turn.
+    #[arg(long)]
+    follow: bool,
+    /// Prints only the mnemonics, with labels for the addresses the program uses, which `chip8 asm` assembles back
+    /// into the same program.
+    #[arg(long)]
+    source: bool,
+    /// Labels addresses with the names in this symbol file, such as one Octo writes, rather than made-up ones.
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+}
+
+/// Prints the program's address, opcode, and mnemonic a line at a time.
+pub fn execute(args: DisasmArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let symbols = read_symbols(args.symbols.as_deref())?;
+    let mut lines = if args.follow {
+        follow(&rom, args.variant)
+    } else {
+        sweep(&rom, PROGRAM_START as u16, args.variant)
+    };
+    symbols.apply(&mut lines);
+    for line in lines {
+        if args.source {
+            println!("{}", line.source());
+        } else {
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use std::{fmt::Write, fs, path::PathBuf};
+
+use clap::Args;
+
+use chip8_rust::{
+    disassembler,
+    machine::{Chip8Error, DumpFormat, SaveState, StateDump},
+};
+
+use super::{headless_builder, parse_inputs, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct InspectArgs {
+    /// A savestate, or a program to start from scratch.
+    file: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Runs this many frames before looking.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    frames: u64,
+    /// Holds keys down while running, as an input script in the format `test` reads says.
+    #[arg(long, value_name = "FILE")]
+    inputs: Option<PathBuf>,
+    /// Prints the program counter, index, timers, stack, and registers.
+    #[arg(long)]
+    registers: bool,
+    /// Prints a hexdump of memory, given as START or START:LENGTH, such as 0x200:64.
+    #[arg(long, value_name = "RANGE", value_parser = parse_range)]
+    memory: Option<(u16, usize)>,
+    /// Disassembles this many instructions either side of the program counter.
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "5")]
+    disasm: Option<usize>,
+    /// Draws the screen, with `#` for lit pixels.
+    #[arg(long)]
+    screen: bool,
+    /// Prints the whole state as json or toml instead, for other programs to read.
+    #[arg(long, value_parser = parse_format, conflicts_with_all = ["registers", "memory", "disasm", "screen"])]
+    format: Option<DumpFormat>,
+}
+
+/// Loads a savestate or a program, runs it for a while if asked, and prints the parts of its state asked for. With
+/// nothing asked for, prints the registers and the code around the program counter.
+pub fn execute(args: InspectArgs) -> CliResult {
+    let state = load(&args)?;
+    if let Some(format) = args.format {
+        println!("{}", StateDump::new(&state).render(format).trim_end());
+        return Ok(());
+    }
+    let nothing_asked =
+        !args.registers && args.memory.is_none() && args.disasm.is_none() && !args.screen;
+    let mut sections = Vec::new();
+    if args.registers || nothing_asked {
+        sections.push(registers(&state));
+    }
+    if let Some((start, len)) = args.memory {
+        sections.push(hexdump(&state, start, len)?);
+    }
+    if let Some(around) = args.disasm.or(nothing_asked.then_some(5)) {
+        sections.push(disassembly(&state, around));
+    }
+    if args.screen {
+        sections.push(screen(&state));
+    }
+    print!("{}", sections.join("\n"));
+    Ok(())
+}
+
+/// Builds a machine from the savestate or program, runs the frames asked for, and saves its state.
+fn load(args: &InspectArgs) -> Result<SaveState, Box<dyn std::error::Error>> {
+    let bytes = fs::read(&args.file)
+        .map_err(|error| format!("could not read {}: {error}", args.file.display()))?;
+    let saved = match SaveState::read_header(&bytes) {
+        Ok(_) => Some(
+            SaveState::from_bytes(&bytes)
+                .map_err(|error| format!("{}: {error}", args.file.display()))?,
+        ),
+        Err(Chip8Error::State("not a savestate")) => None,
+        Err(error) => return Err(format!("{}: {error}", args.file.display()).into()),
+    };
+    let rom = saved.as_ref().map_or(&bytes, |state| &state.rom);
+    let mut config = args
+        .machine
+        .config(rom, saved.is_none().then_some(&args.file))?;
+    if let Some(state) = &saved {
+        config.machine.variant = state.variant;
+        config.machine.ram_size = Some(state.memory.len());
+    }
+    // looking at a program shouldn't touch its saves
+    let mut machine = headless_builder(&config).autosave(false).build()?;
+    match saved {
+        Some(state) => machine.load_state(state)?,
+        None => machine.load_rom(&bytes)?,
+    }
+    let inputs = match &args.inputs {
+        Some(path) => parse_inputs(&read_text(path)?)?,
+        None => Vec::new(),
+    };
+    let mut inputs = inputs.into_iter().peekable();
+    for frame in 0..args.frames {
+        while let Some((_, keys)) = inputs.next_if(|&(at, _)| at <= frame) {
+            machine.keypad_mut().set_mask(keys);
+        }
+        machine.run_frame()?;
+    }
+    Ok(machine.save_state())
+}
+
+fn registers(state: &SaveState) -> String {
+    let cpu = &state.cpu;
+    let mut text = format!(
+        "PC 0x{:03X}  I 0x{:03X}  DT {}  ST {}",
+        cpu.pc(),
+        cpu.index(),
+        state.timers.delay,
+        state.timers.sound
+    );
+    if cpu.is_waiting_for_key() {
+        text.push_str("  waiting for a key");
+    }
+    let stack: Vec<String> = cpu
+        .stack()
+        .entries()
+        .iter()
+        .map(|address| format!("0x{address:03X}"))
+        .collect();
+    let _ = writeln!(text, "\nstack [{}]", stack.join(", "));
+    for (row, values) in cpu.registers().chunks(8).enumerate() {
+        let values: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{:X} {value:02X}", row * 8 + i))
+            .collect();
+        let _ = writeln!(text, "{}", values.join("  "));
+    }
+    text
+}
+
+/// Sixteen bytes a line, each line starting with its address.
+fn hexdump(state: &SaveState, start: u16, len: usize) -> Result<String, String> {
+    let bytes = state.memory.slice(start, len).map_err(|_| {
+        format!(
+            "0x{start:03X}:{len} runs past the end of memory, at 0x{:03X}",
+            state.memory.len()
+        )
+    })?;
+    let mut text = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02X}")).collect();
+        let _ = writeln!(
+            text,
+            "0x{:03X}  {}",
+            start as usize + row * 16,
+            hex.join(" ")
+        );
+    }
+    Ok(text)
+}
+
+/// Disassembles from `around` instructions before the program counter to as many after, marking the one it's on.
+fn disassembly(state: &SaveState, around: usize) -> String {
+    let pc = state.cpu.pc() as usize;
+    let start = pc.saturating_sub(around * 2);
+    let end = (pc + (around + 1) * 2).min(state.memory.len());
+    let bytes = state
+        .memory
+        .slice(start as u16, end.saturating_sub(start))
+        .unwrap_or_default();
+    let mut text = String::new();
+    for line in disassembler::sweep(bytes, start as u16, state.variant) {
+        let marker = if line.address as usize == pc {
+            ">"
+        } else {
+            " "
+        };
+        let _ = writeln!(text, "{marker} {line}");
+    }
+    text
+}
+
+fn screen(state: &SaveState) -> String {
+    let frame = &state.frame;
+    let mut text = String::new();
+    for y in 0..frame.height() {
+        let row: String = (0..frame.width())
+            .map(|x| if frame.get_pixel(x, y) { '#' } else { '.' })
+            .collect();
+        let _ = writeln!(text, "{row}");
+    }
+    text
+}
+
+/// Parses a memory range given as `START` or `START:LENGTH`, in decimal or hex. A lone start shows 64 bytes.
+fn parse_range(range: &str) -> Result<(u16, usize), String> {
+    let number = |text: &str| match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    let (start, len) = range.split_once(':').unwrap_or((range, "64"));
+    let start = number(start).map_err(|_| format!("{start} is not an address"))?;
+    let start = u16::try_from(start).map_err(|_| format!("0x{start:X} is not an address"))?;
+    let len = number(len).map_err(|_| format!("{len} is not a length"))?;
+    Ok((start, len))
+}
+
+fn parse_format(format: &str) -> Result<DumpFormat, String> {
+    match format {
+        "json" => Ok(DumpFormat::Json),
+        "toml" => Ok(DumpFormat::Toml),
+        _ => Err("expected json or toml".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_memory_ranges() {
+        assert_eq!(parse_range("0x200:16"), Ok((0x200, 16)));
+        assert_eq!(parse_range("512"), Ok((0x200, 64)));
+        assert!(parse_range("0x10000").is_err());
+        assert!(parse_range("0x200:lots").is_err());
+    }
+}
@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use chip8_rust::{lint::lint, quirks::Variant};
+
+use super::{read_rom, CliResult};
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// The program to look through.
+    rom: PathBuf,
+    /// Decodes the instructions of this machine: chip8, schip, or xochip.
+    #[arg(long, default_value = "chip8")]
+    variant: Variant,
+}
+
+/// Prints the instructions that run differently on other interpreters, a line each with the quirk they depend on.
+pub fn execute(args: LintArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let warnings = lint(&rom, args.variant);
+    for warning in &warnings {
+        println!("{warning}");
+    }
+    match warnings.len() {
+        0 => println!("no instructions depend on quirks"),
+        1 => println!("1 instruction depends on quirks"),
+        count => println!("{count} instructions depend on quirks"),
+    }
+    Ok(())
+}
@@ -0,0 +1,307 @@
+//! The command line interface of the `chip8` binary. Each subcommand lives in its own module, with its arguments
+//! and the code that carries it out.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand};
+
+use chip8_rust::{
+    clock::ManualClock,
+    config::Config,
+    debugger::Symbols,
+    machine::{check_variant, Chip8Builder},
+    quirks::Variant,
+};
+
+mod asm;
+mod compare;
+mod dap;
+mod deadcode;
+#[cfg(any(feature = "egui", feature = "terminal"))]
+mod debug;
+mod diffstates;
+mod disasm;
+mod inspect;
+mod lint;
+mod quirkdiff;
+mod record;
+mod replay;
+mod run;
+#[cfg(feature = "scripting")]
+mod script;
+mod test;
+mod tracereport;
+
+/// What a subcommand can fail with. Everything is reported the same way, as a message on stderr.
+pub type CliResult = Result<(), Box<dyn Error>>;
+
+/// A CHIP-8 emulator.
+#[derive(Debug, Parser)]
+#[command(name = "chip8", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs a program.
+    Run(run::RunArgs),
+    /// Opens a program in the debugger.
+    #[cfg(any(feature = "egui", feature = "terminal"))]
+    Debug(debug::DebugArgs),
+    /// Runs a program for some frames and checks the screen it ends on.
+    Test(test::TestArgs),
+    /// Disassembles a program.
+    Disasm(disasm::DisasmArgs),
+    /// Assembles a program from the mnemonics `disasm` writes.
+    Asm(asm::AsmArgs),
+    /// Serves the Debug Adapter Protocol, for debugging programs from editors such as VS Code.
+    Dap(dap::DapArgs),
+    /// Reports the parts of a program that look unused, such as code nothing reaches.
+    DeadCode(deadcode::DeadCodeArgs),
+    /// Compares the state logs of two runs, reporting the first frame they differ on.
+    DiffStates(diffstates::DiffStatesArgs),
+    /// Runs a program alongside another emulator's trace log of it, stopping where the two first disagree.
+    Compare(compare::CompareArgs),
+    /// Lists the instructions of a program that run differently on other interpreters.
+    Lint(lint::LintArgs),
+    /// Runs a program with two sets of quirks side by side, stopping at the first frame they disagree on.
+    QuirkDiff(quirkdiff::QuirkDiffArgs),
+    /// Writes a short run of a program as a page of HTML, with each instruction, what it changed, and the screens
+    /// it drew.
+    TraceReport(tracereport::TraceReportArgs),
+    /// Prints parts of a savestate, or of a program's state after running it for a while.
+    Inspect(inspect::InspectArgs),
+    /// Records a run of a program as a movie.
+    Record(record::RecordArgs),
+    /// Plays a movie back, checking it still runs the same.
+    Replay(replay::ReplayArgs),
+    /// Runs a program under the control of a Rhai script.
+    #[cfg(feature = "scripting")]
+    Script(script::ScriptArgs),
+}
+
+impl Cli {
+    pub fn execute(self) -> CliResult {
+        match self.command {
+            Command::Run(args) => run::execute(args),
+            #[cfg(any(feature = "egui", feature = "terminal"))]
+            Command::Debug(args) => debug::execute(args),
+            Command::Test(args) => test::execute(args),
+            Command::Disasm(args) => disasm::execute(args),
+            Command::Asm(args) => asm::execute(args),
+            Command::Dap(args) => dap::execute(args),
+            Command::DeadCode(args) => deadcode::execute(args),
+            Command::DiffStates(args) => diffstates::execute(args),
+            Command::Compare(args) => compare::execute(args),
+            Command::Inspect(args) => inspect::execute(args),
+            Command::Lint(args) => lint::execute(args),
+            Command::QuirkDiff(args) => quirkdiff::execute(args),
+            Command::TraceReport(args) => tracereport::execute(args),
+            Command::Record(args) => record::execute(args),
+            Command::Replay(args) => replay::execute(args),
+            #[cfg(feature = "scripting")]
+            Command::Script(args) => script::execute(args),
+        }
+    }
+}
+
+/// Options for choosing the machine, shared by every subcommand that runs one. They're layered over the config
+/// file.
+#[derive(Debug, Args)]
+struct MachineArgs {
+    /// Reads settings from this file instead of the usual config file.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// The machine to emulate. Only chip8 programs can run so far; schip and xochip are refused.
+    #[arg(long, value_parser = parse_variant)]
+    variant: Option<Variant>,
+    /// Uses the quirks of another variant, such as vip, schip, or xochip, rather than the emulated one's.
+    #[arg(long, value_name = "VARIANT")]
+    quirks: Option<Variant>,
+    /// How many instructions run each frame, as Octo counts speed.
+    #[arg(long, value_name = "INSTRUCTIONS")]
+    speed: Option<u32>,
+    /// Seeds the random number generator, so runs can be repeated.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+impl MachineArgs {
+    /// The config file in use, if there is one.
+    #[cfg_attr(not(feature = "hot-reload"), allow(dead_code))]
+    fn config_path(&self) -> Option<PathBuf> {
+        self.config
+            .clone()
+            .or_else(Config::default_path)
+            .filter(|path| path.is_file())
+    }
+
+    /// Loads the config file and the program's own settings, then puts the options given on the command line over
+    /// them.
+    fn config(&self, rom: &[u8], rom_path: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+        let config = match &self.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::load()?,
+        };
+        let mut config = config.for_rom(rom, rom_path)?;
+        let machine = &mut config.machine;
+        if let Some(variant) = self.variant {
+            machine.variant = variant;
+        }
+        if let Some(quirks) = self.quirks {
+            machine.quirks = quirks.quirks().into();
+        }
+        if let Some(per_frame) = self.speed {
+            machine.instructions_per_second = (per_frame as f64 * machine.tick_rate).round() as u32;
+        }
+        if let Some(seed) = self.seed {
+            machine.seed = Some(seed);
+        }
+        Ok(config)
+    }
+}
+
+/// Starts building a machine whose frames run only when told to, for running as fast as possible.
+fn headless_builder(config: &Config) -> Chip8Builder {
+    config
+        .builder()
+        .clock(Box::new(ManualClock::new()), config.tick_rate())
+}
+
+/// Parses the variant to emulate, turning away those that can't run yet as the config file does.
+fn parse_variant(name: &str) -> Result<Variant, &'static str> {
+    let variant = name.parse()?;
+    check_variant(variant)?;
+    Ok(variant)
+}
+
+/// Reads a program from disk.
+fn read_rom(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    fs::read(path).map_err(|error| format!("could not read {}: {error}", path.display()).into())
+}
+
+fn read_text(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|error| format!("could not read {}: {error}", path.display()))
+}
+
+/// Reads a symbol file, such as one Octo writes for a program, or no symbols at all without one.
+fn read_symbols(path: Option<&Path>) -> Result<Symbols, String> {
+    let Some(path) = path else {
+        return Ok(Symbols::new());
+    };
+    Symbols::parse(&read_text(path)?).map_err(|error| format!("{}: {error}", path.display()))
+}
+
+/// Parses an input script into the frames where the held keys change, and the keys held from then on as keypad
+/// bitmasks.
+fn parse_inputs(script: &str) -> Result<Vec<(u64, u16)>, String> {
+    let mut inputs = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || {
+            format!(
+                "input script line {}: expected a frame, then keys 0 through F or -",
+                number + 1
+            )
+        };
+        let mut fields = line.split_whitespace();
+        let frame: u64 = fields
+            .next()
+            .and_then(|frame| frame.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut keys = 0;
+        for key in fields {
+            if key == "-" {
+                continue;
+            }
+            match u8::from_str_radix(key, 16) {
+                Ok(key) if key <= 0xF => keys |= 1 << key,
+                _ => return Err(invalid()),
+            }
+        }
+        inputs.push((frame, keys));
+    }
+    // later lines for the same frame win, as they would be applied last
+    inputs.sort_by_key(|&(frame, _)| frame);
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn command_line_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn options_layer_over_the_config() {
+        let cli = Cli::try_parse_from([
+            "chip8",
+            "run",
+            "game.ch8",
+            "--variant",
+            "vip",
+            "--speed",
+            "15",
+            "--quirks",
+            "schip",
+        ])
+        .expect("failed to parse arguments");
+        let Command::Run(args) = cli.command else {
+            panic!("parsed the wrong command");
+        };
+        let dir = std::env::temp_dir().join(format!("chip8-cli-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, "[machine]\nseed = 3").unwrap();
+        let machine_args = MachineArgs {
+            config: Some(path),
+            ..args.machine
+        };
+
+        let config = machine_args
+            .config(&[0x12, 0x00], Some(&args.rom))
+            .expect("failed to load config");
+        assert_eq!(config.machine.variant, Variant::Chip8);
+        assert_eq!(config.quirks(), Variant::SuperChip.quirks());
+        assert_eq!(config.machine.instructions_per_second, 900);
+        assert_eq!(config.machine.seed, Some(3));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_variants_that_cant_run() {
+        let error = Cli::try_parse_from(["chip8", "run", "game.ch8", "--variant", "schip"])
+            .expect_err("parsed a variant that can't run");
+        assert!(error
+            .to_string()
+            .contains("only CHIP-8 programs can run, not SUPER-CHIP or XO-CHIP ones"));
+        assert!(Cli::try_parse_from(["chip8", "run", "game.ch8", "--quirks", "octo"]).is_ok());
+    }
+
+    #[test]
+    fn parses_input_scripts() {
+        let script = "# hold 5, then 4 and 6\n30 5\n\n45 4 6\n10 -\n";
+        assert_eq!(
+            parse_inputs(script),
+            Ok(vec![(10, 0), (30, 1 << 5), (45, 1 << 4 | 1 << 6)])
+        );
+        assert_eq!(
+            parse_inputs("5 G"),
+            Err("input script line 1: expected a frame, then keys 0 through F or -".to_string())
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use chip8_rust::{lockstep::Lockstep, quirks::Variant};
+
+use super::{headless_builder, parse_inputs, read_rom, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct QuirkDiffArgs {
+    /// The program to run.
+    rom: PathBuf,
+    /// Runs the second machine with the quirks of this variant, such as vip, schip, or xochip. The first has the
+    /// quirks the config and `--quirks` choose.
+    #[arg(long, value_name = "VARIANT")]
+    against: Variant,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Stops after this many frames if the machines still agree.
+    #[arg(long, value_name = "N", default_value_t = 600)]
+    frames: u64,
+    /// Holds keys down on both machines, as an input script in the format `test` reads says.
+    #[arg(long, value_name = "FILE")]
+    inputs: Option<PathBuf>,
+}
+
+/// Runs the program with two sets of quirks side by side, failing at the first frame their screens or states
+/// differ at the end of.
+pub fn execute(args: QuirkDiffArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let mut config = args.machine.config(&rom, Some(&args.rom))?;
+    let inputs = match &args.inputs {
+        Some(path) => parse_inputs(&read_text(path)?)?,
+        None => Vec::new(),
+    };
+    // random numbers have to match for the quirks to be all that differs
+    config.machine.seed.get_or_insert(0);
+    let first_quirks = config.quirks();
+    // comparing runs shouldn't touch the program's saves
+    let mut first = headless_builder(&config).autosave(false).build()?;
+    config.machine.quirks = args.against.quirks().into();
+    let mut second = headless_builder(&config).autosave(false).build()?;
+    first.load_rom(&rom)?;
+    second.load_rom(&rom)?;
+    if first_quirks == args.against.quirks() {
+        eprintln!("warning: both machines have the same quirks");
+    }
+
+    let mut lockstep = Lockstep::new(first, second);
+    let mut inputs = inputs.into_iter().peekable();
+    let mut divergence = None;
+    for frame in 0..args.frames {
+        while let Some((_, keys)) = inputs.next_if(|&(at, _)| at <= frame) {
+            lockstep.set_keys(keys);
+        }
+        divergence = lockstep.run_frame()?;
+        if divergence.is_some() {
+            break;
+        }
+    }
+    for mut machine in lockstep.into_machines() {
+        machine.stop()?;
+    }
+    match divergence {
+        Some(divergence) => {
+            eprint!("{divergence}");
+            Err(format!("the quirks diverge on frame {}", divergence.frame).into())
+        }
+        None => {
+            println!("ok: the machines agreed for {} frames", args.frames);
+            Ok(())
+        }
+    }
+}
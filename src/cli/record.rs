@@ -0,0 +1,53 @@
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+
+use super::{headless_builder, parse_inputs, read_rom, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct RecordArgs {
+    /// The program to record.
+    rom: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Where to write the movie. Defaults to the program's path with a `.c8mv` extension.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// How many frames to record.
+    #[arg(long, value_name = "N")]
+    frames: u64,
+    /// Holds keys down as a script says, in the format `test` reads.
+    #[arg(long, value_name = "FILE")]
+    inputs: Option<PathBuf>,
+}
+
+/// Runs the program headlessly for the given frames, holding keys down as the input script says, and writes what
+/// happened out as a movie that `replay` can play back.
+pub fn execute(args: RecordArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let inputs = match &args.inputs {
+        Some(path) => parse_inputs(&read_text(path)?)?,
+        None => Vec::new(),
+    };
+    let mut machine = headless_builder(&config).build()?;
+    machine.load_rom(&rom)?;
+    machine.start_recording()?;
+    let mut inputs = inputs.into_iter().peekable();
+    for frame in 0..args.frames {
+        while let Some((_, keys)) = inputs.next_if(|&(at, _)| at <= frame) {
+            machine.keypad_mut().set_mask(keys);
+        }
+        machine.run_frame()?;
+    }
+    let movie = machine.stop_recording().expect("the machine was recording");
+    machine.stop()?;
+
+    let output = args
+        .output
+        .unwrap_or_else(|| args.rom.with_extension("c8mv"));
+    fs::write(&output, movie.to_bytes())
+        .map_err(|error| format!("could not write {}: {error}", output.display()))?;
+    println!("recorded {} frames to {}", movie.len(), output.display());
+    Ok(())
+}
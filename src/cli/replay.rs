@@ -0,0 +1,105 @@
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+
+use chip8_rust::machine::Movie;
+
+use super::{headless_builder, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// The movie to play back.
+    movie: PathBuf,
+    /// Reads settings from this file instead of the usual config file.
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// How many instructions run each frame. This must match the speed the movie was recorded at.
+    #[arg(long, value_name = "INSTRUCTIONS")]
+    speed: Option<u32>,
+    /// Writes the last frame to this file as a PBM image.
+    #[arg(long, value_name = "FILE")]
+    dump_frame: Option<PathBuf>,
+    /// Writes the playback to this file as an animated GIF, in the configured colours and scale.
+    #[cfg(feature = "gif")]
+    #[arg(long, value_name = "FILE")]
+    gif: Option<PathBuf>,
+}
+
+/// Plays a movie back headlessly, failing if the machine stops matching the recording.
+pub fn execute(args: ReplayArgs) -> CliResult {
+    let bytes = fs::read(&args.movie)
+        .map_err(|error| format!("could not read {}: {error}", args.movie.display()))?;
+    let movie =
+        Movie::from_bytes(&bytes).map_err(|error| format!("{}: {error}", args.movie.display()))?;
+    // the movie decides the machine, the config only how fast it runs
+    let machine_args = MachineArgs {
+        config: args.config,
+        variant: Some(movie.variant),
+        quirks: None,
+        speed: args.speed,
+        seed: None,
+    };
+    let mut config = machine_args.config(&movie.start.rom, None)?;
+    config.machine.quirks = movie.quirks.into();
+    config.machine.ram_size = Some(movie.start.memory.len());
+    let mut machine = headless_builder(&config).build()?;
+
+    #[cfg(feature = "gif")]
+    let mut gif = match &args.gif {
+        Some(path) => {
+            let file = fs::File::create(path)
+                .map_err(|error| format!("could not write {}: {error}", path.display()))?;
+            let writer = chip8_rust::gif::GifWriter::new(
+                std::io::BufWriter::new(file),
+                config.display.foreground,
+                config.display.background,
+                config.display.scale as usize,
+                config.tick_rate(),
+            )?;
+            Some(writer)
+        }
+        None => None,
+    };
+
+    let frames = movie.len();
+    machine.play_movie(movie)?;
+    let mut desync = None;
+    for _ in 0..frames {
+        machine.run_frame()?;
+        #[cfg(feature = "gif")]
+        if let Some(gif) = &mut gif {
+            gif.push(&machine.display().frame())?;
+        }
+        desync = machine.playback_desync();
+        if desync.is_some() {
+            break;
+        }
+    }
+    let frame = machine.display().frame();
+    machine.stop()?;
+
+    #[cfg(feature = "gif")]
+    if let Some(gif) = gif {
+        gif.finish()?;
+    }
+    if let Some(path) = &args.dump_frame {
+        fs::write(path, frame.to_pbm())
+            .map_err(|error| format!("could not write {}: {error}", path.display()))?;
+    }
+    if let Some(report) = desync {
+        let since = match report.last_verified {
+            Some(frame) => format!("after frame {frame}"),
+            None => "from the start".to_string(),
+        };
+        return Err(format!(
+            "playback desynced {since}, and frame {} no longer matches the recording",
+            report.frame
+        )
+        .into());
+    }
+    println!(
+        "ok: replayed {frames} frames, ending on {:016x}",
+        frame.digest()
+    );
+    Ok(())
+}
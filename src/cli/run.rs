@@ -0,0 +1,455 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, Receiver},
+    },
+    thread,
+};
+
+use clap::Args;
+#[cfg(any(
+    feature = "sdl",
+    feature = "pixels",
+    feature = "minifb",
+    feature = "terminal"
+))]
+use clap::ValueEnum;
+
+use chip8_rust::{
+    config::{Config, LiveChange},
+    display::Frame,
+    events::Event,
+    frontend::Outputs,
+    machine::{Chip8, Chip8Error, Coverage, CrashReport, Profiler, StateLog, Timeline, Trace},
+};
+
+use super::{headless_builder, read_rom, read_symbols, CliResult, MachineArgs};
+
+/// How many of the busiest addresses `--profile` prints.
+const PROFILE_HOT_SPOTS: usize = 20;
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// The program to run.
+    pub(super) rom: PathBuf,
+    #[command(flatten)]
+    pub(super) machine: MachineArgs,
+    /// Runs with no display or audio, as fast as possible rather than in real time.
+    #[arg(long)]
+    headless: bool,
+    /// Stops after this many frames.
+    #[arg(long, value_name = "N", requires = "headless")]
+    max_frames: Option<u64>,
+    /// Stops once the program jumps to itself, which programs do when they've finished.
+    #[arg(long, requires = "headless")]
+    exit_on_infinite_loop: bool,
+    /// Logs every instruction run to this file, with the registers as it was about to run. The F4 hotkey switches
+    /// the log off and on again.
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+    /// Writes where each step of the trace is in the program, by the names in this symbol file, such as one Octo
+    /// writes.
+    #[arg(long, value_name = "FILE", requires = "trace")]
+    symbols: Option<PathBuf>,
+    /// Writes what each step of the trace did after it in words, such as `# Set V0 to 0x05`.
+    #[arg(long, requires = "trace")]
+    explain: bool,
+    /// Writes a timeline of frames, sprites drawn, the tone, and halts to this file, in the Chrome trace format that
+    /// Perfetto reads.
+    #[arg(long, value_name = "FILE")]
+    timeline: Option<PathBuf>,
+    /// Puts every instruction run on the timeline too, which makes it far larger.
+    #[arg(long, requires = "timeline")]
+    timeline_instructions: bool,
+    /// Counts the instructions run at each address and in each subroutine, and prints the busiest once the machine
+    /// stops.
+    #[arg(long, requires = "headless")]
+    profile: bool,
+    /// Writes a map of which addresses were run as instructions, read as data, and written to this file once the
+    /// machine stops, a line to each run of addresses.
+    #[arg(long, value_name = "FILE", requires = "headless")]
+    coverage: Option<PathBuf>,
+    /// Writes a report to this file if the program crashes, with the registers, the call stack, the code around
+    /// where it failed, and the last instructions run. Headless runs print it on stderr too.
+    #[arg(long, value_name = "FILE")]
+    crash_report: Option<PathBuf>,
+    /// Logs a hash of the machine's state and one of its screen at the end of every frame to this file, for
+    /// finding where two runs part ways with `diff-states`.
+    #[arg(long, value_name = "FILE")]
+    state_log: Option<PathBuf>,
+    /// Writes the last frame to this file as a PBM image once the machine stops.
+    #[arg(long, value_name = "FILE")]
+    dump_frame: Option<PathBuf>,
+    /// The frontend to show the machine with: sdl, pixels, or terminal, of those built in. Defaults to the first
+    /// of them built in.
+    #[cfg(any(
+        feature = "sdl",
+        feature = "pixels",
+        feature = "minifb",
+        feature = "terminal"
+    ))]
+    #[arg(long, conflicts_with = "headless")]
+    frontend: Option<FrontendKind>,
+}
+
+/// The frontends built in, in order of preference.
+#[cfg(any(
+    feature = "sdl",
+    feature = "pixels",
+    feature = "minifb",
+    feature = "terminal"
+))]
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FrontendKind {
+    #[cfg(feature = "sdl")]
+    Sdl,
+    #[cfg(feature = "pixels")]
+    Pixels,
+    #[cfg(feature = "minifb")]
+    Minifb,
+    #[cfg(feature = "terminal")]
+    Terminal,
+}
+
+#[cfg(any(
+    feature = "sdl",
+    feature = "pixels",
+    feature = "minifb",
+    feature = "terminal"
+))]
+impl FrontendKind {
+    fn create(self) -> Box<dyn chip8_rust::frontend::Frontend> {
+        use chip8_rust::frontend;
+        match self {
+            #[cfg(feature = "sdl")]
+            FrontendKind::Sdl => Box::new(frontend::sdl::SdlFrontend::new()),
+            #[cfg(feature = "pixels")]
+            FrontendKind::Pixels => Box::new(frontend::pixels::PixelsFrontend::new()),
+            #[cfg(feature = "minifb")]
+            FrontendKind::Minifb => Box::new(frontend::minifb::MinifbFrontend::new()),
+            #[cfg(feature = "terminal")]
+            FrontendKind::Terminal => Box::new(frontend::terminal::TerminalFrontend::new()),
+        }
+    }
+}
+
+/// Runs the program until interrupted, or headlessly until a stopping condition, then shuts the machine down so it
+/// can autosave.
+pub fn execute(args: RunArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let outputs = Outputs::new();
+    let mut builder = if args.headless {
+        headless_builder(&config)
+    } else {
+        config.builder()
+    };
+    if let Some(path) = &args.trace {
+        let mut trace = Trace::to_file(path)
+            .map_err(|error| format!("could not create {}: {error}", path.display()))?;
+        if args.symbols.is_some() {
+            trace = trace.with_symbols(read_symbols(args.symbols.as_deref())?);
+        }
+        if args.explain {
+            trace = trace.with_explanations();
+        }
+        builder = builder.trace(trace);
+    }
+    let mut machine = outputs.attach(builder).build()?;
+    machine.load_rom(&rom)?;
+    if args.profile {
+        machine.start_profiling(Profiler::new());
+    }
+    if args.coverage.is_some() {
+        machine.start_coverage(Coverage::new());
+    }
+    if let Some(path) = &args.timeline {
+        let mut timeline = Timeline::to_file(path)
+            .map_err(|error| format!("could not create {}: {error}", path.display()))?;
+        if args.timeline_instructions {
+            timeline = timeline.with_instructions();
+        }
+        machine.start_timeline(timeline);
+    }
+    if let Some(path) = &args.state_log {
+        let log = StateLog::to_file(path)
+            .map_err(|error| format!("could not create {}: {error}", path.display()))?;
+        machine.start_state_log(log);
+    }
+    // a headless machine runs flat out for a moment, with no one to tune it
+    #[cfg_attr(not(feature = "hot-reload"), allow(unused_variables))]
+    let (changed, changes) = mpsc::channel();
+    #[cfg(feature = "hot-reload")]
+    if !args.headless {
+        if let Some(path) = args.machine.config_path() {
+            hot_reload::watch_config(path, config.clone(), &rom, &args.rom, changed)?;
+        }
+        hot_reload::watch_rom(&args.rom, &rom, machine.handle())?;
+    }
+    let stop_flag = machine.stop_flag();
+    ctrlc::set_handler(move || stop_flag.store(true, Ordering::Relaxed))?;
+    let result = if args.headless {
+        let result = run_headless(&mut machine, &args);
+        if let Err(Chip8Error::Cpu(error)) = result {
+            report_crash(&CrashReport::new(&machine, error), &args)?;
+        }
+        let stopped = machine.stop();
+        finish_trace(&mut machine, &args)?;
+        finish_timeline(&mut machine, &args)?;
+        finish_state_log(&mut machine, &args)?;
+        if let Some(profiler) = machine.stop_profiling() {
+            print!("{}", profiler.report(PROFILE_HOT_SPOTS));
+        }
+        if let (Some(coverage), Some(path)) = (machine.stop_coverage(), &args.coverage) {
+            fs::write(path, coverage.to_string())
+                .map_err(|error| format!("could not write {}: {error}", path.display()))?;
+        }
+        result.and(stopped).map_err(Into::into)
+    } else {
+        if let Some(path) = args.crash_report.clone() {
+            write_crash_reports(&machine, path);
+        }
+        run_windowed(machine, &outputs, &config, &args, changes)
+    };
+    if let Some(path) = &args.dump_frame {
+        dump_frame(path, &outputs.display.frame())?;
+    }
+    result
+}
+
+/// Shows the machine with the chosen frontend until it's closed, taking up the config changes that arrive on
+/// `changes`.
+#[cfg(any(
+    feature = "sdl",
+    feature = "pixels",
+    feature = "minifb",
+    feature = "terminal"
+))]
+fn run_windowed(
+    machine: Chip8,
+    outputs: &Outputs,
+    config: &Config,
+    args: &RunArgs,
+    changes: Receiver<LiveChange>,
+) -> CliResult {
+    use chip8_rust::frontend::{self, FrontendError};
+
+    let kind = args.frontend.unwrap_or(FrontendKind::value_variants()[0]);
+    let mut frontend = kind.create();
+    // a program dropped on the window gets its own settings, with the options given still over them
+    let config_for_rom = |rom: &[u8], path: &Path| {
+        args.machine
+            .config(rom, Some(path))
+            .map_err(|error| FrontendError::Host(error.to_string()))
+    };
+    Ok(frontend::run(
+        frontend.as_mut(),
+        machine,
+        outputs,
+        config,
+        &args.rom,
+        &config_for_rom,
+        &changes,
+    )?)
+}
+
+/// Runs the machine in real time until interrupted, with nothing to show it on, as no frontend was built in. Of
+/// the config changes that arrive on `changes`, only the speed has anything to apply to.
+#[cfg(not(any(
+    feature = "sdl",
+    feature = "pixels",
+    feature = "minifb",
+    feature = "terminal"
+)))]
+fn run_windowed(
+    mut machine: Chip8,
+    _outputs: &Outputs,
+    _config: &Config,
+    _args: &RunArgs,
+    changes: Receiver<LiveChange>,
+) -> CliResult {
+    let handle = machine.handle();
+    thread::spawn(move || {
+        for change in changes {
+            if let LiveChange::Speed(speed) = change {
+                if handle.set_speed(speed).is_err() {
+                    // the machine has stopped
+                    return;
+                }
+            }
+        }
+    });
+    let result = machine.run();
+    let stopped = machine.stop();
+    Ok(result.and(stopped)?)
+}
+
+/// Runs frames back to back until a stopping condition is met, reporting why it stopped on stderr.
+fn run_headless(machine: &mut Chip8, args: &RunArgs) -> Result<(), Chip8Error> {
+    let stop_flag = machine.stop_flag();
+    loop {
+        if stop_flag.load(Ordering::Relaxed) {
+            eprintln!("interrupted after {} frames", machine.frame_count());
+            return Ok(());
+        }
+        if args.max_frames == Some(machine.frame_count()) {
+            eprintln!("stopped after {} frames", machine.frame_count());
+            return Ok(());
+        }
+        if args.exit_on_infinite_loop && machine.in_infinite_loop() {
+            eprintln!(
+                "stopped at an infinite loop at 0x{:03X} after {} frames",
+                machine.cpu().pc(),
+                machine.frame_count()
+            );
+            return Ok(());
+        }
+        machine.run_frame()?;
+    }
+}
+
+/// Prints what the machine was doing when the program crashed on stderr, and writes it to the file asked for.
+fn report_crash(report: &CrashReport, args: &RunArgs) -> CliResult {
+    eprint!("{report}");
+    match &args.crash_report {
+        Some(path) => fs::write(path, report.to_string())
+            .map_err(|error| format!("could not write {}: {error}", path.display()).into()),
+        None => Ok(()),
+    }
+}
+
+/// Writes the report of any crash of a machine a frontend runs to `path`, from a thread of its own, as the frontend
+/// keeps the machine.
+fn write_crash_reports(machine: &Chip8, path: PathBuf) {
+    let events = machine.subscribe();
+    thread::spawn(move || {
+        for event in events {
+            if let Event::Crashed(report) = event {
+                if let Err(error) = fs::write(&path, report.to_string()) {
+                    eprintln!("could not write {}: {error}", path.display());
+                }
+            }
+        }
+    });
+}
+
+/// Writes out the rest of the trace log, if there is one. Windowed machines are dropped by their frontend, which
+/// writes out their logs as best it can.
+fn finish_trace(machine: &mut Chip8, args: &RunArgs) -> CliResult {
+    match (machine.stop_trace(), &args.trace) {
+        (Some(trace), Some(path)) => trace
+            .finish()
+            .map_err(|error| format!("could not write {}: {error}", path.display()).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Closes the timeline and writes out the rest of it, if there is one. Windowed machines' timelines are closed when
+/// their frontend drops them.
+fn finish_timeline(machine: &mut Chip8, args: &RunArgs) -> CliResult {
+    match (machine.stop_timeline(), &args.timeline) {
+        (Some(timeline), Some(path)) => timeline
+            .finish()
+            .map_err(|error| format!("could not write {}: {error}", path.display()).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Writes out the rest of the state log, if there is one. Windowed machines' logs are written out as best they can be
+/// when their frontend drops them.
+fn finish_state_log(machine: &mut Chip8, args: &RunArgs) -> CliResult {
+    match (machine.stop_state_log(), &args.state_log) {
+        (Some(log), Some(path)) => log
+            .finish()
+            .map_err(|error| format!("could not write {}: {error}", path.display()).into()),
+        _ => Ok(()),
+    }
+}
+
+fn dump_frame(path: &Path, frame: &Frame) -> CliResult {
+    fs::write(path, frame.to_pbm())
+        .map_err(|error| format!("could not write {}: {error}", path.display()).into())
+}
+
+#[cfg(feature = "hot-reload")]
+mod hot_reload {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::mpsc::Sender,
+        thread,
+        time::Duration,
+    };
+
+    use chip8_rust::{
+        config::{Config, ConfigWatcher, LiveChange},
+        machine::MachineHandle,
+        watch::FileWatcher,
+    };
+
+    use crate::cli::CliResult;
+
+    /// How often the files are checked for changes.
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    /// Sends on changes to the config file while the program runs, from a thread of its own, until there's nothing
+    /// left to take them up.
+    pub fn watch_config(
+        path: PathBuf,
+        config: Config,
+        rom: &[u8],
+        rom_path: &Path,
+        changed: Sender<LiveChange>,
+    ) -> CliResult {
+        let mut watcher = ConfigWatcher::new(path, config)?;
+        watcher.set_rom(rom, Some(rom_path));
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            let changes = match watcher.poll() {
+                Ok(changes) => changes,
+                Err(error) => {
+                    eprintln!("warning: {error}");
+                    continue;
+                }
+            };
+            for change in changes {
+                if changed.send(change).is_err() {
+                    // the machine has stopped
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Reloads the program from a fresh start whenever its file changes, on a thread of its own. The machine keeps
+    /// its settings, such as the quirks and speed chosen on the command line.
+    pub fn watch_rom(path: &Path, rom: &[u8], handle: MachineHandle) -> CliResult {
+        let mut watcher = FileWatcher::new(path)?;
+        let mut loaded = rom.to_vec();
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            match watcher.poll() {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(error) => {
+                    eprintln!("warning: {error}");
+                    continue;
+                }
+            }
+            // an assembler may be partway through writing it, in which case the rest of the write is another change
+            let rom = match fs::read(watcher.path()) {
+                Ok(rom) if !rom.is_empty() && rom != loaded => rom,
+                _ => continue,
+            };
+            if handle.load_rom(&rom).is_err() {
+                // the machine has stopped
+                return;
+            }
+            loaded = rom;
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use std::{path::PathBuf, sync::atomic::Ordering};
+
+use clap::Args;
+
+use chip8_rust::script::Script;
+
+use super::{headless_builder, read_rom, read_symbols, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct ScriptArgs {
+    /// The program to run.
+    rom: PathBuf,
+    /// The Rhai script to drive it with.
+    script: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Stops after this many frames, if the script hasn't stopped first.
+    #[arg(long, value_name = "N")]
+    max_frames: Option<u64>,
+    /// Names addresses from this symbol file, such as one Octo writes, for the script to set breakpoints by.
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+}
+
+/// Runs the program headlessly, as fast as possible, under the script's control until the script stops it.
+pub fn execute(args: ScriptArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let source = read_text(&args.script)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let symbols = read_symbols(args.symbols.as_deref())?;
+    let mut machine = headless_builder(&config).build()?;
+    machine.load_rom(&rom)?;
+    let stop_flag = machine.stop_flag();
+    ctrlc::set_handler(move || stop_flag.store(true, Ordering::Relaxed))?;
+    let name = args.script.display();
+    let mut script = Script::new(&source, machine).map_err(|error| format!("{name}: {error}"))?;
+    script.load_symbols(&symbols);
+    let mut result = script.start();
+    while result.is_ok() && !script.is_stopped() && args.max_frames != Some(script.frame_count()) {
+        result = script.run_frame();
+    }
+    let frames = script.frame_count();
+    let stopped = script.into_machine().stop();
+    result.map_err(|error| format!("{name}: {error}"))?;
+    stopped?;
+    eprintln!("stopped after {frames} frames");
+    Ok(())
+}
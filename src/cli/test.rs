@@ -0,0 +1,96 @@
+use std::{fs, path::PathBuf};
+
+use clap::{ArgGroup, Args};
+
+use chip8_rust::display::Frame;
+
+use super::{headless_builder, parse_inputs, read_rom, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("expected").required(true).args(["expect_hash", "expect_frame"])))]
+pub struct TestArgs {
+    /// The program to test.
+    rom: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// How many frames to run before checking the screen.
+    #[arg(long, value_name = "N")]
+    frames: u64,
+    /// Holds keys down as a script says. Each line gives a frame and the keys held from then on, in hex, or `-` for
+    /// none, such as `30 5 A`. Lines starting with `#` are comments.
+    #[arg(long, value_name = "FILE")]
+    inputs: Option<PathBuf>,
+    /// The expected hash of the last frame, as this command prints it.
+    #[arg(long, value_name = "HASH")]
+    expect_hash: Option<String>,
+    /// A PBM image of the expected last frame.
+    #[arg(long, value_name = "FILE")]
+    expect_frame: Option<PathBuf>,
+    /// Writes the last frame to the --expect-frame image instead of checking it.
+    #[arg(long, requires = "expect_frame")]
+    update: bool,
+}
+
+/// Runs the program headlessly for the given frames, then checks the screen it ends on, failing if it's wrong.
+pub fn execute(args: TestArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let inputs = match &args.inputs {
+        Some(path) => parse_inputs(&read_text(path)?)?,
+        None => Vec::new(),
+    };
+    let mut machine = headless_builder(&config).build()?;
+    machine.load_rom(&rom)?;
+    let mut inputs = inputs.into_iter().peekable();
+    for frame in 0..args.frames {
+        while let Some((_, keys)) = inputs.next_if(|&(at, _)| at <= frame) {
+            machine.keypad_mut().set_mask(keys);
+        }
+        machine.run_frame()?;
+    }
+    let frame = machine.display().frame();
+    machine.stop()?;
+
+    let digest = format!("{:016x}", frame.digest());
+    if let Some(expected) = &args.expect_hash {
+        if !expected.eq_ignore_ascii_case(&digest) {
+            return Err(format!(
+                "frame {} ended on {digest}, expected {expected}",
+                args.frames
+            )
+            .into());
+        }
+    }
+    if let Some(path) = &args.expect_frame {
+        if args.update {
+            fs::write(path, frame.to_pbm())
+                .map_err(|error| format!("could not write {}: {error}", path.display()))?;
+            println!(
+                "updated {}: frame {} ended on {digest}",
+                path.display(),
+                args.frames
+            );
+            return Ok(());
+        }
+        let bytes = fs::read(path)
+            .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+        let expected =
+            Frame::from_pbm(&bytes).map_err(|error| format!("{}: {error}", path.display()))?;
+        if expected != *frame {
+            let wrong = expected
+                .pixels()
+                .iter()
+                .zip(frame.pixels())
+                .filter(|(expected, actual)| expected != actual)
+                .count();
+            return Err(format!(
+                "frame {} ended on {digest}, which differs from {} in {wrong} pixels",
+                args.frames,
+                path.display()
+            )
+            .into());
+        }
+    }
+    println!("ok: frame {} ended on {digest}", args.frames);
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::{fs, path::PathBuf};
+
+use clap::Args;
+
+use chip8_rust::{
+    debugger::Debugger,
+    tracereport::{Bound, TraceReport},
+};
+
+use super::{headless_builder, parse_inputs, read_rom, read_text, CliResult, MachineArgs};
+
+#[derive(Debug, Args)]
+pub struct TraceReportArgs {
+    /// The program to report a run of.
+    rom: PathBuf,
+    /// Where to write the report.
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+    /// Reports this many instructions.
+    #[arg(long, value_name = "N", conflicts_with = "frames")]
+    instructions: Option<u64>,
+    /// Reports this many frames' worth of instructions. One frame is reported if neither this nor
+    /// `--instructions` is given.
+    #[arg(long, value_name = "N")]
+    frames: Option<u64>,
+    /// Runs this many frames before the report starts, to get past a title screen or to a part worth showing.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    from_frame: u64,
+    /// Holds keys down, as an input script in the format `test` reads says, counting frames from the start of the
+    /// run rather than the report.
+    #[arg(long, value_name = "FILE")]
+    inputs: Option<PathBuf>,
+    #[command(flatten)]
+    machine: MachineArgs,
+}
+
+/// Runs the program up to the frame the report starts at, then writes the instructions it runs from there as a page
+/// of HTML.
+pub fn execute(args: TraceReportArgs) -> CliResult {
+    let rom = read_rom(&args.rom)?;
+    let config = args.machine.config(&rom, Some(&args.rom))?;
+    let inputs = match &args.inputs {
+        Some(path) => parse_inputs(&read_text(path)?)?,
+        None => Vec::new(),
+    };
+    // reporting a run shouldn't touch its saves
+    let mut machine = headless_builder(&config).autosave(false).build()?;
+    machine.load_rom(&rom)?;
+    let mut leading = inputs.iter().peekable();
+    for frame in 0..args.from_frame {
+        while let Some(&(_, keys)) = leading.next_if(|&&(at, _)| at <= frame) {
+            machine.keypad_mut().set_mask(keys);
+        }
+        machine.run_frame()?;
+    }
+
+    let bound = match (args.instructions, args.frames) {
+        (Some(instructions), _) => Bound::Instructions(instructions),
+        (None, frames) => Bound::Frames(frames.unwrap_or(1)),
+    };
+    let title = args.rom.file_name().map_or_else(
+        || args.rom.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let mut debugger = Debugger::new(machine);
+    let report = TraceReport::record(&mut debugger, title, bound, &inputs)
+        .with_colours(config.display.foreground, config.display.background);
+    debugger.into_machine().stop()?;
+    fs::write(&args.output, report.to_html())
+        .map_err(|error| format!("could not write {}: {error}", args.output.display()))?;
+    println!(
+        "wrote {} instructions to {}; {}",
+        report.steps.len(),
+        args.output.display(),
+        report.ending
+    );
+    Ok(())
+}
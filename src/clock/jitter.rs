@@ -0,0 +1,99 @@
+use std::{fmt, time::Duration};
+
+/// The upper bounds of the jitter histogram's buckets. Anything later than the last lands in an overflow bucket.
+const BUCKET_BOUNDS: [Duration; 7] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+];
+
+/// A histogram of how late a clock's ticks were delivered after their deadlines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitterReport {
+    counts: [u64; BUCKET_BOUNDS.len() + 1],
+}
+
+impl JitterReport {
+    pub(super) fn record(&mut self, lateness: Duration) {
+        let bucket = BUCKET_BOUNDS
+            .iter()
+            .position(|&bound| lateness < bound)
+            .unwrap_or(BUCKET_BOUNDS.len());
+        self.counts[bucket] += 1;
+    }
+
+    /// The number of ticks recorded.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Iterates over the buckets as their upper bound and count, from least to most late. The last bucket has no
+    /// upper bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        BUCKET_BOUNDS
+            .iter()
+            .map(|&bound| Some(bound))
+            .chain([None])
+            .zip(self.counts.iter().copied())
+    }
+
+    /// Gets the bound under which at least `percentile` percent of ticks were delivered.
+    ///
+    /// Returns `None` if that many ticks were later than the largest bucket bound, or if nothing was recorded.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let needed = (total as f64 * percentile / 100.0).ceil() as u64;
+        let mut seen = 0;
+        for (bound, count) in self.buckets() {
+            seen += count;
+            if seen >= needed {
+                return bound;
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for JitterReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().max(1);
+        for (bound, count) in self.buckets() {
+            let label = match bound {
+                Some(bound) => format!("< {bound:?}"),
+                None => format!(">= {:?}", BUCKET_BOUNDS[BUCKET_BOUNDS.len() - 1]),
+            };
+            writeln!(
+                f,
+                "{label:>10}: {count:>8} ({:>5.1}%)",
+                count as f64 * 100.0 / total as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let mut report = JitterReport::default();
+        for _ in 0..99 {
+            report.record(Duration::from_micros(50));
+        }
+        report.record(Duration::from_millis(3));
+        assert_eq!(report.total(), 100);
+        assert_eq!(report.percentile(50.0), Some(Duration::from_micros(100)));
+        assert_eq!(report.percentile(100.0), Some(Duration::from_millis(5)));
+        report.record(Duration::from_secs(1));
+        assert_eq!(report.percentile(100.0), None);
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{RecvError, RecvTimeoutError, TryRecvError},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use super::Tick;
+
+/// How many ticks a listener can fall behind by default before back-pressure kicks in.
+pub const DEFAULT_CAPACITY: usize = 60;
+/// How often a blocked clock checks whether it has been stopped.
+const BLOCK_POLL: Duration = Duration::from_millis(10);
+
+/// What a clock does when a listener's queue of ticks is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Waits for the listener to make room, holding up the clock and every other listener.
+    Block,
+    /// Discards the oldest queued tick to make room for the new one.
+    DropOldest,
+    /// Folds the new tick into the newest queued one, which then stands for both.
+    Coalesce,
+}
+
+struct State {
+    ticks: VecDeque<Tick>,
+    senders: usize,
+    receiver_alive: bool,
+    /// How many ticks have been sent, and how many of those the receiver has acknowledged.
+    sent: u64,
+    acknowledged: u64,
+    /// The task waiting on `poll_recv()`, if any.
+    waker: Option<Waker>,
+}
+
+impl State {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// Creates a bounded tick channel for the named listener with the given back-pressure policy.
+pub(super) fn channel(
+    name: &str,
+    capacity: usize,
+    policy: Backpressure,
+) -> (TickSender, TickReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            ticks: VecDeque::with_capacity(capacity),
+            senders: 1,
+            receiver_alive: true,
+            sent: 0,
+            acknowledged: 0,
+            waker: None,
+        }),
+        changed: Condvar::new(),
+    });
+    let sender = TickSender {
+        name: name.to_string(),
+        shared: Arc::clone(&shared),
+        capacity: capacity.max(1),
+        policy,
+    };
+    (sender, TickReceiver { shared })
+}
+
+/// The clock's end of a listener's channel.
+pub(super) struct TickSender {
+    pub(super) name: String,
+    shared: Arc<Shared>,
+    capacity: usize,
+    policy: Backpressure,
+}
+
+impl TickSender {
+    /// Queues a tick, applying the back-pressure policy if the queue is full.
+    ///
+    /// A blocking send gives up if `stop_flag` is raised. Returns false if the receiver has been dropped.
+    pub(super) fn send(&self, tick: Tick, stop_flag: &AtomicBool) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.receiver_alive {
+            return false;
+        }
+        if state.ticks.len() >= self.capacity {
+            match self.policy {
+                Backpressure::Block => {
+                    while state.ticks.len() >= self.capacity
+                        && state.receiver_alive
+                        && !stop_flag.load(Ordering::Relaxed)
+                    {
+                        state = self
+                            .shared
+                            .changed
+                            .wait_timeout(state, BLOCK_POLL)
+                            .unwrap()
+                            .0;
+                    }
+                    if state.ticks.len() >= self.capacity {
+                        return state.receiver_alive;
+                    }
+                }
+                Backpressure::DropOldest => {
+                    state.ticks.pop_front();
+                }
+                Backpressure::Coalesce => {
+                    let newest = state.ticks.pop_back().unwrap();
+                    state.ticks.push_back(newest.coalesce(tick));
+                    state.wake();
+                    self.shared.changed.notify_all();
+                    return true;
+                }
+            }
+        }
+        state.ticks.push_back(tick);
+        state.sent += 1;
+        state.wake();
+        self.shared.changed.notify_all();
+        true
+    }
+}
+
+impl TickSender {
+    /// Checks whether the receiver has acknowledged every tick sent so far, or has gone away.
+    pub(super) fn is_caught_up(&self) -> bool {
+        let state = self.shared.state.lock().unwrap();
+        state.acknowledged >= state.sent || !state.receiver_alive
+    }
+
+    /// Waits for the receiver to acknowledge every tick sent so far, giving up if `stop_flag` is raised.
+    pub(super) fn wait_for_ack(&self, stop_flag: &AtomicBool) {
+        let mut state = self.shared.state.lock().unwrap();
+        while state.acknowledged < state.sent
+            && state.receiver_alive
+            && !stop_flag.load(Ordering::Relaxed)
+        {
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, BLOCK_POLL)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+impl Clone for TickSender {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        TickSender {
+            name: self.name.clone(),
+            shared: Arc::clone(&self.shared),
+            capacity: self.capacity,
+            policy: self.policy,
+        }
+    }
+}
+
+impl Drop for TickSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        state.wake();
+        self.shared.changed.notify_all();
+    }
+}
+
+/// A listener's end of a clock, receiving ticks in order.
+///
+/// This mirrors the receiving half of `std::sync::mpsc`, and reports disconnection once the clock has been torn
+/// down and every queued tick has been received.
+pub struct TickReceiver {
+    shared: Arc<Shared>,
+}
+
+impl TickReceiver {
+    /// Waits for the next tick.
+    pub fn recv(&self) -> Result<Tick, RecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(tick) = state.ticks.pop_front() {
+                self.shared.changed.notify_all();
+                return Ok(tick);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self.shared.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Takes the next tick if one is queued.
+    pub fn try_recv(&self) -> Result<Tick, TryRecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.ticks.pop_front() {
+            Some(tick) => {
+                self.shared.changed.notify_all();
+                Ok(tick)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Waits up to `timeout` for the next tick.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Tick, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(tick) = state.ticks.pop_front() {
+                self.shared.changed.notify_all();
+                return Ok(tick);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Acknowledges that every tick received so far has been fully handled.
+    ///
+    /// This only matters for a clock in lockstep mode, which waits for every listener to acknowledge the previous
+    /// tick before delivering the next.
+    pub fn ack(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.acknowledged = state.sent - state.ticks.len() as u64;
+        self.shared.changed.notify_all();
+    }
+
+    /// Polls for the next tick from an async task.
+    ///
+    /// Returns `Poll::Ready(None)` once the clock is torn down. Only the most recent task to poll is woken.
+    pub fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<Option<Tick>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(tick) = state.ticks.pop_front() {
+            self.shared.changed.notify_all();
+            Poll::Ready(Some(tick))
+        } else if state.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Waits for the next tick from an async task, giving `None` once the clock is torn down.
+    pub async fn recv_async(&self) -> Option<Tick> {
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Iterates over ticks, waiting for each one, until the clock is torn down.
+    pub fn iter(&self) -> impl Iterator<Item = Tick> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Iterates over the ticks that are already queued, without waiting.
+    pub fn try_iter(&self) -> impl Iterator<Item = Tick> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+}
+
+impl Drop for TickReceiver {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiver_alive = false;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(number: u64) -> Tick {
+        Tick { number, elapsed: 1 }
+    }
+
+    fn send_five(policy: Backpressure) -> Vec<u64> {
+        let stop_flag = AtomicBool::new(false);
+        let (tx, rx) = channel("test", 3, policy);
+        for number in 0..5 {
+            assert!(tx.send(tick(number), &stop_flag));
+        }
+        rx.try_iter().map(|tick| tick.number).collect()
+    }
+
+    #[test]
+    fn drop_oldest() {
+        assert_eq!(send_five(Backpressure::DropOldest), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn coalesce() {
+        assert_eq!(send_five(Backpressure::Coalesce), vec![0, 1, 4]);
+        let stop_flag = AtomicBool::new(false);
+        let (tx, rx) = channel("test", 1, Backpressure::Coalesce);
+        for number in 0..5 {
+            tx.send(tick(number), &stop_flag);
+        }
+        assert_eq!(
+            rx.try_recv(),
+            Ok(Tick {
+                number: 4,
+                elapsed: 5
+            }),
+            "coalesced tick lost its elapsed count"
+        );
+    }
+
+    #[test]
+    fn block_gives_up_when_stopped() {
+        let stop_flag = AtomicBool::new(true);
+        let (tx, rx) = channel("test", 1, Backpressure::Block);
+        assert!(tx.send(tick(0), &stop_flag));
+        assert!(tx.send(tick(1), &stop_flag));
+        assert_eq!(
+            rx.try_iter().count(),
+            1,
+            "blocked send overfilled the queue"
+        );
+    }
+
+    #[test]
+    fn disconnects_after_senders_drop() {
+        let stop_flag = AtomicBool::new(false);
+        let (tx, rx) = channel("test", 3, Backpressure::Block);
+        tx.send(tick(0), &stop_flag);
+        drop(tx);
+        assert_eq!(rx.recv(), Ok(tick(0)), "queued tick lost on disconnect");
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn poll_recv() {
+        let stop_flag = AtomicBool::new(false);
+        let (tx, rx) = channel("test", 3, Backpressure::Block);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        tx.send(tick(0), &stop_flag);
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(tick(0))));
+        drop(tx);
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn ack() {
+        let stop_flag = AtomicBool::new(false);
+        let (tx, rx) = channel("test", 3, Backpressure::Block);
+        assert!(tx.is_caught_up());
+        tx.send(tick(0), &stop_flag);
+        tx.send(tick(1), &stop_flag);
+        rx.recv().unwrap();
+        rx.ack();
+        assert!(!tx.is_caught_up(), "ack covered a tick not yet received");
+        rx.recv().unwrap();
+        rx.ack();
+        assert!(tx.is_caught_up());
+    }
+
+    #[test]
+    fn send_fails_without_receiver() {
+        let (tx, rx) = channel("test", 3, Backpressure::Block);
+        drop(rx);
+        assert!(!tx.send(tick(0), &AtomicBool::new(false)));
+    }
+}
@@ -0,0 +1,961 @@
+use std::{
+    hint, mem,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::speed::Speed;
+
+mod jitter;
+mod listener;
+mod rate;
+mod source;
+#[cfg(feature = "async")]
+mod stream;
+mod vsync;
+
+pub use jitter::JitterReport;
+use listener::TickSender;
+pub use listener::{Backpressure, TickReceiver, DEFAULT_CAPACITY};
+pub use rate::TickRate;
+pub use source::ClockSource;
+#[cfg(feature = "async")]
+pub use stream::TickStream;
+pub use vsync::VsyncClock;
+
+/// How long before a deadline the clock thread stops sleeping and spins by default, as sleeps can overshoot.
+///
+/// Windows sleeps are much coarser than other hosts', so it spins for longer.
+const DEFAULT_SPIN_WINDOW: Duration = if cfg!(windows) {
+    Duration::from_millis(2)
+} else {
+    Duration::from_micros(500)
+};
+/// How far the clock thread can fall behind before it gives up on catching up. A tick never stands for more than
+/// this much time; the rest is skipped.
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+/// How the clock thread waits for each tick's deadline, trading CPU use for precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    /// Sleeps right up to the deadline. Cheapest, but only as precise as the host's sleep.
+    Sleep,
+    /// Sleeps until `spin` before the deadline, then spins the rest of the way.
+    Hybrid { spin: Duration },
+    /// Spins the whole time. Most precise, but keeps a core busy.
+    Spin,
+}
+
+impl Precision {
+    /// Encodes the precision as the spin window before each deadline, in nanoseconds.
+    fn spin_window(self) -> u64 {
+        match self {
+            Precision::Sleep => 0,
+            Precision::Hybrid { spin } => spin.as_nanos() as u64,
+            Precision::Spin => u64::MAX,
+        }
+    }
+    fn from_spin_window(nanos: u64) -> Precision {
+        match nanos {
+            0 => Precision::Sleep,
+            u64::MAX => Precision::Spin,
+            nanos => Precision::Hybrid {
+                spin: Duration::from_nanos(nanos),
+            },
+        }
+    }
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision::Hybrid {
+            spin: DEFAULT_SPIN_WINDOW,
+        }
+    }
+}
+
+/// A single tick of a clock, as delivered to listeners and handlers.
+///
+/// When a clock or listener falls behind, several ticks are coalesced into one carrying the number that elapsed,
+/// rather than being delivered as a burst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tick {
+    /// The number of this tick, counting up from zero for the first tick of the clock. A coalesced tick has the
+    /// number of the last tick it covers.
+    pub number: u64,
+    /// How many ticks this tick stands for, always at least one.
+    pub elapsed: u64,
+}
+
+impl Tick {
+    /// Folds a later tick into this one, so the result covers both.
+    pub fn coalesce(self, later: Tick) -> Tick {
+        Tick {
+            number: later.number,
+            elapsed: self.elapsed + later.elapsed,
+        }
+    }
+}
+
+/// Timing statistics collected by a clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClockStats {
+    /// The number of ticks delivered so far.
+    pub ticks: u64,
+    /// The average time between ticks.
+    pub average_interval: Duration,
+    /// The latest a tick has been delivered after its deadline.
+    pub worst_lateness: Duration,
+}
+
+/// Keeps the tick count and statistics for a clock.
+#[derive(Default)]
+struct StatsRecorder {
+    stats: ClockStats,
+    jitter: JitterReport,
+    first_tick: Option<Instant>,
+}
+
+impl StatsRecorder {
+    /// Records `elapsed` ticks delivered together at `now`, `lateness` after the last one's deadline, and returns
+    /// the tick that covers them.
+    fn record(&mut self, now: Instant, lateness: Duration, elapsed: u64) -> Tick {
+        self.stats.ticks += elapsed;
+        let tick = Tick {
+            number: self.stats.ticks - 1,
+            elapsed,
+        };
+        self.stats.worst_lateness = self.stats.worst_lateness.max(lateness);
+        self.jitter.record(lateness);
+        match self.first_tick {
+            Some(first) => {
+                let nanos = (now - first).as_nanos() / (self.stats.ticks - 1) as u128;
+                self.stats.average_interval = Duration::from_nanos(nanos as u64);
+            }
+            None => self.first_tick = Some(now),
+        }
+        tick
+    }
+}
+
+/// A callback run on the clock thread every tick.
+pub type TickHandler = Box<dyn FnMut(Tick) + Send>;
+
+/// A listener or handler attached to a clock that is already running.
+enum Registration {
+    Listener(TickSender),
+    Handler(TickHandler),
+}
+
+/// A clock that can be used to update listeners on a regular interval.
+///
+/// The clock can either run on its own thread with `start()`, or be driven synchronously by the host calling
+/// `tick()`, which never spawns a thread. The latter is for targets without threads, such as WASM.
+pub struct Clock {
+    stop_flag: Arc<AtomicBool>,
+    pause_flag: Arc<AtomicBool>,
+    lockstep_flag: Arc<AtomicBool>,
+    /// Set if the clock thread panicked, such as in a handler.
+    poisoned: Arc<AtomicBool>,
+    timer_handle: Option<JoinHandle<()>>,
+    interval: Duration,
+    speed: Speed,
+    /// The interval after scaling by the speed, shared with the clock thread.
+    scaled_interval: Arc<AtomicU64>,
+    /// The precision, encoded as a spin window and shared with the clock thread.
+    spin_window: Arc<AtomicU64>,
+    listeners: Vec<TickSender>,
+    handlers: Vec<TickHandler>,
+    stats: Arc<Mutex<StatsRecorder>>,
+    /// The names of listeners whose receivers were dropped while the clock was still ticking.
+    disconnected: Arc<Mutex<Vec<String>>>,
+    registration_tx: Sender<Registration>,
+    registration_rx: Option<Receiver<Registration>>,
+}
+
+impl Clock {
+    pub fn new(interval: Duration) -> Self {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let pause_flag = Arc::new(AtomicBool::new(false));
+        let lockstep_flag = Arc::new(AtomicBool::new(false));
+        let poisoned = Arc::new(AtomicBool::new(false));
+        let timer_handle = None;
+        let speed = Speed::NORMAL;
+        let scaled_interval = Arc::new(AtomicU64::new(interval.as_nanos() as u64));
+        let spin_window = Arc::new(AtomicU64::new(Precision::default().spin_window()));
+        let listeners = Vec::new();
+        let handlers = Vec::new();
+        let stats = Arc::new(Mutex::new(StatsRecorder::default()));
+        let disconnected = Arc::new(Mutex::new(Vec::new()));
+        let (registration_tx, registration_rx) = mpsc::channel();
+        Clock {
+            stop_flag,
+            pause_flag,
+            lockstep_flag,
+            poisoned,
+            timer_handle,
+            interval,
+            speed,
+            scaled_interval,
+            spin_window,
+            listeners,
+            handlers,
+            stats,
+            disconnected,
+            registration_tx,
+            registration_rx: Some(registration_rx),
+        }
+    }
+    /// Creates a clock ticking at the given rate.
+    pub fn with_rate(rate: TickRate) -> Self {
+        Clock::new(rate.interval())
+    }
+    /// Starts the clock.
+    ///
+    /// This function starts a thread that will update any attached listeners and handlers on the specified interval.
+    /// How the thread waits between ticks is set by `set_precision()`; by default it sleeps, waking slightly early to
+    /// spin up to each deadline precisely.
+    pub fn start(&mut self) {
+        let stop_flag = Arc::clone(&self.stop_flag);
+        let pause_flag = Arc::clone(&self.pause_flag);
+        let lockstep_flag = Arc::clone(&self.lockstep_flag);
+        let interval = Arc::clone(&self.scaled_interval);
+        let spin_window = Arc::clone(&self.spin_window);
+        let mut listeners = self.listeners.clone();
+        let mut handlers = mem::take(&mut self.handlers);
+        let registrations = self.registration_rx.take();
+        let stats = Arc::clone(&self.stats);
+        let disconnected = Arc::clone(&self.disconnected);
+        let poisoned = Arc::clone(&self.poisoned);
+        self.timer_handle = Some(thread::spawn(move || {
+            let _poison = PoisonOnPanic(poisoned);
+            // ticks are scheduled off the previous deadline rather than the time we woke up, so late wakeups
+            // don't add up to drift
+            let mut last_tick = Instant::now();
+            let mut last_interval = interval.load(Ordering::Relaxed);
+            while !stop_flag.load(Ordering::Relaxed) {
+                for registration in registrations.iter().flat_map(|rx| rx.try_iter()) {
+                    match registration {
+                        Registration::Listener(tx) => listeners.push(tx),
+                        Registration::Handler(handler) => handlers.push(handler),
+                    }
+                }
+                if pause_flag.load(Ordering::Relaxed) {
+                    thread::park();
+                    // restart the timeline so resuming doesn't fire catch-up ticks
+                    last_tick = Instant::now();
+                    continue;
+                }
+                let nanos = interval.load(Ordering::Relaxed);
+                if nanos != last_interval {
+                    // a new interval or speed starts a new timeline, rather than being measured against the old one
+                    last_interval = nanos;
+                    last_tick = Instant::now();
+                }
+                let interval = Duration::from_nanos(nanos);
+                let deadline = last_tick + interval;
+                let now = Instant::now();
+                if now < deadline {
+                    let remaining = deadline - now;
+                    let spin_window = Duration::from_nanos(spin_window.load(Ordering::Relaxed));
+                    if remaining > spin_window {
+                        thread::park_timeout(remaining - spin_window);
+                    } else {
+                        hint::spin_loop();
+                    }
+                    continue;
+                }
+                if lockstep_flag.load(Ordering::Relaxed) {
+                    for listener in &listeners {
+                        listener.wait_for_ack(&stop_flag);
+                    }
+                }
+                let now = Instant::now();
+                let behind = now - deadline;
+                // after a stall, deliver the missed ticks as one rather than in a burst, up to `MAX_CATCH_UP` of them
+                let (elapsed, lateness) = if interval.is_zero() {
+                    // unlimited ticks have no timeline to keep to
+                    last_tick = now;
+                    (1, Duration::ZERO)
+                } else if behind > MAX_CATCH_UP {
+                    last_tick = now;
+                    let elapsed = 1 + (MAX_CATCH_UP.as_nanos() / interval.as_nanos()) as u64;
+                    (elapsed, behind)
+                } else {
+                    let missed =
+                        u32::try_from(behind.as_nanos() / interval.as_nanos()).unwrap_or(u32::MAX);
+                    let last_deadline = deadline + interval.saturating_mul(missed);
+                    last_tick = last_deadline;
+                    (1 + missed as u64, now - last_deadline)
+                };
+                let tick = stats.lock().unwrap().record(now, lateness, elapsed);
+                deliver(
+                    tick,
+                    &mut handlers,
+                    &mut listeners,
+                    &stop_flag,
+                    &disconnected,
+                );
+            }
+        }));
+    }
+
+    /// Wakes the clock thread so it notices changes to its settings.
+    fn wake(&self) {
+        if let Some(handle) = &self.timer_handle {
+            handle.thread().unpark();
+        }
+    }
+
+    /// Ticks the clock once on the calling thread.
+    ///
+    /// This runs every handler and updates every listener immediately, without regard for the interval. It is an
+    /// error to tick a clock whose thread has been started, as the handlers now belong to that thread. In lockstep
+    /// mode, it is also an error to tick before every listener has acknowledged the previous tick.
+    pub fn tick(&mut self) -> Result<(), &'static str> {
+        self.tick_by(1)
+    }
+
+    /// Ticks the clock once on the calling thread, with the tick standing for `elapsed` ticks.
+    ///
+    /// This is for hosts that keep their own time and may find several ticks are due at once.
+    pub fn tick_by(&mut self, elapsed: u64) -> Result<(), &'static str> {
+        if self.timer_handle.is_some() {
+            Err("cannot tick manually after clock has started")
+        } else if self.stop_flag.load(Ordering::Relaxed) {
+            Err("clock has been terminated")
+        } else if self.is_paused() || elapsed == 0 {
+            Ok(())
+        } else if self.is_lockstep() && !self.listeners.iter().all(TickSender::is_caught_up) {
+            Err("listener has not acknowledged the previous tick")
+        } else {
+            let tick = self
+                .stats
+                .lock()
+                .unwrap()
+                .record(Instant::now(), Duration::ZERO, elapsed);
+            deliver(
+                tick,
+                &mut self.handlers,
+                &mut self.listeners,
+                &self.stop_flag,
+                &self.disconnected,
+            );
+            Ok(())
+        }
+    }
+
+    /// Gets the timing statistics of the clock, including the number of ticks delivered so far.
+    pub fn stats(&self) -> ClockStats {
+        self.stats.lock().unwrap().stats
+    }
+
+    /// Gets a histogram of how late each tick was delivered, for diagnosing stutter. Coalesced ticks count once.
+    ///
+    /// Ticks fired manually with `tick()` are never late.
+    pub fn jitter_report(&self) -> JitterReport {
+        self.stats.lock().unwrap().jitter
+    }
+
+    /// Sets the rate of ticks at normal speed. This is `set_interval()` for rates given in hertz.
+    pub fn set_rate(&mut self, rate: TickRate) {
+        self.set_interval(rate.interval());
+    }
+
+    /// Gets the interval between ticks at normal speed.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Sets the interval between ticks at normal speed.
+    ///
+    /// This can be done while the clock is running, and takes effect from the next tick onward.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+        self.update_scaled_interval();
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Sets the speed of the clock, scaling the interval between ticks.
+    ///
+    /// Like `set_interval()`, this can be done while the clock is running.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        self.update_scaled_interval();
+    }
+
+    fn update_scaled_interval(&self) {
+        let scaled = self.speed.scale_interval(self.interval);
+        self.scaled_interval
+            .store(scaled.as_nanos() as u64, Ordering::Relaxed);
+        self.wake();
+    }
+
+    pub fn precision(&self) -> Precision {
+        Precision::from_spin_window(self.spin_window.load(Ordering::Relaxed))
+    }
+
+    /// Sets how the clock thread waits for each tick. This can be done while the clock is running.
+    pub fn set_precision(&self, precision: Precision) {
+        self.spin_window
+            .store(precision.spin_window(), Ordering::Relaxed);
+        self.wake();
+    }
+
+    /// Pauses the clock.
+    ///
+    /// No ticks are delivered while paused, but the thread, handlers, and listeners are all kept, so the clock
+    /// can pick up where it left off with `resume()`.
+    pub fn pause(&self) {
+        self.pause_flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes a paused clock. The next tick arrives one full interval later.
+    pub fn resume(&self) {
+        self.pause_flag.store(false, Ordering::Relaxed);
+        self.wake();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_flag.load(Ordering::Relaxed)
+    }
+
+    /// Sets whether the clock runs in lockstep with its listeners.
+    ///
+    /// In lockstep, the clock waits for every listener to `ack()` the previous tick before delivering the next,
+    /// so subsystems on different threads never drift apart by a tick. The interval becomes a minimum time
+    /// between ticks rather than an exact one.
+    pub fn set_lockstep(&self, lockstep: bool) {
+        self.lockstep_flag.store(lockstep, Ordering::Relaxed);
+        self.wake();
+    }
+
+    pub fn is_lockstep(&self) -> bool {
+        self.lockstep_flag.load(Ordering::Relaxed)
+    }
+
+    /// Whether the clock thread has panicked, such as in a handler.
+    ///
+    /// A poisoned clock no longer ticks, and refuses new listeners and handlers.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    pub fn teardown(&mut self) -> Result<(), &'static str> {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        self.wake();
+        for listener in self.listeners.drain(..) {
+            drop(listener)
+        }
+        if let Some(handle) = self.timer_handle.take() {
+            handle.join().map_err(|_| "thread panicked")
+        } else {
+            Ok(())
+        }
+    }
+    /// Get a receiver node from the clock for the named listener.
+    ///
+    /// This can be done at any time before teardown. Listeners added while the clock is running receive every
+    /// tick from the next one onward. A listener that falls more than `DEFAULT_CAPACITY` ticks behind loses its
+    /// oldest ticks; use `become_listener_with()` to choose otherwise.
+    ///
+    /// The name identifies the listener in `disconnected_listeners()` if its receiver is dropped early.
+    pub fn become_listener(&mut self, name: &str) -> Result<TickReceiver, &'static str> {
+        self.become_listener_with(name, DEFAULT_CAPACITY, Backpressure::DropOldest)
+    }
+    /// Get a receiver node from the clock that queues up to `capacity` ticks, applying `policy` once full.
+    pub fn become_listener_with(
+        &mut self,
+        name: &str,
+        capacity: usize,
+        policy: Backpressure,
+    ) -> Result<TickReceiver, &'static str> {
+        if self.is_poisoned() {
+            return Err("clock thread panicked");
+        } else if self.stop_flag.load(Ordering::Relaxed) {
+            return Err("clock has been terminated");
+        }
+        let (tx, rx) = listener::channel(name, capacity, policy);
+        if self.timer_handle.is_some() {
+            self.registration_tx
+                .send(Registration::Listener(tx))
+                .map_err(|_| "clock thread has stopped")?;
+        } else {
+            self.listeners.push(tx);
+        }
+        Ok(rx)
+    }
+    /// Gets the names of listeners that stopped receiving before the clock was torn down.
+    ///
+    /// A listener disconnects when its receiver is dropped, which usually means the thread that owned it has
+    /// died. The clock stops sending to it once it is noticed, on the next tick.
+    pub fn disconnected_listeners(&self) -> Vec<String> {
+        self.disconnected.lock().unwrap().clone()
+    }
+    /// Registers a handler to be run on the clock thread every tick.
+    ///
+    /// Handlers avoid the need for a dedicated listener thread when the work per tick is small, such as
+    /// decrementing timers. Like `become_listener()`, this can be done at any time before teardown.
+    pub fn on_tick(&mut self, handler: TickHandler) -> Result<(), &'static str> {
+        if self.is_poisoned() {
+            Err("clock thread panicked")
+        } else if self.stop_flag.load(Ordering::Relaxed) {
+            Err("clock has been terminated")
+        } else if self.timer_handle.is_some() {
+            self.registration_tx
+                .send(Registration::Handler(handler))
+                .map_err(|_| "clock thread has stopped")
+        } else {
+            self.handlers.push(handler);
+            Ok(())
+        }
+    }
+}
+
+/// Marks a clock as poisoned if the thread holding this panics.
+struct PoisonOnPanic(Arc<AtomicBool>);
+
+impl Drop for PoisonOnPanic {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Tears the clock down, so a clock that's dropped without `teardown()` doesn't leave its thread running.
+impl Drop for Clock {
+    fn drop(&mut self) {
+        let _ = self.teardown();
+    }
+}
+
+/// A clock that only ticks when told to, for deterministic tests and hosts that keep their own time.
+///
+/// It offers the same listeners and handlers as `Clock` through `ClockSource`, but never spawns a thread; ticks are
+/// fired by calling `advance()`.
+pub struct ManualClock {
+    clock: Clock,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            clock: Clock::new(Duration::ZERO),
+        }
+    }
+    /// Fires the given number of ticks immediately, in order.
+    pub fn advance(&mut self, ticks: u64) -> Result<(), &'static str> {
+        for _ in 0..ticks {
+            self.clock.tick()?;
+        }
+        Ok(())
+    }
+    /// Fires a single tick standing for the given number of ticks.
+    pub fn advance_coalesced(&mut self, ticks: u64) -> Result<(), &'static str> {
+        self.clock.tick_by(ticks)
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Passes a tick to every handler and listener.
+///
+/// Listeners whose receivers have been dropped are removed and their names recorded in `disconnected`.
+fn deliver(
+    tick: Tick,
+    handlers: &mut [TickHandler],
+    listeners: &mut Vec<TickSender>,
+    stop_flag: &AtomicBool,
+    disconnected: &Mutex<Vec<String>>,
+) {
+    for handler in handlers {
+        handler(tick);
+    }
+    listeners.retain(|listener| {
+        let connected = listener.send(tick, stop_flag);
+        if !connected {
+            disconnected.lock().unwrap().push(listener.name.clone());
+        }
+        connected
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let mut clock = Clock::new(Duration::from_micros(16_667));
+        if let Ok(rx1) = clock.become_listener("t1") {
+            if let Ok(rx2) = clock.become_listener("t2") {
+                let t1 = thread::spawn(move || {
+                    let mut count = 0;
+                    while count < 61 {
+                        println!("count on t1: {}", count);
+                        let _ = rx1.recv();
+                        count += 1;
+                    }
+                    count
+                });
+                let t2 = thread::spawn(move || {
+                    let mut count = 0;
+                    while count < 61 {
+                        println!("count on t2: {}", count);
+                        let _ = rx2.recv();
+                        count += 1;
+                    }
+                    count
+                });
+
+                clock.start();
+
+                let threads = vec![t1, t2];
+                for thread in threads {
+                    assert_eq!(thread.join().unwrap(), 61);
+                }
+            }
+        } else {
+            panic!("could not register listeners")
+        }
+    }
+    #[test]
+    fn test_clock_stop() {
+        let mut clock = Clock::new(Duration::from_micros(16_667));
+        if let Ok(rx1) = clock.become_listener("t1") {
+            if let Ok(rx2) = clock.become_listener("t2") {
+                let t1 = thread::spawn(move || {
+                    while rx1.recv().is_ok() {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                    21
+                });
+                let t2 = thread::spawn(move || {
+                    while rx2.recv().is_ok() {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                    21
+                });
+
+                clock.start();
+                thread::sleep(Duration::from_millis(500));
+                assert!(
+                    clock.teardown().is_ok(),
+                    "timer thread is not safely joined"
+                );
+
+                let threads = vec![t1, t2];
+                for thread in threads {
+                    assert_eq!(thread.join().unwrap(), 21);
+                }
+            } else {
+                panic!("could not register listeners")
+            }
+        } else {
+            panic!("could not register listeners")
+        }
+    }
+    #[test]
+    fn test_clock_manual_tick() {
+        let mut clock = Clock::new(Duration::from_micros(16_667));
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        for _ in 0..3 {
+            assert!(clock.tick().is_ok(), "manual tick failed");
+        }
+        assert_eq!(rx.try_iter().count(), 3, "listener missed manual ticks");
+        clock.start();
+        assert!(
+            clock.tick().is_err(),
+            "manual tick allowed on running clock"
+        );
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+    }
+    #[test]
+    fn test_clock_pause() {
+        let mut clock = Clock::new(Duration::from_millis(5));
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.pause();
+        clock.start();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rx.try_iter().count(), 0, "paused clock delivered ticks");
+        clock.resume();
+        thread::sleep(Duration::from_millis(50));
+        assert!(
+            rx.try_iter().count() > 0,
+            "resumed clock delivered no ticks"
+        );
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+    }
+    #[test]
+    fn test_clock_set_interval() {
+        let mut clock = Clock::new(Duration::from_secs(3600));
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.start();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rx.try_iter().count(), 0, "clock ticked early");
+        clock.set_interval(Duration::from_millis(1));
+        assert_eq!(clock.interval(), Duration::from_millis(1));
+        assert!(
+            rx.recv_timeout(Duration::from_millis(500)).is_ok(),
+            "new interval did not take effect while running"
+        );
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+    }
+    #[test]
+    fn test_clock_listener_after_start() {
+        let mut clock = Clock::new(Duration::from_millis(1));
+        clock.start();
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener on running clock");
+        assert!(
+            rx.recv_timeout(Duration::from_millis(500)).is_ok(),
+            "late listener received no ticks"
+        );
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+        assert!(clock.become_listener("test").is_err());
+    }
+    #[test]
+    fn test_clock_speed() {
+        let mut clock = Clock::new(Duration::from_secs(3600));
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.start();
+        clock.set_speed(Speed::Unlimited);
+        assert!(
+            rx.recv_timeout(Duration::from_millis(500)).is_ok(),
+            "unlimited speed did not take effect while running"
+        );
+        assert_eq!(clock.interval(), Duration::from_secs(3600));
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+    }
+    #[test]
+    fn test_clock_leaving_unlimited_speed() {
+        let mut clock = Clock::new(Duration::from_millis(10));
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.start();
+        clock.set_speed(Speed::Unlimited);
+        thread::sleep(Duration::from_millis(100));
+        clock.set_speed(Speed::NORMAL);
+        rx.try_iter().for_each(drop);
+        let tick = rx
+            .recv_timeout(Duration::from_millis(500))
+            .expect("clock did not tick at normal speed");
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+        // the time spent at unlimited speed isn't owed as catch-up ticks
+        assert!(
+            tick.elapsed < 5,
+            "clock caught up on unlimited time: {tick:?}"
+        );
+    }
+    #[test]
+    fn test_clock_lockstep() {
+        let mut clock = Clock::new(Duration::from_millis(1));
+        clock.set_lockstep(true);
+        let fast = clock
+            .become_listener("fast")
+            .expect("could not register listener");
+        let slow = clock
+            .become_listener("slow")
+            .expect("could not register listener");
+        clock.start();
+        fast.recv().unwrap();
+        fast.ack();
+        // the slow listener hasn't acknowledged its first tick, so the fast one is held back
+        assert!(
+            fast.recv_timeout(Duration::from_millis(20)).is_err(),
+            "clock ran ahead of an unacknowledged listener"
+        );
+        slow.recv().unwrap();
+        slow.ack();
+        assert!(
+            fast.recv_timeout(Duration::from_millis(500)).is_ok(),
+            "clock did not resume after acknowledgement"
+        );
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+    }
+    #[test]
+    fn test_clock_precision() {
+        for precision in [
+            Precision::Sleep,
+            Precision::Hybrid {
+                spin: Duration::from_millis(1),
+            },
+            Precision::Spin,
+        ] {
+            let mut clock = Clock::new(Duration::from_millis(2));
+            clock.set_precision(precision);
+            assert_eq!(clock.precision(), precision);
+            let rx = clock
+                .become_listener("test")
+                .expect("could not register listener");
+            clock.start();
+            assert_eq!(rx.iter().take(5).count(), 5, "{precision:?} clock stalled");
+            assert!(
+                clock.teardown().is_ok(),
+                "timer thread is not safely joined"
+            );
+        }
+    }
+    #[test]
+    fn test_clock_stats() {
+        let mut clock = Clock::new(Duration::from_millis(2));
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.start();
+        let ticks: Vec<Tick> = rx.iter().take(10).collect();
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+        // a late wakeup folds ticks together, skipping numbers, but every tick is still accounted for
+        assert!(
+            ticks.windows(2).all(|pair| pair[0].number < pair[1].number),
+            "ticks are not numbered in order: {ticks:?}"
+        );
+        let covered: u64 = ticks.iter().map(|tick| tick.elapsed).sum();
+        assert_eq!(
+            covered,
+            ticks[9].number + 1,
+            "ticks went missing: {ticks:?}"
+        );
+        let stats = clock.stats();
+        assert!(stats.ticks >= covered, "stats missed ticks");
+        assert!(
+            stats.average_interval >= Duration::from_millis(1),
+            "average interval is shorter than the clock interval"
+        );
+        assert!(clock.jitter_report().total() <= stats.ticks);
+    }
+    #[test]
+    fn test_manual_clock() {
+        let mut clock = ManualClock::new();
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        assert!(clock.advance(61).is_ok(), "manual clock failed to advance");
+        let numbers: Vec<u64> = rx.try_iter().map(|tick| tick.number).collect();
+        assert_eq!(
+            numbers,
+            (1..61).collect::<Vec<u64>>(),
+            "full listener did not drop its oldest tick"
+        );
+        assert_eq!(clock.stats().ticks, 61);
+        assert!(clock.teardown().is_ok());
+        assert!(clock.advance(1).is_err(), "torn down clock still ticks");
+    }
+    #[test]
+    fn test_clock_coalesces_stalls() {
+        let mut clock = Clock::new(Duration::from_millis(1));
+        clock
+            .on_tick(Box::new(|_| thread::sleep(Duration::from_millis(10))))
+            .expect("could not register handler");
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.start();
+        let ticks: Vec<Tick> = rx.iter().take(3).collect();
+        assert!(
+            clock.teardown().is_ok(),
+            "timer thread is not safely joined"
+        );
+        assert!(
+            ticks[1..].iter().all(|tick| tick.elapsed > 1),
+            "stalled clock did not coalesce missed ticks: {ticks:?}"
+        );
+        assert_eq!(ticks[2].number - ticks[1].number, ticks[2].elapsed);
+    }
+    #[test]
+    fn test_clock_disconnected_listener() {
+        let mut clock = ManualClock::new();
+        let kept = clock
+            .become_listener("kept")
+            .expect("could not register listener");
+        let dropped = clock
+            .become_listener("dropped")
+            .expect("could not register listener");
+        drop(dropped);
+        clock.advance(2).expect("failed to advance clock");
+        assert_eq!(clock.disconnected_listeners(), vec!["dropped".to_string()]);
+        assert_eq!(kept.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn dropping_stops_the_thread() {
+        let mut clock = Clock::new(Duration::from_millis(1));
+        let rx = clock
+            .become_listener("test")
+            .expect("failed to listen to clock");
+        clock.start();
+        rx.recv().expect("clock did not tick");
+        drop(clock);
+        rx.try_iter().for_each(drop);
+        assert!(rx.recv().is_err(), "clock thread outlives the clock");
+    }
+
+    #[test]
+    fn panics_poison_the_clock() {
+        let mut clock = Clock::new(Duration::from_millis(1));
+        clock
+            .on_tick(Box::new(|tick| assert!(tick.number < 3, "handler failed")))
+            .expect("failed to register handler");
+        clock.start();
+        let start = Instant::now();
+        while !clock.is_poisoned() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "panic was not noticed"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(clock.become_listener("late").is_err());
+        assert!(
+            clock.teardown().is_err(),
+            "panic is not reported on teardown"
+        );
+    }
+}
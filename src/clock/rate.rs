@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+/// How many times a second a clock ticks, and so how fast the Chip8 timers count down.
+///
+/// Original hardware counts at 60hz, but some software written for PAL machines expects 50hz.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickRate {
+    hz: f64,
+}
+
+impl TickRate {
+    /// The 60hz rate of NTSC machines, which nearly all software expects.
+    pub const NTSC: TickRate = TickRate { hz: 60.0 };
+    /// The 50hz rate of PAL machines.
+    pub const PAL: TickRate = TickRate { hz: 50.0 };
+
+    /// The slowest rate there can be, in ticks per second.
+    pub const MIN_HZ: f64 = 1.0;
+    /// The fastest rate there can be, in ticks per second. Any faster and the default instruction budget would round
+    /// to none at all each tick.
+    pub const MAX_HZ: f64 = 1000.0;
+
+    /// Creates a rate of `MIN_HZ` to `MAX_HZ` ticks per second.
+    pub fn from_hz(hz: f64) -> Result<TickRate, &'static str> {
+        if (TickRate::MIN_HZ..=TickRate::MAX_HZ).contains(&hz) {
+            Ok(TickRate { hz })
+        } else {
+            Err("tick rate must be from 1 to 1000 hz")
+        }
+    }
+
+    pub fn hz(self) -> f64 {
+        self.hz
+    }
+
+    /// The time between ticks at this rate.
+    pub fn interval(self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.hz)
+    }
+
+    /// Converts a budget of instructions per second into instructions per tick, rounding to the nearest. Any budget
+    /// at all runs at least one instruction a tick.
+    pub fn per_tick(self, per_second: u32) -> u32 {
+        let per_tick = (per_second as f64 / self.hz).round() as u32;
+        per_tick.max(per_second.min(1))
+    }
+}
+
+impl Default for TickRate {
+    fn default() -> Self {
+        TickRate::NTSC
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::INSTRUCTIONS_PER_SECOND;
+
+    use super::*;
+
+    #[test]
+    fn rates() {
+        assert_eq!(TickRate::PAL.interval(), Duration::from_millis(20));
+        assert_eq!(TickRate::NTSC.per_tick(600), 10);
+        assert_eq!(TickRate::PAL.per_tick(600), 12);
+        assert_eq!(
+            TickRate::from_hz(30.0).map(TickRate::interval),
+            Ok(Duration::from_secs_f64(1.0 / 30.0))
+        );
+        assert!(TickRate::from_hz(0.0).is_err());
+        assert!(TickRate::from_hz(f64::NAN).is_err());
+        assert_eq!(TickRate::NTSC.per_tick(0), 0);
+    }
+
+    #[test]
+    fn extreme_rates_are_refused() {
+        assert!(TickRate::from_hz(1e-300).is_err());
+        assert!(TickRate::from_hz(0.5).is_err());
+        assert!(TickRate::from_hz(1e300).is_err());
+        assert!(TickRate::from_hz(f64::INFINITY).is_err());
+
+        let slowest = TickRate::from_hz(TickRate::MIN_HZ).unwrap();
+        assert_eq!(slowest.interval(), Duration::from_secs(1));
+        let fastest = TickRate::from_hz(TickRate::MAX_HZ).unwrap();
+        assert_eq!(fastest.interval(), Duration::from_millis(1));
+        assert_eq!(fastest.per_tick(INSTRUCTIONS_PER_SECOND), 1);
+        assert_eq!(fastest.per_tick(60), 1);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::speed::Speed;
+
+use super::{
+    Backpressure, Clock, ClockStats, ManualClock, TickHandler, TickReceiver, VsyncClock,
+    DEFAULT_CAPACITY,
+};
+
+/// A source of ticks that components can attach to, whatever is driving it.
+///
+/// `Clock` ticks on its own thread, `ManualClock` when told to, and `VsyncClock` when the display presents.
+/// Components should take any `ClockSource` so the timing strategy can be chosen per platform.
+pub trait ClockSource: Send {
+    /// Get a receiver node from the clock that queues up to `capacity` ticks, applying `policy` once full.
+    fn become_listener_with(
+        &mut self,
+        name: &str,
+        capacity: usize,
+        policy: Backpressure,
+    ) -> Result<TickReceiver, &'static str>;
+
+    /// Registers a handler to be run every tick, on whichever thread drives the clock.
+    fn on_tick(&mut self, handler: TickHandler) -> Result<(), &'static str>;
+
+    /// Gets the timing statistics of the clock, including the number of ticks delivered so far.
+    fn stats(&self) -> ClockStats;
+
+    /// Gets the names of listeners that stopped receiving before the clock was torn down.
+    fn disconnected_listeners(&self) -> Vec<String>;
+
+    /// Starts the clock, if it runs by itself.
+    fn start(&mut self);
+
+    fn pause(&self);
+
+    fn resume(&self);
+
+    fn is_paused(&self) -> bool;
+
+    /// Stops the clock for good, disconnecting its listeners.
+    fn teardown(&mut self) -> Result<(), &'static str>;
+
+    /// Whether the thread driving the clock has panicked. Clocks ticked on the caller's thread are never poisoned,
+    /// as their panics reach the caller directly.
+    fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Scales how fast the clock ticks. Clocks that don't keep time themselves ignore this.
+    fn set_speed(&mut self, _speed: Speed) {}
+
+    /// Get a receiver node from the clock for the named listener, with the default capacity and policy.
+    fn become_listener(&mut self, name: &str) -> Result<TickReceiver, &'static str> {
+        self.become_listener_with(name, DEFAULT_CAPACITY, Backpressure::DropOldest)
+    }
+
+    /// Gets the number of ticks delivered so far.
+    fn ticks(&self) -> u64 {
+        self.stats().ticks
+    }
+}
+
+impl ClockSource for Clock {
+    fn become_listener_with(
+        &mut self,
+        name: &str,
+        capacity: usize,
+        policy: Backpressure,
+    ) -> Result<TickReceiver, &'static str> {
+        Clock::become_listener_with(self, name, capacity, policy)
+    }
+    fn on_tick(&mut self, handler: TickHandler) -> Result<(), &'static str> {
+        Clock::on_tick(self, handler)
+    }
+    fn stats(&self) -> ClockStats {
+        Clock::stats(self)
+    }
+    fn disconnected_listeners(&self) -> Vec<String> {
+        Clock::disconnected_listeners(self)
+    }
+    fn start(&mut self) {
+        Clock::start(self)
+    }
+    fn pause(&self) {
+        Clock::pause(self)
+    }
+    fn resume(&self) {
+        Clock::resume(self)
+    }
+    fn is_paused(&self) -> bool {
+        Clock::is_paused(self)
+    }
+    fn teardown(&mut self) -> Result<(), &'static str> {
+        Clock::teardown(self)
+    }
+    fn set_speed(&mut self, speed: Speed) {
+        Clock::set_speed(self, speed)
+    }
+    fn is_poisoned(&self) -> bool {
+        Clock::is_poisoned(self)
+    }
+}
+
+impl ClockSource for ManualClock {
+    fn become_listener_with(
+        &mut self,
+        name: &str,
+        capacity: usize,
+        policy: Backpressure,
+    ) -> Result<TickReceiver, &'static str> {
+        self.clock.become_listener_with(name, capacity, policy)
+    }
+    fn on_tick(&mut self, handler: TickHandler) -> Result<(), &'static str> {
+        self.clock.on_tick(handler)
+    }
+    fn stats(&self) -> ClockStats {
+        self.clock.stats()
+    }
+    fn disconnected_listeners(&self) -> Vec<String> {
+        self.clock.disconnected_listeners()
+    }
+    /// Manual clocks only tick when advanced, so there is nothing to start.
+    fn start(&mut self) {}
+    fn pause(&self) {
+        self.clock.pause()
+    }
+    fn resume(&self) {
+        self.clock.resume()
+    }
+    fn is_paused(&self) -> bool {
+        self.clock.is_paused()
+    }
+    fn teardown(&mut self) -> Result<(), &'static str> {
+        self.clock.teardown()
+    }
+}
+
+impl ClockSource for VsyncClock {
+    fn become_listener_with(
+        &mut self,
+        name: &str,
+        capacity: usize,
+        policy: Backpressure,
+    ) -> Result<TickReceiver, &'static str> {
+        self.inner_mut()
+            .become_listener_with(name, capacity, policy)
+    }
+    fn on_tick(&mut self, handler: TickHandler) -> Result<(), &'static str> {
+        self.inner_mut().on_tick(handler)
+    }
+    fn stats(&self) -> ClockStats {
+        self.inner().stats()
+    }
+    fn disconnected_listeners(&self) -> Vec<String> {
+        self.inner().disconnected_listeners()
+    }
+    /// Vsync clocks tick when the display presents, so there is nothing to start.
+    fn start(&mut self) {}
+    fn pause(&self) {
+        VsyncClock::pause(self)
+    }
+    /// Resumes the clock without counting the time spent paused as elapsed.
+    fn resume(&self) {
+        VsyncClock::resume(self)
+    }
+    fn is_paused(&self) -> bool {
+        self.inner().is_paused()
+    }
+    fn teardown(&mut self) -> Result<(), &'static str> {
+        self.inner_mut().teardown()
+    }
+    fn set_speed(&mut self, speed: Speed) {
+        VsyncClock::set_speed(self, speed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Counts the ticks a listener sees while `drive` runs the clock, including those folded into others.
+    fn count_ticks(clock: &mut dyn ClockSource, drive: impl FnOnce(&mut dyn ClockSource)) -> u64 {
+        let rx = clock
+            .become_listener("test")
+            .expect("could not register listener");
+        clock.start();
+        drive(clock);
+        clock.teardown().expect("clock did not tear down");
+        rx.iter().map(|tick| tick.elapsed).sum()
+    }
+
+    #[test]
+    fn sources_are_interchangeable() {
+        let mut manual = ManualClock::new();
+        let ticks = count_ticks(&mut manual, |_| {});
+        assert_eq!(ticks, 0, "manual clock ticked by itself");
+
+        let mut clock = Clock::new(Duration::from_millis(1));
+        let ticks = count_ticks(&mut clock, |clock| {
+            while clock.ticks() < 3 {
+                std::thread::yield_now();
+            }
+        });
+        assert!(ticks >= 3, "threaded clock did not tick through the trait");
+    }
+}
@@ -0,0 +1,43 @@
+use std::{
+    borrow::Borrow,
+    future::poll_fn,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+
+use super::{Tick, TickReceiver};
+
+/// A clock listener as an async stream of ticks, ending when the clock is torn down.
+///
+/// The stream owns its listener, or borrows one that's kept elsewhere, such as a machine's.
+pub struct TickStream<R = TickReceiver> {
+    receiver: R,
+}
+
+impl TickReceiver {
+    pub fn into_stream(self) -> TickStream {
+        TickStream { receiver: self }
+    }
+
+    /// Streams ticks from this listener while keeping hold of it.
+    pub fn stream(&self) -> TickStream<&TickReceiver> {
+        TickStream { receiver: self }
+    }
+}
+
+impl<R: Borrow<TickReceiver> + Unpin> TickStream<R> {
+    /// Waits for the next tick, giving `None` once the clock is torn down.
+    pub async fn next(&mut self) -> Option<Tick> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl<R: Borrow<TickReceiver> + Unpin> Stream for TickStream<R> {
+    type Item = Tick;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Tick>> {
+        self.receiver.borrow().poll_recv(cx)
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::speed::Speed;
+
+use super::{ClockSource, ManualClock, TickRate};
+
+/// How far into the next interval a present can borrow to still count as a tick, as a fraction of the interval.
+///
+/// A display running slightly faster than the clock would otherwise alternate between presents with no tick and
+/// presents with one. Borrowing lets each present tick once, paying the difference back with an occasional empty
+/// present.
+const SLACK: f64 = 0.1;
+/// The most ticks a single present can fire, so a stalled frontend doesn't fire a burst when it recovers.
+const MAX_TICKS_PER_PRESENT: u64 = 4;
+
+/// A clock driven by the frontend's vsync or present callback instead of its own thread.
+///
+/// Listeners and handlers are attached through `ClockSource`. Each call to `present()` fires a tick standing for
+/// however many ticks are due since the last one, and says how many that is, so a frontend driving a machine frame
+/// by frame can run as many. Displays that don't refresh at exactly the clock's rate are compensated for, so
+/// emulation keeps to the clock's rate on average without visible judder.
+///
+/// A zero interval, or unlimited speed, fires as many ticks as a present can on every present.
+pub struct VsyncClock {
+    clock: ManualClock,
+    interval: Duration,
+    speed: Speed,
+    last_present: Option<Instant>,
+    /// Time owed to the next tick, in nanoseconds. Negative when a present has borrowed from the next interval.
+    accumulated: i64,
+    /// Set on resuming, so the next present starts the timeline afresh rather than counting the pause.
+    resumed: AtomicBool,
+}
+
+impl VsyncClock {
+    pub fn new(interval: Duration) -> Self {
+        VsyncClock {
+            clock: ManualClock::new(),
+            interval,
+            speed: Speed::NORMAL,
+            last_present: None,
+            accumulated: 0,
+            resumed: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates a clock ticking at the given rate on average, however fast the display refreshes.
+    pub fn with_rate(rate: TickRate) -> Self {
+        VsyncClock::new(rate.interval())
+    }
+
+    /// Fires the ticks due at a present happening now, returning how many were fired.
+    pub fn present(&mut self) -> Result<u64, &'static str> {
+        self.present_at(Instant::now())
+    }
+
+    /// Fires the ticks due at a present happening at `now`, returning how many were fired.
+    ///
+    /// The first present only starts the timeline.
+    pub fn present_at(&mut self, now: Instant) -> Result<u64, &'static str> {
+        match self.last_present.replace(now) {
+            Some(last_present) => self.present_after(now - last_present),
+            None => {
+                self.resumed.store(false, Ordering::Relaxed);
+                Ok(0)
+            }
+        }
+    }
+
+    /// Fires the ticks due at a present `elapsed` after the last one, returning how many were fired. For hosts that
+    /// time their presents themselves, such as browsers, which have no `Instant`.
+    pub fn present_after(&mut self, elapsed: Duration) -> Result<u64, &'static str> {
+        if self.resumed.swap(false, Ordering::Relaxed) {
+            // the time since the last present was spent paused
+            self.accumulated = 0;
+            return Ok(0);
+        }
+        if self.clock.is_paused() {
+            return Ok(0);
+        }
+        let interval = self.speed.scale_interval(self.interval);
+        if interval.is_zero() {
+            self.clock.advance_coalesced(MAX_TICKS_PER_PRESENT)?;
+            return Ok(MAX_TICKS_PER_PRESENT);
+        }
+        let interval = interval.as_nanos() as i64;
+        self.accumulated += elapsed.as_nanos() as i64;
+        let mut ticks = (self.accumulated / interval).max(0) as u64;
+        if ticks == 0 && self.accumulated as f64 >= interval as f64 * (1.0 - SLACK) {
+            ticks = 1;
+        }
+        if ticks > MAX_TICKS_PER_PRESENT {
+            ticks = MAX_TICKS_PER_PRESENT;
+            self.accumulated = 0;
+        } else {
+            self.accumulated -= ticks as i64 * interval;
+        }
+        self.clock.advance_coalesced(ticks)?;
+        Ok(ticks)
+    }
+
+    /// Forgets the last present, so a long gap (such as a minimized window) isn't counted as elapsed time.
+    pub fn reset(&mut self) {
+        self.last_present = None;
+        self.accumulated = 0;
+    }
+
+    /// The time between ticks at normal speed.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Scales how fast ticks come, from the next present on.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+    }
+
+    /// Pauses the clock. Presents fire no ticks while paused.
+    pub fn pause(&self) {
+        ClockSource::pause(&self.clock);
+    }
+
+    /// Resumes the clock, starting the timeline afresh at the next present so the time spent paused isn't counted.
+    pub fn resume(&self) {
+        self.resumed.store(true, Ordering::Relaxed);
+        ClockSource::resume(&self.clock);
+    }
+
+    pub(super) fn inner(&self) -> &ManualClock {
+        &self.clock
+    }
+
+    pub(super) fn inner_mut(&mut self) -> &mut ManualClock {
+        &mut self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presents `frames` times at `hz`, returning the ticks fired by each present.
+    fn run_display(hz: f64, frames: u32) -> Vec<u64> {
+        let mut clock = VsyncClock::new(Duration::from_micros(16_667));
+        let start = Instant::now();
+        (0..=frames)
+            .map(|frame| {
+                let now = start + Duration::from_secs_f64(frame as f64 / hz);
+                clock.present_at(now).expect("present failed")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exact_display_ticks_once_per_present() {
+        let ticks = run_display(60.0, 600);
+        assert!(ticks[1..].iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn fast_display_skips_occasionally() {
+        let ticks = run_display(61.0, 610);
+        let total: u64 = ticks.iter().sum();
+        assert!((599..=601).contains(&total), "fired {total} ticks in 10s");
+        assert!(
+            ticks.iter().all(|&n| n <= 1),
+            "fast display fired doubled ticks"
+        );
+    }
+
+    #[test]
+    fn slow_display_catches_up() {
+        let ticks = run_display(59.0, 590);
+        let total: u64 = ticks.iter().sum();
+        assert!((599..=601).contains(&total), "fired {total} ticks in 10s");
+    }
+
+    #[test]
+    fn pal_rate_on_ntsc_display() {
+        let mut clock = VsyncClock::with_rate(TickRate::PAL);
+        let start = Instant::now();
+        let total: u64 = (0..=600)
+            .map(|frame| {
+                let now = start + Duration::from_secs_f64(frame as f64 / 60.0);
+                clock.present_at(now).expect("present failed")
+            })
+            .sum();
+        assert!((499..=501).contains(&total), "fired {total} ticks in 10s");
+    }
+
+    #[test]
+    fn high_refresh_display_paces_ticks() {
+        let ticks = run_display(144.0, 1440);
+        let total: u64 = ticks.iter().sum();
+        assert!((599..=601).contains(&total), "fired {total} ticks in 10s");
+    }
+
+    #[test]
+    fn speed_scales_the_ticks_due() {
+        let mut clock = VsyncClock::with_rate(TickRate::PAL);
+        clock.set_speed(Speed::scaled(2.0).unwrap());
+        let frame = Duration::from_millis(20);
+        assert_eq!(clock.present_after(frame).expect("present failed"), 2);
+        clock.set_speed(Speed::scaled(0.5).unwrap());
+        assert_eq!(clock.present_after(frame).expect("present failed"), 0);
+        assert_eq!(clock.present_after(frame).expect("present failed"), 1);
+        clock.set_speed(Speed::Unlimited);
+        assert_eq!(
+            clock.present_after(Duration::ZERO).expect("present failed"),
+            MAX_TICKS_PER_PRESENT
+        );
+
+        clock.set_speed(Speed::NORMAL);
+        assert_eq!(
+            clock
+                .present_after(Duration::from_secs(5))
+                .expect("present failed"),
+            MAX_TICKS_PER_PRESENT
+        );
+        clock.pause();
+        assert_eq!(clock.present_after(frame).expect("present failed"), 0);
+    }
+
+    #[test]
+    fn pauses_and_zero_intervals_fire_no_bursts() {
+        let mut clock = VsyncClock::new(Duration::ZERO);
+        let start = Instant::now();
+        clock.present_at(start).expect("present failed");
+        assert_eq!(
+            clock.present_at(start).expect("present failed"),
+            MAX_TICKS_PER_PRESENT
+        );
+
+        clock.set_interval(Duration::from_micros(16_667));
+        clock.pause();
+        clock.resume();
+        let later = start + Duration::from_secs(10);
+        assert_eq!(clock.present_at(later).expect("present failed"), 0);
+        let next = later + Duration::from_micros(16_667);
+        assert_eq!(clock.present_at(next).expect("present failed"), 1);
+    }
+}
@@ -0,0 +1,715 @@
+//! Settings loaded from a TOML file, layered over the built-in defaults.
+//!
+//! Every section and field is optional, so a config file only needs what it changes. More layers, such as a
+//! per-game file or command line options, can be put over the top with `Config::layer_toml()` or by setting
+//! fields directly.
+
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock::TickRate,
+    hotkeys::{Hotkey, Hotkeys},
+    machine::{
+        check_variant, rom_hash, rom_id, Chip8Builder, DEFAULT_HISTORY_LENGTH,
+        INSTRUCTIONS_PER_SECOND,
+    },
+    quirks::{Quirks, Variant},
+    speed::Speed,
+};
+
+#[cfg(feature = "hot-reload")]
+mod watch;
+
+#[cfg(feature = "hot-reload")]
+pub use watch::ConfigWatcher;
+
+/// The name of the directory the config file lives in, under the platform's config directory.
+const APP_DIRECTORY: &str = "chip8-rust";
+const FILE_NAME: &str = "config.toml";
+
+/// Why a config couldn't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The file couldn't be read.
+    Read(PathBuf, io::ErrorKind),
+    /// The TOML is malformed or has settings of the wrong type, described by the message.
+    Parse(String),
+    /// The settings parse, but make no sense.
+    Invalid(&'static str),
+    /// The file couldn't be watched for changes, described by the message.
+    Watch(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, kind) => {
+                write!(f, "could not read {}: {kind}", path.display())
+            }
+            ConfigError::Parse(message) => write!(f, "invalid config: {message}"),
+            ConfigError::Invalid(message) => write!(f, "invalid config: {message}"),
+            ConfigError::Watch(message) => write!(f, "could not watch config: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Everything that can be configured, for both the machine and the frontend showing it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub machine: MachineConfig,
+    pub display: DisplayConfig,
+    pub audio: AudioConfig,
+    pub saves: SaveConfig,
+    /// Host key names bound to keys on the hex keypad, 0 through F.
+    pub keymap: BTreeMap<String, u8>,
+    /// Host key names bound to emulator controls.
+    pub hotkeys: BTreeMap<String, Hotkey>,
+    /// Settings for particular programs, keyed by the hex SHA-256 hash of the program or its `rom_id()`. Each is laid
+    /// out like a whole config file.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub roms: BTreeMap<String, toml::Table>,
+}
+
+/// A setting that can change while the machine runs, without rebuilding it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveChange {
+    Palette {
+        foreground: Color,
+        background: Color,
+    },
+    Keymap(BTreeMap<String, u8>),
+    Hotkeys(BTreeMap<String, Hotkey>),
+    Speed(Speed),
+    Audio(AudioConfig),
+}
+
+/// How the machine itself behaves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MachineConfig {
+    /// The machine to emulate. Only `chip8` programs can run so far; configs naming another variant are refused.
+    pub variant: Variant,
+    /// Overrides for the variant's quirks. Anything left out follows the variant.
+    pub quirks: QuirkOverrides,
+    pub instructions_per_second: u32,
+    /// How many times a second the timers count down, from 1 to 1000.
+    pub tick_rate: f64,
+    /// A multiple of normal speed, where 1.0 is normal, from 0.25 up.
+    pub speed: f32,
+    /// Overrides the variant's amount of RAM, in bytes.
+    pub ram_size: Option<usize>,
+    /// Seeds the random number generator, so runs can be repeated.
+    pub seed: Option<u64>,
+    /// Reports the machine falling this many ticks behind its clock.
+    pub watchdog: Option<u64>,
+    /// How many of the last instructions run to remember, for the debugger and crash reports.
+    pub history_length: usize,
+}
+
+/// Quirks to change from the variant's defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuirkOverrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vf_reset: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shift_uses_vy: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_store_increments_index: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jump_uses_vx: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrap_sprites: Option<bool>,
+}
+
+impl From<Quirks> for QuirkOverrides {
+    /// Overrides every quirk, so the variant's defaults are ignored entirely.
+    fn from(quirks: Quirks) -> Self {
+        QuirkOverrides {
+            vf_reset: Some(quirks.vf_reset),
+            shift_uses_vy: Some(quirks.shift_uses_vy),
+            load_store_increments_index: Some(quirks.load_store_increments_index),
+            jump_uses_vx: Some(quirks.jump_uses_vx),
+            wrap_sprites: Some(quirks.wrap_sprites),
+        }
+    }
+}
+
+impl QuirkOverrides {
+    /// Applies the overrides to a set of quirks.
+    pub fn apply(self, quirks: Quirks) -> Quirks {
+        Quirks {
+            vf_reset: self.vf_reset.unwrap_or(quirks.vf_reset),
+            shift_uses_vy: self.shift_uses_vy.unwrap_or(quirks.shift_uses_vy),
+            load_store_increments_index: self
+                .load_store_increments_index
+                .unwrap_or(quirks.load_store_increments_index),
+            jump_uses_vx: self.jump_uses_vx.unwrap_or(quirks.jump_uses_vx),
+            wrap_sprites: self.wrap_sprites.unwrap_or(quirks.wrap_sprites),
+        }
+    }
+}
+
+/// How frontends show the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    /// The colour of lit pixels.
+    pub foreground: Color,
+    /// The colour of unlit pixels.
+    pub background: Color,
+    /// How many host pixels wide and tall each Chip8 pixel is drawn.
+    pub scale: u32,
+}
+
+/// How frontends play the tone.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub enabled: bool,
+    /// From 0.0 for silent to 1.0 for full volume.
+    pub volume: f32,
+    /// The pitch of the tone, in hertz.
+    pub frequency: f32,
+}
+
+/// Where savestates go, and what's kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveConfig {
+    /// Where savestate slots are kept. Defaults to a directory beside the config file.
+    pub directory: Option<PathBuf>,
+    pub autosave: bool,
+    /// How many rewind states to keep, or 0 to turn rewinding off.
+    pub rewind_depth: usize,
+    /// How many frames apart rewind states are taken.
+    pub rewind_interval: u64,
+}
+
+/// A colour, written in config files as `#RRGGBB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(0xFF, 0xFF, 0xFF);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = &'static str;
+
+    fn try_from(hex: String) -> Result<Color, &'static str> {
+        let digits = hex
+            .strip_prefix('#')
+            .filter(|digits| digits.len() == 6)
+            .ok_or("colours must be written as #RRGGBB")?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| "colours must be hex digits")?;
+        let [_, r, g, b] = value.to_be_bytes();
+        Ok(Color { r, g, b })
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+    }
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig {
+            variant: Variant::default(),
+            quirks: QuirkOverrides::default(),
+            instructions_per_second: INSTRUCTIONS_PER_SECOND,
+            tick_rate: TickRate::NTSC.hz(),
+            speed: 1.0,
+            ram_size: None,
+            seed: None,
+            watchdog: None,
+            history_length: DEFAULT_HISTORY_LENGTH,
+        }
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            foreground: Color::WHITE,
+            background: Color::BLACK,
+            scale: 10,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            enabled: true,
+            volume: 0.5,
+            frequency: 440.0,
+        }
+    }
+}
+
+impl Default for SaveConfig {
+    fn default() -> Self {
+        SaveConfig {
+            directory: None,
+            autosave: false,
+            rewind_depth: 0,
+            rewind_interval: 5,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            machine: MachineConfig::default(),
+            display: DisplayConfig::default(),
+            audio: AudioConfig::default(),
+            saves: SaveConfig::default(),
+            keymap: default_keymap(),
+            hotkeys: Hotkeys::new()
+                .bindings()
+                .map(|(key, hotkey)| (key.to_string(), hotkey))
+                .collect(),
+            roms: BTreeMap::new(),
+        }
+    }
+}
+
+/// The usual layout of the hex keypad on a QWERTY keyboard, as the left four columns starting from `1`, and a
+/// controller's d-pad on the 5, 7, 8, 9 diamond most games steer with.
+fn default_keymap() -> BTreeMap<String, u8> {
+    let rows = [
+        [("1", 0x1), ("2", 0x2), ("3", 0x3), ("4", 0xC)],
+        [("Q", 0x4), ("W", 0x5), ("E", 0x6), ("R", 0xD)],
+        [("A", 0x7), ("S", 0x8), ("D", 0x9), ("F", 0xE)],
+        [("Z", 0xA), ("X", 0x0), ("C", 0xB), ("V", 0xF)],
+    ];
+    let pad = [
+        ("PadUp", 0x5),
+        ("PadDown", 0x8),
+        ("PadLeft", 0x7),
+        ("PadRight", 0x9),
+        ("PadA", 0x6),
+        ("PadB", 0x4),
+    ];
+    rows.iter()
+        .flatten()
+        .chain(&pad)
+        .map(|&(name, key)| (name.to_string(), key))
+        .collect()
+}
+
+impl Config {
+    /// Loads the config file from the platform's config directory, or the defaults if there isn't one.
+    pub fn load() -> Result<Config, ConfigError> {
+        match Config::default_path() {
+            Some(path) if path.is_file() => Config::from_file(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// Where the config file lives: under `%APPDATA%` on Windows, `~/Library/Application Support` on macOS, and
+    /// `$XDG_CONFIG_HOME` or `~/.config` elsewhere.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        };
+        base.map(|base| base.join(APP_DIRECTORY).join(FILE_NAME))
+    }
+
+    /// Loads a config file over the defaults.
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        config.layer_file(path)?;
+        Ok(config)
+    }
+
+    /// Parses a config over the defaults.
+    pub fn from_toml(toml: &str) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        config.layer_toml(toml)?;
+        Ok(config)
+    }
+
+    /// Puts the settings of a config file over these ones.
+    pub fn layer_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let toml = fs::read_to_string(path)
+            .map_err(|error| ConfigError::Read(path.to_path_buf(), error.kind()))?;
+        self.layer_toml(&toml)
+    }
+
+    /// Puts the settings in some TOML over these ones. Tables are merged, so only the settings given change.
+    pub fn layer_toml(&mut self, toml: &str) -> Result<(), ConfigError> {
+        let overrides: toml::Table =
+            toml::from_str(toml).map_err(|error| ConfigError::Parse(error.to_string()))?;
+        self.layer_table(overrides)
+    }
+
+    fn layer_table(&mut self, overrides: toml::Table) -> Result<(), ConfigError> {
+        let mut merged =
+            toml::Table::try_from(&*self).expect("configs are always serializable as tables");
+        merge(&mut merged, overrides);
+        let config: Config = merged
+            .try_into()
+            .map_err(|error: toml::de::Error| ConfigError::Parse(error.to_string()))?;
+        config.validate()?;
+        *self = config;
+        Ok(())
+    }
+
+    /// The settings for a particular program: these ones, with its `[roms]` entry put over them, then the file
+    /// beside it with the same name and a `.toml` extension, if it has one.
+    pub fn for_rom(&self, rom: &[u8], path: Option<&Path>) -> Result<Config, ConfigError> {
+        let mut config = self.clone();
+        let hash: String = rom_hash(rom)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        let id = rom_id(rom);
+        let overrides = self
+            .roms
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(&hash) || key.eq_ignore_ascii_case(&id));
+        if let Some((_, overrides)) = overrides {
+            config.layer_table(overrides.clone())?;
+        }
+        let sidecar = path
+            .map(|path| path.with_extension("toml"))
+            .filter(|sidecar| sidecar.is_file());
+        if let Some(sidecar) = sidecar {
+            config.layer_file(&sidecar)?;
+        }
+        Ok(config)
+    }
+
+    /// Writes the config as TOML, as it would be read back.
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("configs are always serializable")
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        check_variant(self.machine.variant).map_err(ConfigError::Invalid)?;
+        TickRate::from_hz(self.machine.tick_rate).map_err(ConfigError::Invalid)?;
+        if Speed::scaled(self.machine.speed).is_none() {
+            return Err(ConfigError::Invalid("speed must be a number from 0.25 up"));
+        }
+        if self.keymap.values().any(|&key| key > 0xF) {
+            return Err(ConfigError::Invalid("keymap keys must be 0 through F"));
+        }
+        Ok(())
+    }
+
+    /// The variant's quirks, with the overrides applied.
+    pub fn quirks(&self) -> Quirks {
+        self.machine.quirks.apply(self.machine.variant.quirks())
+    }
+
+    pub fn tick_rate(&self) -> TickRate {
+        TickRate::from_hz(self.machine.tick_rate).expect("configs are validated when loaded")
+    }
+
+    pub fn speed(&self) -> Speed {
+        Speed::scaled(self.machine.speed).expect("configs are validated when loaded")
+    }
+
+    /// Where savestates go: the configured directory, or `saves` beside the config file.
+    pub fn save_directory(&self) -> Option<PathBuf> {
+        self.saves.directory.clone().or_else(|| {
+            Config::default_path()
+                .and_then(|path| path.parent().map(|directory| directory.join("saves")))
+        })
+    }
+
+    /// The configured hotkey bindings.
+    pub fn hotkeys(&self) -> Hotkeys {
+        let mut hotkeys = Hotkeys::empty();
+        for (key, &hotkey) in &self.hotkeys {
+            hotkeys.bind(key, hotkey);
+        }
+        hotkeys
+    }
+
+    /// Looks up the keypad key bound to a host key.
+    pub fn keypad_key(&self, key: &str) -> Option<u8> {
+        self.keymap.get(key).copied()
+    }
+
+    /// The settings that differ in `newer` and can be changed while the machine runs.
+    pub fn live_changes(&self, newer: &Config) -> Vec<LiveChange> {
+        let mut changes = Vec::new();
+        let (display, newer_display) = (&self.display, &newer.display);
+        if (display.foreground, display.background)
+            != (newer_display.foreground, newer_display.background)
+        {
+            changes.push(LiveChange::Palette {
+                foreground: newer_display.foreground,
+                background: newer_display.background,
+            });
+        }
+        if self.keymap != newer.keymap {
+            changes.push(LiveChange::Keymap(newer.keymap.clone()));
+        }
+        if self.hotkeys != newer.hotkeys {
+            changes.push(LiveChange::Hotkeys(newer.hotkeys.clone()));
+        }
+        if self.machine.speed != newer.machine.speed {
+            changes.push(LiveChange::Speed(newer.speed()));
+        }
+        if self.audio != newer.audio {
+            changes.push(LiveChange::Audio(newer.audio));
+        }
+        changes
+    }
+
+    /// Whether `newer` changes anything that only takes effect on a newly built machine, such as the variant.
+    pub fn needs_restart(&self, newer: &Config) -> bool {
+        let mut live = self.clone();
+        for change in self.live_changes(newer) {
+            live.apply(&change);
+        }
+        live != *newer
+    }
+
+    /// Puts a live change into these settings.
+    pub fn apply(&mut self, change: &LiveChange) {
+        match change {
+            LiveChange::Palette {
+                foreground,
+                background,
+            } => {
+                self.display.foreground = *foreground;
+                self.display.background = *background;
+            }
+            LiveChange::Keymap(keymap) => self.keymap = keymap.clone(),
+            LiveChange::Hotkeys(hotkeys) => self.hotkeys = hotkeys.clone(),
+            LiveChange::Speed(Speed::Scaled(multiplier)) => self.machine.speed = multiplier.get(),
+            LiveChange::Speed(Speed::Unlimited) => {}
+            LiveChange::Audio(audio) => self.audio = *audio,
+        }
+    }
+
+    /// Starts building a machine with these settings. Anything the config doesn't cover, such as the display
+    /// backend, can still be set on the builder.
+    pub fn builder(&self) -> Chip8Builder {
+        let machine = &self.machine;
+        let mut builder = Chip8Builder::new()
+            .variant(machine.variant)
+            .quirks(self.quirks())
+            .instructions_per_second(machine.instructions_per_second)
+            .tick_rate(self.tick_rate())
+            .speed(self.speed())
+            .autosave(self.saves.autosave)
+            .history_length(machine.history_length);
+        if let Some(ram_size) = machine.ram_size {
+            builder = builder.ram_size(ram_size);
+        }
+        if let Some(seed) = machine.seed {
+            builder = builder.seed(seed);
+        }
+        if let Some(ticks) = machine.watchdog {
+            builder = builder.watchdog(ticks);
+        }
+        if let Some(directory) = self.save_directory() {
+            builder = builder.save_directory(directory);
+        }
+        if self.saves.rewind_depth > 0 {
+            builder = builder.rewind(self.saves.rewind_depth, self.saves.rewind_interval);
+        }
+        builder
+    }
+}
+
+/// Merges `overrides` into `base`, recursing into tables present in both.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let config = Config::from_toml(
+            r##"
+            [machine]
+            variant = "vip"
+            quirks = { wrap_sprites = true }
+
+            [display]
+            foreground = "#33FF66"
+            "##,
+        )
+        .expect("failed to parse config");
+        assert_eq!(config.machine.variant, Variant::Chip8);
+        assert_eq!(
+            config.quirks(),
+            Quirks {
+                wrap_sprites: true,
+                ..Variant::Chip8.quirks()
+            }
+        );
+        assert_eq!(config.display.foreground, Color::rgb(0x33, 0xFF, 0x66));
+        assert_eq!(config.display.background, Color::BLACK);
+        assert_eq!(
+            config.machine.instructions_per_second,
+            INSTRUCTIONS_PER_SECOND
+        );
+        assert_eq!(config.keypad_key("V"), Some(0xF));
+        assert_eq!(config.hotkeys().lookup("F5"), Some(Hotkey::QuickSave));
+    }
+
+    #[test]
+    fn layers_override_earlier_ones() {
+        let mut config = Config::from_toml("[machine]\nspeed = 2.0\nseed = 7").unwrap();
+        config
+            .layer_toml("[machine]\nspeed = 0.5\n[keymap]\nK = 0xA")
+            .expect("failed to layer config");
+        assert_eq!(config.machine.speed, 0.5);
+        assert_eq!(config.machine.seed, Some(7));
+        assert_eq!(config.keypad_key("K"), Some(0xA));
+        assert_eq!(
+            config.keypad_key("Z"),
+            Some(0xA),
+            "layering dropped a binding"
+        );
+
+        assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+    }
+
+    #[test]
+    fn rejects_bad_settings() {
+        assert!(matches!(
+            Config::from_toml("[display]\nforeground = \"green\""),
+            Err(ConfigError::Parse(_))
+        ));
+        assert_eq!(
+            Config::from_toml("[machine]\ntick_rate = 0.0"),
+            Err(ConfigError::Invalid("tick rate must be from 1 to 1000 hz"))
+        );
+        assert!(Config::from_toml("[machine]\ntick_rate = 1e-300").is_err());
+        assert!(Config::from_toml("[machine]\ntick_rate = 1e9").is_err());
+    }
+
+    #[test]
+    fn picks_out_live_changes() {
+        let config = Config::default();
+        let newer = Config::from_toml(
+            "[display]\nbackground = \"#102030\"\n[machine]\nspeed = 2.0\n[audio]\nvolume = 0.25",
+        )
+        .unwrap();
+        let changes = config.live_changes(&newer);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.contains(&LiveChange::Speed(Speed::scaled(2.0).unwrap())));
+        assert!(!config.needs_restart(&newer));
+
+        let newer =
+            Config::from_toml("[machine]\nspeed = 2.0\nquirks = { wrap_sprites = true }").unwrap();
+        assert_eq!(config.live_changes(&newer).len(), 1);
+        assert!(config.needs_restart(&newer));
+    }
+
+    #[test]
+    fn roms_get_their_own_settings() {
+        let rom = [0x12, 0x00];
+        let config = Config::from_toml(&format!(
+            "[machine]\nspeed = 2.0\n[roms.{}]\nmachine = {{ quirks = {{ wrap_sprites = true }} }}",
+            rom_id(&rom)
+        ))
+        .unwrap();
+        let directory = env::temp_dir().join(format!("chip8-roms-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("game.ch8");
+        fs::write(
+            directory.join("game.toml"),
+            "[display]\nforeground = \"#00FF00\"",
+        )
+        .unwrap();
+
+        let game = config
+            .for_rom(&rom, Some(&path))
+            .expect("failed to load rom settings");
+        assert!(game.quirks().wrap_sprites);
+        assert_eq!(game.machine.speed, 2.0);
+        assert_eq!(game.display.foreground, Color::rgb(0, 0xFF, 0));
+        let other = config.for_rom(&[0x00, 0xE0], None).unwrap();
+        assert!(!other.quirks().wrap_sprites);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn refuses_variants_that_cant_run() {
+        let refused =
+            ConfigError::Invalid("only CHIP-8 programs can run, not SUPER-CHIP or XO-CHIP ones");
+        for name in ["schip", "superchip", "xo-chip", "octo"] {
+            let toml = format!("[machine]\nvariant = \"{name}\"");
+            assert_eq!(Config::from_toml(&toml), Err(refused.clone()), "{name}");
+        }
+
+        let rom = [0x12, 0x00];
+        let config = Config::from_toml(&format!(
+            "[roms.{}]\nmachine = {{ variant = \"schip\" }}",
+            rom_id(&rom)
+        ))
+        .unwrap();
+        assert_eq!(config.for_rom(&rom, None), Err(refused));
+    }
+
+    #[test]
+    fn builds_configured_machines() {
+        let mut config =
+            Config::from_toml("[machine]\nram_size = 65536\n[machine.quirks]\nwrap_sprites = true")
+                .unwrap();
+        config.saves.directory = Some(env::temp_dir());
+        let machine = config
+            .builder()
+            .clock(
+                Box::new(crate::clock::ManualClock::new()),
+                config.tick_rate(),
+            )
+            .build()
+            .expect("failed to build machine");
+        assert_eq!(machine.memory().len(), 65536);
+        assert!(machine.cpu().quirks().wrap_sprites);
+
+        config.machine.variant = Variant::XoChip;
+        assert!(config.builder().build().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+
+use crate::watch::{FileWatcher, WatchError};
+
+use super::{Config, ConfigError, LiveChange};
+
+/// Watches a config file, reloading it when it changes and picking out the settings that can change live.
+///
+/// Only available with the `hot-reload` feature.
+pub struct ConfigWatcher {
+    file: FileWatcher,
+    current: Config,
+    /// The program the settings are for, and where it was loaded from, so its own settings are kept on reloads.
+    rom: Option<(Vec<u8>, Option<PathBuf>)>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching the file at `path`, which `current` was loaded from.
+    pub fn new(path: impl Into<PathBuf>, current: Config) -> Result<ConfigWatcher, ConfigError> {
+        Ok(ConfigWatcher {
+            file: FileWatcher::new(path).map_err(watch_error)?,
+            current,
+            rom: None,
+        })
+    }
+
+    /// Keeps a program's own settings over the file's whenever it's reloaded, as `Config::for_rom()` does.
+    pub fn set_rom(&mut self, rom: &[u8], path: Option<&Path>) {
+        self.rom = Some((rom.to_vec(), path.map(Path::to_path_buf)));
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// The settings in effect: those first loaded, with every live change since put over them.
+    pub fn config(&self) -> &Config {
+        &self.current
+    }
+
+    /// Reloads the file if it's changed since the last poll, giving the settings that changed and can be applied
+    /// while the machine runs. Never blocks.
+    ///
+    /// Changes that need a new machine are left out. A half-written or invalid file gives an error and leaves the
+    /// settings as they were, so callers can report it and keep polling.
+    pub fn poll(&mut self) -> Result<Vec<LiveChange>, ConfigError> {
+        if !self.file.poll().map_err(watch_error)? {
+            return Ok(Vec::new());
+        }
+        let mut newer = Config::from_file(self.file.path())?;
+        if let Some((rom, path)) = &self.rom {
+            newer = newer.for_rom(rom, path.as_deref())?;
+        }
+        let changes = self.current.live_changes(&newer);
+        for change in &changes {
+            self.current.apply(change);
+        }
+        Ok(changes)
+    }
+}
+
+fn watch_error(error: WatchError) -> ConfigError {
+    ConfigError::Watch(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use crate::speed::Speed;
+
+    use super::*;
+
+    #[test]
+    fn reloads_changed_files() {
+        let directory = std::env::temp_dir().join(format!("chip8-watch-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config.toml");
+        fs::write(&path, "[machine]\nspeed = 1.0").unwrap();
+        let mut watcher = ConfigWatcher::new(&path, Config::from_file(&path).unwrap())
+            .expect("failed to watch config");
+        assert_eq!(watcher.poll(), Ok(Vec::new()));
+
+        fs::write(&path, "[machine]\nspeed = 4.0\nvariant = \"xochip\"").unwrap();
+        let mut changes = Vec::new();
+        for _ in 0..100 {
+            // the write may be seen half done, which fails to parse until the rest of it lands
+            changes = watcher.poll().unwrap_or_default();
+            if !changes.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(
+            changes,
+            vec![LiveChange::Speed(Speed::scaled(4.0).unwrap())]
+        );
+        assert_eq!(watcher.config().machine.speed, 4.0);
+        assert_eq!(
+            watcher.config().machine.variant,
+            Default::default(),
+            "a change needing a restart was applied"
+        );
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
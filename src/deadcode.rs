@@ -0,0 +1,219 @@
+//! Finding the parts of a program it never uses, for trimming it down, as when squeezing it under a size limit.
+//!
+//! Code is found by following the program from its start, as `disassembler::follow()` does, and data by where the
+//! code points I. A run of the program adds what that can't see: code reached through `BNNN` jump tables, and data
+//! read at addresses worked out as it runs. Anything found neither way is likely unused, though a program can always
+//! reach further than one run of it shows.
+
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    decoder::Instruction,
+    disassembler::{follow, sweep},
+    machine::{Coverage, Touched},
+    memory::PROGRAM_START,
+    quirks::Variant,
+};
+
+/// Why some bytes of a program look unused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unused {
+    /// Code that following the program reaches, but that the run never ran, such as a branch never taken.
+    NeverRun,
+    /// Bytes that decode as instructions, but that nothing reaches, runs, or points I at.
+    Unreachable,
+    /// Bytes that nothing reaches, runs, points I at, or reads.
+    Data,
+}
+
+impl fmt::Display for Unused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Unused::NeverRun => "never run",
+            Unused::Unreachable => "unreachable code",
+            Unused::Data => "unused data",
+        })
+    }
+}
+
+/// A run of bytes of a program that look unused alike.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedRegion {
+    pub addresses: RangeInclusive<u16>,
+    pub kind: Unused,
+}
+
+/// The parts of a program that look unused, lowest address first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadCodeReport {
+    pub regions: Vec<UnusedRegion>,
+    /// How long the program is, in bytes.
+    pub rom_len: usize,
+}
+
+impl DeadCodeReport {
+    /// Looks through a program for what it never uses, by following it alone, or with the coverage of a run of it
+    /// too.
+    pub fn new(rom: &[u8], variant: Variant, coverage: Option<&Coverage>) -> DeadCodeReport {
+        let mut code = vec![false; rom.len()];
+        let mut pointed_at = Vec::new();
+        for line in follow(rom, variant) {
+            let Some(instruction) = line.instruction else {
+                continue;
+            };
+            let offset = line.address as usize - PROGRAM_START;
+            code[offset..offset + line.bytes.len()].fill(true);
+            if let Instruction::SetIndex { address } | Instruction::SetLongIndex { address } =
+                instruction
+            {
+                pointed_at.extend((address as usize).checked_sub(PROGRAM_START));
+            }
+        }
+        // what I points at runs on to the next code, as tables are indexed by adding to I
+        let mut data = vec![false; rom.len()];
+        for start in pointed_at {
+            for offset in start..rom.len() {
+                if code[offset] {
+                    break;
+                }
+                data[offset] = true;
+            }
+        }
+
+        let touched = |offset: usize| {
+            coverage
+                .map(|coverage| coverage.get((PROGRAM_START + offset) as u16))
+                .unwrap_or_default()
+        };
+        let kinds: Vec<Option<Unused>> = (0..rom.len())
+            .map(|offset| {
+                let touched = touched(offset);
+                if code[offset] {
+                    (coverage.is_some() && !touched.executed).then_some(Unused::NeverRun)
+                } else if data[offset] || touched != Touched::default() {
+                    None
+                } else {
+                    // whether it's code or data is worked out for the whole run of bytes
+                    Some(Unused::Data)
+                }
+            })
+            .collect();
+
+        let mut regions = Vec::new();
+        let mut start = 0;
+        for run in kinds.chunk_by(|a, b| a == b) {
+            let end = start + run.len();
+            if let Some(kind) = run[0] {
+                let kind = match kind {
+                    Unused::Data if looks_like_code(&rom[start..end], start, variant) => {
+                        Unused::Unreachable
+                    }
+                    kind => kind,
+                };
+                let address = |offset: usize| (PROGRAM_START + offset) as u16;
+                regions.push(UnusedRegion {
+                    addresses: address(start)..=address(end - 1),
+                    kind,
+                });
+            }
+            start = end;
+        }
+        DeadCodeReport {
+            regions,
+            rom_len: rom.len(),
+        }
+    }
+
+    /// How many bytes of the program look unused.
+    pub fn unused_bytes(&self) -> usize {
+        self.regions
+            .iter()
+            .map(|region| region.addresses.len())
+            .sum()
+    }
+}
+
+/// Whether some bytes at an offset into a program are whole instructions a program would run, lined up with the
+/// code around them. Runs of zeroes, as programs are padded with, are data.
+fn looks_like_code(bytes: &[u8], offset: usize, variant: Variant) -> bool {
+    let origin = (PROGRAM_START + offset) as u16;
+    offset.is_multiple_of(2)
+        && bytes.iter().any(|&byte| byte != 0)
+        && sweep(bytes, origin, variant).iter().all(|line| {
+            !matches!(
+                line.instruction,
+                None | Some(Instruction::Unknown { .. } | Instruction::MachineCall { .. })
+            )
+        })
+}
+
+/// Writes a line to each region, such as `0x2A0-0x2AF  16 bytes  unreachable code`, then the total.
+impl fmt::Display for DeadCodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for region in &self.regions {
+            let (start, end) = (region.addresses.start(), region.addresses.end());
+            let len = region.addresses.len();
+            let bytes = if len == 1 { "byte" } else { "bytes" };
+            writeln!(
+                f,
+                "0x{start:03X}-0x{end:03X}  {len} {bytes}  {}",
+                region.kind
+            )?;
+        }
+        writeln!(
+            f,
+            "{} of {} bytes look unused",
+            self.unused_bytes(),
+            self.rom_len
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble;
+
+    use super::*;
+
+    #[test]
+    fn finds_unreachable_code_and_unused_data() {
+        let rom = assemble(
+            "
+            LD I, sprite
+            SE V0, 0x01
+            CALL never
+            DRW V0, V0, 2
+            loop: JP loop
+            never: RET
+            orphan: CLS
+            JP loop
+            sprite: DB 0xF0
+            DB 0x90
+            ",
+            Variant::Chip8,
+        )
+        .expect("failed to assemble");
+        let report = DeadCodeReport::new(&rom, Variant::Chip8, None);
+        assert_eq!(
+            report.regions,
+            [UnusedRegion {
+                addresses: 0x20C..=0x20F,
+                kind: Unused::Unreachable,
+            }]
+        );
+
+        // V0 is never 1, so the call is skipped
+        let mut coverage = Coverage::new();
+        for pc in [0x200, 0x202, 0x206, 0x208] {
+            coverage.record_execution(pc, 2);
+        }
+        let report = DeadCodeReport::new(&rom, Variant::Chip8, Some(&coverage));
+        assert_eq!(
+            report.to_string(),
+            "0x204-0x205  2 bytes  never run\n\
+             0x20A-0x20B  2 bytes  never run\n\
+             0x20C-0x20F  4 bytes  unreachable code\n\
+             8 of 18 bytes look unused\n"
+        );
+    }
+}
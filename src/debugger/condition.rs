@@ -0,0 +1,359 @@
+use std::{fmt, str::FromStr};
+
+use crate::{machine::Chip8, system::Register};
+
+/// A condition on the machine's state, such as `V3 == 0x1F && I > 0x300`, that breakpoints and watchpoints can be
+/// made to stop only when it holds.
+///
+/// Conditions can compare registers, with `==`, `!=`, `<`, `<=`, `>`, and `>=`, and join comparisons with `&&`,
+/// `||`, and `!`. The operands are numbers, in decimal, `0x` hex, or `0b` binary, the registers `V0` through `VF`,
+/// `I`, `DT`, and `ST`, the program counter `PC`, the stack depth `SP`, and bytes of memory, as in `[I + 2]`. Sums,
+/// differences, and `&` can be taken of them, and anything can be grouped in parentheses. Any value other than 0
+/// counts as true.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expression: Expression,
+}
+
+/// Why a condition couldn't be parsed, and where in it, counting bytes from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for ConditionError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expression {
+    Number(i64),
+    Register(Register),
+    Pc,
+    Sp,
+    /// The byte of memory at an address.
+    Memory(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Subtract,
+    BitAnd,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, ConditionError> {
+        let mut parser = Parser {
+            source,
+            position: 0,
+        };
+        let expression = parser.or()?;
+        parser.skip_whitespace();
+        if parser.position < source.len() {
+            return Err(parser.error("expected an operator"));
+        }
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expression,
+        })
+    }
+
+    /// The condition as it was written.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the condition holds for the machine as it is now.
+    pub fn holds(&self, machine: &Chip8) -> bool {
+        evaluate(&self.expression, machine) != 0
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ConditionError;
+
+    fn from_str(source: &str) -> Result<Condition, ConditionError> {
+        Condition::parse(source)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn evaluate(expression: &Expression, machine: &Chip8) -> i64 {
+    match expression {
+        Expression::Number(value) => *value,
+        Expression::Register(register) => machine.register(*register) as i64,
+        Expression::Pc => machine.cpu().pc() as i64,
+        Expression::Sp => machine.cpu().stack().depth() as i64,
+        // memory past the end reads as 0, so a condition can't fail partway through running
+        Expression::Memory(address) => {
+            let address = evaluate(address, machine);
+            u16::try_from(address)
+                .ok()
+                .and_then(|address| machine.memory().read(address).ok())
+                .unwrap_or(0) as i64
+        }
+        Expression::Not(operand) => (evaluate(operand, machine) == 0) as i64,
+        Expression::Binary(left, operator, right) => {
+            let left = evaluate(left, machine);
+            // `&&` and `||` don't look at their right side when the left decides them
+            match operator {
+                Operator::Or if left != 0 => return 1,
+                Operator::And if left == 0 => return 0,
+                _ => {}
+            }
+            let right = evaluate(right, machine);
+            match operator {
+                Operator::Or | Operator::And => (right != 0) as i64,
+                Operator::Equal => (left == right) as i64,
+                Operator::NotEqual => (left != right) as i64,
+                Operator::Less => (left < right) as i64,
+                Operator::LessOrEqual => (left <= right) as i64,
+                Operator::Greater => (left > right) as i64,
+                Operator::GreaterOrEqual => (left >= right) as i64,
+                Operator::Add => left.wrapping_add(right),
+                Operator::Subtract => left.wrapping_sub(right),
+                Operator::BitAnd => left & right,
+            }
+        }
+    }
+}
+
+/// A recursive descent parser, a function to each level of precedence, loosest first.
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ConditionError {
+        ConditionError {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Takes the first of the operators that comes next.
+    fn operator(&mut self, operators: &[(&str, Operator)]) -> Option<Operator> {
+        self.skip_whitespace();
+        let rest = &self.source[self.position..];
+        let &(text, operator) = operators.iter().find(|(text, _)| rest.starts_with(text))?;
+        self.position += text.len();
+        Some(operator)
+    }
+
+    /// Parses a run of operands joined by the operators of one level, from left to right.
+    fn binary(
+        &mut self,
+        operators: &[(&str, Operator)],
+        operand: fn(&mut Self) -> Result<Expression, ConditionError>,
+    ) -> Result<Expression, ConditionError> {
+        let mut left = operand(self)?;
+        while let Some(operator) = self.operator(operators) {
+            let right = operand(self)?;
+            left = Expression::Binary(Box::new(left), operator, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expression, ConditionError> {
+        self.binary(&[("||", Operator::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expression, ConditionError> {
+        self.binary(&[("&&", Operator::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expression, ConditionError> {
+        // longer operators first, so `<=` isn't taken for `<`
+        let operators = [
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ];
+        let left = self.sum()?;
+        match self.operator(&operators) {
+            Some(operator) => {
+                let right = self.sum()?;
+                Ok(Expression::Binary(
+                    Box::new(left),
+                    operator,
+                    Box::new(right),
+                ))
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expression, ConditionError> {
+        self.binary(
+            &[("+", Operator::Add), ("-", Operator::Subtract)],
+            Self::bits,
+        )
+    }
+
+    fn bits(&mut self) -> Result<Expression, ConditionError> {
+        self.skip_whitespace();
+        let mut left = self.unary()?;
+        // `&` but not `&&`, which belongs to a looser level
+        loop {
+            self.skip_whitespace();
+            let rest = &self.source[self.position..];
+            if !rest.starts_with('&') || rest.starts_with("&&") {
+                return Ok(left);
+            }
+            self.position += 1;
+            let right = self.unary()?;
+            left = Expression::Binary(Box::new(left), Operator::BitAnd, Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, ConditionError> {
+        self.skip_whitespace();
+        let rest = &self.source[self.position..];
+        if rest.starts_with('!') && !rest.starts_with("!=") {
+            self.position += 1;
+            return Ok(Expression::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, ConditionError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let rest = &self.source[start..];
+        if let Some(open) = rest.chars().next().filter(|&c| c == '(' || c == '[') {
+            self.position += 1;
+            let inner = self.or()?;
+            self.skip_whitespace();
+            let close = if open == '(' { ')' } else { ']' };
+            if !self.source[self.position..].starts_with(close) {
+                return Err(self.error(&format!("expected `{close}`")));
+            }
+            self.position += 1;
+            return Ok(match open {
+                '(' => inner,
+                _ => Expression::Memory(Box::new(inner)),
+            });
+        }
+        let word: &str = rest
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        if word.is_empty() {
+            return Err(self.error("expected a number, register, or `(`"));
+        }
+        self.position += word.len();
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            return parse_number(word)
+                .map(Expression::Number)
+                .ok_or_else(|| ConditionError {
+                    position: start,
+                    message: format!("`{word}` is not a number"),
+                });
+        }
+        match word.to_ascii_uppercase().as_str() {
+            "PC" => Ok(Expression::Pc),
+            "SP" => Ok(Expression::Sp),
+            _ => word
+                .parse()
+                .map(Expression::Register)
+                .map_err(|_| ConditionError {
+                    position: start,
+                    message: format!("`{word}` is not a register"),
+                }),
+        }
+    }
+}
+
+pub(super) fn parse_number(word: &str) -> Option<i64> {
+    let lower = word.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{ManualClock, TickRate};
+
+    use super::*;
+
+    /// A machine that has run V3 = 0x1F, I = 0x301, and a step of a jump to itself.
+    fn machine() -> Chip8 {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        machine
+            .load_rom(&[0x63, 0x1F, 0xA3, 0x01, 0x12, 0x04])
+            .expect("failed to load rom");
+        machine.step_n(3).expect("failed to step");
+        machine
+    }
+
+    fn holds(source: &str) -> bool {
+        let condition: Condition = source.parse().expect("failed to parse condition");
+        condition.holds(&machine())
+    }
+
+    #[test]
+    fn evaluates_against_the_machine() {
+        assert!(holds("V3 == 0x1F && I > 0x300"));
+        assert!(!holds("v3 == 0x1f && i > 0x301"));
+        assert!(holds("V0 != 0 || PC == 0x204"));
+        assert!(holds("!(SP > 0) && DT <= 0"));
+        assert!(holds("(V3 & 0b11) == 3 && I - 1 == 0x300"));
+        // the first byte of the program is 0x63
+        assert!(holds("[0x200] == 0x63 && [PC] == 0x12"));
+    }
+
+    #[test]
+    fn reports_where_it_cant_parse() {
+        let error = |source: &str| Condition::parse(source).unwrap_err();
+        assert_eq!(
+            error("V3 == VG"),
+            ConditionError {
+                position: 6,
+                message: "`VG` is not a register".to_string()
+            }
+        );
+        assert_eq!(error("(V0 == 1").message, "expected `)`");
+        assert_eq!(error("V0 == 1 V1").position, 8);
+        assert_eq!(
+            error("V0 ==").message,
+            "expected a number, register, or `(`"
+        );
+    }
+}
@@ -0,0 +1,1093 @@
+//! A server for the Debug Adapter Protocol (<https://microsoft.github.io/debug-adapter-protocol/>), so editors such
+//! as VS Code can debug programs on the emulator with the debugging views they already have.
+//!
+//! There's no source to step through, so the server makes one: a disassembly of the program, followed from its
+//! start as `disassembler::follow()` does, which the editor asks for by reference. Breakpoints can be set on its
+//! lines, or on addresses from the editor's own disassembly view. The registers, stack, and screen are shown as
+//! variables, memory can be read and written, and the debugger's history lets the editor step backwards.
+//!
+//! One program is debugged at a time, as one thread. The machine has no window, so a program waiting on a key waits
+//! for good: the debugger stops there rather than running on.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    disassembler::{follow, sweep},
+    machine::{Chip8, Chip8Error, CrashReport},
+    system::{AccessKind, Register},
+};
+
+use super::{
+    Catchpoint, Condition, Debugger, Edit, MemoryWatchpoint, StopReason, Symbols, Watchpoint,
+};
+
+/// The one thread a machine has, as the editor sees it.
+const THREAD: u64 = 1;
+
+/// The one source there is, the program's disassembly.
+const LISTING: u64 = 1;
+
+/// The groups of variables the editor is shown.
+const REGISTERS: u64 = 1;
+const STACK: u64 = 2;
+const SCREEN: u64 = 3;
+
+/// A run of the debugger the editor asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+    StepBack,
+    ReverseContinue,
+}
+
+/// What to do once a request's been answered.
+enum After {
+    /// Tell the editor it can set its breakpoints.
+    Initialized,
+    Run(Motion),
+    /// Tell the editor the machine stopped without running, as at the start of the program.
+    Stopped(&'static str),
+    Disconnect,
+}
+
+/// The program's disassembly as the editor is shown it, a line to each instruction or byte of data, and another to
+/// each label.
+struct Listing {
+    name: String,
+    text: String,
+    /// The address of each line, or `None` for a label's.
+    addresses: Vec<Option<u16>>,
+}
+
+impl Listing {
+    fn new(name: String, debugger: &Debugger) -> Listing {
+        let machine = debugger.machine();
+        let mut lines = follow(machine.rom(), machine.variant());
+        debugger.symbols().apply(&mut lines);
+        let mut text = String::new();
+        let mut addresses = Vec::new();
+        for line in lines {
+            if let Some(label) = &line.label {
+                text.push_str(&format!("{label}:\n"));
+                addresses.push(None);
+            }
+            text.push_str(&format!("{line}\n"));
+            addresses.push(Some(line.address));
+        }
+        Listing {
+            name,
+            text,
+            addresses,
+        }
+    }
+
+    /// The line, counting from 1, that an address is on, if it starts one.
+    fn line(&self, address: u16) -> Option<usize> {
+        self.addresses
+            .iter()
+            .position(|&line| line == Some(address))
+            .map(|index| index + 1)
+    }
+
+    /// The address a line, counting from 1, stops at: a label's stops at what it labels.
+    fn address(&self, line: usize) -> Option<u16> {
+        self.addresses
+            .get(line.checked_sub(1)?..)?
+            .iter()
+            .find_map(|&address| address)
+    }
+
+    fn source(&self) -> Value {
+        json!({ "name": self.name, "sourceReference": LISTING })
+    }
+}
+
+/// A program being debugged.
+struct Session {
+    debugger: Debugger,
+    listing: Listing,
+    stop_on_entry: bool,
+    /// The breakpoints the editor set on lines of the listing, and on addresses, which it replaces a kind at a time.
+    line_breakpoints: Vec<u16>,
+    address_breakpoints: Vec<u16>,
+}
+
+struct Server<W, L> {
+    output: W,
+    seq: u64,
+    launch: L,
+    session: Option<Session>,
+    /// The stop flag of the machine being debugged, for the thread reading requests to interrupt it with.
+    stop_flag: Arc<Mutex<Option<Arc<AtomicBool>>>>,
+    after: Option<After>,
+}
+
+/// Serves one editor the protocol over `input` and `output`, such as stdin and stdout or a socket, until it
+/// disconnects.
+///
+/// The editor's launch request names the program to debug, which `launch` builds a machine for and loads, as the
+/// command line does. It can also give `symbols`, a symbol file to name addresses with and set the breakpoints it
+/// marks, and `stopOnEntry`, to stop before the first instruction.
+pub fn serve(
+    input: impl Read + Send + 'static,
+    output: impl Write,
+    launch: impl FnMut(&Path) -> Result<Chip8, String>,
+) -> io::Result<()> {
+    let stop_flag: Arc<Mutex<Option<Arc<AtomicBool>>>> = Arc::default();
+    let (sender, requests) = mpsc::channel();
+    let reader_flag = Arc::clone(&stop_flag);
+    // requests are read on their own thread, so a pause can stop the machine while it runs
+    thread::spawn(move || {
+        let interrupt = || {
+            if let Some(flag) = &*reader_flag.lock().unwrap() {
+                flag.store(true, Ordering::Relaxed);
+            }
+        };
+        let mut input = BufReader::new(input);
+        loop {
+            let message = read_message(&mut input);
+            match &message {
+                Ok(Some(request)) => {
+                    if let Some("pause" | "disconnect" | "terminate") = request["command"].as_str()
+                    {
+                        interrupt();
+                    }
+                }
+                Ok(None) | Err(_) => interrupt(),
+            }
+            let done = !matches!(message, Ok(Some(_)));
+            if sender.send(message).is_err() || done {
+                break;
+            }
+        }
+    });
+
+    let mut server = Server {
+        output,
+        seq: 0,
+        launch,
+        session: None,
+        stop_flag,
+        after: None,
+    };
+    for message in requests {
+        let Some(request) = message? else {
+            break;
+        };
+        if !server.handle(&request)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a message: a `Content-Length` header and a blank line, then that many bytes of JSON. Returns `None` at the
+/// end of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message has no Content-Length")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = serde_json::to_vec(message)?;
+    write!(output, "Content-Length: {}\r\n\r\n", body.len())?;
+    output.write_all(&body)?;
+    output.flush()
+}
+
+impl<W: Write, L: FnMut(&Path) -> Result<Chip8, String>> Server<W, L> {
+    /// Answers a request, then does what it asked for, returning whether to go on serving.
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let result = self.request(command, &request["arguments"]);
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = message.into(),
+        }
+        self.send(response)?;
+        match self.after.take() {
+            None => {}
+            Some(After::Initialized) => self.event("initialized", Value::Null)?,
+            Some(After::Run(motion)) => self.run(motion)?,
+            Some(After::Stopped(reason)) => self.stopped(reason, None)?,
+            Some(After::Disconnect) => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn request(&mut self, command: &str, arguments: &Value) -> Result<Value, String> {
+        let motion = match command {
+            "initialize" => {
+                self.after = Some(After::Initialized);
+                return Ok(capabilities());
+            }
+            "launch" => return self.launch(arguments),
+            "disconnect" | "terminate" => {
+                self.after = Some(After::Disconnect);
+                return Ok(Value::Null);
+            }
+            "threads" => return Ok(json!({ "threads": [{ "id": THREAD, "name": "CHIP-8" }] })),
+            "continue" => Motion::Continue,
+            "next" => Motion::StepOver,
+            "stepIn" => Motion::StepIn,
+            "stepOut" => Motion::StepOut,
+            "stepBack" => Motion::StepBack,
+            "reverseContinue" => Motion::ReverseContinue,
+            _ => {
+                let session = self
+                    .session
+                    .as_mut()
+                    .ok_or("no program has been launched")?;
+                return session.request(command, arguments, &mut self.after);
+            }
+        };
+        if self.session.is_none() {
+            return Err("no program has been launched".to_string());
+        }
+        self.after = Some(After::Run(motion));
+        Ok(match motion {
+            Motion::Continue => json!({ "allThreadsContinued": true }),
+            _ => Value::Null,
+        })
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or("launch needs the `program` to debug")?;
+        let program = PathBuf::from(program);
+        let machine = (self.launch)(&program)?;
+        *self.stop_flag.lock().unwrap() = Some(machine.stop_flag());
+        let mut debugger = Debugger::new(machine);
+        if let Some(path) = arguments["symbols"].as_str() {
+            let text = fs::read_to_string(path)
+                .map_err(|error| format!("could not read {path}: {error}"))?;
+            let symbols = Symbols::parse(&text).map_err(|error| format!("{path}: {error}"))?;
+            debugger.load_symbols(&symbols);
+        }
+        let name = program.file_name().unwrap_or(program.as_os_str());
+        let listing = Listing::new(format!("{}.s", name.to_string_lossy()), &debugger);
+        self.session = Some(Session {
+            debugger,
+            listing,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+            line_breakpoints: Vec::new(),
+            address_breakpoints: Vec::new(),
+        });
+        Ok(Value::Null)
+    }
+
+    /// Runs the debugger as the editor asked, then tells it why it stopped.
+    fn run(&mut self, motion: Motion) -> io::Result<()> {
+        let Some(session) = self.session.as_mut() else {
+            return Ok(());
+        };
+        let debugger = &mut session.debugger;
+        let result = match motion {
+            Motion::Continue => debugger.continue_(),
+            Motion::StepIn => debugger.step(),
+            Motion::StepOver => debugger.step_over(),
+            Motion::StepOut => debugger.step_out(),
+            Motion::StepBack => debugger.step_back(),
+            Motion::ReverseContinue => debugger.reverse_continue(),
+        };
+        match result {
+            Ok(reason) => {
+                // stepping in runs a single instruction, which the debug console explains
+                if let (Motion::StepIn, StopReason::Step) = (motion, reason) {
+                    if let Some(explanation) = session.debugger.explain_last() {
+                        let body =
+                            json!({ "category": "console", "output": format!("{explanation}\n") });
+                        self.event("output", body)?;
+                    }
+                }
+                let (reason, description) = describe(reason);
+                self.stopped(reason, description)
+            }
+            Err(Chip8Error::Stopped) => {
+                self.event("terminated", Value::Null)?;
+                Ok(())
+            }
+            Err(error) => {
+                // the debug console gets the whole story of a crash
+                if let Chip8Error::Cpu(cpu_error) = error {
+                    let report = CrashReport::new(session.debugger.machine(), cpu_error);
+                    let body = json!({ "category": "stderr", "output": report.to_string() });
+                    self.event("output", body)?;
+                }
+                self.stopped("exception", Some(error.to_string()))
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: &str, description: Option<String>) -> io::Result<()> {
+        let mut body = json!({ "reason": reason, "threadId": THREAD, "allThreadsStopped": true });
+        if let Some(description) = description {
+            body["description"] = description.clone().into();
+            body["text"] = description.into();
+        }
+        self.event("stopped", body)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message)
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        write_message(&mut self.output, &message)
+    }
+}
+
+/// What the server can do, as the editor's told in answer to `initialize`.
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsDataBreakpoints": true,
+        "supportsDataBreakpointBytes": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsDisassembleRequest": true,
+        "supportsReadMemoryRequest": true,
+        "supportsWriteMemoryRequest": true,
+        "supportsSetVariable": true,
+        "supportsStepBack": true,
+        "supportsTerminateRequest": true,
+        "supportsSteppingGranularity": false,
+        "exceptionBreakpointFilters": [
+            { "filter": "draw", "label": "Sprite drawn" },
+            { "filter": "collision", "label": "Sprite collided" },
+            { "filter": "key", "label": "Key read" },
+        ],
+    })
+}
+
+/// The reason the protocol gives for the debugger stopping, and a description of it if there's more to say.
+fn describe(reason: StopReason) -> (&'static str, Option<String>) {
+    match reason {
+        StopReason::Breakpoint(_) => ("breakpoint", None),
+        StopReason::Watchpoint { register, value } => (
+            "data breakpoint",
+            Some(format!("{register} = 0x{value:02X}")),
+        ),
+        StopReason::MemoryWatchpoint { pc, access } => {
+            let (address, value) = (access.address, access.value);
+            let description = match access.kind {
+                AccessKind::Read => format!("0x{pc:03X} read 0x{value:02X} from 0x{address:03X}"),
+                AccessKind::Write => format!("0x{pc:03X} wrote 0x{value:02X} to 0x{address:03X}"),
+            };
+            ("data breakpoint", Some(description))
+        }
+        StopReason::Draw { collision: true } => ("exception", Some("sprite collided".to_string())),
+        StopReason::Draw { collision: false } => ("exception", Some("sprite drawn".to_string())),
+        StopReason::KeyRead { key } => ("exception", Some(format!("key {key:X} read"))),
+        StopReason::InfiniteLoop(pc) => ("pause", Some(format!("looping forever at 0x{pc:03X}"))),
+        StopReason::WaitingForKey => ("pause", Some("waiting for a key".to_string())),
+        StopReason::Interrupted => ("pause", None),
+        StopReason::HistoryStart => ("step", Some("as far back as the history goes".to_string())),
+        StopReason::Step | StopReason::StepBack | StopReason::Frame => ("step", None),
+    }
+}
+
+impl Session {
+    fn request(
+        &mut self,
+        command: &str,
+        arguments: &Value,
+        after: &mut Option<After>,
+    ) -> Result<Value, String> {
+        match command {
+            "configurationDone" => {
+                *after = Some(match self.stop_on_entry {
+                    true => After::Stopped("entry"),
+                    false => After::Run(Motion::Continue),
+                });
+                Ok(Value::Null)
+            }
+            "pause" => Ok(Value::Null),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(arguments),
+            "setExceptionBreakpoints" => self.set_exception_breakpoints(arguments),
+            "dataBreakpointInfo" => Ok(self.data_breakpoint_info(arguments)),
+            "setDataBreakpoints" => self.set_data_breakpoints(arguments),
+            "source" => Ok(json!({ "content": self.listing.text })),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                { "name": "Stack", "variablesReference": STACK, "expensive": false },
+                { "name": "Screen", "variablesReference": SCREEN, "expensive": false },
+            ]})),
+            "variables" => {
+                Ok(json!({ "variables": self.variables(arguments["variablesReference"].as_u64()) }))
+            }
+            "setVariable" => self.set_variable(arguments),
+            "evaluate" => self.evaluate(arguments["expression"].as_str().unwrap_or_default()),
+            "readMemory" => self.read_memory(arguments),
+            "writeMemory" => self.write_memory(arguments),
+            "disassemble" => self.disassemble(arguments),
+            _ => Err(format!("`{command}` isn't supported")),
+        }
+    }
+
+    /// Replaces the breakpoints on lines of the listing, answering which lines they could be set on.
+    fn set_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let mut set = Vec::new();
+        let mut breakpoints = Vec::new();
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+            let address = self.listing.address(line);
+            let result = match address {
+                Some(address) => self
+                    .add_breakpoint(address, &breakpoint["condition"])
+                    .map(|()| address),
+                None => Err("no instruction on this line".to_string()),
+            };
+            breakpoints.push(match result {
+                Ok(address) => {
+                    set.push(address);
+                    json!({ "verified": true, "line": self.listing.line(address), "source": self.listing.source() })
+                }
+                Err(message) => json!({ "verified": false, "line": line, "message": message }),
+            });
+        }
+        let old = std::mem::replace(&mut self.line_breakpoints, set);
+        self.forget_breakpoints(old);
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Replaces the breakpoints on addresses, as set from the editor's disassembly view.
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        let mut set = Vec::new();
+        let mut breakpoints = Vec::new();
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let result = self
+                .address(&breakpoint["instructionReference"], &breakpoint["offset"])
+                .and_then(|address| {
+                    self.add_breakpoint(address, &breakpoint["condition"])
+                        .map(|()| address)
+                });
+            breakpoints.push(match result {
+                Ok(address) => {
+                    set.push(address);
+                    json!({ "verified": true, "instructionReference": format!("0x{address:03X}") })
+                }
+                Err(message) => json!({ "verified": false, "message": message }),
+            });
+        }
+        let old = std::mem::replace(&mut self.address_breakpoints, set);
+        self.forget_breakpoints(old);
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn add_breakpoint(&mut self, address: u16, condition: &Value) -> Result<(), String> {
+        match condition
+            .as_str()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+        {
+            Some(text) => {
+                let condition = Condition::parse(text).map_err(|error| error.to_string())?;
+                self.debugger.add_conditional_breakpoint(address, condition);
+            }
+            None => {
+                self.debugger.add_breakpoint(address);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes breakpoints that were replaced, unless they've been set again some other way.
+    fn forget_breakpoints(&mut self, old: Vec<u16>) {
+        for address in old {
+            if !self.line_breakpoints.contains(&address)
+                && !self.address_breakpoints.contains(&address)
+            {
+                self.debugger.remove_breakpoint(address);
+            }
+        }
+    }
+
+    /// What can be watched of a register, or of an address or range of addresses, such as `0x300..0x340`. Registers
+    /// are only watched for writes, and memory for reads and writes too.
+    fn data_breakpoint_info(&self, arguments: &Value) -> Value {
+        let name = arguments["name"].as_str().unwrap_or_default();
+        if arguments["variablesReference"].as_u64() == Some(REGISTERS) {
+            if let Ok(register) = name.parse::<Register>() {
+                return json!({
+                    "dataId": register.to_string(),
+                    "description": format!("writes to {register}"),
+                    "accessTypes": ["write"],
+                });
+            }
+        }
+        let symbols = self.debugger.symbols();
+        let watchpoint = match (symbols.resolve(name), arguments["bytes"].as_u64()) {
+            (Some(address), bytes) => {
+                let end = address as u64 + bytes.unwrap_or(1).max(1) - 1;
+                Ok(MemoryWatchpoint::writes(
+                    address..=end.min(u16::MAX as u64) as u16,
+                ))
+            }
+            (None, _) => MemoryWatchpoint::parse(name, symbols),
+        };
+        match watchpoint {
+            Ok(watchpoint) => {
+                let (start, end) = (watchpoint.addresses.start(), watchpoint.addresses.end());
+                json!({
+                    "dataId": format!("0x{start:03X}..=0x{end:03X}"),
+                    "description": format!("0x{start:03X}-0x{end:03X}"),
+                    "accessTypes": ["read", "write", "readWrite"],
+                })
+            }
+            Err(message) => json!({ "dataId": null, "description": message }),
+        }
+    }
+
+    /// Replaces every watchpoint with the data breakpoints the editor has.
+    fn set_data_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        self.debugger.clear_watchpoints();
+        self.debugger.clear_memory_watchpoints();
+        let mut breakpoints = Vec::new();
+        for breakpoint in arguments["breakpoints"].as_array().into_iter().flatten() {
+            let result = self.add_data_breakpoint(breakpoint);
+            breakpoints.push(match result {
+                Ok(()) => json!({ "verified": true }),
+                Err(message) => json!({ "verified": false, "message": message }),
+            });
+        }
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn add_data_breakpoint(&mut self, breakpoint: &Value) -> Result<(), String> {
+        let id = breakpoint["dataId"].as_str().unwrap_or_default();
+        let condition = match breakpoint["condition"].as_str().map(str::trim) {
+            Some(text) if !text.is_empty() => {
+                Some(Condition::parse(text).map_err(|error| error.to_string())?)
+            }
+            _ => None,
+        };
+        if let Ok(register) = id.parse::<Register>() {
+            let watchpoint = Watchpoint::any(register);
+            self.debugger.add_watchpoint(match condition {
+                Some(condition) => watchpoint.when(condition),
+                None => watchpoint,
+            });
+            return Ok(());
+        }
+        let kind = match breakpoint["accessType"].as_str() {
+            Some("read") => "read",
+            Some("readWrite") => "access",
+            _ => "write",
+        };
+        let watchpoint = MemoryWatchpoint::parse(&format!("{id} {kind}"), self.debugger.symbols())?;
+        self.debugger.add_memory_watchpoint(match condition {
+            Some(condition) => watchpoint.when(condition),
+            None => watchpoint,
+        });
+        Ok(())
+    }
+
+    /// Sets the catchpoints the editor shows as exception breakpoints.
+    fn set_exception_breakpoints(&mut self, arguments: &Value) -> Result<Value, String> {
+        self.debugger.clear_catchpoints();
+        for filter in arguments["filters"].as_array().into_iter().flatten() {
+            let catchpoint = match filter.as_str() {
+                Some("draw") => Catchpoint::Draw {
+                    collisions_only: false,
+                },
+                Some("collision") => Catchpoint::Draw {
+                    collisions_only: true,
+                },
+                Some("key") => Catchpoint::Key(None),
+                _ => return Err(format!("unknown exception filter {filter}")),
+            };
+            self.debugger.add_catchpoint(catchpoint);
+        }
+        Ok(Value::Null)
+    }
+
+    /// The instruction the machine is stopped on, then the call to each subroutine it's inside, innermost first.
+    fn stack_trace(&self) -> Value {
+        let pc = self.debugger.machine().cpu().pc();
+        let calls = self.debugger.call_stack().into_iter().map(|frame| {
+            frame
+                .call_site
+                .unwrap_or(frame.return_address.wrapping_sub(2))
+        });
+        let frames: Vec<Value> = std::iter::once(pc)
+            .chain(calls)
+            .enumerate()
+            .map(|(id, address)| {
+                let name = self
+                    .debugger
+                    .symbols()
+                    .describe(address)
+                    .unwrap_or_else(|| format!("0x{address:03X}"));
+                let mut frame = json!({
+                    "id": id,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("0x{address:03X}"),
+                });
+                if let Some(line) = self.listing.line(address) {
+                    frame["line"] = line.into();
+                    frame["column"] = 1.into();
+                    frame["source"] = self.listing.source();
+                }
+                frame
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn variables(&self, reference: Option<u64>) -> Vec<Value> {
+        let machine = self.debugger.machine();
+        match reference {
+            Some(REGISTERS) => {
+                let byte = |name: String, value: u16| json!({ "name": name, "value": format!("0x{value:02X}"), "variablesReference": 0 });
+                let address = |name: &str, value: u16| {
+                    json!({
+                        "name": name,
+                        "value": format!("0x{value:03X}"),
+                        "variablesReference": 0,
+                        "memoryReference": format!("0x{value:03X}"),
+                    })
+                };
+                let mut variables: Vec<Value> = (0..16)
+                    .map(Register::V)
+                    .map(|register| byte(register.to_string(), machine.register(register)))
+                    .collect();
+                variables.push(address("I", machine.register(Register::Index)));
+                variables.push(address("PC", machine.cpu().pc()));
+                variables.push(byte("DT".to_string(), machine.register(Register::Delay)));
+                variables.push(byte("ST".to_string(), machine.register(Register::Sound)));
+                variables
+            }
+            Some(STACK) => machine
+                .cpu()
+                .stack()
+                .entries()
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(depth, &address)| {
+                    json!({
+                        "name": depth.to_string(),
+                        "value": format!("0x{address:03X}"),
+                        "variablesReference": 0,
+                        "memoryReference": format!("0x{address:03X}"),
+                    })
+                })
+                .collect(),
+            Some(SCREEN) => {
+                let frame = machine.display().frame();
+                (0..frame.height())
+                    .map(|y| {
+                        let row: String = (0..frame.width())
+                            .map(|x| if frame.get_pixel(x, y) { '█' } else { '·' })
+                            .collect();
+                        json!({ "name": format!("{y:02}"), "value": row, "variablesReference": 0 })
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn set_variable(&mut self, arguments: &Value) -> Result<Value, String> {
+        if arguments["variablesReference"].as_u64() != Some(REGISTERS) {
+            return Err("only registers can be set".to_string());
+        }
+        let name = arguments["name"].as_str().unwrap_or_default();
+        let value = arguments["value"].as_str().unwrap_or_default();
+        let edit = Edit::parse(&format!("{name} = {value}"), self.debugger.symbols())?;
+        self.debugger
+            .edit(edit)
+            .map_err(|error| error.to_string())?;
+        let value = edit.to_string();
+        let value = value.split_once("= ").map_or(&*value, |(_, value)| value);
+        Ok(json!({ "value": value }))
+    }
+
+    /// Evaluates an edit, such as `V3 = 0x10`, a register, or an address, giving the byte there.
+    fn evaluate(&mut self, expression: &str) -> Result<Value, String> {
+        let expression = expression.trim();
+        if expression.contains('=') && !expression.contains("==") {
+            let edit = Edit::parse(expression, self.debugger.symbols())?;
+            self.debugger
+                .edit(edit)
+                .map_err(|error| error.to_string())?;
+            return Ok(json!({ "result": edit.to_string(), "variablesReference": 0 }));
+        }
+        let machine = self.debugger.machine();
+        if expression.eq_ignore_ascii_case("PC") {
+            let pc = machine.cpu().pc();
+            return Ok(json!({ "result": format!("0x{pc:03X}"), "variablesReference": 0 }));
+        }
+        if let Ok(register) = expression.parse::<Register>() {
+            let value = machine.register(register);
+            return Ok(json!({ "result": format!("0x{value:02X}"), "variablesReference": 0 }));
+        }
+        let address = self
+            .debugger
+            .symbols()
+            .resolve(expression)
+            .ok_or_else(|| format!("`{expression}` is not a register, address, or label"))?;
+        let value = machine
+            .memory()
+            .read(address)
+            .map_err(|_| format!("0x{address:03X} is past the end of memory"))?;
+        Ok(json!({
+            "result": format!("[0x{address:03X}] = 0x{value:02X}"),
+            "variablesReference": 0,
+            "memoryReference": format!("0x{address:03X}"),
+        }))
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, String> {
+        let address = self.address(&arguments["memoryReference"], &arguments["offset"])?;
+        let memory = self.debugger.machine().memory();
+        let count = arguments["count"].as_u64().unwrap_or_default() as usize;
+        let readable = count.min(memory.len().saturating_sub(address as usize));
+        let bytes = memory
+            .slice(address, readable)
+            .map_err(|_| format!("0x{address:03X} is past the end of memory"))?;
+        Ok(json!({
+            "address": format!("0x{address:03X}"),
+            "data": encode_base64(bytes),
+            "unreadableBytes": count - readable,
+        }))
+    }
+
+    fn write_memory(&mut self, arguments: &Value) -> Result<Value, String> {
+        let address = self.address(&arguments["memoryReference"], &arguments["offset"])?;
+        let data = arguments["data"].as_str().unwrap_or_default();
+        let bytes = decode_base64(data).ok_or("the data isn't base64")?;
+        for (offset, &value) in bytes.iter().enumerate() {
+            let address = address.wrapping_add(offset as u16);
+            self.debugger
+                .edit(Edit::Memory { address, value })
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(json!({ "bytesWritten": bytes.len() }))
+    }
+
+    /// Disassembles memory as it is now, for the editor's disassembly view. Instructions are counted as two bytes
+    /// going backwards, as where they start can't be told.
+    fn disassemble(&self, arguments: &Value) -> Result<Value, String> {
+        let address = self.address(&arguments["memoryReference"], &arguments["offset"])?;
+        let machine = self.debugger.machine();
+        let memory = machine.memory();
+        let count = arguments["instructionCount"].as_u64().unwrap_or_default() as usize;
+        let skip = arguments["instructionOffset"].as_i64().unwrap_or_default();
+        let start = (address as i64 + skip * 2).clamp(0, memory.len() as i64) as usize;
+        // enough bytes for the longest instructions
+        let len = (count * 4).min(memory.len() - start);
+        let bytes = memory
+            .slice(start as u16, len)
+            .map_err(|_| format!("0x{start:03X} is past the end of memory"))?;
+        let mut instructions: Vec<Value> = sweep(bytes, start as u16, machine.variant())
+            .into_iter()
+            .take(count)
+            .map(|line| {
+                let bytes: String = line
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("{byte:02X}"))
+                    .collect();
+                let mut instruction = json!({
+                    "address": format!("0x{:03X}", line.address),
+                    "instructionBytes": bytes,
+                    "instruction": line.source(),
+                });
+                if let Some(symbol) = self.debugger.symbols().name(line.address) {
+                    instruction["symbol"] = symbol.into();
+                }
+                if let Some(number) = self.listing.line(line.address) {
+                    instruction["location"] = self.listing.source();
+                    instruction["line"] = number.into();
+                }
+                instruction
+            })
+            .collect();
+        // the editor asks for a fixed number, even past the end of memory
+        while instructions.len() < count {
+            let address = start + len + instructions.len();
+            instructions.push(json!({
+                "address": format!("0x{address:03X}"),
+                "instruction": "",
+                "presentationHint": "invalid",
+            }));
+        }
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    /// The address a memory reference and an offset from it point to.
+    fn address(&self, reference: &Value, offset: &Value) -> Result<u16, String> {
+        let reference = reference.as_str().unwrap_or_default();
+        let address = self
+            .debugger
+            .symbols()
+            .resolve(reference)
+            .ok_or_else(|| format!("`{reference}` is not an address"))?;
+        let address = address as i64 + offset.as_i64().unwrap_or_default();
+        u16::try_from(address).map_err(|_| format!("0x{address:X} is not an address"))
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes as base64, which the protocol sends memory as.
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (index, &byte)| {
+            word | (byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(BASE64[(word >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u32> = text
+        .trim_end_matches('=')
+        .bytes()
+        .map(|digit| {
+            BASE64
+                .iter()
+                .position(|&known| known == digit)
+                .map(|value| value as u32)
+        })
+        .collect::<Option<_>>()?;
+    let mut bytes = Vec::new();
+    for chunk in digits.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let word = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |word, (index, &digit)| word | digit << (18 - 6 * index));
+        for index in 0..chunk.len() - 1 {
+            bytes.push((word >> (16 - 8 * index)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc::Receiver, time::Duration};
+
+    use crate::{
+        assembler::assemble,
+        machine::{Chip8Builder, Chip8Error},
+        quirks::Variant,
+    };
+
+    use super::*;
+
+    /// Input fed to the server a piece at a time, as an editor would send it.
+    struct Feed {
+        pieces: Receiver<Vec<u8>>,
+        buffer: io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for Feed {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            if self.buffer.position() as usize == self.buffer.get_ref().len() {
+                match self.pieces.recv() {
+                    Ok(piece) => self.buffer = io::Cursor::new(piece),
+                    Err(_) => return Ok(0),
+                }
+            }
+            self.buffer.read(buffer)
+        }
+    }
+
+    /// A writer whose output can be read while the server has it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn messages(&self) -> Vec<Value> {
+            let written = self.0.lock().unwrap().clone();
+            let mut input = io::Cursor::new(written);
+            std::iter::from_fn(|| read_message(&mut input).unwrap()).collect()
+        }
+    }
+
+    fn request(seq: u64, command: &str, arguments: Value) -> Vec<u8> {
+        let mut framed = Vec::new();
+        let message =
+            json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments });
+        write_message(&mut framed, &message).unwrap();
+        framed
+    }
+
+    #[test]
+    fn debugs_a_program_for_an_editor() {
+        let source = "
+            LD V0, 0x05
+            CALL draw
+            loop: JP loop
+            draw: LD I, 0x300
+            RET
+        ";
+        let rom = assemble(source, Variant::Chip8).expect("failed to assemble");
+        let path = std::env::temp_dir().join(format!("dap-{}.ch8", std::process::id()));
+        fs::write(&path, &rom).unwrap();
+
+        let (pieces, feed) = mpsc::channel();
+        let input = Feed {
+            pieces: feed,
+            buffer: io::Cursor::new(Vec::new()),
+        };
+        let output = Shared::default();
+        let server = {
+            let output = output.clone();
+            thread::spawn(move || {
+                serve(input, output, move |path: &Path| {
+                    let rom = fs::read(path).map_err(|error| error.to_string())?;
+                    let mut machine = Chip8Builder::new()
+                        .build()
+                        .map_err(|error: Chip8Error| error.to_string())?;
+                    machine.load_rom(&rom).map_err(|error| error.to_string())?;
+                    Ok(machine)
+                })
+            })
+        };
+
+        // the listing has a line for the label, so the breakpoint on RET is on line 7
+        let program = path.to_string_lossy();
+        for piece in [
+            request(1, "initialize", json!({ "adapterID": "chip8" })),
+            request(2, "launch", json!({ "program": program })),
+            request(
+                3,
+                "setBreakpoints",
+                json!({ "source": { "sourceReference": 1 }, "breakpoints": [{ "line": 7 }] }),
+            ),
+            request(4, "configurationDone", Value::Null),
+        ] {
+            pieces.send(piece).unwrap();
+        }
+        let stopped =
+            |messages: &[Value]| messages.iter().any(|message| message["event"] == "stopped");
+        for _ in 0..500 {
+            if stopped(&output.messages()) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        for piece in [
+            request(5, "stackTrace", json!({ "threadId": 1 })),
+            request(6, "variables", json!({ "variablesReference": REGISTERS })),
+            request(
+                7,
+                "writeMemory",
+                json!({ "memoryReference": "0x300", "data": "q80=" }),
+            ),
+            request(
+                8,
+                "readMemory",
+                json!({ "memoryReference": "0x2FF", "count": 3 }),
+            ),
+            request(9, "disconnect", Value::Null),
+        ] {
+            pieces.send(piece).unwrap();
+        }
+        server.join().unwrap().expect("the server failed");
+        fs::remove_file(&path).unwrap();
+
+        let messages = output.messages();
+        let response = |seq: u64| {
+            messages
+                .iter()
+                .find(|message| message["request_seq"] == seq)
+                .unwrap_or_else(|| panic!("no response to request {seq}"))
+        };
+        assert!(messages.iter().all(|message| message["success"] != false));
+        assert_eq!(response(1)["body"]["supportsStepBack"], true);
+        assert_eq!(response(3)["body"]["breakpoints"][0]["verified"], true);
+        let stopped = messages
+            .iter()
+            .find(|message| message["event"] == "stopped")
+            .unwrap();
+        assert_eq!(stopped["body"]["reason"], "breakpoint");
+
+        let frames = &response(5)["body"]["stackFrames"];
+        assert_eq!(frames[0]["instructionPointerReference"], "0x208");
+        assert_eq!(frames[0]["line"], 7);
+        assert_eq!(frames[1]["instructionPointerReference"], "0x202");
+        let registers = &response(6)["body"]["variables"];
+        assert_eq!(registers[0]["value"], "0x05");
+        assert_eq!(registers[16]["value"], "0x300");
+        assert_eq!(response(8)["body"]["data"], encode_base64(&[0, 0xAB, 0xCD]));
+        assert_eq!(decode_base64("AKvN"), Some(vec![0, 0xAB, 0xCD]));
+    }
+}
@@ -0,0 +1,75 @@
+use std::fmt;
+
+use crate::{
+    machine::{Chip8, Chip8Error},
+    system::Register,
+};
+
+use super::{condition::parse_number, Symbols};
+
+/// A change to a paused machine, as typed into a debugger: `V3 = 0x10`, `I = sprites`, `PC = 0x204`, `DT = 60`, or
+/// `[0x300] = 0xFF` for a byte of memory. Addresses can be labels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Register(Register, u16),
+    Pc(u16),
+    Memory { address: u16, value: u8 },
+}
+
+impl Edit {
+    /// Parses an edit, in any case, looking labels up in `symbols`.
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<Edit, String> {
+        let (target, value) = text
+            .split_once('=')
+            .ok_or("expected `target = value`, such as `V3 = 0x10` or `[0x300] = 0xFF`")?;
+        let (target, value) = (target.trim(), value.trim());
+        let address = |text: &str| {
+            symbols
+                .resolve(text)
+                .ok_or_else(|| format!("`{text}` is not an address or label"))
+        };
+        let number = |max: u16| {
+            parse_number(value)
+                .and_then(|value| u16::try_from(value).ok())
+                .filter(|&value| value <= max)
+                .ok_or_else(|| format!("`{value}` is not a number up to 0x{max:X}"))
+        };
+        if let Some(inner) = target
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            return Ok(Edit::Memory {
+                address: address(inner)?,
+                value: number(0xFF)? as u8,
+            });
+        }
+        if target.eq_ignore_ascii_case("PC") {
+            return Ok(Edit::Pc(address(value)?));
+        }
+        match target.parse().map_err(|error: &str| error.to_string())? {
+            Register::Index => Ok(Edit::Register(Register::Index, address(value)?)),
+            register => Ok(Edit::Register(register, number(0xFF)?)),
+        }
+    }
+
+    /// Makes the change, which the machine acts on once it runs again.
+    pub fn apply(self, machine: &mut Chip8) -> Result<(), Chip8Error> {
+        match self {
+            Edit::Register(register, value) => machine.set_register(register, value),
+            Edit::Pc(pc) => machine.set_pc(pc),
+            Edit::Memory { address, value } => machine.poke(address, value),
+        }
+    }
+}
+
+/// Writes the edit as it's typed, with addresses in hex.
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::Register(Register::Index, value) => write!(f, "I = 0x{value:03X}"),
+            Edit::Register(register, value) => write!(f, "{register} = 0x{value:02X}"),
+            Edit::Pc(pc) => write!(f, "PC = 0x{pc:03X}"),
+            Edit::Memory { address, value } => write!(f, "[0x{address:03X}] = 0x{value:02X}"),
+        }
+    }
+}
@@ -0,0 +1,1199 @@
+//! Stopping a machine where you want it: breakpoints, watchpoints on registers and ranges of memory, catchpoints on
+//! drawing and reading keys, and stepping by the instruction, over and out of subroutines, or by the frame.
+//! Breakpoints and watchpoints can be made conditional, stopping only when a `Condition` holds.
+//!
+//! While it's stopped, the machine's registers and memory can be changed with an `Edit`, to see what the program
+//! does with other values.
+//!
+//! Copies of memory can be kept as named snapshots, to see what changes between two points, such as before and
+//! after pressing a key.
+//!
+//! The debugger can also go backwards. It keeps a savestate every `HISTORY_INTERVAL` instructions, and steps back by
+//! loading the last one before where it's going and running forwards from there to it.
+//!
+//! A `Debugger` takes the machine over and pauses it, so nothing runs but what it's told to, and the timers only
+//! count down as frames finish. Every time it stops it says why, both to its caller and on the machine's event bus,
+//! so views and frontends can follow along.
+
+use std::{collections::BTreeMap, fmt, ops::RangeInclusive, sync::atomic::Ordering};
+
+use crate::{
+    decoder::{decode, Instruction},
+    disassembler,
+    events::Event,
+    explain::{self, explain, Operands},
+    keypad::Keypad,
+    machine::{Chip8, Chip8Error, RewindBuffer},
+    memory::Memory,
+    system::{AccessKind, MemoryAccess, Register},
+};
+
+mod condition;
+pub mod dap;
+mod edit;
+mod snapshot;
+mod stack;
+mod symbols;
+
+pub use condition::{Condition, ConditionError};
+pub use edit::Edit;
+pub use snapshot::{diff_memory, MemoryChange};
+pub use stack::{call_stack, CallFrame};
+pub use symbols::{Symbols, SymbolsError};
+
+/// How many instructions apart the debugger keeps states to go back to. Stepping back runs up to this many
+/// instructions again.
+pub const HISTORY_INTERVAL: u64 = 256;
+
+/// How many states the debugger keeps, which at `HISTORY_INTERVAL` reaches back some 260,000 instructions: over six
+/// minutes at the usual speed.
+pub const HISTORY_DEPTH: usize = 1024;
+
+/// Why the debugger stopped running the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// A single instruction was stepped, or a subroutine stepped over or out of.
+    Step,
+    /// A frame finished.
+    Frame,
+    /// The program counter reached a breakpoint. The instruction there hasn't run yet.
+    Breakpoint(u16),
+    /// The instruction just run wrote a watched register, leaving it holding `value`.
+    Watchpoint { register: Register, value: u16 },
+    /// The instruction at `pc`, just run, read or wrote a byte of watched memory.
+    MemoryWatchpoint { pc: u16, access: MemoryAccess },
+    /// The instruction just run drew a sprite, which turned off a lit pixel if `collision` is set.
+    Draw { collision: bool },
+    /// The instruction just run read a key: tested it with EX9E or EXA1, or got it with FX0A.
+    KeyRead { key: u8 },
+    /// The program is jumping to itself at the given address, so it would never reach a breakpoint.
+    InfiniteLoop(u16),
+    /// The program is waiting on a key, which can't be pressed while the debugger runs it.
+    WaitingForKey,
+    /// The machine's stop flag was set.
+    Interrupted,
+    /// A single instruction was stepped back over.
+    StepBack,
+    /// Going backwards reached the oldest state the debugger kept, or where it took the machine over.
+    HistoryStart,
+}
+
+/// Where a run stops, besides at breakpoints and the other reasons to.
+#[derive(Debug, Clone, Copy)]
+enum Until {
+    /// Only when something stops it.
+    Stopped,
+    /// At the end of the frame.
+    FrameEnd,
+    /// At `address`, once the stack is no deeper than `depth`: a breakpoint on where a subroutine returns to, that
+    /// calls it makes to itself don't stop at.
+    Return { address: u16, depth: usize },
+}
+
+/// Stops the machine when an instruction writes a register, perhaps only with certain values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub register: Register,
+    /// The values that stop the machine when written.
+    pub values: RangeInclusive<u16>,
+    /// What else must hold after the write for the machine to stop.
+    pub condition: Option<Condition>,
+}
+
+impl Watchpoint {
+    /// Watches for any write to a register.
+    pub fn any(register: Register) -> Watchpoint {
+        Watchpoint::range(register, 0..=u16::MAX)
+    }
+
+    /// Watches for a register being written with one value.
+    pub fn value(register: Register, value: u16) -> Watchpoint {
+        Watchpoint::range(register, value..=value)
+    }
+
+    /// Watches for a register being written with any of a range of values.
+    pub fn range(register: Register, values: RangeInclusive<u16>) -> Watchpoint {
+        Watchpoint {
+            register,
+            values,
+            condition: None,
+        }
+    }
+
+    /// The same watchpoint, stopping only when `condition` holds too.
+    pub fn when(mut self, condition: Condition) -> Watchpoint {
+        self.condition = Some(condition);
+        self
+    }
+
+    fn is_hit_by(&self, register: Register, value: u16, machine: &Chip8) -> bool {
+        self.register == register
+            && self.values.contains(&value)
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.holds(machine))
+    }
+}
+
+/// Stops the machine when an instruction reads or writes any byte of a range of memory, as when a table of sprites
+/// is being overwritten and it isn't known from where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryWatchpoint {
+    pub addresses: RangeInclusive<u16>,
+    /// Whether reading the memory as data stops the machine. Fetching instructions doesn't.
+    pub reads: bool,
+    pub writes: bool,
+    /// What else must hold after the access for the machine to stop.
+    pub condition: Option<Condition>,
+}
+
+impl MemoryWatchpoint {
+    /// Watches for any write to a range of memory.
+    pub fn writes(addresses: RangeInclusive<u16>) -> MemoryWatchpoint {
+        MemoryWatchpoint {
+            addresses,
+            reads: false,
+            writes: true,
+            condition: None,
+        }
+    }
+
+    /// Watches for any read of a range of memory as data, as a sprite or into the registers.
+    pub fn reads(addresses: RangeInclusive<u16>) -> MemoryWatchpoint {
+        MemoryWatchpoint {
+            reads: true,
+            writes: false,
+            ..MemoryWatchpoint::writes(addresses)
+        }
+    }
+
+    /// Watches for reads and writes alike.
+    pub fn accesses(addresses: RangeInclusive<u16>) -> MemoryWatchpoint {
+        MemoryWatchpoint {
+            reads: true,
+            ..MemoryWatchpoint::writes(addresses)
+        }
+    }
+
+    /// The same watchpoint, stopping only when `condition` holds too.
+    pub fn when(mut self, condition: Condition) -> MemoryWatchpoint {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Parses a watchpoint as typed into a debugger: addresses, then what to watch for, `write` if not given, `read`,
+    /// or `access` for both, such as `0x300..0x340`, `sprites..=sprites+15 read`, or `0x2A0 access`. The end of a
+    /// `..` range isn't watched, as in Rust. Addresses can be labels, and words can be in any case.
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<MemoryWatchpoint, String> {
+        let address = |text: &str| {
+            symbols
+                .resolve(text)
+                .ok_or_else(|| format!("`{text}` is not an address or label"))
+        };
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (range, kind) = match words[..] {
+            [range] => (range, "write"),
+            [range, kind] => (range, kind),
+            _ => return Err("expected addresses and `read`, `write`, or `access`".to_string()),
+        };
+        let addresses = if let Some((start, end)) = range.split_once("..=") {
+            address(start)?..=address(end)?
+        } else if let Some((start, end)) = range.split_once("..") {
+            let end = address(end)?
+                .checked_sub(1)
+                .ok_or("the range of addresses is empty")?;
+            address(start)?..=end
+        } else {
+            let address = address(range)?;
+            address..=address
+        };
+        if addresses.is_empty() {
+            return Err("the range of addresses is empty".to_string());
+        }
+        match kind.to_ascii_lowercase().as_str() {
+            "write" | "writes" => Ok(MemoryWatchpoint::writes(addresses)),
+            "read" | "reads" => Ok(MemoryWatchpoint::reads(addresses)),
+            "access" | "accesses" => Ok(MemoryWatchpoint::accesses(addresses)),
+            _ => Err(format!("`{kind}` is not `read`, `write`, or `access`")),
+        }
+    }
+
+    fn is_hit_by(&self, access: MemoryAccess, machine: &Chip8) -> bool {
+        let kind = match access.kind {
+            AccessKind::Read => self.reads,
+            AccessKind::Write => self.writes,
+        };
+        kind && self.addresses.contains(&access.address)
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.holds(machine))
+    }
+}
+
+/// Writes the watchpoint as it's typed, such as `0x300..=0x33F write`, leaving out any condition.
+impl fmt::Display for MemoryWatchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match (self.reads, self.writes) {
+            (true, true) => "access",
+            (true, false) => "read",
+            _ => "write",
+        };
+        let (start, end) = (self.addresses.start(), self.addresses.end());
+        write!(f, "0x{start:03X}..=0x{end:03X} {kind}")
+    }
+}
+
+/// Stops the machine after a kind of instruction runs, wherever it is, as CHIP-8 programs are often easier to find
+/// your way around by what they draw and the keys they read than by address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Catchpoint {
+    /// Stops after every sprite drawn with DXYN, or only those that collide with a lit pixel.
+    Draw { collisions_only: bool },
+    /// Stops after the program reads a key, or only the key given: tested with EX9E or EXA1, or waited for with FX0A
+    /// once it's been pressed.
+    Key(Option<u8>),
+}
+
+impl Catchpoint {
+    fn catches(self, reason: StopReason) -> bool {
+        match (self, reason) {
+            (Catchpoint::Draw { collisions_only }, StopReason::Draw { collision }) => {
+                collision || !collisions_only
+            }
+            (Catchpoint::Key(key), StopReason::KeyRead { key: read }) => {
+                key.is_none_or(|key| key == read)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Runs a machine under the control of breakpoints, watchpoints, catchpoints, and stepping.
+///
+/// Frames still end where they would under `Chip8::run_frame()`, however they're stepped through, so the timers and
+/// display keep in time with the instructions. Movies record and play back as usual, but a rewinding machine is
+/// stepped forwards all the same.
+pub struct Debugger {
+    machine: Chip8,
+    /// The addresses with breakpoints, and the conditions any of them stop on.
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watchpoints: Vec<Watchpoint>,
+    memory_watchpoints: Vec<MemoryWatchpoint>,
+    catchpoints: Vec<Catchpoint>,
+    /// How many instructions of the current frame have run.
+    frame_progress: u64,
+    /// States of the machine to go back to, taken every `HISTORY_INTERVAL` instructions and whenever the keypad
+    /// changes, by how many instructions had run.
+    history: RewindBuffer,
+    /// How many instructions the debugger has run, less any stepped back over.
+    steps: u64,
+    /// The machine's frame count when the debugger took it over.
+    start_frame: u64,
+    /// The keypad as the last instruction found it, so presses between states aren't lost going back.
+    keypad: Keypad,
+    symbols: Symbols,
+    /// Copies of memory, by name.
+    snapshots: BTreeMap<String, Memory>,
+    /// The last instruction run, with the machine as it was before and after, for explaining it.
+    last_step: Option<(Instruction, Operands, Operands)>,
+}
+
+impl Debugger {
+    /// Takes a machine over, pausing it. The labels of a disassembly of its program are its symbols to start with.
+    pub fn new(mut machine: Chip8) -> Debugger {
+        machine.pause();
+        let symbols =
+            Symbols::from_disassembly(&disassembler::follow(machine.rom(), machine.variant()));
+        let mut debugger = Debugger {
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            memory_watchpoints: Vec::new(),
+            catchpoints: Vec::new(),
+            frame_progress: 0,
+            history: RewindBuffer::new(HISTORY_DEPTH, HISTORY_INTERVAL),
+            steps: 0,
+            start_frame: machine.frame_count(),
+            keypad: machine.keypad().clone(),
+            symbols,
+            snapshots: BTreeMap::new(),
+            last_step: None,
+            machine,
+        };
+        debugger.clear_history();
+        debugger
+    }
+
+    pub fn machine(&self) -> &Chip8 {
+        &self.machine
+    }
+
+    /// Says in words what the last instruction run did, with the values it worked on and what came of it.
+    pub fn explain_last(&self) -> Option<String> {
+        let (instruction, before, after) = self.last_step.as_ref()?;
+        Some(explain(*instruction, before, Some(after)))
+    }
+
+    /// Says in words what the next instruction will do, with the values it will work on.
+    pub fn explain_next(&self) -> Option<String> {
+        explain::explain_next(&self.machine)
+    }
+
+    /// The machine, to change as you like. Anything but pressing keys should be followed by `clear_history()`, or
+    /// going back will replay the program without the change.
+    pub fn machine_mut(&mut self) -> &mut Chip8 {
+        &mut self.machine
+    }
+
+    /// The names of addresses in the program, for showing in place of the addresses.
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn symbols_mut(&mut self) -> &mut Symbols {
+        &mut self.symbols
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Adds names from a symbol file, such as one written alongside an Octo program, over those the debugger had,
+    /// and sets the breakpoints it marks.
+    pub fn load_symbols(&mut self, symbols: &Symbols) {
+        self.symbols.merge(symbols);
+        for (address, _) in symbols.breakpoints() {
+            self.add_breakpoint(address);
+        }
+    }
+
+    /// The subroutine calls the machine is inside, innermost first, named from the symbols.
+    pub fn call_stack(&self) -> Vec<CallFrame> {
+        call_stack(self.machine.cpu(), self.machine.memory(), &self.symbols)
+    }
+
+    /// Keeps a copy of memory as it is now under `name`, replacing any snapshot already called that.
+    pub fn take_snapshot(&mut self, name: impl Into<String>) {
+        self.snapshots
+            .insert(name.into(), self.machine.memory().clone());
+    }
+
+    pub fn snapshot(&self, name: &str) -> Option<&Memory> {
+        self.snapshots.get(name)
+    }
+
+    /// Returns whether there was a snapshot to remove.
+    pub fn remove_snapshot(&mut self, name: &str) -> bool {
+        self.snapshots.remove(name).is_some()
+    }
+
+    /// The names of the snapshots, in order.
+    pub fn snapshots(&self) -> impl Iterator<Item = &str> {
+        self.snapshots.keys().map(String::as_str)
+    }
+
+    /// How memory has changed since the snapshot called `name` was taken, or `None` if there isn't one.
+    pub fn diff_snapshot(&self, name: &str) -> Option<Vec<MemoryChange>> {
+        let old = self.snapshots.get(name)?;
+        Some(diff_memory(old, self.machine.memory()))
+    }
+
+    /// How memory changed from one snapshot to another, or `None` if either is missing.
+    pub fn diff_snapshots(&self, old: &str, new: &str) -> Option<Vec<MemoryChange>> {
+        Some(diff_memory(
+            self.snapshots.get(old)?,
+            self.snapshots.get(new)?,
+        ))
+    }
+
+    /// Changes a register or byte of memory, which the program sees once it runs on. The machine can't be taken back
+    /// to before the change, as going back would replay the program without it.
+    pub fn edit(&mut self, edit: Edit) -> Result<(), Chip8Error> {
+        edit.apply(&mut self.machine)?;
+        self.clear_history();
+        Ok(())
+    }
+
+    /// Forgets every state to go back to, so the machine as it is now is as far back as the debugger can go.
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+        self.history.push(self.steps, &self.machine.save_state());
+    }
+
+    /// How many instructions the debugger has run, less any it's stepped back over.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Hands the machine back, still paused, perhaps partway through a frame.
+    pub fn into_machine(self) -> Chip8 {
+        self.machine
+    }
+
+    /// Stops before the instruction at `address` runs. Returns whether the breakpoint is new.
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address, None).is_none()
+    }
+
+    /// Stops before the instruction at `address` runs, if `condition` holds then, replacing any breakpoint already
+    /// there. Returns whether the breakpoint is new.
+    pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Condition) -> bool {
+        self.breakpoints.insert(address, Some(condition)).is_none()
+    }
+
+    /// Returns whether there was a breakpoint to remove.
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// The addresses with breakpoints, lowest first.
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    /// The condition of the breakpoint at `address`, if it has one.
+    pub fn breakpoint_condition(&self, address: u16) -> Option<&Condition> {
+        self.breakpoints.get(&address)?.as_ref()
+    }
+
+    /// Stops after any instruction that writes the watched register with a watched value. Returns whether the
+    /// watchpoint is new.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        if self.watchpoints.contains(&watchpoint) {
+            return false;
+        }
+        self.watchpoints.push(watchpoint);
+        true
+    }
+
+    /// Returns whether there was a watchpoint to remove.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|watching| watching != watchpoint);
+        self.watchpoints.len() != before
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// The watchpoints, in the order they were added.
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Stops after any instruction that reads or writes watched memory as the watchpoint says. Returns whether the
+    /// watchpoint is new.
+    pub fn add_memory_watchpoint(&mut self, watchpoint: MemoryWatchpoint) -> bool {
+        if self.memory_watchpoints.contains(&watchpoint) {
+            return false;
+        }
+        self.memory_watchpoints.push(watchpoint);
+        true
+    }
+
+    /// Returns whether there was a watchpoint to remove.
+    pub fn remove_memory_watchpoint(&mut self, watchpoint: &MemoryWatchpoint) -> bool {
+        let before = self.memory_watchpoints.len();
+        self.memory_watchpoints
+            .retain(|watching| watching != watchpoint);
+        self.memory_watchpoints.len() != before
+    }
+
+    pub fn clear_memory_watchpoints(&mut self) {
+        self.memory_watchpoints.clear();
+    }
+
+    /// The watchpoints on memory, in the order they were added.
+    pub fn memory_watchpoints(&self) -> &[MemoryWatchpoint] {
+        &self.memory_watchpoints
+    }
+
+    /// Stops after any instruction of the kind the catchpoint is for. Returns whether the catchpoint is new.
+    pub fn add_catchpoint(&mut self, catchpoint: Catchpoint) -> bool {
+        if self.catchpoints.contains(&catchpoint) {
+            return false;
+        }
+        self.catchpoints.push(catchpoint);
+        true
+    }
+
+    /// Returns whether there was a catchpoint to remove.
+    pub fn remove_catchpoint(&mut self, catchpoint: Catchpoint) -> bool {
+        let before = self.catchpoints.len();
+        self.catchpoints.retain(|&catching| catching != catchpoint);
+        self.catchpoints.len() != before
+    }
+
+    pub fn clear_catchpoints(&mut self) {
+        self.catchpoints.clear();
+    }
+
+    /// The catchpoints, in the order they were added.
+    pub fn catchpoints(&self) -> &[Catchpoint] {
+        &self.catchpoints
+    }
+
+    /// How many instructions of the current frame have run.
+    pub fn frame_progress(&self) -> u64 {
+        self.frame_progress
+    }
+
+    /// Runs one instruction, whatever breakpoint it's on, finishing the frame if it was the frame's last.
+    pub fn step(&mut self) -> Result<StopReason, Chip8Error> {
+        self.execute()?;
+        Ok(self.stop(StopReason::Step))
+    }
+
+    /// Runs the rest of the current frame, stopping early at a breakpoint past the first instruction, a watchpoint,
+    /// or a catchpoint.
+    pub fn step_frame(&mut self) -> Result<StopReason, Chip8Error> {
+        self.run(Until::FrameEnd)
+    }
+
+    /// Steps an instruction, running any subroutine it calls until it returns, so the machine stops on the
+    /// instruction after the call. Anything that stops `continue_()` stops it inside the subroutine too.
+    pub fn step_over(&mut self) -> Result<StopReason, Chip8Error> {
+        let pc = self.machine.cpu().pc();
+        match self.machine.memory().read_opcode(pc).map(decode) {
+            Ok(Instruction::Call { .. }) => self.run(Until::Return {
+                address: pc.wrapping_add(2),
+                depth: self.machine.cpu().stack().depth(),
+            }),
+            _ => self.step(),
+        }
+    }
+
+    /// Runs until the subroutine the machine is in returns, stopping on the instruction after the call to it.
+    /// Anything that stops `continue_()` stops it sooner, and outside any subroutine it runs just as `continue_()`
+    /// does.
+    pub fn step_out(&mut self) -> Result<StopReason, Chip8Error> {
+        let stack = self.machine.cpu().stack();
+        let until = match stack.entries().last() {
+            Some(&address) => Until::Return {
+                address,
+                depth: stack.depth() - 1,
+            },
+            None => Until::Stopped,
+        };
+        self.run(until)
+    }
+
+    /// Runs until the machine reaches a breakpoint past the first instruction, writes a watched register, or runs an
+    /// instruction a catchpoint is for, or can't get any further: it's in an infinite loop, waiting on a key, or its
+    /// stop flag is set.
+    ///
+    /// The machine runs as fast as it can, not in time with its clock.
+    pub fn continue_(&mut self) -> Result<StopReason, Chip8Error> {
+        self.run(Until::Stopped)
+    }
+
+    /// Goes back one instruction, to just before the last one to run.
+    ///
+    /// The machine is put back exactly as it was, besides what was done through `machine_mut()`. Anything it did
+    /// outside itself happens again as it runs forwards to the instruction, such as events being published.
+    pub fn step_back(&mut self) -> Result<StopReason, Chip8Error> {
+        if self.steps == 0 || self.history.oldest() == Some(self.steps) {
+            return Ok(self.stop(StopReason::HistoryStart));
+        }
+        self.go_back_to(self.steps - 1)?;
+        Ok(self.stop(StopReason::StepBack))
+    }
+
+    /// Goes back to the last time the machine reached a breakpoint, or as far back as the debugger can go.
+    ///
+    /// Watchpoints and catchpoints aren't stopped at going backwards.
+    pub fn reverse_continue(&mut self) -> Result<StopReason, Chip8Error> {
+        let mut end = self.steps;
+        while let Some(oldest) = self.history.oldest().filter(|&oldest| oldest < end) {
+            // replay each stretch between states, from the latest back, until one reaches a breakpoint
+            let start = self.go_back_to_state(end - 1, 0)?;
+            let mut hit = None;
+            while self.steps < end {
+                if self.is_breakpoint_hit(self.machine.cpu().pc()) {
+                    hit = Some(self.steps);
+                }
+                self.execute()?;
+            }
+            if let Some(hit) = hit {
+                self.go_back_to(hit)?;
+                let pc = self.machine.cpu().pc();
+                return Ok(self.stop(StopReason::Breakpoint(pc)));
+            }
+            if start == oldest {
+                break;
+            }
+            end = start;
+        }
+        let oldest = self.history.oldest().unwrap_or(self.steps);
+        self.go_back_to(oldest)?;
+        Ok(self.stop(StopReason::HistoryStart))
+    }
+
+    /// Puts the machine back to how it was after `step` instructions, running forwards from the last state kept
+    /// before then.
+    fn go_back_to(&mut self, step: u64) -> Result<(), Chip8Error> {
+        // far enough back that the machine's history of instructions fills up again on the way
+        let length = self.machine.history().length() as u64;
+        self.go_back_to_state(step, length)?;
+        self.machine.history_mut().clear();
+        // nothing should be logged twice
+        let trace = self.machine.stop_trace();
+        let mut result = Ok(());
+        while self.steps < step && result.is_ok() {
+            result = self.execute().map(drop);
+        }
+        if let Some(trace) = trace {
+            self.machine.start_trace(trace);
+        }
+        result
+    }
+
+    /// Loads the last state kept from at least `before` instructions before `step`, returning the step it was taken
+    /// at.
+    fn go_back_to_state(&mut self, step: u64, before: u64) -> Result<u64, Chip8Error> {
+        let (at, state) = self
+            .history
+            .rewind(step, before)
+            .ok_or(Chip8Error::State("the debugger has no history"))?;
+        self.machine.load_state(state)?;
+        self.last_step = None;
+        let per_frame = self.machine.instructions_per_frame();
+        self.steps = at;
+        self.frame_progress = at % per_frame;
+        self.machine
+            .set_frame_count(self.start_frame + at / per_frame);
+        self.keypad = self.machine.keypad().clone();
+        Ok(at)
+    }
+
+    fn run(&mut self, until: Until) -> Result<StopReason, Chip8Error> {
+        let stop_flag = self.machine.stop_flag();
+        stop_flag.store(false, Ordering::Relaxed);
+        let one_frame = matches!(until, Until::FrameEnd);
+        let mut first = true;
+        loop {
+            let pc = self.machine.cpu().pc();
+            if !first && self.is_breakpoint_hit(pc) {
+                return Ok(self.stop(StopReason::Breakpoint(pc)));
+            }
+            if let Until::Return { address, depth } = until {
+                if !first && pc == address && self.machine.cpu().stack().depth() <= depth {
+                    return Ok(self.stop(StopReason::Step));
+                }
+            }
+            if !one_frame {
+                if self.machine.in_infinite_loop() {
+                    return Ok(self.stop(StopReason::InfiniteLoop(pc)));
+                }
+                // the first time round, a key may have come since it last stopped waiting
+                if !first && self.machine.cpu().is_waiting_for_key() {
+                    return Ok(self.stop(StopReason::WaitingForKey));
+                }
+            }
+            if stop_flag.load(Ordering::Relaxed) {
+                return Ok(self.stop(StopReason::Interrupted));
+            }
+            first = false;
+            let (instruction, finished_frame) = self.execute()?;
+            if let Some(reason) = self
+                .watchpoint_hit()
+                .or_else(|| self.memory_watchpoint_hit(pc))
+                .or_else(|| self.catchpoint_hit(instruction))
+            {
+                return Ok(self.stop(reason));
+            }
+            if finished_frame && one_frame {
+                return Ok(self.stop(StopReason::Frame));
+            }
+        }
+    }
+
+    /// Runs one instruction, returning it and whether it finished a frame.
+    fn execute(&mut self) -> Result<(Instruction, bool), Chip8Error> {
+        if self.frame_progress == 0 {
+            self.machine.begin_frame();
+        }
+        // keys pressed between states would be missed going back, so the state is kept whenever they change
+        if self.history.is_due(self.steps) || self.machine.keypad() != &self.keypad {
+            self.history.push(self.steps, &self.machine.save_state());
+            self.keypad = self.machine.keypad().clone();
+        }
+        let before = Operands::of(&self.machine);
+        let instruction = self.machine.step()?;
+        self.last_step = Some((instruction, before, Operands::of(&self.machine)));
+        self.steps += 1;
+        self.frame_progress += 1;
+        if self.frame_progress < self.machine.instructions_per_frame() {
+            return Ok((instruction, false));
+        }
+        self.frame_progress = 0;
+        self.machine.end_frame();
+        Ok((instruction, true))
+    }
+
+    fn is_breakpoint_hit(&self, pc: u16) -> bool {
+        match self.breakpoints.get(&pc) {
+            Some(Some(condition)) => condition.holds(&self.machine),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// The first write of the last instruction that a watchpoint stops on, if any.
+    fn watchpoint_hit(&self) -> Option<StopReason> {
+        self.machine
+            .register_writes()
+            .iter()
+            .find(|&&(register, value)| {
+                self.watchpoints
+                    .iter()
+                    .any(|watchpoint| watchpoint.is_hit_by(register, value, &self.machine))
+            })
+            .map(|&(register, value)| StopReason::Watchpoint { register, value })
+    }
+
+    /// The first access to memory of the last instruction, run at `pc`, that a watchpoint stops on, if any.
+    fn memory_watchpoint_hit(&self, pc: u16) -> Option<StopReason> {
+        self.machine
+            .memory_accesses()
+            .iter()
+            .find(|&&access| {
+                self.memory_watchpoints
+                    .iter()
+                    .any(|watchpoint| watchpoint.is_hit_by(access, &self.machine))
+            })
+            .map(|&access| StopReason::MemoryWatchpoint { pc, access })
+    }
+
+    /// What the last instruction did that a catchpoint stops on, if anything.
+    fn catchpoint_hit(&self, instruction: Instruction) -> Option<StopReason> {
+        let v = |x: u8| self.machine.register(Register::V(x)) as u8;
+        let reason = match instruction {
+            Instruction::Draw { .. } => StopReason::Draw {
+                collision: v(0xF) == 1,
+            },
+            Instruction::SkipKeyPressed { x } | Instruction::SkipKeyNotPressed { x } => {
+                StopReason::KeyRead { key: v(x) & 0xF }
+            }
+            // FX0A runs over and over until a key comes
+            Instruction::WaitKey { x } if !self.machine.cpu().is_waiting_for_key() => {
+                StopReason::KeyRead { key: v(x) }
+            }
+            _ => return None,
+        };
+        self.catchpoints
+            .iter()
+            .any(|catchpoint| catchpoint.catches(reason))
+            .then_some(reason)
+    }
+
+    fn stop(&mut self, reason: StopReason) -> StopReason {
+        // the timeline has its own rows for frames, and a mark for every step would bury the rest
+        if !matches!(reason, StopReason::Step | StopReason::Frame) {
+            if let Some(timeline) = self.machine.timeline_mut() {
+                timeline.debugger_stop(&format!("{reason:?}"));
+            }
+        }
+        self.machine.events().publish(Event::DebugStop(reason));
+        reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::MachineState,
+        memory::PROGRAM_START,
+    };
+
+    use super::*;
+
+    const START: u16 = PROGRAM_START as u16;
+
+    /// A debugger over a machine running `rom` at 10 instructions a frame.
+    fn debugging(rom: &[u8]) -> Debugger {
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .instructions_per_second(600)
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(rom).expect("failed to load rom");
+        Debugger::new(machine)
+    }
+
+    /// V0 += 1, V1 = V0, jump back to the start.
+    const COUNTER: [u8; 6] = [0x70, 0x01, 0x81, 0x00, 0x12, 0x00];
+
+    #[test]
+    fn stops_at_breakpoints() {
+        let mut debugger = debugging(&COUNTER);
+        let events = debugger.machine().subscribe();
+        assert_eq!(debugger.machine().state(), MachineState::Paused);
+        debugger.add_breakpoint(START + 2);
+        assert_eq!(debugger.continue_(), Ok(StopReason::Breakpoint(START + 2)));
+        assert_eq!(debugger.machine().cpu().registers()[0], 1);
+        // continuing from a breakpoint runs past it
+        assert_eq!(debugger.continue_(), Ok(StopReason::Breakpoint(START + 2)));
+        assert_eq!(debugger.machine().cpu().registers()[0], 2);
+        let stops: Vec<_> = events
+            .try_iter()
+            .filter(|event| matches!(event, Event::DebugStop(_)))
+            .collect();
+        assert_eq!(stops.len(), 2, "stops aren't published");
+    }
+
+    #[test]
+    fn steps_by_instruction_and_frame() {
+        let mut debugger = debugging(&COUNTER);
+        assert_eq!(debugger.step(), Ok(StopReason::Step));
+        assert_eq!(debugger.machine().cpu().pc(), START + 2);
+        assert_eq!(debugger.frame_progress(), 1);
+        assert_eq!(debugger.step_frame(), Ok(StopReason::Frame));
+        assert_eq!(debugger.frame_progress(), 0);
+        assert_eq!(debugger.machine().frame_count(), 1);
+        // ten instructions a frame, three to a loop
+        assert_eq!(debugger.machine().cpu().registers()[0], 4);
+    }
+
+    #[test]
+    fn timers_only_count_down_as_frames_finish() {
+        // V0 = 5, delay = V0, jump to self
+        let mut debugger = debugging(&[0x60, 0x05, 0xF0, 0x15, 0x12, 0x04]);
+        debugger.step().expect("failed to step");
+        debugger.step().expect("failed to step");
+        assert_eq!(debugger.machine().timers().retrieve_delay_timer(), 5);
+        debugger.step_frame().expect("failed to step frame");
+        assert_eq!(debugger.machine().timers().retrieve_delay_timer(), 4);
+    }
+
+    #[test]
+    fn stops_on_watched_writes() {
+        let mut debugger = debugging(&COUNTER);
+        debugger.add_watchpoint(Watchpoint::value(Register::V(1), 3));
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::Watchpoint {
+                register: Register::V(1),
+                value: 3
+            })
+        );
+        // stopped just after the write
+        assert_eq!(debugger.machine().cpu().pc(), START + 4);
+        assert!(debugger.remove_watchpoint(&Watchpoint::value(Register::V(1), 3)));
+
+        // V0 = 0 writes V0 even though it already held 0, then I = 0x300, then loop
+        let mut debugger = debugging(&[0x60, 0x00, 0xA3, 0x00, 0x12, 0x04]);
+        debugger.add_watchpoint(Watchpoint::any(Register::V(0)));
+        debugger.add_watchpoint(Watchpoint::range(Register::Index, 0x200..=0xFFF));
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::Watchpoint {
+                register: Register::V(0),
+                value: 0
+            })
+        );
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::Watchpoint {
+                register: Register::Index,
+                value: 0x300
+            })
+        );
+    }
+
+    #[test]
+    fn stops_on_watched_memory() {
+        // I = 0x300, V0 = 7, store V0, I = 0x340, store V0, I = 0x320, load V0, jump to self
+        let rom = [
+            0xA3, 0x00, 0x60, 0x07, 0xF0, 0x55, 0xA3, 0x40, 0xF0, 0x55, 0xA3, 0x20, 0xF0, 0x65,
+            0x12, 0x0E,
+        ];
+        let mut debugger = debugging(&rom);
+        let symbols = Symbols::new();
+        let table = MemoryWatchpoint::parse("0x300..0x340", &symbols).expect("failed to parse");
+        assert_eq!(table.to_string(), "0x300..=0x33F write");
+        debugger.add_memory_watchpoint(table);
+        debugger.add_memory_watchpoint(MemoryWatchpoint::parse("0x320 READ", &symbols).unwrap());
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::MemoryWatchpoint {
+                pc: START + 4,
+                access: MemoryAccess {
+                    address: 0x300,
+                    value: 7,
+                    kind: AccessKind::Write
+                }
+            })
+        );
+        // the write to 0x340 is just past the table
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::MemoryWatchpoint {
+                pc: START + 12,
+                access: MemoryAccess {
+                    address: 0x320,
+                    value: 0,
+                    kind: AccessKind::Read
+                }
+            })
+        );
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::InfiniteLoop(START + 14))
+        );
+        assert!(MemoryWatchpoint::parse("0x340..0x340", &symbols).is_err());
+    }
+
+    #[test]
+    fn steps_over_and_out_of_calls() {
+        // call a routine that adds to V0 and calls itself until V0 is 3, then add to V1 and stop
+        let rom = [
+            0x22, 0x06, 0x71, 0x01, 0x12, 0x04, 0x70, 0x01, 0x30, 0x03, 0x22, 0x06, 0x00, 0xEE,
+        ];
+        let mut debugger = debugging(&rom);
+        assert_eq!(debugger.step_over(), Ok(StopReason::Step));
+        assert_eq!(debugger.machine().cpu().pc(), START + 2);
+        assert_eq!(debugger.machine().cpu().registers()[0], 3);
+        assert_eq!(debugger.step_over(), Ok(StopReason::Step));
+        assert_eq!(debugger.machine().cpu().pc(), START + 4);
+
+        let mut debugger = debugging(&rom);
+        for _ in 0..4 {
+            debugger.step().expect("failed to step");
+        }
+        assert_eq!(debugger.machine().cpu().stack().depth(), 2);
+        // out of the inner call, past where the calls it made return to the same place
+        assert_eq!(debugger.step_out(), Ok(StopReason::Step));
+        assert_eq!(debugger.machine().cpu().pc(), START + 12);
+        assert_eq!(debugger.machine().cpu().stack().depth(), 1);
+        assert_eq!(debugger.step_out(), Ok(StopReason::Step));
+        assert_eq!(debugger.machine().cpu().pc(), START + 2);
+        assert_eq!(debugger.step_out(), Ok(StopReason::InfiniteLoop(START + 4)));
+    }
+
+    #[test]
+    fn stops_only_when_conditions_hold() {
+        let condition = |source: &str| source.parse::<Condition>().expect("failed to parse");
+        let mut debugger = debugging(&COUNTER);
+        debugger.add_conditional_breakpoint(START + 4, condition("V0 == 3 && V1 == 3"));
+        assert_eq!(debugger.continue_(), Ok(StopReason::Breakpoint(START + 4)));
+        assert_eq!(debugger.machine().cpu().registers()[0], 3);
+        debugger.clear_breakpoints();
+        debugger.add_watchpoint(Watchpoint::any(Register::V(0)).when(condition("V1 >= 5")));
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::Watchpoint {
+                register: Register::V(0),
+                value: 6
+            })
+        );
+    }
+
+    #[test]
+    fn catches_drawing_and_reading_keys() {
+        // draw the font's 0 twice at the same place, test key V1, clear, wait for a key, loop
+        let mut debugger = debugging(&[
+            0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05, 0xE1, 0x9E, 0x00, 0xE0, 0xF2, 0x0A, 0x12, 0x0C,
+        ]);
+        debugger.add_catchpoint(Catchpoint::Draw {
+            collisions_only: true,
+        });
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::Draw { collision: true })
+        );
+        assert_eq!(debugger.machine().cpu().pc(), START + 6);
+        assert!(debugger.remove_catchpoint(Catchpoint::Draw {
+            collisions_only: true
+        }));
+        debugger.add_catchpoint(Catchpoint::Key(Some(3)));
+        assert_eq!(debugger.continue_(), Ok(StopReason::WaitingForKey));
+        let keypad = debugger.machine_mut().keypad_mut();
+        keypad.press(3);
+        keypad.release(3);
+        assert_eq!(debugger.continue_(), Ok(StopReason::KeyRead { key: 3 }));
+        assert_eq!(debugger.machine().cpu().registers()[2], 3);
+
+        let mut debugger = debugging(&[0xE1, 0x9E, 0x12, 0x00]);
+        debugger.add_catchpoint(Catchpoint::Key(None));
+        assert_eq!(debugger.step_frame(), Ok(StopReason::KeyRead { key: 0 }));
+    }
+
+    #[test]
+    fn steps_back_to_exactly_where_it_was() {
+        let mut debugger = debugging(&COUNTER);
+        assert_eq!(debugger.step_back(), Ok(StopReason::HistoryStart));
+        // past a frame and a kept state
+        let before = 2 * HISTORY_INTERVAL + 5;
+        for _ in 0..before {
+            debugger.step().expect("failed to step");
+        }
+        let state = debugger.machine().save_state();
+        let frames = debugger.machine().frame_count();
+        let history = debugger.machine().history().clone();
+        debugger.step().expect("failed to step");
+        assert_eq!(debugger.step_back(), Ok(StopReason::StepBack));
+        assert_eq!(debugger.steps(), before);
+        assert_eq!(debugger.machine().save_state(), state);
+        assert_eq!(debugger.machine().history(), &history);
+        assert_eq!(debugger.machine().frame_count(), frames);
+        assert_eq!(debugger.frame_progress(), before % 10);
+    }
+
+    #[test]
+    fn reverse_continues_to_the_last_breakpoint() {
+        let mut debugger = debugging(&COUNTER);
+        for _ in 0..100 {
+            debugger.step_frame().expect("failed to step frame");
+        }
+        debugger.add_breakpoint(START + 2);
+        assert_eq!(
+            debugger.reverse_continue(),
+            Ok(StopReason::Breakpoint(START + 2))
+        );
+        // the loop is three instructions, the second of them on the breakpoint
+        assert_eq!(debugger.steps(), 997);
+        assert_eq!(debugger.machine().cpu().registers()[0], (333 % 256) as u8);
+        assert_eq!(debugger.machine().frame_count(), 99);
+        assert_eq!(
+            debugger.reverse_continue(),
+            Ok(StopReason::Breakpoint(START + 2))
+        );
+        assert_eq!(debugger.steps(), 994);
+
+        let mut debugger = debugging(&COUNTER);
+        debugger.step_frame().expect("failed to step frame");
+        assert_eq!(debugger.reverse_continue(), Ok(StopReason::HistoryStart));
+        assert_eq!(debugger.steps(), 0);
+        assert_eq!(debugger.machine().cpu().registers()[0], 0);
+    }
+
+    #[test]
+    fn names_the_calls_on_the_stack() {
+        // call 0x206, jump to self; 0x206 calls 0x20A, then returns; 0x20A loops
+        let mut debugger = debugging(&[
+            0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x22, 0x0A, 0x00, 0xEE, 0x12, 0x0A,
+        ]);
+        let symbols = Symbols::parse(":label spin 0x20A\n:breakpoint looping 0x20A")
+            .expect("failed to parse symbols");
+        debugger.load_symbols(&symbols);
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), [0x20A]);
+        for _ in 0..3 {
+            debugger.step().expect("failed to step");
+        }
+        assert_eq!(
+            debugger.call_stack(),
+            [
+                CallFrame {
+                    return_address: 0x208,
+                    call_site: Some(0x206),
+                    routine: Some(0x20A),
+                    label: Some("spin".to_string())
+                },
+                CallFrame {
+                    return_address: 0x202,
+                    call_site: Some(0x200),
+                    routine: Some(0x206),
+                    label: Some("sub_206".to_string())
+                },
+            ]
+        );
+        assert_eq!(
+            debugger.symbols().describe(0x208).as_deref(),
+            Some("sub_206+2")
+        );
+        assert_eq!(debugger.symbols().address("spin"), Some(0x20A));
+    }
+
+    #[test]
+    fn edits_registers_and_memory() {
+        let mut debugger = debugging(&COUNTER);
+        debugger.step().expect("failed to step");
+        let parse = |text| Edit::parse(text, debugger.symbols());
+        let edits = [
+            parse("v0 = 0x10"),
+            parse("[0x300] = 255"),
+            parse("PC = LABEL_200+2"),
+            parse("I = label_200"),
+        ];
+        assert_eq!(
+            edits,
+            [
+                Ok(Edit::Register(Register::V(0), 0x10)),
+                Ok(Edit::Memory {
+                    address: 0x300,
+                    value: 0xFF
+                }),
+                Ok(Edit::Pc(START + 2)),
+                Ok(Edit::Register(Register::Index, START)),
+            ]
+        );
+        assert_eq!(
+            parse("V0 = 0x100"),
+            Err("`0x100` is not a number up to 0xFF".to_string())
+        );
+        assert!(parse("PC 0x200").is_err());
+        for edit in edits {
+            debugger.edit(edit.unwrap()).expect("failed to edit");
+        }
+        assert_eq!(debugger.machine().memory().read(0x300), Ok(0xFF));
+        assert_eq!(debugger.step_back(), Ok(StopReason::HistoryStart));
+        // V1 = V0
+        debugger.step().expect("failed to step");
+        assert_eq!(debugger.machine().cpu().registers()[1], 0x10);
+    }
+
+    #[test]
+    fn diffs_memory_against_snapshots() {
+        // I = 0x300, V0 = 1, store V0, V0 = 2, store V0, loop
+        let mut debugger = debugging(&[
+            0xA3, 0x00, 0x60, 0x01, 0xF0, 0x55, 0x60, 0x02, 0xF0, 0x55, 0x12, 0x0A,
+        ]);
+        debugger.take_snapshot("start");
+        for _ in 0..3 {
+            debugger.step().expect("failed to step");
+        }
+        debugger.take_snapshot("stored");
+        assert_eq!(
+            debugger.diff_snapshot("start"),
+            Some(vec![MemoryChange {
+                address: 0x300,
+                old: 0,
+                new: 1
+            }])
+        );
+        debugger.step_frame().expect("failed to step frame");
+        let changes = debugger
+            .diff_snapshots("stored", "start")
+            .expect("missing snapshot");
+        assert_eq!(changes[0].to_string(), "300  01 -> 00");
+        // storing moves I on, as the original interpreter did
+        assert_eq!(
+            debugger.diff_snapshot("stored").unwrap()[0].to_string(),
+            "301  00 -> 02"
+        );
+        assert_eq!(debugger.diff_snapshot("missing"), None);
+        assert!(debugger.remove_snapshot("start"));
+        assert_eq!(debugger.snapshots().collect::<Vec<_>>(), ["stored"]);
+    }
+
+    #[test]
+    fn stops_where_the_program_cant_go_on() {
+        let mut debugger = debugging(&[0x00, 0xE0, 0x12, 0x02]);
+        assert_eq!(
+            debugger.continue_(),
+            Ok(StopReason::InfiniteLoop(START + 2))
+        );
+        let mut debugger = debugging(&[0xF0, 0x0A]);
+        assert_eq!(debugger.continue_(), Ok(StopReason::WaitingForKey));
+        // a frame still finishes while the program waits
+        assert_eq!(debugger.step_frame(), Ok(StopReason::Frame));
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+
+use crate::memory::Memory;
+
+/// A byte of memory that differs between two snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryChange {
+    pub address: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+impl fmt::Display for MemoryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:03X}  {:02X} -> {:02X}",
+            self.address, self.old, self.new
+        )
+    }
+}
+
+/// The bytes that differ from `old` to `new`, lowest address first. Memory of different sizes is only compared as
+/// far as the smaller goes.
+pub fn diff_memory(old: &Memory, new: &Memory) -> Vec<MemoryChange> {
+    let len = old.len().min(new.len());
+    let (Ok(old), Ok(new)) = (old.slice(0, len), new.slice(0, len)) else {
+        return Vec::new();
+    };
+    old.iter()
+        .zip(new)
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(address, (&old, &new))| MemoryChange {
+            address: address as u16,
+            old,
+            new,
+        })
+        .collect()
+}
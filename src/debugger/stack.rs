@@ -0,0 +1,47 @@
+use crate::{
+    decoder::{decode, Instruction},
+    memory::Memory,
+    system::Cpu,
+};
+
+use super::Symbols;
+
+/// A subroutine call still waiting on its return.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallFrame {
+    /// Where the subroutine will return to, as kept on the stack.
+    pub return_address: u16,
+    /// The address of the call, if the instruction before the return address is one.
+    pub call_site: Option<u16>,
+    /// The subroutine called, if the call could be found.
+    pub routine: Option<u16>,
+    /// The subroutine's name in the symbol table.
+    pub label: Option<String>,
+}
+
+/// The calls a CPU is inside, innermost first.
+///
+/// The stack only keeps return addresses, so the rest is worked out from the call just before each one. A program
+/// that pushed a return address some other way, or rewrote its code since, gives frames without a call site.
+pub fn call_stack(cpu: &Cpu, memory: &Memory, symbols: &Symbols) -> Vec<CallFrame> {
+    cpu.stack()
+        .entries()
+        .iter()
+        .rev()
+        .map(|&return_address| {
+            let call_site = return_address.wrapping_sub(2);
+            let routine = match memory.read_opcode(call_site).map(decode) {
+                Ok(Instruction::Call { address }) => Some(address),
+                _ => None,
+            };
+            CallFrame {
+                return_address,
+                call_site: routine.map(|_| call_site),
+                routine,
+                label: routine
+                    .and_then(|routine| symbols.name(routine))
+                    .map(str::to_string),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+};
+
+use crate::disassembler::Line;
+
+use super::condition::parse_number;
+
+/// Why a symbol file couldn't be read, and the line it was on, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolsError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SymbolsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SymbolsError {}
+
+/// Names for addresses in a program, such as its subroutines and sprites, for the debugger to show in place of bare
+/// addresses.
+///
+/// An address can go by several names, as when two Octo labels mark the same place, but is shown by the first it was
+/// given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Symbols {
+    /// The name each address is shown by.
+    names: BTreeMap<u16, String>,
+    /// Every name, with the address it stands for.
+    addresses: BTreeMap<String, u16>,
+    /// Addresses the program's source marked to stop at, with what it called them.
+    breakpoints: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    /// The labels a disassembly gave the addresses its program refers to.
+    pub fn from_disassembly(lines: &[Line]) -> Symbols {
+        let mut symbols = Symbols::new();
+        for line in lines {
+            if let Some(label) = &line.label {
+                symbols.insert(line.address, label.clone());
+            }
+        }
+        symbols
+    }
+
+    /// Reads a symbol file, as Octo's tools and `chip8 asm --symbols` write them. Each line gives a kind, a name,
+    /// and an address, such as `:label main 0x200` or `:breakpoint boom 0x2A4`, and `#` starts a comment. A line of
+    /// just a name and an address is a label too. Other kinds, such as `:const`, name things that aren't addresses,
+    /// and are skipped.
+    pub fn parse(text: &str) -> Result<Symbols, SymbolsError> {
+        let mut symbols = Symbols::new();
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| SymbolsError {
+                line: number + 1,
+                message,
+            };
+            let code = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = code.split_whitespace().collect();
+            let (kind, name, value) = match words[..] {
+                [] => continue,
+                [kind, ..]
+                    if kind.starts_with(':') && ![":", ":label", ":breakpoint"].contains(&kind) =>
+                {
+                    continue
+                }
+                [kind, name, value] => (kind, name, value),
+                [name, value] if !name.starts_with(':') => (":label", name, value),
+                _ => return Err(error("expected a kind, a name, and an address".to_string())),
+            };
+            if name.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(error(format!("`{name}` is not a valid name")));
+            }
+            let address = parse_number(value)
+                .and_then(|address| u16::try_from(address).ok())
+                .ok_or_else(|| error(format!("`{value}` is not an address")))?;
+            if kind == ":breakpoint" {
+                symbols.add_breakpoint(address, name);
+            } else {
+                symbols.alias(address, name);
+            }
+        }
+        Ok(symbols)
+    }
+
+    /// Names an address, replacing any name it had.
+    pub fn insert(&mut self, address: u16, name: impl Into<String>) {
+        let name = name.into();
+        self.forget(&name);
+        if let Some(old) = self.names.insert(address, name.clone()) {
+            self.addresses.remove(&old);
+        }
+        self.addresses.insert(name, address);
+    }
+
+    /// Lets `name` stand for an address too, which is shown by it only if it has no other name.
+    pub fn alias(&mut self, address: u16, name: impl Into<String>) {
+        let name = name.into();
+        self.forget(&name);
+        self.names.entry(address).or_insert_with(|| name.clone());
+        self.addresses.insert(name, address);
+    }
+
+    /// Forgets every name of an address, returning the one it was shown by.
+    pub fn remove(&mut self, address: u16) -> Option<String> {
+        self.addresses.retain(|_, &mut named| named != address);
+        self.names.remove(&address)
+    }
+
+    /// Adds the names and breakpoints of other symbols over these, as when loading a symbol file.
+    pub fn merge(&mut self, other: &Symbols) {
+        for (address, name) in other.iter() {
+            self.insert(address, name);
+        }
+        for (name, address) in other.names() {
+            self.alias(address, name);
+        }
+        for (address, name) in other.breakpoints() {
+            self.add_breakpoint(address, name);
+        }
+    }
+
+    /// Marks an address to stop at, naming the breakpoint.
+    pub fn add_breakpoint(&mut self, address: u16, name: impl Into<String>) {
+        self.breakpoints.insert(address, name.into());
+    }
+
+    /// The addresses marked to stop at, lowest first, with the breakpoints' names.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.breakpoints
+            .iter()
+            .map(|(&address, name)| (address, name.as_str()))
+    }
+
+    /// The name of exactly this address.
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    /// The address by a name.
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Reads an address written as a number, a name, or a name and an offset, such as `0x2A4`, `draw_ball`, or
+    /// `draw_ball+4`. A name that isn't known matches one differing only in case, as terminals type keys in upper
+    /// case.
+    pub fn resolve(&self, text: &str) -> Option<u16> {
+        let text = text.trim();
+        if let Some(address) = parse_number(text) {
+            return u16::try_from(address).ok();
+        }
+        let (name, offset) = match text.split_once('+') {
+            Some((name, offset)) => (name.trim(), parse_number(offset.trim())?),
+            None => (text, 0),
+        };
+        let address = self.address(name).or_else(|| {
+            self.names()
+                .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                .map(|(_, address)| address)
+        })? as i64
+            + offset;
+        u16::try_from(address).ok()
+    }
+
+    /// Describes an address by the nearest name at or before it, such as `draw_ball+4`.
+    pub fn describe(&self, address: u16) -> Option<String> {
+        let (&start, name) = self.names.range(..=address).next_back()?;
+        Some(match address - start {
+            0 => name.clone(),
+            offset => format!("{name}+{offset}"),
+        })
+    }
+
+    /// Relabels a disassembly with these names, wherever they name the start of a line. Lines without a name keep
+    /// the disassembler's labels.
+    pub fn apply(&self, lines: &mut [Line]) {
+        let starts: HashSet<u16> = lines.iter().map(|line| line.address).collect();
+        let named = |address: u16| {
+            starts
+                .contains(&address)
+                .then(|| self.name(address))
+                .flatten()
+                .map(str::to_string)
+        };
+        for line in lines {
+            if let Some(name) = named(line.address) {
+                line.label = Some(name);
+            }
+            if let Some(name) = line
+                .instruction
+                .and_then(|instruction| instruction.target())
+                .and_then(named)
+            {
+                line.target_label = Some(name);
+            }
+        }
+    }
+
+    /// Every name an address is shown by, by address from lowest.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.names
+            .iter()
+            .map(|(&address, name)| (address, name.as_str()))
+    }
+
+    /// Every name, including those an address isn't shown by, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = (&str, u16)> {
+        self.addresses
+            .iter()
+            .map(|(name, &address)| (name.as_str(), address))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Stops a name standing for anything, handing the address it was shown by to another of its names.
+    fn forget(&mut self, name: &str) {
+        let Some(address) = self.addresses.remove(name) else {
+            return;
+        };
+        if self.names.get(&address).is_some_and(|shown| shown == name) {
+            self.names.remove(&address);
+            let other = self
+                .addresses
+                .iter()
+                .find(|(_, &named)| named == address)
+                .map(|(other, _)| other.clone());
+            if let Some(other) = other {
+                self.names.insert(address, other);
+            }
+        }
+    }
+}
+
+/// Writes the symbols as a symbol file that `Symbols::parse()` reads back, every name of an address on a line of its
+/// own.
+impl fmt::Display for Symbols {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<(u16, &str)> = self
+            .names()
+            .map(|(name, address)| (address, name))
+            .collect();
+        // the name an address is shown by comes first, so it still is once read back
+        names.sort_by_key(|&(address, name)| (address, self.name(address) != Some(name), name));
+        for (address, name) in names {
+            writeln!(f, ":label {name} 0x{address:03X}")?;
+        }
+        for (address, name) in self.breakpoints() {
+            writeln!(f, ":breakpoint {name} 0x{address:03X}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_and_writes_symbol_files() {
+        let text = "
+            # from an Octo program
+            :const lives 3
+            :label main 0x200
+            :label start 0x200
+            :breakpoint lost-life 0x2A4
+            draw_ball 0x2A0
+        ";
+        let symbols = Symbols::parse(text).expect("failed to parse symbols");
+        assert_eq!(symbols.name(0x200), Some("main"));
+        assert_eq!(symbols.address("start"), Some(0x200));
+        assert_eq!(symbols.address("lives"), None);
+        assert_eq!(
+            symbols.breakpoints().collect::<Vec<_>>(),
+            [(0x2A4, "lost-life")]
+        );
+        assert_eq!(symbols.resolve("draw_ball+4"), Some(0x2A4));
+        assert_eq!(symbols.resolve("0x2A4"), Some(0x2A4));
+        assert_eq!(symbols.resolve("nowhere"), None);
+        assert_eq!(Symbols::parse(&symbols.to_string()), Ok(symbols));
+
+        assert_eq!(
+            Symbols::parse(":label main 0x10000"),
+            Err(SymbolsError {
+                line: 1,
+                message: "`0x10000` is not an address".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,332 @@
+//! Turning programs back into instructions, for the CLI, the debugger's views, and traces.
+//!
+//! A disassembly is a list of `Line`s, each an instruction or a byte of data, with its address, the bytes it came
+//! from, and the `Instruction` the decoder made of them. Addresses the program jumps or calls to or points I at get
+//! labels, so the disassembly reads as source that `chip8 asm` assembles back into the same program.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use crate::{
+    decoder::{decode_for, Instruction},
+    memory::PROGRAM_START,
+    quirks::Variant,
+};
+
+/// A line of disassembly: an instruction, or a byte of data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    /// The bytes the line was made from: a whole instruction, or the one byte of data.
+    pub bytes: Vec<u8>,
+    /// The instruction, or `None` for data.
+    pub instruction: Option<Instruction>,
+    /// The label of this line's address, if anything in the disassembly refers to it.
+    pub label: Option<String>,
+    /// The label of the address the instruction refers to, if that's the start of a line.
+    pub target_label: Option<String>,
+}
+
+impl Line {
+    fn instruction(address: u16, bytes: &[u8], instruction: Instruction) -> Line {
+        Line {
+            address,
+            bytes: bytes.to_vec(),
+            instruction: Some(instruction),
+            label: None,
+            target_label: None,
+        }
+    }
+
+    fn data(address: u16, byte: u8) -> Line {
+        Line {
+            address,
+            bytes: vec![byte],
+            instruction: None,
+            label: None,
+            target_label: None,
+        }
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.instruction.is_none()
+    }
+
+    /// The raw opcode: the first two bytes of an instruction, or the byte of data.
+    pub fn opcode(&self) -> u16 {
+        match self.bytes[..] {
+            [high, low, ..] => u16::from_be_bytes([high, low]),
+            [byte] => byte as u16,
+            [] => 0,
+        }
+    }
+
+    /// The mnemonic, such as `LD`, or `DB` for data.
+    pub fn mnemonic(&self) -> &'static str {
+        self.instruction.map_or("DB", Instruction::mnemonic)
+    }
+
+    /// The operands as written, such as `V0` and `0x05`, with the target's label standing in for its address.
+    pub fn operands(&self) -> Vec<String> {
+        let Some(instruction) = self.instruction else {
+            return vec![format!("0x{:02X}", self.bytes[0])];
+        };
+        let written = instruction.to_string();
+        let mut operands: Vec<String> = match written.split_once(' ') {
+            Some((_, operands)) => operands.split(", ").map(str::to_string).collect(),
+            None => Vec::new(),
+        };
+        // the address is always the last operand
+        if let (Some(label), Some(last)) = (&self.target_label, operands.last_mut()) {
+            *last = match last.strip_prefix("LONG ") {
+                Some(_) => format!("LONG {label}"),
+                None => label.clone(),
+            };
+        }
+        operands
+    }
+
+    /// The line as assembly source, without the address and bytes, but with labels.
+    pub fn source(&self) -> String {
+        let operands = self.operands();
+        let text = if operands.is_empty() {
+            self.mnemonic().to_string()
+        } else {
+            format!("{} {}", self.mnemonic(), operands.join(", "))
+        };
+        match &self.label {
+            Some(label) => format!("{label}: {text}"),
+            None => text,
+        }
+    }
+}
+
+/// Writes the address, the bytes a word at a time, and the instruction, with addresses rather than labels.
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = self.address;
+        match self.instruction {
+            Some(instruction) => {
+                let bytes: Vec<String> = self
+                    .bytes
+                    .chunks(2)
+                    .map(|word| word.iter().map(|byte| format!("{byte:02X}")).collect())
+                    .collect();
+                write!(f, "0x{address:03X}  {:<9}  {instruction}", bytes.join(" "))
+            }
+            None => {
+                let byte = self.bytes[0];
+                write!(f, "0x{address:03X}  {byte:02X}         DB 0x{byte:02X}")
+            }
+        }
+    }
+}
+
+/// Decodes the instruction at an offset into some code, if there's a whole one there.
+fn instruction_at(code: &[u8], offset: usize, variant: Variant) -> Option<Instruction> {
+    let word = |offset: usize| {
+        Some(u16::from_be_bytes([
+            *code.get(offset)?,
+            *code.get(offset + 1)?,
+        ]))
+    };
+    let instruction = decode_for(word(offset)?, word(offset + 2).unwrap_or(0), variant);
+    (offset + instruction.size() as usize <= code.len()).then_some(instruction)
+}
+
+/// Decodes every two bytes in turn, from the start of some code to its end. `origin` is the address the code starts
+/// at.
+pub fn sweep(code: &[u8], origin: u16, variant: Variant) -> Vec<Line> {
+    lines(code, origin, |offset| instruction_at(code, offset, variant))
+}
+
+/// Decodes only the instructions of a program reachable from its start, showing the rest as data.
+///
+/// Jumps through `BNNN` can't be followed without running the program, so code only reached that way shows as data.
+pub fn follow(rom: &[u8], variant: Variant) -> Vec<Line> {
+    let mut code = BTreeMap::new();
+    let mut pending = vec![0];
+    while let Some(offset) = pending.pop() {
+        if code.contains_key(&offset) {
+            continue;
+        }
+        let Some(instruction) = instruction_at(rom, offset, variant) else {
+            continue;
+        };
+        code.insert(offset, instruction);
+        let next = offset + instruction.size() as usize;
+        let target = |address: u16| (address as usize).checked_sub(PROGRAM_START);
+        match instruction {
+            Instruction::Jump { address } => pending.extend(target(address)),
+            Instruction::Call { address } => {
+                pending.extend(target(address).into_iter().chain([next]))
+            }
+            Instruction::SkipEqualValue { .. }
+            | Instruction::SkipNotEqualValue { .. }
+            | Instruction::SkipEqual { .. }
+            | Instruction::SkipNotEqual { .. }
+            | Instruction::SkipKeyPressed { .. }
+            | Instruction::SkipKeyNotPressed { .. } => {
+                pending.push(next);
+                // a skip passes over a whole instruction, which on XO-CHIP may be four bytes
+                if let Some(skipped) = instruction_at(rom, next, variant) {
+                    pending.push(next + skipped.size() as usize);
+                }
+            }
+            Instruction::Return
+            | Instruction::Exit
+            | Instruction::JumpOffset { .. }
+            | Instruction::MachineCall { .. }
+            | Instruction::Unknown { .. } => {}
+            _ => pending.push(next),
+        }
+    }
+    lines(rom, PROGRAM_START as u16, |offset| {
+        code.get(&offset).copied()
+    })
+}
+
+/// Splits code into lines, taking instructions where `instruction_at` finds them and bytes of data elsewhere, then
+/// labels them.
+fn lines(
+    code: &[u8],
+    origin: u16,
+    instruction_at: impl Fn(usize) -> Option<Instruction>,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let address = origin.wrapping_add(offset as u16);
+        match instruction_at(offset) {
+            Some(instruction) => {
+                let size = instruction.size() as usize;
+                lines.push(Line::instruction(
+                    address,
+                    &code[offset..offset + size],
+                    instruction,
+                ));
+                offset += size;
+            }
+            None => {
+                lines.push(Line::data(address, code[offset]));
+                offset += 1;
+            }
+        }
+    }
+    label(&mut lines);
+    lines
+}
+
+/// Names every line something refers to, after what refers to it: `sub_` for calls, `data_` for I, and `label_`
+/// for jumps.
+fn label(lines: &mut [Line]) {
+    let starts: HashMap<u16, usize> = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| (line.address, index))
+        .collect();
+    let mut labels: HashMap<u16, String> = HashMap::new();
+    for instruction in lines.iter().filter_map(|line| line.instruction) {
+        let Some(target) = instruction.target() else {
+            continue;
+        };
+        if !starts.contains_key(&target) {
+            continue;
+        }
+        let prefix = match instruction {
+            Instruction::Call { .. } => "sub",
+            Instruction::SetIndex { .. } | Instruction::SetLongIndex { .. } => "data",
+            _ => "label",
+        };
+        // a call outranks a jump, so subroutines keep their names wherever else they're jumped to from
+        if prefix == "sub" || !labels.contains_key(&target) {
+            labels.insert(target, format!("{prefix}_{target:03X}"));
+        }
+    }
+    for (&address, label) in &labels {
+        lines[starts[&address]].label = Some(label.clone());
+    }
+    for line in lines.iter_mut() {
+        let target = line.instruction.and_then(Instruction::target);
+        line.target_label = target.and_then(|target| labels.get(&target).cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble;
+
+    use super::*;
+
+    // V0 = 5, jump over a sprite byte, then jump to itself
+    const ROM: [u8; 7] = [0x60, 0x05, 0x12, 0x05, 0xF0, 0x12, 0x05];
+
+    #[test]
+    fn sweeps_every_word() {
+        let lines: Vec<String> = sweep(&ROM, 0x200, Variant::Chip8)
+            .iter()
+            .map(Line::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "0x200  6005       LD V0, 0x05",
+                "0x202  1205       JP 0x205",
+                "0x204  F012       DW 0xF012",
+                "0x206  05         DB 0x05",
+            ]
+        );
+    }
+
+    #[test]
+    fn follows_control_flow_around_data() {
+        let lines: Vec<String> = follow(&ROM, Variant::Chip8)
+            .iter()
+            .map(Line::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "0x200  6005       LD V0, 0x05",
+                "0x202  1205       JP 0x205",
+                "0x204  F0         DB 0xF0",
+                "0x205  1205       JP 0x205",
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_long_instructions() {
+        let lines = sweep(&[0xF0, 0x00, 0x12, 0x34], 0x200, Variant::XoChip);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].to_string(), "0x200  F000 1234  LD I, LONG 0x1234");
+        assert_eq!(lines[0].opcode(), 0xF000);
+        assert_eq!(lines[0].operands(), ["I", "LONG 0x1234"]);
+    }
+
+    #[test]
+    fn labels_targets_as_source() {
+        // call a subroutine, point I at a sprite, jump to self; the subroutine returns; the sprite
+        let rom = [0x22, 0x06, 0xA2, 0x08, 0x12, 0x04, 0x00, 0xEE, 0xF0];
+        let lines = follow(&rom, Variant::Chip8);
+        let source: Vec<String> = lines.iter().map(Line::source).collect();
+        assert_eq!(
+            source,
+            [
+                "CALL sub_206",
+                "LD I, data_208",
+                "label_204: JP label_204",
+                "sub_206: RET",
+                "data_208: DB 0xF0",
+            ]
+        );
+        assert_eq!(lines[1].mnemonic(), "LD");
+        assert_eq!(lines[1].target_label.as_deref(), Some("data_208"));
+        assert_eq!(
+            assemble(&source.join("\n"), Variant::Chip8),
+            Ok(rom.to_vec())
+        );
+    }
+}
@@ -1,49 +1,326 @@
-pub static FONT: [[u8; 5]; 16] = [
-    [0xF0, 0x90, 0x90, 0x90, 0xF0], // 0
-    [0x20, 0x60, 0x20, 0x20, 0x70], // 1
-    [0xF0, 0x10, 0xF0, 0x80, 0xF0], // 2
-    [0xF0, 0x10, 0xF0, 0x10, 0xF0], // 3
-    [0x90, 0x90, 0xF0, 0x10, 0x10], // 4
-    [0xF0, 0x80, 0xF0, 0x10, 0xF0], // 5
-    [0xF0, 0x80, 0xF0, 0x90, 0xF0], // 6
-    [0xF0, 0x10, 0x20, 0x40, 0x40], // 7
-    [0xF0, 0x90, 0xF0, 0x90, 0xF0], // 8
-    [0xF0, 0x90, 0xF0, 0x10, 0xF0], // 9
-    [0xF0, 0x90, 0xF0, 0x90, 0x90], // A
-    [0xE0, 0x90, 0xE0, 0x90, 0xE0], // B
-    [0xF0, 0x80, 0x80, 0x80, 0xF0], // C
-    [0xE0, 0x90, 0x90, 0x90, 0xE0], // D
-    [0xF0, 0x80, 0xF0, 0x80, 0xF0], // E
-    [0xF0, 0x80, 0xF0, 0x80, 0x80], // F
-];
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
 
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub use chip8_core::{
+    framebuffer::{HEIGHT, WIDTH},
+    memory::FONT,
+};
+
+/// The Chip8's monochrome screen.
+///
+/// The pixels live in a shared `Frame`, so handing a frame to frontends never copies it. Writing to the display
+/// only copies the pixels if a frontend is still holding on to the last frame it was given.
 pub struct Display {
-    pixels: [u8; WIDTH * HEIGHT],
+    frame: Arc<Frame>,
+    /// Whether any pixel has been written since the last frame was taken.
+    dirty: bool,
+}
+
+/// The display's pixels at the end of a frame, as handed to frontends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    fn blank() -> Frame {
+        Frame {
+            pixels: vec![0; WIDTH * HEIGHT],
+        }
+    }
+    pub fn width(&self) -> usize {
+        WIDTH
+    }
+    pub fn height(&self) -> usize {
+        HEIGHT
+    }
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[x + y * WIDTH] == 1
+    }
+    /// Sets a pixel of this frame alone, such as for drawing an overlay over a copy.
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        self.pixels[x + y * WIDTH] = on as u8;
+    }
+    /// The pixels row by row, one byte each, 1 for on and 0 for off.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    /// Writes the frame as a plain PBM image, which most image tools can read and diffs line by line.
+    pub fn to_pbm(&self) -> String {
+        let mut pbm = format!("P1\n{WIDTH} {HEIGHT}\n");
+        for row in self.pixels.chunks(WIDTH) {
+            let row: Vec<&str> = row
+                .iter()
+                .map(|&pixel| if pixel == 1 { "1" } else { "0" })
+                .collect();
+            pbm.push_str(&row.join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
+    /// Reads a PBM image of the screen's size, plain or raw, as written by `to_pbm()` or an image tool.
+    pub fn from_pbm(pbm: &[u8]) -> Result<Frame, &'static str> {
+        let (magic, rest) = pbm.split_at_checked(2).ok_or("not a pbm image")?;
+        let raw = match magic {
+            b"P1" => false,
+            b"P4" => true,
+            _ => return Err("not a pbm image"),
+        };
+        // the header is whitespace separated, with comments running to the end of a line
+        let mut fields = Vec::new();
+        let mut rest = rest;
+        while fields.len() < 2 {
+            let start = rest
+                .iter()
+                .position(|byte| !byte.is_ascii_whitespace())
+                .ok_or("pbm image is truncated")?;
+            rest = &rest[start..];
+            if rest[0] == b'#' {
+                let end = rest
+                    .iter()
+                    .position(|&byte| byte == b'\n')
+                    .unwrap_or(rest.len());
+                rest = &rest[end..];
+                continue;
+            }
+            let end = rest
+                .iter()
+                .position(|byte| byte.is_ascii_whitespace())
+                .unwrap_or(rest.len());
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        if fields != [WIDTH.to_string().as_bytes(), HEIGHT.to_string().as_bytes()] {
+            return Err("pbm image is not the size of the screen");
+        }
+        let pixels: Vec<u8> = if raw {
+            // a single whitespace byte separates the header from the bits
+            let bits = rest.get(1..).ok_or("pbm image is truncated")?;
+            bits.iter()
+                .take(WIDTH * HEIGHT / 8)
+                .flat_map(|byte| (0..8).rev().map(move |bit| byte >> bit & 1))
+                .collect()
+        } else {
+            rest.iter()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .map(|&byte| match byte {
+                    b'0' => Ok(0),
+                    b'1' => Ok(1),
+                    _ => Err("pbm image has a pixel that isn't 0 or 1"),
+                })
+                .collect::<Result<_, _>>()?
+        };
+        if pixels.len() != WIDTH * HEIGHT {
+            return Err("pbm image is not the size of the screen");
+        }
+        Ok(Frame { pixels })
+    }
+    /// A short hash of the pixels, for telling cheaply whether two frames are the same.
+    pub fn digest(&self) -> u64 {
+        let hash = Sha256::digest(&self.pixels);
+        u64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
 }
 
 impl Display {
     pub fn new() -> Display {
         Display {
-            pixels: [0; WIDTH * HEIGHT],
+            frame: Arc::new(Frame::blank()),
+            dirty: false,
         }
     }
     pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
-        self.pixels[x + y * WIDTH] = on as u8;
+        Arc::make_mut(&mut self.frame).pixels[x + y * WIDTH] = on as u8;
+        self.dirty = true;
     }
-    pub fn get_pixel(&mut self, x: usize, y: usize) -> bool {
-        self.pixels[x + y * WIDTH] == 1
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.frame.get_pixel(x, y)
     }
     pub fn clear(&mut self) {
-        for x in 0..WIDTH {
-            for y in 0..HEIGHT {
-                self.set_pixel(x, y, false);
-            }
+        match Arc::get_mut(&mut self.frame) {
+            Some(frame) => frame.pixels.fill(0),
+            // no need to copy pixels that are about to be cleared
+            None => self.frame = Arc::new(Frame::blank()),
         }
+        self.dirty = true;
+    }
+    /// Shares the current pixels as a frame, without copying them.
+    pub fn frame(&self) -> Arc<Frame> {
+        Arc::clone(&self.frame)
+    }
+    /// Replaces every pixel with those of `frame`, as when loading a savestate.
+    pub fn load_frame(&mut self, frame: Frame) {
+        self.frame = Arc::new(frame);
+        self.dirty = true;
+    }
+    /// Takes whether the display has been written to since this was last called.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
     }
-    // not implemented yet
+    /// XORs a sprite onto the display, returning whether any pixel was turned off.
+    ///
+    /// The starting position wraps around the screen, but the sprite itself is
+    /// clipped at the edges.
     pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
-        false
+        self.blit(x, y, sprite, false)
+    }
+    /// Like `draw()`, but the parts of the sprite past the edges wrap around the screen too.
+    pub fn draw_wrapping(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.blit(x, y, sprite, true)
+    }
+    fn blit(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
+        let (x, y) = (x % WIDTH, y % HEIGHT);
+        let mut collision = false;
+        for (row, byte) in sprite.iter().enumerate() {
+            let mut py = y + row;
+            if py >= HEIGHT {
+                if !wrap {
+                    break;
+                }
+                py %= HEIGHT;
+            }
+            for bit in 0..8 {
+                let mut px = x + bit;
+                if px >= WIDTH {
+                    if !wrap {
+                        break;
+                    }
+                    px %= WIDTH;
+                }
+                if byte & (0x80 >> bit) == 0 {
+                    continue;
+                }
+                let on = self.get_pixel(px, py);
+                collision |= on;
+                self.set_pixel(px, py, !on);
+            }
+        }
+        collision
+    }
+}
+
+/// Somewhere to show the display, such as a window or a terminal.
+pub trait DisplayBackend: Send {
+    /// Shows the current contents of the display. Called once per frame.
+    fn present(&mut self, display: &Display);
+}
+
+/// A backend that shows nothing, but keeps the last frame it was given, for running without a screen as in CI.
+///
+/// Clones share the same frame, so keep one to look at the frame after handing another to the machine.
+#[derive(Debug, Clone)]
+pub struct HeadlessBackend {
+    frame: Arc<Mutex<Arc<Frame>>>,
+    presented: Arc<AtomicU64>,
+}
+
+impl HeadlessBackend {
+    pub fn new() -> HeadlessBackend {
+        HeadlessBackend {
+            frame: Arc::new(Mutex::new(Arc::new(Frame::blank()))),
+            presented: Arc::new(AtomicU64::new(0)),
+        }
+    }
+    /// The last frame presented, or a blank one if none has been.
+    pub fn frame(&self) -> Arc<Frame> {
+        Arc::clone(&self.frame.lock().unwrap())
+    }
+    /// How many frames have been presented.
+    pub fn frames_presented(&self) -> u64 {
+        self.presented.load(Ordering::Relaxed)
+    }
+}
+
+impl DisplayBackend for HeadlessBackend {
+    fn present(&mut self, display: &Display) {
+        *self.frame.lock().unwrap() = display.frame();
+        self.presented.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Default for HeadlessBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_xors_and_reports_collision() {
+        let mut display = Display::new();
+        assert!(!display.draw(0, 0, &FONT[0]));
+        assert!(display.get_pixel(0, 0));
+        assert!(!display.get_pixel(1, 1));
+        assert!(display.draw(0, 0, &FONT[0]));
+        assert!(!display.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn draw_wraps_start_and_clips_edges() {
+        let mut display = Display::new();
+        display.draw(WIDTH + 62, HEIGHT + 31, &[0xFF, 0xFF]);
+        assert!(display.get_pixel(62, 31));
+        assert!(display.get_pixel(63, 31));
+        assert!(!display.get_pixel(0, 31));
+        assert!(!display.get_pixel(62, 0));
+    }
+
+    #[test]
+    fn frames_are_shared_until_written() {
+        let mut display = Display::new();
+        let first = display.frame();
+        assert!(Arc::ptr_eq(&first, &display.frame()), "frame is copied");
+        display.set_pixel(1, 1, true);
+        assert!(!first.get_pixel(1, 1), "a handed out frame changed");
+        assert!(display.frame().get_pixel(1, 1));
+    }
+
+    #[test]
+    fn headless_backend_keeps_the_last_frame() {
+        let backend = HeadlessBackend::new();
+        let mut display = Display::new();
+        display.draw(0, 0, &[0xC0]);
+        backend.clone().present(&display);
+        assert_eq!(backend.frames_presented(), 1);
+        let pbm = backend.frame().to_pbm();
+        let mut lines = pbm.lines();
+        assert_eq!(lines.next(), Some("P1"));
+        assert_eq!(lines.next(), Some("64 32"));
+        assert!(lines.next().unwrap().starts_with("1 1 0 "));
+        assert_eq!(lines.count(), HEIGHT - 1);
+        assert_eq!(
+            Frame::from_pbm(pbm.as_bytes()).as_ref(),
+            Ok(&*backend.frame())
+        );
+
+        let mut raw = b"P4\n# a comment\n64 32\n".to_vec();
+        raw.extend([0xC0]);
+        raw.extend([0; WIDTH * HEIGHT / 8 - 1]);
+        assert_eq!(Frame::from_pbm(&raw).as_ref(), Ok(&*backend.frame()));
+        assert!(Frame::from_pbm(b"P1\n8 8\n").is_err());
+    }
+
+    #[test]
+    fn draw_wrapping_wraps_edges() {
+        let mut display = Display::new();
+        display.draw_wrapping(62, 31, &[0xFF, 0xFF]);
+        assert!(display.get_pixel(0, 31));
+        assert!(display.get_pixel(62, 0));
+        assert!(display.get_pixel(5, 0));
+        assert!(!display.get_pixel(6, 0));
     }
 }
@@ -0,0 +1,108 @@
+use std::sync::{
+    mpsc::{self, Receiver, Sender},
+    Arc, Mutex,
+};
+
+use crate::{
+    debugger::StopReason,
+    display::Frame,
+    machine::{
+        Chip8Error, CrashReport, DesyncReport, HaltReason, MachineState, Movie, SaveState,
+        WatchdogReport,
+    },
+    system::TimerEvent,
+};
+
+/// Something that happened in the machine, broadcast to every subscriber.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A frame finished and the display changed since the last one. The frame is shared, not copied.
+    FrameReady(Arc<Frame>),
+    /// The tone started (`true`) or stopped (`false`).
+    SoundEdge(bool),
+    /// A timer was set or ran out.
+    Timer(TimerEvent),
+    /// A program was loaded, with its length in bytes.
+    RomLoaded { len: usize },
+    /// The machine moved to a new state.
+    StateChanged(MachineState),
+    /// The machine halted.
+    Halted(HaltReason),
+    /// The CPU couldn't run an instruction, and what the machine was doing when it failed. It's published just before
+    /// the machine halts.
+    Crashed(Arc<CrashReport>),
+    /// A command sent through a `MachineHandle` couldn't be carried out.
+    CommandFailed(Chip8Error),
+    /// The machine has fallen behind its clock, and may be stuck.
+    Watchdog(WatchdogReport),
+    /// The machine saved its state when asked to by a `MachineHandle`.
+    StateSaved(Arc<SaveState>),
+    /// The machine's state was saved to a numbered slot.
+    SlotSaved(u8),
+    /// The machine's state was loaded from a numbered slot.
+    SlotLoaded(u8),
+    /// The program just loaded has an autosave from the last time the machine shut down, which can be resumed with
+    /// `Chip8::resume_autosave()`.
+    AutosaveAvailable,
+    /// A movie finished recording when asked to by a `MachineHandle`.
+    MovieRecorded(Arc<Movie>),
+    /// A movie being played back ran out of input.
+    PlaybackFinished,
+    /// A movie being played back stopped matching its recording.
+    Desync(DesyncReport),
+    /// A `Debugger` stopped the machine, and why.
+    DebugStop(StopReason),
+}
+
+/// Broadcasts events from the machine's subsystems to any number of subscribers.
+///
+/// Each subscriber gets its own queue, so a slow subscriber never holds up the machine; drop the receiver to
+/// unsubscribe.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<Event>>>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus::default()
+    }
+
+    /// Subscribes to every event published from now on.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Sends an event to every subscriber, forgetting any that have unsubscribed.
+    pub fn publish(&self, event: Event) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broadcasts_to_every_subscriber() {
+        let bus = EventBus::new();
+        let a = bus.subscribe();
+        let b = bus.subscribe();
+        bus.publish(Event::SoundEdge(true));
+        assert_eq!(a.try_recv(), Ok(Event::SoundEdge(true)));
+        assert_eq!(b.try_recv(), Ok(Event::SoundEdge(true)));
+        drop(a);
+        bus.publish(Event::SoundEdge(false));
+        assert_eq!(bus.subscriber_count(), 1, "unsubscribed receivers are kept");
+        assert_eq!(b.try_recv(), Ok(Event::SoundEdge(false)));
+    }
+}
@@ -0,0 +1,378 @@
+//! Explaining instructions in words, with the values they work on filled in, such as `Draw a 5-byte sprite from
+//! I=0x20A at (V2=12, V3=7); VF set to 1 because pixels collided`. Stepping through a program with these is a way
+//! to learn what each instruction does.
+//!
+//! An instruction is explained from the machine as it was before it ran, which says what it's about to do. Given
+//! the machine after it ran too, the explanation goes on to say what came of it: the result, the flag and why, or
+//! whether a skip was taken.
+
+use crate::{
+    decoder::Instruction, machine::Chip8, memory::Memory, quirks::Quirks, system::REGISTER_COUNT,
+};
+
+/// What instructions read, taken from a machine before or after one runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operands {
+    pub pc: u16,
+    pub registers: [u8; REGISTER_COUNT],
+    pub index: u16,
+    /// The address a return would go back to, if in a subroutine.
+    pub return_address: Option<u16>,
+    pub delay_timer: u8,
+    pub waiting_for_key: bool,
+    pub quirks: Quirks,
+}
+
+impl Operands {
+    pub fn of(machine: &Chip8) -> Operands {
+        let cpu = machine.cpu();
+        Operands {
+            pc: cpu.pc(),
+            registers: *cpu.registers(),
+            index: cpu.index(),
+            return_address: cpu.stack().entries().last().copied(),
+            delay_timer: machine.timers().retrieve_delay_timer(),
+            waiting_for_key: cpu.is_waiting_for_key(),
+            quirks: cpu.quirks(),
+        }
+    }
+
+    /// A register as it's written in an explanation, such as `V3=0x05`.
+    fn v(&self, x: u8) -> String {
+        format!("V{x:X}=0x{:02X}", self.registers[x as usize])
+    }
+}
+
+/// Explains the instruction a machine is about to run, unless its program counter is past the end of memory.
+pub fn explain_next(machine: &Chip8) -> Option<String> {
+    let entry = machine.next_trace_entry()?;
+    Some(explain(entry.instruction, &Operands::of(machine), None))
+}
+
+/// Explains an instruction from the machine as it was before it ran, and what came of it from the machine after, if
+/// given.
+pub fn explain(instruction: Instruction, before: &Operands, after: Option<&Operands>) -> String {
+    let v = |x: u8| before.v(x);
+    let registers = |first: u8, last: u8| {
+        if first == last {
+            format!("V{first:X}")
+        } else {
+            format!("V{first:X} through V{last:X}")
+        }
+    };
+    let mut text = match instruction {
+        Instruction::ClearScreen => "Clear the screen".to_string(),
+        Instruction::Return => match before.return_address {
+            Some(address) => format!("Return from the subroutine to 0x{address:03X}"),
+            None => "Return from a subroutine, with none to return from".to_string(),
+        },
+        Instruction::MachineCall { address } => {
+            format!("Call the machine code at 0x{address:03X}, which only the original hardware could run")
+        }
+        Instruction::Jump { address } => format!("Jump to 0x{address:03X}"),
+        Instruction::Call { address } => format!(
+            "Call the subroutine at 0x{address:03X}, to return to 0x{:03X}",
+            before.pc.wrapping_add(2)
+        ),
+        Instruction::SkipEqualValue { x, value } => {
+            format!("Skip the next instruction if {} equals 0x{value:02X}", v(x))
+        }
+        Instruction::SkipNotEqualValue { x, value } => format!(
+            "Skip the next instruction if {} doesn't equal 0x{value:02X}",
+            v(x)
+        ),
+        Instruction::SkipEqual { x, y } => {
+            format!("Skip the next instruction if {} equals {}", v(x), v(y))
+        }
+        Instruction::SkipNotEqual { x, y } => format!(
+            "Skip the next instruction if {} doesn't equal {}",
+            v(x),
+            v(y)
+        ),
+        Instruction::SetValue { x, value } => format!("Set V{x:X} to 0x{value:02X}"),
+        Instruction::AddValue { x, value } => {
+            format!("Add 0x{value:02X} to {}", v(x))
+        }
+        Instruction::Set { x, y } => format!("Copy {} into V{x:X}", v(y)),
+        Instruction::Or { x, y } => format!("Set V{x:X} to {} OR {}", v(x), v(y)),
+        Instruction::And { x, y } => format!("Set V{x:X} to {} AND {}", v(x), v(y)),
+        Instruction::Xor { x, y } => format!("Set V{x:X} to {} XOR {}", v(x), v(y)),
+        Instruction::Add { x, y } => format!("Add {} to {}", v(y), v(x)),
+        Instruction::Sub { x, y } => format!("Subtract {} from {}", v(y), v(x)),
+        Instruction::SubReverse { x, y } => {
+            format!("Set V{x:X} to {} minus {}", v(y), v(x))
+        }
+        Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } => {
+            let direction = match instruction {
+                Instruction::ShiftRight { .. } => "right",
+                _ => "left",
+            };
+            if before.quirks.shift_uses_vy && x != y {
+                format!("Shift {} {direction} a bit into V{x:X}", v(y))
+            } else {
+                format!("Shift {} {direction} a bit", v(x))
+            }
+        }
+        Instruction::SetIndex { address } => format!("Point I at 0x{address:03X}"),
+        Instruction::JumpOffset { address } => {
+            let offset = if before.quirks.jump_uses_vx {
+                (address >> 8) as u8 & 0xF
+            } else {
+                0
+            };
+            let target = address.wrapping_add(before.registers[offset as usize] as u16);
+            format!(
+                "Jump to 0x{address:03X} plus {}, which is 0x{target:03X}",
+                v(offset)
+            )
+        }
+        Instruction::Random { x, mask } => {
+            format!("Set V{x:X} to a random number AND 0x{mask:02X}")
+        }
+        Instruction::Draw { x, y, height } => {
+            let (column, row) = (before.registers[x as usize], before.registers[y as usize]);
+            let sprite = match height {
+                0 => "a 16x16 sprite".to_string(),
+                height => format!("a {height}-byte sprite"),
+            };
+            let edges = if before.quirks.wrap_sprites {
+                "wrapping round the edges"
+            } else {
+                "clipped at the edges"
+            };
+            format!(
+                "Draw {sprite} from I=0x{:03X} at (V{x:X}={column}, V{y:X}={row}), {edges}",
+                before.index
+            )
+        }
+        Instruction::SkipKeyPressed { x } => format!(
+            "Skip the next instruction if key {:X} (from {}) is held",
+            before.registers[x as usize] & 0xF,
+            v(x)
+        ),
+        Instruction::SkipKeyNotPressed { x } => format!(
+            "Skip the next instruction if key {:X} (from {}) isn't held",
+            before.registers[x as usize] & 0xF,
+            v(x)
+        ),
+        Instruction::GetDelay { x } => format!(
+            "Copy the delay timer, 0x{:02X}, into V{x:X}",
+            before.delay_timer
+        ),
+        Instruction::WaitKey { x } => {
+            format!("Wait for a key to be pressed and released, then put it in V{x:X}")
+        }
+        Instruction::SetDelay { x } => format!("Set the delay timer to {}", v(x)),
+        Instruction::SetSound { x } => {
+            format!(
+                "Set the sound timer to {}, sounding the tone until it runs out",
+                v(x)
+            )
+        }
+        Instruction::AddIndex { x } => {
+            format!("Add {} to I=0x{:03X}", v(x), before.index)
+        }
+        Instruction::FontCharacter { x } => {
+            let digit = before.registers[x as usize] & 0xF;
+            format!(
+                "Point I at the font sprite for the digit {digit:X} (from {}), at 0x{:03X}",
+                v(x),
+                Memory::font_address(digit)
+            )
+        }
+        Instruction::StoreBcd { x } => {
+            let value = before.registers[x as usize];
+            format!(
+                "Write the decimal digits {}, {}, and {} of V{x:X}={value} to memory at I=0x{:03X}",
+                value / 100,
+                value / 10 % 10,
+                value % 10,
+                before.index
+            )
+        }
+        Instruction::StoreRegisters { x } => format!(
+            "Write {} to memory from I=0x{:03X}",
+            registers(0, x),
+            before.index
+        ),
+        Instruction::LoadRegisters { x } => format!(
+            "Read {} from memory at I=0x{:03X}",
+            registers(0, x),
+            before.index
+        ),
+        Instruction::ScrollDown { rows } => format!("Scroll the screen down {rows} pixels"),
+        Instruction::ScrollRight => "Scroll the screen right 4 pixels".to_string(),
+        Instruction::ScrollLeft => "Scroll the screen left 4 pixels".to_string(),
+        Instruction::Exit => "Exit the interpreter".to_string(),
+        Instruction::LowResolution => "Switch to the 64x32 screen".to_string(),
+        Instruction::HighResolution => "Switch to the 128x64 screen".to_string(),
+        Instruction::LargeFontCharacter { x } => format!(
+            "Point I at the large font sprite for the digit {:X} (from {})",
+            before.registers[x as usize] & 0xF,
+            v(x)
+        ),
+        Instruction::StoreFlags { x } => {
+            format!("Save {} in the flag registers", registers(0, x))
+        }
+        Instruction::LoadFlags { x } => {
+            format!("Load {} from the flag registers", registers(0, x))
+        }
+        Instruction::ScrollUp { rows } => format!("Scroll the screen up {rows} pixels"),
+        Instruction::StoreRange { x, y } => format!(
+            "Write {} to memory from I=0x{:03X}",
+            registers(x.min(y), x.max(y)),
+            before.index
+        ),
+        Instruction::LoadRange { x, y } => format!(
+            "Read {} from memory at I=0x{:03X}",
+            registers(x.min(y), x.max(y)),
+            before.index
+        ),
+        Instruction::SetLongIndex { address } => format!("Point I at 0x{address:04X}"),
+        Instruction::SelectPlanes { mask } => {
+            format!("Draw on the bitplanes in the mask {mask:02b} from now on")
+        }
+        Instruction::LoadAudio => {
+            format!("Load the 16-byte audio pattern at I=0x{:03X}", before.index)
+        }
+        Instruction::SetPitch { x } => format!("Set the pitch of the tone to {}", v(x)),
+        Instruction::Unknown { opcode } => {
+            format!("{opcode:04X} isn't an instruction, so running it stops the machine")
+        }
+    };
+    if let Some(after) = after {
+        text.push_str(&outcome(instruction, before, after));
+    }
+    text
+}
+
+/// What came of an instruction, to follow what it was going to do, or nothing if there's no more to say.
+fn outcome(instruction: Instruction, before: &Operands, after: &Operands) -> String {
+    let result = |x: u8| format!(", making 0x{:02X}", after.registers[x as usize]);
+    let flag = after.registers[0xF];
+    // an instruction writing VF itself loses its result to the flag
+    let with_flag = |x: u8, why: &str| {
+        if x == 0xF {
+            format!("; VF set to {flag} {why}, over the result")
+        } else {
+            format!("{}; VF set to {flag} {why}", result(x))
+        }
+    };
+    let skipped = || {
+        if after.pc == before.pc.wrapping_add(instruction.size()) {
+            "; not skipped".to_string()
+        } else {
+            "; skipped".to_string()
+        }
+    };
+    match instruction {
+        Instruction::SkipEqualValue { .. }
+        | Instruction::SkipNotEqualValue { .. }
+        | Instruction::SkipEqual { .. }
+        | Instruction::SkipNotEqual { .. }
+        | Instruction::SkipKeyPressed { .. }
+        | Instruction::SkipKeyNotPressed { .. } => skipped(),
+        Instruction::AddValue { x, .. } | Instruction::Random { x, .. } => result(x),
+        Instruction::Or { x, .. } | Instruction::And { x, .. } | Instruction::Xor { x, .. } => {
+            if before.quirks.vf_reset && x != 0xF {
+                format!("{}; VF reset to 0", result(x))
+            } else {
+                result(x)
+            }
+        }
+        Instruction::Add { x, .. } => {
+            let why = if flag == 1 {
+                "because it carried"
+            } else {
+                "as it didn't carry"
+            };
+            with_flag(x, why)
+        }
+        Instruction::Sub { x, .. } | Instruction::SubReverse { x, .. } => {
+            let why = if flag == 1 {
+                "as it didn't borrow"
+            } else {
+                "because it borrowed"
+            };
+            with_flag(x, why)
+        }
+        Instruction::ShiftRight { x, .. } | Instruction::ShiftLeft { x, .. } => {
+            with_flag(x, "from the bit shifted out")
+        }
+        Instruction::Draw { .. } => {
+            if flag == 1 {
+                "; VF set to 1 because pixels collided".to_string()
+            } else {
+                "; VF set to 0 as no pixels collided".to_string()
+            }
+        }
+        Instruction::WaitKey { x } => {
+            if after.waiting_for_key {
+                "; still waiting".to_string()
+            } else {
+                format!("; got key {:X}", after.registers[x as usize])
+            }
+        }
+        Instruction::AddIndex { .. } => format!(", making 0x{:03X}", after.index),
+        Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. }
+            if after.index != before.index =>
+        {
+            format!("; I moves on to 0x{:03X}", after.index)
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        decoder::decode,
+    };
+
+    use super::*;
+
+    #[test]
+    fn explains_instructions_with_their_values() {
+        // V2 = 12, V3 = 7, I = font 0, draw it twice, V4 = 0xF0, V4 += V4
+        let rom = [
+            0x62, 0x0C, 0x63, 0x07, 0xF0, 0x29, 0xD2, 0x35, 0xD2, 0x35, 0x64, 0xF0, 0x84, 0x44,
+        ];
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&rom).expect("failed to load rom");
+        let mut explained = Vec::new();
+        for _ in 0..7 {
+            let before = Operands::of(&machine);
+            let instruction = machine.step().expect("failed to step");
+            explained.push(explain(instruction, &before, Some(&Operands::of(&machine))));
+        }
+        assert_eq!(explained[0], "Set V2 to 0x0C");
+        assert_eq!(
+            explained[2],
+            "Point I at the font sprite for the digit 0 (from V0=0x00), at 0x050"
+        );
+        assert_eq!(
+            explained[3],
+            "Draw a 5-byte sprite from I=0x050 at (V2=12, V3=7), clipped at the edges; \
+             VF set to 0 as no pixels collided"
+        );
+        assert!(explained[4].ends_with("VF set to 1 because pixels collided"));
+        assert_eq!(
+            explained[6],
+            "Add V4=0xF0 to V4=0xF0, making 0xE0; VF set to 1 because it carried"
+        );
+
+        let before = Operands::of(&machine);
+        assert_eq!(
+            explain(decode(0x3410), &before, None),
+            "Skip the next instruction if V4=0xE0 equals 0x10"
+        );
+        assert_eq!(
+            explain_next(&machine).as_deref(),
+            Some("Call the machine code at 0x000, which only the original hardware could run")
+        );
+    }
+}
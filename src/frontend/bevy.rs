@@ -0,0 +1,225 @@
+//! A machine inside a Bevy app, for games that want a CHIP-8 cabinet of their own.
+//!
+//! The app inserts a `Chip8Cabinet` as a non-send resource, as the machine can't be shared between threads, shows
+//! the image from `screen()` however it likes, such as on a sprite or a material, and adds `Chip8Plugin` to run it.
+//! Like the browser, Bevy calls back on its own schedule, so the machine runs however many frames are owed each
+//! update rather than on a thread of its own. Keys come from `ButtonInput<KeyCode>`, named as SDL names them, and the
+//! tone is left to the app to play, as `is_tone_on()` says.
+
+use ::bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, Handle},
+    ecs::{
+        schedule::IntoSystemConfigs,
+        system::{NonSendMut, Res, ResMut},
+    },
+    image::{Image, ImageSampler},
+    input::{keyboard::KeyCode, ButtonInput},
+    time::Time,
+};
+
+use crate::{
+    clock::{ManualClock, VsyncClock},
+    config::{Color, Config},
+    display::{HEIGHT, WIDTH},
+    machine::{Chip8, Chip8Error, MachineState},
+};
+
+use super::{code_key_name, frame_to_rgba, KeyInput, Outputs, RGBA_FRAME_LEN};
+
+/// Runs the `Chip8Cabinet` resource, if there is one, every update.
+pub struct Chip8Plugin;
+
+impl Plugin for Chip8Plugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (feed_keys, run_frames).chain());
+    }
+}
+
+/// A machine, its display drawn to an image, and the keys passed on to it.
+pub struct Chip8Cabinet {
+    machine: Chip8,
+    outputs: Outputs,
+    input: KeyInput,
+    /// Says how many frames are owed at each update.
+    clock: VsyncClock,
+    foreground: Color,
+    background: Color,
+    screen: Handle<Image>,
+    /// Why the machine stopped, if it failed.
+    error: Option<Chip8Error>,
+}
+
+impl Chip8Cabinet {
+    /// Builds a machine running `rom` with `config`, which should already be `Config::for_rom()`, and adds the image
+    /// it's drawn to.
+    pub fn new(
+        rom: &[u8],
+        config: &Config,
+        images: &mut Assets<Image>,
+    ) -> Result<Chip8Cabinet, Chip8Error> {
+        let outputs = Outputs::new();
+        let builder = config
+            .builder()
+            .clock(Box::new(ManualClock::new()), config.tick_rate());
+        let mut machine = outputs.attach(builder).build()?;
+        machine.load_rom(rom)?;
+
+        let mut image = Image::default();
+        image.texture_descriptor.size.width = WIDTH as u32;
+        image.texture_descriptor.size.height = HEIGHT as u32;
+        image.data = vec![0; RGBA_FRAME_LEN];
+        // keep the pixels sharp however far the image is stretched
+        image.sampler = ImageSampler::nearest();
+        let screen = images.add(image);
+        Ok(Chip8Cabinet {
+            input: KeyInput::new(config, machine.handle()),
+            clock: VsyncClock::with_rate(config.tick_rate()),
+            machine,
+            outputs,
+            foreground: config.display.foreground,
+            background: config.display.background,
+            screen,
+            error: None,
+        })
+    }
+
+    /// The image the display is drawn to, in sRGB, a texel to a pixel.
+    pub fn screen(&self) -> Handle<Image> {
+        self.screen.clone()
+    }
+
+    pub fn machine(&self) -> &Chip8 {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Chip8 {
+        &mut self.machine
+    }
+
+    /// Whether the machine is sounding its tone.
+    pub fn is_tone_on(&self) -> bool {
+        self.outputs.tone.is_on()
+    }
+
+    /// Why the machine stopped, if it failed. It doesn't run again afterwards.
+    pub fn error(&self) -> Option<Chip8Error> {
+        self.error
+    }
+
+    fn fail(&mut self, error: Chip8Error) {
+        self.error.get_or_insert(error);
+    }
+}
+
+fn feed_keys(keys: Res<ButtonInput<KeyCode>>, cabinet: Option<NonSendMut<Chip8Cabinet>>) {
+    let Some(mut cabinet) = cabinet else {
+        return;
+    };
+    // Bevy's key codes go by the same names as the browser's
+    let pressed = keys
+        .get_just_pressed()
+        .map(|key| (format!("{key:?}"), true));
+    let released = keys
+        .get_just_released()
+        .map(|key| (format!("{key:?}"), false));
+    for (code, down) in pressed.chain(released) {
+        let key = code_key_name(&code);
+        let result = if down {
+            cabinet.input.key_down(key)
+        } else {
+            cabinet.input.key_up(key)
+        };
+        if let Err(error) = result {
+            cabinet.fail(error);
+        }
+    }
+}
+
+fn run_frames(
+    time: Res<Time>,
+    cabinet: Option<NonSendMut<Chip8Cabinet>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(mut cabinet) = cabinet else {
+        return;
+    };
+    let cabinet = &mut *cabinet;
+    if cabinet.error.is_some() {
+        return;
+    }
+    cabinet.machine.process_commands();
+    cabinet.clock.set_speed(cabinet.machine.speed());
+    // timed even while paused, so the time spent paused isn't owed afterwards
+    let due = cabinet.clock.present_after(time.delta());
+    match (cabinet.machine.state(), due) {
+        (MachineState::Stopped, _) => cabinet.fail(Chip8Error::Stopped),
+        (_, Err(error)) => cabinet.fail(Chip8Error::Clock(error)),
+        (MachineState::Paused, _) => {}
+        (_, Ok(due)) => {
+            for _ in 0..due {
+                if let Err(error) = cabinet.machine.run_frame() {
+                    cabinet.fail(error);
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(image) = images.get_mut(&cabinet.screen) {
+        frame_to_rgba(
+            &cabinet.outputs.display.frame(),
+            cabinet.foreground,
+            cabinet.background,
+            &mut image.data,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn runs_the_cabinet_and_draws_its_screen() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<Time>()
+            .add_plugins(Chip8Plugin);
+        // draw the font's 0 at the corner
+        let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        let config = Config::default();
+        let cabinet = {
+            let mut images = app.world_mut().resource_mut::<Assets<Image>>();
+            Chip8Cabinet::new(&rom, &config, &mut images).unwrap()
+        };
+        let screen = cabinet.screen();
+        app.insert_non_send_resource(cabinet);
+
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyX);
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(20));
+        app.update();
+
+        let cabinet = app.world().non_send_resource::<Chip8Cabinet>();
+        assert_eq!(cabinet.error(), None);
+        assert!(cabinet.machine().keypad().is_pressed(0x0));
+        let images = app.world().resource::<Assets<Image>>();
+        let corner = &images.get(&screen).unwrap().data[..4];
+        assert_eq!(
+            corner,
+            [
+                config.display.foreground.r,
+                config.display.foreground.g,
+                config.display.foreground.b,
+                0xFF
+            ]
+        );
+    }
+}
@@ -0,0 +1,506 @@
+//! A debugger window through egui: the screen, with panels for the registers, disassembly, memory, stack, keypad,
+//! timers, and which memory has been run, read, and written, and controls to pause, step, and run. While paused,
+//! registers and memory can be changed by typing an edit such as `V3 = 0x10` or `[0x300] = 0xFF`. Addresses are
+//! shown and typed by the labels of the program's disassembly and of any symbol file given.
+//!
+//! The machine runs on the window's thread a frame at a time, so the panels always show it between instructions.
+//! There's no sound; the timers panel shows when the tone is playing.
+
+use eframe::egui::{
+    self, Color32, ColorImage, Key, Rect, RichText, ScrollArea, Sense, TextStyle, TextureHandle,
+    TextureOptions,
+};
+
+use crate::{
+    clock::VsyncClock,
+    config::Config,
+    debugger::{Edit, Symbols},
+    disassembler::{follow, sweep},
+    display::{HEIGHT, WIDTH},
+    explain::explain_next,
+    machine::{Chip8, Chip8Error, Coverage, MachineState},
+};
+
+use super::{frame_to_rgba, FrontendError, KeyInput, Outputs, RGBA_FRAME_LEN};
+
+/// How many instructions the disassembly shows, from the program counter on.
+const DISASSEMBLY_LINES: usize = 24;
+
+/// How many bytes the memory panel shows to a row.
+const MEMORY_ROW: usize = 16;
+
+/// How many cells the coverage strip shows all of memory in.
+const COVERAGE_CELLS: usize = 256;
+
+/// The keypad as it's laid out on the COSMAC VIP.
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Runs the machine in a debugger window until the window is closed, then shuts it down so it can autosave.
+///
+/// The machine must have been built with `outputs` attached and a `ManualClock`, as the window drives it. It keeps
+/// coverage from then on, if it wasn't already. `symbols` name addresses over the labels of the disassembly; the
+/// window has no breakpoints, so those they mark are left alone. Keys are looked up in the config's keymap and
+/// hotkeys by the names SDL gives them, so one config works with every frontend.
+pub fn run(
+    mut machine: Chip8,
+    outputs: &Outputs,
+    symbols: &Symbols,
+    config: &Config,
+    title: &str,
+) -> Result<(), FrontendError> {
+    let scale = config.display.scale.max(1) as f32;
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size([WIDTH as f32 * scale + 400.0, HEIGHT as f32 * scale + 200.0]),
+        ..Default::default()
+    };
+    if machine.coverage().is_none() {
+        machine.start_coverage(Coverage::new());
+    }
+    let debugger = Debugger::new(&mut machine, outputs, symbols, config);
+    let shown = eframe::run_native(title, options, Box::new(|_| Ok(Box::new(debugger))))
+        .map_err(|error| FrontendError::Host(error.to_string()));
+    let stopped = machine.stop().map_err(FrontendError::from);
+    shown.and(stopped)
+}
+
+/// Which panels are showing.
+struct Panels {
+    registers: bool,
+    disassembly: bool,
+    memory: bool,
+    stack: bool,
+    keypad: bool,
+    timers: bool,
+    coverage: bool,
+}
+
+struct Debugger<'a> {
+    machine: &'a mut Chip8,
+    outputs: Outputs,
+    config: Config,
+    input: KeyInput,
+    /// Says how many frames are owed at each update.
+    clock: VsyncClock,
+    panels: Panels,
+    screen: Option<TextureHandle>,
+    rgba: Vec<u8>,
+    /// Why the machine was last paused by an error, or why the last edit couldn't be made.
+    error: Option<String>,
+    /// The edit being typed.
+    edit: String,
+    /// The names addresses are shown and typed by.
+    symbols: Symbols,
+}
+
+impl<'a> Debugger<'a> {
+    fn new(
+        machine: &'a mut Chip8,
+        outputs: &Outputs,
+        symbols: &Symbols,
+        config: &Config,
+    ) -> Debugger<'a> {
+        let input = KeyInput::new(config, machine.handle());
+        let mut labels = Symbols::from_disassembly(&follow(machine.rom(), machine.variant()));
+        labels.merge(symbols);
+        Debugger {
+            machine,
+            outputs: outputs.clone(),
+            config: config.clone(),
+            input,
+            clock: VsyncClock::with_rate(config.tick_rate()),
+            panels: Panels {
+                registers: true,
+                disassembly: true,
+                memory: false,
+                stack: true,
+                keypad: false,
+                timers: true,
+                coverage: false,
+            },
+            screen: None,
+            rgba: vec![0; RGBA_FRAME_LEN],
+            error: None,
+            edit: String::new(),
+            symbols: labels,
+        }
+    }
+
+    /// Runs the frames owed since the last update, pausing on an error.
+    fn advance(&mut self) {
+        self.clock.set_speed(self.machine.speed());
+        // timed even while paused, so the time spent paused isn't owed afterwards
+        let due = match self.clock.present() {
+            Ok(due) => due,
+            Err(error) => return self.fail(Chip8Error::Clock(error).to_string()),
+        };
+        if self.machine.state() == MachineState::Paused {
+            return;
+        }
+        for _ in 0..due {
+            if let Err(error) = self.machine.run_frame() {
+                self.fail(error.to_string());
+                return;
+            }
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        self.machine.pause();
+        self.error = Some(error);
+    }
+
+    /// Passes keys on to the machine, unless a panel is taking typing.
+    fn read_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let events = ctx.input(|input| input.events.clone());
+        for event in events {
+            let egui::Event::Key { key, pressed, .. } = event else {
+                continue;
+            };
+            let name = key_name(key);
+            let sent = if pressed {
+                self.input.key_down(name)
+            } else {
+                self.input.key_up(name)
+            };
+            if let Err(error) = sent {
+                self.fail(error.to_string());
+            }
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("View", |ui| {
+                let panels = &mut self.panels;
+                ui.checkbox(&mut panels.registers, "Registers");
+                ui.checkbox(&mut panels.disassembly, "Disassembly");
+                ui.checkbox(&mut panels.memory, "Memory");
+                ui.checkbox(&mut panels.stack, "Stack");
+                ui.checkbox(&mut panels.keypad, "Keypad");
+                ui.checkbox(&mut panels.timers, "Timers");
+                ui.checkbox(&mut panels.coverage, "Coverage");
+            });
+            ui.separator();
+            let paused = self.machine.state() == MachineState::Paused;
+            if paused {
+                if ui.button("Run").clicked() {
+                    self.error = None;
+                    self.machine.resume();
+                }
+            } else if ui.button("Pause").clicked() {
+                self.machine.pause();
+            }
+            if ui
+                .add_enabled(paused, egui::Button::new("Step"))
+                .on_hover_text("Runs one instruction")
+                .clicked()
+            {
+                if let Err(error) = self.machine.step() {
+                    self.fail(error.to_string());
+                }
+            }
+            if ui
+                .add_enabled(paused, egui::Button::new("Frame"))
+                .on_hover_text("Runs one frame")
+                .clicked()
+            {
+                if let Err(error) = self.machine.frame_advance() {
+                    self.fail(error.to_string());
+                }
+            }
+            if ui.button("Reset").clicked() {
+                self.error = None;
+                self.machine.reset();
+            }
+            ui.separator();
+            let edit = ui
+                .add_enabled(
+                    paused,
+                    egui::TextEdit::singleline(&mut self.edit)
+                        .hint_text("V3 = 0x10")
+                        .desired_width(140.0),
+                )
+                .on_hover_text(
+                    "Changes a register or memory, such as `PC = 0x204` or `[0x300] = 0xFF`",
+                );
+            if edit.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                self.apply_edit();
+            }
+        });
+    }
+
+    /// Makes the change typed, labels and all, keeping it to fix if it can't be made.
+    fn apply_edit(&mut self) {
+        let applied = Edit::parse(&self.edit, &self.symbols)
+            .and_then(|edit| edit.apply(self.machine).map_err(|error| error.to_string()));
+        match applied {
+            Ok(()) => {
+                self.edit.clear();
+                self.error = None;
+            }
+            Err(message) => self.error = Some(message),
+        }
+    }
+
+    fn status(&self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let state = match self.machine.state() {
+                MachineState::Paused => "paused",
+                MachineState::Stopped => "stopped",
+                MachineState::Ready | MachineState::Running => "running",
+            };
+            ui.label(format!("{state}  frame {}", self.machine.frame_count()));
+            // while paused, stepping is easier to follow knowing what comes next
+            if state == "paused" {
+                if let Some(explanation) = explain_next(self.machine) {
+                    ui.separator();
+                    ui.label(format!("next: {explanation}"));
+                }
+            }
+            if let Some(error) = &self.error {
+                ui.separator();
+                ui.colored_label(Color32::LIGHT_RED, error);
+            }
+        });
+    }
+
+    /// Draws the display as large as fits, keeping its shape. It's drawn as it is, not as last presented, so steps
+    /// show up.
+    fn screen(&mut self, ui: &mut egui::Ui) {
+        frame_to_rgba(
+            &self.machine.display().frame(),
+            self.config.display.foreground,
+            self.config.display.background,
+            &mut self.rgba,
+        );
+        let image = ColorImage::from_rgba_unmultiplied([WIDTH, HEIGHT], &self.rgba);
+        let texture = match &mut self.screen {
+            Some(texture) => {
+                texture.set(image, TextureOptions::NEAREST);
+                texture
+            }
+            None => self.screen.insert(ui.ctx().load_texture(
+                "screen",
+                image,
+                TextureOptions::NEAREST,
+            )),
+        };
+        let available = ui.available_size();
+        let scale = (available.x / WIDTH as f32).min(available.y / HEIGHT as f32);
+        let size = egui::vec2(WIDTH as f32, HEIGHT as f32) * scale;
+        ui.centered_and_justified(|ui| {
+            ui.add(egui::Image::new(&*texture).fit_to_exact_size(size));
+        });
+    }
+
+    fn keypad(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("keypad").show(ui, |ui| {
+            for row in KEYPAD_LAYOUT {
+                for key in row {
+                    let pressed = self.machine.keypad().is_pressed(key);
+                    let label = RichText::new(format!("{key:X}")).monospace();
+                    // clicking toggles the key, for holding keys down while stepping
+                    if ui.selectable_label(pressed, label).clicked() {
+                        let keypad = self.machine.keypad_mut();
+                        if pressed {
+                            keypad.release(key);
+                        } else {
+                            keypad.press(key);
+                        }
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    }
+}
+
+impl eframe::App for Debugger<'_> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.machine.process_commands();
+        self.read_keys(ctx);
+        self.advance();
+
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| self.controls(ui));
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| self.status(ui));
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none().fill(Color32::BLACK))
+            .show(ctx, |ui| self.screen(ui));
+
+        let (machine, symbols) = (&*self.machine, &self.symbols);
+        egui::Window::new("Registers")
+            .open(&mut self.panels.registers)
+            .show(ctx, |ui| registers(ui, machine));
+        egui::Window::new("Disassembly")
+            .open(&mut self.panels.disassembly)
+            .show(ctx, |ui| disassembly(ui, machine, symbols));
+        egui::Window::new("Memory")
+            .open(&mut self.panels.memory)
+            .show(ctx, |ui| memory(ui, machine));
+        egui::Window::new("Stack")
+            .open(&mut self.panels.stack)
+            .show(ctx, |ui| stack(ui, machine, symbols));
+        egui::Window::new("Timers")
+            .open(&mut self.panels.timers)
+            .show(ctx, |ui| timers(ui, machine, &self.outputs));
+        egui::Window::new("Coverage")
+            .open(&mut self.panels.coverage)
+            .show(ctx, |ui| coverage(ui, machine));
+        let mut keypad = self.panels.keypad;
+        egui::Window::new("Keypad")
+            .open(&mut keypad)
+            .show(ctx, |ui| self.keypad(ui));
+        self.panels.keypad = keypad;
+
+        if self.machine.state() != MachineState::Paused {
+            ctx.request_repaint();
+        }
+    }
+}
+
+fn registers(ui: &mut egui::Ui, machine: &Chip8) {
+    let cpu = machine.cpu();
+    egui::Grid::new("registers").show(ui, |ui| {
+        for (row, values) in cpu.registers().chunks(4).enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                ui.monospace(format!("V{:X} {value:02X}", row * 4 + column));
+            }
+            ui.end_row();
+        }
+    });
+    ui.separator();
+    ui.monospace(format!("PC {:03X}   I {:03X}", cpu.pc(), cpu.index()));
+}
+
+fn disassembly(ui: &mut egui::Ui, machine: &Chip8, symbols: &Symbols) {
+    let memory = machine.memory();
+    let pc = machine.cpu().pc() as usize;
+    // as many bytes as the longest instructions could take
+    let end = (pc + 4 * DISASSEMBLY_LINES).min(memory.len());
+    let code = memory
+        .slice(pc as u16, end.saturating_sub(pc))
+        .unwrap_or_default();
+    let mut lines = sweep(code, pc as u16, machine.variant());
+    symbols.apply(&mut lines);
+    for (row, line) in lines.iter().take(DISASSEMBLY_LINES).enumerate() {
+        let marker = if row == 0 { "▶" } else { " " };
+        let (address, opcode) = (line.address, line.opcode());
+        let label = line.label.as_deref().unwrap_or_default();
+        ui.monospace(format!(
+            "{marker} {address:03X}  {opcode:04X}  {label:<10} {} {}",
+            line.mnemonic(),
+            line.operands().join(", ")
+        ));
+    }
+}
+
+fn memory(ui: &mut egui::Ui, machine: &Chip8) {
+    let memory = machine.memory();
+    let row_height = ui.text_style_height(&TextStyle::Monospace);
+    let rows = memory.len().div_ceil(MEMORY_ROW);
+    ScrollArea::vertical().show_rows(ui, row_height, rows, |ui, range| {
+        for row in range {
+            let address = (row * MEMORY_ROW) as u16;
+            let len = MEMORY_ROW.min(memory.len() - row * MEMORY_ROW);
+            let Ok(bytes) = memory.slice(address, len) else {
+                break;
+            };
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            ui.monospace(format!("{address:03X}  {}", bytes.join(" ")));
+        }
+    });
+}
+
+fn stack(ui: &mut egui::Ui, machine: &Chip8, symbols: &Symbols) {
+    let stack = machine.cpu().stack();
+    ui.label(format!("{} of {}", stack.depth(), stack.capacity()));
+    // the top of the stack first, as it's where the next return goes
+    for (depth, address) in stack.entries().iter().enumerate().rev() {
+        let name = symbols.describe(*address).unwrap_or_default();
+        ui.monospace(format!("{depth:2}  {address:03X}  {name}"));
+    }
+}
+
+fn timers(ui: &mut egui::Ui, machine: &Chip8, outputs: &Outputs) {
+    let timers = machine.timers();
+    ui.monospace(format!("delay {:3}", timers.retrieve_delay_timer()));
+    ui.monospace(format!("sound {:3}", timers.retrieve_sound_timer()));
+    let tone = if outputs.tone.is_on() { "on" } else { "off" };
+    ui.monospace(format!("tone  {tone}"));
+}
+
+/// All of memory as a strip, each cell coloured by what's been done with the bytes it stands for: green where
+/// they've been run, red written, and blue read.
+fn coverage(ui: &mut egui::Ui, machine: &Chip8) {
+    let Some(coverage) = machine.coverage() else {
+        ui.label("Not keeping coverage");
+        return;
+    };
+    ui.monospace(format!(
+        "{} bytes run, {} read, {} written",
+        coverage.executed(),
+        coverage.read(),
+        coverage.written()
+    ));
+    let len = machine.memory().len();
+    let width = ui.available_width().max(COVERAGE_CELLS as f32);
+    let (strip, response) = ui.allocate_exact_size(egui::vec2(width, 24.0), Sense::hover());
+    let painter = ui.painter_at(strip);
+    let cell_width = width / COVERAGE_CELLS as f32;
+    let cell_bytes = len.div_ceil(COVERAGE_CELLS);
+    for cell in 0..COVERAGE_CELLS {
+        let start = cell * cell_bytes;
+        let touched = coverage.any(start..start + cell_bytes);
+        let color = if touched.executed {
+            Color32::GREEN
+        } else if touched.written {
+            Color32::RED
+        } else if touched.read {
+            Color32::LIGHT_BLUE
+        } else {
+            Color32::DARK_GRAY
+        };
+        let left = strip.left() + cell as f32 * cell_width;
+        let rect = Rect::from_x_y_ranges(left..=left + cell_width, strip.y_range());
+        painter.rect_filled(rect, 0.0, color);
+    }
+    if let Some(pointer) = response.hover_pos() {
+        let cell = ((pointer.x - strip.left()) / cell_width) as usize;
+        let start = cell.min(COVERAGE_CELLS - 1) * cell_bytes;
+        response.on_hover_text(format!(
+            "{start:03X}-{:03X}: {}",
+            start + cell_bytes - 1,
+            coverage.any(start..start + cell_bytes)
+        ));
+    }
+}
+
+/// The name SDL gives a key, which is what the config file uses.
+fn key_name(key: Key) -> &'static str {
+    match key {
+        Key::Enter => "Return",
+        Key::Equals => "=",
+        Key::Minus => "-",
+        Key::Comma => ",",
+        Key::Period => ".",
+        Key::Slash => "/",
+        Key::Semicolon => ";",
+        Key::Quote => "'",
+        Key::OpenBracket => "[",
+        Key::CloseBracket => "]",
+        Key::Backslash => "\\",
+        Key::Backtick => "`",
+        // letters, digits, function keys, and the rest are named the same in both
+        _ => key.name(),
+    }
+}
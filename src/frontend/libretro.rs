@@ -0,0 +1,550 @@
+//! A libretro core, so RetroArch and other libretro frontends can run programs with their own video, audio, input,
+//! and savestate handling.
+//!
+//! Build the library as a `cdylib` with the `libretro` feature and name it as frontends expect:
+//!
+//! ```text
+//! cargo rustc --release --lib --crate-type cdylib --features libretro
+//! cp target/release/libchip8_rust.so chip8_libretro.so
+//! ```
+//!
+//! The frontend calls in on one thread and drives the machine a frame at a time. The keypad is played with the
+//! keyboard, by the letters and digits in the config's keymap, or with the RetroPad, by the `Pad` names in it. The
+//! config file is read as usual, but hotkeys and autosaves are left to the frontend.
+
+use std::{
+    ffi::{c_char, c_uint, c_void},
+    ptr, slice,
+    sync::Mutex,
+};
+
+use crate::{
+    clock::ManualClock,
+    config::{Color, Config},
+    display::{Frame, HEIGHT, WIDTH},
+    machine::{Chip8, Chip8Error, SaveState},
+};
+
+use super::{Outputs, SquareWave};
+
+/// The version of the libretro API this core implements.
+const API_VERSION: c_uint = 1;
+
+const ENVIRONMENT_SET_MESSAGE: c_uint = 6;
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const REGION_NTSC: c_uint = 0;
+const DEVICE_JOYPAD: c_uint = 1;
+const DEVICE_KEYBOARD: c_uint = 3;
+
+/// The RetroPad buttons by their libretro IDs, named as controller buttons are in the keymap.
+const PAD_BUTTONS: [(c_uint, &str); 10] = [
+    (0, "PadB"),
+    (1, "PadY"),
+    (2, "PadBack"),
+    (3, "PadStart"),
+    (4, "PadUp"),
+    (5, "PadDown"),
+    (6, "PadLeft"),
+    (7, "PadRight"),
+    (8, "PadA"),
+    (9, "PadX"),
+];
+
+const SAMPLE_RATE: f64 = 44_100.0;
+
+/// How long messages stay on screen, in frames.
+const MESSAGE_FRAMES: c_uint = 180;
+
+type EnvironmentFn = extern "C" fn(command: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn =
+    extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = extern "C" fn();
+type InputStateFn = extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    geometry: GameGeometry,
+    timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct Message {
+    text: *const c_char,
+    frames: c_uint,
+}
+
+/// The frontend's callbacks, as it hands them over.
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// The loaded program's machine, between `retro_load_game()` and `retro_unload_game()`.
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+struct Core {
+    machine: Chip8,
+    config: Config,
+    wave: SquareWave,
+    /// The screen as the frontend takes it, a `0x00RRGGBB` word to a pixel.
+    xrgb: Vec<u32>,
+    samples: Vec<f32>,
+    stereo: Vec<i16>,
+    /// Part of a sample owed from earlier frames.
+    samples_owed: f64,
+    /// How long every state of this machine encodes to.
+    state_size: usize,
+    /// Set once the machine fails, so it stops running rather than failing every frame.
+    failed: bool,
+}
+
+impl Core {
+    fn new(rom: &[u8], config: Config) -> Result<Core, Chip8Error> {
+        let outputs = Outputs::new();
+        let builder = config
+            .builder()
+            .clock(Box::new(ManualClock::new()), config.tick_rate())
+            .autosave(false);
+        let mut machine = outputs.attach(builder).build()?;
+        machine.load_rom(rom)?;
+        let wave = SquareWave::new(outputs.tone.clone(), &config.audio, SAMPLE_RATE as u32);
+        let state_size = machine.save_state().encode().len();
+        Ok(Core {
+            machine,
+            config,
+            wave,
+            xrgb: vec![0; WIDTH * HEIGHT],
+            samples: Vec::new(),
+            stereo: Vec::new(),
+            samples_owed: 0.0,
+            state_size,
+            failed: false,
+        })
+    }
+
+    fn fps(&self) -> f64 {
+        self.config.tick_rate().hz()
+    }
+
+    /// Holds down the keypad keys whose keys or buttons are held.
+    fn read_input(&mut self, input_state: InputStateFn) {
+        let mut mask = 0;
+        for (name, &key) in &self.config.keymap {
+            let held = PAD_BUTTONS
+                .iter()
+                .find(|(_, button)| button == name)
+                .map(|&(id, _)| input_state(0, DEVICE_JOYPAD, 0, id))
+                .or_else(|| retro_key(name).map(|id| input_state(0, DEVICE_KEYBOARD, 0, id)))
+                .is_some_and(|state| state != 0);
+            if held {
+                mask |= 1 << key;
+            }
+        }
+        self.machine.keypad_mut().set_mask(mask);
+    }
+
+    /// Fills the stereo buffer with a frame's worth of the tone.
+    fn fill_audio(&mut self) {
+        let owed = self.samples_owed + SAMPLE_RATE / self.fps();
+        self.samples_owed = owed.fract();
+        self.samples.resize(owed as usize, 0.0);
+        self.wave.fill(&mut self.samples);
+        self.stereo.clear();
+        for &sample in &self.samples {
+            let sample = (sample * i16::MAX as f32) as i16;
+            self.stereo.extend([sample, sample]);
+        }
+    }
+
+    fn draw(&mut self, frame: &Frame) {
+        let word = |color: Color| u32::from_be_bytes([0, color.r, color.g, color.b]);
+        let foreground = word(self.config.display.foreground);
+        let background = word(self.config.display.background);
+        for (pixel, &on) in self.xrgb.iter_mut().zip(frame.pixels()) {
+            *pixel = if on == 1 { foreground } else { background };
+        }
+    }
+
+    /// Writes the state into `buffer`, which must be `state_size` long.
+    fn serialize(&self, buffer: &mut [u8]) -> bool {
+        let state = self.machine.save_state().encode();
+        if state.len() != buffer.len() {
+            return false;
+        }
+        buffer.copy_from_slice(&state);
+        true
+    }
+
+    fn unserialize(&mut self, buffer: &[u8]) -> Result<(), Chip8Error> {
+        self.machine.load_state(SaveState::decode(buffer)?)?;
+        self.failed = false;
+        Ok(())
+    }
+}
+
+/// The libretro keycode of a key named in the keymap, for the letters and digits, which libretro numbers as
+/// lowercase ASCII.
+fn retro_key(name: &str) -> Option<c_uint> {
+    match name.as_bytes() {
+        &[byte] if byte.is_ascii_alphanumeric() => Some(byte.to_ascii_lowercase() as c_uint),
+        _ => None,
+    }
+}
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap()
+}
+
+/// Shows a message over the game, if the frontend will.
+fn show_message(environment: Option<EnvironmentFn>, text: &str) {
+    let Some(environment) = environment else {
+        return;
+    };
+    let text = format!("chip8: {text}\0");
+    let mut message = Message {
+        text: text.as_ptr().cast(),
+        frames: MESSAGE_FRAMES,
+    };
+    environment(
+        ENVIRONMENT_SET_MESSAGE,
+        (&mut message as *mut Message).cast(),
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+/// Unused, as the core hands over a frame's audio at once.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    retro_unload_game();
+}
+
+/// # Safety
+///
+/// `info` must point to a `retro_system_info` to fill in.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    // SAFETY: the frontend passes a valid pointer, as the API requires
+    let info = unsafe { &mut *info };
+    info.library_name = c"chip8-rust".as_ptr();
+    info.library_version = concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast();
+    info.valid_extensions = c"ch8|c8|sc8|xo8".as_ptr();
+    info.need_fullpath = false;
+    info.block_extract = false;
+}
+
+/// # Safety
+///
+/// `info` must point to a `retro_system_av_info` to fill in.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let fps = CORE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or_else(|| Config::default().tick_rate().hz(), Core::fps);
+    // SAFETY: the frontend passes a valid pointer, as the API requires
+    let info = unsafe { &mut *info };
+    info.geometry = GameGeometry {
+        base_width: WIDTH as c_uint,
+        base_height: HEIGHT as c_uint,
+        max_width: WIDTH as c_uint,
+        max_height: HEIGHT as c_uint,
+        aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+    };
+    info.timing = SystemTiming {
+        fps,
+        sample_rate: SAMPLE_RATE,
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.machine.reset();
+        core.failed = false;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = callbacks();
+    let mut core = CORE.lock().unwrap();
+    let Some(core) = core.as_mut() else {
+        return;
+    };
+    if let Some(input_poll) = callbacks.input_poll {
+        input_poll();
+    }
+    if let Some(input_state) = callbacks.input_state {
+        core.read_input(input_state);
+    }
+    if !core.failed {
+        if let Err(error) = core.machine.run_frame() {
+            core.failed = true;
+            show_message(callbacks.environment, &error.to_string());
+        }
+    }
+    let frame = core.machine.display().frame();
+    core.draw(&frame);
+    if let Some(video_refresh) = callbacks.video_refresh {
+        video_refresh(
+            core.xrgb.as_ptr().cast(),
+            WIDTH as c_uint,
+            HEIGHT as c_uint,
+            WIDTH * 4,
+        );
+    }
+    core.fill_audio();
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        audio_sample_batch(core.stereo.as_ptr(), core.stereo.len() / 2);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    CORE.lock()
+        .unwrap()
+        .as_ref()
+        .map_or(0, |core| core.state_size)
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = CORE.lock().unwrap();
+    let Some(core) = core.as_ref() else {
+        return false;
+    };
+    // SAFETY: the frontend passes a buffer of the size it says, as the API requires
+    let buffer = unsafe { slice::from_raw_parts_mut(data.cast::<u8>(), size) };
+    core.serialize(buffer)
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let environment = callbacks().environment;
+    let mut core = CORE.lock().unwrap();
+    let Some(core) = core.as_mut() else {
+        return false;
+    };
+    // SAFETY: the frontend passes a buffer of the size it says, as the API requires
+    let buffer = unsafe { slice::from_raw_parts(data.cast::<u8>(), size) };
+    match core.unserialize(buffer) {
+        Ok(()) => true,
+        Err(error) => {
+            show_message(environment, &error.to_string());
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+///
+/// `game` must be null or point to a `retro_game_info` whose data is `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    // SAFETY: the frontend passes a valid pointer or null, as the API requires
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let callbacks = callbacks();
+    let Some(environment) = callbacks.environment else {
+        return false;
+    };
+    let mut format = PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        ENVIRONMENT_SET_PIXEL_FORMAT,
+        (&mut format as *mut c_uint).cast(),
+    ) {
+        return false;
+    }
+    // SAFETY: as above
+    let rom = unsafe { slice::from_raw_parts(game.data.cast::<u8>(), game.size) };
+    let core = Config::load()
+        .and_then(|config| config.for_rom(rom, None))
+        .map_err(|error| error.to_string())
+        .and_then(|config| Core::new(rom, config).map_err(|error| error.to_string()));
+    match core {
+        Ok(core) => {
+            *CORE.lock().unwrap() = Some(core);
+            true
+        }
+        Err(error) => {
+            show_message(Some(environment), &error);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    if let Some(mut core) = CORE.lock().unwrap().take() {
+        // there's no one to tell if shutting down fails
+        let _ = core.machine.stop();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+/// The machine's memory isn't shared, so frontends keep no save RAM or memory maps for it.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn states_fit_the_same_buffer() {
+        // 6001 7001 1202: count up in V0 forever
+        let rom = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+        let mut core = Core::new(&rom, Config::default()).expect("failed to build core");
+        let mut saved = vec![0; core.state_size];
+        assert!(core.serialize(&mut saved));
+        core.machine.run_frame().expect("failed to run frame");
+        let mut later = vec![0; core.state_size];
+        assert!(core.serialize(&mut later));
+        assert_ne!(saved, later);
+        core.unserialize(&saved).expect("failed to load state");
+        assert_eq!(core.machine.cpu().registers()[0], 0);
+    }
+
+    #[test]
+    fn states_keep_their_size_after_a_key_release() {
+        // jump to self, so nothing ever takes the release
+        let mut core = Core::new(&[0x12, 0x00], Config::default()).expect("failed to build core");
+        core.machine.keypad_mut().press(0x5);
+        core.machine.run_frame().expect("failed to run frame");
+        core.machine.keypad_mut().release(0x5);
+        let mut state = vec![0; core.state_size];
+        assert!(core.serialize(&mut state));
+        core.unserialize(&state).expect("failed to load state");
+        assert_eq!(core.machine.keypad_mut().take_released(), Some(0x5));
+    }
+
+    #[test]
+    fn keymap_letters_and_digits_are_keyboard_keys() {
+        assert_eq!(retro_key("Q"), Some(b'q' as c_uint));
+        assert_eq!(retro_key("4"), Some(b'4' as c_uint));
+        assert_eq!(retro_key("PadA"), None);
+        assert_eq!(retro_key("="), None);
+    }
+}
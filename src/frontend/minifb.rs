@@ -0,0 +1,174 @@
+//! A window and keyboard input through minifb, for a window with as little underneath it as can be.
+//!
+//! There's no sound and no controllers. Being the smallest frontend, it's also the one to read first when writing
+//! another.
+
+use std::borrow::Cow;
+
+use ::minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+
+use crate::{
+    config::{Color, Config},
+    display::{Frame, HEIGHT, WIDTH},
+    speed::Speed,
+};
+
+use super::{Frontend, FrontendError, InputEvent};
+
+/// How often the window is redrawn, which paces the loop as there's no vsync to wait for.
+const FRAMES_PER_SECOND: usize = 60;
+
+/// Shows the machine in a resizable window, and plays it with the keyboard.
+#[derive(Default)]
+pub struct MinifbFrontend {
+    window: Option<MinifbWindow>,
+}
+
+/// What minifb keeps while the window is open.
+struct MinifbWindow {
+    window: Window,
+    /// The frame as minifb takes it, a `0RGB` word to a pixel.
+    buffer: Vec<u32>,
+    foreground: u32,
+    background: u32,
+}
+
+impl MinifbFrontend {
+    pub fn new() -> MinifbFrontend {
+        MinifbFrontend::default()
+    }
+
+    fn window(&mut self) -> Result<&mut MinifbWindow, FrontendError> {
+        self.window
+            .as_mut()
+            .ok_or_else(|| FrontendError::Host("the window isn't open".to_string()))
+    }
+}
+
+impl Frontend for MinifbFrontend {
+    fn open(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let scale = config.display.scale.max(1) as usize;
+        let options = WindowOptions {
+            resize: true,
+            // keep the picture's shape however the window is resized
+            scale_mode: ScaleMode::AspectRatioStretch,
+            ..WindowOptions::default()
+        };
+        let mut window = Window::new(title, WIDTH * scale, HEIGHT * scale, options)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        window.set_target_fps(FRAMES_PER_SECOND);
+        self.window = Some(MinifbWindow {
+            window,
+            buffer: vec![0; WIDTH * HEIGHT],
+            foreground: rgb(config.display.foreground),
+            background: rgb(config.display.background),
+        });
+        Ok(())
+    }
+
+    fn configure(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        window.window.set_title(title);
+        window.foreground = rgb(config.display.foreground);
+        window.background = rgb(config.display.background);
+        Ok(())
+    }
+
+    /// Hands back the keys that went down and up as the window was last drawn.
+    fn poll(&mut self) -> Result<Vec<InputEvent>, FrontendError> {
+        let window = &self.window()?.window;
+        if !window.is_open() {
+            return Ok(vec![InputEvent::Quit]);
+        }
+        let pressed = window.get_keys_pressed(KeyRepeat::No).into_iter();
+        let released = window.get_keys_released().into_iter();
+        Ok(pressed
+            .map(|key| InputEvent::KeyDown(key_name(key).into_owned()))
+            .chain(released.map(|key| InputEvent::KeyUp(key_name(key).into_owned())))
+            .collect())
+    }
+
+    fn present(&mut self, frame: &Frame, _speed: Speed) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        for (pixel, &on) in window.buffer.iter_mut().zip(frame.pixels()) {
+            *pixel = if on == 1 {
+                window.foreground
+            } else {
+                window.background
+            };
+        }
+        // waits out the rest of the frame, which paces the loop
+        window
+            .window
+            .update_with_buffer(&window.buffer, WIDTH, HEIGHT)
+            .map_err(|error| FrontendError::Host(error.to_string()))
+    }
+
+    fn set_tone(&mut self, _on: bool) {}
+
+    fn close(&mut self) -> Result<(), FrontendError> {
+        self.window = None;
+        Ok(())
+    }
+}
+
+fn rgb(color: Color) -> u32 {
+    u32::from_be_bytes([0, color.r, color.g, color.b])
+}
+
+/// The name SDL gives a key, which is what the config file uses.
+fn key_name(key: Key) -> Cow<'static, str> {
+    let name = match key {
+        Key::Key0 => "0",
+        Key::Key1 => "1",
+        Key::Key2 => "2",
+        Key::Key3 => "3",
+        Key::Key4 => "4",
+        Key::Key5 => "5",
+        Key::Key6 => "6",
+        Key::Key7 => "7",
+        Key::Key8 => "8",
+        Key::Key9 => "9",
+        Key::Equal => "=",
+        Key::Minus => "-",
+        Key::Comma => ",",
+        Key::Period => ".",
+        Key::Slash => "/",
+        Key::Semicolon => ";",
+        Key::Apostrophe => "'",
+        Key::LeftBracket => "[",
+        Key::RightBracket => "]",
+        Key::Backslash => "\\",
+        Key::Backquote => "`",
+        Key::Enter => "Return",
+        // letters, function keys, arrows, and the rest are named the same in both
+        _ => return Cow::Owned(format!("{key:?}")),
+    };
+    Cow::Borrowed(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_keys_as_sdl_does() {
+        assert_eq!(key_name(Key::Q), "Q");
+        assert_eq!(key_name(Key::Key4), "4");
+        assert_eq!(key_name(Key::Equal), "=");
+        assert_eq!(key_name(Key::Left), "Left");
+        assert_eq!(key_name(Key::F5), "F5");
+    }
+
+    #[test]
+    fn packs_colors_as_0rgb() {
+        assert_eq!(
+            rgb(Color {
+                r: 0x12,
+                g: 0x34,
+                b: 0x56
+            }),
+            0x0012_3456
+        );
+    }
+}
@@ -0,0 +1,990 @@
+//! What the windowed frontends have in common: running the machine on a thread of its own, turning host keys into
+//! keypad presses and hotkeys, drawing frames in colour, and making the tone.
+//!
+//! A frontend implements `Frontend`, and `run()` does the rest: it starts the machine with `MachineThread::spawn()`,
+//! passes the frontend's input on, and has it show whatever the machine last presented through the `Outputs`
+//! attached as it was built, with the `Osd` drawn over it, and any `DebugView` windows it has open. Frontends whose
+//! hosts call back on their own schedule, such as the browser, drive the machine frame by frame themselves instead.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs, mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Receiver,
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    audio::AudioSink,
+    config::{AudioConfig, Color, Config, LiveChange},
+    display::{Frame, HeadlessBackend, HEIGHT, WIDTH},
+    events::Event,
+    hotkeys::{Hotkey, Hotkeys},
+    machine::{Chip8, Chip8Builder, Chip8Error, Command, MachineHandle, MachineState},
+    speed::Speed,
+};
+
+#[cfg(feature = "bevy_chip8")]
+pub mod bevy;
+#[cfg(feature = "egui")]
+pub mod egui;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "minifb")]
+pub mod minifb;
+pub mod osd;
+#[cfg(feature = "pixels")]
+pub mod pixels;
+mod scripted;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod views;
+#[cfg(feature = "wasm")]
+pub mod web;
+
+/// Why a frontend stopped or couldn't start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrontendError {
+    Machine(Chip8Error),
+    /// The windowing or audio library failed, described by the message.
+    Host(String),
+}
+
+impl fmt::Display for FrontendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrontendError::Machine(error) => write!(f, "{error}"),
+            FrontendError::Host(message) => write!(f, "frontend error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for FrontendError {}
+
+impl From<Chip8Error> for FrontendError {
+    fn from(error: Chip8Error) -> Self {
+        FrontendError::Machine(error)
+    }
+}
+
+pub use osd::{MenuChoice, Osd};
+pub use scripted::ScriptedFrontend;
+pub use views::{DebugView, ViewImage};
+
+/// Something that comes in from the host, with keys named as the config file names them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(String),
+    KeyUp(String),
+    /// A file was dropped on the window, to run in place of the program.
+    OpenRom(PathBuf),
+    /// The window was closed, or the player otherwise asked to stop.
+    Quit,
+}
+
+/// A way of showing a machine and playing it: a window, a terminal, or a script in a test.
+///
+/// `run()` calls `open()` once, then `poll()` and `present()` in turn for as long as the machine runs, with
+/// `configure()` when another program is opened, then `close()`, even if something failed along the way.
+pub trait Frontend {
+    /// Opens the window, or whatever the frontend shows the machine in.
+    fn open(&mut self, config: &Config, title: &str) -> Result<(), FrontendError>;
+
+    /// Takes up the settings and title of another program, opened while the window is.
+    fn configure(&mut self, config: &Config, title: &str) -> Result<(), FrontendError>;
+
+    /// Takes the input that's come in since the last poll. Frontends without vsync wait here for about a frame,
+    /// so the loop doesn't spin.
+    fn poll(&mut self) -> Result<Vec<InputEvent>, FrontendError>;
+
+    /// Shows a frame, along with the speed the machine runs at for frontends with room for it.
+    fn present(&mut self, frame: &Frame, speed: Speed) -> Result<(), FrontendError>;
+
+    /// Starts or stops the tone.
+    fn set_tone(&mut self, on: bool);
+
+    /// Opens a window for a debug view, or closes it if it's open. Frontends with room for only the one window
+    /// leave this be.
+    fn toggle_view(&mut self, _view: DebugView) -> Result<(), FrontendError> {
+        Ok(())
+    }
+
+    /// The debug views with windows open, in no particular order.
+    fn open_views(&self) -> Vec<DebugView> {
+        Vec::new()
+    }
+
+    /// Shows a debug view in its window, if it's open.
+    fn present_view(&mut self, _view: DebugView, _image: &ViewImage) -> Result<(), FrontendError> {
+        Ok(())
+    }
+
+    /// Closes the window and puts the host back as it was.
+    fn close(&mut self) -> Result<(), FrontendError>;
+}
+
+/// Works out the settings for a program opened while running, from its contents and where it came from.
+pub type ConfigForRom<'a> = dyn Fn(&[u8], &Path) -> Result<Config, FrontendError> + 'a;
+
+/// Runs the machine in real time and shows it through the frontend until the player quits or the machine stops,
+/// then shuts it down so it can autosave.
+///
+/// The machine must have been built with `outputs` attached, running the program at `rom`. Keys are looked up in the
+/// config's keymap and hotkeys, and the menu hotkey pauses the machine and opens the pause menu. A program dropped
+/// on the window, or loaded from the menu, replaces the running one on a machine built afresh with the settings
+/// `config_for_rom` gives, once the old one has shut down and autosaved; the machine's stop flag carries over. A
+/// program that can't be opened is reported on the overlay, and the running one carries on.
+///
+/// Changes to the config file that arrive on `changes`, such as from a `ConfigWatcher`, are taken up as they come:
+/// the palette and audio by the frontend, the keymap and hotkeys by the input, and the speed by the machine.
+pub fn run(
+    frontend: &mut dyn Frontend,
+    machine: Chip8,
+    outputs: &Outputs,
+    config: &Config,
+    rom: &Path,
+    config_for_rom: &ConfigForRom,
+    changes: &Receiver<LiveChange>,
+) -> Result<(), FrontendError> {
+    frontend.open(config, &rom_title(rom))?;
+    let mut session = Session::start(machine, config.clone(), rom.to_path_buf());
+    let mut osd = Osd::new();
+    let shown = loop {
+        let path = match show(frontend, &mut session, &mut osd, outputs, changes) {
+            Ok(Some(path)) => path,
+            result => break result.map(drop),
+        };
+        let opened = open_rom(&path, outputs, session.machine.stop_flag(), config_for_rom);
+        let (machine, config) = match opened {
+            Ok(opened) => opened,
+            Err(error) => {
+                // the program that was running carries on
+                osd.show_message(error.to_string());
+                continue;
+            }
+        };
+        // the old machine shuts down, and autosaves, before the new one starts, as they share a stop flag
+        let stopped = session.machine.stop();
+        session = Session::start(machine, config, path);
+        if let Err(error) = stopped {
+            break Err(error.into());
+        }
+        if let Err(error) = frontend.configure(&session.config, &rom_title(&session.rom)) {
+            break Err(error);
+        }
+    };
+    let closed = frontend.close();
+    let stopped = session.machine.stop().map_err(FrontendError::from);
+    shown.and(closed).and(stopped)
+}
+
+/// The title of a window showing the program at `path`.
+pub fn rom_title(path: &Path) -> String {
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    format!("chip8 - {}", name.to_string_lossy())
+}
+
+/// The machine being shown, the settings it was built with, and where its program came from.
+struct Session {
+    machine: MachineThread,
+    input: KeyInput,
+    config: Config,
+    rom: PathBuf,
+    events: Receiver<Event>,
+    /// Whether the machine was last heard to be paused.
+    paused: bool,
+    /// Whether the machine's been asked for a state to draw debug views from, and hasn't answered yet.
+    state_requested: bool,
+    /// Whether the program had an autosave when it was loaded, which the player hasn't yet been asked about.
+    autosave: bool,
+}
+
+impl Session {
+    fn start(machine: Chip8, config: Config, rom: PathBuf) -> Session {
+        // subscribed before it runs, so nothing it says is missed
+        let events = machine.subscribe();
+        // the program was loaded before anything could subscribe, so the autosave it found went unheard
+        let autosave = machine.has_autosave();
+        let machine = MachineThread::spawn(machine);
+        let input = KeyInput::new(&config, machine.handle().clone());
+        Session {
+            machine,
+            input,
+            config,
+            rom,
+            events,
+            paused: false,
+            state_requested: false,
+            autosave,
+        }
+    }
+
+    /// Pauses the machine and asks whether to pick up from its autosave. Gives back whether closing the menu should
+    /// resume the machine, which it should if the machine was running before, or before the menu already open.
+    fn offer_autosave(&self, osd: &mut Osd, resume: bool) -> Result<bool, FrontendError> {
+        let resume = if osd.is_menu_open() {
+            resume
+        } else {
+            !self.paused
+        };
+        self.machine.handle().pause()?;
+        osd.offer_autosave(self.rom_directory());
+        Ok(resume)
+    }
+
+    /// Takes up a change to the config file made while the machine runs.
+    fn apply(
+        &mut self,
+        frontend: &mut dyn Frontend,
+        change: &LiveChange,
+    ) -> Result<(), FrontendError> {
+        self.config.apply(change);
+        match change {
+            LiveChange::Palette { .. } | LiveChange::Audio(_) => {
+                frontend.configure(&self.config, &rom_title(&self.rom))
+            }
+            LiveChange::Keymap(_) | LiveChange::Hotkeys(_) => {
+                self.input.configure(&self.config);
+                Ok(())
+            }
+            LiveChange::Speed(speed) => Ok(self.input.set_speed(*speed)?),
+        }
+    }
+
+    /// The directory the pause menu lists programs from, which is the running program's.
+    fn rom_directory(&self) -> &Path {
+        self.rom
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+}
+
+/// Shows the machine until the player quits or it stops, or gives back the path of a program to open instead.
+fn show(
+    frontend: &mut dyn Frontend,
+    session: &mut Session,
+    osd: &mut Osd,
+    outputs: &Outputs,
+    changes: &Receiver<LiveChange>,
+) -> Result<Option<PathBuf>, FrontendError> {
+    let mut tone = false;
+    // whether closing the menu should resume the machine, as it wasn't paused before the menu opened
+    let mut resume = false;
+    if mem::take(&mut session.autosave) {
+        resume = session.offer_autosave(osd, resume)?;
+    }
+    while !session.machine.is_finished() {
+        for event in session.events.try_iter() {
+            match event {
+                Event::StateChanged(state) => session.paused = state == MachineState::Paused,
+                Event::AutosaveAvailable => resume = session.offer_autosave(osd, resume)?,
+                Event::StateSaved(state) => {
+                    session.state_requested = false;
+                    for view in frontend.open_views() {
+                        frontend.present_view(view, &view.render(&state))?;
+                    }
+                }
+                event => {
+                    if let Some(message) = osd::event_message(&event) {
+                        osd.show_message(message);
+                    }
+                }
+            }
+        }
+        for change in changes.try_iter() {
+            session.apply(frontend, &change)?;
+        }
+        for event in frontend.poll()? {
+            match event {
+                InputEvent::KeyDown(key) if osd.is_menu_open() => {
+                    let choice = if session.input.hotkey(&key) == Some(Hotkey::Menu) {
+                        osd.back()
+                    } else {
+                        osd.menu_key(&key)
+                    };
+                    let handle = session.machine.handle();
+                    match choice {
+                        Some(MenuChoice::Resume) if resume => handle.resume()?,
+                        Some(MenuChoice::Reset) => {
+                            handle.reset()?;
+                            if resume {
+                                handle.resume()?;
+                            }
+                        }
+                        Some(MenuChoice::ResumeAutosave) => {
+                            handle.resume_autosave()?;
+                            if resume {
+                                handle.resume()?;
+                            }
+                        }
+                        Some(MenuChoice::DiscardAutosave) => {
+                            handle.discard_autosave()?;
+                            if resume {
+                                handle.resume()?;
+                            }
+                        }
+                        Some(MenuChoice::OpenRom(path)) => {
+                            if tone {
+                                frontend.set_tone(false);
+                            }
+                            // should the program fail to open, the running one carries on as before
+                            if resume {
+                                handle.resume()?;
+                            }
+                            return Ok(Some(path));
+                        }
+                        Some(MenuChoice::Quit) => return Ok(None),
+                        Some(MenuChoice::Resume) | None => {}
+                    }
+                }
+                InputEvent::KeyDown(key) => {
+                    let hotkey = session.input.hotkey(&key);
+                    if hotkey == Some(Hotkey::Menu) {
+                        resume = !session.paused;
+                        session.machine.handle().pause()?;
+                        osd.open_menu(session.rom_directory());
+                    } else if let Some(view) = hotkey.and_then(DebugView::for_hotkey) {
+                        frontend.toggle_view(view)?;
+                    } else {
+                        let speed = session.input.speed();
+                        session.input.key_down(&key)?;
+                        if session.input.speed() != speed {
+                            osd.show_message(osd::speed_message(session.input.speed()));
+                        }
+                    }
+                }
+                InputEvent::KeyUp(key) => {
+                    session.input.key_up(&key)?;
+                }
+                InputEvent::OpenRom(path) => {
+                    // the next machine starts silent
+                    if tone {
+                        frontend.set_tone(false);
+                    }
+                    return Ok(Some(path));
+                }
+                InputEvent::Quit => return Ok(None),
+            }
+        }
+        if outputs.tone.is_on() != tone {
+            tone = !tone;
+            frontend.set_tone(tone);
+        }
+        if !session.state_requested && !frontend.open_views().is_empty() {
+            session.machine.handle().send(Command::SaveState)?;
+            session.state_requested = true;
+        }
+        let frame = outputs.display.frame();
+        frontend.present(&osd.draw(&frame), session.input.speed())?;
+    }
+    Ok(None)
+}
+
+/// Builds a machine running the program at `path`, stopped by `stop_flag`.
+fn open_rom(
+    path: &Path,
+    outputs: &Outputs,
+    stop_flag: Arc<AtomicBool>,
+    config_for_rom: &ConfigForRom,
+) -> Result<(Chip8, Config), FrontendError> {
+    let rom = fs::read(path).map_err(|error| {
+        FrontendError::Host(format!("could not read {}: {error}", path.display()))
+    })?;
+    let config = config_for_rom(&rom, path)?;
+    let builder = outputs.attach(config.builder()).stop_flag(stop_flag);
+    // a machine that's built and then dropped stops the one running, which shares its stop flag
+    builder.check_rom(&rom)?;
+    let mut machine = builder.build()?;
+    machine.load_rom(&rom)?;
+    Ok((machine, config))
+}
+
+/// The ends of a machine a frontend shows and plays it through, kept on the frontend's side.
+#[derive(Debug, Clone, Default)]
+pub struct Outputs {
+    pub display: HeadlessBackend,
+    pub tone: ToneSwitch,
+}
+
+impl Outputs {
+    pub fn new() -> Outputs {
+        Outputs::default()
+    }
+
+    /// Has the machine being built present its frames and play its tone here.
+    pub fn attach(&self, builder: Chip8Builder) -> Chip8Builder {
+        builder
+            .display_backend(Box::new(self.display.clone()))
+            .audio_sink(Box::new(self.tone.clone()))
+    }
+}
+
+/// A machine running in real time on a thread of its own, as the host's event loop needs the main thread.
+///
+/// Browsers have no threads to spare, so the web frontend drives the machine frame by frame instead.
+pub struct MachineThread {
+    handle: MachineHandle,
+    stop_flag: Arc<AtomicBool>,
+    thread: JoinHandle<Result<(), Chip8Error>>,
+}
+
+impl MachineThread {
+    /// Runs the machine until it's stopped, then shuts it down so it can autosave.
+    pub fn spawn(mut machine: Chip8) -> MachineThread {
+        let handle = machine.handle();
+        let stop_flag = machine.stop_flag();
+        let thread = thread::spawn(move || {
+            let result = machine.run();
+            result.and(machine.stop())
+        });
+        MachineThread {
+            handle,
+            stop_flag,
+            thread,
+        }
+    }
+
+    pub fn handle(&self) -> &MachineHandle {
+        &self.handle
+    }
+
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop_flag)
+    }
+
+    /// Whether the machine has stopped by itself, such as on an error.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stops the machine and waits for it to shut down, giving back why it stopped if it failed.
+    pub fn stop(self) -> Result<(), Chip8Error> {
+        self.stop_flag.store(true, Ordering::Relaxed);
+        self.thread.join().expect("the machine thread panicked")
+    }
+}
+
+/// Turns host keys, named as in the config file, into keypad presses and hotkeys for a machine.
+///
+/// Presses of a key already held are dropped, so frontends can pass on key repeats as they come.
+pub struct KeyInput {
+    keymap: BTreeMap<String, u8>,
+    hotkeys: Hotkeys,
+    handle: MachineHandle,
+    held: HashSet<String>,
+    speed: Speed,
+}
+
+impl KeyInput {
+    pub fn new(config: &Config, handle: MachineHandle) -> KeyInput {
+        KeyInput {
+            keymap: config.keymap.clone(),
+            hotkeys: config.hotkeys(),
+            handle,
+            held: HashSet::new(),
+            speed: config.speed(),
+        }
+    }
+
+    /// Takes up the keymap and hotkeys of a changed config. Keys already held stay held.
+    pub fn configure(&mut self, config: &Config) {
+        self.keymap = config.keymap.clone();
+        self.hotkeys = config.hotkeys();
+    }
+
+    /// Sets the speed the machine runs at.
+    pub fn set_speed(&mut self, speed: Speed) -> Result<(), Chip8Error> {
+        self.handle.set_speed(speed)?;
+        self.speed = speed;
+        Ok(())
+    }
+
+    /// Passes on a host key being pressed, returning whether anything is bound to it.
+    pub fn key_down(&mut self, key: &str) -> Result<bool, Chip8Error> {
+        if !self.held.insert(key.to_string()) {
+            return Ok(self.is_bound(key));
+        }
+        if let Some(&keypad_key) = self.keymap.get(key) {
+            self.handle.press_key(keypad_key)?;
+        } else if let Some(hotkey) = self.hotkeys.lookup(key) {
+            self.handle.hotkey(hotkey)?;
+            self.speed = hotkey.apply_to_speed(self.speed).unwrap_or(self.speed);
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Passes on a host key being released, returning whether anything is bound to it.
+    pub fn key_up(&mut self, key: &str) -> Result<bool, Chip8Error> {
+        self.held.remove(key);
+        if let Some(&keypad_key) = self.keymap.get(key) {
+            self.handle.release_key(keypad_key)?;
+        } else if let Some(hotkey) = self.hotkeys.lookup(key) {
+            if hotkey.is_held() {
+                self.handle.release_hotkey(hotkey)?;
+            }
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// The hotkey bound to a host key, if it isn't bound to the keypad instead.
+    pub fn hotkey(&self, key: &str) -> Option<Hotkey> {
+        if self.keymap.contains_key(key) {
+            return None;
+        }
+        self.hotkeys.lookup(key)
+    }
+
+    /// The speed the machine runs at, as far as the hotkeys passed on have changed it.
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    fn is_bound(&self, key: &str) -> bool {
+        self.keymap.contains_key(key) || self.hotkeys.lookup(key).is_some()
+    }
+}
+
+/// The name SDL gives a key, which is what the config file uses, from its W3C `KeyboardEvent.code`, which browsers
+/// and Bevy both name keys by.
+#[cfg(any(feature = "wasm", feature = "bevy_chip8"))]
+fn code_key_name(code: &str) -> &str {
+    if let Some(letter) = code.strip_prefix("Key") {
+        return letter;
+    }
+    if let Some(digit) = code.strip_prefix("Digit") {
+        return digit;
+    }
+    match code {
+        "Equal" => "=",
+        "Minus" => "-",
+        "Comma" => ",",
+        "Period" => ".",
+        "Slash" => "/",
+        "Semicolon" => ";",
+        "Quote" => "'",
+        "BracketLeft" => "[",
+        "BracketRight" => "]",
+        "Backslash" => "\\",
+        "Backquote" => "`",
+        "Enter" => "Return",
+        "ArrowUp" => "Up",
+        "ArrowDown" => "Down",
+        "ArrowLeft" => "Left",
+        "ArrowRight" => "Right",
+        // function keys, Tab, Backspace, Space, and the rest are named the same in both
+        _ => code,
+    }
+}
+
+/// How many bytes `frame_to_rgb()` writes.
+pub const RGB_FRAME_LEN: usize = WIDTH * HEIGHT * 3;
+
+/// How many bytes `frame_to_rgba()` writes.
+pub const RGBA_FRAME_LEN: usize = WIDTH * HEIGHT * 4;
+
+/// Draws a frame as packed RGB bytes, three to a pixel, row by row.
+pub fn frame_to_rgb(frame: &Frame, foreground: Color, background: Color, rgb: &mut [u8]) {
+    for (pixel, &on) in rgb.chunks_exact_mut(3).zip(frame.pixels()) {
+        let color = if on == 1 { foreground } else { background };
+        pixel.copy_from_slice(&[color.r, color.g, color.b]);
+    }
+}
+
+/// Draws a frame as packed RGBA bytes, four to a pixel, row by row, all opaque.
+pub fn frame_to_rgba(frame: &Frame, foreground: Color, background: Color, rgba: &mut [u8]) {
+    for (pixel, &on) in rgba.chunks_exact_mut(4).zip(frame.pixels()) {
+        let color = if on == 1 { foreground } else { background };
+        pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+    }
+}
+
+/// An `AudioSink` that only remembers whether the tone is on, for an audio callback on another thread to read.
+///
+/// Clones share the same switch.
+#[derive(Debug, Clone, Default)]
+pub struct ToneSwitch {
+    on: Arc<AtomicBool>,
+}
+
+impl ToneSwitch {
+    pub fn new() -> ToneSwitch {
+        ToneSwitch::default()
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on.load(Ordering::Relaxed)
+    }
+}
+
+impl AudioSink for ToneSwitch {
+    fn set_tone(&mut self, on: bool) {
+        self.on.store(on, Ordering::Relaxed);
+    }
+}
+
+/// Makes the tone as a square wave, for audio APIs that ask for samples.
+#[derive(Debug, Clone)]
+pub struct SquareWave {
+    tone: ToneSwitch,
+    /// How far through a cycle the wave is, from 0.0 to 1.0.
+    phase: f32,
+    step: f32,
+    volume: f32,
+}
+
+impl SquareWave {
+    pub fn new(tone: ToneSwitch, audio: &AudioConfig, sample_rate: u32) -> SquareWave {
+        SquareWave {
+            tone,
+            phase: 0.0,
+            step: audio.frequency / sample_rate as f32,
+            volume: if audio.enabled {
+                audio.volume.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Fills a buffer of mono samples, with silence while the tone is off.
+    pub fn fill(&mut self, samples: &mut [f32]) {
+        if !self.tone.is_on() {
+            samples.fill(0.0);
+            // start the next tone at the top of a cycle, so it doesn't begin with a click
+            self.phase = 0.0;
+            return;
+        }
+        for sample in samples {
+            *sample = if self.phase < 0.5 {
+                self.volume
+            } else {
+                -self.volume
+            };
+            self.phase = (self.phase + self.step) % 1.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use crate::clock::{ManualClock, TickRate};
+
+    use super::*;
+
+    #[test]
+    fn keys_go_to_the_keypad_or_hotkeys() {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        let mut input = KeyInput::new(&Config::default(), machine.handle());
+        assert_eq!(input.key_down("W"), Ok(true));
+        assert_eq!(input.key_down("F5"), Ok(true));
+        assert_eq!(input.key_down("Insert"), Ok(false));
+        machine.process_commands();
+        assert!(machine.keypad().is_pressed(0x5));
+        assert_eq!(input.key_up("W"), Ok(true));
+        machine.process_commands();
+        assert!(!machine.keypad().is_pressed(0x5));
+    }
+
+    #[test]
+    fn runs_a_frontend_until_it_quits() {
+        // draws a 0 in the corner and beeps, then loops
+        let rom = [
+            0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x61, 0x05, 0xF1, 0x18, 0x12, 0x0A,
+        ];
+        let outputs = Outputs::new();
+        let mut machine = outputs
+            .attach(Chip8::builder())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&rom).expect("failed to load rom");
+        let script = vec![vec![InputEvent::KeyDown("W".to_string())]; 10];
+        let mut frontend = ScriptedFrontend::new(script, Duration::from_millis(16));
+        run(
+            &mut frontend,
+            machine,
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &|_, _| Ok(Config::default()),
+            &mpsc::channel().1,
+        )
+        .expect("failed to run frontend");
+
+        assert!(!frontend.open);
+        assert_eq!(frontend.frames.len(), 10);
+        assert!(frontend.frames.last().unwrap().get_pixel(0, 0));
+        assert_eq!(frontend.tones.first(), Some(&true));
+    }
+
+    #[test]
+    fn runs_a_dropped_rom_in_place_of_the_program() {
+        // draws a 0 in the corner, then loops
+        let first = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        // clears the screen, then loops
+        let second = [0x00, 0xE0, 0x12, 0x02];
+        let directory = std::env::temp_dir().join(format!("chip8-drop-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("blank.ch8");
+        fs::write(&path, second).unwrap();
+
+        let outputs = Outputs::new();
+        let mut machine = outputs
+            .attach(Chip8::builder())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&first).expect("failed to load rom");
+        let stop_flag = machine.stop_flag();
+        let script = [
+            vec![vec![]; 5],
+            vec![vec![InputEvent::OpenRom(path.clone())]],
+            vec![vec![]; 5],
+        ];
+        let mut frontend = ScriptedFrontend::new(script.concat(), Duration::from_millis(16));
+        let seen = std::cell::RefCell::new(Vec::new());
+        let config_for_rom = |rom: &[u8], _: &Path| {
+            seen.borrow_mut().push(rom.to_vec());
+            Ok(Config::default())
+        };
+        run(
+            &mut frontend,
+            machine,
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &config_for_rom,
+            &mpsc::channel().1,
+        )
+        .expect("failed to run frontend");
+        fs::remove_dir_all(directory).unwrap();
+
+        assert_eq!(seen.into_inner(), [second.to_vec()]);
+        assert_eq!(frontend.titles, ["chip8 - test.ch8", "chip8 - blank.ch8"]);
+        assert!(frontend.frames[4].get_pixel(0, 0));
+        assert!(!frontend.frames.last().unwrap().get_pixel(0, 0));
+        // the new machine stopped through the same flag as the first
+        assert!(stop_flag.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn keeps_running_when_a_dropped_file_cannot_open() {
+        // draws a 0 in the corner, then loops
+        let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        let directory = std::env::temp_dir().join(format!("chip8-bad-drop-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let too_big = directory.join("too-big.ch8");
+        fs::write(&too_big, vec![0; 0x1000]).unwrap();
+        let missing = directory.join("missing.ch8");
+
+        let outputs = Outputs::new();
+        let mut machine = outputs
+            .attach(Chip8::builder())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&rom).expect("failed to load rom");
+        let script = [
+            vec![vec![]; 5],
+            vec![vec![InputEvent::OpenRom(too_big)]],
+            vec![vec![InputEvent::OpenRom(missing)]],
+            vec![vec![]; 5],
+        ];
+        let mut frontend = ScriptedFrontend::new(script.concat(), Duration::from_millis(16));
+        let result = run(
+            &mut frontend,
+            machine,
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &|_, _| Ok(Config::default()),
+            &mpsc::channel().1,
+        );
+        fs::remove_dir_all(directory).unwrap();
+
+        result.expect("failed to run frontend");
+        assert_eq!(frontend.titles, ["chip8 - test.ch8"]);
+        // every frame but the two the files were dropped on
+        assert_eq!(frontend.frames.len(), 10);
+        assert!(frontend.frames.last().unwrap().get_pixel(0, 0));
+    }
+
+    #[test]
+    fn quits_from_the_pause_menu() {
+        // draws a 0 in the corner, then loops
+        let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        let outputs = Outputs::new();
+        let mut machine = outputs
+            .attach(Chip8::builder())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&rom).expect("failed to load rom");
+        let key = |key: &str| vec![InputEvent::KeyDown(key.to_string())];
+        let script = [
+            vec![vec![]; 5],
+            vec![key("Escape"), key("Down"), key("Down"), key("Down")],
+            vec![key("Return")],
+            vec![vec![]; 5],
+        ];
+        let mut frontend = ScriptedFrontend::new(script.concat(), Duration::from_millis(16));
+        run(
+            &mut frontend,
+            machine,
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &|_, _| Ok(Config::default()),
+            &mpsc::channel().1,
+        )
+        .expect("failed to run frontend");
+
+        assert_eq!(frontend.frames.len(), 9);
+        assert!(frontend.frames[4].get_pixel(0, 0));
+        // the menu covers the display
+        assert!(!frontend.frames.last().unwrap().get_pixel(0, 0));
+    }
+
+    #[test]
+    fn offers_to_resume_an_autosave() {
+        let root =
+            std::env::temp_dir().join(format!("chip8-frontend-autosave-{}", std::process::id()));
+        // V0 += 1, jump back
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .save_directory(&root)
+            .autosave(true)
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&rom).expect("failed to load rom");
+        machine.step_n(5).expect("steps failed");
+        machine.stop().expect("failed to stop machine");
+
+        let outputs = Outputs::new();
+        let build = || {
+            let mut machine = outputs
+                .attach(Chip8::builder().save_directory(&root))
+                .build()
+                .expect("failed to build machine");
+            machine.load_rom(&rom).expect("failed to load rom");
+            machine
+        };
+        let key = |key: &str| vec![InputEvent::KeyDown(key.to_string())];
+        let script = [vec![key("Return")], vec![vec![]; 5]];
+        let mut frontend = ScriptedFrontend::new(script.concat(), Duration::from_millis(16));
+        run(
+            &mut frontend,
+            build(),
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &|_, _| Ok(Config::default()),
+            &mpsc::channel().1,
+        )
+        .expect("failed to run frontend");
+        let resumed = !build().has_autosave();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(resumed, "the autosave was not resumed");
+    }
+
+    #[test]
+    fn takes_up_config_changes_while_running() {
+        let outputs = Outputs::new();
+        let mut machine = outputs
+            .attach(Chip8::builder())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&[0x12, 0x00]).expect("failed to load rom");
+        let (changed, changes) = mpsc::channel();
+        let palette = LiveChange::Palette {
+            foreground: Color::rgb(0x33, 0xFF, 0x66),
+            background: Color::rgb(0x10, 0x20, 0x30),
+        };
+        changed.send(palette).unwrap();
+        let mut frontend = ScriptedFrontend::new(vec![vec![]; 3], Duration::from_millis(16));
+        run(
+            &mut frontend,
+            machine,
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &|_, _| Ok(Config::default()),
+            &changes,
+        )
+        .expect("failed to run frontend");
+
+        let display = &frontend.configs.last().expect("never configured").display;
+        assert_eq!(display.foreground, Color::rgb(0x33, 0xFF, 0x66));
+        assert_eq!(display.background, Color::rgb(0x10, 0x20, 0x30));
+    }
+
+    #[test]
+    fn draws_open_debug_views() {
+        let outputs = Outputs::new();
+        let mut machine = outputs
+            .attach(Chip8::builder())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&[0x12, 0x00]).expect("failed to load rom");
+        let key = |key: &str| vec![InputEvent::KeyDown(key.to_string())];
+        let script = [vec![key("F2"), key("F3"), key("F3")], vec![vec![]; 5]];
+        let mut frontend = ScriptedFrontend::new(script.concat(), Duration::from_millis(16));
+        run(
+            &mut frontend,
+            machine,
+            &outputs,
+            &Config::default(),
+            Path::new("test.ch8"),
+            &|_, _| Ok(Config::default()),
+            &mpsc::channel().1,
+        )
+        .expect("failed to run frontend");
+
+        assert_eq!(frontend.views, [DebugView::Disassembly]);
+        let (view, image) = frontend.view_images.last().expect("no view was drawn");
+        assert_eq!(*view, DebugView::Disassembly);
+        assert_eq!(
+            (image.width(), image.height()),
+            DebugView::Disassembly.size()
+        );
+    }
+
+    #[cfg(any(feature = "wasm", feature = "bevy_chip8"))]
+    #[test]
+    fn names_coded_keys_as_sdl_does() {
+        assert_eq!(code_key_name("KeyQ"), "Q");
+        assert_eq!(code_key_name("Digit4"), "4");
+        assert_eq!(code_key_name("Equal"), "=");
+        assert_eq!(code_key_name("ArrowLeft"), "Left");
+        assert_eq!(code_key_name("F5"), "F5");
+    }
+
+    #[test]
+    fn square_wave_is_silent_while_the_tone_is_off() {
+        let mut tone = ToneSwitch::new();
+        let audio = AudioConfig {
+            enabled: true,
+            volume: 0.5,
+            frequency: 1000.0,
+        };
+        let mut wave = SquareWave::new(tone.clone(), &audio, 4000);
+        let mut samples = [1.0; 4];
+        wave.fill(&mut samples);
+        assert_eq!(samples, [0.0; 4]);
+        tone.set_tone(true);
+        wave.fill(&mut samples);
+        assert_eq!(samples, [0.5, 0.5, -0.5, -0.5]);
+    }
+}
@@ -0,0 +1,501 @@
+//! Text drawn over the machine's display: short messages that fade after a moment, such as a savestate being
+//! saved or the speed changing, and the pause menu, which also asks whether to pick up from an autosave.
+//!
+//! The overlay is drawn into a copy of the frame before it's presented, in the display's own pixels and a tiny font
+//! of its own, so it looks the same on every frontend and needs nothing from any of them. Text is lit pixels on a
+//! cleared box, so it can be read whatever's underneath.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    display::{Frame, HEIGHT, WIDTH},
+    events::Event,
+    speed::Speed,
+};
+
+/// How long a message stays up.
+pub const MESSAGE_TIME: Duration = Duration::from_secs(2);
+
+/// How many messages are shown at once, newest at the bottom. Older ones make way.
+const MAX_MESSAGES: usize = 2;
+
+/// How far apart characters and lines are, in pixels, counting the gap after each.
+pub(crate) const CHAR_WIDTH: usize = 4;
+pub(crate) const LINE_HEIGHT: usize = 6;
+
+/// How many files the program list shows at once, below its title.
+const LIST_ROWS: usize = HEIGHT / LINE_HEIGHT - 1;
+
+/// Somewhere the overlay's font can draw: a frame, or the image of a debug view.
+pub(crate) trait Canvas {
+    fn width(&self) -> usize;
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool);
+}
+
+impl Canvas for Frame {
+    fn width(&self) -> usize {
+        WIDTH
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        Frame::set_pixel(self, x, y, on);
+    }
+}
+
+/// The overlay: any messages still up, and the pause menu while it's open.
+#[derive(Debug, Default)]
+pub struct Osd {
+    /// The messages showing, oldest first, with when each comes down.
+    messages: VecDeque<(String, Instant)>,
+    menu: Option<Menu>,
+}
+
+/// What the player chose from the pause menu.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuChoice {
+    Resume,
+    Reset,
+    /// A program to run in place of this one.
+    OpenRom(PathBuf),
+    /// Pick up from the program's autosave.
+    ResumeAutosave,
+    /// Throw the program's autosave away and carry on from the start.
+    DiscardAutosave,
+    Quit,
+}
+
+/// The entries on the pause menu's first page, in order.
+const MAIN_ITEMS: [(&str, MainItem); 4] = [
+    ("RESUME", MainItem::Resume),
+    ("RESET", MainItem::Reset),
+    ("LOAD ROM", MainItem::LoadRom),
+    ("QUIT", MainItem::Quit),
+];
+
+/// The choices offered for an autosave, in order.
+const AUTOSAVE_ITEMS: [(&str, MenuChoice); 2] = [
+    ("RESUME SAVE", MenuChoice::ResumeAutosave),
+    ("START AFRESH", MenuChoice::DiscardAutosave),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MainItem {
+    Resume,
+    Reset,
+    LoadRom,
+    Quit,
+}
+
+#[derive(Debug)]
+struct Menu {
+    /// Where the program list is read from.
+    directory: PathBuf,
+    page: Page,
+    selected: usize,
+}
+
+#[derive(Debug)]
+enum Page {
+    Main,
+    /// The files in the directory, sorted, scrolled down to `top`.
+    Roms {
+        files: Vec<PathBuf>,
+        top: usize,
+    },
+    /// Whether to pick up from the program's autosave.
+    Autosave,
+}
+
+impl Osd {
+    pub fn new() -> Osd {
+        Osd::default()
+    }
+
+    /// Puts up a message for `MESSAGE_TIME`. It's drawn in capitals, cut off at the edge of the display.
+    pub fn show_message(&mut self, text: impl Into<String>) {
+        self.show_message_at(text.into(), Instant::now());
+    }
+
+    fn show_message_at(&mut self, text: String, now: Instant) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((text, now + MESSAGE_TIME));
+    }
+
+    pub fn is_menu_open(&self) -> bool {
+        self.menu.is_some()
+    }
+
+    /// Opens the pause menu at its first page, listing programs in `directory` should the player go to load one.
+    pub fn open_menu(&mut self, directory: &Path) {
+        self.menu = Some(Menu {
+            directory: directory.to_path_buf(),
+            page: Page::Main,
+            selected: 0,
+        });
+    }
+
+    /// Opens the menu to ask whether to pick up from the program's autosave or start afresh. Backing out leaves the
+    /// autosave for later.
+    pub fn offer_autosave(&mut self, directory: &Path) {
+        self.menu = Some(Menu {
+            directory: directory.to_path_buf(),
+            page: Page::Autosave,
+            selected: 0,
+        });
+    }
+
+    pub fn close_menu(&mut self) {
+        self.menu = None;
+    }
+
+    /// Moves about the open menu with a key, named as the config file names them: up and down on the arrows or
+    /// the pad, choosing with Return, Space, or A, and going back with Backspace or B. Gives back what the player
+    /// chose, if anything; the menu closes on any choice.
+    pub fn menu_key(&mut self, key: &str) -> Option<MenuChoice> {
+        let menu = self.menu.as_mut()?;
+        match key {
+            "Up" | "PadUp" => menu.move_by(-1),
+            "Down" | "PadDown" => menu.move_by(1),
+            "Return" | "Space" | "PadA" => return self.select(),
+            "Backspace" | "PadB" => return self.back(),
+            _ => {}
+        }
+        None
+    }
+
+    /// Goes back a page of the open menu, or closes it from the first page, which resumes.
+    pub fn back(&mut self) -> Option<MenuChoice> {
+        let menu = self.menu.as_mut()?;
+        match menu.page {
+            Page::Main | Page::Autosave => {
+                self.menu = None;
+                Some(MenuChoice::Resume)
+            }
+            Page::Roms { .. } => {
+                menu.page = Page::Main;
+                menu.selected = main_index(MainItem::LoadRom);
+                None
+            }
+        }
+    }
+
+    fn select(&mut self) -> Option<MenuChoice> {
+        let menu = self.menu.as_mut()?;
+        let choice = match &menu.page {
+            Page::Main => match MAIN_ITEMS[menu.selected].1 {
+                MainItem::Resume => MenuChoice::Resume,
+                MainItem::Reset => MenuChoice::Reset,
+                MainItem::LoadRom => {
+                    menu.page = Page::Roms {
+                        files: list_files(&menu.directory),
+                        top: 0,
+                    };
+                    menu.selected = 0;
+                    return None;
+                }
+                MainItem::Quit => MenuChoice::Quit,
+            },
+            Page::Roms { files, .. } => MenuChoice::OpenRom(files.get(menu.selected)?.clone()),
+            Page::Autosave => AUTOSAVE_ITEMS[menu.selected].1.clone(),
+        };
+        self.menu = None;
+        Some(choice)
+    }
+
+    /// Draws the overlay over a frame, leaving the frame as it is if there's nothing to show.
+    pub fn draw<'a>(&mut self, frame: &'a Frame) -> Cow<'a, Frame> {
+        self.draw_at(frame, Instant::now())
+    }
+
+    fn draw_at<'a>(&mut self, frame: &'a Frame, now: Instant) -> Cow<'a, Frame> {
+        self.messages.retain(|&(_, until)| until > now);
+        if let Some(menu) = &self.menu {
+            let mut frame = frame.clone();
+            menu.draw(&mut frame);
+            return Cow::Owned(frame);
+        }
+        if self.messages.is_empty() {
+            return Cow::Borrowed(frame);
+        }
+        let mut frame = frame.clone();
+        let top = HEIGHT - self.messages.len() * LINE_HEIGHT;
+        clear_rows(&mut frame, top - 1, HEIGHT);
+        for (line, (text, _)) in self.messages.iter().enumerate() {
+            draw_text(&mut frame, 1, top + line * LINE_HEIGHT, text);
+        }
+        Cow::Owned(frame)
+    }
+}
+
+impl Menu {
+    fn len(&self) -> usize {
+        match &self.page {
+            Page::Main => MAIN_ITEMS.len(),
+            Page::Roms { files, .. } => files.len(),
+            Page::Autosave => AUTOSAVE_ITEMS.len(),
+        }
+    }
+
+    fn move_by(&mut self, step: isize) {
+        let last = self.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(step).min(last);
+        if let Page::Roms { top, .. } = &mut self.page {
+            // keep the selection in view
+            *top = (*top)
+                .min(self.selected)
+                .max((self.selected + 1).saturating_sub(LIST_ROWS));
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        clear_rows(frame, 0, HEIGHT);
+        let (title, items, top): (_, Vec<Cow<str>>, _) = match &self.page {
+            Page::Main => (
+                "PAUSED",
+                MAIN_ITEMS.iter().map(|&(name, _)| name.into()).collect(),
+                0,
+            ),
+            Page::Roms { files, top } => (
+                "LOAD ROM",
+                files
+                    .iter()
+                    .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+                    .collect(),
+                *top,
+            ),
+            Page::Autosave => (
+                "AUTOSAVE FOUND",
+                AUTOSAVE_ITEMS
+                    .iter()
+                    .map(|&(name, _)| name.into())
+                    .collect(),
+                0,
+            ),
+        };
+        draw_text(frame, 1, 1, title);
+        if items.is_empty() {
+            draw_text(frame, CHAR_WIDTH, 1 + LINE_HEIGHT, "NO FILES");
+        }
+        for (row, (index, item)) in items
+            .iter()
+            .enumerate()
+            .skip(top)
+            .take(LIST_ROWS)
+            .enumerate()
+        {
+            let y = 1 + (row + 1) * LINE_HEIGHT;
+            if index == self.selected {
+                draw_text(frame, 0, y, ">");
+            }
+            draw_text(frame, CHAR_WIDTH, y, item);
+        }
+    }
+}
+
+fn main_index(item: MainItem) -> usize {
+    MAIN_ITEMS
+        .iter()
+        .position(|&(_, main_item)| main_item == item)
+        .expect("every item is on the menu")
+}
+
+/// The regular files in a directory, sorted by name. A directory that can't be read has none.
+fn list_files(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .map(|entry| entry.path())
+        .collect();
+    files.sort();
+    files
+}
+
+/// The message to put up for a machine event, for the events the player would want to know about.
+pub fn event_message(event: &Event) -> Option<String> {
+    match event {
+        Event::SlotSaved(slot) => Some(format!("SAVED SLOT {slot}")),
+        Event::SlotLoaded(slot) => Some(format!("LOADED SLOT {slot}")),
+        Event::CommandFailed(error) => Some(error.to_string()),
+        _ => None,
+    }
+}
+
+/// The message to put up for the speed changing.
+pub fn speed_message(speed: Speed) -> String {
+    match speed {
+        Speed::Scaled(multiplier) => format!("SPEED {multiplier}X"),
+        Speed::Unlimited => "SPEED MAX".to_string(),
+    }
+}
+
+fn clear_rows(frame: &mut Frame, top: usize, bottom: usize) {
+    for y in top..bottom {
+        for x in 0..WIDTH {
+            frame.set_pixel(x, y, false);
+        }
+    }
+}
+
+/// Draws a line of text with its top left corner at `x`, `y`, stopping at the edge of the canvas.
+pub(crate) fn draw_text(canvas: &mut impl Canvas, x: usize, y: usize, text: &str) {
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i * CHAR_WIDTH;
+        if left + 3 > canvas.width() {
+            break;
+        }
+        for (row, bits) in glyph(c).into_iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    canvas.set_pixel(left + column, y + row, true);
+                }
+            }
+        }
+    }
+}
+
+/// A character in the overlay's 3x5 font, a row to a byte from the top, leftmost pixel in the highest of the low
+/// three bits. Letters are all capitals, and characters the font hasn't got come out as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ' ' => [0; 5],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::Display;
+
+    use super::*;
+
+    #[test]
+    fn shows_messages_until_they_expire() {
+        let blank = Display::new().frame();
+        let mut osd = Osd::new();
+        let now = Instant::now();
+        osd.show_message_at("SAVED SLOT 2".to_string(), now);
+
+        let shown = osd.draw_at(&blank, now);
+        // the S at the start of the bottom line
+        assert!(shown.get_pixel(2, HEIGHT - LINE_HEIGHT));
+        assert!(!shown.get_pixel(1, HEIGHT - LINE_HEIGHT));
+        let shown = osd.draw_at(&blank, now + MESSAGE_TIME);
+        assert!(matches!(shown, Cow::Borrowed(_)));
+        assert_eq!(*shown, *blank);
+    }
+
+    #[test]
+    fn picks_a_program_from_the_menu() {
+        let directory = std::env::temp_dir().join(format!("chip8-osd-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("b.ch8"), [0x12, 0x00]).unwrap();
+        fs::write(directory.join("a.ch8"), [0x12, 0x00]).unwrap();
+        let mut osd = Osd::new();
+        osd.open_menu(&directory);
+
+        assert_eq!(osd.menu_key("Down"), None);
+        assert_eq!(osd.menu_key("Down"), None);
+        // into the program list, and back out to where it was
+        assert_eq!(osd.menu_key("Return"), None);
+        assert_eq!(osd.menu_key("Backspace"), None);
+        assert_eq!(osd.menu_key("Return"), None);
+        assert_eq!(osd.menu_key("PadDown"), None);
+        assert_eq!(osd.menu_key("PadDown"), None);
+        let chosen = osd.menu_key("PadA");
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(chosen, Some(MenuChoice::OpenRom(directory.join("b.ch8"))));
+        assert!(!osd.is_menu_open());
+    }
+
+    #[test]
+    fn backing_out_of_the_menu_resumes() {
+        let mut osd = Osd::new();
+        osd.open_menu(Path::new("."));
+        assert_eq!(osd.menu_key("Up"), None);
+        assert_eq!(osd.back(), Some(MenuChoice::Resume));
+        assert!(!osd.is_menu_open());
+    }
+
+    #[test]
+    fn offers_to_resume_an_autosave() {
+        let mut osd = Osd::new();
+        osd.offer_autosave(Path::new("."));
+        assert_eq!(osd.menu_key("Down"), None);
+        assert_eq!(osd.menu_key("Return"), Some(MenuChoice::DiscardAutosave));
+        assert!(!osd.is_menu_open());
+
+        osd.offer_autosave(Path::new("."));
+        assert_eq!(osd.menu_key("PadA"), Some(MenuChoice::ResumeAutosave));
+        osd.offer_autosave(Path::new("."));
+        assert_eq!(osd.back(), Some(MenuChoice::Resume));
+    }
+
+    #[test]
+    fn describes_speeds() {
+        assert_eq!(speed_message(Speed::scaled(2.0).unwrap()), "SPEED 2X");
+        assert_eq!(speed_message(Speed::scaled(0.25).unwrap()), "SPEED 0.25X");
+        assert_eq!(speed_message(Speed::Unlimited), "SPEED MAX");
+    }
+}
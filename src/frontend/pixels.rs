@@ -0,0 +1,268 @@
+//! A window and keyboard input through winit, drawn with pixels, in pure Rust for hosts without SDL2.
+//!
+//! There's no sound, as neither library plays any. Debug views open in windows of their own, and keys pressed in
+//! them are passed on as if pressed in the main one.
+
+use std::borrow::Cow;
+
+use ::pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::EventLoop,
+    platform::run_return::EventLoopExtRunReturn,
+    window::{Window, WindowBuilder, WindowId},
+};
+
+use crate::{
+    config::{Color, Config},
+    display::{Frame, HEIGHT, WIDTH},
+    speed::Speed,
+};
+
+use super::{frame_to_rgba, DebugView, Frontend, FrontendError, InputEvent, ViewImage};
+
+/// How many window pixels a debug view's pixel takes up when its window opens.
+const VIEW_SCALE: u32 = 3;
+
+/// Shows the machine in a resizable window, and plays it with the keyboard.
+#[derive(Default)]
+pub struct PixelsFrontend {
+    window: Option<PixelsWindow>,
+}
+
+/// What winit and pixels keep while the window is open. The surface goes before the window it draws on.
+struct PixelsWindow {
+    pixels: Pixels,
+    window: Window,
+    /// The debug views open, each in a window of its own.
+    views: Vec<ViewWindow>,
+    event_loop: EventLoop<()>,
+    foreground: Color,
+    background: Color,
+}
+
+/// A debug view's window, and the surface it's drawn on, which goes first.
+struct ViewWindow {
+    view: DebugView,
+    pixels: Pixels,
+    window: Window,
+}
+
+impl PixelsFrontend {
+    pub fn new() -> PixelsFrontend {
+        PixelsFrontend::default()
+    }
+
+    fn window(&mut self) -> Result<&mut PixelsWindow, FrontendError> {
+        self.window
+            .as_mut()
+            .ok_or_else(|| FrontendError::Host("the window isn't open".to_string()))
+    }
+}
+
+impl Frontend for PixelsFrontend {
+    fn open(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let event_loop = EventLoop::new();
+        let scale = config.display.scale.max(1);
+        let window = WindowBuilder::new()
+            .with_title(title)
+            .with_inner_size(LogicalSize::new(
+                WIDTH as u32 * scale,
+                HEIGHT as u32 * scale,
+            ))
+            .with_min_inner_size(LogicalSize::new(WIDTH as u32, HEIGHT as u32))
+            .build(&event_loop)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        let size = window.inner_size();
+        let surface = SurfaceTexture::new(size.width, size.height, &window);
+        // pixels scales the picture to fit the window, keeping its shape
+        let pixels = Pixels::new(WIDTH as u32, HEIGHT as u32, surface)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        self.window = Some(PixelsWindow {
+            pixels,
+            window,
+            views: Vec::new(),
+            event_loop,
+            foreground: config.display.foreground,
+            background: config.display.background,
+        });
+        Ok(())
+    }
+
+    fn configure(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        window.window.set_title(title);
+        window.foreground = config.display.foreground;
+        window.background = config.display.background;
+        Ok(())
+    }
+
+    /// Runs the event loop until it's handled what's waiting, then hands back the input.
+    fn poll(&mut self) -> Result<Vec<InputEvent>, FrontendError> {
+        let PixelsWindow {
+            pixels,
+            window,
+            views,
+            event_loop,
+            ..
+        } = self.window()?;
+        let main = window.id();
+        let mut closed: Vec<WindowId> = Vec::new();
+        let mut input = Vec::new();
+        let mut result = Ok(());
+        event_loop.run_return(|event, _, control_flow| {
+            control_flow.set_poll();
+            match event {
+                Event::WindowEvent { window_id, event } => match event {
+                    WindowEvent::CloseRequested if window_id == main => {
+                        input.push(InputEvent::Quit);
+                    }
+                    WindowEvent::CloseRequested => closed.push(window_id),
+                    WindowEvent::DroppedFile(path) => input.push(InputEvent::OpenRom(path)),
+                    WindowEvent::Resized(size) => {
+                        let surface = if window_id == main {
+                            Some(&mut *pixels)
+                        } else {
+                            views
+                                .iter_mut()
+                                .find(|view| view.window.id() == window_id)
+                                .map(|view| &mut view.pixels)
+                        };
+                        let resized =
+                            surface.map(|surface| surface.resize_surface(size.width, size.height));
+                        if let Some(Err(error)) = resized {
+                            result = Err(FrontendError::Host(error.to_string()));
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(key),
+                                state,
+                                ..
+                            },
+                        ..
+                    } => {
+                        let name = key_name(key).into_owned();
+                        input.push(match state {
+                            ElementState::Pressed => InputEvent::KeyDown(name),
+                            ElementState::Released => InputEvent::KeyUp(name),
+                        });
+                    }
+                    _ => {}
+                },
+                Event::MainEventsCleared => control_flow.set_exit(),
+                _ => {}
+            }
+        });
+        views.retain(|view| !closed.contains(&view.window.id()));
+        result.map(|()| input)
+    }
+
+    fn present(&mut self, frame: &Frame, _speed: Speed) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        frame_to_rgba(
+            frame,
+            window.foreground,
+            window.background,
+            window.pixels.frame_mut(),
+        );
+        // waits for vsync, which paces the loop
+        window
+            .pixels
+            .render()
+            .map_err(|error| FrontendError::Host(error.to_string()))
+    }
+
+    fn set_tone(&mut self, _on: bool) {}
+
+    fn toggle_view(&mut self, view: DebugView) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        if let Some(open) = window.views.iter().position(|open| open.view == view) {
+            window.views.remove(open);
+            return Ok(());
+        }
+        let (width, height) = view.size();
+        let view_window = WindowBuilder::new()
+            .with_title(view.title())
+            .with_inner_size(LogicalSize::new(
+                width as u32 * VIEW_SCALE,
+                height as u32 * VIEW_SCALE,
+            ))
+            .build(&window.event_loop)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        let size = view_window.inner_size();
+        let surface = SurfaceTexture::new(size.width, size.height, &view_window);
+        // no vsync, as the main window already waits for it
+        let pixels = PixelsBuilder::new(width as u32, height as u32, surface)
+            .enable_vsync(false)
+            .build()
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        window.views.push(ViewWindow {
+            view,
+            pixels,
+            window: view_window,
+        });
+        Ok(())
+    }
+
+    fn open_views(&self) -> Vec<DebugView> {
+        self.window
+            .iter()
+            .flat_map(|window| window.views.iter().map(|open| open.view))
+            .collect()
+    }
+
+    fn present_view(&mut self, view: DebugView, image: &ViewImage) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        let Some(open) = window.views.iter_mut().find(|open| open.view == view) else {
+            return Ok(());
+        };
+        image.to_rgba(
+            window.foreground,
+            window.background,
+            open.pixels.frame_mut(),
+        );
+        open.pixels
+            .render()
+            .map_err(|error| FrontendError::Host(error.to_string()))
+    }
+
+    fn close(&mut self) -> Result<(), FrontendError> {
+        self.window = None;
+        Ok(())
+    }
+}
+
+/// The name SDL gives a key, which is what the config file uses.
+fn key_name(key: VirtualKeyCode) -> Cow<'static, str> {
+    use VirtualKeyCode::*;
+    let name = match key {
+        Key1 => "1",
+        Key2 => "2",
+        Key3 => "3",
+        Key4 => "4",
+        Key5 => "5",
+        Key6 => "6",
+        Key7 => "7",
+        Key8 => "8",
+        Key9 => "9",
+        Key0 => "0",
+        Back => "Backspace",
+        Equals => "=",
+        Minus => "-",
+        Comma => ",",
+        Period => ".",
+        Slash => "/",
+        Semicolon => ";",
+        Apostrophe => "'",
+        LBracket => "[",
+        RBracket => "]",
+        Backslash => "\\",
+        Grave => "`",
+        // letters, function keys, and the rest are named the same in both
+        _ => return Cow::Owned(format!("{key:?}")),
+    };
+    Cow::Borrowed(name)
+}
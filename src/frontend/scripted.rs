@@ -0,0 +1,98 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::{config::Config, display::Frame, speed::Speed};
+
+use super::{DebugView, Frontend, FrontendError, InputEvent, ViewImage};
+
+/// A frontend that plays input from a script and keeps what it's shown, for testing what runs a frontend.
+///
+/// Each poll takes the next batch of input from the script, after waiting a frame's time so the machine gets to
+/// run; once the script is used up, it quits.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedFrontend {
+    script: VecDeque<Vec<InputEvent>>,
+    frame_time: Duration,
+    /// Whether the frontend is open, between `open()` and `close()`.
+    pub open: bool,
+    /// Every frame presented, in order.
+    pub frames: Vec<Frame>,
+    /// Every time the tone was turned on or off, in order.
+    pub tones: Vec<bool>,
+    /// The title it was opened with, then every one it was configured with, in order.
+    pub titles: Vec<String>,
+    /// The config it was opened with, then every one it was configured with, in order.
+    pub configs: Vec<Config>,
+    /// The debug views toggled open, in the order they were opened.
+    pub views: Vec<DebugView>,
+    /// Every debug view presented, in order.
+    pub view_images: Vec<(DebugView, ViewImage)>,
+}
+
+impl ScriptedFrontend {
+    /// A frontend that hands over one batch of input from `script` a poll, waiting `frame_time` before each.
+    pub fn new(
+        script: impl IntoIterator<Item = Vec<InputEvent>>,
+        frame_time: Duration,
+    ) -> ScriptedFrontend {
+        ScriptedFrontend {
+            script: script.into_iter().collect(),
+            frame_time,
+            ..ScriptedFrontend::default()
+        }
+    }
+}
+
+impl Frontend for ScriptedFrontend {
+    fn open(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        self.open = true;
+        self.titles.push(title.to_string());
+        self.configs.push(config.clone());
+        Ok(())
+    }
+
+    fn configure(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        self.titles.push(title.to_string());
+        self.configs.push(config.clone());
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<InputEvent>, FrontendError> {
+        std::thread::sleep(self.frame_time);
+        Ok(self
+            .script
+            .pop_front()
+            .unwrap_or_else(|| vec![InputEvent::Quit]))
+    }
+
+    fn present(&mut self, frame: &Frame, _speed: Speed) -> Result<(), FrontendError> {
+        self.frames.push(frame.clone());
+        Ok(())
+    }
+
+    fn set_tone(&mut self, on: bool) {
+        self.tones.push(on);
+    }
+
+    fn toggle_view(&mut self, view: DebugView) -> Result<(), FrontendError> {
+        if let Some(open) = self.views.iter().position(|&open| open == view) {
+            self.views.remove(open);
+        } else {
+            self.views.push(view);
+        }
+        Ok(())
+    }
+
+    fn open_views(&self) -> Vec<DebugView> {
+        self.views.clone()
+    }
+
+    fn present_view(&mut self, view: DebugView, image: &ViewImage) -> Result<(), FrontendError> {
+        self.view_images.push((view, image.clone()));
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), FrontendError> {
+        self.open = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+//! A window, keyboard and controller input, and sound through SDL2, with debug views in windows of their own.
+
+use std::path::PathBuf;
+
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    controller::{Button, GameController},
+    event::{Event, WindowEvent},
+    pixels::Color as SdlColor,
+    rect::Rect,
+    render::Canvas,
+    video::Window,
+    EventPump, GameControllerSubsystem, Sdl, VideoSubsystem,
+};
+
+use crate::{
+    audio::AudioSink,
+    config::{Color, Config},
+    display::{Frame, HEIGHT, WIDTH},
+    speed::Speed,
+};
+
+use super::{DebugView, Frontend, FrontendError, InputEvent, SquareWave, ToneSwitch, ViewImage};
+
+/// The sample rate asked of the audio device, which may pick another.
+const SAMPLE_RATE: i32 = 44_100;
+
+/// How many window pixels a debug view's pixel takes up when its window opens.
+const VIEW_SCALE: u32 = 3;
+
+/// Plays the tone from SDL's audio thread.
+struct Beeper(SquareWave);
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, samples: &mut [f32]) {
+        self.0.fill(samples);
+    }
+}
+
+/// Shows the machine in a resizable window, and plays it with the keyboard or controllers.
+///
+/// Controller buttons are looked up in the keymap and hotkeys by names like `PadUp` and `PadA`. Keys pressed in a
+/// debug view's window are passed on as if pressed in the main one.
+#[derive(Default)]
+pub struct SdlFrontend {
+    tone: ToneSwitch,
+    window: Option<SdlWindow>,
+}
+
+/// What SDL keeps while the window is open.
+struct SdlWindow {
+    canvas: Canvas<Window>,
+    /// The debug views open, each in a window of its own.
+    views: Vec<(DebugView, Canvas<Window>)>,
+    video: VideoSubsystem,
+    events: EventPump,
+    controllers: GameControllerSubsystem,
+    pads: Vec<GameController>,
+    audio: AudioDevice<Beeper>,
+    foreground: SdlColor,
+    background: SdlColor,
+    _sdl: Sdl,
+}
+
+impl SdlFrontend {
+    pub fn new() -> SdlFrontend {
+        SdlFrontend::default()
+    }
+
+    fn window(&mut self) -> Result<&mut SdlWindow, FrontendError> {
+        self.window
+            .as_mut()
+            .ok_or_else(|| FrontendError::Host("the window isn't open".to_string()))
+    }
+}
+
+impl Frontend for SdlFrontend {
+    fn open(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let sdl = sdl2::init().map_err(FrontendError::Host)?;
+        let video = sdl.video().map_err(FrontendError::Host)?;
+        let scale = config.display.scale.max(1);
+        let window = video
+            .window(title, WIDTH as u32 * scale, HEIGHT as u32 * scale)
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        let mut canvas = window
+            .into_canvas()
+            .present_vsync()
+            .build()
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        // keep the picture's shape however the window is resized, with a display pixel to a logical pixel
+        canvas
+            .set_logical_size(WIDTH as u32, HEIGHT as u32)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        let controllers = sdl.game_controller().map_err(FrontendError::Host)?;
+        let events = sdl.event_pump().map_err(FrontendError::Host)?;
+
+        let audio = sdl.audio().map_err(FrontendError::Host)?;
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio
+            .open_playback(None, &desired, |spec| {
+                Beeper(SquareWave::new(
+                    self.tone.clone(),
+                    &config.audio,
+                    spec.freq as u32,
+                ))
+            })
+            .map_err(FrontendError::Host)?;
+        device.resume();
+
+        self.window = Some(SdlWindow {
+            canvas,
+            views: Vec::new(),
+            video,
+            events,
+            controllers,
+            pads: Vec::new(),
+            audio: device,
+            foreground: color(config.display.foreground),
+            background: color(config.display.background),
+            _sdl: sdl,
+        });
+        Ok(())
+    }
+
+    fn configure(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let tone = self.tone.clone();
+        let window = self.window()?;
+        window
+            .canvas
+            .window_mut()
+            .set_title(title)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        window.foreground = color(config.display.foreground);
+        window.background = color(config.display.background);
+        let sample_rate = window.audio.spec().freq as u32;
+        window.audio.lock().0 = SquareWave::new(tone, &config.audio, sample_rate);
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<Vec<InputEvent>, FrontendError> {
+        let window = self.window()?;
+        let mut input = Vec::new();
+        for event in window.events.poll_iter() {
+            match event {
+                Event::Quit { .. } => input.push(InputEvent::Quit),
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Close,
+                    ..
+                } => {
+                    // SDL only quits by itself once the last window's closed
+                    if window_id == window.canvas.window().id() {
+                        input.push(InputEvent::Quit);
+                    } else {
+                        window
+                            .views
+                            .retain(|(_, canvas)| canvas.window().id() != window_id);
+                    }
+                }
+                Event::DropFile { filename, .. } => {
+                    input.push(InputEvent::OpenRom(PathBuf::from(filename)));
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => input.push(InputEvent::KeyDown(key.name())),
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => input.push(InputEvent::KeyUp(key.name())),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    // a controller that won't open is left out rather than ending the game
+                    window.pads.extend(window.controllers.open(which).ok());
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    if let Some(name) = button_name(button) {
+                        input.push(InputEvent::KeyDown(name.to_string()));
+                    }
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    if let Some(name) = button_name(button) {
+                        input.push(InputEvent::KeyUp(name.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(input)
+    }
+
+    fn present(&mut self, frame: &Frame, _speed: Speed) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        let lit: Vec<Rect> = (0..HEIGHT)
+            .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| frame.get_pixel(x, y))
+            .map(|(x, y)| Rect::new(x as i32, y as i32, 1, 1))
+            .collect();
+        let canvas = &mut window.canvas;
+        canvas.set_draw_color(window.background);
+        canvas.clear();
+        canvas.set_draw_color(window.foreground);
+        canvas.fill_rects(&lit).map_err(FrontendError::Host)?;
+        // waits for vsync, which paces the loop
+        canvas.present();
+        Ok(())
+    }
+
+    fn set_tone(&mut self, on: bool) {
+        self.tone.set_tone(on);
+    }
+
+    fn toggle_view(&mut self, view: DebugView) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        if let Some(open) = window.views.iter().position(|&(open, _)| open == view) {
+            window.views.remove(open);
+            return Ok(());
+        }
+        let (width, height) = view.size();
+        let view_window = window
+            .video
+            .window(
+                view.title(),
+                width as u32 * VIEW_SCALE,
+                height as u32 * VIEW_SCALE,
+            )
+            .resizable()
+            .build()
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        // no vsync, as the main window already waits for it
+        let mut canvas = view_window
+            .into_canvas()
+            .build()
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        canvas
+            .set_logical_size(width as u32, height as u32)
+            .map_err(|error| FrontendError::Host(error.to_string()))?;
+        window.views.push((view, canvas));
+        Ok(())
+    }
+
+    fn open_views(&self) -> Vec<DebugView> {
+        self.window
+            .iter()
+            .flat_map(|window| window.views.iter().map(|&(view, _)| view))
+            .collect()
+    }
+
+    fn present_view(&mut self, view: DebugView, image: &ViewImage) -> Result<(), FrontendError> {
+        let window = self.window()?;
+        let Some((_, canvas)) = window.views.iter_mut().find(|(open, _)| *open == view) else {
+            return Ok(());
+        };
+        let lit: Vec<Rect> = (0..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.get_pixel(x, y))
+            .map(|(x, y)| Rect::new(x as i32, y as i32, 1, 1))
+            .collect();
+        canvas.set_draw_color(window.background);
+        canvas.clear();
+        canvas.set_draw_color(window.foreground);
+        canvas.fill_rects(&lit).map_err(FrontendError::Host)?;
+        canvas.present();
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), FrontendError> {
+        self.window = None;
+        Ok(())
+    }
+}
+
+fn color(color: Color) -> SdlColor {
+    SdlColor::RGB(color.r, color.g, color.b)
+}
+
+/// The name a controller button goes by in the keymap.
+fn button_name(button: Button) -> Option<&'static str> {
+    let name = match button {
+        Button::DPadUp => "PadUp",
+        Button::DPadDown => "PadDown",
+        Button::DPadLeft => "PadLeft",
+        Button::DPadRight => "PadRight",
+        Button::A => "PadA",
+        Button::B => "PadB",
+        Button::X => "PadX",
+        Button::Y => "PadY",
+        Button::Start => "PadStart",
+        Button::Back => "PadBack",
+        _ => return None,
+    };
+    Some(name)
+}
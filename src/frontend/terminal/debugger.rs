@@ -0,0 +1,623 @@
+//! A debugger in the terminal: the screen, with panes for the disassembly, registers, call stack, the last
+//! instructions run, and memory, and a strip showing which memory has been run, read, and written, driven from the
+//! keyboard. It works anywhere a terminal does, such as over SSH, where the egui debugger can't open.
+//!
+//! The keys the debugger takes are listed along the bottom, and come before the keymap:
+//!
+//! - F5 runs the machine in time with its clock, or pauses it
+//! - F6 runs a frame, F10 a single instruction, explaining what it did, F11 steps over a subroutine call, and F12
+//!   out of the subroutine
+//! - F7 steps back an instruction, F8 goes back to the last breakpoint
+//! - F9 sets or clears a breakpoint on the instruction under the cursor
+//! - F2 snapshots memory, after which the bytes changed since are shown in bold, and F3 forgets the snapshot
+//! - Up and Down move the cursor through the disassembly, PageUp and PageDown scroll the memory, and Home has both
+//!   follow the program counter and index register again
+//! - Return pauses the machine to type a change to it, such as `V3 = 0x10`, `PC = main`, or `[0x300] = 0xFF`, made
+//!   with Return again or dropped with Escape. `watch` and some memory, such as `watch 0x300..0x340` or
+//!   `watch sprites read`, sets a watchpoint on it the same way, or clears it if it was set
+//! - Ctrl+C quits
+//!
+//! Everything else bound in the keymap presses keypad keys, whether the machine's running or not.
+
+use std::collections::HashSet;
+
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame as TerminalFrame,
+};
+
+use crate::{
+    clock::VsyncClock,
+    config::Config,
+    debugger::{Debugger, Edit, MemoryWatchpoint, StopReason, Symbols},
+    disassembler::sweep,
+    display::{HEIGHT, WIDTH},
+    machine::{Chip8, Chip8Error, Coverage, MachineState, Touched},
+    system::AccessKind,
+};
+
+use super::{
+    super::{Frontend, FrontendError, InputEvent},
+    Screen, TerminalFrontend,
+};
+
+/// How many bytes the memory pane shows to a row.
+const MEMORY_ROW: usize = 16;
+
+/// How many instructions the disassembly keeps on screen before the cursor.
+const DISASSEMBLY_CONTEXT: u16 = 4;
+
+/// What the snapshot F2 takes is called.
+const SNAPSHOT: &str = "terminal";
+
+const HELP: &str =
+    "F5 run/pause  F6 frame  F10/F11/F12 step/over/out  F7 back  F8 reverse  F9 breakpoint  F2/F3 snapshot  Return edit  ^C quit";
+
+/// Debugs the machine in the terminal until Ctrl+C, then shuts it down so it can autosave.
+///
+/// The machine starts running unless it's paused, and keeps coverage from then on if it wasn't already. The names in
+/// `symbols` are shown over those of the disassembly, and the breakpoints it marks are set. Keys are looked up in the config's keymap by the names SDL gives them, as
+/// every frontend does; the hotkeys aren't used, as the debugger has keys of its own.
+pub fn run(
+    mut machine: Chip8,
+    symbols: &Symbols,
+    config: &Config,
+    title: &str,
+) -> Result<(), FrontendError> {
+    let running = machine.state() != MachineState::Paused;
+    if machine.coverage().is_none() {
+        machine.start_coverage(Coverage::new());
+    }
+    let mut debugger = Debugger::new(machine);
+    debugger.load_symbols(symbols);
+    let mut ui = DebuggerUi::new(debugger, config, title);
+    ui.running = running;
+    let mut terminal = TerminalFrontend::new();
+    terminal.open(config, title)?;
+    let shown = ui.show(&mut terminal);
+    // the terminal is put back even if showing the machine failed
+    let closed = terminal.close();
+    let stopped = ui
+        .debugger
+        .into_machine()
+        .stop()
+        .map_err(FrontendError::from);
+    shown.and(closed).and(stopped)
+}
+
+struct DebuggerUi {
+    debugger: Debugger,
+    config: Config,
+    title: String,
+    running: bool,
+    /// Says how many frames are owed at each redraw.
+    clock: VsyncClock,
+    /// The instruction breakpoints are set on, which follows the program counter whenever the machine stops.
+    cursor: u16,
+    /// The first row of memory shown, or `None` to follow the index register.
+    memory_row: Option<usize>,
+    /// Why the machine last stopped, or the error that stopped it.
+    status: String,
+    /// The change being typed, if one is.
+    editing: Option<String>,
+}
+
+impl DebuggerUi {
+    fn new(debugger: Debugger, config: &Config, title: &str) -> DebuggerUi {
+        DebuggerUi {
+            cursor: debugger.machine().cpu().pc(),
+            debugger,
+            config: config.clone(),
+            title: title.to_string(),
+            running: false,
+            clock: VsyncClock::with_rate(config.tick_rate()),
+            memory_row: None,
+            status: String::new(),
+            editing: None,
+        }
+    }
+
+    /// Passes input on and runs the frames due until the terminal asks to quit.
+    fn show(&mut self, terminal: &mut TerminalFrontend) -> Result<(), FrontendError> {
+        loop {
+            for event in terminal.poll()? {
+                match event {
+                    InputEvent::Quit => return Ok(()),
+                    InputEvent::KeyDown(key) => self.key_down(&key),
+                    InputEvent::KeyUp(key) => self.key_up(&key),
+                    InputEvent::OpenRom(_) => {}
+                }
+            }
+            self.clock.set_speed(self.debugger.machine().speed());
+            let due = self.clock.present().map_err(Chip8Error::Clock)?;
+            if self.running {
+                for _ in 0..due {
+                    let outcome = self.debugger.step_frame();
+                    self.outcome(outcome);
+                    if !self.running {
+                        break;
+                    }
+                }
+            }
+            terminal.draw(|frame| self.render(frame))?;
+        }
+    }
+
+    /// Carries out a debugger command, or presses the keypad key bound to `key`.
+    fn key_down(&mut self, key: &str) {
+        if let Some(text) = &mut self.editing {
+            match key {
+                "Return" => self.finish_edit(),
+                "Escape" => self.editing = None,
+                "Backspace" => drop(text.pop()),
+                "Space" => text.push(' '),
+                _ if key.chars().count() == 1 => text.push_str(key),
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            "F5" => {
+                self.running = !self.running;
+                if !self.running {
+                    self.status = "paused".to_string();
+                    self.cursor = self.debugger.machine().cpu().pc();
+                }
+            }
+            "F6" => self.command(Debugger::step_frame),
+            "F7" => self.command(Debugger::step_back),
+            "F8" => self.command(Debugger::reverse_continue),
+            "F9" => {
+                if !self.debugger.remove_breakpoint(self.cursor) {
+                    self.debugger.add_breakpoint(self.cursor);
+                }
+            }
+            "F10" => self.command(Debugger::step),
+            "F11" => self.command(Debugger::step_over),
+            "F12" => self.command(Debugger::step_out),
+            "F2" => {
+                self.debugger.take_snapshot(SNAPSHOT);
+                self.status = "memory snapshot taken".to_string();
+            }
+            "F3" => {
+                self.debugger.remove_snapshot(SNAPSHOT);
+                self.status.clear();
+            }
+            "Up" => self.cursor = self.cursor.saturating_sub(2),
+            "Down" => self.cursor = self.cursor.saturating_add(2),
+            "PageUp" | "PageDown" => {
+                let rows = self.debugger.machine().memory().len().div_ceil(MEMORY_ROW);
+                let row = self.memory_row.unwrap_or_else(|| self.index_row());
+                self.memory_row = Some(match key {
+                    "PageUp" => row.saturating_sub(8),
+                    _ => (row + 8).min(rows.saturating_sub(1)),
+                });
+            }
+            "Home" => {
+                self.cursor = self.debugger.machine().cpu().pc();
+                self.memory_row = None;
+            }
+            "Return" => {
+                self.running = false;
+                self.editing = Some(String::new());
+            }
+            _ => {
+                if let Some(keypad_key) = self.config.keypad_key(key) {
+                    self.debugger.machine_mut().keypad_mut().press(keypad_key);
+                }
+            }
+        }
+    }
+
+    fn key_up(&mut self, key: &str) {
+        if let Some(keypad_key) = self.config.keypad_key(key) {
+            self.debugger.machine_mut().keypad_mut().release(keypad_key);
+        }
+    }
+
+    /// Makes the change typed, or sets or clears the watchpoint, saying what it did or why it couldn't.
+    fn finish_edit(&mut self) {
+        let Some(text) = self.editing.take() else {
+            return;
+        };
+        if let Some(watched) = text
+            .split_once(' ')
+            .filter(|(word, _)| word.eq_ignore_ascii_case("watch"))
+            .map(|(_, watched)| watched)
+        {
+            self.status = match MemoryWatchpoint::parse(watched, self.debugger.symbols()) {
+                Ok(watchpoint) if self.debugger.remove_memory_watchpoint(&watchpoint) => {
+                    format!("stopped watching {watchpoint}")
+                }
+                Ok(watchpoint) => {
+                    let status = format!("watching {watchpoint}");
+                    self.debugger.add_memory_watchpoint(watchpoint);
+                    status
+                }
+                Err(message) => message,
+            };
+            return;
+        }
+        self.status = match Edit::parse(&text, self.debugger.symbols()) {
+            Ok(edit) => match self.debugger.edit(edit) {
+                Ok(()) => format!("set {edit}"),
+                Err(error) => error.to_string(),
+            },
+            Err(message) => message,
+        };
+    }
+
+    /// Runs a command that stops the machine, which pauses it if it was running.
+    fn command(&mut self, command: fn(&mut Debugger) -> Result<StopReason, Chip8Error>) {
+        self.running = false;
+        let outcome = command(&mut self.debugger);
+        self.outcome(outcome);
+    }
+
+    /// Pauses on anything but a frame finishing, saying why.
+    fn outcome(&mut self, outcome: Result<StopReason, Chip8Error>) {
+        match outcome {
+            Ok(StopReason::Frame) if self.running => return,
+            // a single instruction is explained, which says more than that it ran
+            Ok(StopReason::Step) if !self.running => {
+                self.status = self
+                    .debugger
+                    .explain_last()
+                    .unwrap_or_else(|| describe(StopReason::Step));
+            }
+            Ok(reason) => self.status = describe(reason),
+            Err(error) => self.status = error.to_string(),
+        }
+        self.running = false;
+        self.cursor = self.debugger.machine().cpu().pc();
+    }
+
+    fn index_row(&self) -> usize {
+        self.debugger.machine().cpu().index() as usize / MEMORY_ROW
+    }
+
+    fn render(&self, frame: &mut TerminalFrame) {
+        let [top, bottom, coverage, status] = Layout::vertical([
+            Constraint::Length(HEIGHT as u16 / 2 + 2),
+            Constraint::Min(6),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [screen, side] =
+            Layout::horizontal([Constraint::Length(WIDTH as u16 + 2), Constraint::Min(28)])
+                .areas(top);
+        let [registers, below] =
+            Layout::vertical([Constraint::Length(7), Constraint::Min(3)]).areas(side);
+        let [stack, history] =
+            Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).areas(below);
+        let [disassembly, memory] = Layout::horizontal([
+            Constraint::Min(40),
+            Constraint::Length(MEMORY_ROW as u16 * 3 + 7),
+        ])
+        .areas(bottom);
+
+        let block = Block::bordered().title(" screen ");
+        let inner = block.inner(screen);
+        frame.render_widget(block, screen);
+        frame.render_widget(
+            Screen {
+                frame: &self.debugger.machine().display().frame(),
+                foreground: self.config.display.foreground,
+                background: self.config.display.background,
+            },
+            inner,
+        );
+        frame.render_widget(self.registers(), registers);
+        frame.render_widget(self.stack(), stack);
+        frame.render_widget(self.history(history), history);
+        frame.render_widget(self.disassembly(disassembly), disassembly);
+        frame.render_widget(self.memory(memory), memory);
+        frame.render_widget(self.coverage(coverage), coverage);
+
+        let machine = self.debugger.machine();
+        let state = if self.running { "running" } else { "paused" };
+        let line = match &self.editing {
+            Some(text) => format!(" edit: {text}_   Return to change, Escape to cancel"),
+            None => format!(
+                " {}  {state}  frame {}  {}   {HELP}",
+                self.title,
+                machine.frame_count(),
+                self.status
+            ),
+        };
+        frame.render_widget(Line::from(line), status);
+    }
+
+    fn registers(&self) -> Paragraph<'_> {
+        let machine = self.debugger.machine();
+        let cpu = machine.cpu();
+        let mut lines: Vec<Line> = cpu
+            .registers()
+            .chunks(4)
+            .enumerate()
+            .map(|(row, values)| {
+                let values: Vec<String> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(column, value)| format!("V{:X} {value:02X}", row * 4 + column))
+                    .collect();
+                Line::from(values.join("  "))
+            })
+            .collect();
+        let timers = machine.timers();
+        lines.push(Line::from(format!(
+            "PC {:03X}  I {:03X}  DT {:02X}  ST {:02X}",
+            cpu.pc(),
+            cpu.index(),
+            timers.retrieve_delay_timer(),
+            timers.retrieve_sound_timer()
+        )));
+        Paragraph::new(lines).block(Block::bordered().title(" registers "))
+    }
+
+    /// The subroutines the machine is in, innermost first, named where the symbols can.
+    fn stack(&self) -> Paragraph<'_> {
+        let lines: Vec<Line> = self
+            .debugger
+            .call_stack()
+            .into_iter()
+            .map(|call| {
+                let routine = match (call.label, call.routine) {
+                    (Some(label), _) => label,
+                    (None, Some(routine)) => format!("{routine:03X}"),
+                    (None, None) => "?".to_string(),
+                };
+                match call.call_site {
+                    Some(call_site) => Line::from(format!("{routine}  from {call_site:03X}")),
+                    None => Line::from(format!("{routine}  back to {:03X}", call.return_address)),
+                }
+            })
+            .collect();
+        let stack = self.debugger.machine().cpu().stack();
+        let title = format!(" stack {}/{} ", stack.depth(), stack.capacity());
+        Paragraph::new(lines).block(Block::bordered().title(title))
+    }
+
+    /// The last instructions run, as many as fit, ending with the latest.
+    fn history(&self, area: Rect) -> Paragraph<'_> {
+        let history = self.debugger.machine().history();
+        let rows = area.height.saturating_sub(2) as usize;
+        let mut lines: Vec<Line> = history
+            .iter()
+            .rev()
+            .take(rows)
+            .map(|executed| Line::from(executed.to_string()))
+            .collect();
+        lines.reverse();
+        Paragraph::new(lines).block(Block::bordered().title(" history "))
+    }
+
+    /// The instructions around the cursor, marking the program counter, the cursor, and breakpoints.
+    fn disassembly(&self, area: Rect) -> Paragraph<'_> {
+        let machine = self.debugger.machine();
+        let memory = machine.memory();
+        let pc = machine.cpu().pc();
+        let rows = area.height.saturating_sub(2) as usize;
+        // an even distance back, so the sweep lines up with the cursor
+        let start = self.cursor.saturating_sub(2 * DISASSEMBLY_CONTEXT) as usize;
+        // as many bytes as the longest instructions could take
+        let end = (start + 4 * rows).min(memory.len());
+        let code = memory
+            .slice(start as u16, end.saturating_sub(start))
+            .unwrap_or_default();
+        let symbols = self.debugger.symbols();
+        let breakpoints: Vec<u16> = self.debugger.breakpoints().collect();
+        let lines: Vec<Line> = sweep(code, start as u16, machine.variant())
+            .iter()
+            .take(rows)
+            .map(|line| {
+                let address = line.address;
+                let breakpoint = if breakpoints.contains(&address) {
+                    "●"
+                } else {
+                    " "
+                };
+                let marker = if address == pc { "▶" } else { " " };
+                let label = symbols.name(address).unwrap_or_default();
+                let text = format!(
+                    "{breakpoint}{marker} {address:03X}  {:04X}  {label:<10} {} {}",
+                    line.opcode(),
+                    line.mnemonic(),
+                    line.operands().join(", ")
+                );
+                if address == self.cursor {
+                    Line::styled(text, Style::new().add_modifier(Modifier::REVERSED))
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title(" disassembly "))
+    }
+
+    /// Rows of memory from where it's scrolled to, or from the index register, which is underlined. Bytes changed
+    /// since the snapshot are in bold.
+    fn memory(&self, area: Rect) -> Paragraph<'_> {
+        let machine = self.debugger.machine();
+        let memory = machine.memory();
+        let index = machine.cpu().index() as usize;
+        let first = self.memory_row.unwrap_or_else(|| self.index_row());
+        let rows = area.height.saturating_sub(2) as usize;
+        let changes = self.debugger.diff_snapshot(SNAPSHOT);
+        let changed: HashSet<usize> = changes
+            .iter()
+            .flatten()
+            .map(|change| change.address as usize)
+            .collect();
+        let lines: Vec<Line> = (first..memory.len().div_ceil(MEMORY_ROW))
+            .take(rows)
+            .map_while(|row| {
+                let address = row * MEMORY_ROW;
+                let len = MEMORY_ROW.min(memory.len() - address);
+                let bytes = memory.slice(address as u16, len).ok()?;
+                let mut spans = vec![Span::raw(format!("{address:03X} "))];
+                for (offset, byte) in bytes.iter().enumerate() {
+                    spans.push(Span::raw(" "));
+                    let mut style = Style::new();
+                    if address + offset == index {
+                        style = style.add_modifier(Modifier::UNDERLINED);
+                    }
+                    if changed.contains(&(address + offset)) {
+                        style = style.add_modifier(Modifier::BOLD);
+                    }
+                    spans.push(Span::styled(format!("{byte:02X}"), style));
+                }
+                Some(Line::from(spans))
+            })
+            .collect();
+        let title = match changes {
+            Some(changes) => format!(" memory, {} changed ", changes.len()),
+            None => " memory ".to_string(),
+        };
+        Paragraph::new(lines).block(Block::bordered().title(title))
+    }
+
+    /// All of memory in a row, each cell coloured by what's been done with the bytes it stands for: green where
+    /// they've been run, red written, and blue read.
+    fn coverage(&self, area: Rect) -> Paragraph<'_> {
+        let machine = self.debugger.machine();
+        let Some(coverage) = machine.coverage() else {
+            return Paragraph::new("").block(Block::bordered().title(" coverage off "));
+        };
+        let cells = area.width.saturating_sub(2).max(1) as usize;
+        let len = machine.memory().len();
+        let spans: Vec<Span> = (0..cells)
+            .map(|cell| {
+                let start = cell * len / cells;
+                let end = ((cell + 1) * len / cells).max(start + 1);
+                let color = match coverage.any(start..end) {
+                    Touched { executed: true, .. } => Color::Green,
+                    Touched { written: true, .. } => Color::Red,
+                    Touched { read: true, .. } => Color::Blue,
+                    _ => return Span::styled("·", Style::new().fg(Color::DarkGray)),
+                };
+                Span::styled("█", Style::new().fg(color))
+            })
+            .collect();
+        let title = format!(
+            " coverage: {} bytes run, {} read, {} written ",
+            coverage.executed(),
+            coverage.read(),
+            coverage.written()
+        );
+        Paragraph::new(Line::from(spans)).block(Block::bordered().title(title))
+    }
+}
+
+fn describe(reason: StopReason) -> String {
+    match reason {
+        StopReason::Step => "stepped".to_string(),
+        StopReason::Frame => "frame finished".to_string(),
+        StopReason::Breakpoint(address) => format!("breakpoint at {address:03X}"),
+        StopReason::Watchpoint { register, value } => format!("{register} written with {value:X}"),
+        StopReason::MemoryWatchpoint { pc, access } => {
+            let (address, value) = (access.address, access.value);
+            match access.kind {
+                AccessKind::Read => format!("{pc:03X} read {value:02X} from {address:03X}"),
+                AccessKind::Write => format!("{pc:03X} wrote {value:02X} to {address:03X}"),
+            }
+        }
+        StopReason::Draw { collision: true } => "drew a sprite, colliding".to_string(),
+        StopReason::Draw { collision: false } => "drew a sprite".to_string(),
+        StopReason::KeyRead { key } => format!("read key {key:X}"),
+        StopReason::InfiniteLoop(address) => format!("looping forever at {address:03X}"),
+        StopReason::WaitingForKey => "waiting for a key".to_string(),
+        StopReason::Interrupted => "interrupted".to_string(),
+        StopReason::StepBack => "stepped back".to_string(),
+        StopReason::HistoryStart => "as far back as it goes".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use crate::clock::{ManualClock, TickRate};
+
+    use super::*;
+
+    fn debugging(rom: &[u8]) -> DebuggerUi {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        machine.load_rom(rom).expect("failed to load rom");
+        DebuggerUi::new(Debugger::new(machine), &Config::default(), "test")
+    }
+
+    /// Everything drawn, row after row.
+    fn drawn(ui: &DebuggerUi) -> String {
+        let mut terminal =
+            Terminal::new(TestBackend::new(120, 40)).expect("failed to open terminal");
+        terminal
+            .draw(|frame| ui.render(frame))
+            .expect("failed to draw");
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect()
+    }
+
+    #[test]
+    fn steps_and_sets_breakpoints_from_the_keyboard() {
+        // V0 += 1, then jump back
+        let mut ui = debugging(&[0x70, 0x01, 0x12, 0x00]);
+        ui.key_down("Down");
+        ui.key_down("F9");
+        ui.key_down("F10");
+        assert_eq!(ui.debugger.machine().cpu().pc(), 0x202);
+        assert_eq!(ui.status, "Add 0x01 to V0=0x00, making 0x01");
+        ui.key_down("F7");
+        assert_eq!(ui.status, "stepped back");
+        ui.key_down("F5");
+        assert!(ui.running);
+        let outcome = ui.debugger.step_frame();
+        ui.outcome(outcome);
+        assert_eq!(ui.status, "breakpoint at 202");
+        assert!(!ui.running);
+
+        let text = drawn(&ui);
+        assert!(text.contains("●▶ 202  1200"), "{text}");
+        assert!(text.contains("V0 01"), "{text}");
+        assert!(text.contains("0x200  7001  ADD V0, 0x01"), "{text}");
+    }
+
+    #[test]
+    fn edits_registers_as_typed() {
+        let mut ui = debugging(&[0x70, 0x01, 0x12, 0x00]);
+        for key in ["Return", "V", "5", "Space", "=", "Space", "7", "Return"] {
+            ui.key_down(key);
+        }
+        assert_eq!(ui.status, "set V5 = 0x07");
+        assert_eq!(ui.debugger.machine().cpu().registers()[5], 7);
+        for key in ["Return", "P", "C", "Escape"] {
+            ui.key_down(key);
+        }
+        assert_eq!(ui.editing, None);
+        assert!(drawn(&ui).contains("V5 07"));
+    }
+
+    #[test]
+    fn counts_the_bytes_changed_since_the_snapshot() {
+        // I = 0x300, V0 = 1, store V0, loop
+        let mut ui = debugging(&[0xA3, 0x00, 0x60, 0x01, 0xF0, 0x55, 0x12, 0x06]);
+        ui.key_down("F2");
+        for _ in 0..3 {
+            ui.key_down("F10");
+        }
+        let text = drawn(&ui);
+        assert!(text.contains(" memory, 1 changed "), "{text}");
+        ui.key_down("F3");
+        assert!(drawn(&ui).contains(" memory "));
+    }
+}
@@ -0,0 +1,289 @@
+//! The screen drawn in the terminal with half-block characters, for playing over SSH or testing without a window.
+//!
+//! Most terminals only report keys being pressed, so a key counts as held until it stops repeating, unless the
+//! terminal can report releases too. There's no sound; the status bar shows a note while the tone plays.
+//!
+//! `debugger` draws a debugger in the terminal the same way, for debugging where there's no window.
+
+use std::{
+    collections::HashMap,
+    io,
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    buffer::Buffer,
+    crossterm::{
+        event::{
+            self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+            PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+        },
+        execute,
+        terminal::supports_keyboard_enhancement,
+    },
+    layout::{Constraint, Layout, Rect},
+    style::{Color as TermColor, Style},
+    text::Line,
+    widgets::Widget,
+    DefaultTerminal,
+};
+
+use crate::{
+    config::{Color, Config},
+    display::{Frame, HEIGHT, WIDTH},
+    speed::Speed,
+};
+
+use super::{Frontend, FrontendError, InputEvent};
+
+pub mod debugger;
+
+/// How long a key counts as held after the terminal last reported it, when it can't report releases. Long enough
+/// to bridge the gap before key repeat starts.
+const HOLD_TIME: Duration = Duration::from_millis(600);
+
+/// How long to wait for input between redraws.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Shows the machine in the terminal, and plays it with the keyboard. Ctrl+C quits.
+#[derive(Default)]
+pub struct TerminalFrontend {
+    tone: bool,
+    session: Option<Session>,
+}
+
+/// The terminal while it's taken over, and what's needed to draw in it.
+struct Session {
+    terminal: DefaultTerminal,
+    /// Whether the terminal reports keys being released.
+    releases: bool,
+    /// Keys the terminal can't report releasing, and when they were last reported.
+    held: HashMap<String, Instant>,
+    title: String,
+    instructions_per_second: u32,
+    foreground: Color,
+    background: Color,
+}
+
+impl TerminalFrontend {
+    pub fn new() -> TerminalFrontend {
+        TerminalFrontend::default()
+    }
+
+    fn session(&mut self) -> Result<&mut Session, FrontendError> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| FrontendError::Host("the terminal isn't open".to_string()))
+    }
+
+    /// Redraws the whole terminal with `render`.
+    fn draw(&mut self, render: impl FnOnce(&mut ratatui::Frame)) -> Result<(), FrontendError> {
+        self.session()?.terminal.draw(render).map_err(host)?;
+        Ok(())
+    }
+}
+
+impl Frontend for TerminalFrontend {
+    fn open(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let terminal = ratatui::try_init().map_err(host)?;
+        let releases = supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            let pushed = execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            );
+            if let Err(error) = pushed {
+                // the terminal is being put back regardless, so a failure here has nowhere better to go
+                let _ = ratatui::try_restore();
+                return Err(host(error));
+            }
+        }
+        self.session = Some(Session {
+            terminal,
+            releases,
+            held: HashMap::new(),
+            title: title.to_string(),
+            instructions_per_second: config.machine.instructions_per_second,
+            foreground: config.display.foreground,
+            background: config.display.background,
+        });
+        Ok(())
+    }
+
+    fn configure(&mut self, config: &Config, title: &str) -> Result<(), FrontendError> {
+        let session = self.session()?;
+        session.title = title.to_string();
+        session.instructions_per_second = config.machine.instructions_per_second;
+        session.foreground = config.display.foreground;
+        session.background = config.display.background;
+        Ok(())
+    }
+
+    /// Waits about a frame for keys, and releases keys the terminal has stopped repeating.
+    fn poll(&mut self) -> Result<Vec<InputEvent>, FrontendError> {
+        let session = self.session()?;
+        let mut input = Vec::new();
+        let mut timeout = FRAME_INTERVAL;
+        while event::poll(timeout).map_err(host)? {
+            timeout = Duration::ZERO;
+            let Event::Key(key) = event::read().map_err(host)? else {
+                continue;
+            };
+            if is_quit(&key) {
+                input.push(InputEvent::Quit);
+                continue;
+            }
+            let Some(name) = key_name(key.code) else {
+                continue;
+            };
+            match key.kind {
+                KeyEventKind::Press | KeyEventKind::Repeat => {
+                    if !session.releases {
+                        session.held.insert(name.clone(), Instant::now());
+                    }
+                    input.push(InputEvent::KeyDown(name));
+                }
+                KeyEventKind::Release => input.push(InputEvent::KeyUp(name)),
+            }
+        }
+        let now = Instant::now();
+        session.held.retain(|name, &mut at| {
+            let keep = now - at < HOLD_TIME;
+            if !keep {
+                input.push(InputEvent::KeyUp(name.clone()));
+            }
+            keep
+        });
+        Ok(input)
+    }
+
+    fn present(&mut self, frame: &Frame, speed: Speed) -> Result<(), FrontendError> {
+        let tone = self.tone;
+        let session = self.session()?;
+        let ips = match speed {
+            Speed::Scaled(_) => format!(
+                "{} IPS",
+                speed.scale_budget(session.instructions_per_second)
+            ),
+            Speed::Unlimited => "unlimited IPS".to_string(),
+        };
+        let sound = if tone { "♪" } else { " " };
+        let status_line = format!(" {}  {ips}  {sound}", session.title);
+        let screen = Screen {
+            frame,
+            foreground: session.foreground,
+            background: session.background,
+        };
+        self.draw(|terminal_frame| {
+            let [screen_area, status] =
+                Layout::vertical([Constraint::Length(HEIGHT as u16 / 2), Constraint::Length(1)])
+                    .areas(terminal_frame.area());
+            terminal_frame.render_widget(screen, screen_area);
+            terminal_frame.render_widget(Line::from(status_line), status);
+        })
+    }
+
+    fn set_tone(&mut self, on: bool) {
+        self.tone = on;
+    }
+
+    fn close(&mut self) -> Result<(), FrontendError> {
+        let Some(session) = self.session.take() else {
+            return Ok(());
+        };
+        if session.releases {
+            // the terminal is being put back regardless, so a failure here has nowhere better to go
+            let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+        }
+        ratatui::try_restore().map_err(host)
+    }
+}
+
+fn host(error: io::Error) -> FrontendError {
+    FrontendError::Host(error.to_string())
+}
+
+fn is_quit(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// The name SDL gives a key, which is what the config file uses.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_uppercase().to_string(),
+        KeyCode::F(number) => format!("F{number}"),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Enter => "Return".to_string(),
+        KeyCode::Esc => "Escape".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The display, two pixels to a character cell: the upper half block in the top pixel's colour, over the bottom
+/// pixel's colour.
+struct Screen<'a> {
+    frame: &'a Frame,
+    foreground: Color,
+    background: Color,
+}
+
+impl Widget for Screen<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let color = |on: bool| {
+            let color = if on { self.foreground } else { self.background };
+            TermColor::Rgb(color.r, color.g, color.b)
+        };
+        let width = (WIDTH as u16).min(area.width);
+        let left = area.x + (area.width - width) / 2;
+        for row in 0..(HEIGHT as u16 / 2).min(area.height) {
+            for column in 0..width {
+                let (x, y) = (column as usize, row as usize * 2);
+                let style = Style::new()
+                    .fg(color(self.frame.get_pixel(x, y)))
+                    .bg(color(self.frame.get_pixel(x, y + 1)));
+                if let Some(cell) = buf.cell_mut((left + column, area.y + row)) {
+                    cell.set_symbol("▀").set_style(style);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_two_rows_to_a_cell() {
+        let mut pbm = format!("P1\n{WIDTH} {HEIGHT}\n1").into_bytes();
+        pbm.extend(std::iter::repeat_n(b'0', WIDTH * HEIGHT - 1));
+        let frame = Frame::from_pbm(&pbm).expect("failed to build frame");
+        let area = Rect::new(0, 0, WIDTH as u16 + 4, HEIGHT as u16 / 2);
+        let mut buf = Buffer::empty(area);
+        Screen {
+            frame: &frame,
+            foreground: Color::WHITE,
+            background: Color::BLACK,
+        }
+        .render(area, &mut buf);
+
+        // centred, with the lit pixel on top of the first cell
+        assert_eq!(buf[(0, 0)].symbol(), " ");
+        let cell = &buf[(2, 0)];
+        assert_eq!(cell.symbol(), "▀");
+        assert_eq!(cell.fg, TermColor::Rgb(0xFF, 0xFF, 0xFF));
+        assert_eq!(cell.bg, TermColor::Rgb(0, 0, 0));
+        assert_eq!(buf[(3, 0)].fg, TermColor::Rgb(0, 0, 0));
+    }
+}
@@ -0,0 +1,265 @@
+//! Debug views, drawn for windows of their own beside the display: memory, disassembly, and memory as sprites.
+//!
+//! The machine runs on a thread of its own, so views are drawn from a `SaveState` it's asked for each frame while
+//! any view is open, rather than by reaching into the machine. Like the overlay, views are drawn in lit and unlit
+//! pixels and the overlay's font, for frontends to show in the display's colours.
+
+use crate::{config::Color, disassembler::sweep, hotkeys::Hotkey, machine::SaveState};
+
+use super::osd::{draw_text, Canvas, CHAR_WIDTH, LINE_HEIGHT};
+
+/// How many lines of text the memory and disassembly views have.
+const TEXT_LINES: usize = 32;
+
+/// How many bytes a line of the memory view shows.
+const BYTES_PER_LINE: usize = 8;
+
+/// How many lines of the disassembly come before the instruction about to run.
+const LINES_BEFORE_PC: usize = 8;
+
+/// How the sprite view lays memory out: columns of bytes, a pixel to a bit, with a gap between columns.
+const SPRITE_COLUMNS: usize = 32;
+const SPRITE_COLUMN_BYTES: usize = 128;
+const SPRITE_COLUMN_WIDTH: usize = 9;
+
+/// Something about the machine that can be shown in a window of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    /// The 256 bytes around the index register, where sprites are drawn from and BCD goes, with I's byte marked.
+    Memory,
+    /// The instructions around the program counter, with the next to run marked.
+    Disassembly,
+    /// Memory drawn as sprite rows, a byte to a row of 8 pixels, with I's byte marked.
+    Sprites,
+}
+
+impl DebugView {
+    /// The view a hotkey toggles, if it toggles one.
+    pub fn for_hotkey(hotkey: Hotkey) -> Option<DebugView> {
+        match hotkey {
+            Hotkey::MemoryView => Some(DebugView::Memory),
+            Hotkey::DisassemblyView => Some(DebugView::Disassembly),
+            Hotkey::SpriteView => Some(DebugView::Sprites),
+            _ => None,
+        }
+    }
+
+    /// The title of the view's window.
+    pub fn title(self) -> &'static str {
+        match self {
+            DebugView::Memory => "chip8 - memory",
+            DebugView::Disassembly => "chip8 - disassembly",
+            DebugView::Sprites => "chip8 - sprites",
+        }
+    }
+
+    /// How big the view's image is, in pixels, width first.
+    pub fn size(self) -> (usize, usize) {
+        match self {
+            // an address and a line of bytes
+            DebugView::Memory => (
+                (5 + 3 * BYTES_PER_LINE) * CHAR_WIDTH,
+                TEXT_LINES * LINE_HEIGHT,
+            ),
+            DebugView::Disassembly => (32 * CHAR_WIDTH, TEXT_LINES * LINE_HEIGHT),
+            DebugView::Sprites => (SPRITE_COLUMNS * SPRITE_COLUMN_WIDTH, SPRITE_COLUMN_BYTES),
+        }
+    }
+
+    /// Draws the view of a machine in the given state.
+    pub fn render(self, state: &SaveState) -> ViewImage {
+        let (width, height) = self.size();
+        let mut image = ViewImage::new(width, height);
+        match self {
+            DebugView::Memory => draw_memory(&mut image, state),
+            DebugView::Disassembly => draw_disassembly(&mut image, state),
+            DebugView::Sprites => draw_sprites(&mut image, state),
+        }
+        image
+    }
+}
+
+/// A debug view as drawn, a byte to a pixel, 1 for lit and 0 for unlit, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl ViewImage {
+    fn new(width: usize, height: usize) -> ViewImage {
+        ViewImage {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[x + y * self.width] == 1
+    }
+
+    /// The pixels row by row, one byte each, 1 for lit and 0 for unlit.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Draws the image as packed RGBA bytes, four to a pixel, row by row, all opaque.
+    pub fn to_rgba(&self, foreground: Color, background: Color, rgba: &mut [u8]) {
+        for (pixel, &on) in rgba.chunks_exact_mut(4).zip(&self.pixels) {
+            let color = if on == 1 { foreground } else { background };
+            pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+        }
+    }
+
+    /// Flips the pixels in a box, to mark what's in it.
+    fn invert(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                self.pixels[x + y * self.width] ^= 1;
+            }
+        }
+    }
+}
+
+impl Canvas for ViewImage {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        if x < self.width && y < self.height {
+            self.pixels[x + y * self.width] = on as u8;
+        }
+    }
+}
+
+fn draw_memory(image: &mut ViewImage, state: &SaveState) {
+    let index = state.cpu.index() as usize;
+    let start = index & !0xFF;
+    for line in 0..TEXT_LINES {
+        let address = start + line * BYTES_PER_LINE;
+        let y = line * LINE_HEIGHT;
+        let mut text = format!("{address:04X}");
+        for offset in 0..BYTES_PER_LINE {
+            match state.memory.read((address + offset) as u16) {
+                Ok(byte) => text.push_str(&format!(" {byte:02X}")),
+                Err(_) => text.push_str(" --"),
+            }
+        }
+        draw_text(image, 0, y, &text);
+        if (address..address + BYTES_PER_LINE).contains(&index) {
+            let column = 5 + 3 * (index - address);
+            image.invert(column * CHAR_WIDTH - 1, y, 3 * CHAR_WIDTH - 1, LINE_HEIGHT);
+        }
+    }
+}
+
+fn draw_disassembly(image: &mut ViewImage, state: &SaveState) {
+    let pc = state.cpu.pc();
+    // instructions are mostly two bytes, so stepping back two at a time lines up with the program counter
+    let start = pc.saturating_sub(2 * LINES_BEFORE_PC as u16) as usize;
+    // as many bytes as the longest instructions could take
+    let end = (start + 4 * TEXT_LINES).min(state.memory.len());
+    let code = state
+        .memory
+        .slice(start as u16, end.saturating_sub(start))
+        .unwrap_or_default();
+    for (row, line) in sweep(code, start as u16, state.variant)
+        .iter()
+        .take(TEXT_LINES)
+        .enumerate()
+    {
+        let marker = if line.address == pc { '>' } else { ' ' };
+        let (address, opcode) = (line.address, line.opcode());
+        let text = match line.instruction {
+            Some(instruction) => format!("{marker}{address:04X} {opcode:04X} {instruction}"),
+            None => format!("{marker}{address:04X} {opcode:02X}   DB 0x{opcode:02X}"),
+        };
+        draw_text(image, 0, row * LINE_HEIGHT, &text);
+    }
+}
+
+fn draw_sprites(image: &mut ViewImage, state: &SaveState) {
+    let index = state.cpu.index() as usize;
+    // the 4K of memory that I points into, which is all of it but on XO-CHIP
+    let start = index & !0xFFF;
+    for column in 0..SPRITE_COLUMNS {
+        for row in 0..SPRITE_COLUMN_BYTES {
+            let address = start + column * SPRITE_COLUMN_BYTES + row;
+            let byte = state.memory.read(address as u16).unwrap_or(0);
+            let x = column * SPRITE_COLUMN_WIDTH;
+            for bit in 0..8 {
+                image.set_pixel(x + bit, row, byte & (0x80 >> bit) != 0);
+            }
+            if address == index {
+                image.invert(x, row, 8, 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Chip8,
+        memory::FONT_START,
+    };
+
+    use super::*;
+
+    /// The state of a machine after running `steps` instructions of `rom`.
+    fn state(rom: &[u8], steps: usize) -> SaveState {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        machine.load_rom(rom).expect("failed to load rom");
+        for _ in 0..steps {
+            machine.step().expect("failed to step");
+        }
+        machine.save_state()
+    }
+
+    #[test]
+    fn draws_the_font_in_the_sprite_view() {
+        let image = DebugView::Sprites.render(&state(&[0x12, 0x00], 0));
+        // the font's 0 is F0 90 90 90 F0, at the top of the first column
+        let row = |y: usize| (0..8).map(|x| image.get_pixel(x, y)).collect::<Vec<_>>();
+        let top = FONT_START;
+        assert_eq!(
+            row(top),
+            [true, true, true, true, false, false, false, false]
+        );
+        assert_eq!(
+            row(top + 1),
+            [true, false, false, true, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn marks_the_byte_at_i() {
+        // LD I, 0x050
+        let image = DebugView::Memory.render(&state(&[0xA0, 0x50], 1));
+        // 0x050 starts the eleventh line, and its byte the first after the address
+        let y = 10 * LINE_HEIGHT;
+        let x = 5 * CHAR_WIDTH - 1;
+        assert!(image.get_pixel(x, y));
+        assert!(!image.get_pixel(x - 2, y));
+    }
+
+    #[test]
+    fn marks_the_next_instruction() {
+        let image = DebugView::Disassembly.render(&state(&[0x00, 0xE0, 0x12, 0x00], 0));
+        let marker_line = (0..TEXT_LINES).find(|&line| image.get_pixel(0, line * LINE_HEIGHT));
+        assert_eq!(marker_line, Some(LINES_BEFORE_PC));
+    }
+}
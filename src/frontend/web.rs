@@ -0,0 +1,203 @@
+//! A canvas, keyboard input, and sound through WebAudio, for running in the browser from wasm32.
+//!
+//! Browsers won't let the machine have a thread of its own, so the page calls `frame()` from
+//! `requestAnimationFrame` and the machine runs however many frames are owed since the last call. Build the library
+//! as a `cdylib` for `wasm32-unknown-unknown` with the `wasm` feature and run `wasm-bindgen --target web` over it;
+//! `web/index.html` shows how a page puts it together.
+
+use std::time::Duration;
+
+use wasm_bindgen::{prelude::*, Clamped, JsCast};
+use web_sys::{
+    AudioContext, CanvasRenderingContext2d, GainNode, HtmlCanvasElement, ImageData, OscillatorType,
+};
+
+use crate::{
+    clock::{ManualClock, VsyncClock},
+    config::Config,
+    display::{HEIGHT, WIDTH},
+    machine::{Chip8, Chip8Error, MachineState},
+};
+
+use super::{code_key_name, frame_to_rgba, FrontendError, KeyInput, Outputs, RGBA_FRAME_LEN};
+
+/// A machine shown on a canvas, for a page to drive.
+#[wasm_bindgen]
+pub struct WebFrontend {
+    machine: Chip8,
+    outputs: Outputs,
+    config: Config,
+    input: KeyInput,
+    /// Says how many frames are owed at each call.
+    clock: VsyncClock,
+    context: CanvasRenderingContext2d,
+    rgba: Vec<u8>,
+    beeper: Option<Beeper>,
+    /// When `frame()` was last called, in milliseconds.
+    last_call: Option<f64>,
+}
+
+#[wasm_bindgen]
+impl WebFrontend {
+    /// Builds a machine running `rom`, drawing on `canvas`, with the config given as TOML put over the defaults.
+    ///
+    /// The canvas is sized to the display's pixels; scale it up with CSS and `image-rendering: pixelated`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        canvas: HtmlCanvasElement,
+        rom: &[u8],
+        config: Option<String>,
+    ) -> Result<WebFrontend, JsError> {
+        let config = match config {
+            Some(toml) => Config::from_toml(&toml)?,
+            None => Config::default(),
+        }
+        .for_rom(rom, None)?;
+        let outputs = Outputs::new();
+        let mut builder = config
+            .builder()
+            .clock(Box::new(ManualClock::new()), config.tick_rate())
+            .autosave(false);
+        if config.machine.seed.is_none() {
+            builder = builder.seed((js_sys::Math::random() * u64::MAX as f64) as u64);
+        }
+        let mut machine = outputs.attach(builder).build()?;
+        machine.load_rom(rom)?;
+
+        canvas.set_width(WIDTH as u32);
+        canvas.set_height(HEIGHT as u32);
+        let context = canvas
+            .get_context("2d")
+            .map_err(host)?
+            .and_then(|context| context.dyn_into::<CanvasRenderingContext2d>().ok())
+            .ok_or_else(|| FrontendError::Host("the canvas has no 2D context".to_string()))?;
+        let beeper = if config.audio.enabled {
+            Some(Beeper::new(&config).map_err(host)?)
+        } else {
+            None
+        };
+        let input = KeyInput::new(&config, machine.handle());
+        let clock = VsyncClock::with_rate(config.tick_rate());
+        Ok(WebFrontend {
+            machine,
+            outputs,
+            config,
+            input,
+            clock,
+            context,
+            rgba: vec![0; RGBA_FRAME_LEN],
+            beeper,
+            last_call: None,
+        })
+    }
+
+    /// Runs the frames owed since the last call and draws the display, given the time from
+    /// `requestAnimationFrame` in milliseconds.
+    pub fn frame(&mut self, now: f64) -> Result<(), JsError> {
+        self.machine.process_commands();
+        let elapsed = self.last_call.map_or(0.0, |last| (now - last).max(0.0));
+        self.last_call = Some(now);
+        if self.machine.state() == MachineState::Stopped {
+            return Err(FrontendError::Machine(Chip8Error::Stopped).into());
+        }
+        self.clock.set_speed(self.machine.speed());
+        // timed even while paused, so the time spent paused isn't owed afterwards
+        let due = self
+            .clock
+            .present_after(Duration::from_secs_f64(elapsed / 1000.0))
+            .map_err(Chip8Error::Clock)?;
+        if self.machine.state() != MachineState::Paused {
+            for _ in 0..due {
+                self.machine.run_frame()?;
+            }
+        }
+
+        frame_to_rgba(
+            &self.outputs.display.frame(),
+            self.config.display.foreground,
+            self.config.display.background,
+            &mut self.rgba,
+        );
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.rgba),
+            WIDTH as u32,
+            HEIGHT as u32,
+        )
+        .map_err(host)?;
+        self.context
+            .put_image_data(&image, 0.0, 0.0)
+            .map_err(host)?;
+        if let Some(beeper) = &mut self.beeper {
+            beeper.set_tone(self.outputs.tone.is_on());
+        }
+        Ok(())
+    }
+
+    /// Passes on a key being pressed, given its `KeyboardEvent.code`, returning whether anything is bound to it so
+    /// the page knows whether to prevent the default action.
+    #[wasm_bindgen(js_name = keyDown)]
+    pub fn key_down(&mut self, code: &str) -> Result<bool, JsError> {
+        // browsers only let sound start once the player has done something
+        if let Some(beeper) = &self.beeper {
+            // nothing needs to wait for it to resume
+            let _ = beeper.context.resume().map_err(host)?;
+        }
+        Ok(self.input.key_down(code_key_name(code))?)
+    }
+
+    /// Passes on a key being released, given its `KeyboardEvent.code`.
+    #[wasm_bindgen(js_name = keyUp)]
+    pub fn key_up(&mut self, code: &str) -> Result<bool, JsError> {
+        Ok(self.input.key_up(code_key_name(code))?)
+    }
+
+    /// Stops the machine and the tone for good.
+    pub fn stop(&mut self) -> Result<(), JsError> {
+        if let Some(beeper) = self.beeper.take() {
+            // the machine is stopping regardless, so there's nowhere better for this to go
+            let _ = beeper.context.close();
+        }
+        Ok(self.machine.stop()?)
+    }
+}
+
+/// A square wave that's always playing, turned up and down as the tone goes on and off.
+struct Beeper {
+    context: AudioContext,
+    gain: GainNode,
+    volume: f32,
+    on: bool,
+}
+
+impl Beeper {
+    fn new(config: &Config) -> Result<Beeper, JsValue> {
+        let context = AudioContext::new()?;
+        let oscillator = context.create_oscillator()?;
+        oscillator.set_type(OscillatorType::Square);
+        oscillator.frequency().set_value(config.audio.frequency);
+        let gain = context.create_gain()?;
+        gain.gain().set_value(0.0);
+        oscillator.connect_with_audio_node(&gain)?;
+        gain.connect_with_audio_node(&context.destination())?;
+        oscillator.start()?;
+        Ok(Beeper {
+            context,
+            gain,
+            volume: config.audio.volume.clamp(0.0, 1.0),
+            on: false,
+        })
+    }
+
+    fn set_tone(&mut self, on: bool) {
+        if on != self.on {
+            self.on = on;
+            self.gain
+                .gain()
+                .set_value(if on { self.volume } else { 0.0 });
+        }
+    }
+}
+
+fn host(error: JsValue) -> FrontendError {
+    FrontendError::Host(error.as_string().unwrap_or_else(|| format!("{error:?}")))
+}
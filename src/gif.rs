@@ -0,0 +1,190 @@
+//! Writes frames of the display out as an animated GIF, for sharing runs of a program.
+
+use std::io::{self, Write};
+
+use weezl::{encode::Encoder, BitOrder};
+
+use crate::{
+    clock::TickRate,
+    config::Color,
+    display::{Frame, HEIGHT, WIDTH},
+};
+
+/// The smallest LZW code size GIF allows, which is enough for a two colour palette.
+const MIN_CODE_SIZE: u8 = 2;
+
+/// Writes an animated GIF one frame of the display at a time.
+///
+/// Frames that look the same as the one before are folded into it, so a program that sits still costs nothing.
+/// Each image is held back until the next different one arrives, as that decides how long it is shown for.
+pub struct GifWriter<W: Write> {
+    writer: W,
+    scale: usize,
+    rate: TickRate,
+    /// The image waiting to be written, and how many frames it has been shown for.
+    pending: Option<(Frame, u64)>,
+    /// How many frames the written images cover, and how many hundredths of a second they last.
+    elapsed: (u64, u64),
+    images: usize,
+}
+
+impl<W: Write> GifWriter<W> {
+    /// Starts a GIF that loops forever, drawing pixels `scale` times over in the given colours. `rate` is how
+    /// often frames are pushed, which sets how long each is shown.
+    pub fn new(
+        mut writer: W,
+        foreground: Color,
+        background: Color,
+        scale: usize,
+        rate: TickRate,
+    ) -> io::Result<GifWriter<W>> {
+        let scale = scale.max(1);
+        let size = |pixels: usize| {
+            u16::try_from(pixels * scale)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "gif is too large"))
+        };
+        let (width, height) = (size(WIDTH)?, size(HEIGHT)?);
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        // a global palette of two colours, background first so pixels index it directly
+        writer.write_all(&[0x80, 0, 0])?;
+        writer.write_all(&[background.r, background.g, background.b])?;
+        writer.write_all(&[foreground.r, foreground.g, foreground.b])?;
+        writer.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(GifWriter {
+            writer,
+            scale,
+            rate,
+            pending: None,
+            elapsed: (0, 0),
+            images: 0,
+        })
+    }
+
+    /// Adds a frame to the animation.
+    pub fn push(&mut self, frame: &Frame) -> io::Result<()> {
+        match &mut self.pending {
+            Some((pending, shown)) if pending == frame => {
+                *shown += 1;
+                Ok(())
+            }
+            _ => {
+                let previous = self.pending.replace((frame.clone(), 1));
+                match previous {
+                    Some((image, shown)) => self.write_image(&image, shown),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// How many images have been written so far, not counting the one held back.
+    pub fn images(&self) -> usize {
+        self.images
+    }
+
+    /// Writes the last image and the end of the GIF, handing back the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some((image, shown)) = self.pending.take() {
+            self.write_image(&image, shown)?;
+        }
+        self.writer.write_all(&[0x3B])?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_image(&mut self, frame: &Frame, shown: u64) -> io::Result<()> {
+        // work out delays from the running total, so rounding each one doesn't make the animation drift
+        let frames = self.elapsed.0 + shown;
+        let centis = (frames as f64 * 100.0 / self.rate.hz()).round() as u64;
+        let delay = u16::try_from(centis - self.elapsed.1).unwrap_or(u16::MAX);
+        self.elapsed = (frames, centis);
+
+        let writer = &mut self.writer;
+        writer.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        writer.write_all(&delay.to_le_bytes())?;
+        writer.write_all(&[0x00, 0x00])?;
+        let (width, height) = ((WIDTH * self.scale) as u16, (HEIGHT * self.scale) as u16);
+        writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        writer.write_all(&[0x00, MIN_CODE_SIZE])?;
+
+        let mut indices = Vec::with_capacity(WIDTH * HEIGHT * self.scale * self.scale);
+        for row in frame.pixels().chunks(WIDTH) {
+            let start = indices.len();
+            for &pixel in row {
+                indices.extend(std::iter::repeat_n(pixel, self.scale));
+            }
+            for _ in 1..self.scale {
+                indices.extend_from_within(start..start + WIDTH * self.scale);
+            }
+        }
+        let data = Encoder::new(BitOrder::Lsb, MIN_CODE_SIZE)
+            .encode(&indices)
+            .map_err(io::Error::other)?;
+        for block in data.chunks(255) {
+            writer.write_all(&[block.len() as u8])?;
+            writer.write_all(block)?;
+        }
+        writer.write_all(&[0x00])?;
+        self.images += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use weezl::decode::Decoder;
+
+    use super::*;
+
+    fn frame_with_pixel(pixel: Option<(usize, usize)>) -> Frame {
+        let mut pbm = format!("P1\n{WIDTH} {HEIGHT}\n").into_bytes();
+        for row in 0..HEIGHT {
+            for column in 0..WIDTH {
+                pbm.push(if pixel == Some((column, row)) {
+                    b'1'
+                } else {
+                    b'0'
+                });
+            }
+            pbm.push(b'\n');
+        }
+        Frame::from_pbm(&pbm).expect("failed to build frame")
+    }
+
+    #[test]
+    fn folds_repeated_frames_into_one_image() {
+        let mut gif = GifWriter::new(Vec::new(), Color::WHITE, Color::BLACK, 2, TickRate::NTSC)
+            .expect("failed to start gif");
+        let blank = frame_with_pixel(None);
+        let dotted = frame_with_pixel(Some((3, 1)));
+        for _ in 0..30 {
+            gif.push(&blank).unwrap();
+        }
+        gif.push(&dotted).unwrap();
+        assert_eq!(gif.images(), 1);
+        let bytes = gif.finish().unwrap();
+
+        assert!(bytes.starts_with(b"GIF89a"));
+        assert_eq!(bytes.last(), Some(&0x3B));
+        // header, screen, palette, and the looping extension come first, then the first image's delay
+        let image = &bytes[6 + 7 + 6 + 19..];
+        assert_eq!(&image[..4], &[0x21, 0xF9, 0x04, 0x00]);
+        assert_eq!(u16::from_le_bytes([image[4], image[5]]), 50);
+
+        let mut data = Vec::new();
+        let mut blocks = &image[8 + 10 + 1..];
+        while blocks[0] != 0 {
+            let len = blocks[0] as usize;
+            data.extend_from_slice(&blocks[1..=len]);
+            blocks = &blocks[len + 1..];
+        }
+        let indices = Decoder::new(BitOrder::Lsb, MIN_CODE_SIZE)
+            .decode(&data)
+            .expect("image data is not valid lzw");
+        assert_eq!(indices, vec![0; WIDTH * HEIGHT * 4]);
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::speed::Speed;
+
+/// An emulator control bound to a host key, as opposed to a key on the Chip8 keypad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hotkey {
+    SpeedUp,
+    SpeedDown,
+    ToggleFastForward,
+    /// Saves to the selected savestate slot.
+    QuickSave,
+    /// Loads the selected savestate slot.
+    QuickLoad,
+    NextSlot,
+    PreviousSlot,
+    /// Rewinds the machine for as long as it's held.
+    Rewind,
+    TogglePause,
+    /// Runs a single frame while paused.
+    FrameAdvance,
+    /// Opens the frontend's pause menu. The machine itself does nothing with it.
+    Menu,
+    /// Opens or closes a window showing memory, in frontends with room for more windows.
+    MemoryView,
+    /// Opens or closes a window showing the disassembly around the program counter.
+    DisassemblyView,
+    /// Opens or closes a window showing memory as sprites.
+    SpriteView,
+    /// Switches the instruction trace off or on, starting one in memory if there isn't one.
+    ToggleTrace,
+}
+
+impl Hotkey {
+    /// Whether the hotkey acts for as long as it's held, so frontends should report releasing it too.
+    pub fn is_held(self) -> bool {
+        matches!(self, Hotkey::Rewind)
+    }
+
+    /// Gets the speed that results from pressing this hotkey at the given speed, if it changes speed at all.
+    pub fn apply_to_speed(self, speed: Speed) -> Option<Speed> {
+        match self {
+            Hotkey::SpeedUp => Some(speed.faster()),
+            Hotkey::SpeedDown => Some(speed.slower()),
+            Hotkey::ToggleFastForward => Some(speed.toggle_fast_forward()),
+            Hotkey::QuickSave
+            | Hotkey::QuickLoad
+            | Hotkey::NextSlot
+            | Hotkey::PreviousSlot
+            | Hotkey::Rewind
+            | Hotkey::TogglePause
+            | Hotkey::FrameAdvance
+            | Hotkey::Menu
+            | Hotkey::MemoryView
+            | Hotkey::DisassemblyView
+            | Hotkey::SpriteView
+            | Hotkey::ToggleTrace => None,
+        }
+    }
+}
+
+/// Bindings from host key names to hotkeys.
+///
+/// Key names are whatever the frontend uses to describe its keys; the defaults use plain names like `Tab`.
+#[derive(Debug, Clone)]
+pub struct Hotkeys {
+    bindings: HashMap<String, Hotkey>,
+}
+
+impl Hotkeys {
+    pub fn new() -> Hotkeys {
+        let mut hotkeys = Hotkeys::empty();
+        hotkeys.bind("=", Hotkey::SpeedUp);
+        hotkeys.bind("-", Hotkey::SpeedDown);
+        hotkeys.bind("Tab", Hotkey::ToggleFastForward);
+        hotkeys.bind("F5", Hotkey::QuickSave);
+        hotkeys.bind("F9", Hotkey::QuickLoad);
+        hotkeys.bind("F6", Hotkey::PreviousSlot);
+        hotkeys.bind("F7", Hotkey::NextSlot);
+        hotkeys.bind("Backspace", Hotkey::Rewind);
+        hotkeys.bind("P", Hotkey::TogglePause);
+        hotkeys.bind("F10", Hotkey::FrameAdvance);
+        hotkeys.bind("Escape", Hotkey::Menu);
+        hotkeys.bind("F1", Hotkey::MemoryView);
+        hotkeys.bind("F2", Hotkey::DisassemblyView);
+        hotkeys.bind("F3", Hotkey::SpriteView);
+        hotkeys.bind("F4", Hotkey::ToggleTrace);
+        hotkeys
+    }
+
+    /// No bindings at all, for frontends that bind every hotkey themselves.
+    pub fn empty() -> Hotkeys {
+        Hotkeys {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Binds a key to a hotkey, replacing any existing binding for that key.
+    pub fn bind(&mut self, key: &str, hotkey: Hotkey) {
+        self.bindings.insert(key.to_string(), hotkey);
+    }
+    /// Looks up the hotkey bound to a key.
+    pub fn lookup(&self, key: &str) -> Option<Hotkey> {
+        self.bindings.get(key).copied()
+    }
+
+    /// Every binding, as pairs of key names and hotkeys in no particular order.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, Hotkey)> {
+        self.bindings
+            .iter()
+            .map(|(key, &hotkey)| (key.as_str(), hotkey))
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,30 @@
+pub mod assembler;
+pub mod audio;
+pub mod clock;
+pub mod config;
+pub mod deadcode;
+pub mod debugger;
+pub mod disassembler;
+pub mod display;
+pub mod events;
+pub mod explain;
+pub mod frontend;
+#[cfg(feature = "gif")]
+pub mod gif;
+pub mod hotkeys;
+pub mod lint;
+pub mod lockstep;
+pub mod machine;
+pub mod memory;
+pub mod reference;
+pub mod rng;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod shutdown;
+pub mod speed;
+pub mod system;
+pub mod tracereport;
+#[cfg(feature = "hot-reload")]
+pub mod watch;
+
+pub use chip8_core::{decoder, keypad, quirks};
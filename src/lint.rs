@@ -0,0 +1,398 @@
+//! Finding the instructions of a program that run differently from one interpreter to the next, so its author
+//! knows which it will break on.
+//!
+//! The code is found by following the program from its start, as `disassembler::follow()` does, and looked at a
+//! straight run of instructions at a time, between jumps, calls, skips, and the places they go to. What can't be
+//! worked out from a run alone, such as where a sprite is drawn from registers set elsewhere, isn't flagged.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    decoder::Instruction,
+    disassembler::follow,
+    display::{HEIGHT, WIDTH},
+    quirks::{Quirks, Variant},
+};
+
+/// The variants whose quirks are compared, with the names they're known by.
+const VARIANTS: [(Variant, &str); 3] = [
+    (Variant::Chip8, "VIP"),
+    (Variant::SuperChip, "SUPER-CHIP"),
+    (Variant::XoChip, "XO-CHIP"),
+];
+
+/// A behaviour that differs between interpreters, one of `Quirks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quirk {
+    VfReset,
+    ShiftUsesVy,
+    LoadStoreIncrementsIndex,
+    JumpUsesVx,
+    WrapSprites,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 5] = [
+        Quirk::VfReset,
+        Quirk::ShiftUsesVy,
+        Quirk::LoadStoreIncrementsIndex,
+        Quirk::JumpUsesVx,
+        Quirk::WrapSprites,
+    ];
+
+    /// The quirk's name in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Quirk::VfReset => "vf_reset",
+            Quirk::ShiftUsesVy => "shift_uses_vy",
+            Quirk::LoadStoreIncrementsIndex => "load_store_increments_index",
+            Quirk::JumpUsesVx => "jump_uses_vx",
+            Quirk::WrapSprites => "wrap_sprites",
+        }
+    }
+
+    /// Whether the quirk is set in some quirks.
+    pub fn is_set(self, quirks: Quirks) -> bool {
+        match self {
+            Quirk::VfReset => quirks.vf_reset,
+            Quirk::ShiftUsesVy => quirks.shift_uses_vy,
+            Quirk::LoadStoreIncrementsIndex => quirks.load_store_increments_index,
+            Quirk::JumpUsesVx => quirks.jump_uses_vx,
+            Quirk::WrapSprites => quirks.wrap_sprites,
+        }
+    }
+
+    /// Whether an instruction can run differently with the quirk set.
+    pub fn affects(self, instruction: Instruction) -> bool {
+        match self {
+            Quirk::VfReset => matches!(
+                instruction,
+                Instruction::Or { .. } | Instruction::And { .. } | Instruction::Xor { .. }
+            ),
+            Quirk::ShiftUsesVy => matches!(
+                instruction,
+                Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } if x != y
+            ),
+            Quirk::LoadStoreIncrementsIndex => matches!(
+                instruction,
+                Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. }
+            ),
+            Quirk::JumpUsesVx => matches!(instruction, Instruction::JumpOffset { .. }),
+            Quirk::WrapSprites => matches!(instruction, Instruction::Draw { .. }),
+        }
+    }
+
+    /// What an interpreter does with the quirk set, and without it.
+    fn behaviours(self) -> (&'static str, &'static str) {
+        match self {
+            Quirk::VfReset => ("resets VF", "leaves VF alone"),
+            Quirk::ShiftUsesVy => ("shifts VY into VX", "shifts VX in place"),
+            Quirk::LoadStoreIncrementsIndex => ("moves I past the registers", "leaves I alone"),
+            Quirk::JumpUsesVx => ("jumps to NNN plus VX", "jumps to NNN plus V0"),
+            Quirk::WrapSprites => ("wraps the sprite around", "clips the sprite"),
+        }
+    }
+}
+
+/// An instruction whose behaviour depends on a quirk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkWarning {
+    pub address: u16,
+    pub instruction: Instruction,
+    pub quirk: Quirk,
+    /// Why the quirk matters here, such as `I is used at 0x20A`.
+    pub reason: String,
+}
+
+/// Writes the warning as a line, such as `0x204  SHR V1, V2  VY isn't known to equal VX: shifts VY into VX on VIP
+/// and XO-CHIP, but shifts VX in place on SUPER-CHIP (shift_uses_vy)`.
+impl fmt::Display for QuirkWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let named = |set: bool| {
+            let names: Vec<&str> = VARIANTS
+                .iter()
+                .filter(|(variant, _)| self.quirk.is_set(variant.quirks()) == set)
+                .map(|&(_, name)| name)
+                .collect();
+            names.join(" and ")
+        };
+        let (with, without) = self.quirk.behaviours();
+        write!(
+            f,
+            "0x{:03X}  {:<16}  {}: {with} on {}, but {without} on {} ({})",
+            self.address,
+            self.instruction.to_string(),
+            self.reason,
+            named(true),
+            named(false),
+            self.quirk.name()
+        )
+    }
+}
+
+/// Looks through a program's code for the instructions that depend on quirks, lowest address first.
+pub fn lint(rom: &[u8], variant: Variant) -> Vec<QuirkWarning> {
+    let code: Vec<(u16, Instruction)> = follow(rom, variant)
+        .into_iter()
+        .filter_map(|line| Some((line.address, line.instruction?)))
+        .collect();
+    let targets: HashSet<u16> = code
+        .iter()
+        .filter_map(|&(_, instruction)| instruction.target())
+        .collect();
+    let mut warnings = Vec::new();
+    let mut start = 0;
+    while start < code.len() {
+        let mut end = start + 1;
+        while end < code.len() && continues(&code[end - 1], &code[end], &targets) {
+            end += 1;
+        }
+        lint_run(&code[start..end], &mut warnings);
+        start = end;
+    }
+    warnings
+}
+
+/// Whether `next` always runs straight after `previous`, and nothing else goes there.
+fn continues(
+    &(address, previous): &(u16, Instruction),
+    &(next_address, _): &(u16, Instruction),
+    targets: &HashSet<u16>,
+) -> bool {
+    let ends_run = matches!(
+        previous,
+        Instruction::Jump { .. }
+            | Instruction::JumpOffset { .. }
+            | Instruction::Call { .. }
+            | Instruction::Return
+            | Instruction::Exit
+            | Instruction::SkipEqualValue { .. }
+            | Instruction::SkipNotEqualValue { .. }
+            | Instruction::SkipEqual { .. }
+            | Instruction::SkipNotEqual { .. }
+            | Instruction::SkipKeyPressed { .. }
+            | Instruction::SkipKeyNotPressed { .. }
+    );
+    !ends_run
+        && next_address == address.wrapping_add(previous.size())
+        && !targets.contains(&next_address)
+}
+
+/// Looks through a straight run of instructions.
+fn lint_run(run: &[(u16, Instruction)], warnings: &mut Vec<QuirkWarning>) {
+    for (i, &(address, instruction)) in run.iter().enumerate() {
+        let (before, after) = (&run[..i], &run[i + 1..]);
+        let mut warn = |quirk: Quirk, reason: String| {
+            warnings.push(QuirkWarning {
+                address,
+                instruction,
+                quirk,
+                reason,
+            })
+        };
+        match instruction {
+            Instruction::ShiftRight { x, y } | Instruction::ShiftLeft { x, y } if x != y => {
+                // copying one into the other first makes the shift the same everywhere
+                let last_write = before
+                    .iter()
+                    .rev()
+                    .find(|(_, earlier)| writes(*earlier, x) || writes(*earlier, y));
+                let copied = matches!(
+                    last_write,
+                    Some((_, Instruction::Set { x: a, y: b })) if (*a, *b) == (x, y) || (*a, *b) == (y, x)
+                );
+                if !copied {
+                    warn(
+                        Quirk::ShiftUsesVy,
+                        format!("V{y:X} isn't known to equal V{x:X}"),
+                    );
+                }
+            }
+            Instruction::Or { .. } | Instruction::And { .. } | Instruction::Xor { .. } => {
+                let flag_read = after
+                    .iter()
+                    .take_while(|(_, later)| !writes(*later, 0xF) || reads(*later, 0xF))
+                    .find(|(_, later)| reads(*later, 0xF));
+                if let Some((read_at, _)) = flag_read {
+                    warn(Quirk::VfReset, format!("VF is read at 0x{read_at:03X}"));
+                }
+            }
+            Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. } => {
+                let index_used = after
+                    .iter()
+                    .take_while(|(_, later)| !sets_index(*later))
+                    .find(|(_, later)| uses_index(*later));
+                if let Some((used_at, _)) = index_used {
+                    warn(
+                        Quirk::LoadStoreIncrementsIndex,
+                        format!("I is used at 0x{used_at:03X}"),
+                    );
+                }
+            }
+            Instruction::JumpOffset { address } if address >> 8 != 0 => {
+                warn(Quirk::JumpUsesVx, format!("V{:X} isn't V0", address >> 8));
+            }
+            Instruction::Draw { x, y, height } => {
+                let value = |register: u8| match before
+                    .iter()
+                    .rev()
+                    .find(|(_, earlier)| writes(*earlier, register))
+                {
+                    Some((_, Instruction::SetValue { value, .. })) => Some(*value as usize),
+                    _ => None,
+                };
+                let (Some(column), Some(row)) = (value(x), value(y)) else {
+                    continue;
+                };
+                // on the low resolution screen, where a height of 0 draws SUPER-CHIP's 16 by 16 sprites
+                let (width, height) = match height {
+                    0 => (16, 16),
+                    height => (8, height as usize),
+                };
+                let (column, row) = (column % WIDTH, row % HEIGHT);
+                if column + width > WIDTH || row + height > HEIGHT {
+                    warn(
+                        Quirk::WrapSprites,
+                        format!("the sprite at {column}, {row} crosses the edge of the screen"),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether an instruction reads a V register.
+fn reads(instruction: Instruction, register: u8) -> bool {
+    use Instruction::*;
+    match instruction {
+        SkipEqualValue { x, .. }
+        | SkipNotEqualValue { x, .. }
+        | AddValue { x, .. }
+        | SkipKeyPressed { x }
+        | SkipKeyNotPressed { x }
+        | SetDelay { x }
+        | SetSound { x }
+        | AddIndex { x }
+        | FontCharacter { x }
+        | LargeFontCharacter { x }
+        | StoreBcd { x }
+        | SetPitch { x } => x == register,
+        SkipEqual { x, y }
+        | SkipNotEqual { x, y }
+        | Or { x, y }
+        | And { x, y }
+        | Xor { x, y }
+        | Add { x, y }
+        | Sub { x, y }
+        | SubReverse { x, y }
+        | ShiftRight { x, y }
+        | ShiftLeft { x, y }
+        | Draw { x, y, .. } => x == register || y == register,
+        Set { y, .. } => y == register,
+        StoreRegisters { x } | StoreFlags { x } => register <= x,
+        StoreRange { x, y } => (x.min(y)..=x.max(y)).contains(&register),
+        JumpOffset { address } => register == 0 || register == (address >> 8) as u8,
+        _ => false,
+    }
+}
+
+/// Whether an instruction writes a V register, as the flag or otherwise.
+fn writes(instruction: Instruction, register: u8) -> bool {
+    use Instruction::*;
+    match instruction {
+        SetValue { x, .. }
+        | AddValue { x, .. }
+        | Set { x, .. }
+        | Or { x, .. }
+        | And { x, .. }
+        | Xor { x, .. }
+        | Random { x, .. }
+        | GetDelay { x }
+        | WaitKey { x } => x == register,
+        Add { x, .. }
+        | Sub { x, .. }
+        | SubReverse { x, .. }
+        | ShiftRight { x, .. }
+        | ShiftLeft { x, .. } => x == register || register == 0xF,
+        Draw { .. } => register == 0xF,
+        LoadRegisters { x } | LoadFlags { x } => register <= x,
+        LoadRange { x, y } => (x.min(y)..=x.max(y)).contains(&register),
+        _ => false,
+    }
+}
+
+/// Whether an instruction points I somewhere new, whatever it pointed at before.
+fn sets_index(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::SetIndex { .. }
+            | Instruction::SetLongIndex { .. }
+            | Instruction::FontCharacter { .. }
+            | Instruction::LargeFontCharacter { .. }
+    )
+}
+
+/// Whether an instruction depends on where I points.
+fn uses_index(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Draw { .. }
+            | Instruction::AddIndex { .. }
+            | Instruction::StoreBcd { .. }
+            | Instruction::StoreRegisters { .. }
+            | Instruction::LoadRegisters { .. }
+            | Instruction::StoreRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::LoadAudio
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble;
+
+    use super::*;
+
+    #[test]
+    fn flags_instructions_that_depend_on_quirks() {
+        let rom = assemble(
+            "
+            LD V0, 0x3C
+            LD V1, 0x1E
+            LD I, 0x300
+            DRW V0, V1, 4
+            LD [I], V1
+            ADD I, V0
+            LD V2, V3
+            SHR V2, V3
+            SHR V4, V5
+            OR V6, V7
+            SE VF, 0x00
+            JP V0, 0x210
+            ",
+            Variant::Chip8,
+        )
+        .expect("failed to assemble");
+        let warnings = lint(&rom, Variant::Chip8);
+        let flagged: Vec<(u16, Quirk)> = warnings
+            .iter()
+            .map(|warning| (warning.address, warning.quirk))
+            .collect();
+        assert_eq!(
+            flagged,
+            [
+                (0x206, Quirk::WrapSprites),
+                (0x208, Quirk::LoadStoreIncrementsIndex),
+                (0x210, Quirk::ShiftUsesVy),
+                (0x212, Quirk::VfReset),
+                (0x216, Quirk::JumpUsesVx),
+            ]
+        );
+        assert_eq!(
+            warnings[2].to_string(),
+            "0x210  SHR V4, V5        V5 isn't known to equal V4: shifts VY into VX on VIP and XO-CHIP, but shifts \
+             VX in place on SUPER-CHIP (shift_uses_vy)"
+        );
+    }
+}
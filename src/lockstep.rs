@@ -0,0 +1,335 @@
+//! Running a program on two machines side by side, such as with the quirks of two interpreters, to find the first
+//! frame where they stop agreeing. When a game works in one interpreter and not another, it goes wrong there.
+//!
+//! Both machines are held to the same keys, and should be seeded the same, so only how they're set up can tell
+//! them apart.
+
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    lint::Quirk,
+    machine::{Chip8, Chip8Error, Executed},
+};
+
+/// How many of the last instructions each machine ran a divergence shows.
+pub const DIVERGENCE_HISTORY: usize = 16;
+
+/// Something two machines disagree on, with what the first and then the second has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    Screen {
+        /// How many pixels differ.
+        pixels: usize,
+    },
+    Pc([u16; 2]),
+    Index([u16; 2]),
+    Register {
+        x: u8,
+        values: [u8; 2],
+    },
+    /// The return addresses on the stacks, from the bottom up.
+    Stack([Vec<u16>; 2]),
+    DelayTimer([u8; 2]),
+    SoundTimer([u8; 2]),
+    WaitingForKey([bool; 2]),
+    Memory {
+        /// From the first address that differs to the last.
+        addresses: RangeInclusive<u16>,
+        /// How many bytes in between differ.
+        bytes: usize,
+    },
+    /// One machine couldn't run an instruction, and stopped, while the other carried on.
+    Halted {
+        /// 0 for the first machine, 1 for the second.
+        machine: usize,
+        error: Chip8Error,
+    },
+}
+
+impl Difference {
+    /// Lists what two machines disagree on, the screen first.
+    fn between([a, b]: [&Chip8; 2]) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        let (screen_a, screen_b) = (a.display().frame(), b.display().frame());
+        let pixels = screen_a
+            .pixels()
+            .iter()
+            .zip(screen_b.pixels())
+            .filter(|(a, b)| a != b)
+            .count();
+        if pixels > 0 {
+            differences.push(Difference::Screen { pixels });
+        }
+        let (cpu_a, cpu_b) = (a.cpu(), b.cpu());
+        if cpu_a.pc() != cpu_b.pc() {
+            differences.push(Difference::Pc([cpu_a.pc(), cpu_b.pc()]));
+        }
+        if cpu_a.index() != cpu_b.index() {
+            differences.push(Difference::Index([cpu_a.index(), cpu_b.index()]));
+        }
+        for (x, (&value_a, &value_b)) in cpu_a.registers().iter().zip(cpu_b.registers()).enumerate()
+        {
+            if value_a != value_b {
+                differences.push(Difference::Register {
+                    x: x as u8,
+                    values: [value_a, value_b],
+                });
+            }
+        }
+        let (stack_a, stack_b) = (cpu_a.stack().entries(), cpu_b.stack().entries());
+        if stack_a != stack_b {
+            differences.push(Difference::Stack([stack_a.to_vec(), stack_b.to_vec()]));
+        }
+        let (timers_a, timers_b) = (a.timers(), b.timers());
+        let delay = [
+            timers_a.retrieve_delay_timer(),
+            timers_b.retrieve_delay_timer(),
+        ];
+        if delay[0] != delay[1] {
+            differences.push(Difference::DelayTimer(delay));
+        }
+        let sound = [
+            timers_a.retrieve_sound_timer(),
+            timers_b.retrieve_sound_timer(),
+        ];
+        if sound[0] != sound[1] {
+            differences.push(Difference::SoundTimer(sound));
+        }
+        let waiting = [cpu_a.is_waiting_for_key(), cpu_b.is_waiting_for_key()];
+        if waiting[0] != waiting[1] {
+            differences.push(Difference::WaitingForKey(waiting));
+        }
+        let (memory_a, memory_b) = (a.memory(), b.memory());
+        let len = memory_a.len().min(memory_b.len());
+        let (bytes_a, bytes_b) = (
+            memory_a.slice(0, len).unwrap_or_default(),
+            memory_b.slice(0, len).unwrap_or_default(),
+        );
+        let differing: Vec<usize> = (0..len).filter(|&i| bytes_a[i] != bytes_b[i]).collect();
+        if let (Some(&first), Some(&last)) = (differing.first(), differing.last()) {
+            differences.push(Difference::Memory {
+                addresses: first as u16..=last as u16,
+                bytes: differing.len(),
+            });
+        }
+        differences
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Screen { pixels } => write!(f, "screen: {pixels} pixels differ"),
+            Difference::Pc([a, b]) => write!(f, "PC: 0x{a:03X} vs 0x{b:03X}"),
+            Difference::Index([a, b]) => write!(f, "I: 0x{a:03X} vs 0x{b:03X}"),
+            Difference::Register { x, values: [a, b] } => {
+                write!(f, "V{x:X}: 0x{a:02X} vs 0x{b:02X}")
+            }
+            Difference::Stack(stacks) => {
+                let [a, b] = stacks.each_ref().map(|stack| {
+                    let addresses: Vec<String> = stack
+                        .iter()
+                        .map(|address| format!("0x{address:03X}"))
+                        .collect();
+                    format!("[{}]", addresses.join(", "))
+                });
+                write!(f, "stack: {a} vs {b}")
+            }
+            Difference::DelayTimer([a, b]) => write!(f, "DT: 0x{a:02X} vs 0x{b:02X}"),
+            Difference::SoundTimer([a, b]) => write!(f, "ST: 0x{a:02X} vs 0x{b:02X}"),
+            Difference::WaitingForKey([a, _]) => {
+                let which = if *a { "first" } else { "second" };
+                write!(f, "only the {which} machine is waiting for a key")
+            }
+            Difference::Memory { addresses, bytes } => write!(
+                f,
+                "memory: {bytes} bytes differ from 0x{:03X} to 0x{:03X}",
+                addresses.start(),
+                addresses.end()
+            ),
+            Difference::Halted { machine, error } => {
+                let which = if *machine == 0 { "first" } else { "second" };
+                write!(f, "the {which} machine stopped: {error}")
+            }
+        }
+    }
+}
+
+/// The first frame two machines disagreed at the end of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameDivergence {
+    /// The frame, counting from 1.
+    pub frame: u64,
+    pub differences: Vec<Difference>,
+    /// The quirks set on one machine and not the other.
+    pub quirks: Vec<Quirk>,
+    /// The last instructions each machine ran, oldest first.
+    pub ran: [Vec<Executed>; 2],
+}
+
+/// Writes what differs, then the last instructions each machine ran side by side, marking those that run
+/// differently with the quirks that differ.
+impl fmt::Display for FrameDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "the machines diverged on frame {}", self.frame)?;
+        for difference in &self.differences {
+            writeln!(f, "  {difference}")?;
+        }
+        if !self.quirks.is_empty() {
+            let names: Vec<&str> = self.quirks.iter().map(|quirk| quirk.name()).collect();
+            writeln!(f, "quirks that differ: {}", names.join(", "))?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "the last instructions run, * where a quirk that differs matters:"
+        )?;
+        writeln!(f, "  {:<34}  second", "first")?;
+        let rows = self.ran[0].len().max(self.ran[1].len());
+        let column = |ran: &[Executed], row: usize| {
+            // the lists end together, on the last instruction each ran
+            let Some(executed) = (row + ran.len()).checked_sub(rows).and_then(|i| ran.get(i))
+            else {
+                return String::new();
+            };
+            let affected = self
+                .quirks
+                .iter()
+                .any(|quirk| quirk.affects(executed.instruction()));
+            format!("{executed}{}", if affected { " *" } else { "" })
+        };
+        for row in 0..rows {
+            let (a, b) = (column(&self.ran[0], row), column(&self.ran[1], row));
+            writeln!(f, "  {a:<34}  {b}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Two machines run a frame at a time, together.
+pub struct Lockstep {
+    machines: [Chip8; 2],
+    frame: u64,
+}
+
+impl Lockstep {
+    /// Runs two machines with the same program loaded.
+    pub fn new(first: Chip8, second: Chip8) -> Lockstep {
+        Lockstep {
+            machines: [first, second],
+            frame: 0,
+        }
+    }
+
+    pub fn machines(&self) -> [&Chip8; 2] {
+        [&self.machines[0], &self.machines[1]]
+    }
+
+    pub fn into_machines(self) -> [Chip8; 2] {
+        self.machines
+    }
+
+    /// How many frames have run.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Holds the same keys down on both machines, a bit for each key.
+    pub fn set_keys(&mut self, mask: u16) {
+        for machine in &mut self.machines {
+            machine.keypad_mut().set_mask(mask);
+        }
+    }
+
+    /// Runs a frame on both machines, then compares their screens and states. Fails only if both machines fail
+    /// alike, as then they still agree; one failing alone is a divergence.
+    pub fn run_frame(&mut self) -> Result<Option<FrameDivergence>, Chip8Error> {
+        self.frame += 1;
+        let results = [self.machines[0].run_frame(), self.machines[1].run_frame()];
+        let halted = match results {
+            [Err(a), Err(b)] if a == b => return Err(a),
+            [Err(error), _] => Some(Difference::Halted { machine: 0, error }),
+            [_, Err(error)] => Some(Difference::Halted { machine: 1, error }),
+            _ => None,
+        };
+        let [a, b] = self.machines();
+        let agree = a.display().frame().digest() == b.display().frame().digest()
+            && a.state_digest() == b.state_digest();
+        if agree && halted.is_none() {
+            return Ok(None);
+        }
+        let (quirks_a, quirks_b) = (a.cpu().quirks(), b.cpu().quirks());
+        let last = |machine: &Chip8| {
+            let history = machine.history();
+            history
+                .iter()
+                .skip(history.len().saturating_sub(DIVERGENCE_HISTORY))
+                .collect()
+        };
+        Ok(Some(FrameDivergence {
+            frame: self.frame,
+            differences: halted
+                .into_iter()
+                .chain(Difference::between([a, b]))
+                .collect(),
+            quirks: Quirk::ALL
+                .into_iter()
+                .filter(|quirk| quirk.is_set(quirks_a) != quirk.is_set(quirks_b))
+                .collect(),
+            ran: [last(a), last(b)],
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        quirks::Variant,
+    };
+
+    use super::*;
+
+    fn machine(variant: Variant, rom: &[u8]) -> Chip8 {
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .quirks(variant.quirks())
+            .instructions_per_second(60)
+            .seed(1)
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(rom).expect("failed to load rom");
+        machine
+    }
+
+    #[test]
+    fn finds_the_frame_quirks_part_ways() {
+        // V1 = 3, SHR V0, V1, jump to self: VIP shifts V1 into V0, SUPER-CHIP shifts V0 in place
+        let rom = [0x61, 0x03, 0x80, 0x16, 0x12, 0x04];
+        let mut lockstep = Lockstep::new(
+            machine(Variant::Chip8, &rom),
+            machine(Variant::SuperChip, &rom),
+        );
+        assert_eq!(lockstep.run_frame(), Ok(None));
+        let divergence = lockstep
+            .run_frame()
+            .expect("failed to run")
+            .expect("the machines didn't diverge");
+        assert_eq!(divergence.frame, 2);
+        assert_eq!(
+            divergence.differences,
+            [
+                Difference::Register {
+                    x: 0,
+                    values: [1, 0]
+                },
+                Difference::Register {
+                    x: 0xF,
+                    values: [1, 0]
+                },
+            ]
+        );
+        assert!(divergence.quirks.contains(&Quirk::ShiftUsesVy));
+        assert!(divergence.to_string().contains("0x202  8016  SHR V0, V1 *"));
+    }
+}
@@ -0,0 +1,400 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex},
+};
+
+use crate::{
+    audio::AudioSink,
+    clock::{Clock, ClockSource, TickRate},
+    display::{Display, DisplayBackend},
+    events::{Event, EventBus},
+    keypad::Keypad,
+    memory::Memory,
+    quirks::{Quirks, Variant},
+    rng::{self, Rng},
+    speed::Speed,
+    system::{Cpu, TimerEvent, Timers},
+};
+
+use super::{
+    check_rom_fits, check_variant,
+    watchdog::{self, Heartbeat},
+    Chip8, Chip8Error, Hooks, InstructionHistory, RewindBuffer, SaveSlots, Trace,
+    DEFAULT_HISTORY_LENGTH, INSTRUCTIONS_PER_SECOND,
+};
+
+/// Configures and builds a `Chip8`.
+///
+/// Anything left unset falls back to the defaults of the chosen variant, which is CHIP-8 unless told otherwise.
+#[derive(Default)]
+pub struct Chip8Builder {
+    variant: Variant,
+    quirks: Option<Quirks>,
+    instructions_per_second: Option<u32>,
+    ram_size: Option<usize>,
+    clock: Option<(Box<dyn ClockSource>, TickRate)>,
+    tick_rate: Option<TickRate>,
+    speed: Option<Speed>,
+    display_backend: Option<Box<dyn DisplayBackend>>,
+    audio_sink: Option<Box<dyn AudioSink>>,
+    hooks: Option<Box<dyn Hooks>>,
+    watchdog: Option<u64>,
+    seed: Option<u64>,
+    save_directory: Option<PathBuf>,
+    autosave: bool,
+    rewind: Option<(usize, u64)>,
+    trace: Option<Trace>,
+    history_length: Option<usize>,
+    stop_flag: Option<Arc<AtomicBool>>,
+}
+
+impl Chip8Builder {
+    pub fn new() -> Chip8Builder {
+        Chip8Builder::default()
+    }
+
+    /// Chooses the machine to emulate, which decides the default quirks and RAM size. Only CHIP-8 machines can be
+    /// built so far: neither the SUPER-CHIP and XO-CHIP instructions nor their bigger screen are emulated yet.
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Overrides the variant's quirks.
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// Sets how many instructions run per second. Defaults to `INSTRUCTIONS_PER_SECOND`.
+    pub fn instructions_per_second(mut self, instructions_per_second: u32) -> Self {
+        self.instructions_per_second = Some(instructions_per_second);
+        self
+    }
+
+    /// Overrides the variant's amount of RAM, in bytes.
+    pub fn ram_size(mut self, ram_size: usize) -> Self {
+        self.ram_size = Some(ram_size);
+        self
+    }
+
+    /// Drives the machine from any clock, which should tick at `rate`. Defaults to a 60hz clock thread.
+    pub fn clock(mut self, clock: Box<dyn ClockSource>, rate: TickRate) -> Self {
+        self.clock = Some((clock, rate));
+        self
+    }
+
+    /// Sets the rate of the default clock thread. Ignored if a clock is given with `clock()`.
+    pub fn tick_rate(mut self, rate: TickRate) -> Self {
+        self.tick_rate = Some(rate);
+        self
+    }
+
+    /// Starts the machine at a speed other than normal.
+    pub fn speed(mut self, speed: Speed) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Shows the display on a backend once per frame.
+    pub fn display_backend(mut self, backend: Box<dyn DisplayBackend>) -> Self {
+        self.display_backend = Some(backend);
+        self
+    }
+
+    /// Plays the tone on a sink while the sound timer is running.
+    pub fn audio_sink(mut self, sink: Box<dyn AudioSink>) -> Self {
+        self.audio_sink = Some(sink);
+        self
+    }
+
+    /// Calls an embedder's hooks as the machine runs.
+    pub fn hooks(mut self, hooks: Box<dyn Hooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Watches for the machine falling `ticks` ticks behind its clock while running, such as when its thread is
+    /// stuck, and publishes `Event::Watchdog` when it does.
+    pub fn watchdog(mut self, ticks: u64) -> Self {
+        self.watchdog = Some(ticks);
+        self
+    }
+
+    /// Seeds the random number generator, so runs can be repeated. Defaults to a seed from the system time.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Keeps numbered savestate slots under `directory`, with a directory per program.
+    pub fn save_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.save_directory = Some(directory.into());
+        self
+    }
+
+    /// Writes a savestate of the loaded program when the machine shuts down, which it offers to resume the next time
+    /// the program is loaded. Needs a save directory.
+    pub fn autosave(mut self, autosave: bool) -> Self {
+        self.autosave = autosave;
+        self
+    }
+
+    /// Keeps the last `depth` states, taken every `interval` frames, so the machine can rewind.
+    ///
+    /// A CHIP-8 state is a little over 4KB, so a depth of 3600 taken every 5 frames reaches back five minutes in
+    /// about 15MB. With the `compression` feature, states after the newest take a few hundred bytes at most.
+    pub fn rewind(mut self, depth: usize, interval: u64) -> Self {
+        self.rewind = Some((depth, interval));
+        self
+    }
+
+    /// Logs every instruction the machine runs, from the first.
+    pub fn trace(mut self, trace: Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Remembers the last `length` instructions run, rather than `DEFAULT_HISTORY_LENGTH`. A length of 0 remembers
+    /// none.
+    pub fn history_length(mut self, length: usize) -> Self {
+        self.history_length = Some(length);
+        self
+    }
+
+    /// Stops `run()` when `flag` is set, rather than a flag of the machine's own, so one flag can stop whichever of
+    /// a series of machines is running.
+    pub fn stop_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.stop_flag = Some(flag);
+        self
+    }
+
+    /// Fails if a program is too big for the memory of the machine this would build, so it can be turned away
+    /// before there's a machine to shut down.
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        check_rom_fits(rom, self.ram_size.unwrap_or(self.variant.ram_size()))
+    }
+
+    /// Checks the configuration and builds the machine.
+    pub fn build(self) -> Result<Chip8, Chip8Error> {
+        check_variant(self.variant).map_err(Chip8Error::Config)?;
+        let instructions_per_second = self
+            .instructions_per_second
+            .unwrap_or(INSTRUCTIONS_PER_SECOND);
+        if instructions_per_second == 0 {
+            return Err(Chip8Error::Config(
+                "instructions per second must be nonzero",
+            ));
+        }
+        let memory = Memory::with_size(self.ram_size.unwrap_or(self.variant.ram_size()))
+            .map_err(Chip8Error::Config)?;
+        let (mut clock, rate) = self.clock.unwrap_or_else(|| {
+            let rate = self.tick_rate.unwrap_or(TickRate::NTSC);
+            (Box::new(Clock::with_rate(rate)), rate)
+        });
+        let speed = self.speed.unwrap_or(Speed::NORMAL);
+        clock.set_speed(speed);
+        // the machine starts silent, with nothing for the hooks to hear about
+        let timers = Timers::new();
+        timers.set_delay_timer(0);
+        timers.set_sound_timer(0);
+        timers.attach(clock.as_mut()).map_err(Chip8Error::Clock)?;
+        let audio_sink = self.audio_sink.map(|sink| Arc::new(Mutex::new(sink)));
+        if let Some(sink) = &audio_sink {
+            let sink = Arc::clone(sink);
+            timers.on_change(Box::new(move |event| match event {
+                TimerEvent::SoundSet(value) => sink.lock().unwrap().set_tone(value > 0),
+                TimerEvent::SoundExpired => sink.lock().unwrap().set_tone(false),
+                TimerEvent::DelaySet(_) | TimerEvent::DelayExpired => {}
+            }));
+        }
+        let hooks = Arc::new(Mutex::new(self.hooks));
+        let events = Arc::new(EventBus::new());
+        let timer_hooks = Arc::clone(&hooks);
+        let timer_events = Arc::clone(&events);
+        let mut sounding = false;
+        timers.on_change(Box::new(move |event| {
+            timer_events.publish(Event::Timer(event));
+            let now_sounding = match event {
+                TimerEvent::SoundSet(value) => value > 0,
+                TimerEvent::SoundExpired => false,
+                TimerEvent::DelaySet(_) | TimerEvent::DelayExpired => sounding,
+            };
+            if now_sounding == sounding {
+                return;
+            }
+            sounding = now_sounding;
+            timer_events.publish(Event::SoundEdge(sounding));
+            if let Some(hooks) = timer_hooks.lock().unwrap().as_mut() {
+                if sounding {
+                    hooks.on_sound_start();
+                } else {
+                    hooks.on_sound_stop();
+                }
+            }
+        }));
+        let heartbeat = match self.watchdog {
+            Some(0) => return Err(Chip8Error::Config("watchdog limit must be nonzero")),
+            Some(limit) => {
+                let heartbeat = Arc::new(Heartbeat::default());
+                clock
+                    .on_tick(watchdog::handler(
+                        Arc::clone(&heartbeat),
+                        limit,
+                        Arc::clone(&events),
+                    ))
+                    .map_err(Chip8Error::Clock)?;
+                Some(heartbeat)
+            }
+            None => None,
+        };
+        if self.autosave && self.save_directory.is_none() {
+            return Err(Chip8Error::Config("autosave needs a save directory"));
+        }
+        let rewind = match self.rewind {
+            Some((0, _)) => return Err(Chip8Error::Config("rewind depth must be nonzero")),
+            Some((_, 0)) => return Err(Chip8Error::Config("rewind interval must be nonzero")),
+            Some((depth, interval)) => Some(RewindBuffer::new(depth, interval)),
+            None => None,
+        };
+        let (command_tx, commands) = mpsc::channel();
+        let ticks = clock
+            .become_listener("machine")
+            .map_err(Chip8Error::Clock)?;
+        Ok(Chip8 {
+            variant: self.variant,
+            instructions_per_second,
+            cpu: Cpu::with_quirks(self.quirks.unwrap_or(self.variant.quirks())),
+            memory,
+            rom: Vec::new(),
+            display: Display::new(),
+            keypad: Keypad::new(),
+            timers,
+            clock,
+            display_backend: self.display_backend,
+            audio_sink,
+            hooks,
+            events,
+            commands,
+            command_tx,
+            speed,
+            heartbeat,
+            ticks,
+            rate,
+            rng: self.seed.map(Rng::new).unwrap_or_else(rng::from_time),
+            seed: self.seed,
+            slots: self.save_directory.map(SaveSlots::new),
+            autosave: self.autosave,
+            rewind,
+            rewinding: false,
+            frames: 0,
+            recording: None,
+            playback: None,
+            trace: self.trace,
+            profiler: None,
+            coverage: None,
+            timeline: None,
+            state_log: None,
+            history: InstructionHistory::new(self.history_length.unwrap_or(DEFAULT_HISTORY_LENGTH)),
+            register_writes: Vec::new(),
+            memory_accesses: Vec::new(),
+            started: false,
+            running: false,
+            paused: false,
+            stopped: false,
+            stop_flag: self
+                .stop_flag
+                .unwrap_or_else(|| Arc::new(AtomicBool::new(false))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::ManualClock;
+
+    use super::*;
+
+    fn manual_builder() -> Chip8Builder {
+        Chip8Builder::new().clock(Box::new(ManualClock::new()), TickRate::NTSC)
+    }
+
+    #[test]
+    fn variant_defaults() {
+        let machine = manual_builder().build().expect("failed to build machine");
+        assert_eq!(machine.memory().len(), Variant::Chip8.ram_size());
+        assert_eq!(machine.cpu().quirks(), Variant::Chip8.quirks());
+        let quirks = Quirks {
+            wrap_sprites: true,
+            ..Quirks::default()
+        };
+        let machine = manual_builder()
+            .quirks(quirks)
+            .build()
+            .expect("failed to build machine");
+        assert_eq!(machine.cpu().quirks(), quirks);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        assert!(manual_builder().ram_size(0x100).build().is_err());
+        assert!(manual_builder().instructions_per_second(0).build().is_err());
+        for variant in [Variant::SuperChip, Variant::XoChip] {
+            assert!(matches!(
+                manual_builder().variant(variant).build(),
+                Err(Chip8Error::Config(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn seeded_machines_repeat() {
+        // V0 = random, V1 = random
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF];
+        let registers = || {
+            let mut machine = manual_builder()
+                .seed(7)
+                .build()
+                .expect("failed to build machine");
+            machine.load_rom(&rom).expect("failed to load rom");
+            machine.step().expect("instruction failed");
+            machine.step().expect("instruction failed");
+            machine.cpu().registers()[..2].to_vec()
+        };
+        assert_eq!(registers(), registers());
+    }
+
+    struct RecordingSink(Arc<Mutex<Vec<bool>>>);
+
+    impl AudioSink for RecordingSink {
+        fn set_tone(&mut self, on: bool) {
+            self.0.lock().unwrap().push(on);
+        }
+    }
+
+    #[test]
+    fn audio_sink_follows_sound_timer() {
+        let tones = Arc::new(Mutex::new(Vec::new()));
+        let machine = manual_builder()
+            .audio_sink(Box::new(RecordingSink(Arc::clone(&tones))))
+            .build()
+            .expect("failed to build machine");
+        machine.timers().set_sound_timer(2);
+        machine.timers().advance(2);
+        assert_eq!(*tones.lock().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn pausing_silences_the_tone() {
+        let tones = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = manual_builder()
+            .audio_sink(Box::new(RecordingSink(Arc::clone(&tones))))
+            .build()
+            .expect("failed to build machine");
+        machine.timers().set_sound_timer(2);
+        machine.pause();
+        machine.resume();
+        machine.stop().expect("failed to stop machine");
+        assert_eq!(*tones.lock().unwrap(), vec![true, false, true, false]);
+    }
+}
@@ -0,0 +1,42 @@
+//! Compression for savestates, when the `compression` feature is enabled. Without it, bytes are stored as they are.
+
+use super::Chip8Error;
+
+/// Whether this build compresses what it stores.
+pub const ENABLED: bool = cfg!(feature = "compression");
+
+#[cfg(feature = "compression")]
+pub fn compress(bytes: Vec<u8>) -> Vec<u8> {
+    lz4_flex::compress_prepend_size(&bytes)
+}
+
+#[cfg(not(feature = "compression"))]
+pub fn compress(bytes: Vec<u8>) -> Vec<u8> {
+    bytes
+}
+
+#[cfg(feature = "compression")]
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Chip8Error> {
+    lz4_flex::decompress_size_prepended(bytes)
+        .map_err(|_| Chip8Error::State("savestate is corrupt"))
+}
+
+#[cfg(not(feature = "compression"))]
+pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Chip8Error> {
+    Ok(bytes.to_vec())
+}
+
+/// Gives what turns `newer` back into `older` when passed to `undelta()`. States a few frames apart barely differ,
+/// so this is mostly zeroes, which compress to almost nothing.
+pub fn delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    older
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| byte ^ newer.get(i).copied().unwrap_or(0))
+        .collect()
+}
+
+/// Recovers the older bytes from a `delta()` and the newer bytes it was taken against.
+pub fn undelta(delta: &[u8], newer: &[u8]) -> Vec<u8> {
+    self::delta(delta, newer)
+}
@@ -0,0 +1,180 @@
+use std::{
+    fmt,
+    ops::{Range, RangeInclusive},
+};
+
+use crate::system::{AccessKind, MemoryAccess};
+
+/// What's been done with a byte of memory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Touched {
+    /// The byte was part of an instruction that ran.
+    pub executed: bool,
+    /// An instruction read the byte as data, as a sprite or into the registers.
+    pub read: bool,
+    pub written: bool,
+}
+
+impl Touched {
+    pub fn is_untouched(self) -> bool {
+        self == Touched::default()
+    }
+}
+
+/// Writes what was done as words, such as `executed read`, or `untouched`.
+impl fmt::Display for Touched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words: Vec<&str> = [
+            (self.executed, "executed"),
+            (self.read, "read"),
+            (self.written, "written"),
+        ]
+        .into_iter()
+        .filter_map(|(done, word)| done.then_some(word))
+        .collect();
+        match words[..] {
+            [] => write!(f, "untouched"),
+            _ => write!(f, "{}", words.join(" ")),
+        }
+    }
+}
+
+/// Which bytes of memory a machine has ever run as instructions, read as data, or written, for telling a program's
+/// code from its data, or finding the branches its tests never took.
+///
+/// Every instruction and access is counted, including those of a program waiting on a key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    /// What's been done with each byte, as far as the highest address touched.
+    bytes: Vec<Touched>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// Counts the `len` bytes of an instruction that ran at `pc`.
+    pub(crate) fn record_execution(&mut self, pc: u16, len: u16) {
+        for offset in 0..len {
+            self.byte(pc.wrapping_add(offset)).executed = true;
+        }
+    }
+
+    pub(super) fn record_access(&mut self, access: MemoryAccess) {
+        let byte = self.byte(access.address);
+        match access.kind {
+            AccessKind::Read => byte.read = true,
+            AccessKind::Write => byte.written = true,
+        }
+    }
+
+    fn byte(&mut self, address: u16) -> &mut Touched {
+        let address = address as usize;
+        if address >= self.bytes.len() {
+            self.bytes.resize(address + 1, Touched::default());
+        }
+        &mut self.bytes[address]
+    }
+
+    /// What's been done with the byte at an address.
+    pub fn get(&self, address: u16) -> Touched {
+        self.bytes
+            .get(address as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Everything that's been done with any of the bytes in a range of addresses, as when showing many bytes as
+    /// one.
+    pub fn any(&self, addresses: Range<usize>) -> Touched {
+        let end = addresses.end.min(self.bytes.len());
+        let start = addresses.start.min(end);
+        self.bytes[start..end]
+            .iter()
+            .fold(Touched::default(), |all, byte| Touched {
+                executed: all.executed || byte.executed,
+                read: all.read || byte.read,
+                written: all.written || byte.written,
+            })
+    }
+
+    /// The runs of addresses touched alike, lowest first, leaving out those never touched.
+    pub fn ranges(&self) -> impl Iterator<Item = (RangeInclusive<u16>, Touched)> + '_ {
+        let mut start = 0;
+        self.bytes
+            .chunk_by(|a, b| a == b)
+            .map(move |run| {
+                let range = start as u16..=(start + run.len() - 1) as u16;
+                start += run.len();
+                (range, run[0])
+            })
+            .filter(|(_, touched)| !touched.is_untouched())
+    }
+
+    /// How many bytes have been run as instructions.
+    pub fn executed(&self) -> usize {
+        self.bytes.iter().filter(|byte| byte.executed).count()
+    }
+
+    /// How many bytes have been read as data.
+    pub fn read(&self) -> usize {
+        self.bytes.iter().filter(|byte| byte.read).count()
+    }
+
+    /// How many bytes have been written.
+    pub fn written(&self) -> usize {
+        self.bytes.iter().filter(|byte| byte.written).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+}
+
+/// Writes the coverage as a map, a line to each run of addresses touched alike, such as `0x200-0x2A3  executed`.
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (range, touched) in self.ranges() {
+            writeln!(
+                f,
+                "0x{:03X}-0x{:03X}  {touched}",
+                range.start(),
+                range.end()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_runs_of_bytes_touched_alike() {
+        let mut coverage = Coverage::new();
+        coverage.record_execution(0x200, 2);
+        coverage.record_execution(0x202, 2);
+        for address in [0x206, 0x207] {
+            coverage.record_access(MemoryAccess {
+                address,
+                value: 0xF0,
+                kind: AccessKind::Read,
+            });
+        }
+        coverage.record_access(MemoryAccess {
+            address: 0x207,
+            value: 0,
+            kind: AccessKind::Write,
+        });
+
+        assert_eq!(coverage.executed(), 4);
+        assert!(coverage.get(0x206).read);
+        assert!(coverage.get(0x300).is_untouched());
+        assert_eq!(
+            coverage.to_string(),
+            "0x200-0x203  executed\n0x206-0x206  read\n0x207-0x207  read written\n"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+
+use crate::{
+    debugger::{call_stack, CallFrame, Symbols},
+    disassembler::{sweep, Line},
+    system::CpuError,
+};
+
+use super::{Chip8, Executed};
+
+/// How many of the last instructions run a crash report shows.
+pub const CRASH_HISTORY: usize = 32;
+
+/// How many bytes of memory either side of the failed instruction a crash report disassembles.
+const CRASH_CONTEXT: u16 = 8;
+
+/// What a machine was doing when its CPU couldn't run an instruction, for working out how a program got there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    pub error: CpuError,
+    /// The address of the instruction that failed.
+    pub pc: u16,
+    /// How many frames had finished.
+    pub frame: u64,
+    pub registers: [u8; 16],
+    pub index: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    /// The calls the program was inside, innermost first.
+    pub call_stack: Vec<CallFrame>,
+    /// Memory around the failed instruction as it is now, decoded from a few instructions before it.
+    pub disassembly: Vec<Line>,
+    /// The last instructions run, oldest first, ending with the one that failed. Fewer are kept if the machine was
+    /// built to remember fewer.
+    pub history: Vec<Executed>,
+}
+
+impl CrashReport {
+    /// Reports on a machine just after `error`, before anything else has changed it.
+    pub fn new(machine: &Chip8, error: CpuError) -> CrashReport {
+        let pc = match error {
+            CpuError::UnknownOpcode { pc, .. }
+            | CpuError::StackOverflow { pc }
+            | CpuError::StackUnderflow { pc }
+            | CpuError::MemoryOutOfBounds { pc, .. } => pc,
+        };
+        let memory = machine.memory();
+        let start = pc.saturating_sub(CRASH_CONTEXT);
+        let end = (pc as usize + CRASH_CONTEXT as usize + 2).min(memory.len());
+        let disassembly = match memory.slice(start, end.saturating_sub(start as usize)) {
+            Ok(bytes) => sweep(bytes, start, machine.variant()),
+            Err(_) => Vec::new(),
+        };
+        let history = machine.history();
+        let cpu = machine.cpu();
+        CrashReport {
+            error,
+            pc,
+            frame: machine.frame_count(),
+            registers: *cpu.registers(),
+            index: cpu.index(),
+            delay_timer: machine.timers().retrieve_delay_timer(),
+            sound_timer: machine.timers().retrieve_sound_timer(),
+            call_stack: call_stack(cpu, memory, &Symbols::new()),
+            disassembly,
+            history: history
+                .iter()
+                .skip(history.len().saturating_sub(CRASH_HISTORY))
+                .collect(),
+        }
+    }
+}
+
+/// Writes the report for people to read: the error, the registers, the call stack, the disassembly with the failed
+/// instruction marked, and the last instructions run.
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} on frame {}", self.error, self.frame)?;
+        writeln!(f)?;
+        writeln!(f, "registers:")?;
+        for (row, values) in self.registers.chunks(8).enumerate() {
+            let values: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(x, value)| format!("V{:X} {value:02X}", row * 8 + x))
+                .collect();
+            writeln!(f, "  {}", values.join("  "))?;
+        }
+        writeln!(
+            f,
+            "  I {:03X}  DT {:02X}  ST {:02X}",
+            self.index, self.delay_timer, self.sound_timer
+        )?;
+        writeln!(f)?;
+        writeln!(f, "call stack:")?;
+        if self.call_stack.is_empty() {
+            writeln!(f, "  (not in a subroutine)")?;
+        }
+        for frame in &self.call_stack {
+            write!(f, "  returns to 0x{:03X}", frame.return_address)?;
+            if let Some(routine) = frame.routine {
+                write!(f, ", in 0x{routine:03X}")?;
+            }
+            if let Some(call_site) = frame.call_site {
+                write!(f, ", called from 0x{call_site:03X}")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "disassembly:")?;
+        for line in &self.disassembly {
+            let marker = if line.address == self.pc { ">" } else { " " };
+            writeln!(f, "{marker} {line}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "the last {} instructions run:", self.history.len())?;
+        for executed in &self.history {
+            writeln!(f, "  {executed}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    decoder,
+    display::{HEIGHT, WIDTH},
+    keypad::KEY_COUNT,
+    quirks::{Quirks, Variant},
+};
+
+use super::SaveState;
+
+/// The text formats a `StateDump` can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Json,
+    Toml,
+}
+
+/// A savestate laid out for people to read rather than for loading, for debugging and teaching.
+///
+/// Registers are named V0 through VF, addresses are written in hex, the instruction at the program counter is
+/// disassembled, and the screen is drawn with `#` for lit pixels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StateDump {
+    pub variant: Variant,
+    pub pc: String,
+    pub opcode: String,
+    /// The instruction at the program counter, or why there isn't one.
+    pub instruction: String,
+    pub index: String,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub waiting_for_key: bool,
+    /// Return addresses, from the bottom of the stack up.
+    pub stack: Vec<String>,
+    pub keys_pressed: Vec<String>,
+    pub screen: Vec<String>,
+    pub registers: BTreeMap<String, u8>,
+    pub quirks: Quirks,
+}
+
+impl StateDump {
+    pub fn new(state: &SaveState) -> StateDump {
+        let cpu = &state.cpu;
+        let (opcode, instruction) = match state.memory.read_opcode(cpu.pc()) {
+            Ok(opcode) => (
+                format!("0x{opcode:04X}"),
+                decoder::decode(opcode).to_string(),
+            ),
+            Err(_) => (String::new(), "out of bounds".to_string()),
+        };
+        StateDump {
+            variant: state.variant,
+            pc: format!("0x{:03X}", cpu.pc()),
+            opcode,
+            instruction,
+            index: format!("0x{:03X}", cpu.index()),
+            delay_timer: state.timers.delay,
+            sound_timer: state.timers.sound,
+            waiting_for_key: cpu.is_waiting_for_key(),
+            stack: cpu
+                .stack()
+                .entries()
+                .iter()
+                .map(|address| format!("0x{address:03X}"))
+                .collect(),
+            keys_pressed: (0..KEY_COUNT as u8)
+                .filter(|&key| state.keypad.is_pressed(key))
+                .map(|key| format!("{key:X}"))
+                .collect(),
+            screen: (0..HEIGHT)
+                .map(|y| {
+                    (0..WIDTH)
+                        .map(|x| {
+                            if state.frame.get_pixel(x, y) {
+                                '#'
+                            } else {
+                                '.'
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+            registers: cpu
+                .registers()
+                .iter()
+                .enumerate()
+                .map(|(i, &value)| (format!("V{i:X}"), value))
+                .collect(),
+            quirks: cpu.quirks(),
+        }
+    }
+
+    pub fn render(&self, format: DumpFormat) -> String {
+        match format {
+            DumpFormat::Json => {
+                serde_json::to_string_pretty(self).expect("state dumps are always serializable")
+            }
+            DumpFormat::Toml => {
+                toml::to_string_pretty(self).expect("state dumps are always serializable")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Chip8,
+    };
+
+    use super::*;
+
+    #[test]
+    fn dumps_are_readable() {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        // VA = 5, I = font for the digit in VA, draw it at (VA, VA)
+        machine
+            .load_rom(&[0x6A, 0x05, 0xFA, 0x29, 0xDA, 0xA5])
+            .expect("failed to load rom");
+        machine.step_n(2).expect("steps failed");
+        let dump = machine.dump_state();
+        assert_eq!(dump.pc, "0x204");
+        assert_eq!(dump.instruction, "DRW VA, VA, 5");
+        assert_eq!(dump.registers["VA"], 5);
+        machine.step().expect("step failed");
+        let dump = machine.dump_state();
+        assert_eq!(&dump.screen[5][..9], ".....####");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&dump.render(DumpFormat::Json)).expect("json is invalid");
+        assert_eq!(json["registers"]["VA"], 5);
+        let toml: toml::Value =
+            toml::from_str(&dump.render(DumpFormat::Toml)).expect("toml is invalid");
+        assert_eq!(toml["registers"]["VA"].as_integer(), Some(5));
+        assert_eq!(toml["index"].as_str(), Some("0x069"));
+    }
+}
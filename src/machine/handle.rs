@@ -0,0 +1,161 @@
+use std::sync::{
+    mpsc::{Receiver, Sender},
+    Arc,
+};
+
+use crate::{
+    events::{Event, EventBus},
+    hotkeys::Hotkey,
+    speed::Speed,
+};
+
+use super::{Chip8Error, Movie, SaveState};
+
+/// An instruction for a machine, sent from another thread through a `MachineHandle`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    LoadRom(Vec<u8>),
+    Pause,
+    Resume,
+    Reset,
+    Stop,
+    SetSpeed(Speed),
+    PressKey(u8),
+    ReleaseKey(u8),
+    /// Captures the machine's state, which is published as `Event::StateSaved`.
+    SaveState,
+    LoadState(Box<SaveState>),
+    SaveSlot(u8),
+    LoadSlot(u8),
+    /// Carries out a hotkey, such as changing speed or quicksaving.
+    Hotkey(Hotkey),
+    /// Ends a hotkey that acts while held, such as rewinding.
+    ReleaseHotkey(Hotkey),
+    /// Goes back at least this many frames.
+    Rewind(u64),
+    StartRecording,
+    /// Stops recording, publishing the movie as `Event::MovieRecorded`.
+    StopRecording,
+    PlayMovie(Box<Movie>),
+    /// Runs a single frame while paused.
+    FrameAdvance,
+    ResumeAutosave,
+    DiscardAutosave,
+    /// Switches the trace off or on, as `Chip8::toggle_trace()` does.
+    ToggleTrace,
+}
+
+/// Controls a machine running on another thread, without needing access to it.
+///
+/// Commands are queued and carried out by the machine between frames. A handle can be cloned freely, and outlives
+/// the machine; sending to a machine that's gone gives `Chip8Error::Stopped`.
+#[derive(Debug, Clone)]
+pub struct MachineHandle {
+    commands: Sender<Command>,
+    events: Arc<EventBus>,
+}
+
+impl MachineHandle {
+    pub(super) fn new(commands: Sender<Command>, events: Arc<EventBus>) -> MachineHandle {
+        MachineHandle { commands, events }
+    }
+
+    pub fn send(&self, command: Command) -> Result<(), Chip8Error> {
+        self.commands.send(command).map_err(|_| Chip8Error::Stopped)
+    }
+
+    /// Subscribes to the machine's events. Drop the receiver to unsubscribe.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    pub fn load_rom(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.send(Command::LoadRom(rom.to_vec()))
+    }
+
+    pub fn pause(&self) -> Result<(), Chip8Error> {
+        self.send(Command::Pause)
+    }
+
+    pub fn resume(&self) -> Result<(), Chip8Error> {
+        self.send(Command::Resume)
+    }
+
+    pub fn reset(&self) -> Result<(), Chip8Error> {
+        self.send(Command::Reset)
+    }
+
+    pub fn stop(&self) -> Result<(), Chip8Error> {
+        self.send(Command::Stop)
+    }
+
+    pub fn set_speed(&self, speed: Speed) -> Result<(), Chip8Error> {
+        self.send(Command::SetSpeed(speed))
+    }
+
+    pub fn press_key(&self, key: u8) -> Result<(), Chip8Error> {
+        self.send(Command::PressKey(key))
+    }
+
+    pub fn release_key(&self, key: u8) -> Result<(), Chip8Error> {
+        self.send(Command::ReleaseKey(key))
+    }
+
+    /// Asks the machine to save its state. Subscribe first to receive it as `Event::StateSaved`.
+    pub fn save_state(&self) -> Result<(), Chip8Error> {
+        self.send(Command::SaveState)
+    }
+
+    pub fn load_state(&self, state: SaveState) -> Result<(), Chip8Error> {
+        self.send(Command::LoadState(Box::new(state)))
+    }
+
+    pub fn save_slot(&self, slot: u8) -> Result<(), Chip8Error> {
+        self.send(Command::SaveSlot(slot))
+    }
+
+    pub fn load_slot(&self, slot: u8) -> Result<(), Chip8Error> {
+        self.send(Command::LoadSlot(slot))
+    }
+
+    pub fn hotkey(&self, hotkey: Hotkey) -> Result<(), Chip8Error> {
+        self.send(Command::Hotkey(hotkey))
+    }
+
+    pub fn release_hotkey(&self, hotkey: Hotkey) -> Result<(), Chip8Error> {
+        self.send(Command::ReleaseHotkey(hotkey))
+    }
+
+    pub fn rewind(&self, frames: u64) -> Result<(), Chip8Error> {
+        self.send(Command::Rewind(frames))
+    }
+
+    pub fn start_recording(&self) -> Result<(), Chip8Error> {
+        self.send(Command::StartRecording)
+    }
+
+    /// Asks the machine to stop recording. Subscribe first to receive the movie as `Event::MovieRecorded`.
+    pub fn stop_recording(&self) -> Result<(), Chip8Error> {
+        self.send(Command::StopRecording)
+    }
+
+    pub fn play_movie(&self, movie: Movie) -> Result<(), Chip8Error> {
+        self.send(Command::PlayMovie(Box::new(movie)))
+    }
+
+    pub fn frame_advance(&self) -> Result<(), Chip8Error> {
+        self.send(Command::FrameAdvance)
+    }
+
+    pub fn resume_autosave(&self) -> Result<(), Chip8Error> {
+        self.send(Command::ResumeAutosave)
+    }
+
+    pub fn discard_autosave(&self) -> Result<(), Chip8Error> {
+        self.send(Command::DiscardAutosave)
+    }
+
+    pub fn toggle_trace(&self) -> Result<(), Chip8Error> {
+        self.send(Command::ToggleTrace)
+    }
+}
@@ -0,0 +1,94 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::decoder::{decode, Instruction};
+
+/// How many instructions a machine remembers running, unless built to remember another number.
+pub const DEFAULT_HISTORY_LENGTH: usize = 64;
+
+/// An instruction a machine ran, and where.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Executed {
+    pub pc: u16,
+    /// The first two bytes of the instruction.
+    pub opcode: u16,
+}
+
+impl Executed {
+    pub fn instruction(&self) -> Instruction {
+        decode(self.opcode)
+    }
+}
+
+/// Writes the instruction as `0x204  1234  JP 0x234`.
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:03X}  {:04X}  {}",
+            self.pc,
+            self.opcode,
+            self.instruction()
+        )
+    }
+}
+
+/// The last instructions a machine ran, for working out how it got where it is, such as when a program jumps into
+/// data. Unlike a `Trace`, it's always kept, and only as much as it takes to answer that.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionHistory {
+    entries: VecDeque<Executed>,
+    length: usize,
+}
+
+impl InstructionHistory {
+    /// Remembers the last `length` instructions. A length of 0 remembers nothing.
+    pub fn new(length: usize) -> InstructionHistory {
+        InstructionHistory {
+            entries: VecDeque::with_capacity(length),
+            length,
+        }
+    }
+
+    pub(super) fn record(&mut self, pc: u16, opcode: u16) {
+        if self.length == 0 {
+            return;
+        }
+        if self.entries.len() == self.length {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Executed { pc, opcode });
+    }
+
+    /// The instructions remembered, the oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Executed> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// The instruction run last.
+    pub fn last(&self) -> Option<Executed> {
+        self.entries.back().copied()
+    }
+
+    /// How many instructions are remembered at most.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for InstructionHistory {
+    fn default() -> Self {
+        InstructionHistory::new(DEFAULT_HISTORY_LENGTH)
+    }
+}
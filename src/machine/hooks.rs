@@ -0,0 +1,33 @@
+use crate::{display::Frame, system::CpuError};
+
+/// Why the machine halted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The CPU couldn't execute an instruction.
+    Cpu(CpuError),
+    /// The machine was stopped for good.
+    Stopped,
+    /// The clock's thread panicked, so the machine stopped.
+    ClockPanicked,
+}
+
+/// Callbacks for embedders to hear about what the machine is doing, without polling it.
+///
+/// Every method does nothing by default, so implement only what you need. The sound methods run on whichever thread
+/// counts the timers down, usually the clock's; the rest run on the thread driving the machine.
+pub trait Hooks: Send {
+    /// A frame has finished, and the display changed since the last one.
+    fn on_draw(&mut self, _frame: &Frame) {}
+
+    /// The sound timer was set, starting the tone.
+    fn on_sound_start(&mut self) {}
+
+    /// The sound timer ran out or was cleared, stopping the tone.
+    fn on_sound_stop(&mut self) {}
+
+    /// The machine halted, either on an error or by being stopped.
+    fn on_halt(&mut self, _reason: HaltReason) {}
+
+    /// The CPU started waiting on a keypress.
+    fn on_key_wait(&mut self) {}
+}
@@ -0,0 +1,2257 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    audio::AudioSink,
+    clock::{ClockSource, TickRate, TickReceiver},
+    decoder::{decode, decode_for, Instruction},
+    display::{Display, DisplayBackend, Frame},
+    events::{Event, EventBus},
+    explain::{explain, Operands},
+    hotkeys::Hotkey,
+    keypad::Keypad,
+    memory::{Memory, PROGRAM_START},
+    quirks::Variant,
+    rng::Rng,
+    shutdown::Shutdown,
+    speed::Speed,
+    system::{Bus, Cpu, CpuError, MemoryAccess, Register, Timers},
+};
+
+mod builder;
+mod compress;
+mod coverage;
+mod crash;
+mod dump;
+mod handle;
+mod history;
+mod hooks;
+mod movie;
+mod profile;
+mod rewind;
+mod savestate;
+mod slots;
+mod statelog;
+mod timeline;
+mod trace;
+mod watchdog;
+
+pub use builder::Chip8Builder;
+pub use coverage::{Coverage, Touched};
+pub use crash::{CrashReport, CRASH_HISTORY};
+pub use dump::{DumpFormat, StateDump};
+pub use handle::{Command, MachineHandle};
+pub use history::{Executed, InstructionHistory, DEFAULT_HISTORY_LENGTH};
+pub use hooks::{HaltReason, Hooks};
+pub use movie::{DesyncReport, Movie, DEFAULT_CHECKPOINT_INTERVAL, MOVIE_FORMAT_VERSION};
+pub use profile::{Profiler, RoutineProfile};
+pub use rewind::RewindBuffer;
+pub use savestate::{rom_hash, SaveHeader, SaveState, FORMAT_VERSION};
+pub use slots::{rom_id, SaveSlots, SLOT_COUNT};
+pub use statelog::{LogDivergence, StateLog, StateLogEntry};
+pub use timeline::Timeline;
+pub use trace::{Trace, TraceEntry, DEFAULT_TRACE_CAPACITY};
+pub use watchdog::WatchdogReport;
+
+use movie::Playback;
+use watchdog::Heartbeat;
+
+/// How often an idle `run()` checks its stop flag while waiting for commands.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How many instructions the machine runs per second by default, spread evenly over the clock's ticks.
+pub const INSTRUCTIONS_PER_SECOND: u32 = 700;
+
+/// Why the machine stopped or couldn't be set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    Cpu(CpuError),
+    Clock(&'static str),
+    Rom(&'static str),
+    /// The machine was built with settings that don't work together.
+    Config(&'static str),
+    /// A savestate couldn't be read or doesn't fit this machine.
+    State(&'static str),
+    /// The machine has been stopped for good.
+    Stopped,
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::Cpu(error) => write!(f, "cpu error: {error}"),
+            Chip8Error::Clock(error) => write!(f, "clock error: {error}"),
+            Chip8Error::Rom(error) => write!(f, "rom error: {error}"),
+            Chip8Error::Config(error) => write!(f, "invalid configuration: {error}"),
+            Chip8Error::State(error) => write!(f, "savestate error: {error}"),
+            Chip8Error::Stopped => write!(f, "machine has been stopped"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+impl From<CpuError> for Chip8Error {
+    fn from(error: CpuError) -> Self {
+        Chip8Error::Cpu(error)
+    }
+}
+
+/// Where a machine is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineState {
+    /// Built, and not currently running.
+    Ready,
+    /// Inside `run()`, executing in time with the clock.
+    Running,
+    /// The clock, timers, and tone are paused. Stepping by hand still works.
+    Paused,
+    /// Stopped for good, with the clock torn down.
+    Stopped,
+}
+
+/// An audio sink shared between the machine and the timer hook that drives it.
+type SharedAudio = Arc<Mutex<Box<dyn AudioSink>>>;
+
+/// The embedder's hooks, shared with the timer hook that reports the tone.
+type SharedHooks = Arc<Mutex<Option<Box<dyn Hooks>>>>;
+
+/// A whole Chip8 machine, with its components wired together.
+///
+/// The timers are attached to the machine's clock, and `run()` executes instructions in time with its ticks. Use
+/// `step()` instead to drive the machine yourself. Use `Chip8::builder()` to configure the machine.
+pub struct Chip8 {
+    variant: Variant,
+    instructions_per_second: u32,
+    cpu: Cpu,
+    memory: Memory,
+    /// The loaded program, kept so resetting can restore it.
+    rom: Vec<u8>,
+    display: Display,
+    keypad: Keypad,
+    timers: Timers,
+    clock: Box<dyn ClockSource>,
+    display_backend: Option<Box<dyn DisplayBackend>>,
+    audio_sink: Option<SharedAudio>,
+    hooks: SharedHooks,
+    events: Arc<EventBus>,
+    commands: Receiver<Command>,
+    command_tx: Sender<Command>,
+    speed: Speed,
+    /// Shared with the watchdog's tick handler, if the machine has one.
+    heartbeat: Option<Arc<Heartbeat>>,
+    ticks: TickReceiver,
+    rate: TickRate,
+    rng: Rng,
+    /// The seed the random number generator was built with, if it was given one.
+    seed: Option<u64>,
+    slots: Option<SaveSlots>,
+    /// Whether to write an autosave of the loaded program when shutting down.
+    autosave: bool,
+    rewind: Option<RewindBuffer>,
+    /// Whether frames rewind instead of running, as while a rewind hotkey is held.
+    rewinding: bool,
+    /// How many frames have run since the program was loaded, going back when the machine rewinds.
+    frames: u64,
+    recording: Option<Movie>,
+    playback: Option<Playback>,
+    trace: Option<Trace>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    timeline: Option<Timeline>,
+    state_log: Option<StateLog>,
+    history: InstructionHistory,
+    /// The registers the last instruction wrote, and what it wrote.
+    register_writes: Vec<(Register, u16)>,
+    /// The bytes of memory the last instruction read as data or wrote.
+    memory_accesses: Vec<MemoryAccess>,
+    started: bool,
+    running: bool,
+    /// Whether the machine was paused, as opposed to its clock being paused while the machine is idle.
+    paused: bool,
+    stopped: bool,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl Chip8 {
+    /// Creates a CHIP-8 machine driven by a 60hz clock thread.
+    pub fn new() -> Chip8 {
+        Chip8::builder()
+            .build()
+            .expect("the default configuration is valid")
+    }
+
+    /// Creates a machine driven by any clock, which should tick at `rate`.
+    pub fn with_clock(clock: Box<dyn ClockSource>, rate: TickRate) -> Result<Chip8, Chip8Error> {
+        Chip8::builder().clock(clock, rate).build()
+    }
+
+    /// Starts configuring a machine.
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+
+    /// Resets the machine and loads a program, ready to run from the start.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        check_rom_fits(rom, self.memory.len())?;
+        self.rom = rom.to_vec();
+        self.reset();
+        self.frames = 0;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        self.update_idle();
+        self.events.publish(Event::RomLoaded { len: rom.len() });
+        if self.has_autosave() {
+            self.events.publish(Event::AutosaveAvailable);
+        }
+        Ok(())
+    }
+
+    /// Executes a single instruction.
+    pub fn step(&mut self) -> Result<Instruction, Chip8Error> {
+        self.check_clock()?;
+        if self.stopped {
+            return Err(Chip8Error::Stopped);
+        }
+        let pc = self.cpu.pc();
+        let was_waiting = self.cpu.is_waiting_for_key();
+        // a program waiting on a key runs the same instruction over and over, which is only worth logging once
+        let mut explaining = None;
+        if !was_waiting {
+            explaining = self.trace_step();
+            if let Ok(opcode) = self.memory.read_opcode(pc) {
+                self.history.record(pc, opcode);
+            }
+        }
+        let mut bus = Bus {
+            memory: &mut self.memory,
+            display: &mut self.display,
+            keypad: &mut self.keypad,
+            timers: &self.timers,
+            rng: &mut self.rng,
+            writes: &mut self.register_writes,
+            accesses: &mut self.memory_accesses,
+        };
+        bus.writes.clear();
+        bus.accesses.clear();
+        match self.cpu.step(&mut bus) {
+            Ok(instruction) => {
+                if !was_waiting && self.cpu.is_waiting_for_key() {
+                    self.call_hooks(|hooks| hooks.on_key_wait());
+                }
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.record(pc, instruction);
+                }
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_execution(pc, 2);
+                    for &access in &self.memory_accesses {
+                        coverage.record_access(access);
+                    }
+                }
+                if self.timeline.is_some() {
+                    self.timeline_step(pc, instruction, was_waiting);
+                }
+                if let Some((entry, before)) = explaining {
+                    let explanation = explain(instruction, &before, Some(&Operands::of(self)));
+                    if let Some(trace) = &mut self.trace {
+                        trace.record_explained(entry, Some(&explanation));
+                    }
+                }
+                Ok(instruction)
+            }
+            Err(error) => {
+                if let (Some((entry, _)), Some(trace)) = (explaining, &mut self.trace) {
+                    trace.record(entry);
+                }
+                if let Some(timeline) = &mut self.timeline {
+                    timeline.halt(&error.to_string());
+                }
+                let report = CrashReport::new(self, error);
+                self.events.publish(Event::Crashed(Arc::new(report)));
+                self.halt(HaltReason::Cpu(error));
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Logs the instruction about to run to the trace, if one is on. A trace that explains steps logs them once
+    /// they've run, so the entry is given back with the machine as it was, to log then.
+    fn trace_step(&mut self) -> Option<(TraceEntry, Operands)> {
+        let trace = self.trace.as_ref().filter(|trace| trace.is_enabled())?;
+        let explains = trace.explains();
+        let entry = self.next_trace_entry()?;
+        if explains {
+            return Some((entry, Operands::of(self)));
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.record(entry);
+        }
+        None
+    }
+
+    /// The machine as the next instruction is about to run, as a trace would log it, unless the program counter is
+    /// past the end of memory.
+    pub fn next_trace_entry(&self) -> Option<TraceEntry> {
+        let pc = self.cpu.pc();
+        let opcode = self.memory.read_opcode(pc).ok()?;
+        let next = self.memory.read_opcode(pc.wrapping_add(2)).unwrap_or(0);
+        Some(TraceEntry {
+            pc,
+            opcode,
+            instruction: decode_for(opcode, next, self.variant),
+            registers: *self.cpu.registers(),
+            index: self.cpu.index(),
+            sp: self.cpu.stack().depth() as u8,
+        })
+    }
+
+    /// Puts an instruction that just ran at `pc` on the timeline, with the sprite it drew or the key it started
+    /// waiting for, and the tone if it's started or stopped since the last one.
+    fn timeline_step(&mut self, pc: u16, instruction: Instruction, was_waiting: bool) {
+        let opcode = self.memory.read_opcode(pc).unwrap_or(0);
+        let sounding = self.timers.retrieve_sound_timer() > 0;
+        let registers = *self.cpu.registers();
+        let waiting = !was_waiting && self.cpu.is_waiting_for_key();
+        let Some(timeline) = &mut self.timeline else {
+            return;
+        };
+        timeline.instruction(pc, opcode, instruction);
+        if let Instruction::Draw { x, y, height } = instruction {
+            let (x, y) = (registers[x as usize], registers[y as usize]);
+            timeline.draw(x, y, height, registers[0xF] == 1);
+        }
+        if waiting {
+            timeline.key_wait(pc);
+        }
+        timeline.sound(sounding);
+    }
+
+    /// The registers the last instruction wrote, in the order it wrote them, with what each holds after it.
+    pub fn register_writes(&self) -> &[(Register, u16)] {
+        &self.register_writes
+    }
+
+    /// The bytes of memory the last instruction read as data or wrote, in the order it touched them.
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        &self.memory_accesses
+    }
+
+    /// Whether the next instruction jumps to itself, which programs use to stop for good. Only the timers can change
+    /// from then on.
+    pub fn in_infinite_loop(&self) -> bool {
+        let pc = self.cpu.pc();
+        self.memory
+            .read_opcode(pc)
+            .is_ok_and(|opcode| decode(opcode) == Instruction::Jump { address: pc })
+    }
+
+    /// Executes up to `n` instructions, stopping early on an error.
+    pub fn step_n(&mut self, n: u64) -> Result<(), Chip8Error> {
+        for _ in 0..n {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Runs one frame: a tick's worth of instructions, one countdown of the timers, and presenting the display.
+    ///
+    /// This is for driving the machine yourself instead of with `run()`. Don't use both at once, or the timers
+    /// will count down twice as fast.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        let rewinding = self.rewinding;
+        self.execute_frame(1)?;
+        // rewound timers are already where they were
+        if !rewinding {
+            self.timers.tick();
+        }
+        self.present();
+        Ok(())
+    }
+
+    /// Executes the instructions owed for `ticks` ticks of the clock, or goes back as many frames while rewinding.
+    fn execute_frame(&mut self, ticks: u64) -> Result<(), Chip8Error> {
+        if self.rewinding {
+            return self.rewind(ticks).map(drop);
+        }
+        let per_tick = self.rate.per_tick(self.instructions_per_second) as u64;
+        // each tick is a frame of its own, so movies see the same input on the same instructions every time
+        for _ in 0..ticks {
+            self.replay_input();
+            self.step_n(per_tick)?;
+            self.finish_frame();
+        }
+        Ok(())
+    }
+
+    /// Counts a frame's instructions as run, checking the movie and recording a rewind point if one's due.
+    fn finish_frame(&mut self) {
+        self.frames += 1;
+        if let Some(timeline) = &mut self.timeline {
+            timeline.frame(self.frames);
+        }
+        if self.state_log.is_some() {
+            let (state, screen) = (self.state_digest(), self.display.frame().digest());
+            if let Some(log) = &mut self.state_log {
+                log.frame(self.frames, state, screen);
+            }
+        }
+        self.check_movie();
+        if self
+            .rewind
+            .as_ref()
+            .is_some_and(|rewind| rewind.is_due(self.frames))
+        {
+            let state = self.save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.record(self.frames, &state);
+            }
+        }
+    }
+
+    /// Puts the frame count back to where it was, for a debugger that's gone back to an earlier state.
+    pub(crate) fn set_frame_count(&mut self, frames: u64) {
+        self.frames = frames;
+    }
+
+    /// How many instructions make up a frame, at the machine's speed and clock rate.
+    pub(crate) fn instructions_per_frame(&self) -> u64 {
+        self.rate.per_tick(self.instructions_per_second) as u64
+    }
+
+    /// Starts a frame run an instruction at a time, as the debugger does, taking its input from a movie if one is
+    /// playing.
+    pub(crate) fn begin_frame(&mut self) {
+        self.replay_input();
+    }
+
+    /// Ends a frame run an instruction at a time, after `instructions_per_frame()` of them: the timers count down
+    /// and the display is presented, just as at the end of `run_frame()`.
+    pub(crate) fn end_frame(&mut self) {
+        self.finish_frame();
+        self.timers.tick();
+        self.present();
+    }
+
+    /// Records the keys held this frame, or presses the ones a movie held on this frame.
+    fn replay_input(&mut self) {
+        if let Some(movie) = &mut self.recording {
+            movie.inputs.push(self.keypad.mask());
+        }
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+        match playback.next_input() {
+            Some(input) => self.keypad.set_mask(input),
+            None => {
+                self.playback = None;
+                self.events.publish(Event::PlaybackFinished);
+            }
+        }
+    }
+
+    /// Checkpoints the state at the end of a frame into the movie being recorded, or checks it against the movie
+    /// being played back, publishing `Event::Desync` when playback first diverges.
+    fn check_movie(&mut self) {
+        let recording = self
+            .recording
+            .as_ref()
+            .is_some_and(|movie| movie.is_checkpoint(movie.inputs.len()));
+        let playing = self
+            .playback
+            .as_ref()
+            .is_some_and(|playback| playback.movie.is_checkpoint(playback.frame));
+        if !recording && !playing {
+            return;
+        }
+        let digest = self.save_state().digest();
+        if let Some(movie) = &mut self.recording {
+            movie.checkpoints.push(digest);
+        }
+        let report = self
+            .playback
+            .as_mut()
+            .and_then(|playback| playback.verify(digest));
+        if let Some(report) = report {
+            self.events.publish(Event::Desync(report));
+        }
+    }
+
+    /// Starts recording a movie from the machine's current state, checkpointing every frame.
+    pub fn start_recording(&mut self) -> Result<(), Chip8Error> {
+        self.start_recording_with(DEFAULT_CHECKPOINT_INTERVAL)
+    }
+
+    /// Starts recording a movie, checkpointing the state every `checkpoint_interval` frames so playback can tell
+    /// when it diverges.
+    pub fn start_recording_with(&mut self, checkpoint_interval: u32) -> Result<(), Chip8Error> {
+        if checkpoint_interval == 0 {
+            return Err(Chip8Error::Config("checkpoint interval must be nonzero"));
+        }
+        if self.playback.is_some() {
+            return Err(Chip8Error::State("a movie is playing"));
+        }
+        self.recording = Some(Movie::new(
+            self.save_state(),
+            self.seed,
+            checkpoint_interval,
+        ));
+        Ok(())
+    }
+
+    /// Stops recording, giving the movie recorded so far.
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Starts logging every instruction run to a trace, replacing any trace already going.
+    pub fn start_trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    /// Stops tracing, giving the trace back so its entries can be read or its log finished.
+    pub fn stop_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Starts counting the instructions run with a profiler, replacing any profiler already counting.
+    pub fn start_profiling(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Stops profiling, giving the profiler back with what it counted.
+    pub fn stop_profiling(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Starts keeping track of which bytes of memory are run, read, and written, replacing any coverage already
+    /// being kept.
+    pub fn start_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(coverage);
+    }
+
+    /// Stops keeping coverage, giving back what was kept.
+    pub fn stop_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Starts putting what the machine does on a timeline, replacing any timeline already going.
+    pub fn start_timeline(&mut self, mut timeline: Timeline) {
+        timeline.set_speed(self.instructions_per_second);
+        self.timeline = Some(timeline);
+    }
+
+    /// Stops the timeline, giving it back so it can be finished.
+    pub fn stop_timeline(&mut self) -> Option<Timeline> {
+        self.timeline.take()
+    }
+
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    pub(crate) fn timeline_mut(&mut self) -> Option<&mut Timeline> {
+        self.timeline.as_mut()
+    }
+
+    /// Starts logging hashes of the machine's state and screen at the end of every frame, replacing any log already
+    /// going.
+    pub fn start_state_log(&mut self, log: StateLog) {
+        self.state_log = Some(log);
+    }
+
+    /// Stops the state log, giving it back so it can be finished.
+    pub fn stop_state_log(&mut self) -> Option<StateLog> {
+        self.state_log.take()
+    }
+
+    /// The last instructions the machine ran, which it always keeps, unlike a trace.
+    pub fn history(&self) -> &InstructionHistory {
+        &self.history
+    }
+
+    pub(crate) fn history_mut(&mut self) -> &mut InstructionHistory {
+        &mut self.history
+    }
+
+    /// Switches the trace off or back on, or starts keeping the last `DEFAULT_TRACE_CAPACITY` steps in memory if
+    /// there's no trace yet. Returns whether it's now on.
+    pub fn toggle_trace(&mut self) -> bool {
+        let trace = self
+            .trace
+            .get_or_insert_with(|| Trace::ring(DEFAULT_TRACE_CAPACITY).disabled());
+        trace.set_enabled(!trace.is_enabled());
+        trace.is_enabled()
+    }
+
+    /// Loads a movie's start state and replays its input from the next frame on.
+    ///
+    /// The movie must have been recorded on the loaded program, if there is one. Once its input runs out, the
+    /// machine carries on as normal and publishes `Event::PlaybackFinished`.
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), Chip8Error> {
+        // a movie that can't play leaves any other one recording or playing as it was
+        self.check_loadable(&movie.start)?;
+        self.recording = None;
+        self.playback = None;
+        self.restore(movie.start.clone());
+        self.playback = Some(Playback {
+            movie,
+            frame: 0,
+            last_verified: None,
+            desync: None,
+        });
+        Ok(())
+    }
+
+    /// Stops playing back a movie, leaving the machine where it got to.
+    pub fn stop_playback(&mut self) -> Option<Movie> {
+        self.playback.take().map(|playback| playback.movie)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Where the movie being played back diverged from its recording, if it has.
+    pub fn playback_desync(&self) -> Option<DesyncReport> {
+        self.playback.as_ref().and_then(|playback| playback.desync)
+    }
+
+    /// Refuses anything that would break the movie being recorded or played back.
+    fn check_no_movie(&self) -> Result<(), Chip8Error> {
+        if self.recording.is_some() || self.playback.is_some() {
+            Err(Chip8Error::State("a movie is recording or playing"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// How many frames have run since the program was loaded. Rewinding takes frames back off.
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Goes back at least `frames` frames, or as far as the rewind buffer reaches, giving how many frames it went
+    /// back.
+    ///
+    /// The buffer only holds a state every few frames, so this lands on the nearest one before.
+    pub fn rewind(&mut self, frames: u64) -> Result<u64, Chip8Error> {
+        let rewind = self
+            .rewind
+            .as_mut()
+            .ok_or(Chip8Error::State("rewind is not enabled"))?;
+        let Some((frame, state)) = rewind.rewind(self.frames, frames) else {
+            return Ok(0);
+        };
+        let rewound = self.frames.saturating_sub(frame);
+        self.load_state(state)?;
+        self.frames = frame;
+        Ok(rewound)
+    }
+
+    /// Makes frames rewind instead of running, until turned off again.
+    pub fn set_rewinding(&mut self, rewinding: bool) -> Result<(), Chip8Error> {
+        if rewinding && self.rewind.is_none() {
+            return Err(Chip8Error::State("rewind is not enabled"));
+        }
+        if rewinding {
+            self.check_no_movie()?;
+        }
+        self.rewinding = rewinding;
+        Ok(())
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    fn present(&mut self) {
+        if let Some(backend) = &mut self.display_backend {
+            backend.present(&self.display);
+        }
+        if self.display.take_dirty() {
+            let frame = self.display.frame();
+            self.call_hooks(|hooks| hooks.on_draw(&frame));
+            self.events.publish(Event::FrameReady(frame));
+        }
+    }
+
+    /// Runs the machine in time with its clock until it's stopped or hits an error.
+    ///
+    /// This blocks, so stop it from another thread with the flag from `stop_flag()`. The machine can be run again
+    /// afterwards.
+    pub fn run(&mut self) -> Result<(), Chip8Error> {
+        self.begin_run()?;
+        let result = self.run_loop();
+        self.end_run();
+        result
+    }
+
+    /// Runs the machine in time with its clock like `run()`, but awaits its ticks as a `TickStream` instead of
+    /// blocking, so it can share a thread with other tasks.
+    ///
+    /// There's no waiting on commands without blocking, so the clock keeps ticking while the machine's idle and
+    /// commands are picked up a tick at a time. Stop it with the flag from `stop_flag()`, which is checked every
+    /// tick.
+    #[cfg(feature = "async")]
+    pub async fn run_async(&mut self) -> Result<(), Chip8Error> {
+        self.begin_run()?;
+        let result = self.run_loop_async().await;
+        self.update_idle();
+        self.end_run();
+        result
+    }
+
+    fn begin_run(&mut self) -> Result<(), Chip8Error> {
+        self.check_clock()?;
+        if self.stopped {
+            return Err(Chip8Error::Stopped);
+        }
+        self.update_idle();
+        if !self.started {
+            self.clock.start();
+            self.started = true;
+        }
+        self.stop_flag.store(false, Ordering::Relaxed);
+        // ticks from before we started running aren't owed any work
+        self.ticks.try_iter().for_each(drop);
+        self.running = true;
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.start(self.clock.ticks(), self.cpu.pc());
+        }
+        self.publish_state();
+        Ok(())
+    }
+
+    fn end_run(&mut self) {
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.stop();
+        }
+        self.running = false;
+        self.publish_state();
+    }
+
+    fn run_loop(&mut self) -> Result<(), Chip8Error> {
+        loop {
+            self.process_commands();
+            if self.stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+            if self.is_idle() {
+                // the clock is paused too, so sleep until there's something to do rather than waking every tick
+                match self.commands.recv_timeout(IDLE_POLL_INTERVAL) {
+                    Ok(command) => self.handle_command(command),
+                    Err(_) => self.check_clock()?,
+                }
+                continue;
+            }
+            // wake up now and then even without ticks, so a slow clock can't hold up commands or stopping
+            let tick = match self.ticks.recv_timeout(self.rate.interval()) {
+                Ok(tick) => tick,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.check_clock()?;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.check_clock()?;
+                    break;
+                }
+            };
+            // the clock counts the timers down itself
+            self.execute_frame(tick.elapsed)?;
+            self.present();
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.beat(tick.number, self.cpu.pc());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn run_loop_async(&mut self) -> Result<(), Chip8Error> {
+        loop {
+            self.process_commands();
+            if self.stop_flag.load(Ordering::Relaxed) {
+                break;
+            }
+            if self.is_idle() && self.clock.is_paused() {
+                // ticks stand in for waking up to check on commands
+                self.clock.resume();
+            }
+            let Some(tick) = self.ticks.stream().next().await else {
+                self.check_clock()?;
+                break;
+            };
+            if self.is_idle() {
+                continue;
+            }
+            self.execute_frame(tick.elapsed)?;
+            self.present();
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.beat(tick.number, self.cpu.pc());
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets a flag that stops `run()` when set. Unlike `stop()`, the machine can be run again afterwards.
+    ///
+    /// An idle machine only checks the flag a few times a second; `MachineHandle::stop()` is noticed at once.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop_flag)
+    }
+
+    pub fn state(&self) -> MachineState {
+        if self.stopped {
+            MachineState::Stopped
+        } else if self.paused {
+            MachineState::Paused
+        } else if self.running {
+            MachineState::Running
+        } else {
+            MachineState::Ready
+        }
+    }
+
+    /// Pauses the clock, which also stops the timers counting down, and silences the tone.
+    ///
+    /// A paused machine is idle: its clock thread sleeps, and `run()` only wakes up for commands.
+    pub fn pause(&mut self) {
+        if self.stopped {
+            return;
+        }
+        self.paused = true;
+        self.update_idle();
+        self.set_tone(false);
+        self.publish_state();
+    }
+
+    /// Resumes the clock, and the tone if the sound timer is still running.
+    pub fn resume(&mut self) {
+        if self.stopped {
+            return;
+        }
+        self.paused = false;
+        self.set_tone(self.timers.retrieve_sound_timer() > 0);
+        self.update_idle();
+        self.publish_state();
+    }
+
+    /// Runs exactly one frame while paused: a tick's worth of instructions, one countdown of the timers, and
+    /// presenting the display. The machine stays paused afterwards.
+    pub fn frame_advance(&mut self) -> Result<(), Chip8Error> {
+        if !self.paused {
+            return Err(Chip8Error::State("frame advance only works while paused"));
+        }
+        self.run_frame()
+    }
+
+    /// Whether there's nothing to run, because the machine is paused or has no program.
+    fn is_idle(&self) -> bool {
+        self.paused || self.rom.is_empty()
+    }
+
+    /// Pauses the clock while the machine is idle, so its thread sleeps instead of ticking for nothing.
+    fn update_idle(&mut self) {
+        let idle = self.is_idle();
+        if idle && !self.clock.is_paused() {
+            self.clock.pause();
+        } else if !idle && self.clock.is_paused() {
+            self.clock.resume();
+        }
+    }
+
+    /// Stops the machine for good: `run()` returns, the clock and timers are shut down, and the tone is silenced.
+    ///
+    /// Nothing can run on the machine afterwards.
+    pub fn stop(&mut self) -> Result<(), Chip8Error> {
+        if self.stopped {
+            return Ok(());
+        }
+        self.shut_down(HaltReason::Stopped)
+    }
+
+    /// Shuts every component down in order, and reports why the machine halted.
+    fn shut_down(&mut self, reason: HaltReason) -> Result<(), Chip8Error> {
+        self.stopped = true;
+        self.stop_flag.store(true, Ordering::Relaxed);
+        // the clock goes first, so the timers can't start the tone again once it's silenced
+        let result = self.clock.shutdown().map_err(Chip8Error::Clock);
+        // the clock has stopped, so the timers hold still for the autosave
+        let result = result.and(self.autosave());
+        self.timers
+            .shutdown()
+            .expect("timers have nothing that can fail to shut down");
+        self.set_tone(false);
+        self.publish_state();
+        self.halt(reason);
+        result
+    }
+
+    /// Writes the autosave of the loaded program, if the machine autosaves and has a program.
+    fn autosave(&self) -> Result<(), Chip8Error> {
+        match &self.slots {
+            Some(slots) if self.autosave && !self.rom.is_empty() => {
+                slots.save_autosave(&self.save_state())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the loaded program has an autosave to resume from.
+    pub fn has_autosave(&self) -> bool {
+        self.slots
+            .as_ref()
+            .is_some_and(|slots| !self.rom.is_empty() && slots.has_autosave(&self.rom))
+    }
+
+    /// Picks up where the loaded program left off when the machine last shut down, removing the autosave. An
+    /// autosave that can't be loaded, such as while a movie is recording, is kept to resume later.
+    pub fn resume_autosave(&mut self) -> Result<(), Chip8Error> {
+        let rom = self.rom.clone();
+        let state = self.slots_or_err()?.load_autosave(&rom)?;
+        self.load_state(state)?;
+        self.slots_or_err()?.remove_autosave(&rom)
+    }
+
+    /// Throws away the loaded program's autosave, to start afresh.
+    pub fn discard_autosave(&mut self) -> Result<(), Chip8Error> {
+        let rom = self.rom.clone();
+        self.slots_or_err()?.remove_autosave(&rom)
+    }
+
+    /// Fails if the clock's thread has panicked, stopping the machine the first time it's noticed.
+    fn check_clock(&mut self) -> Result<(), Chip8Error> {
+        if !self.clock.is_poisoned() {
+            return Ok(());
+        }
+        if !self.stopped {
+            // tearing the clock down reports the same panic we're about to
+            let _ = self.shut_down(HaltReason::ClockPanicked);
+        }
+        Err(Chip8Error::Clock("clock thread panicked"))
+    }
+
+    /// Subscribes to the machine's events. Drop the receiver to unsubscribe.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Gets the bus the machine publishes its events on, for other subsystems to publish on too.
+    pub fn events(&self) -> &Arc<EventBus> {
+        &self.events
+    }
+
+    /// Creates a handle for controlling the machine from another thread.
+    pub fn handle(&self) -> MachineHandle {
+        MachineHandle::new(self.command_tx.clone(), Arc::clone(&self.events))
+    }
+
+    /// Carries out the commands sent from handles. `run()` does this itself between frames.
+    ///
+    /// Commands that fail are reported with `Event::CommandFailed`, as there's no one to return the error to.
+    pub fn process_commands(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            self.handle_command(command);
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        if let Err(error) = self.execute_command(command) {
+            self.events.publish(Event::CommandFailed(error));
+        }
+    }
+
+    fn execute_command(&mut self, command: Command) -> Result<(), Chip8Error> {
+        match command {
+            Command::LoadRom(rom) => return self.load_rom(&rom),
+            Command::LoadState(state) => return self.load_state(*state),
+            Command::Stop => return self.stop(),
+            Command::Pause => self.pause(),
+            Command::Resume => self.resume(),
+            Command::Reset => self.reset(),
+            Command::SetSpeed(speed) => self.set_speed(speed),
+            Command::PressKey(key) => self.keypad.press(key),
+            Command::ReleaseKey(key) => self.keypad.release(key),
+            Command::SaveSlot(slot) => return self.save_slot(slot),
+            Command::LoadSlot(slot) => return self.load_slot(slot),
+            Command::Hotkey(hotkey) => return self.apply_hotkey(hotkey),
+            Command::ReleaseHotkey(hotkey) => return self.release_hotkey(hotkey),
+            Command::Rewind(frames) => return self.rewind(frames).map(drop),
+            Command::StartRecording => return self.start_recording(),
+            Command::FrameAdvance => return self.frame_advance(),
+            Command::ResumeAutosave => return self.resume_autosave(),
+            Command::DiscardAutosave => return self.discard_autosave(),
+            Command::ToggleTrace => {
+                self.toggle_trace();
+            }
+            Command::PlayMovie(movie) => return self.play_movie(*movie),
+            Command::StopRecording => {
+                if let Some(movie) = self.stop_recording() {
+                    self.events.publish(Event::MovieRecorded(Arc::new(movie)));
+                }
+            }
+            Command::SaveState => {
+                let state = self.save_state();
+                self.events.publish(Event::StateSaved(Arc::new(state)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Scales how fast the clock ticks, and with it how fast instructions run.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        self.clock.set_speed(speed);
+    }
+
+    fn publish_state(&self) {
+        self.events.publish(Event::StateChanged(self.state()));
+    }
+
+    fn halt(&self, reason: HaltReason) {
+        self.call_hooks(|hooks| hooks.on_halt(reason));
+        self.events.publish(Event::Halted(reason));
+    }
+
+    /// Replaces the embedder's hooks.
+    pub fn set_hooks(&mut self, hooks: Box<dyn Hooks>) {
+        *self.hooks.lock().unwrap() = Some(hooks);
+    }
+
+    fn call_hooks(&self, f: impl FnOnce(&mut dyn Hooks)) {
+        if let Some(hooks) = self.hooks.lock().unwrap().as_mut() {
+            f(hooks.as_mut());
+        }
+    }
+
+    fn set_tone(&self, on: bool) {
+        if let Some(sink) = &self.audio_sink {
+            sink.lock().unwrap().set_tone(on);
+        }
+    }
+
+    /// Resets the CPU, display, timers, and keypad, and restores memory to the freshly loaded program.
+    ///
+    /// Any movie being recorded or played back is stopped, as it can't follow the machine through a reset.
+    pub fn reset(&mut self) {
+        self.recording = None;
+        self.playback = None;
+        self.cpu = Cpu::with_quirks(self.cpu.quirks());
+        self.memory.clear();
+        self.memory
+            .load_rom(&self.rom)
+            .expect("the rom fit when it was loaded");
+        self.display.clear();
+        self.timers.set_delay_timer(0);
+        self.timers.set_sound_timer(0);
+        self.keypad.reset();
+        self.history.clear();
+    }
+
+    /// Captures the whole state of the machine.
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            variant: self.variant,
+            rom: self.rom.clone(),
+            cpu: self.cpu.clone(),
+            memory: self.memory.clone(),
+            timers: self.timers.snapshot(),
+            frame: Frame::clone(&self.display.frame()),
+            keypad: self.keypad.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// A short hash of the registers, stack, timers, and memory, leaving out the quirks, so machines set up
+    /// differently can be compared. It's worked out the same way from one version of this crate to the next.
+    pub fn state_digest(&self) -> u64 {
+        let mut hash = Sha256::new();
+        hash.update(self.cpu.registers());
+        hash.update(self.cpu.pc().to_le_bytes());
+        hash.update(self.cpu.index().to_le_bytes());
+        for &address in self.cpu.stack().entries() {
+            hash.update(address.to_le_bytes());
+        }
+        hash.update([
+            self.cpu.stack().depth() as u8,
+            self.cpu.is_waiting_for_key() as u8,
+            self.timers.retrieve_delay_timer(),
+            self.timers.retrieve_sound_timer(),
+        ]);
+        hash.update(self.memory.slice(0, self.memory.len()).unwrap_or_default());
+        u64::from_le_bytes(hash.finalize()[..8].try_into().unwrap())
+    }
+
+    /// Lays out the machine's state for people to read. Use `StateDump::render()` to write it as JSON or TOML.
+    pub fn dump_state(&self) -> StateDump {
+        StateDump::new(&self.save_state())
+    }
+
+    /// Puts the machine back into a saved state.
+    ///
+    /// The state must come from the same variant with the same amount of RAM, and from the loaded program if there
+    /// is one. With no program loaded, the state brings its own.
+    pub fn load_state(&mut self, state: SaveState) -> Result<(), Chip8Error> {
+        self.check_no_movie()?;
+        self.check_loadable(&state)?;
+        self.restore(state);
+        Ok(())
+    }
+
+    /// Fails if a state can't be loaded into this machine, or isn't of the loaded program, if there is one.
+    fn check_loadable(&self, state: &SaveState) -> Result<(), Chip8Error> {
+        state.validate()?;
+        let rom = if self.rom.is_empty() {
+            &state.rom
+        } else {
+            &self.rom
+        };
+        state.check_compatible(self.variant, rom)?;
+        if state.memory.len() != self.memory.len() {
+            return Err(Chip8Error::State("savestate has a different amount of ram"));
+        }
+        Ok(())
+    }
+
+    /// Puts the machine into a state that's been checked with `check_loadable()`.
+    fn restore(&mut self, state: SaveState) {
+        self.rom = state.rom;
+        self.cpu = state.cpu;
+        self.memory = state.memory;
+        self.timers.restore(state.timers);
+        self.display.load_frame(state.frame);
+        self.keypad = state.keypad;
+        self.rng = state.rng;
+        self.update_idle();
+    }
+
+    /// The machine's savestate slots, if it was given a save directory.
+    pub fn slots(&self) -> Option<&SaveSlots> {
+        self.slots.as_ref()
+    }
+
+    fn slots_or_err(&mut self) -> Result<&mut SaveSlots, Chip8Error> {
+        self.slots
+            .as_mut()
+            .ok_or(Chip8Error::State("no save directory is set"))
+    }
+
+    /// Saves the machine's state to one of the loaded program's slots.
+    pub fn save_slot(&mut self, slot: u8) -> Result<(), Chip8Error> {
+        let state = self.save_state();
+        self.slots_or_err()?.save(slot, &state)?;
+        self.events.publish(Event::SlotSaved(slot));
+        Ok(())
+    }
+
+    /// Loads the machine's state from one of the loaded program's slots.
+    pub fn load_slot(&mut self, slot: u8) -> Result<(), Chip8Error> {
+        let rom = self.rom.clone();
+        let state = self.slots_or_err()?.load(&rom, slot)?;
+        self.load_state(state)?;
+        self.events.publish(Event::SlotLoaded(slot));
+        Ok(())
+    }
+
+    /// Saves to the selected slot.
+    pub fn quicksave(&mut self) -> Result<(), Chip8Error> {
+        let slot = self.slots_or_err()?.selected();
+        self.save_slot(slot)
+    }
+
+    /// Loads the selected slot.
+    pub fn quickload(&mut self) -> Result<(), Chip8Error> {
+        let slot = self.slots_or_err()?.selected();
+        self.load_slot(slot)
+    }
+
+    /// Carries out what a hotkey does to the machine.
+    pub fn apply_hotkey(&mut self, hotkey: Hotkey) -> Result<(), Chip8Error> {
+        if let Some(speed) = hotkey.apply_to_speed(self.speed) {
+            self.set_speed(speed);
+            return Ok(());
+        }
+        match hotkey {
+            Hotkey::QuickSave => self.quicksave(),
+            Hotkey::QuickLoad => self.quickload(),
+            Hotkey::NextSlot => self.slots_or_err().map(SaveSlots::select_next),
+            Hotkey::PreviousSlot => self.slots_or_err().map(SaveSlots::select_previous),
+            Hotkey::Rewind => self.set_rewinding(true),
+            Hotkey::TogglePause => {
+                if self.paused {
+                    self.resume();
+                } else {
+                    self.pause();
+                }
+                Ok(())
+            }
+            Hotkey::FrameAdvance => self.frame_advance(),
+            Hotkey::ToggleTrace => {
+                self.toggle_trace();
+                Ok(())
+            }
+            // the frontend opens its menu and windows
+            Hotkey::Menu | Hotkey::MemoryView | Hotkey::DisassemblyView | Hotkey::SpriteView => {
+                Ok(())
+            }
+            Hotkey::SpeedUp | Hotkey::SpeedDown | Hotkey::ToggleFastForward => Ok(()),
+        }
+    }
+
+    /// Ends what a held hotkey does, for hotkeys that act for as long as they're held.
+    pub fn release_hotkey(&mut self, hotkey: Hotkey) -> Result<(), Chip8Error> {
+        match hotkey {
+            Hotkey::Rewind => self.set_rewinding(false),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// What a register holds.
+    pub fn register(&self, register: Register) -> u16 {
+        match register {
+            Register::V(x) => self.cpu.registers()[x as usize] as u16,
+            Register::Index => self.cpu.index(),
+            Register::Delay => self.timers.retrieve_delay_timer() as u16,
+            Register::Sound => self.timers.retrieve_sound_timer() as u16,
+        }
+    }
+
+    /// Writes a register from outside the program, keeping as many bits of `value` as the register holds. Movies
+    /// only record input, so this can't be done while one is recording or playing.
+    pub fn set_register(&mut self, register: Register, value: u16) -> Result<(), Chip8Error> {
+        self.check_no_movie()?;
+        match register {
+            Register::V(x) => self.cpu.set_register(x as usize, value as u8),
+            Register::Index => self.cpu.set_index(value),
+            Register::Delay => self.timers.set_delay_timer(value as u8),
+            Register::Sound => self.timers.set_sound_timer(value as u8),
+        }
+        Ok(())
+    }
+
+    /// Moves the program counter from outside the program, which can't be done while a movie is recording or
+    /// playing.
+    pub fn set_pc(&mut self, pc: u16) -> Result<(), Chip8Error> {
+        self.check_no_movie()?;
+        self.cpu.set_pc(pc);
+        Ok(())
+    }
+
+    /// Writes a byte of memory from outside the program, which can't be done while a movie is recording or playing.
+    pub fn poke(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        self.check_no_movie()?;
+        self.memory
+            .write(address, value)
+            .map_err(|_| Chip8Error::State("address is past the end of memory"))?;
+        Ok(())
+    }
+
+    /// Gets the loaded program, as it was before it ran.
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    pub fn keypad_mut(&mut self) -> &mut Keypad {
+        &mut self.keypad
+    }
+
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    pub fn clock(&self) -> &dyn ClockSource {
+        self.clock.as_ref()
+    }
+
+    pub fn clock_mut(&mut self) -> &mut dyn ClockSource {
+        self.clock.as_mut()
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown for Chip8 {
+    fn shutdown(&mut self) -> Result<(), &'static str> {
+        self.stop().map_err(|error| match error {
+            Chip8Error::Clock(error) => error,
+            _ => "machine failed to stop",
+        })
+    }
+}
+
+/// Fails if programs for `variant` can't run yet. Only CHIP-8 ones can, so far.
+pub fn check_variant(variant: Variant) -> Result<(), &'static str> {
+    if variant != Variant::Chip8 {
+        return Err("only CHIP-8 programs can run, not SUPER-CHIP or XO-CHIP ones");
+    }
+    Ok(())
+}
+
+/// Fails if a program is too big to load into `ram_size` bytes of memory.
+fn check_rom_fits(rom: &[u8], ram_size: usize) -> Result<(), Chip8Error> {
+    if rom.len() > ram_size.saturating_sub(PROGRAM_START) {
+        return Err(Chip8Error::Rom("rom is too large to fit in memory"));
+    }
+    Ok(())
+}
+
+impl Drop for Chip8 {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        clock::{Clock, ManualClock},
+        system::{AccessKind, TimerEvent},
+    };
+
+    use super::*;
+
+    fn manual_machine(rom: &[u8]) -> Chip8 {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        machine.load_rom(rom).expect("failed to load rom");
+        machine
+    }
+
+    #[test]
+    fn steps_through_a_program() {
+        // V0 = 5, V1 = 7, V0 += V1, I = font for V0, draw it
+        let mut machine =
+            manual_machine(&[0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0xF0, 0x29, 0xD0, 0x05]);
+        for _ in 0..5 {
+            machine.step().expect("instruction failed");
+        }
+        assert_eq!(machine.cpu().registers()[0], 12);
+        assert_eq!(machine.cpu().index(), Memory::font_address(0xC));
+        assert!(machine.display().get_pixel(12, 12), "sprite is not drawn");
+        assert_eq!(machine.cpu().pc(), PROGRAM_START as u16 + 10);
+    }
+
+    #[test]
+    fn traces_steps_while_switched_on() {
+        // V0 += 1, jump back
+        let mut machine = manual_machine(&[0x70, 0x01, 0x12, 0x00]);
+        assert!(machine.toggle_trace(), "trace isn't switched on");
+        machine.step_n(3).expect("steps failed");
+        assert!(!machine.toggle_trace(), "trace isn't switched off");
+        machine.step().expect("step failed");
+        let trace = machine.stop_trace().expect("no trace");
+        let entries: Vec<_> = trace.entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].instruction, Instruction::Jump { address: 0x200 });
+        // the state is logged before the instruction runs
+        assert_eq!(entries[2].pc, 0x200);
+        assert_eq!(entries[2].registers[0], 1);
+    }
+
+    #[test]
+    fn publishes_a_crash_report() {
+        // V0 = 1, then call the call, which calls itself until the stack overflows
+        let mut machine = manual_machine(&[0x60, 0x01, 0x22, 0x02]);
+        let events = machine.subscribe();
+        let error = CpuError::StackOverflow { pc: 0x202 };
+        assert_eq!(machine.step_n(20), Err(Chip8Error::Cpu(error)));
+        let report = events
+            .try_iter()
+            .find_map(|event| match event {
+                Event::Crashed(report) => Some(report),
+                _ => None,
+            })
+            .expect("no crash report");
+        assert_eq!(report.pc, 0x202);
+        assert_eq!(report.registers[0], 1);
+        assert_eq!(report.call_stack.len(), 16);
+        assert_eq!(report.call_stack[0].call_site, Some(0x202));
+        assert_eq!(report.history.len(), 18);
+        assert_eq!(
+            report.history.last().map(|executed| executed.pc),
+            Some(0x202)
+        );
+        assert!(report
+            .disassembly
+            .iter()
+            .any(|line| line.address == 0x202 && line.opcode() == 0x2202));
+        assert_eq!(*report, CrashReport::new(&machine, error));
+        assert!(report.to_string().starts_with("stack overflow at 202"));
+    }
+
+    #[test]
+    fn covers_the_memory_instructions_touch() {
+        // I = 0x20A, draw a sprite from there, store V0 and V1 over it, then loop
+        let mut machine =
+            manual_machine(&[0xA2, 0x0A, 0xD0, 0x12, 0xF1, 0x55, 0x12, 0x06, 0x00, 0x00]);
+        machine.start_coverage(Coverage::new());
+        machine.step_n(2).expect("steps failed");
+        assert_eq!(
+            machine.memory_accesses(),
+            [0x20A, 0x20B].map(|address| MemoryAccess {
+                address,
+                value: 0,
+                kind: AccessKind::Read,
+            })
+        );
+        machine.step_n(2).expect("steps failed");
+        let coverage = machine.stop_coverage().expect("no coverage");
+        assert_eq!(
+            coverage.to_string(),
+            "0x200-0x207  executed\n0x20A-0x20B  read written\n"
+        );
+    }
+
+    #[test]
+    fn remembers_the_last_instructions_run() {
+        // V0 += 1, jump back, then an opcode that doesn't exist
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .history_length(3)
+            .build()
+            .expect("failed to build machine");
+        machine
+            .load_rom(&[0x70, 0x01, 0x30, 0x03, 0x12, 0x00, 0xFF, 0xFF])
+            .expect("failed to load rom");
+        machine.step_n(8).expect("steps failed");
+        assert!(machine.step().is_err(), "the bad opcode ran");
+        let history: Vec<_> = machine
+            .history()
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect();
+        assert_eq!(
+            history,
+            [
+                "0x200  7001  ADD V0, 0x01",
+                "0x202  3003  SE V0, 0x03",
+                "0x206  FFFF  DW 0xFFFF"
+            ]
+        );
+
+        machine.reset();
+        assert!(machine.history().is_empty());
+    }
+
+    #[test]
+    fn errors_stop_on_the_instruction() {
+        let mut machine = manual_machine(&[0xFF, 0xFF]);
+        assert_eq!(
+            machine.step(),
+            Err(Chip8Error::Cpu(CpuError::UnknownOpcode {
+                pc: PROGRAM_START as u16,
+                opcode: 0xFFFF
+            }))
+        );
+        assert_eq!(machine.cpu().pc(), PROGRAM_START as u16);
+    }
+
+    #[test]
+    fn frames_run_a_tick_of_work() {
+        // V0 += 1, jump back
+        let mut machine = manual_machine(&[0x70, 0x01, 0x12, 0x00]);
+        machine.timers().set_delay_timer(10);
+        machine.run_frame().expect("frame failed");
+        let per_tick = TickRate::NTSC.per_tick(INSTRUCTIONS_PER_SECOND);
+        assert_eq!(machine.cpu().registers()[0] as u32, per_tick / 2);
+        assert_eq!(machine.timers().retrieve_delay_timer(), 9);
+        machine.step_n(3).expect("steps failed");
+        assert_eq!(machine.cpu().registers()[0] as u32, per_tick / 2 + 2);
+    }
+
+    #[test]
+    fn reset_restores_the_rom() {
+        // V0 = 5, I = 0x200, store V0 over the first instruction
+        let rom = [0x60, 0x05, 0xA2, 0x00, 0xF0, 0x55];
+        let mut machine = manual_machine(&rom);
+        machine.step_n(3).expect("steps failed");
+        assert_eq!(machine.memory().read(PROGRAM_START as u16), Ok(0x05));
+        machine.reset();
+        assert_eq!(machine.cpu().registers()[0], 0);
+        assert_eq!(machine.cpu().pc(), PROGRAM_START as u16);
+        assert_eq!(
+            machine.memory().slice(PROGRAM_START as u16, rom.len()),
+            Ok(&rom[..])
+        );
+        assert_eq!(machine.rom(), rom);
+    }
+
+    struct CountingBackend(Arc<AtomicUsize>);
+
+    impl DisplayBackend for CountingBackend {
+        fn present(&mut self, _display: &Display) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn run_until_stopped() {
+        // V0 = 3, set the delay timer from V0, then loop forever
+        let presented = Arc::new(AtomicUsize::new(0));
+        let mut machine = Chip8::builder()
+            .clock(
+                Box::new(Clock::new(Duration::from_millis(1))),
+                TickRate::NTSC,
+            )
+            .display_backend(Box::new(CountingBackend(Arc::clone(&presented))))
+            .build()
+            .expect("failed to build machine");
+        machine
+            .load_rom(&[0x60, 0x03, 0xF0, 0x15, 0x12, 0x04])
+            .expect("failed to load rom");
+        let stop = machine.stop_flag();
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(machine.run(), Ok(()));
+        stopper.join().unwrap();
+        assert_eq!(machine.cpu().pc(), PROGRAM_START as u16 + 4);
+        assert_eq!(
+            machine.timers().retrieve_delay_timer(),
+            0,
+            "timers are not counted down by the machine's clock"
+        );
+        assert!(
+            presented.load(Ordering::Relaxed) > 0,
+            "frames are not presented"
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn runs_async_until_stopped() {
+        use std::{
+            future::Future,
+            pin::pin,
+            task::{Context, Poll, Wake, Waker},
+        };
+
+        struct Unpark(thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        // V0 = 3, set the delay timer from V0, then loop forever
+        let mut machine = Chip8::with_clock(
+            Box::new(Clock::new(Duration::from_millis(1))),
+            TickRate::NTSC,
+        )
+        .expect("failed to build machine");
+        machine
+            .load_rom(&[0x60, 0x03, 0xF0, 0x15, 0x12, 0x04])
+            .expect("failed to load rom");
+        machine.pause();
+        let handle = machine.handle();
+        let stop = machine.stop_flag();
+        let controller = thread::spawn(move || {
+            // commands still get through while paused, with nothing blocking on them
+            thread::sleep(Duration::from_millis(20));
+            handle.resume().expect("failed to send command");
+            thread::sleep(Duration::from_millis(30));
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let result = {
+            let mut run = pin!(machine.run_async());
+            loop {
+                match run.as_mut().poll(&mut cx) {
+                    Poll::Ready(result) => break result,
+                    Poll::Pending => thread::park(),
+                }
+            }
+        };
+        assert_eq!(result, Ok(()));
+        controller.join().unwrap();
+        assert_eq!(machine.cpu().pc(), PROGRAM_START as u16 + 4);
+        assert_eq!(machine.state(), MachineState::Ready);
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut machine = manual_machine(&[0x12, 0x00]);
+        assert_eq!(machine.state(), MachineState::Ready);
+        machine.pause();
+        assert_eq!(machine.state(), MachineState::Paused);
+        machine.step().expect("stepping by hand fails while paused");
+        machine.resume();
+        assert_eq!(machine.state(), MachineState::Ready);
+        machine.stop().expect("failed to stop machine");
+        assert_eq!(machine.state(), MachineState::Stopped);
+        assert_eq!(machine.step(), Err(Chip8Error::Stopped));
+        assert_eq!(machine.run(), Err(Chip8Error::Stopped));
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Called {
+        Draw,
+        SoundStart,
+        SoundStop,
+        Halt(HaltReason),
+        KeyWait,
+    }
+
+    struct RecordingHooks(Arc<Mutex<Vec<Called>>>);
+
+    impl Hooks for RecordingHooks {
+        fn on_draw(&mut self, _frame: &Frame) {
+            self.0.lock().unwrap().push(Called::Draw);
+        }
+        fn on_sound_start(&mut self) {
+            self.0.lock().unwrap().push(Called::SoundStart);
+        }
+        fn on_sound_stop(&mut self) {
+            self.0.lock().unwrap().push(Called::SoundStop);
+        }
+        fn on_halt(&mut self, reason: HaltReason) {
+            self.0.lock().unwrap().push(Called::Halt(reason));
+        }
+        fn on_key_wait(&mut self) {
+            self.0.lock().unwrap().push(Called::KeyWait);
+        }
+    }
+
+    #[test]
+    fn hooks_are_called() {
+        // V0 = 2, start the tone, draw the font sprite for 2, wait for a key
+        let mut machine =
+            manual_machine(&[0x60, 0x02, 0xF0, 0x18, 0xF0, 0x29, 0xD0, 0x05, 0xF1, 0x0A]);
+        let called = Arc::new(Mutex::new(Vec::new()));
+        machine.set_hooks(Box::new(RecordingHooks(Arc::clone(&called))));
+        machine.run_frame().expect("frame failed");
+        machine.timers().advance(1);
+        machine.stop().expect("failed to stop machine");
+        assert_eq!(
+            *called.lock().unwrap(),
+            vec![
+                Called::SoundStart,
+                Called::KeyWait,
+                Called::Draw,
+                Called::SoundStop,
+                Called::Halt(HaltReason::Stopped),
+            ]
+        );
+    }
+
+    #[test]
+    fn events_are_published() {
+        // V0 = 2, start the tone, draw the font sprite for 2, then loop forever
+        let rom = [0x60, 0x02, 0xF0, 0x18, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x08];
+        let mut machine = manual_machine(&[]);
+        let events = machine.subscribe();
+        machine.load_rom(&rom).expect("failed to load rom");
+        machine.run_frame().expect("frame failed");
+        machine.pause();
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                Event::Timer(TimerEvent::DelaySet(0)),
+                Event::Timer(TimerEvent::SoundSet(0)),
+                Event::RomLoaded { len: rom.len() },
+                Event::Timer(TimerEvent::SoundSet(2)),
+                Event::SoundEdge(true),
+                Event::FrameReady(machine.display().frame()),
+                Event::StateChanged(MachineState::Paused),
+            ]
+        );
+    }
+
+    #[test]
+    fn handle_controls_a_running_machine() {
+        let mut machine = Chip8::with_clock(
+            Box::new(Clock::new(Duration::from_millis(1))),
+            TickRate::NTSC,
+        )
+        .expect("failed to build machine");
+        let handle = machine.handle();
+        let events = handle.subscribe();
+        let runner = thread::spawn(move || {
+            let result = machine.run();
+            (machine, result)
+        });
+        let rom = [0x12, 0x00];
+        handle.load_rom(&rom).expect("failed to send command");
+        handle.press_key(0).expect("failed to send command");
+        handle.pause().expect("failed to send command");
+        handle
+            .load_rom(&[0; 0x1000])
+            .expect("failed to send command");
+        let received: Vec<Event> = events
+            .iter()
+            .take_while(|event| !matches!(event, Event::CommandFailed(_)))
+            .collect();
+        assert!(received.contains(&Event::StateChanged(MachineState::Paused)));
+        handle.stop().expect("failed to send command");
+        let (machine, result) = runner.join().unwrap();
+        assert_eq!(result, Ok(()));
+        assert_eq!(machine.state(), MachineState::Stopped);
+        assert!(machine.keypad().is_pressed(0));
+        assert_eq!(
+            machine.rom(),
+            rom,
+            "the oversized rom replaced the loaded one"
+        );
+        drop(machine);
+        assert_eq!(handle.stop(), Err(Chip8Error::Stopped));
+    }
+
+    #[test]
+    fn clock_panics_halt_the_machine() {
+        let mut machine = Chip8::with_clock(
+            Box::new(Clock::new(Duration::from_millis(1))),
+            TickRate::NTSC,
+        )
+        .expect("failed to build machine");
+        machine
+            .clock_mut()
+            .on_tick(Box::new(|tick| assert!(tick.number < 3, "handler failed")))
+            .expect("failed to register handler");
+        machine.load_rom(&[0x12, 0x00]).expect("failed to load rom");
+        let events = machine.subscribe();
+        let error = Chip8Error::Clock("clock thread panicked");
+        assert_eq!(machine.run(), Err(error));
+        assert_eq!(
+            machine.step(),
+            Err(error),
+            "later calls don't report the panic"
+        );
+        assert_eq!(machine.state(), MachineState::Stopped);
+        assert!(events
+            .try_iter()
+            .any(|event| event == Event::Halted(HaltReason::ClockPanicked)));
+    }
+
+    #[test]
+    fn components_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Chip8>();
+        assert_send::<MachineHandle>();
+        assert_send::<Cpu>();
+        assert_send::<Memory>();
+        assert_send::<Display>();
+        assert_send::<Keypad>();
+        assert_send::<Timers>();
+        assert_send::<Clock>();
+    }
+
+    #[test]
+    fn machines_run_side_by_side() {
+        let spawn = |value: u8| {
+            let mut machine = Chip8::with_clock(
+                Box::new(Clock::new(Duration::from_millis(1))),
+                TickRate::NTSC,
+            )
+            .expect("failed to build machine");
+            // V0 = value, set the delay timer and I from V0, then loop forever
+            machine
+                .load_rom(&[0x60, value, 0xF0, 0x15, 0xF0, 0x29, 0x12, 0x06])
+                .expect("failed to load rom");
+            let events = machine.subscribe();
+            let stop = machine.stop_flag();
+            let runner = thread::spawn(move || {
+                machine.run().expect("machine failed");
+                machine
+            });
+            (runner, stop, events)
+        };
+        let machines = [spawn(1), spawn(0xF)];
+        thread::sleep(Duration::from_millis(50));
+        for ((runner, stop, events), (value, other)) in
+            machines.into_iter().zip([(1, 0xF), (0xF, 1)])
+        {
+            stop.store(true, Ordering::Relaxed);
+            let machine = runner.join().unwrap();
+            assert_eq!(machine.cpu().registers()[0], value);
+            assert_eq!(machine.cpu().index(), Memory::font_address(value));
+            assert!(
+                events
+                    .try_iter()
+                    .all(|event| event != Event::Timer(TimerEvent::DelaySet(other))),
+                "machines share events"
+            );
+        }
+    }
+
+    #[test]
+    fn clock_sleeps_while_idle() {
+        let mut machine = Chip8::with_clock(
+            Box::new(Clock::new(Duration::from_millis(1))),
+            TickRate::NTSC,
+        )
+        .expect("failed to build machine");
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ticks);
+        machine
+            .clock_mut()
+            .on_tick(Box::new(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }))
+            .expect("failed to register handler");
+        let handle = machine.handle();
+        let events = handle.subscribe();
+        let runner = thread::spawn(move || machine.run());
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            ticks.load(Ordering::Relaxed),
+            0,
+            "clock ticks without a rom"
+        );
+        handle
+            .load_rom(&[0x12, 0x00])
+            .expect("failed to send command");
+        thread::sleep(Duration::from_millis(20));
+        assert!(
+            ticks.load(Ordering::Relaxed) > 0,
+            "clock does not tick with a rom"
+        );
+        handle.pause().expect("failed to send command");
+        events
+            .iter()
+            .find(|event| *event == Event::StateChanged(MachineState::Paused))
+            .expect("machine did not pause");
+        let paused_at = ticks.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(20));
+        assert!(
+            ticks.load(Ordering::Relaxed) <= paused_at + 1,
+            "clock ticks while paused"
+        );
+        handle.stop().expect("failed to send command");
+        assert_eq!(runner.join().unwrap(), Ok(()));
+    }
+
+    struct StallingBackend;
+
+    impl DisplayBackend for StallingBackend {
+        fn present(&mut self, _display: &Display) {
+            thread::sleep(Duration::from_millis(30));
+        }
+    }
+
+    #[test]
+    fn watchdog_reports_stalls() {
+        let mut machine = Chip8::builder()
+            .clock(
+                Box::new(Clock::new(Duration::from_millis(1))),
+                TickRate::NTSC,
+            )
+            .display_backend(Box::new(StallingBackend))
+            .watchdog(10)
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&[0x12, 0x00]).expect("failed to load rom");
+        let handle = machine.handle();
+        let events = handle.subscribe();
+        let runner = thread::spawn(move || machine.run());
+        let report = events
+            .iter()
+            .find_map(|event| match event {
+                Event::Watchdog(report) => Some(report),
+                _ => None,
+            })
+            .expect("watchdog did not report the stall");
+        assert!(report.stalled_ticks >= 10);
+        assert_eq!(report.pc, PROGRAM_START as u16);
+        handle.stop().expect("failed to send command");
+        assert_eq!(runner.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn run_returns_errors() {
+        let mut machine = Chip8::with_clock(
+            Box::new(Clock::new(Duration::from_millis(1))),
+            TickRate::NTSC,
+        )
+        .expect("failed to build machine");
+        machine.load_rom(&[0x00, 0xEE]).expect("failed to load rom");
+        assert_eq!(
+            machine.run(),
+            Err(Chip8Error::Cpu(CpuError::StackUnderflow {
+                pc: PROGRAM_START as u16
+            }))
+        );
+    }
+
+    #[test]
+    fn savestates_round_trip() {
+        // V0 = random, I = font for V0, draw it, V1 += 1, jump back to the start
+        let rom = [0xC0, 0xFF, 0xF0, 0x29, 0xD0, 0x05, 0x71, 0x01, 0x12, 0x00];
+        let mut machine = manual_machine(&rom);
+        machine.step_n(7).expect("steps failed");
+        machine.timers().set_sound_timer(20);
+        let bytes = machine.save_state().to_bytes();
+
+        machine.step_n(12).expect("steps failed");
+        let expected = machine.save_state();
+
+        let state = SaveState::from_bytes(&bytes).expect("failed to decode savestate");
+        machine.load_state(state).expect("failed to load savestate");
+        assert_eq!(machine.timers().retrieve_sound_timer(), 20);
+        machine.step_n(12).expect("steps failed");
+        assert_eq!(
+            machine.save_state(),
+            expected,
+            "machine diverged after loading"
+        );
+
+        assert_eq!(
+            SaveState::from_bytes(&bytes[..bytes.len() / 2]),
+            Err(Chip8Error::State("savestate is corrupt"))
+        );
+        let mut other = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .ram_size(0x2000)
+            .build()
+            .expect("failed to build machine");
+        assert_eq!(
+            other.load_state(expected),
+            Err(Chip8Error::State("savestate has a different amount of ram"))
+        );
+    }
+
+    #[test]
+    fn savestates_are_checked_before_loading() {
+        let mut machine = manual_machine(&[0x60, 0x05]);
+        let state = machine.save_state();
+        let mut bytes = state.to_bytes();
+        let header = SaveState::read_header(&bytes).expect("failed to read header");
+        assert_eq!(header.version, FORMAT_VERSION);
+        assert_eq!(header.variant, Variant::Chip8);
+        assert_eq!(header.rom_hash, rom_hash(&[0x60, 0x05]));
+
+        // the version follows the four byte magic
+        bytes[4] = bytes[4].wrapping_add(1);
+        assert_eq!(
+            SaveState::from_bytes(&bytes),
+            Err(Chip8Error::State(
+                "savestate was written by an incompatible version"
+            ))
+        );
+        assert_eq!(
+            SaveState::from_bytes(b"not a savestate at all"),
+            Err(Chip8Error::State("not a savestate"))
+        );
+
+        machine.load_rom(&[0x12, 0x00]).expect("failed to load rom");
+        assert_eq!(
+            machine.load_state(state.clone()),
+            Err(Chip8Error::State("savestate is from a different rom"))
+        );
+        let mut other = state.clone();
+        other.variant = Variant::SuperChip;
+        assert_eq!(
+            machine.load_state(other),
+            Err(Chip8Error::State("savestate is from a different variant"))
+        );
+    }
+
+    #[test]
+    fn hotkeys_quicksave_and_quickload() {
+        let root = std::env::temp_dir().join(format!("chip8-quicksave-{}", std::process::id()));
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .save_directory(&root)
+            .build()
+            .expect("failed to build machine");
+        // V0 += 1, jump back
+        machine
+            .load_rom(&[0x70, 0x01, 0x12, 0x00])
+            .expect("failed to load rom");
+        let events = machine.subscribe();
+        machine.apply_hotkey(Hotkey::NextSlot).unwrap();
+        machine.step_n(2).expect("steps failed");
+        machine
+            .apply_hotkey(Hotkey::QuickSave)
+            .expect("quicksave failed");
+        machine.step_n(4).expect("steps failed");
+        assert_eq!(machine.cpu().registers()[0], 3);
+        machine
+            .apply_hotkey(Hotkey::QuickLoad)
+            .expect("quickload failed");
+        assert_eq!(machine.cpu().registers()[0], 1);
+        let slots: Vec<_> = events
+            .try_iter()
+            .filter(|event| matches!(event, Event::SlotSaved(_) | Event::SlotLoaded(_)))
+            .collect();
+        assert_eq!(slots, vec![Event::SlotSaved(1), Event::SlotLoaded(1)]);
+        std::fs::remove_dir_all(root).unwrap();
+
+        let mut unsaved = manual_machine(&[0x12, 0x00]);
+        assert_eq!(
+            unsaved.quicksave(),
+            Err(Chip8Error::State("no save directory is set"))
+        );
+    }
+
+    #[test]
+    fn rewinds_to_earlier_frames() {
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .rewind(4, 2)
+            .build()
+            .expect("failed to build machine");
+        // V0 += 1, jump back
+        machine
+            .load_rom(&[0x70, 0x01, 0x12, 0x00])
+            .expect("failed to load rom");
+        let mut states = vec![machine.save_state()];
+        for _ in 0..9 {
+            machine.run_frame().expect("frame failed");
+            states.push(machine.save_state());
+        }
+        // states are kept at frames 3, 5, 7, and 9, having dropped frame 1
+        assert_eq!(machine.rewind(4), Ok(4));
+        assert_eq!(machine.frame_count(), 5);
+        assert_eq!(machine.save_state().cpu, states[5].cpu);
+
+        machine.apply_hotkey(Hotkey::Rewind).unwrap();
+        machine.run_frame().expect("frame failed");
+        assert_eq!(machine.frame_count(), 3);
+        machine.run_frame().expect("frame failed");
+        assert_eq!(machine.frame_count(), 3, "rewound past the oldest state");
+        machine.release_hotkey(Hotkey::Rewind).unwrap();
+        machine.run_frame().expect("frame failed");
+        assert_eq!(machine.frame_count(), 4);
+        assert_eq!(machine.save_state().cpu, states[4].cpu);
+
+        assert_eq!(
+            manual_machine(&[0x12, 0x00]).rewind(1),
+            Err(Chip8Error::State("rewind is not enabled"))
+        );
+    }
+
+    #[test]
+    fn movies_replay_runs() {
+        // V1 = 5, then forever: V0 = random, count frames with key 5 held in V2
+        let rom = [0x61, 0x05, 0xC0, 0xFF, 0xE1, 0xA1, 0x72, 0x01, 0x12, 0x02];
+        let build = |seed| {
+            let mut machine = Chip8::builder()
+                .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+                .seed(seed)
+                .build()
+                .expect("failed to build machine");
+            machine.load_rom(&rom).expect("failed to load rom");
+            machine
+        };
+        let mut machine = build(1);
+        machine.start_recording().unwrap();
+        for frame in 0..10 {
+            if frame == 3 {
+                machine.keypad_mut().press(5);
+            } else if frame == 6 {
+                machine.keypad_mut().release(5);
+            }
+            machine.run_frame().expect("frame failed");
+        }
+        let movie = machine.stop_recording().expect("nothing was recorded");
+        assert_eq!(movie.len(), 10);
+        assert_eq!(movie.seed, Some(1));
+        let expected = machine.save_state();
+        assert_ne!(expected.cpu.registers()[2], 0, "key presses were not seen");
+
+        let mut replay = build(2);
+        let events = replay.subscribe();
+        let decoded = Movie::from_bytes(&movie.to_bytes()).expect("failed to decode movie");
+        replay.play_movie(decoded).expect("failed to play movie");
+        assert_eq!(
+            replay.load_state(expected.clone()),
+            Err(Chip8Error::State("a movie is recording or playing"))
+        );
+        for _ in 0..10 {
+            replay.run_frame().expect("frame failed");
+        }
+        assert_eq!(replay.save_state(), expected, "replay diverged");
+        assert_eq!(replay.playback_desync(), None);
+        replay.run_frame().expect("frame failed");
+        assert!(!replay.is_playing());
+        assert!(events
+            .try_iter()
+            .any(|event| event == Event::PlaybackFinished));
+
+        // holding the key a frame early changes the count at the end of the second frame
+        let mut tampered = movie;
+        tampered.inputs[1] = 1 << 5;
+        replay.play_movie(tampered).expect("failed to play movie");
+        for _ in 0..4 {
+            replay.run_frame().expect("frame failed");
+        }
+        let report = DesyncReport {
+            frame: 2,
+            last_verified: Some(1),
+        };
+        assert_eq!(replay.playback_desync(), Some(report));
+        let desyncs: Vec<_> = events
+            .try_iter()
+            .filter(|event| matches!(event, Event::Desync(_)))
+            .collect();
+        assert_eq!(
+            desyncs,
+            vec![Event::Desync(report)],
+            "desync is not reported once"
+        );
+    }
+
+    #[test]
+    fn movies_of_other_programs_leave_recordings_alone() {
+        let mut other = manual_machine(&[0x70, 0x01, 0x12, 0x00]);
+        other.start_recording().unwrap();
+        other.run_frame().expect("frame failed");
+        let movie = other.stop_recording().expect("nothing was recorded");
+
+        let mut machine = manual_machine(&[0x12, 0x00]);
+        machine.start_recording().unwrap();
+        machine.run_frame().expect("frame failed");
+        assert_eq!(
+            machine.play_movie(movie),
+            Err(Chip8Error::State("savestate is from a different rom"))
+        );
+        assert!(machine.is_recording(), "recording is dropped");
+        assert!(!machine.is_playing());
+        machine.run_frame().expect("frame failed");
+        assert_eq!(machine.stop_recording().map(|movie| movie.len()), Some(2));
+    }
+
+    #[test]
+    fn frame_advance_runs_one_frame_while_paused() {
+        // V0 += 1, jump back
+        let mut machine = manual_machine(&[0x70, 0x01, 0x12, 0x00]);
+        assert_eq!(
+            machine.frame_advance(),
+            Err(Chip8Error::State("frame advance only works while paused"))
+        );
+        machine.apply_hotkey(Hotkey::TogglePause).unwrap();
+        machine.timers().set_delay_timer(5);
+        machine
+            .apply_hotkey(Hotkey::FrameAdvance)
+            .expect("frame advance failed");
+        let per_tick = TickRate::NTSC.per_tick(INSTRUCTIONS_PER_SECOND);
+        assert_eq!(machine.cpu().registers()[0] as u32, per_tick / 2);
+        assert_eq!(machine.timers().retrieve_delay_timer(), 4);
+        assert_eq!(machine.frame_count(), 1);
+        assert_eq!(machine.state(), MachineState::Paused);
+    }
+
+    #[test]
+    fn detects_jumps_to_themselves() {
+        // V0 = 1, jump back to the start, then halt
+        let mut machine = manual_machine(&[0x60, 0x01, 0x12, 0x00, 0x12, 0x04]);
+        machine.step_n(2).expect("steps failed");
+        assert!(
+            !machine.in_infinite_loop(),
+            "a loop back to the start is infinite"
+        );
+        let mut machine = manual_machine(&[0x60, 0x01, 0x12, 0x04, 0x12, 0x04]);
+        machine.step_n(2).expect("steps failed");
+        assert!(machine.in_infinite_loop());
+    }
+
+    #[test]
+    fn autosaves_resume_on_the_next_load() {
+        let root = std::env::temp_dir().join(format!("chip8-autosave-{}", std::process::id()));
+        let build = || {
+            Chip8::builder()
+                .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+                .save_directory(&root)
+                .autosave(true)
+                .build()
+                .expect("failed to build machine")
+        };
+        // V0 += 1, jump back
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let mut machine = build();
+        machine.load_rom(&rom).expect("failed to load rom");
+        machine.step_n(5).expect("steps failed");
+        machine.stop().expect("failed to stop machine");
+
+        let mut machine = build();
+        let events = machine.subscribe();
+        machine.load_rom(&rom).expect("failed to load rom");
+        assert!(events
+            .try_iter()
+            .any(|event| event == Event::AutosaveAvailable));
+        machine
+            .start_recording()
+            .expect("failed to start recording");
+        assert!(machine.resume_autosave().is_err());
+        assert!(
+            machine.has_autosave(),
+            "autosave is lost when it can't be loaded"
+        );
+        machine.stop_recording();
+        machine.resume_autosave().expect("failed to resume");
+        assert_eq!(machine.cpu().registers()[0], 3);
+        assert!(!machine.has_autosave(), "autosave is kept after resuming");
+        drop(machine);
+        std::fs::remove_dir_all(root).unwrap();
+
+        assert!(Chip8::builder().autosave(true).build().is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::quirks::{Quirks, Variant};
+
+use super::{savestate::rom_hash, Chip8Error, SaveState};
+
+/// Marks the start of an encoded movie.
+const MAGIC: [u8; 4] = *b"C8MV";
+
+/// The version of the encoding written by `Movie::to_bytes()`.
+pub const MOVIE_FORMAT_VERSION: u16 = 3;
+
+/// How many frames apart movies check the state of the machine by default. Checking every frame pins a desync to
+/// the frame it happened on, for eight bytes a frame.
+pub const DEFAULT_CHECKPOINT_INTERVAL: u32 = 1;
+
+/// A recording of a run: where it started, and which keys were held on every frame after.
+///
+/// Playing a movie back loads its start state and replays its input, which repeats the run exactly as long as the
+/// machine is driven the same way. Drive it with `run_frame()`, as `run()` counts the timers down on the clock's
+/// own thread, out of step with the instructions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Movie {
+    /// The SHA-256 hash of the program the movie was recorded on.
+    pub rom_hash: [u8; 32],
+    pub variant: Variant,
+    pub quirks: Quirks,
+    /// The seed the machine's random number generator was built with, if it was given one.
+    pub seed: Option<u64>,
+    pub start: SaveState,
+    /// The pressed keys at the start of each frame, as keypad bitmasks.
+    pub inputs: Vec<u16>,
+    /// How many frames apart the checkpoints are.
+    pub checkpoint_interval: u32,
+    /// The digest of the machine's state at the end of every `checkpoint_interval`th frame.
+    pub checkpoints: Vec<u64>,
+}
+
+/// Where a movie's playback stopped matching the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DesyncReport {
+    /// The frame whose checkpoint didn't match, counting from 1.
+    pub frame: usize,
+    /// The last frame whose checkpoint matched, if any did. The divergence happened in the frames after it.
+    pub last_verified: Option<usize>,
+}
+
+impl Movie {
+    /// Starts a movie from a state, with no frames recorded yet, checkpointing every `checkpoint_interval` frames.
+    pub fn new(start: SaveState, seed: Option<u64>, checkpoint_interval: u32) -> Movie {
+        Movie {
+            rom_hash: rom_hash(&start.rom),
+            variant: start.variant,
+            quirks: start.cpu.quirks(),
+            seed,
+            start,
+            inputs: Vec::new(),
+            checkpoint_interval,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Whether a checkpoint belongs at the end of `frame`, counting from 1.
+    pub fn is_checkpoint(&self, frame: usize) -> bool {
+        frame.is_multiple_of(self.checkpoint_interval as usize)
+    }
+
+    /// The checkpoint expected at the end of `frame`, if there is one.
+    pub fn checkpoint(&self, frame: usize) -> Option<u64> {
+        if !self.is_checkpoint(frame) || frame == 0 {
+            return None;
+        }
+        self.checkpoints
+            .get(frame / self.checkpoint_interval as usize - 1)
+            .copied()
+    }
+
+    /// How many frames the movie lasts.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(MOVIE_FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self).expect("movies are always serializable");
+        bytes
+    }
+
+    /// Decodes a movie written by `to_bytes()`, checking it hangs together.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Movie, Chip8Error> {
+        if bytes.len() < 6 || bytes[..4] != MAGIC {
+            return Err(Chip8Error::State("not a movie"));
+        }
+        if u16::from_le_bytes([bytes[4], bytes[5]]) != MOVIE_FORMAT_VERSION {
+            return Err(Chip8Error::State(
+                "movie was written by an incompatible version",
+            ));
+        }
+        let movie: Movie =
+            bincode::deserialize(&bytes[6..]).map_err(|_| Chip8Error::State("movie is corrupt"))?;
+        movie.start.validate()?;
+        if movie.checkpoint_interval == 0
+            || movie.rom_hash != rom_hash(&movie.start.rom)
+            || movie.variant != movie.start.variant
+            || movie.quirks != movie.start.cpu.quirks()
+        {
+            return Err(Chip8Error::State("movie is corrupt"));
+        }
+        Ok(movie)
+    }
+}
+
+/// A movie being played back, and how far through it is.
+#[derive(Debug)]
+pub(super) struct Playback {
+    pub(super) movie: Movie,
+    pub(super) frame: usize,
+    pub(super) last_verified: Option<usize>,
+    /// Where playback first diverged, if it has.
+    pub(super) desync: Option<DesyncReport>,
+}
+
+impl Playback {
+    /// Takes the input for the next frame, if the movie hasn't finished.
+    pub(super) fn next_input(&mut self) -> Option<u16> {
+        let input = self.movie.inputs.get(self.frame).copied();
+        self.frame += 1;
+        input
+    }
+
+    /// Checks the digest of the machine's state at the end of the current frame against the movie's checkpoint,
+    /// giving a report the first time they differ.
+    pub(super) fn verify(&mut self, digest: u64) -> Option<DesyncReport> {
+        let expected = self.movie.checkpoint(self.frame)?;
+        if self.desync.is_some() {
+            return None;
+        }
+        if expected == digest {
+            self.last_verified = Some(self.frame);
+            return None;
+        }
+        self.desync = Some(DesyncReport {
+            frame: self.frame,
+            last_verified: self.last_verified,
+        });
+        self.desync
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::decoder::Instruction;
+
+/// How much of a program's time went to a subroutine, counted in instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoutineProfile {
+    /// The address the subroutine was called at, or `None` for code run outside any subroutine.
+    pub entry: Option<u16>,
+    pub calls: u64,
+    /// The instructions run in the subroutine itself, including its return.
+    pub own: u64,
+    /// The instructions run in the subroutine and everything it called.
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AddressCount {
+    count: u64,
+    instruction: Instruction,
+}
+
+/// Counts the instructions a machine runs, at each address and in each subroutine, for finding where a program
+/// spends its time.
+///
+/// Subroutines are followed through their calls and returns, so one that's jumped into rather than called counts
+/// towards its caller. Every instruction is counted, with nothing sampled or estimated.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    instructions: u64,
+    addresses: HashMap<u16, AddressCount>,
+    routines: HashMap<Option<u16>, RoutineProfile>,
+    /// The entries of the subroutines being run, innermost last.
+    calls: Vec<u16>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Counts an instruction that just ran at `pc`.
+    pub(super) fn record(&mut self, pc: u16, instruction: Instruction) {
+        self.instructions += 1;
+        self.addresses
+            .entry(pc)
+            .and_modify(|address| address.count += 1)
+            .or_insert(AddressCount {
+                count: 1,
+                instruction,
+            });
+        let current = self.calls.last().copied();
+        self.routine(current).own += 1;
+        // a recursive subroutine is on the stack more than once, but its total only counts each instruction once
+        let mut counted = Vec::with_capacity(self.calls.len() + 1);
+        for entry in self.calls.iter().map(|&entry| Some(entry)).chain([None]) {
+            if !counted.contains(&entry) {
+                counted.push(entry);
+            }
+        }
+        for entry in counted {
+            self.routine(entry).total += 1;
+        }
+        match instruction {
+            Instruction::Call { address } => {
+                self.calls.push(address);
+                self.routine(Some(address)).calls += 1;
+            }
+            Instruction::Return => {
+                self.calls.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn routine(&mut self, entry: Option<u16>) -> &mut RoutineProfile {
+        self.routines.entry(entry).or_insert(RoutineProfile {
+            entry,
+            calls: 0,
+            own: 0,
+            total: 0,
+        })
+    }
+
+    /// How many instructions have been counted.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// How many times the instruction at each address ran, the most first.
+    pub fn hot_spots(&self) -> Vec<(u16, u64)> {
+        let mut spots: Vec<(u16, u64)> = self
+            .addresses
+            .iter()
+            .map(|(&pc, address)| (pc, address.count))
+            .collect();
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots
+    }
+
+    /// How many times the instruction at an address ran.
+    pub fn count_at(&self, pc: u16) -> u64 {
+        self.addresses.get(&pc).map_or(0, |address| address.count)
+    }
+
+    /// Each subroutine, the most instructions run in it and what it called first.
+    pub fn routines(&self) -> Vec<RoutineProfile> {
+        let mut routines: Vec<RoutineProfile> = self.routines.values().copied().collect();
+        routines.sort_by(|a, b| b.total.cmp(&a.total).then(a.entry.cmp(&b.entry)));
+        routines
+    }
+
+    /// Forgets everything counted so far.
+    pub fn clear(&mut self) {
+        *self = Profiler::new();
+    }
+
+    /// Writes up the `limit` hottest addresses and every subroutine as tables, with each one's share of the
+    /// instructions run.
+    pub fn report(&self, limit: usize) -> String {
+        let share = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+        let mut report = format!("{} instructions\n\nhot spots\n", self.instructions);
+        report.push_str("address       count   share  instruction\n");
+        for (pc, count) in self.hot_spots().into_iter().take(limit) {
+            let instruction = self.addresses[&pc].instruction;
+            let _ = writeln!(
+                report,
+                "0x{pc:03X}  {count:>12} {:>6.1}%  {instruction}",
+                share(count)
+            );
+        }
+        report.push_str("\nsubroutines\n");
+        report.push_str("entry        calls           own           total   share\n");
+        for routine in self.routines() {
+            let entry = match routine.entry {
+                Some(entry) => format!("0x{entry:03X}"),
+                None => "top  ".to_string(),
+            };
+            let _ = writeln!(
+                report,
+                "{entry}  {:>11}  {:>12}  {:>14} {:>6.1}%",
+                routine.calls,
+                routine.own,
+                routine.total,
+                share(routine.total)
+            );
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_by_address_and_subroutine() {
+        let mut profiler = Profiler::new();
+        // the top level calls 0x300 twice, which calls 0x400 once each time
+        for _ in 0..2 {
+            profiler.record(0x200, Instruction::Call { address: 0x300 });
+            profiler.record(0x300, Instruction::Call { address: 0x400 });
+            profiler.record(0x400, Instruction::Return);
+            profiler.record(0x302, Instruction::Return);
+        }
+        profiler.record(0x202, Instruction::Jump { address: 0x202 });
+        assert_eq!(profiler.instructions(), 9);
+        assert_eq!(profiler.count_at(0x400), 2);
+        assert_eq!(profiler.hot_spots()[0], (0x200, 2));
+        assert_eq!(
+            profiler.routines(),
+            [
+                RoutineProfile {
+                    entry: None,
+                    calls: 0,
+                    own: 3,
+                    total: 9
+                },
+                RoutineProfile {
+                    entry: Some(0x300),
+                    calls: 2,
+                    own: 4,
+                    total: 6
+                },
+                RoutineProfile {
+                    entry: Some(0x400),
+                    calls: 2,
+                    own: 2,
+                    total: 2
+                },
+            ]
+        );
+        let report = profiler.report(1);
+        assert!(
+            report.contains("0x200             2   22.2%  CALL 0x300"),
+            "{report}"
+        );
+    }
+}
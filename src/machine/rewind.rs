@@ -0,0 +1,157 @@
+use std::collections::VecDeque;
+
+use super::{compress, SaveState};
+
+/// A ring buffer of savestates taken every few frames, for stepping the machine back in time.
+///
+/// Once `depth` states are held, the oldest is dropped for each new one, so the buffer reaches back at most
+/// `depth * interval` frames. Only the newest state is kept whole; each older one is kept as the difference from
+/// the state after it, compressed if the `compression` feature is enabled.
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    /// The newest state, encoded, along with the frame it was taken at.
+    newest: Option<(u64, Vec<u8>)>,
+    /// The older states oldest first, each a delta against the state after it, along with its frame.
+    older: VecDeque<(u64, Vec<u8>)>,
+    depth: usize,
+    interval: u64,
+}
+
+impl RewindBuffer {
+    pub fn new(depth: usize, interval: u64) -> RewindBuffer {
+        RewindBuffer {
+            newest: None,
+            older: VecDeque::with_capacity(depth),
+            depth,
+            interval,
+        }
+    }
+
+    /// Whether a state should be taken at `frame`, for callers that want to avoid capturing one needlessly.
+    pub fn is_due(&self, frame: u64) -> bool {
+        match &self.newest {
+            Some((last, _)) => frame >= last + self.interval,
+            None => true,
+        }
+    }
+
+    /// Keeps the state of the machine at `frame`, if one is due.
+    pub fn record(&mut self, frame: u64, state: &SaveState) {
+        if self.is_due(frame) {
+            self.push(frame, state);
+        }
+    }
+
+    /// Keeps the state of the machine at `frame`, whether or not one is due, replacing the newest state if it's from
+    /// the same frame.
+    pub fn push(&mut self, frame: u64, state: &SaveState) {
+        let encoded = state.encode();
+        if let Some((at, newest)) = self.newest.take().filter(|&(at, _)| at < frame) {
+            let delta = compress::delta(&newest, &encoded);
+            self.older.push_back((at, compress::compress(delta)));
+            while self.older.len() >= self.depth {
+                self.older.pop_front();
+            }
+        }
+        self.newest = Some((frame, encoded));
+    }
+
+    /// Finds the latest state from at least `frames` frames before `frame`, dropping every state after it.
+    ///
+    /// Gives the oldest state if none go back that far, along with the frame it was taken at.
+    pub fn rewind(&mut self, frame: u64, frames: u64) -> Option<(u64, SaveState)> {
+        let target = frame.saturating_sub(frames);
+        let (mut at, mut newest) = self.newest.take()?;
+        while at > target {
+            let Some((older_at, delta)) = self.older.pop_back() else {
+                break;
+            };
+            let delta = compress::decompress(&delta).expect("rewind states are never corrupt");
+            newest = compress::undelta(&delta, &newest);
+            at = older_at;
+        }
+        let state = SaveState::decode(&newest).expect("rewind states are never corrupt");
+        self.newest = Some((at, newest));
+        Some((at, state))
+    }
+
+    /// The frame the oldest state was taken at, which is as far back as the buffer can go.
+    pub fn oldest(&self) -> Option<u64> {
+        self.older
+            .front()
+            .or(self.newest.as_ref())
+            .map(|&(frame, _)| frame)
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.older.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.older.len() + self.newest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// How many bytes the kept states take up.
+    pub fn size_in_bytes(&self) -> usize {
+        let newest = self.newest.as_ref().map_or(0, |(_, bytes)| bytes.len());
+        newest
+            + self
+                .older
+                .iter()
+                .map(|(_, bytes)| bytes.len())
+                .sum::<usize>()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Chip8,
+        memory::MAX_RAM_SIZE,
+    };
+
+    use super::*;
+
+    #[test]
+    fn keeps_deltas_of_older_states() {
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .ram_size(MAX_RAM_SIZE)
+            .build()
+            .expect("failed to build machine");
+        // V0 += 1, jump back
+        machine
+            .load_rom(&[0x70, 0x01, 0x12, 0x00])
+            .expect("failed to load rom");
+        let mut buffer = RewindBuffer::new(3, 1);
+        let mut states = Vec::new();
+        for frame in 0..4 {
+            machine.step().expect("step failed");
+            states.push(machine.save_state());
+            buffer.record(frame, &states[frame as usize]);
+        }
+        assert_eq!(buffer.len(), 3, "the oldest state is not dropped");
+        assert_eq!(buffer.oldest(), Some(1));
+        if compress::ENABLED {
+            let whole = states[3].encode().len();
+            assert!(buffer.size_in_bytes() < whole + whole / 10);
+        }
+        assert_eq!(buffer.rewind(3, 1), Some((2, states[2].clone())));
+        assert_eq!(buffer.rewind(2, 5), Some((1, states[1].clone())));
+        assert_eq!(buffer.len(), 1);
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    display::{Frame, HEIGHT, WIDTH},
+    keypad::Keypad,
+    memory::{Memory, PROGRAM_START},
+    quirks::Variant,
+    rng::Rng,
+    system::{Cpu, TimerSnapshot},
+};
+
+use super::{compress, Chip8Error};
+
+/// Marks the start of an encoded savestate.
+const MAGIC: [u8; 4] = *b"C8ST";
+
+/// The version of the encoding written by `SaveState::to_bytes()`. Bump it whenever the layout of the state
+/// changes, so old states are refused rather than misread.
+pub const FORMAT_VERSION: u16 = 3;
+
+/// What an encoded savestate says about itself, readable without decoding the rest of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveHeader {
+    magic: [u8; 4],
+    pub version: u16,
+    pub variant: Variant,
+    /// The SHA-256 hash of the program the state was saved from.
+    pub rom_hash: [u8; 32],
+    /// Whether the rest of the savestate is compressed, which only builds with the `compression` feature can read.
+    pub compressed: bool,
+}
+
+/// Hashes a program, to tell whether a savestate belongs to it.
+pub fn rom_hash(rom: &[u8]) -> [u8; 32] {
+    Sha256::digest(rom).into()
+}
+
+/// Everything needed to put a machine back exactly as it was: memory, the CPU and its quirks, the timers, the
+/// screen, the keypad, and the random number generator.
+///
+/// Savestates are plain data, so they can be kept in memory or turned into bytes with `to_bytes()` and written out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveState {
+    pub variant: Variant,
+    /// The loaded program, so resetting after loading the state still works.
+    pub rom: Vec<u8>,
+    pub cpu: Cpu,
+    pub memory: Memory,
+    pub timers: TimerSnapshot,
+    pub frame: Frame,
+    pub keypad: Keypad,
+    pub rng: Rng,
+}
+
+impl SaveState {
+    /// Describes the savestate as its encoding will.
+    pub fn header(&self) -> SaveHeader {
+        SaveHeader {
+            magic: MAGIC,
+            version: FORMAT_VERSION,
+            variant: self.variant,
+            rom_hash: rom_hash(&self.rom),
+            compressed: compress::ENABLED,
+        }
+    }
+
+    /// Encodes the savestate in a compact binary form, after a header giving the format version, the variant, and
+    /// a hash of the program. The state is compressed if the `compression` feature is enabled.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            bincode::serialize(&self.header()).expect("savestates are always serializable");
+        bytes.extend(compress::compress(self.encode()));
+        bytes
+    }
+
+    /// Encodes just the state, without a header or compression.
+    ///
+    /// Every field encodes to a fixed size, so this comes to the same length for every state of a machine running a
+    /// given program, for hosts that keep states in buffers of a fixed size.
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("savestates are always serializable")
+    }
+
+    /// Decodes a state written by `encode()`.
+    pub fn decode(bytes: &[u8]) -> Result<SaveState, Chip8Error> {
+        let state: SaveState =
+            bincode::deserialize(bytes).map_err(|_| Chip8Error::State("savestate is corrupt"))?;
+        state.validate()?;
+        Ok(state)
+    }
+
+    /// Reads the header of an encoded savestate, checking it's a savestate this version can read.
+    pub fn read_header(bytes: &[u8]) -> Result<SaveHeader, Chip8Error> {
+        SaveState::split_header(bytes).map(|(header, _)| header)
+    }
+
+    fn split_header(mut bytes: &[u8]) -> Result<(SaveHeader, &[u8]), Chip8Error> {
+        let header: SaveHeader = bincode::deserialize_from(&mut bytes)
+            .map_err(|_| Chip8Error::State("not a savestate"))?;
+        if header.magic != MAGIC {
+            return Err(Chip8Error::State("not a savestate"));
+        }
+        if header.version != FORMAT_VERSION {
+            return Err(Chip8Error::State(
+                "savestate was written by an incompatible version",
+            ));
+        }
+        Ok((header, bytes))
+    }
+
+    /// Decodes a savestate written by `to_bytes()`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveState, Chip8Error> {
+        let (header, body) = SaveState::split_header(bytes)?;
+        let state = if header.compressed {
+            if !compress::ENABLED {
+                return Err(Chip8Error::State(
+                    "savestate is compressed, and this build can't decompress it",
+                ));
+            }
+            SaveState::decode(&compress::decompress(body)?)?
+        } else {
+            SaveState::decode(body)?
+        };
+        if state.variant != header.variant || rom_hash(&state.rom) != header.rom_hash {
+            return Err(Chip8Error::State("savestate is corrupt"));
+        }
+        Ok(state)
+    }
+
+    /// A short hash of the whole state, for telling cheaply whether two machines are in the same state.
+    pub fn digest(&self) -> u64 {
+        let hash = Sha256::digest(self.encode());
+        u64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
+
+    /// Checks the state was saved from `rom` running on `variant`, so it can be loaded into that machine.
+    pub fn check_compatible(&self, variant: Variant, rom: &[u8]) -> Result<(), Chip8Error> {
+        if self.variant != variant {
+            return Err(Chip8Error::State("savestate is from a different variant"));
+        }
+        if self.rom != rom {
+            return Err(Chip8Error::State("savestate is from a different rom"));
+        }
+        Ok(())
+    }
+
+    /// Checks that the state could have come from a real machine, so loading it can't panic later.
+    pub(super) fn validate(&self) -> Result<(), Chip8Error> {
+        let stack = self.cpu.stack();
+        if stack.depth() > stack.capacity() {
+            return Err(Chip8Error::State("stack is deeper than it can be"));
+        }
+        if self.frame.pixels().len() != WIDTH * HEIGHT {
+            return Err(Chip8Error::State("screen is the wrong size"));
+        }
+        if self.rom.len() > self.memory.len().saturating_sub(PROGRAM_START) {
+            return Err(Chip8Error::State("rom does not fit in memory"));
+        }
+        Ok(())
+    }
+}
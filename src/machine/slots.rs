@@ -0,0 +1,201 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{savestate::rom_hash, Chip8Error, SaveState};
+
+/// How many numbered slots each program gets.
+pub const SLOT_COUNT: u8 = 10;
+
+/// Numbered savestate slots on disk, kept in a directory per program so each game has its own set.
+///
+/// Slots live at `<root>/<rom id>/slot<n>.state`, where the ROM id comes from a hash of the program. One slot is
+/// selected at a time, which is where quicksaves and quickloads go. Each program also has an autosave beside its
+/// slots, written when the machine shuts down.
+#[derive(Debug, Clone)]
+pub struct SaveSlots {
+    root: PathBuf,
+    selected: u8,
+}
+
+impl SaveSlots {
+    pub fn new(root: impl Into<PathBuf>) -> SaveSlots {
+        SaveSlots {
+            root: root.into(),
+            selected: 0,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The slot quicksaves and quickloads use.
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+
+    pub fn select(&mut self, slot: u8) -> Result<(), Chip8Error> {
+        check_slot(slot)?;
+        self.selected = slot;
+        Ok(())
+    }
+
+    /// Selects the next slot, wrapping around after the last.
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % SLOT_COUNT;
+    }
+
+    /// Selects the previous slot, wrapping around before the first.
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + SLOT_COUNT - 1) % SLOT_COUNT;
+    }
+
+    /// The directory holding a program's slots.
+    pub fn directory(&self, rom: &[u8]) -> PathBuf {
+        self.root.join(rom_id(rom))
+    }
+
+    /// Where a program's slot is stored.
+    pub fn path(&self, rom: &[u8], slot: u8) -> PathBuf {
+        self.directory(rom).join(format!("slot{slot}.state"))
+    }
+
+    /// Where a program's autosave is stored.
+    pub fn autosave_path(&self, rom: &[u8]) -> PathBuf {
+        self.directory(rom).join("autosave.state")
+    }
+
+    /// Writes a savestate to a slot of the program it was saved from, replacing what was there.
+    pub fn save(&self, slot: u8, state: &SaveState) -> Result<(), Chip8Error> {
+        check_slot(slot)?;
+        self.write(&self.path(&state.rom, slot), state)
+    }
+
+    /// Reads a savestate from one of a program's slots.
+    pub fn load(&self, rom: &[u8], slot: u8) -> Result<SaveState, Chip8Error> {
+        check_slot(slot)?;
+        read(&self.path(rom, slot))
+    }
+
+    /// Writes the autosave of the program a savestate was saved from.
+    pub fn save_autosave(&self, state: &SaveState) -> Result<(), Chip8Error> {
+        self.write(&self.autosave_path(&state.rom), state)
+    }
+
+    /// Reads a program's autosave.
+    pub fn load_autosave(&self, rom: &[u8]) -> Result<SaveState, Chip8Error> {
+        read(&self.autosave_path(rom))
+    }
+
+    pub fn has_autosave(&self, rom: &[u8]) -> bool {
+        self.autosave_path(rom).is_file()
+    }
+
+    /// Deletes a program's autosave, if it has one.
+    pub fn remove_autosave(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        match fs::remove_file(self.autosave_path(rom)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                Err(Chip8Error::State("could not remove autosave"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&self, path: &Path, state: &SaveState) -> Result<(), Chip8Error> {
+        let write = || -> io::Result<()> {
+            fs::create_dir_all(self.directory(&state.rom))?;
+            // write beside the slot and move it into place, so a crash never leaves half a savestate behind
+            let partial = path.with_extension("partial");
+            fs::write(&partial, state.to_bytes())?;
+            fs::rename(&partial, path)
+        };
+        write().map_err(|_| Chip8Error::State("could not write savestate"))
+    }
+
+    /// The slots a program has saved in.
+    pub fn occupied(&self, rom: &[u8]) -> Vec<u8> {
+        (0..SLOT_COUNT)
+            .filter(|&slot| self.path(rom, slot).is_file())
+            .collect()
+    }
+}
+
+fn read(path: &Path) -> Result<SaveState, Chip8Error> {
+    let bytes = fs::read(path).map_err(|error| match error.kind() {
+        io::ErrorKind::NotFound => Chip8Error::State("savestate slot is empty"),
+        _ => Chip8Error::State("could not read savestate slot"),
+    })?;
+    SaveState::from_bytes(&bytes)
+}
+
+fn check_slot(slot: u8) -> Result<(), Chip8Error> {
+    if slot < SLOT_COUNT {
+        Ok(())
+    } else {
+        Err(Chip8Error::State("no such savestate slot"))
+    }
+}
+
+/// A short name for a program that stays the same across runs, taken from the start of its SHA-256 hash.
+pub fn rom_id(rom: &[u8]) -> String {
+    rom_hash(rom)[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Chip8,
+    };
+
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("chip8-slots-{}-{nanos}", std::process::id()))
+    }
+
+    #[test]
+    fn slots_are_kept_per_rom() {
+        let root = temp_root();
+        let slots = SaveSlots::new(&root);
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        machine.load_rom(&[0x60, 0x05]).expect("failed to load rom");
+        machine.step().expect("step failed");
+        let state = machine.save_state();
+        slots.save(3, &state).expect("failed to save slot");
+
+        assert_eq!(slots.occupied(&state.rom), vec![3]);
+        assert_eq!(slots.load(&state.rom, 3), Ok(state));
+        assert_eq!(
+            slots.load(&[0x12, 0x00], 3),
+            Err(Chip8Error::State("savestate slot is empty"))
+        );
+        assert_eq!(
+            slots.save(SLOT_COUNT, &machine.save_state()),
+            Err(Chip8Error::State("no such savestate slot"))
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn selection_wraps() {
+        let mut slots = SaveSlots::new("saves");
+        slots.select_previous();
+        assert_eq!(slots.selected(), SLOT_COUNT - 1);
+        slots.select_next();
+        assert_eq!(slots.selected(), 0);
+        assert!(slots.select(SLOT_COUNT).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// The first line of a state log, naming the columns of the rest. A log whose hashes are worked out differently
+/// gets a new header, so logs that can't be compared aren't.
+const HEADER: &str = "# chip8 state log v1: frame state screen";
+
+/// A log of a hash of the machine's state and one of its screen at the end of every frame, a line to each frame.
+/// Two runs of a program, such as by two versions of this crate, can be compared by their logs to find the first
+/// frame they differ on, without keeping whole savestates around.
+///
+/// Lines look like `42 3f1c0a9e5b7d2640 00a1b2c3d4e5f607`, with the hashes `Chip8::state_digest()` and
+/// `Frame::digest()` give.
+pub struct StateLog {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    /// The first error writing the log, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl StateLog {
+    /// Writes the log to `writer`, buffered.
+    pub fn to_writer(writer: impl Write + Send + 'static) -> StateLog {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        let mut log = StateLog {
+            writer: BufWriter::new(writer),
+            error: None,
+        };
+        log.write_line(format_args!("{HEADER}"));
+        log
+    }
+
+    /// Writes the log to a new file at `path`, replacing any already there.
+    pub fn to_file(path: impl AsRef<Path>) -> io::Result<StateLog> {
+        Ok(StateLog::to_writer(File::create(path)?))
+    }
+
+    /// Logs the end of the `frame`th frame.
+    pub(super) fn frame(&mut self, frame: u64, state: u64, screen: u64) {
+        self.write_line(format_args!("{frame} {state:016x} {screen:016x}"));
+    }
+
+    /// Writes out what's still buffered, reporting the first error writing the log, if there was one.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.error.is_none() {
+            if let Err(error) = self.writer.flush() {
+                self.error = Some(error);
+            }
+        }
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn write_line(&mut self, line: fmt::Arguments) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = writeln!(self.writer, "{line}") {
+            self.error = Some(error);
+        }
+    }
+}
+
+/// A line of a state log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateLogEntry {
+    pub frame: u64,
+    pub state: u64,
+    pub screen: u64,
+}
+
+impl StateLogEntry {
+    /// Reads the lines of a state log, as `StateLog` writes it.
+    pub fn parse_log(text: &str) -> Result<Vec<StateLogEntry>, String> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((_, line)) if line.starts_with("# chip8 state log") => {
+                return Err(format!("`{line}` is a state log this version can't read"))
+            }
+            _ => return Err("not a state log".to_string()),
+        }
+        let mut entries = Vec::new();
+        for (number, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let mut entry = || -> Option<StateLogEntry> {
+                let entry = StateLogEntry {
+                    frame: words.next()?.parse().ok()?,
+                    state: u64::from_str_radix(words.next()?, 16).ok()?,
+                    screen: u64::from_str_radix(words.next()?, 16).ok()?,
+                };
+                words.next().is_none().then_some(entry)
+            };
+            let entry =
+                entry().ok_or_else(|| format!("line {}: `{line}` is not a frame", number + 1))?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+/// Where two state logs first disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogDivergence {
+    /// The two runs finished a frame differently.
+    Frame {
+        frame: u64,
+        state: bool,
+        screen: bool,
+    },
+    /// The logs have different frames at the same line, as when one run rewound and the other didn't.
+    Frames { first: u64, second: u64 },
+    /// One log stops before the other, after the frames they both have agreed.
+    Ended {
+        /// 0 if the first log is the shorter, 1 if the second.
+        shorter: usize,
+        /// The last frame both logs have.
+        last: Option<u64>,
+    },
+}
+
+impl LogDivergence {
+    /// Compares two state logs line by line, finding where they first disagree, if anywhere.
+    pub fn find(first: &[StateLogEntry], second: &[StateLogEntry]) -> Option<LogDivergence> {
+        for (a, b) in first.iter().zip(second) {
+            if a.frame != b.frame {
+                return Some(LogDivergence::Frames {
+                    first: a.frame,
+                    second: b.frame,
+                });
+            }
+            if a != b {
+                return Some(LogDivergence::Frame {
+                    frame: a.frame,
+                    state: a.state != b.state,
+                    screen: a.screen != b.screen,
+                });
+            }
+        }
+        if first.len() == second.len() {
+            return None;
+        }
+        let shared = first.len().min(second.len());
+        Some(LogDivergence::Ended {
+            shorter: if first.len() < second.len() { 0 } else { 1 },
+            last: shared.checked_sub(1).map(|i| first[i].frame),
+        })
+    }
+}
+
+impl fmt::Display for LogDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogDivergence::Frame {
+                frame,
+                state,
+                screen,
+            } => {
+                let what = match (state, screen) {
+                    (true, true) => "the state and the screen differ",
+                    (true, false) => "the state differs",
+                    _ => "the screen differs",
+                };
+                write!(f, "frame {frame}: {what}")
+            }
+            LogDivergence::Frames { first, second } => {
+                write!(
+                    f,
+                    "frame {first} of the first log is frame {second} of the second"
+                )
+            }
+            LogDivergence::Ended { shorter, last } => {
+                let which = if *shorter == 0 { "first" } else { "second" };
+                match last {
+                    Some(last) => write!(f, "the {which} log ends after frame {last}"),
+                    None => write!(f, "the {which} log has no frames"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Chip8,
+        quirks::Variant,
+    };
+
+    use super::*;
+
+    /// A writer whose output can be read after the log has taken it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Logs three frames of a program with a variant's quirks, an instruction to each frame.
+    fn log(rom: &[u8], variant: Variant) -> Vec<StateLogEntry> {
+        let output = Shared::default();
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .instructions_per_second(60)
+            .quirks(variant.quirks())
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(rom).expect("failed to load rom");
+        machine.start_state_log(StateLog::to_writer(output.clone()));
+        for _ in 0..3 {
+            machine.run_frame().expect("failed to run");
+        }
+        let log = machine.stop_state_log().expect("no state log");
+        log.finish().expect("failed to write the log");
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        StateLogEntry::parse_log(&text).expect("failed to parse the log")
+    }
+
+    #[test]
+    fn finds_the_first_frame_two_logs_differ_on() {
+        // V1 = 3, SHR V0, V1, jump to self, which shifts V0 or V1 depending on the quirks
+        let rom = [0x61, 0x03, 0x80, 0x16, 0x12, 0x04];
+        let first = log(&rom, Variant::Chip8);
+        let second = log(&rom, Variant::SuperChip);
+        assert_eq!(first.len(), 3);
+        assert_eq!(LogDivergence::find(&first, &first), None);
+        assert_eq!(
+            LogDivergence::find(&first, &second),
+            Some(LogDivergence::Frame {
+                frame: 2,
+                state: true,
+                screen: false
+            })
+        );
+        assert_eq!(
+            LogDivergence::find(&first, &first[..1]),
+            Some(LogDivergence::Ended {
+                shorter: 1,
+                last: Some(1)
+            })
+        );
+        assert!(StateLogEntry::parse_log("1 00 00").is_err());
+    }
+}
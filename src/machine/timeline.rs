@@ -0,0 +1,329 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::decoder::Instruction;
+
+/// The rows of the timeline, which the trace viewers show as threads of one process.
+const FRAMES: u32 = 1;
+const CPU: u32 = 2;
+const SOUND: u32 = 3;
+const DEBUGGER: u32 = 4;
+
+/// An event in Chrome's trace event format, as Perfetto and `chrome://tracing` read it.
+#[derive(Debug, Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "str::is_empty")]
+    cat: &'a str,
+    /// The phase: `X` for a span, `B` and `E` for a span's beginning and end, `i` for an instant, and `M` for
+    /// naming a row.
+    ph: &'a str,
+    /// When the event happened, in microseconds of emulated time since the timeline started.
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u32,
+    /// How far an instant reaches across the viewer: `t` for its own row.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    s: Option<&'a str>,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    args: Value,
+}
+
+/// A timeline of what a machine did, written in Chrome's trace event format for viewing in Perfetto
+/// (<https://ui.perfetto.dev>) or `chrome://tracing`.
+///
+/// Frames are spans on a row of their own, as is the tone while it sounds. Sprites drawn, waits for a key, halts,
+/// and the debugger stopping are instants. Time is emulated time, counted from the instructions run at the machine's
+/// speed, so a timeline of a program comes out the same however fast it was really run.
+///
+/// `with_instructions()` adds a span for every instruction, which makes for very large files but shows exactly
+/// what each frame spent its time on.
+pub struct Timeline {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    instructions: bool,
+    /// How long an instruction takes, in microseconds.
+    step: f64,
+    /// The emulated time now, in microseconds.
+    now: f64,
+    frame_start: f64,
+    sounding: bool,
+    written: usize,
+    /// The first error writing the timeline, after which nothing more is written.
+    error: Option<io::Error>,
+    finished: bool,
+}
+
+impl Timeline {
+    /// Writes the timeline to `writer`, buffered.
+    pub fn to_writer(writer: impl Write + Send + 'static) -> Timeline {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        let mut timeline = Timeline {
+            writer: BufWriter::new(writer),
+            instructions: false,
+            step: 0.0,
+            now: 0.0,
+            frame_start: 0.0,
+            sounding: false,
+            written: 0,
+            error: None,
+            finished: false,
+        };
+        timeline.name_rows();
+        timeline
+    }
+
+    /// Writes the timeline to a new file at `path`, replacing any already there.
+    pub fn to_file(path: impl AsRef<Path>) -> io::Result<Timeline> {
+        Ok(Timeline::to_writer(File::create(path)?))
+    }
+
+    /// The same timeline, but with a span for every instruction run.
+    pub fn with_instructions(mut self) -> Timeline {
+        self.instructions = true;
+        self
+    }
+
+    /// Times instructions by the speed a machine runs them at.
+    pub(super) fn set_speed(&mut self, instructions_per_second: u32) {
+        self.step = 1_000_000.0 / instructions_per_second.max(1) as f64;
+    }
+
+    /// Counts the time an instruction that just ran at `pc` took.
+    pub(super) fn instruction(&mut self, pc: u16, opcode: u16, instruction: Instruction) {
+        if self.instructions {
+            self.write(TraceEvent {
+                name: instruction.mnemonic(),
+                cat: "instruction",
+                ph: "X",
+                ts: self.now,
+                dur: Some(self.step),
+                pid: 1,
+                tid: CPU,
+                s: None,
+                args: json!({
+                    "pc": format!("0x{pc:03X}"),
+                    "opcode": format!("{opcode:04X}"),
+                    "instruction": instruction.to_string(),
+                }),
+            });
+        }
+        self.now += self.step;
+    }
+
+    /// Marks a sprite drawn at `x`, `y`, which turned off a lit pixel if `collision` is set.
+    pub(super) fn draw(&mut self, x: u8, y: u8, height: u8, collision: bool) {
+        let args = json!({ "x": x, "y": y, "height": height, "collision": collision });
+        self.instant("draw", CPU, args);
+    }
+
+    /// Marks the program starting to wait on a key at `pc`.
+    pub(super) fn key_wait(&mut self, pc: u16) {
+        self.instant("wait for key", CPU, json!({ "pc": format!("0x{pc:03X}") }));
+    }
+
+    /// Marks the machine halting on an error.
+    pub(super) fn halt(&mut self, reason: &str) {
+        self.instant("halt", CPU, json!({ "reason": reason }));
+    }
+
+    /// Marks the debugger stopping the machine.
+    pub(crate) fn debugger_stop(&mut self, reason: &str) {
+        self.instant("debugger stop", DEBUGGER, json!({ "reason": reason }));
+    }
+
+    /// Starts or ends the span of the tone sounding, if it's changed.
+    pub(super) fn sound(&mut self, on: bool) {
+        if on == self.sounding {
+            return;
+        }
+        self.sounding = on;
+        self.write(TraceEvent {
+            name: "tone",
+            cat: "sound",
+            ph: if on { "B" } else { "E" },
+            ts: self.now,
+            dur: None,
+            pid: 1,
+            tid: SOUND,
+            s: None,
+            args: Value::Null,
+        });
+    }
+
+    /// Ends a frame, the `number`th the machine has run.
+    pub(super) fn frame(&mut self, number: u64) {
+        self.write(TraceEvent {
+            name: "frame",
+            cat: "frame",
+            ph: "X",
+            ts: self.frame_start,
+            dur: Some(self.now - self.frame_start),
+            pid: 1,
+            tid: FRAMES,
+            s: None,
+            args: json!({ "frame": number }),
+        });
+        self.frame_start = self.now;
+    }
+
+    /// Ends the timeline and writes out what's still buffered, reporting the first error writing it, if there was
+    /// one.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close();
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn instant(&mut self, name: &str, row: u32, args: Value) {
+        self.write(TraceEvent {
+            name,
+            cat: "",
+            ph: "i",
+            ts: self.now,
+            dur: None,
+            pid: 1,
+            tid: row,
+            s: Some("t"),
+            args,
+        });
+    }
+
+    fn name_rows(&mut self) {
+        self.metadata("process_name", 0, "chip8");
+        for (row, name) in [
+            (FRAMES, "frames"),
+            (CPU, "cpu"),
+            (SOUND, "sound"),
+            (DEBUGGER, "debugger"),
+        ] {
+            self.metadata("thread_name", row, name);
+        }
+    }
+
+    fn metadata(&mut self, kind: &str, row: u32, name: &str) {
+        self.write(TraceEvent {
+            name: kind,
+            cat: "",
+            ph: "M",
+            ts: 0.0,
+            dur: None,
+            pid: 1,
+            tid: row,
+            s: None,
+            args: json!({ "name": name }),
+        });
+    }
+
+    /// Writes an event as an element of the array the whole timeline is.
+    fn write(&mut self, event: TraceEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let separator = if self.written == 0 { "[\n" } else { ",\n" };
+        let written = self.writer.write_all(separator.as_bytes()).and_then(|()| {
+            serde_json::to_writer(&mut self.writer, &event).map_err(io::Error::from)
+        });
+        match written {
+            Ok(()) => self.written += 1,
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Ends the tone's span if it's still sounding and closes the array, once.
+    fn close(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.sound(false);
+        if self.error.is_none() {
+            let closing = if self.written == 0 { "[]\n" } else { "\n]\n" };
+            if let Err(error) = self
+                .writer
+                .write_all(closing.as_bytes())
+                .and_then(|()| self.writer.flush())
+            {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// A timeline that's dropped without being finished is still closed, as best it can be, so the viewers can read it.
+impl Drop for Timeline {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// A writer whose output can be read after the timeline has taken it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_chrome_trace_events() {
+        let output = Shared::default();
+        let mut timeline = Timeline::to_writer(output.clone()).with_instructions();
+        timeline.set_speed(1_000_000);
+        let clear = Instruction::ClearScreen;
+        timeline.instruction(0x200, 0x00E0, clear);
+        timeline.sound(true);
+        timeline.instruction(0x202, 0x00E0, clear);
+        timeline.draw(8, 4, 5, false);
+        timeline.frame(1);
+        timeline.finish().expect("failed to write the timeline");
+
+        let written = output.0.lock().unwrap().clone();
+        let events: Vec<Value> = serde_json::from_slice(&written).expect("timeline isn't JSON");
+        let events: Vec<_> = events.iter().filter(|event| event["ph"] != "M").collect();
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event["name"].as_str().unwrap(),
+                    event["ph"].as_str().unwrap(),
+                    event["ts"].as_f64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("CLS", "X", 0.0),
+                ("tone", "B", 1.0),
+                ("CLS", "X", 1.0),
+                ("draw", "i", 2.0),
+                ("frame", "X", 0.0),
+                ("tone", "E", 2.0),
+            ]
+        );
+        assert_eq!(events[4]["dur"], 2.0);
+        assert_eq!(events[3]["args"]["height"], 5);
+    }
+}
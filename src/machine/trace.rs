@@ -0,0 +1,270 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{debugger::Symbols, decoder::Instruction, system::REGISTER_COUNT};
+
+/// How many steps a trace started by `Hotkey::ToggleTrace` keeps, when no other trace was set up.
+pub const DEFAULT_TRACE_CAPACITY: usize = 10_000;
+
+/// The machine as an instruction was about to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    /// The first two bytes of the instruction.
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub registers: [u8; REGISTER_COUNT],
+    pub index: u16,
+    /// How many addresses are on the stack.
+    pub sp: u8,
+}
+
+/// Writes the entry as a line of a trace log, such as `0200 6005 LD V0, 0x05  00 00 .. 00 I 0000 SP 0`: the
+/// program counter, opcode, and instruction, then V0 through VF, I, and the stack pointer, all in hex.
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04X} {:04X} {:<20}",
+            self.pc,
+            self.opcode,
+            self.instruction.to_string()
+        )?;
+        for register in self.registers {
+            write!(f, " {register:02X}")?;
+        }
+        write!(f, " I {:04X} SP {:X}", self.index, self.sp)
+    }
+}
+
+enum Sink {
+    Writer(BufWriter<Box<dyn Write + Send>>),
+    Ring {
+        entries: VecDeque<TraceEntry>,
+        capacity: usize,
+    },
+}
+
+/// A log of every instruction a machine runs, for comparing with other emulators' logs when a program misbehaves.
+///
+/// A trace either writes each step as a line of text, or keeps the latest steps in memory. It can be switched off
+/// and on again while the machine runs, with `Hotkey::ToggleTrace` or `Chip8::toggle_trace()`.
+pub struct Trace {
+    sink: Sink,
+    enabled: bool,
+    /// Names for the addresses steps are logged at, written after them.
+    symbols: Option<Symbols>,
+    /// Whether steps are logged once they've run, with what they did in words.
+    explanations: bool,
+    /// The first error writing the log, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl Trace {
+    /// Writes each step to `writer` as a line, buffered.
+    pub fn to_writer(writer: impl Write + Send + 'static) -> Trace {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        Trace::with_sink(Sink::Writer(BufWriter::new(writer)))
+    }
+
+    /// Writes each step to a new file at `path`, replacing any already there.
+    pub fn to_file(path: impl AsRef<Path>) -> io::Result<Trace> {
+        Ok(Trace::to_writer(File::create(path)?))
+    }
+
+    /// Keeps the last `capacity` steps in memory, forgetting the oldest as new ones come.
+    pub fn ring(capacity: usize) -> Trace {
+        Trace::with_sink(Sink::Ring {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        })
+    }
+
+    fn with_sink(sink: Sink) -> Trace {
+        Trace {
+            sink,
+            enabled: true,
+            symbols: None,
+            explanations: false,
+            error: None,
+        }
+    }
+
+    /// The same trace, but switched off until it's switched on.
+    pub fn disabled(mut self) -> Trace {
+        self.enabled = false;
+        self
+    }
+
+    /// The same trace, but writing each step's place in the program after it, such as `draw_ball+4`, where the
+    /// symbols name it.
+    pub fn with_symbols(mut self, symbols: Symbols) -> Trace {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /// The same trace, but writing what each step did after it, such as `# Set V0 to 0x05`. Written steps are
+    /// logged once they've run rather than before, as it takes running them to know. A trace kept in memory keeps
+    /// no explanations.
+    pub fn with_explanations(mut self) -> Trace {
+        self.explanations = true;
+        self
+    }
+
+    /// Whether steps are logged once they've run, with explanations.
+    pub(super) fn explains(&self) -> bool {
+        self.explanations && matches!(self.sink, Sink::Writer(_))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Stops or starts logging steps, keeping what's been logged so far.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// The steps kept in memory, oldest first. A trace written out keeps none.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        let entries = match &self.sink {
+            Sink::Ring { entries, .. } => Some(entries.iter()),
+            Sink::Writer(_) => None,
+        };
+        entries.into_iter().flatten()
+    }
+
+    pub(super) fn record(&mut self, entry: TraceEntry) {
+        self.record_explained(entry, None);
+    }
+
+    /// Logs a step, with what it did if it's been explained.
+    pub(super) fn record_explained(&mut self, entry: TraceEntry, explanation: Option<&str>) {
+        if !self.enabled {
+            return;
+        }
+        match &mut self.sink {
+            Sink::Writer(writer) => {
+                if self.error.is_none() {
+                    let place = self
+                        .symbols
+                        .as_ref()
+                        .and_then(|symbols| symbols.describe(entry.pc));
+                    let written = write!(writer, "{entry}")
+                        .and_then(|()| match place {
+                            Some(place) => write!(writer, "  {place}"),
+                            None => Ok(()),
+                        })
+                        .and_then(|()| match explanation {
+                            Some(explanation) => write!(writer, "  # {explanation}"),
+                            None => Ok(()),
+                        })
+                        .and_then(|()| writeln!(writer));
+                    if let Err(error) = written {
+                        self.error = Some(error);
+                    }
+                }
+            }
+            Sink::Ring { entries, capacity } => {
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                if *capacity > 0 {
+                    entries.push_back(entry);
+                }
+            }
+        }
+    }
+
+    /// Writes out what's still buffered, reporting the first error writing the log, if there was one.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        match &mut self.sink {
+            Sink::Writer(writer) => writer.flush(),
+            Sink::Ring { .. } => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn entry(pc: u16) -> TraceEntry {
+        TraceEntry {
+            pc,
+            opcode: 0x6005,
+            instruction: Instruction::SetValue { x: 0, value: 5 },
+            registers: [0; REGISTER_COUNT],
+            index: 0x50,
+            sp: 1,
+        }
+    }
+
+    /// A writer whose output can be read after the trace has taken it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_a_line_per_step() {
+        let output = Shared::default();
+        let mut trace = Trace::to_writer(output.clone());
+        trace.record(entry(0x200));
+        trace.set_enabled(false);
+        trace.record(entry(0x202));
+        trace.finish().expect("failed to finish trace");
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "0200 6005 LD V0, 0x05          {} I 0050 SP 1\n",
+                ["00"; 16].join(" ")
+            )
+        );
+
+        let output = Shared::default();
+        let mut symbols = Symbols::new();
+        symbols.insert(0x200, "main");
+        let mut trace = Trace::to_writer(output.clone()).with_symbols(symbols);
+        trace.record(entry(0x204));
+        trace.finish().expect("failed to finish trace");
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(text.ends_with(" SP 1  main+4\n"), "{text}");
+
+        let output = Shared::default();
+        let mut trace = Trace::to_writer(output.clone()).with_explanations();
+        trace.record_explained(entry(0x200), Some("Set V0 to 0x05"));
+        trace.finish().expect("failed to finish trace");
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(text.ends_with(" SP 1  # Set V0 to 0x05\n"), "{text}");
+    }
+
+    #[test]
+    fn rings_keep_the_latest_steps() {
+        let mut trace = Trace::ring(2);
+        for pc in [0x200, 0x202, 0x204] {
+            trace.record(entry(pc));
+        }
+        let pcs: Vec<u16> = trace.entries().map(|entry| entry.pc).collect();
+        assert_eq!(pcs, [0x202, 0x204]);
+    }
+}
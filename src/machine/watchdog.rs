@@ -0,0 +1,70 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering},
+    Arc,
+};
+
+use crate::{
+    clock::TickHandler,
+    events::{Event, EventBus},
+};
+
+/// What the watchdog knew when it noticed the machine had stopped keeping up with its clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogReport {
+    /// How many ticks have passed since the machine last finished a frame.
+    pub stalled_ticks: u64,
+    /// The tick the machine last finished a frame for.
+    pub last_tick: u64,
+    /// Where the program counter was at the end of that frame.
+    pub pc: u16,
+}
+
+/// The signs of life `run()` gives the watchdog after every frame.
+#[derive(Debug, Default)]
+pub(super) struct Heartbeat {
+    running: AtomicBool,
+    last_tick: AtomicU64,
+    pc: AtomicU16,
+}
+
+impl Heartbeat {
+    /// Starts watching from the given tick.
+    pub(super) fn start(&self, tick: u64, pc: u16) {
+        self.beat(tick, pc);
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    /// Stops watching, as the machine isn't expected to keep up outside of `run()`.
+    pub(super) fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    pub(super) fn beat(&self, tick: u64, pc: u16) {
+        self.pc.store(pc, Ordering::Relaxed);
+        self.last_tick.store(tick, Ordering::Relaxed);
+    }
+}
+
+/// Creates a tick handler that publishes `Event::Watchdog` once the machine falls `limit` ticks behind.
+///
+/// It runs on the clock's thread, so it keeps watching even when the machine's thread is stuck. Each stall is
+/// reported once.
+pub(super) fn handler(heartbeat: Arc<Heartbeat>, limit: u64, events: Arc<EventBus>) -> TickHandler {
+    let mut reported_tick = None;
+    Box::new(move |tick| {
+        if !heartbeat.running.load(Ordering::Relaxed) {
+            return;
+        }
+        let last_tick = heartbeat.last_tick.load(Ordering::Relaxed);
+        let stalled_ticks = tick.number.saturating_sub(last_tick);
+        if stalled_ticks < limit || reported_tick == Some(last_tick) {
+            return;
+        }
+        reported_tick = Some(last_tick);
+        events.publish(Event::Watchdog(WatchdogReport {
+            stalled_ticks,
+            last_tick,
+            pc: heartbeat.pc.load(Ordering::Relaxed),
+        }));
+    })
+}
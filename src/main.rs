@@ -1,7 +1,15 @@
-mod clock;
-mod display;
-mod system;
+use std::process::ExitCode;
 
-fn main() {
-    println!("Hello, world!");
+use clap::Parser;
+
+mod cli;
+
+fn main() -> ExitCode {
+    match cli::Cli::parse().execute() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
 }
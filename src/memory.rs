@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use chip8_core::memory::load_font;
+pub use chip8_core::memory::{FONT_START, PROGRAM_START, RAM_SIZE};
+
+/// The most RAM the 16-bit address space can reach.
+pub const MAX_RAM_SIZE: usize = 0x10000;
+
+/// The Chip8's RAM, with the font loaded and room for a program at `PROGRAM_START`.
+///
+/// Out-of-bounds accesses give you the offending address as an error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Memory {
+    ram: Vec<u8>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory::filled(RAM_SIZE)
+    }
+    /// Creates memory of a different size, which must leave room for a program and fit in the address space.
+    pub fn with_size(size: usize) -> Result<Memory, &'static str> {
+        if size <= PROGRAM_START {
+            Err("ram is too small to hold a program")
+        } else if size > MAX_RAM_SIZE {
+            Err("ram is larger than the address space")
+        } else {
+            Ok(Memory::filled(size))
+        }
+    }
+    fn filled(size: usize) -> Memory {
+        let mut memory = Memory { ram: vec![0; size] };
+        load_font(&mut memory.ram);
+        memory
+    }
+    /// Zeroes memory, leaving only the font loaded.
+    pub fn clear(&mut self) {
+        self.ram.fill(0);
+        load_font(&mut self.ram);
+    }
+    /// Copies a program into memory at `PROGRAM_START`.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), &'static str> {
+        if rom.len() > self.ram.len() - PROGRAM_START {
+            return Err("rom is too large to fit in memory");
+        }
+        self.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+    pub fn read(&self, address: u16) -> Result<u8, u16> {
+        self.ram.get(address as usize).copied().ok_or(address)
+    }
+    pub fn write(&mut self, address: u16, value: u8) -> Result<(), u16> {
+        match self.ram.get_mut(address as usize) {
+            Some(byte) => {
+                *byte = value;
+                Ok(())
+            }
+            None => Err(address),
+        }
+    }
+    /// Reads the big-endian opcode at an address.
+    pub fn read_opcode(&self, address: u16) -> Result<u16, u16> {
+        let high = self.read(address)?;
+        let low = self.read(address.wrapping_add(1))?;
+        Ok(u16::from_be_bytes([high, low]))
+    }
+    /// Reads `len` bytes starting at an address.
+    pub fn slice(&self, address: u16, len: usize) -> Result<&[u8], u16> {
+        let start = address as usize;
+        self.ram
+            .get(start..start + len)
+            .ok_or((start + len).min(u16::MAX as usize) as u16)
+    }
+    /// Gets the address of the font sprite for a hex digit.
+    pub fn font_address(digit: u8) -> u16 {
+        chip8_core::memory::font_address(digit)
+    }
+    pub fn len(&self) -> usize {
+        self.ram.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ram.is_empty()
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::FONT;
+
+    use super::*;
+
+    #[test]
+    fn font_is_loaded() {
+        let memory = Memory::new();
+        assert_eq!(
+            memory.slice(Memory::font_address(0xA), 5),
+            Ok(&FONT[0xA][..])
+        );
+    }
+
+    #[test]
+    fn rom_loading() {
+        let mut memory = Memory::new();
+        assert!(memory.load_rom(&[0x12, 0x00]).is_ok());
+        assert_eq!(memory.read_opcode(PROGRAM_START as u16), Ok(0x1200));
+        assert!(memory
+            .load_rom(&vec![0; RAM_SIZE - PROGRAM_START + 1])
+            .is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(
+            Memory::with_size(MAX_RAM_SIZE).map(|memory| memory.len()),
+            Ok(MAX_RAM_SIZE)
+        );
+        assert!(Memory::with_size(PROGRAM_START).is_err());
+        assert!(Memory::with_size(MAX_RAM_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut memory = Memory::new();
+        assert_eq!(memory.read(0x1000), Err(0x1000));
+        assert_eq!(memory.write(0xFFFF, 1), Err(0xFFFF));
+        assert!(memory.read_opcode(0xFFF).is_err());
+    }
+}
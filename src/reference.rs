@@ -0,0 +1,406 @@
+//! Comparing a run of a program with a trace log another emulator wrote of it, stopping at the first instruction
+//! where the two disagree. Once a program mostly works, this is how the last differences are found: the log says
+//! exactly which instruction left a register other than it should have.
+//!
+//! Every emulator logs in its own way, so a `TraceFormat` says which columns of a log's lines hold what. Only the
+//! columns it names are compared, so a log without the stack pointer, say, is still of use.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    debugger::Debugger,
+    machine::{Chip8, Chip8Error, Executed, TraceEntry},
+    system::Register,
+};
+
+/// The columns of the logs `chip8 run --trace` writes, such as
+/// `0200 6005 LD V0, 0x05  00 00 .. 00 I 0000 SP 0`. Logs written with symbols end in a name too, and need a `_`
+/// after `sp`.
+pub const DEFAULT_TRACE_FORMAT: &str = "pc opcode * v0-vf _ i _ sp";
+
+/// Something about the machine a trace log can record before each instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Pc,
+    /// The first two bytes of the instruction about to run.
+    Opcode,
+    V(u8),
+    Index,
+    /// How many addresses are on the stack.
+    StackPointer,
+    Delay,
+    Sound,
+}
+
+impl Field {
+    fn read(self, machine: &Chip8) -> u16 {
+        let pc = machine.cpu().pc();
+        match self {
+            Field::Pc => pc,
+            Field::Opcode => machine.memory().read_opcode(pc).unwrap_or_default(),
+            Field::V(x) => machine.register(Register::V(x)),
+            Field::Index => machine.register(Register::Index),
+            Field::StackPointer => machine.cpu().stack().depth() as u16,
+            Field::Delay => machine.register(Register::Delay),
+            Field::Sound => machine.register(Register::Sound),
+        }
+    }
+
+    /// Writes a value of the field in hex, as wide as it usually is.
+    fn format(self, value: u16) -> String {
+        match self {
+            Field::Pc | Field::Index => format!("0x{value:03X}"),
+            Field::Opcode => format!("0x{value:04X}"),
+            Field::StackPointer => format!("{value}"),
+            Field::V(_) | Field::Delay | Field::Sound => format!("0x{value:02X}"),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Field::Pc => write!(f, "PC"),
+            Field::Opcode => write!(f, "opcode"),
+            Field::V(x) => write!(f, "V{x:X}"),
+            Field::Index => write!(f, "I"),
+            Field::StackPointer => write!(f, "SP"),
+            Field::Delay => write!(f, "DT"),
+            Field::Sound => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Field(Field),
+    /// A column that isn't compared.
+    Skip,
+    /// However many columns it takes for the rest to reach the end of the line, as for a disassembled instruction.
+    Rest,
+}
+
+/// Which columns of a trace log's lines hold what, written as a column name for each, separated by spaces: `pc`,
+/// `opcode`, `v0` through `vf`, `i`, `sp`, `dt`, and `st`, or a range of registers such as `v0-vf`. `_` is a column
+/// that isn't compared, and `*` any number of them, however many leave the columns after it at the end of the line.
+///
+/// Lines are split into columns at spaces and commas. Values are in hex, with or without `0x` or `$`, and anything
+/// up to a `:` or `=` is left out, so `PC:0200` and `V0=05` read as they should.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceFormat {
+    columns: Vec<Column>,
+}
+
+impl TraceFormat {
+    /// Reads the values a line of a log gives, by the field they're of.
+    pub fn read_line(&self, line: &str) -> Result<Vec<(Field, u16)>, String> {
+        let words: Vec<&str> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .collect();
+        let rest = self
+            .columns
+            .iter()
+            .position(|&column| column == Column::Rest);
+        let after_rest = rest.map_or(0, |rest| self.columns.len() - rest - 1);
+        if words.len() < self.columns.len() - rest.map_or(0, |_| 1) {
+            return Err(format!(
+                "expected {} columns, found {}",
+                self.columns.len(),
+                words.len()
+            ));
+        }
+        let mut values = Vec::new();
+        for (index, &column) in self.columns.iter().enumerate() {
+            let Column::Field(field) = column else {
+                continue;
+            };
+            // the columns after `*` count from the end of the line
+            let word = match rest {
+                Some(rest) if index > rest => words[words.len() - after_rest + (index - rest - 1)],
+                _ => words[index],
+            };
+            let value = word
+                .rsplit([':', '='])
+                .next()
+                .and_then(parse_hex)
+                .ok_or_else(|| format!("`{word}` is not a value of {field}"))?;
+            values.push((field, value));
+        }
+        Ok(values)
+    }
+}
+
+impl Default for TraceFormat {
+    fn default() -> TraceFormat {
+        DEFAULT_TRACE_FORMAT
+            .parse()
+            .expect("the default format is valid")
+    }
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<TraceFormat, String> {
+        let register = |name: &str| {
+            let digit = name.strip_prefix(['v', 'V'])?;
+            (digit.len() == 1)
+                .then(|| u8::from_str_radix(digit, 16).ok())
+                .flatten()
+        };
+        let mut columns = Vec::new();
+        for word in text.split_whitespace() {
+            let field = match word.to_ascii_lowercase().as_str() {
+                "_" => {
+                    columns.push(Column::Skip);
+                    continue;
+                }
+                "*" if columns.contains(&Column::Rest) => {
+                    return Err("only one `*` can be used".to_string())
+                }
+                "*" => {
+                    columns.push(Column::Rest);
+                    continue;
+                }
+                "pc" => Field::Pc,
+                "opcode" | "op" => Field::Opcode,
+                "i" => Field::Index,
+                "sp" => Field::StackPointer,
+                "dt" => Field::Delay,
+                "st" => Field::Sound,
+                name => {
+                    let range = match name.split_once('-') {
+                        Some((first, last)) => register(first).zip(register(last)),
+                        None => register(name).map(|x| (x, x)),
+                    };
+                    let (first, last) =
+                        range.ok_or_else(|| format!("`{word}` is not a column name"))?;
+                    columns.extend((first..=last).map(|x| Column::Field(Field::V(x))));
+                    continue;
+                }
+            };
+            columns.push(Column::Field(field));
+        }
+        if !columns
+            .iter()
+            .any(|column| matches!(column, Column::Field(_)))
+        {
+            return Err("the format has nothing to compare".to_string());
+        }
+        Ok(TraceFormat { columns })
+    }
+}
+
+fn parse_hex(word: &str) -> Option<u16> {
+    let digits = word
+        .strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .or_else(|| word.strip_prefix('$'))
+        .unwrap_or(word);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// A value the reference log and this machine disagree on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mismatch {
+    pub field: Field,
+    pub expected: u16,
+    pub actual: u16,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.field,
+            self.field.format(self.expected),
+            self.field.format(self.actual)
+        )
+    }
+}
+
+/// Where a run first disagreed with the reference log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The line of the log that was disagreed with, counting from 1.
+    pub line: usize,
+    /// How many instructions had run, all agreeing with the log.
+    pub step: u64,
+    pub mismatches: Vec<Mismatch>,
+    /// The line of the log, as it was written, less any comment.
+    pub reference: String,
+    /// The machine as it was about to run the next instruction, as this emulator logs it.
+    pub actual: Option<TraceEntry>,
+    /// The instruction run last, most likely the one that went wrong.
+    pub last: Option<Executed>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "diverged from line {} of the reference after {} instructions",
+            self.line, self.step
+        )?;
+        for mismatch in &self.mismatches {
+            writeln!(f, "  {mismatch}")?;
+        }
+        if let Some(last) = self.last {
+            writeln!(f, "after {last}")?;
+        }
+        writeln!(f, "reference: {}", self.reference)?;
+        if let Some(actual) = self.actual {
+            writeln!(f, "this:      {actual}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How a comparison with a reference log ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    /// Every line of the log agreed with the machine, for this many instructions.
+    Matched {
+        steps: u64,
+    },
+    Diverged(Divergence),
+    /// The machine couldn't run the instruction before `line` of the log, which the log says ran.
+    Halted {
+        line: usize,
+        step: u64,
+        error: Chip8Error,
+    },
+    /// The program started waiting on a key before `line` of the log, which can't be pressed.
+    WaitingForKey {
+        line: usize,
+        step: u64,
+    },
+}
+
+/// Runs the debugger's machine an instruction at a time alongside a reference log, comparing it with each line
+/// before the instruction runs, until the log runs out or they disagree. The machine's left where they did. Blank
+/// lines are skipped, and anything after a `#` on a line.
+pub fn compare(
+    debugger: &mut Debugger,
+    reference: &str,
+    format: &TraceFormat,
+) -> Result<Comparison, String> {
+    let mut step = 0;
+    for (number, text) in reference.lines().enumerate() {
+        // what follows a `#` is a comment, such as the explanations `run --trace --explain` writes
+        let text = text.split('#').next().unwrap_or_default().trim();
+        if text.is_empty() {
+            continue;
+        }
+        let line = number + 1;
+        let expected = format
+            .read_line(text)
+            .map_err(|message| format!("line {line}: {message}"))?;
+        let machine = debugger.machine();
+        if step > 0 && machine.cpu().is_waiting_for_key() {
+            return Ok(Comparison::WaitingForKey { line, step });
+        }
+        let mismatches: Vec<Mismatch> = expected
+            .into_iter()
+            .map(|(field, expected)| Mismatch {
+                field,
+                expected,
+                actual: field.read(machine),
+            })
+            .filter(|mismatch| mismatch.expected != mismatch.actual)
+            .collect();
+        if !mismatches.is_empty() {
+            return Ok(Comparison::Diverged(Divergence {
+                line,
+                step,
+                mismatches,
+                reference: text.to_string(),
+                actual: machine.next_trace_entry(),
+                last: machine.history().last(),
+            }));
+        }
+        if let Err(error) = debugger.step() {
+            return Ok(Comparison::Halted { line, step, error });
+        }
+        step += 1;
+    }
+    Ok(Comparison::Matched { steps: step })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Trace,
+    };
+
+    use super::*;
+
+    /// V0 = 5, V1 = 3, V0 += V1, I = 0x300, jump to self
+    const ROM: [u8; 10] = [0x60, 0x05, 0x61, 0x03, 0x80, 0x14, 0xA3, 0x00, 0x12, 0x08];
+
+    fn debugging(trace: Option<Trace>) -> Debugger {
+        let mut builder = Chip8::builder().clock(Box::new(ManualClock::new()), TickRate::NTSC);
+        if let Some(trace) = trace {
+            builder = builder.trace(trace);
+        }
+        let mut machine = builder.build().expect("failed to build machine");
+        machine.load_rom(&ROM).expect("failed to load rom");
+        Debugger::new(machine)
+    }
+
+    #[test]
+    fn stops_where_a_run_leaves_the_reference() {
+        // this emulator's own log is the reference
+        let mut debugger = debugging(Some(Trace::ring(16)));
+        for _ in 0..5 {
+            debugger.step().expect("failed to step");
+        }
+        let log: Vec<String> = debugger
+            .machine()
+            .trace()
+            .expect("no trace")
+            .entries()
+            .map(TraceEntry::to_string)
+            .collect();
+        let format = TraceFormat::default();
+        let reference = log.join("\n");
+        assert_eq!(
+            compare(&mut debugging(None), &reference, &format),
+            Ok(Comparison::Matched { steps: 5 })
+        );
+
+        // another emulator's, where ADD left V0 at 9
+        let format: TraceFormat = "_ PC v0-v1 I".parse().expect("failed to parse format");
+        let reference = "
+            # step pc v0 v1 i
+            1 PC:0200 V0=00 V1=00 $0000
+            2 PC:0202 V0=05 V1=00 $0000
+            3 PC:0204 V0=05 V1=03 $0000
+            4 PC:0206 V0=09 V1=03 $0000
+            5 PC:0208 V0=09 V1=03 $0300
+        ";
+        let mut debugger = debugging(None);
+        let Ok(Comparison::Diverged(divergence)) = compare(&mut debugger, reference, &format)
+        else {
+            panic!("the run didn't diverge");
+        };
+        assert_eq!(divergence.line, 6);
+        assert_eq!(divergence.step, 3);
+        assert_eq!(
+            divergence.mismatches,
+            [Mismatch {
+                field: Field::V(0),
+                expected: 9,
+                actual: 8
+            }]
+        );
+        assert_eq!(divergence.last.map(|last| last.pc), Some(0x204));
+        assert_eq!(debugger.machine().cpu().pc(), 0x206);
+
+        assert!("pc vg".parse::<TraceFormat>().is_err());
+        assert!(format.read_line("1 PC:0200").is_err());
+    }
+}
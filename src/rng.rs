@@ -0,0 +1,18 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use chip8_core::rng::Rng;
+
+/// Creates a generator seeded from the system time.
+///
+/// wasm32 has no system time to read, so there every generator made this way starts from the same seed; hosts there
+/// should pick a seed of their own.
+pub fn from_time() -> Rng {
+    if cfg!(target_arch = "wasm32") {
+        return Rng::new(0);
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default();
+    Rng::new(nanos)
+}
@@ -0,0 +1,420 @@
+//! Scripts in Rhai that drive a machine: reading and writing its registers and memory, pressing keys, and setting
+//! breakpoints, with hooks run after every frame and at every breakpoint. They're for bots, trainers, automated
+//! tests, and one-off tools, with no recompiling.
+//!
+//! A script's top level runs once, when it starts, and is where it sets up its hooks:
+//!
+//! ```text
+//! let presses = 0;
+//! break_at(0x2A4, "V3 == 0");
+//! on_break(|pc| print(`lost a life at ${pc}`));
+//! on_frame(|frame| {
+//!     if frame % 30 == 0 { press(5); presses += 1; } else { release(5); }
+//!     if presses == 10 { stop(); }
+//! });
+//! ```
+//!
+//! The functions scripts have, besides Rhai's own:
+//!
+//! - `reg(name)` and `set_reg(name, value)` read and write a register: `V0` through `VF`, `I`, `DT`, or `ST`
+//! - `pc()` and `set_pc(address)` read and move the program counter
+//! - `peek(address)` and `poke(address, value)` read and write a byte of memory
+//! - `press(key)` and `release(key)` hold and let go of keypad keys, 0 through 15
+//! - `pixel(x, y)` tells whether a pixel of the display is lit
+//! - `frame_count()` is how many frames have run
+//! - `break_at(address)` stops at an address, perhaps only when a condition holds, as `break_at(address, condition)`
+//!   does, and `clear_break(address)` removes the breakpoint. The address can be a label instead, as a string such
+//!   as `"draw_ball"` or `"draw_ball+4"`, named by the program's symbols
+//! - `on_frame(f)` calls `f` with the frame count after every frame, and `on_break(f)` calls it with the address of
+//!   every breakpoint reached
+//! - `stop()` stops running the machine once the script returns
+//!
+//! The machine runs under a `Debugger`, which scripts use to stop at breakpoints.
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, ParseError, Scope, AST, INT};
+
+use crate::{
+    debugger::{Condition, Debugger, StopReason, Symbols},
+    machine::{Chip8, Chip8Error},
+    system::Register,
+};
+
+/// Why a script couldn't run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    Machine(Chip8Error),
+    /// The script didn't parse, or failed as it ran, described by the message.
+    Script(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Machine(error) => write!(f, "{error}"),
+            ScriptError::Script(message) => write!(f, "script error: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<Chip8Error> for ScriptError {
+    fn from(error: Chip8Error) -> Self {
+        ScriptError::Machine(error)
+    }
+}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(error: Box<EvalAltResult>) -> Self {
+        ScriptError::Script(error.to_string())
+    }
+}
+
+impl From<ParseError> for ScriptError {
+    fn from(error: ParseError) -> Self {
+        ScriptError::Script(error.to_string())
+    }
+}
+
+/// What the script's functions share with the machine running it.
+struct Shared {
+    debugger: Debugger,
+    on_frame: Vec<FnPtr>,
+    on_break: Vec<FnPtr>,
+    stopped: bool,
+}
+
+/// A script and the machine it drives.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Script {
+    /// Compiles a script to drive the machine with, taking the machine over. Nothing runs until `start()`.
+    pub fn new(source: &str, machine: Chip8) -> Result<Script, ScriptError> {
+        let shared = Rc::new(RefCell::new(Shared {
+            debugger: Debugger::new(machine),
+            on_frame: Vec::new(),
+            on_break: Vec::new(),
+            stopped: false,
+        }));
+        let mut engine = Engine::new();
+        register_functions(&mut engine, &shared);
+        let ast = engine.compile(source)?;
+        Ok(Script {
+            engine,
+            ast,
+            scope: Scope::new(),
+            shared,
+        })
+    }
+
+    /// Adds names from a symbol file for the script to set breakpoints by, and sets the breakpoints it marks.
+    pub fn load_symbols(&mut self, symbols: &Symbols) {
+        self.shared.borrow_mut().debugger.load_symbols(symbols);
+    }
+
+    /// Runs the script's top level, which sets up its hooks.
+    pub fn start(&mut self) -> Result<(), ScriptError> {
+        self.engine.run_ast_with_scope(&mut self.scope, &self.ast)?;
+        Ok(())
+    }
+
+    /// Runs a frame, calling the script's hooks at every breakpoint on the way and once the frame finishes. Does
+    /// nothing once the script has stopped.
+    pub fn run_frame(&mut self) -> Result<(), ScriptError> {
+        while !self.is_stopped() {
+            let reason = self.shared.borrow_mut().debugger.step_frame()?;
+            match reason {
+                StopReason::Breakpoint(pc) => {
+                    self.call_hooks(|shared| &shared.on_break, pc as INT)?
+                }
+                StopReason::Interrupted => self.shared.borrow_mut().stopped = true,
+                _ => {
+                    let frame = self.frame_count() as INT;
+                    return self.call_hooks(|shared| &shared.on_frame, frame);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls each of one kind of hook, which may use the machine themselves, so it isn't borrowed meanwhile.
+    fn call_hooks(
+        &self,
+        hooks: fn(&Shared) -> &Vec<FnPtr>,
+        argument: INT,
+    ) -> Result<(), ScriptError> {
+        let hooks = hooks(&self.shared.borrow()).clone();
+        for hook in hooks {
+            // whatever a hook returns is ignored
+            let _: Dynamic = hook.call(&self.engine, &self.ast, (argument,))?;
+        }
+        Ok(())
+    }
+
+    /// Whether the script has called `stop()`, or the machine's stop flag was set.
+    pub fn is_stopped(&self) -> bool {
+        self.shared.borrow().stopped
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.shared.borrow().debugger.machine().frame_count()
+    }
+
+    /// Hands the machine back, paused.
+    pub fn into_machine(self) -> Chip8 {
+        // the engine's functions hold the rest of the references to what's shared
+        let Script { engine, shared, .. } = self;
+        drop(engine);
+        let shared = Rc::into_inner(shared).expect("the script's functions outlived its engine");
+        shared.into_inner().debugger.into_machine()
+    }
+}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn register_functions(engine: &mut Engine, shared: &Rc<RefCell<Shared>>) {
+    let machine = Rc::clone(shared);
+    engine.register_fn("reg", move |name: &str| -> ScriptResult<INT> {
+        let register = parse_register(name)?;
+        Ok(machine.borrow().debugger.machine().register(register) as INT)
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn(
+        "set_reg",
+        move |name: &str, value: INT| -> ScriptResult<()> {
+            let register = parse_register(name)?;
+            let value = to_u16(value, "value")?;
+            let mut shared = machine.borrow_mut();
+            shared
+                .debugger
+                .machine_mut()
+                .set_register(register, value)
+                .map_err(fail)
+        },
+    );
+    let machine = Rc::clone(shared);
+    engine.register_fn("pc", move || {
+        machine.borrow().debugger.machine().cpu().pc() as INT
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("set_pc", move |address: INT| -> ScriptResult<()> {
+        let address = to_u16(address, "address")?;
+        let mut shared = machine.borrow_mut();
+        shared.debugger.machine_mut().set_pc(address).map_err(fail)
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("peek", move |address: INT| -> ScriptResult<INT> {
+        let address = to_u16(address, "address")?;
+        let shared = machine.borrow();
+        shared
+            .debugger
+            .machine()
+            .memory()
+            .read(address)
+            .map(INT::from)
+            .map_err(|_| fail("address is past the end of memory"))
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn(
+        "poke",
+        move |address: INT, value: INT| -> ScriptResult<()> {
+            let address = to_u16(address, "address")?;
+            let value = u8::try_from(value).map_err(|_| fail("value doesn't fit in a byte"))?;
+            let mut shared = machine.borrow_mut();
+            shared
+                .debugger
+                .machine_mut()
+                .poke(address, value)
+                .map_err(fail)
+        },
+    );
+    let machine = Rc::clone(shared);
+    engine.register_fn("press", move |key: INT| -> ScriptResult<()> {
+        let key = to_key(key)?;
+        machine
+            .borrow_mut()
+            .debugger
+            .machine_mut()
+            .keypad_mut()
+            .press(key);
+        Ok(())
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("release", move |key: INT| -> ScriptResult<()> {
+        let key = to_key(key)?;
+        let mut shared = machine.borrow_mut();
+        shared.debugger.machine_mut().keypad_mut().release(key);
+        Ok(())
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("pixel", move |x: INT, y: INT| {
+        let frame = machine.borrow().debugger.machine().display().frame();
+        match (usize::try_from(x), usize::try_from(y)) {
+            (Ok(x), Ok(y)) if x < frame.width() && y < frame.height() => frame.get_pixel(x, y),
+            _ => false,
+        }
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("frame_count", move || {
+        machine.borrow().debugger.machine().frame_count() as INT
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("break_at", move |address: INT| -> ScriptResult<()> {
+        let address = to_u16(address, "address")?;
+        machine.borrow_mut().debugger.add_breakpoint(address);
+        Ok(())
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn(
+        "break_at",
+        move |address: INT, condition: &str| -> ScriptResult<()> {
+            let address = to_u16(address, "address")?;
+            let condition = Condition::parse(condition).map_err(fail)?;
+            let mut shared = machine.borrow_mut();
+            shared
+                .debugger
+                .add_conditional_breakpoint(address, condition);
+            Ok(())
+        },
+    );
+    let machine = Rc::clone(shared);
+    engine.register_fn("break_at", move |label: &str| -> ScriptResult<()> {
+        let mut shared = machine.borrow_mut();
+        let address = resolve(&shared, label)?;
+        shared.debugger.add_breakpoint(address);
+        Ok(())
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn(
+        "break_at",
+        move |label: &str, condition: &str| -> ScriptResult<()> {
+            let mut shared = machine.borrow_mut();
+            let address = resolve(&shared, label)?;
+            let condition = Condition::parse(condition).map_err(fail)?;
+            shared
+                .debugger
+                .add_conditional_breakpoint(address, condition);
+            Ok(())
+        },
+    );
+    let machine = Rc::clone(shared);
+    engine.register_fn("clear_break", move |address: INT| -> ScriptResult<()> {
+        let address = to_u16(address, "address")?;
+        machine.borrow_mut().debugger.remove_breakpoint(address);
+        Ok(())
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("clear_break", move |label: &str| -> ScriptResult<()> {
+        let mut shared = machine.borrow_mut();
+        let address = resolve(&shared, label)?;
+        shared.debugger.remove_breakpoint(address);
+        Ok(())
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("on_frame", move |hook: FnPtr| {
+        machine.borrow_mut().on_frame.push(hook);
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("on_break", move |hook: FnPtr| {
+        machine.borrow_mut().on_break.push(hook);
+    });
+    let machine = Rc::clone(shared);
+    engine.register_fn("stop", move || machine.borrow_mut().stopped = true);
+}
+
+fn fail(error: impl ToString) -> Box<EvalAltResult> {
+    error.to_string().into()
+}
+
+fn parse_register(name: &str) -> ScriptResult<Register> {
+    name.parse()
+        .map_err(|_| fail(format!("`{name}` is not a register")))
+}
+
+/// The address a label stands for, perhaps with an offset, as in `draw_ball+4`.
+fn resolve(shared: &Shared, label: &str) -> ScriptResult<u16> {
+    shared
+        .debugger
+        .symbols()
+        .resolve(label)
+        .ok_or_else(|| fail(format!("`{label}` is not a known label")))
+}
+
+fn to_u16(value: INT, what: &str) -> ScriptResult<u16> {
+    u16::try_from(value).map_err(|_| fail(format!("{what} {value} is out of range")))
+}
+
+fn to_key(key: INT) -> ScriptResult<u8> {
+    match u8::try_from(key) {
+        Ok(key) if key <= 0xF => Ok(key),
+        _ => Err(fail(format!("{key} is not a key, which go from 0 to 15"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{ManualClock, TickRate};
+
+    use super::*;
+
+    fn compile(source: &str) -> Result<Script, ScriptError> {
+        let mut machine = Chip8::with_clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .expect("failed to build machine");
+        machine.load_rom(&COUNTER).expect("failed to load rom");
+        Script::new(source, machine)
+    }
+
+    /// V0 += 1, then jump back.
+    const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn hooks_frames_and_breakpoints() {
+        let mut script = compile(
+            r#"
+                let hits = 0;
+                break_at(0x202, "V0 == 3");
+                on_break(|pc| { hits += 1; poke(0x300, pc & 0xFF); });
+                on_frame(|frame| {
+                    if frame == 2 { set_reg("V5", hits); press(0xA); stop(); }
+                });
+            "#,
+        )
+        .expect("failed to compile script");
+        script.start().expect("failed to start script");
+        while !script.is_stopped() {
+            script.run_frame().expect("failed to run frame");
+        }
+        let machine = script.into_machine();
+        assert_eq!(machine.frame_count(), 2);
+        assert_eq!(machine.memory().read(0x300), Ok(0x02));
+        assert_eq!(machine.register(Register::V(5)), 1);
+        assert!(machine.keypad().is_pressed(0xA));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert!(matches!(compile("let = 1;"), Err(ScriptError::Script(_))));
+        let mut script = compile(r#"set_reg("VG", 1);"#).expect("failed to compile script");
+        let error = script.start().unwrap_err().to_string();
+        assert!(error.contains("`VG` is not a register"), "{error}");
+        let mut script =
+            compile("on_frame(|frame| poke(0x300, 256));").expect("failed to compile script");
+        script.start().expect("failed to start script");
+        assert!(script.run_frame().is_err());
+
+        // the disassembly labels the jump's target
+        let mut script = compile(r#"break_at("label_200+2"); break_at("nowhere");"#)
+            .expect("failed to compile script");
+        let error = script.start().unwrap_err().to_string();
+        assert!(error.contains("`nowhere` is not a known label"), "{error}");
+        let breakpoints: Vec<u16> = script.shared.borrow().debugger.breakpoints().collect();
+        assert_eq!(breakpoints, [0x202]);
+    }
+}
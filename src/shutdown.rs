@@ -0,0 +1,15 @@
+use crate::clock::ClockSource;
+
+/// A component that can be shut down, releasing any thread or callback it holds.
+///
+/// Shutting down is idempotent, and components that own threads also shut down when dropped. The machine shuts
+/// its components down in a defined order when it's stopped.
+pub trait Shutdown {
+    fn shutdown(&mut self) -> Result<(), &'static str>;
+}
+
+impl<T: ClockSource + ?Sized> Shutdown for T {
+    fn shutdown(&mut self) -> Result<(), &'static str> {
+        self.teardown()
+    }
+}
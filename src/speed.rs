@@ -0,0 +1,147 @@
+use std::{fmt, time::Duration};
+
+/// The multipliers that `faster()` and `slower()` step through, from slowest to fastest.
+const STEPS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// A multiple of normal speed, where 1.0 is normal. Only finite multiples of `Multiplier::MIN` and up can be made,
+/// so every multiple scales an interval to something a clock can keep.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Multiplier(f32);
+
+impl Multiplier {
+    /// The slowest speed, a quarter of normal.
+    pub const MIN: f32 = 0.25;
+
+    pub fn new(multiplier: f32) -> Option<Multiplier> {
+        (multiplier.is_finite() && multiplier >= Multiplier::MIN).then_some(Multiplier(multiplier))
+    }
+
+    pub fn get(self) -> f32 {
+        self.0
+    }
+}
+
+impl fmt::Display for Multiplier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How fast emulation runs relative to real hardware.
+///
+/// A speed scales the clock's tick rate and the CPU's instruction budget together, so games behave the same as
+/// at normal speed, only faster or slower.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// Runs at a multiple of normal speed.
+    Scaled(Multiplier),
+    /// Runs as fast as the host allows.
+    Unlimited,
+}
+
+impl Speed {
+    pub const NORMAL: Speed = Speed::Scaled(Multiplier(1.0));
+
+    /// Runs at `multiplier` times normal speed, if that's a multiplier `Multiplier::new()` takes.
+    pub fn scaled(multiplier: f32) -> Option<Speed> {
+        Multiplier::new(multiplier).map(Speed::Scaled)
+    }
+
+    /// Steps up to the next fastest speed, or to unlimited from the fastest step.
+    pub fn faster(self) -> Speed {
+        match self {
+            Speed::Scaled(multiplier) => STEPS
+                .iter()
+                .find(|&&step| step > multiplier.get())
+                .map_or(Speed::Unlimited, |&step| Speed::Scaled(Multiplier(step))),
+            Speed::Unlimited => Speed::Unlimited,
+        }
+    }
+
+    /// Steps down to the next slowest speed, bottoming out at the slowest step.
+    pub fn slower(self) -> Speed {
+        let multiplier = match self {
+            Speed::Scaled(multiplier) => multiplier.get(),
+            Speed::Unlimited => f32::INFINITY,
+        };
+        let step = STEPS
+            .iter()
+            .rev()
+            .find(|&&step| step < multiplier)
+            .unwrap_or(&STEPS[0]);
+        Speed::Scaled(Multiplier(*step))
+    }
+
+    /// Switches between unlimited and normal speed.
+    pub fn toggle_fast_forward(self) -> Speed {
+        match self {
+            Speed::Unlimited => Speed::NORMAL,
+            Speed::Scaled(_) => Speed::Unlimited,
+        }
+    }
+
+    /// Scales the interval between ticks. Unlimited speed has no interval at all.
+    pub fn scale_interval(self, interval: Duration) -> Duration {
+        match self {
+            Speed::Scaled(multiplier) => interval.div_f64(multiplier.get() as f64),
+            Speed::Unlimited => Duration::ZERO,
+        }
+    }
+
+    /// Scales an instruction budget, such as instructions per second. Unlimited speed has no budget.
+    pub fn scale_budget(self, budget: u32) -> u32 {
+        match self {
+            Speed::Scaled(multiplier) => (budget as f32 * multiplier.get()) as u32,
+            Speed::Unlimited => u32::MAX,
+        }
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::NORMAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speed(multiplier: f32) -> Speed {
+        Speed::scaled(multiplier).expect("not a speed")
+    }
+
+    #[test]
+    fn speed_steps() {
+        assert_eq!(Speed::NORMAL.faster(), speed(2.0));
+        assert_eq!(Speed::NORMAL.slower(), speed(0.5));
+        assert_eq!(speed(8.0).faster(), Speed::Unlimited);
+        assert_eq!(Speed::Unlimited.slower(), speed(8.0));
+        assert_eq!(speed(0.25).slower(), speed(0.25));
+        assert_eq!(speed(3.0).slower(), speed(2.0));
+    }
+
+    #[test]
+    fn speed_scales_together() {
+        let interval = Duration::from_millis(16);
+        assert_eq!(
+            speed(2.0).scale_interval(interval),
+            Duration::from_millis(8)
+        );
+        assert_eq!(speed(2.0).scale_budget(700), 1400);
+        assert_eq!(
+            speed(0.25).scale_interval(interval),
+            Duration::from_millis(64)
+        );
+        assert_eq!(speed(0.25).scale_budget(700), 175);
+        assert_eq!(Speed::Unlimited.scale_interval(interval), Duration::ZERO);
+    }
+
+    #[test]
+    fn only_sensible_speeds_can_be_made() {
+        for multiplier in [0.0, -1.0, 0.1, f32::NAN, f32::INFINITY] {
+            assert_eq!(Speed::scaled(multiplier), None, "{multiplier} was taken");
+        }
+        assert_eq!(Speed::scaled(1.0), Some(Speed::NORMAL));
+    }
+}
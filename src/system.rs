@@ -1,198 +1,591 @@
 use std::{
+    collections::VecDeque,
     sync::{
-        atomic::{AtomicBool, AtomicU8, Ordering},
-        mpsc::Receiver,
-        Arc,
+        atomic::{AtomicU16, Ordering},
+        Arc, Mutex, TryLockError,
     },
-    thread::{self, JoinHandle},
-    time::{Duration, Instant},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+pub use chip8_core::cpu::{Cpu, CpuError, Register, Stack, REGISTER_COUNT};
+
+use crate::{
+    clock::{ClockSource, TickHandler},
+    display::Display,
+    keypad::Keypad,
+    memory::Memory,
+    rng::Rng,
+    shutdown::Shutdown,
 };
 
 // TODO: most of these should be configurable
-const RAM_SIZE: usize = 4096;
-const REGISTER_COUNT: usize = 16;
-const STACK_SIZE: u8 = 16;
 const RUNLOOP_TIMER_DEFAULT: u8 = 8;
-const PROGRAM_START: usize = 0x200;
-const TIMER_INTERVAL: Duration = Duration::from_micros(16_667);
-
-/// A stack component built on top of a fixed-size array with Result<> types to prevent overflows and underflows.
-#[derive(Debug)]
-pub struct Stack {
-    memory: [u16; STACK_SIZE as usize],
-    p: u8,
+/// The interval of the standard 60hz timers. Other rates are available through `TickRate`.
+pub const TIMER_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// The values of both timers at the same instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimerSnapshot {
+    pub delay: u8,
+    pub sound: u8,
 }
 
-impl Stack {
-    pub fn new() -> Stack {
-        Stack {
-            memory: [0; STACK_SIZE as usize],
-            p: 0,
-        }
+impl TimerSnapshot {
+    fn pack(self) -> u16 {
+        u16::from_be_bytes([self.delay, self.sound])
     }
-    /// Pushes a value onto the stack. On error, it will give you the stack pointer.
-    pub fn push(&mut self, value: u16) -> Result<(), u8> {
-        if self.p >= STACK_SIZE {
-            Err(self.p)
-        } else {
-            self.memory[self.p as usize] = value;
-            self.p += 1;
-            Ok(())
-        }
+    fn unpack(packed: u16) -> TimerSnapshot {
+        let [delay, sound] = packed.to_be_bytes();
+        TimerSnapshot { delay, sound }
     }
-    /// Pops a value from the stack, giving you the value if successful, or the stack pointer if unsuccessful.
-    pub fn pop(&mut self) -> Result<u16, u8> {
-        if self.p == 0 {
-            Err(0)
-        } else if self.p >= STACK_SIZE {
-            Err(self.p)
-        } else {
-            self.p -= 1;
-            Ok(self.memory[self.p as usize])
+    /// Subtracts `ticks` from the timers, stopping at zero.
+    fn decremented(self, ticks: u64) -> TimerSnapshot {
+        let ticks = ticks.min(u8::MAX as u64) as u8;
+        TimerSnapshot {
+            delay: self.delay.saturating_sub(ticks),
+            sound: self.sound.saturating_sub(ticks),
         }
     }
 }
 
-/// A timer component that meets Chip8 specifications, and a thread to guarantee a 60hz clock cycle.
-/// Must be `start()`ed before use, and must be `teardown()`ed after use.
+/// A timer component that meets Chip8 specifications.
+///
+/// The timers do not keep time themselves; they are driven by a `Clock` ticking at 60hz (or another `TickRate`),
+/// either by `attach()`ing to one or by calling `tick()` directly. Attached timers stop counting down while their
+/// clock is paused.
+///
+/// Both timers are packed into a single atomic, so they always count down together and can be read consistently.
 pub struct Timers {
-    delay_timer: Arc<AtomicU8>,
-    sound_timer: Arc<AtomicU8>,
-    timer_handle: Option<JoinHandle<()>>,
+    shared: Arc<SharedTimers>,
+}
+
+/// A change to the timers that hooks are told about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerEvent {
+    /// The delay timer was set to the given value.
+    DelaySet(u8),
+    /// The sound timer was set to the given value. Setting it nonzero starts the tone.
+    SoundSet(u8),
+    /// The delay timer counted down to zero.
+    DelayExpired,
+    /// The sound timer counted down to zero, stopping the tone.
+    SoundExpired,
+}
+
+/// A callback run whenever the timers change.
+///
+/// Hooks run on whichever thread changed the timers, which for countdowns is the clock's thread. A hook may read
+/// and set the timers, such as to restart the delay timer when it expires; the hooks are told about what it sets
+/// once they've all been told about the change before. A hook must not register hooks itself.
+pub type TimerHook = Box<dyn FnMut(TimerEvent) + Send>;
+
+/// The state shared between the timers and the handlers that count them down.
+struct SharedTimers {
+    timers: AtomicU16,
+    hooks: Mutex<Vec<TimerHook>>,
+    /// Changes the hooks have yet to be told about, queued while they're being told about another.
+    pending: Mutex<VecDeque<TimerEvent>>,
+}
+
+impl SharedTimers {
+    /// Applies `f` to the timers atomically, returning their values before and after.
+    fn update(&self, f: impl Fn(TimerSnapshot) -> TimerSnapshot) -> (TimerSnapshot, TimerSnapshot) {
+        let packed = self
+            .timers
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |packed| {
+                Some(f(TimerSnapshot::unpack(packed)).pack())
+            })
+            .unwrap();
+        let before = TimerSnapshot::unpack(packed);
+        (before, f(before))
+    }
+
+    /// Subtracts `ticks` from both timers, stopping at zero.
+    fn advance(&self, ticks: u64) {
+        let (before, after) = self.update(|timers| timers.decremented(ticks));
+        if before.delay > 0 && after.delay == 0 {
+            self.notify(TimerEvent::DelayExpired);
+        }
+        if before.sound > 0 && after.sound == 0 {
+            self.notify(TimerEvent::SoundExpired);
+        }
+    }
+
+    fn notify(&self, event: TimerEvent) {
+        self.pending.lock().unwrap().push_back(event);
+        loop {
+            let mut hooks = match self.hooks.try_lock() {
+                Ok(hooks) => hooks,
+                // the hooks are being told about something else, by a hook on this thread setting a timer or by
+                // another thread, and whoever's telling them will tell them about this too
+                Err(TryLockError::WouldBlock) => return,
+                Err(TryLockError::Poisoned(error)) => error.into_inner(),
+            };
+            loop {
+                // the queue's lock is let go before the hooks run, so they can queue more
+                let Some(event) = self.pending.lock().unwrap().pop_front() else {
+                    break;
+                };
+                for hook in hooks.iter_mut() {
+                    hook(event);
+                }
+            }
+            drop(hooks);
+            // another thread may have queued something just before the hooks were let go
+            if self.pending.lock().unwrap().is_empty() {
+                return;
+            }
+        }
+    }
 }
 
 impl Timers {
     pub fn new() -> Timers {
+        let timers = TimerSnapshot {
+            delay: RUNLOOP_TIMER_DEFAULT,
+            sound: RUNLOOP_TIMER_DEFAULT,
+        };
         Timers {
-            delay_timer: Arc::new(AtomicU8::new(RUNLOOP_TIMER_DEFAULT)),
-            sound_timer: Arc::new(AtomicU8::new(RUNLOOP_TIMER_DEFAULT)),
-            timer_handle: None,
+            shared: Arc::new(SharedTimers {
+                timers: AtomicU16::new(timers.pack()),
+                hooks: Mutex::new(Vec::new()),
+                pending: Mutex::new(VecDeque::new()),
+            }),
         }
     }
-    /// Starts the timer.
+    /// Attaches the timers to a clock.
     ///
-    /// This function starts a thread that will update every 1/60th of a second, subtracting one from
-    /// nonzero values of the delay and sound timers. The thread can be terminated with `teardown()`.
-    pub fn start(&mut self, tick_rx: Receiver<()>) {
-        let delay_timer = Arc::clone(&self.delay_timer);
-        let sound_timer = Arc::clone(&self.sound_timer);
-        self.timer_handle = Some(thread::spawn(move || {
-            println!("timer thread started");
-            while let Ok(_) = tick_rx.recv() {
-                println!("timer tick");
-                if delay_timer.load(Ordering::SeqCst) > 0 {
-                    delay_timer.fetch_sub(1, Ordering::SeqCst);
-                }
-                if sound_timer.load(Ordering::SeqCst) > 0 {
-                    sound_timer.fetch_sub(1, Ordering::SeqCst);
-                }
-            }
-        }));
+    /// Every tick of the clock will subtract one from nonzero values of the delay and sound timers. This runs on
+    /// whichever thread drives the clock, so no additional thread is needed.
+    pub fn attach(&self, clock: &mut dyn ClockSource) -> Result<(), &'static str> {
+        clock.on_tick(self.tick_handler())
     }
 
-    /// Stops the timer.
-    pub fn teardown(&mut self) -> Result<(), &str> {
-        if self.timer_handle.is_some() {
-            self.timer_handle
-                .take()
-                .unwrap()
-                .join()
-                .map_err(|_| "thread panicked")
-        } else {
-            Ok(())
-        }
+    /// Creates a tick handler that counts these timers down, for registering with any kind of clock.
+    pub fn tick_handler(&self) -> TickHandler {
+        let shared = Arc::clone(&self.shared);
+        Box::new(move |tick| shared.advance(tick.elapsed))
+    }
+
+    /// Registers a hook to be told whenever a timer is set or reaches zero.
+    pub fn on_change(&self, hook: TimerHook) {
+        self.shared.hooks.lock().unwrap().push(hook);
+    }
+
+    /// Subtracts one from nonzero values of the delay and sound timers.
+    pub fn tick(&self) {
+        self.advance(1);
+    }
+
+    /// Counts the timers down by the given number of ticks at once, stopping at zero.
+    pub fn advance(&self, ticks: u64) {
+        self.shared.advance(ticks);
+    }
+
+    /// Reads both timers at once.
+    pub fn snapshot(&self) -> TimerSnapshot {
+        TimerSnapshot::unpack(self.shared.timers.load(Ordering::SeqCst))
     }
 
     pub fn retrieve_delay_timer(&self) -> u8 {
-        self.delay_timer.load(Ordering::SeqCst)
+        self.snapshot().delay
+    }
+
+    pub fn retrieve_sound_timer(&self) -> u8 {
+        self.snapshot().sound
     }
 
     pub fn set_delay_timer(&self, value: u8) {
-        self.delay_timer.store(value, Ordering::SeqCst);
+        self.shared.update(|timers| TimerSnapshot {
+            delay: value,
+            ..timers
+        });
+        self.shared.notify(TimerEvent::DelaySet(value));
     }
 
     pub fn set_sound_timer(&self, value: u8) {
-        self.sound_timer.store(value, Ordering::SeqCst);
+        self.shared.update(|timers| TimerSnapshot {
+            sound: value,
+            ..timers
+        });
+        self.shared.notify(TimerEvent::SoundSet(value));
+    }
+
+    /// Sets both timers at once, as when loading a savestate.
+    pub fn restore(&self, timers: TimerSnapshot) {
+        self.shared.update(|_| timers);
+        self.shared.notify(TimerEvent::DelaySet(timers.delay));
+        self.shared.notify(TimerEvent::SoundSet(timers.sound));
     }
 }
 
-pub struct CPU {
-    ram: [u8; RAM_SIZE],
-    registers: [u8; REGISTER_COUNT],
-    stack: Stack,
-    pc: u16,
-    index: u16,
-    delay_timer: u8,
-    sound_timer: u8,
-    //display: Display,
+/// Timers keep no thread of their own, so shutting them down just detaches their hooks, releasing anything the
+/// hooks hold. The timers still count down if their clock keeps running.
+impl Shutdown for Timers {
+    fn shutdown(&mut self) -> Result<(), &'static str> {
+        self.shared.hooks.lock().unwrap().clear();
+        Ok(())
+    }
 }
 
-impl CPU {
-    pub fn new() -> CPU {
-        CPU {
-            ram: [0; RAM_SIZE],
-            registers: [0; REGISTER_COUNT],
-            stack: Stack::new(),
-            pc: 0,
-            index: 0,
-            delay_timer: 0,
-            sound_timer: 0,
-        }
+impl Default for Timers {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::clock::Clock;
+/// Whether an instruction read a byte of memory or wrote it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
 
-    use super::*;
+/// A byte of memory an instruction read as data or wrote, with the value read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: u16,
+    pub value: u8,
+    pub kind: AccessKind,
+}
 
-    #[test]
-    fn stack_push_pop() {
-        let mut stack = Stack::new();
-        assert!(stack.push(0xEF).is_ok(), "push failed on 0xEF");
-        assert!(stack.push(0xAB).is_ok(), "push failed on 0xAB");
-        assert_eq!(stack.pop(), Ok(0xAB), "stack is not last-in first-out");
-        assert_eq!(stack.pop(), Ok(0xEF), "stack is not last-in first-out");
+/// The components an instruction can touch, borrowed from the machine for one step.
+pub struct Bus<'a> {
+    pub memory: &'a mut Memory,
+    pub display: &'a mut Display,
+    pub keypad: &'a mut Keypad,
+    pub timers: &'a Timers,
+    pub rng: &'a mut Rng,
+    /// Where the registers the instruction writes are collected, with what they hold after it.
+    pub writes: &'a mut Vec<(Register, u16)>,
+    /// Where the bytes of memory the instruction reads as data and writes are collected.
+    pub accesses: &'a mut Vec<MemoryAccess>,
+}
+
+impl chip8_core::Bus for Bus<'_> {
+    fn read(&self, address: u16) -> Result<u8, u16> {
+        self.memory.read(address)
     }
-    #[test]
-    fn pop_on_empty() {
-        let mut stack = Stack::new();
-        assert!(stack.pop().is_err());
+
+    fn write(&mut self, address: u16, value: u8) -> Result<(), u16> {
+        self.memory.write(address, value)?;
+        self.accesses.push(MemoryAccess {
+            address,
+            value,
+            kind: AccessKind::Write,
+        });
+        Ok(())
     }
-    #[test]
-    fn stack_overflow() {
-        let mut stack = Stack::new();
-        for i in 1..17 {
-            assert!(stack.push(i as u16).is_ok());
+
+    fn clear_display(&mut self) {
+        self.display.clear();
+    }
+
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8], wrap: bool) -> bool {
+        if wrap {
+            self.display.draw_wrapping(x, y, sprite)
+        } else {
+            self.display.draw(x, y, sprite)
         }
-        assert!(stack.push(0xEF).is_err());
     }
 
+    fn is_key_pressed(&self, key: u8) -> bool {
+        self.keypad.is_pressed(key)
+    }
+
+    fn take_released_key(&mut self) -> Option<u8> {
+        self.keypad.take_released()
+    }
+
+    fn delay_timer(&self) -> u8 {
+        self.timers.retrieve_delay_timer()
+    }
+
+    fn set_delay_timer(&mut self, value: u8) {
+        self.timers.set_delay_timer(value);
+    }
+
+    fn set_sound_timer(&mut self, value: u8) {
+        self.timers.set_sound_timer(value);
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng.next_u8()
+    }
+
+    fn register_written(&mut self, register: Register, value: u16) {
+        self.writes.push((register, value));
+    }
+
+    fn memory_read(&mut self, address: u16, value: u8) {
+        self.accesses.push(MemoryAccess {
+            address,
+            value,
+            kind: AccessKind::Read,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{Clock, ManualClock};
+    use crate::memory::PROGRAM_START;
+    use crate::quirks::{Quirks, Variant};
+
+    use super::*;
+
     #[test]
     fn timer_works() {
-        let mut clock = Clock::new(TIMER_INTERVAL);
-        let mut timers = Timers::new();
+        let mut clock = ManualClock::new();
+        let timers = Timers::new();
         timers.set_delay_timer(30);
         timers.set_sound_timer(240);
-        let tick_rx = clock
-            .become_listener()
-            .expect("failed to create tick receiver");
-        clock.start();
-        timers.start(tick_rx);
-        thread::sleep(Duration::from_millis(500));
+        clock
+            .on_tick(timers.tick_handler())
+            .expect("failed to attach timers to clock");
+        clock.advance(29).expect("failed to advance clock");
+        assert_eq!(timers.retrieve_delay_timer(), 1);
+        clock.advance(1).expect("failed to advance clock");
         assert_eq!(
             timers.retrieve_delay_timer(),
             0,
             "timer does not count down as expected"
         );
+        clock.advance(1).expect("failed to advance clock");
+        assert_eq!(timers.retrieve_delay_timer(), 0, "timer counts below zero");
+    }
+    #[test]
+    fn timer_attached_to_running_clock() {
+        let mut clock = Clock::new(Duration::from_millis(1));
+        let timers = Timers::new();
+        timers.set_delay_timer(5);
+        timers
+            .attach(&mut clock)
+            .expect("failed to attach timers to clock");
+        let rx = clock
+            .become_listener("test")
+            .expect("failed to listen to clock");
+        clock.start();
+        rx.iter().take(5).for_each(drop);
         assert!(
             clock.teardown().is_ok(),
             "clock thread is not safely joined"
         );
-        assert!(
-            timers.teardown().is_ok(),
-            "timer thread is not safely joined"
+        assert_eq!(
+            timers.retrieve_delay_timer(),
+            0,
+            "timer does not count down on the clock thread"
+        );
+    }
+    #[test]
+    fn timer_manual_tick() {
+        let mut clock = Clock::new(TIMER_INTERVAL);
+        let timers = Timers::new();
+        timers.set_delay_timer(2);
+        timers
+            .attach(&mut clock)
+            .expect("failed to attach timers to clock");
+        for _ in 0..3 {
+            clock.tick().expect("manual tick failed");
+        }
+        assert_eq!(
+            timers.retrieve_delay_timer(),
+            0,
+            "timer does not count down on manual ticks"
+        );
+    }
+    #[test]
+    fn timer_respects_pause() {
+        let mut clock = Clock::new(TIMER_INTERVAL);
+        let timers = Timers::new();
+        timers.set_delay_timer(5);
+        timers
+            .attach(&mut clock)
+            .expect("failed to attach timers to clock");
+        clock.pause();
+        clock.tick().expect("manual tick failed");
+        assert_eq!(
+            timers.retrieve_delay_timer(),
+            5,
+            "timer counts down while clock is paused"
         );
+        clock.resume();
+        clock.tick().expect("manual tick failed");
+        assert_eq!(timers.retrieve_delay_timer(), 4);
+    }
+    #[test]
+    fn timer_snapshot() {
+        let timers = Timers::new();
+        timers.set_delay_timer(3);
+        timers.set_sound_timer(1);
+        timers.tick();
+        assert_eq!(timers.retrieve_sound_timer(), 0);
+        assert_eq!(
+            timers.snapshot(),
+            TimerSnapshot { delay: 2, sound: 0 },
+            "timers do not count down together"
+        );
+    }
+    #[test]
+    fn timer_coalesced_ticks() {
+        let mut clock = ManualClock::new();
+        let timers = Timers::new();
+        timers.set_delay_timer(30);
+        timers.set_sound_timer(5);
+        clock
+            .on_tick(timers.tick_handler())
+            .expect("failed to attach timers to clock");
+        clock
+            .advance_coalesced(10)
+            .expect("failed to advance clock");
+        assert_eq!(
+            timers.snapshot(),
+            TimerSnapshot {
+                delay: 20,
+                sound: 0
+            },
+            "timers do not count down by the elapsed ticks"
+        );
+    }
+    #[test]
+    fn timer_hooks() {
+        let timers = Timers::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        timers.on_change(Box::new(move |event| recorded.lock().unwrap().push(event)));
+        timers.set_delay_timer(1);
+        timers.set_sound_timer(2);
+        timers.advance(3);
+        timers.advance(1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                TimerEvent::DelaySet(1),
+                TimerEvent::SoundSet(2),
+                TimerEvent::DelayExpired,
+                TimerEvent::SoundExpired,
+            ],
+            "hooks are not told about timer changes exactly once"
+        );
+    }
+    #[test]
+    fn timer_hooks_can_set_timers() {
+        let timers = Timers::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let restarted = Timers {
+            shared: Arc::clone(&timers.shared),
+        };
+        let recorded = Arc::clone(&events);
+        timers.on_change(Box::new(move |event| {
+            recorded.lock().unwrap().push(event);
+            if event == TimerEvent::DelayExpired {
+                restarted.set_delay_timer(5);
+            }
+        }));
+        timers.set_delay_timer(1);
+        timers.advance(1);
+        assert_eq!(timers.retrieve_delay_timer(), 5);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                TimerEvent::DelaySet(1),
+                TimerEvent::DelayExpired,
+                TimerEvent::DelaySet(5),
+            ]
+        );
+    }
+
+    /// Runs `steps` instructions of a program on a bare CPU.
+    fn run_program(program: &[u8], steps: usize, keypad: &mut Keypad) -> (Cpu, Memory) {
+        run_program_with(Quirks::default(), program, steps, keypad)
+    }
+    fn run_program_with(
+        quirks: Quirks,
+        program: &[u8],
+        steps: usize,
+        keypad: &mut Keypad,
+    ) -> (Cpu, Memory) {
+        let mut cpu = Cpu::with_quirks(quirks);
+        let mut memory = Memory::new();
+        memory.load_rom(program).expect("failed to load program");
+        let mut display = Display::new();
+        let timers = Timers::new();
+        let mut rng = Rng::new(1);
+        let mut writes = Vec::new();
+        let mut accesses = Vec::new();
+        let mut bus = Bus {
+            memory: &mut memory,
+            display: &mut display,
+            keypad,
+            timers: &timers,
+            rng: &mut rng,
+            writes: &mut writes,
+            accesses: &mut accesses,
+        };
+        for _ in 0..steps {
+            cpu.step(&mut bus).expect("instruction failed");
+        }
+        (cpu, memory)
+    }
+    #[test]
+    fn cpu_arithmetic_flags() {
+        // V0 = FF, V1 = 02, V0 += V1, V2 = 01, V3 = 03, V2 -= V3
+        let program = [
+            0x60, 0xFF, 0x61, 0x02, 0x80, 0x14, 0x62, 0x01, 0x63, 0x03, 0x82, 0x35,
+        ];
+        let (cpu, _) = run_program(&program, 3, &mut Keypad::new());
+        assert_eq!(cpu.registers()[0], 0x01);
+        assert_eq!(cpu.registers()[0xF], 1, "carry is not set");
+        let (cpu, _) = run_program(&program, 6, &mut Keypad::new());
+        assert_eq!(cpu.registers()[2], 0xFE);
+        assert_eq!(cpu.registers()[0xF], 0, "borrow is not reported");
+    }
+    #[test]
+    fn cpu_call_and_return() {
+        // call 0x206, jump to self, padding, return
+        let (cpu, _) = run_program(
+            &[0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0x00, 0xEE],
+            2,
+            &mut Keypad::new(),
+        );
+        assert_eq!(cpu.pc(), PROGRAM_START as u16 + 2);
+    }
+    #[test]
+    fn cpu_stores_registers_and_bcd() {
+        // V0 = 123, V1 = 7, I = 0x300, store BCD of V0, I = 0x310, store V0..V1
+        let (cpu, memory) = run_program(
+            &[
+                0x60, 0x7B, 0x61, 0x07, 0xA3, 0x00, 0xF0, 0x33, 0xA3, 0x10, 0xF1, 0x55,
+            ],
+            6,
+            &mut Keypad::new(),
+        );
+        assert_eq!(memory.slice(0x300, 3), Ok(&[1, 2, 3][..]));
+        assert_eq!(memory.slice(0x310, 2), Ok(&[0x7B, 0x07][..]));
+        assert_eq!(cpu.index(), 0x312, "FX55 does not advance the index");
+    }
+    #[test]
+    fn cpu_waits_for_key_release() {
+        let mut keypad = Keypad::new();
+        keypad.press(0x5);
+        keypad.release(0x5);
+        // a release from before the instruction doesn't count
+        let (cpu, _) = run_program(&[0xF2, 0x0A], 3, &mut keypad);
+        assert!(cpu.is_waiting_for_key());
+        assert_eq!(cpu.pc(), PROGRAM_START as u16);
+    }
+    #[test]
+    fn cpu_follows_quirks() {
+        // V1 = 3, V0 = 81, V0 >>= V1, I = 0x300, store V0
+        let program = [0x61, 0x03, 0x60, 0x81, 0x80, 0x16, 0xA3, 0x00, 0xF0, 0x55];
+        let (cpu, _) = run_program(&program, 5, &mut Keypad::new());
+        assert_eq!(cpu.registers()[0], 0x01, "shift does not use VY");
+        assert_eq!(cpu.index(), 0x301);
+        let (cpu, _) =
+            run_program_with(Variant::SuperChip.quirks(), &program, 5, &mut Keypad::new());
+        assert_eq!(cpu.registers()[0], 0x40, "shift does not happen in place");
+        assert_eq!(cpu.index(), 0x300, "store advances the index");
     }
 }
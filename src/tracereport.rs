@@ -0,0 +1,470 @@
+//! Turning a short run of a program, such as a frame or some instructions, into a page of HTML that stands on its
+//! own: the program's disassembly, each instruction run with what it changed, and the screen after each that drew.
+//! The page needs nothing else to show, so it can be put up anywhere and linked to, to walk through how a program
+//! works.
+
+use std::{collections::BTreeSet, fmt, sync::Arc};
+
+use crate::{
+    config::Color,
+    debugger::Debugger,
+    decoder::Instruction,
+    disassembler::{self, Line},
+    display::Frame,
+    explain::{explain, Operands},
+    machine::Chip8Error,
+    quirks::Variant,
+    system::{AccessKind, CpuError, Register},
+};
+
+/// The most instructions a report holds, however far its bound reaches, so a long bound can't make a page too big
+/// to open.
+pub const MAX_REPORT_STEPS: usize = 10_000;
+
+/// How big a pixel of a screen snapshot is drawn, in CSS pixels.
+const SNAPSHOT_SCALE: usize = 4;
+
+/// How much of a run a report covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// This many instructions.
+    Instructions(u64),
+    /// Until this many frames have finished, the first being the one the machine is part way through, if it is.
+    Frames(u64),
+}
+
+/// Why a report stops where it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    /// The run reached its bound.
+    Bound,
+    /// The report holds `MAX_REPORT_STEPS` instructions.
+    Full,
+    /// The program jumps to itself at this address, so nothing more would happen.
+    InfiniteLoop(u16),
+    /// The program is waiting on a key, which nothing presses.
+    WaitingForKey,
+    /// The next instruction couldn't run.
+    Halted(Chip8Error),
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ending::Bound => write!(f, "the run reached its end"),
+            Ending::Full => write!(
+                f,
+                "the report holds {MAX_REPORT_STEPS} instructions, as many as it can"
+            ),
+            Ending::InfiniteLoop(address) => {
+                write!(f, "the program jumps to itself at 0x{address:03X}")
+            }
+            Ending::WaitingForKey => write!(f, "the program is waiting for a key"),
+            Ending::Halted(error) => write!(f, "the machine stopped: {error}"),
+        }
+    }
+}
+
+/// A register an instruction changed, with what it held before and after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub register: Register,
+    pub before: u16,
+    pub after: u16,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = if self.register == Register::Index {
+            3
+        } else {
+            2
+        };
+        write!(
+            f,
+            "{} {:0width$X} → {:0width$X}",
+            self.register, self.before, self.after
+        )
+    }
+}
+
+/// An instruction run, and what came of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportStep {
+    /// How many instructions ran before this one in the report.
+    pub step: usize,
+    /// The frame the instruction ran in, counting from 1.
+    pub frame: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub explanation: String,
+    /// The registers the instruction changed, in the order it first wrote them.
+    pub changes: Vec<Change>,
+    /// The bytes of memory it wrote, and what it wrote to them.
+    pub memory_writes: Vec<(u16, u8)>,
+    /// The screen after the instruction, if it drew, cleared, scrolled, or changed resolution.
+    pub screen: Option<Arc<Frame>>,
+}
+
+/// A run of a program, ready to write out as HTML.
+#[derive(Debug, Clone)]
+pub struct TraceReport {
+    pub title: String,
+    pub variant: Variant,
+    /// The program's disassembly, following its code from the start.
+    pub listing: Vec<Line>,
+    pub steps: Vec<ReportStep>,
+    pub ending: Ending,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl TraceReport {
+    /// Runs the debugger's machine from where it is until the bound, or until it can't go on, reporting each
+    /// instruction. `inputs` holds keys down from the start of frames, as `(frame, keys)` pairs counting frames from
+    /// 0, a bit for each key.
+    pub fn record(
+        debugger: &mut Debugger,
+        title: impl Into<String>,
+        bound: Bound,
+        inputs: &[(u64, u16)],
+    ) -> TraceReport {
+        let machine = debugger.machine();
+        let variant = machine.variant();
+        let listing = disassembler::follow(machine.rom(), variant);
+        let first_frame = machine.frame_count();
+        let mut inputs = inputs.iter().peekable();
+        let mut steps = Vec::new();
+        let ending = loop {
+            let machine = debugger.machine();
+            let done = match bound {
+                Bound::Instructions(count) => steps.len() as u64 >= count,
+                Bound::Frames(count) => machine.frame_count() - first_frame >= count,
+            };
+            if done {
+                break Ending::Bound;
+            }
+            if steps.len() >= MAX_REPORT_STEPS {
+                break Ending::Full;
+            }
+            if machine.cpu().is_waiting_for_key() {
+                break Ending::WaitingForKey;
+            }
+            if machine.in_infinite_loop() {
+                break Ending::InfiniteLoop(machine.cpu().pc());
+            }
+            let frame = machine.frame_count();
+            let mut keys = None;
+            while let Some(&(_, mask)) = inputs.next_if(|(at, _)| *at <= frame) {
+                keys = Some(mask);
+            }
+            if let Some(mask) = keys {
+                debugger.machine_mut().keypad_mut().set_mask(mask);
+            }
+            match ReportStep::run(debugger, steps.len()) {
+                Ok(step) => steps.push(step),
+                Err(error) => break Ending::Halted(error),
+            }
+        };
+        TraceReport {
+            title: title.into(),
+            variant,
+            listing,
+            steps,
+            ending,
+            foreground: Color::WHITE,
+            background: Color::BLACK,
+        }
+    }
+
+    /// Draws screen snapshots in these colours, rather than white on black.
+    pub fn with_colours(mut self, foreground: Color, background: Color) -> TraceReport {
+        self.foreground = foreground;
+        self.background = background;
+        self
+    }
+
+    /// Writes the report as a page of HTML, with its styles inline and its screens as SVG, so it needs no other
+    /// files.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = escape(&self.title);
+        let (foreground, background) =
+            (String::from(self.foreground), String::from(self.background));
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!(
+            "<title>{title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n"
+        ));
+        html.push_str(&format!("<h1>{title}</h1>\n"));
+        let frames = match (self.steps.first(), self.steps.last()) {
+            (Some(first), Some(last)) if first.frame == last.frame => {
+                format!(" in frame {}", first.frame)
+            }
+            (Some(first), Some(last)) => format!(" in frames {} to {}", first.frame, last.frame),
+            _ => String::new(),
+        };
+        html.push_str(&format!(
+            "<p>{} instructions run{frames} with the {} quirks. The run ends because {}.</p>\n",
+            self.steps.len(),
+            variant_name(self.variant),
+            escape(&self.ending.to_string()),
+        ));
+
+        html.push_str("<h2>Instructions run</h2>\n<table class=\"steps\">\n");
+        html.push_str(
+            "<tr><th>#</th><th>Frame</th><th>Address</th><th>Opcode</th><th>Instruction</th>\
+             <th>What it does</th><th>Changes</th></tr>\n",
+        );
+        let listed: BTreeSet<u16> = self.listing.iter().map(|line| line.address).collect();
+        for step in &self.steps {
+            let address = if listed.contains(&step.pc) {
+                format!("<a href=\"#x{0:03X}\">0x{0:03X}</a>", step.pc)
+            } else {
+                format!("0x{:03X}", step.pc)
+            };
+            let mut changes: Vec<String> = step.changes.iter().map(Change::to_string).collect();
+            changes.extend(
+                step.memory_writes
+                    .iter()
+                    .map(|(address, value)| format!("[0x{address:03X}] = {value:02X}")),
+            );
+            html.push_str(&format!(
+                "<tr id=\"step{}\"><td>{}</td><td>{}</td><td>{address}</td><td>{:04X}</td><td>{}</td><td>{}</td>\
+                 <td>{}</td></tr>\n",
+                step.step + 1,
+                step.step + 1,
+                step.frame,
+                step.opcode,
+                escape(&step.instruction.to_string()),
+                escape(&step.explanation),
+                escape(&changes.join(", ")),
+            ));
+            if let Some(screen) = &step.screen {
+                html.push_str(&format!(
+                    "<tr class=\"screen\"><td colspan=\"7\">{}</td></tr>\n",
+                    svg(screen, &foreground, &background)
+                ));
+            }
+        }
+        html.push_str("</table>\n");
+
+        let ran: BTreeSet<u16> = self.steps.iter().map(|step| step.pc).collect();
+        html.push_str("<h2>Disassembly</h2>\n");
+        html.push_str(
+            "<p>Instructions the run reached are highlighted.</p>\n<pre class=\"listing\">\n",
+        );
+        for line in &self.listing {
+            let class = if ran.contains(&line.address) {
+                " class=\"ran\""
+            } else {
+                ""
+            };
+            let label = match &line.label {
+                Some(label) => format!("{}:\n", escape(label)),
+                None => String::new(),
+            };
+            html.push_str(&format!(
+                "{label}<span id=\"x{:03X}\"{class}>{}</span>\n",
+                line.address,
+                escape(&line.to_string())
+            ));
+        }
+        html.push_str("</pre>\n</body>\n</html>\n");
+        html
+    }
+}
+
+impl ReportStep {
+    /// Runs the debugger's next instruction, taking down what it did.
+    fn run(debugger: &mut Debugger, step: usize) -> Result<ReportStep, Chip8Error> {
+        let machine = debugger.machine();
+        let frame = machine.frame_count() + 1;
+        let pc = machine.cpu().pc();
+        let entry =
+            machine
+                .next_trace_entry()
+                .ok_or(Chip8Error::Cpu(CpuError::MemoryOutOfBounds {
+                    pc,
+                    address: pc,
+                }))?;
+        let before = Operands::of(machine);
+        let sound = machine.timers().retrieve_sound_timer();
+        debugger.step()?;
+        let machine = debugger.machine();
+        let after = Operands::of(machine);
+        let mut changes: Vec<Change> = Vec::new();
+        for &(register, value) in machine.register_writes() {
+            match changes
+                .iter_mut()
+                .find(|change| change.register == register)
+            {
+                Some(change) => change.after = value,
+                None => changes.push(Change {
+                    register,
+                    before: value_before(&before, sound, register),
+                    after: value,
+                }),
+            }
+        }
+        changes.retain(|change| change.before != change.after);
+        let memory_writes = machine
+            .memory_accesses()
+            .iter()
+            .filter(|access| access.kind == AccessKind::Write)
+            .map(|access| (access.address, access.value))
+            .collect();
+        let screen = changes_screen(entry.instruction).then(|| machine.display().frame());
+        Ok(ReportStep {
+            step,
+            frame,
+            pc: entry.pc,
+            opcode: entry.opcode,
+            instruction: entry.instruction,
+            explanation: explain(entry.instruction, &before, Some(&after)),
+            changes,
+            memory_writes,
+            screen,
+        })
+    }
+}
+
+/// What a register held before an instruction, from the machine as it was then.
+fn value_before(before: &Operands, sound: u8, register: Register) -> u16 {
+    match register {
+        Register::V(x) => before.registers[x as usize] as u16,
+        Register::Index => before.index,
+        Register::Delay => before.delay_timer as u16,
+        Register::Sound => sound as u16,
+    }
+}
+
+/// Whether an instruction changes what's on the screen, or could.
+fn changes_screen(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::ClearScreen
+            | Instruction::Draw { .. }
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollUp { .. }
+            | Instruction::ScrollLeft
+            | Instruction::ScrollRight
+            | Instruction::LowResolution
+            | Instruction::HighResolution
+    )
+}
+
+fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Chip8 => "CHIP-8",
+        Variant::SuperChip => "SUPER-CHIP",
+        Variant::XoChip => "XO-CHIP",
+    }
+}
+
+/// Draws a screen as SVG, a rectangle to each run of lit pixels along a row.
+fn svg(screen: &Frame, foreground: &str, background: &str) -> String {
+    let (width, height) = (screen.width(), screen.height());
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {height}\" width=\"{}\" height=\"{}\" \
+         shape-rendering=\"crispEdges\"><rect width=\"{width}\" height=\"{height}\" fill=\"{background}\"/>",
+        width * SNAPSHOT_SCALE,
+        height * SNAPSHOT_SCALE,
+    );
+    svg.push_str(&format!("<g fill=\"{foreground}\">"));
+    for y in 0..height {
+        let mut x = 0;
+        while x < width {
+            if !screen.get_pixel(x, y) {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < width && screen.get_pixel(x, y) {
+                x += 1;
+            }
+            svg.push_str(&format!(
+                "<rect x=\"{start}\" y=\"{y}\" width=\"{}\" height=\"1\"/>",
+                x - start
+            ));
+        }
+    }
+    svg.push_str("</g></svg>");
+    svg
+}
+
+/// Makes text safe to put in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table.steps { border-collapse: collapse; }
+table.steps th, table.steps td { padding: 0.2em 0.6em; text-align: left; vertical-align: top; }
+table.steps tr:nth-child(even) { background: #f4f4f4; }
+table.steps td:nth-child(-n+5) { font-family: monospace; white-space: nowrap; }
+table.steps td:nth-child(7) { font-family: monospace; }
+tr.screen td { padding: 0.5em 0.6em 1em; }
+pre.listing span { display: block; }
+pre.listing span.ran { background: #fff3b0; }
+pre.listing span:target { outline: 2px solid #e0a000; }
+";
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::{ManualClock, TickRate},
+        machine::Chip8,
+    };
+
+    use super::*;
+
+    #[test]
+    fn reports_each_instruction_with_its_changes_and_screens() {
+        // CLS, V0 = 5, I = the font's 5, draw it at (V0, V0), jump to self
+        let rom = [0x00, 0xE0, 0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x08];
+        let mut machine = Chip8::builder()
+            .clock(Box::new(ManualClock::new()), TickRate::NTSC)
+            .build()
+            .expect("failed to build machine");
+        machine.load_rom(&rom).expect("failed to load rom");
+        let mut debugger = Debugger::new(machine);
+        let report = TraceReport::record(&mut debugger, "a <demo>", Bound::Instructions(10), &[]);
+        assert_eq!(report.ending, Ending::InfiniteLoop(0x208));
+        assert_eq!(report.steps.len(), 4);
+        assert_eq!(
+            report.steps[1].changes,
+            [Change {
+                register: Register::V(0),
+                before: 0,
+                after: 5
+            }]
+        );
+        let screens: Vec<usize> = report
+            .steps
+            .iter()
+            .filter(|step| step.screen.is_some())
+            .map(|step| step.step)
+            .collect();
+        assert_eq!(screens, [0, 3]);
+        assert!(report.steps[3].screen.as_ref().unwrap().get_pixel(5, 5));
+
+        let html = report.to_html();
+        assert!(html.contains("<title>a &lt;demo&gt;</title>"));
+        assert!(html.contains("<a href=\"#x202\">0x202</a>"));
+        assert!(html.contains("V0 00 → 05"));
+        assert!(html.contains("<span id=\"x206\" class=\"ran\">"));
+        assert_eq!(html.matches("<svg").count(), 2);
+    }
+}
@@ -0,0 +1,111 @@
+//! Noticing files change on disk, so the config file and the program can be reloaded while the machine runs.
+//!
+//! Only available with the `hot-reload` feature.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Why a file couldn't be watched, described by the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchError(String);
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for WatchError {}
+
+impl From<notify::Error> for WatchError {
+    fn from(error: notify::Error) -> Self {
+        WatchError(error.to_string())
+    }
+}
+
+/// Watches a file for changes.
+///
+/// The file's directory is watched rather than the file itself, so editors and assemblers that save by replacing the
+/// file are still noticed.
+pub struct FileWatcher {
+    /// Kept alive for as long as the file is watched.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    path: PathBuf,
+}
+
+impl FileWatcher {
+    /// Starts watching the file at `path`, which needn't exist yet.
+    pub fn new(path: impl Into<PathBuf>) -> Result<FileWatcher, WatchError> {
+        let path = path.into();
+        let (event_tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(event_tx)?;
+        let directory = path
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        Ok(FileWatcher {
+            _watcher: watcher,
+            events,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file has changed since the last poll. Never blocks.
+    pub fn poll(&mut self) -> Result<bool, WatchError> {
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            let event = event?;
+            changed |= !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == self.path.file_name());
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn notices_the_file_being_replaced() {
+        let directory =
+            std::env::temp_dir().join(format!("chip8-rom-watch-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("game.ch8");
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        let mut watcher = FileWatcher::new(&path).expect("failed to watch rom");
+        fs::write(directory.join("other.ch8"), [0x00, 0xE0]).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(
+            watcher.poll(),
+            Ok(false),
+            "another file's change was noticed"
+        );
+
+        let written = directory.join("game.tmp");
+        fs::write(&written, [0x12, 0x02]).unwrap();
+        fs::rename(&written, &path).unwrap();
+        let changed = (0..100).any(|_| {
+            thread::sleep(Duration::from_millis(20));
+            watcher.poll() == Ok(true)
+        });
+        assert!(changed);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}